@@ -1,37 +1,212 @@
-use std::collections::HashMap;
+//! Cache actor - memory-bounded cache of nodes and directory listings
+//!
+//! Listings are stored raw (before the pipeline runs), so one entry serves
+//! every sort/filter/group setting, with a stamp of the directory to
+//! revalidate them against. The Watcher and Poller send `Invalidate` for
+//! changed nodes; the cache then drops the node, its own listing, its
+//! parent's listing and everything cached below it.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use flume::Sender;
 
 use crate::actors::Actor;
 use crate::model::node::{FileNode, NodeId};
+use crate::model::registry::NodeRegistry;
+use crate::services::cache::{ApproxSize, LruCache};
 
 /// Commands for cache actor
 #[derive(Debug, Clone)]
 pub enum CacheCommand {
     Store(FileNode),
     StoreBatch(Vec<FileNode>),
-    Get(NodeId),
+    /// Cache the unprocessed listing of a directory
+    StoreListing {
+        path: PathBuf,
+        entries: Vec<FileNode>,
+        stamp: ListingStamp,
+    },
+    Get(NodeId, Sender<Option<FileNode>>),
+    GetListing(NodeId, Sender<Option<(Vec<FileNode>, ListingStamp)>>),
     Invalidate(NodeId),
     Clear,
 }
 
+/// Version of a directory a listing was taken at
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListingStamp {
+    pub modified: Option<SystemTime>,
+    pub etag: Option<String>,
+}
+
+impl ListingStamp {
+    pub fn of(dir: &FileNode) -> Self {
+        Self {
+            modified: dir.modified,
+            etag: dir.meta.etag.clone(),
+        }
+    }
+
+    /// Whether a listing with this stamp still shows `dir`; never for a
+    /// provider that reports no version
+    pub fn matches(&self, dir: &FileNode) -> bool {
+        (self.modified.is_some() || self.etag.is_some()) && *self == Self::of(dir)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Node(NodeId),
+    Listing(NodeId),
+}
+
+enum Cached {
+    Node(FileNode),
+    Listing {
+        path: PathBuf,
+        entries: Vec<FileNode>,
+        stamp: ListingStamp,
+    },
+}
+
+impl Cached {
+    fn path(&self) -> &Path {
+        match self {
+            Cached::Node(node) => &node.path,
+            Cached::Listing { path, .. } => path,
+        }
+    }
+}
+
+impl ApproxSize for Cached {
+    fn approx_size(&self) -> usize {
+        match self {
+            Cached::Node(node) => node.approx_size(),
+            Cached::Listing { path, entries, stamp } => {
+                path.as_os_str().len() + entries.approx_size() + stamp.etag.as_ref().map_or(0, String::capacity)
+            }
+        }
+    }
+}
+
 /// Cache actor - LRU cache for file nodes
 pub struct Cache {
     commands: flume::Receiver<CacheCommand>,
-    entries: HashMap<NodeId, FileNode>,
-    capacity: usize,
+    registry: NodeRegistry,
+    entries: LruCache<CacheKey, Cached>,
+    /// Keys by the path they are cached for; a subtree is a range
+    paths: BTreeMap<PathBuf, Vec<CacheKey>>,
 }
 
 impl Cache {
-    pub fn new(commands: flume::Receiver<CacheCommand>, capacity: usize) -> Self {
-        todo!()
+    /// Create a cache holding at most roughly `capacity` bytes
+    pub fn new(commands: flume::Receiver<CacheCommand>, registry: NodeRegistry, capacity: usize) -> Self {
+        Self {
+            commands,
+            registry,
+            entries: LruCache::new(capacity),
+            paths: BTreeMap::new(),
+        }
+    }
+
+    fn handle_command(&mut self, cmd: CacheCommand) {
+        match cmd {
+            CacheCommand::Store(node) => self.insert(CacheKey::Node(node.id), Cached::Node(node)),
+            CacheCommand::StoreBatch(nodes) => {
+                for node in nodes {
+                    self.insert(CacheKey::Node(node.id), Cached::Node(node));
+                }
+            }
+            CacheCommand::StoreListing { path, entries, stamp } => {
+                let parent = NodeId::from_path(&path);
+                self.insert(CacheKey::Listing(parent), Cached::Listing { path, entries, stamp });
+            }
+            CacheCommand::Get(node, reply) => {
+                let hit = match self.entries.get(&CacheKey::Node(node)) {
+                    Some(Cached::Node(node)) => Some(node.clone()),
+                    _ => None,
+                };
+                let _ = reply.send(hit);
+            }
+            CacheCommand::GetListing(parent, reply) => {
+                let hit = match self.entries.get(&CacheKey::Listing(parent)) {
+                    Some(Cached::Listing { entries, stamp, .. }) => Some((entries.clone(), stamp.clone())),
+                    _ => None,
+                };
+                let _ = reply.send(hit);
+            }
+            CacheCommand::Invalidate(node) => self.invalidate(node),
+            CacheCommand::Clear => {
+                self.entries.clear();
+                self.paths.clear();
+            }
+        }
+    }
+
+    fn insert(&mut self, key: CacheKey, value: Cached) {
+        self.remove(&key);
+        let path = value.path().to_path_buf();
+        for (evicted, cached) in self.entries.push(key, value) {
+            self.unindex(cached.path(), &evicted);
+        }
+        if self.entries.contains(&key) {
+            self.paths.entry(path).or_default().push(key);
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(cached) = self.entries.remove(key) {
+            self.unindex(cached.path(), key);
+        }
+    }
+
+    fn unindex(&mut self, path: &Path, key: &CacheKey) {
+        if let Some(keys) = self.paths.get_mut(path) {
+            keys.retain(|k| k != key);
+            if keys.is_empty() {
+                self.paths.remove(path);
+            }
+        }
+    }
+
+    fn invalidate(&mut self, node: NodeId) {
+        self.remove(&CacheKey::Node(node));
+        self.remove(&CacheKey::Listing(node));
+
+        // Without a path only the exact entries can go
+        let Some(path) = self.registry.resolve(node) else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            self.remove(&CacheKey::Listing(NodeId::from_path(&parent.to_path_buf())));
+        }
+        // A changed directory invalidates everything cached beneath it;
+        // paths sort by component, so that is one range of the index
+        let below: Vec<PathBuf> = self
+            .paths
+            .range(path.clone()..)
+            .map(|(p, _)| p)
+            .take_while(|p| p.starts_with(&path))
+            .cloned()
+            .collect();
+        for p in below {
+            for key in self.paths.remove(&p).unwrap_or_default() {
+                self.entries.remove(&key);
+            }
+        }
     }
 }
 
 impl Actor for Cache {
-    async fn run(self) {
-        todo!()
+    async fn run(mut self) {
+        while let Ok(cmd) = self.commands.recv_async().await {
+            self.handle_command(cmd);
+        }
     }
-    
+
     fn name(&self) -> &'static str {
         "cache"
     }
-}
\ No newline at end of file
+}
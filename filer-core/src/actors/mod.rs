@@ -1,8 +1,14 @@
 pub mod cache;
 pub mod navigator;
+pub mod operations;
+pub mod poller;
 pub mod previewer;
 pub mod scanner;
 pub mod searcher;
+pub mod syncer;
+pub mod system;
+pub mod trasher;
+#[cfg(target_os = "linux")]
 pub mod watcher;
 
 /// Trait for all actors
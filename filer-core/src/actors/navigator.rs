@@ -5,20 +5,33 @@
 //! - Managing back/forward history
 //! - Coordinating with Scanner for directory listing
 //! - Maintaining view settings (sort, filter, show hidden)
+//! - Keeping each view's ordered selection, pruned as its listing changes
+//! - Keeping several views (panes or tabs) per session with a shared
+//!   clipboard, and sending copies and moves to the other pane
+//! - Saving named sessions to a `SessionStore` and restoring them, as
+//!   well as reopening closed ones
+//! - Telling the Poller when a session is active or gone
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use flume::{Receiver, Sender};
 use serde::{Deserialize, Serialize};
 
 use crate::Event;
+use crate::errors::CoreError;
+use crate::actors::operations::OpCommand;
+use crate::actors::poller::PollCommand;
 use crate::actors::{Actor, scanner};
 use crate::api::events;
-use crate::model::node::NodeId;
+use crate::model::node::{FileNode, NodeId};
+use crate::model::selection::{Selection, SelectionEdit};
 use crate::model::registry::NodeRegistry;
-use crate::model::session::SessionId;
+use crate::model::session::{SessionId, ViewId};
 use crate::pipeline::{Pipeline, PipelineConfig};
+use crate::services::sessions::{SavedSession, SavedView, SessionStore};
+use crate::services::transfer::{TransferMode, TransferOptions};
 use scanner::ScanCommand;
 
 /// Navigation commands
@@ -47,15 +60,109 @@ pub enum NavCommand {
         session: SessionId,
         config: PipelineConfig,
     },
-    /// Update selection
+    /// Replace the selection
     SetSelected {
         session: SessionId,
         nodes: Vec<NodeId>,
     },
+    Select {
+        session: SessionId,
+        edit: SelectionEdit,
+    },
+    /// What a view shows now, in display order (sent by the Scanner)
+    Listed {
+        session: SessionId,
+        view: ViewId,
+        parent: NodeId,
+        entries: Arc<[FileNode]>,
+    },
     /// Get current state snapshot
     GetState(SessionId),
     Invalidate(NodeId),
-    NewSession(SessionId)
+    NewSession(SessionId),
+    /// Create a session under a stable name, restoring what was saved
+    /// under it
+    OpenSession {
+        session: SessionId,
+        name: String,
+    },
+    /// Destroy a session, keeping its state to reopen
+    CloseSession(SessionId),
+    /// Restore the most recently closed session as `session`
+    ReopenClosed(SessionId),
+    /// Run `command` against `view` instead of the focused view
+    View {
+        view: ViewId,
+        command: Box<NavCommand>,
+    },
+    /// Open a view where the focused one is and focus it
+    OpenView {
+        session: SessionId,
+        view: ViewId,
+    },
+    /// Close a view; the last one stays open
+    CloseView {
+        session: SessionId,
+        view: ViewId,
+    },
+    FocusView {
+        session: SessionId,
+        view: ViewId,
+    },
+    /// Put nodes on the clipboard, the view's selection if `nodes` is empty
+    Clip {
+        session: SessionId,
+        mode: TransferMode,
+        nodes: Vec<NodeId>,
+    },
+    /// Copy or move what is on the clipboard, by default into the view's
+    /// directory; a cut is only pasted once
+    Paste {
+        session: SessionId,
+        destination: Option<NodeId>,
+        options: TransferOptions,
+    },
+    ClearClipboard(SessionId),
+    /// Copy or move nodes, the view's selection if `nodes` is empty, by
+    /// default into the other view's directory
+    Transfer {
+        session: SessionId,
+        mode: TransferMode,
+        nodes: Vec<NodeId>,
+        destination: Option<NodeId>,
+        options: TransferOptions,
+    },
+}
+
+impl NavCommand {
+    /// Session a user command comes from; `None` for what actors report
+    fn user_session(&self) -> Option<SessionId> {
+        match self {
+            NavCommand::Listed { .. } | NavCommand::Invalidate(_) => None,
+            NavCommand::View { command, .. } => command.user_session(),
+            NavCommand::Back(session)
+            | NavCommand::Forward(session)
+            | NavCommand::Up(session)
+            | NavCommand::Refresh(session)
+            | NavCommand::GetState(session)
+            | NavCommand::NewSession(session)
+            | NavCommand::CloseSession(session)
+            | NavCommand::ReopenClosed(session)
+            | NavCommand::ClearClipboard(session)
+            | NavCommand::Navigate { session, .. }
+            | NavCommand::NavigateToPath { session, .. }
+            | NavCommand::SetPipeline { session, .. }
+            | NavCommand::SetSelected { session, .. }
+            | NavCommand::Select { session, .. }
+            | NavCommand::OpenSession { session, .. }
+            | NavCommand::OpenView { session, .. }
+            | NavCommand::CloseView { session, .. }
+            | NavCommand::FocusView { session, .. }
+            | NavCommand::Clip { session, .. }
+            | NavCommand::Paste { session, .. }
+            | NavCommand::Transfer { session, .. } => Some(*session),
+        }
+    }
 }
 
 /// Navigation state snapshot (sent to UI via events)
@@ -103,10 +210,12 @@ pub struct NavigatorState {
     pub history_limit: usize,
     /// Pipeline configuration (serializable)
     pub pipeline_config: PipelineConfig,
-    /// Selected nodes
-    pub selected: HashSet<NodeId>,
+    /// Selected nodes, in the order they were selected
+    pub selected: Selection,
 
     pub register: NodeRegistry,
+    /// Last listing of a directory, with that directory
+    listing: Option<(NodeId, Arc<[FileNode]>)>,
 }
 
 impl NavigatorState {
@@ -124,7 +233,8 @@ impl NavigatorState {
                 filter: None,
                 group: None,
             },
-            selected: HashSet::new(),
+            selected: Selection::default(),
+            listing: None,
         }
     }
 
@@ -143,7 +253,8 @@ impl NavigatorState {
                 filter: None,
                 group: None,
             },
-            selected: HashSet::new(),
+            selected: Selection::default(),
+            listing: None,
         }
     }
 
@@ -217,8 +328,273 @@ impl NavigatorState {
                 .map(|f| self.register.clone().have_par(f).is_some())
                 .is_some(),
             pipeline: self.pipeline_config.clone(),
-            selected: self.selected.iter().map(|f| f.clone()).collect(),
+            selected: self.selected.nodes().to_vec(),
+        }
+    }
+
+    /// State to save, with nodes the registry no longer knows left out
+    pub fn to_saved(&self, view: &ViewId) -> SavedView {
+        let current = self.history.len().checked_sub(self.history_index + 1);
+        let mut history = Vec::with_capacity(self.history.len());
+        let mut position = None;
+        for (i, node) in self.history.iter().enumerate() {
+            if let Some(path) = self.register.resolve(*node) {
+                history.push(path);
+            }
+            if current.is_some_and(|current| i <= current) && !history.is_empty() {
+                position = Some(history.len() - 1);
+            }
+        }
+        let selected: Vec<PathBuf> = self
+            .selected
+            .nodes()
+            .iter()
+            .filter_map(|node| self.register.resolve(*node))
+            .collect();
+        SavedView {
+            view: view.clone(),
+            history_index: history.len().saturating_sub(position.unwrap_or(0) + 1),
+            history,
+            pipeline: self.pipeline_config.clone(),
+            selected,
+        }
+    }
+
+    /// Take over saved state, registering its paths again
+    ///
+    /// Paths that no longer exist are dropped; the current directory
+    /// falls back to the nearest older entry that is left.
+    pub fn restore(&mut self, saved: &SavedView) {
+        let current = saved.history.len().checked_sub(saved.history_index + 1);
+        self.history.clear();
+        let mut position = None;
+        for (i, path) in saved.history.iter().enumerate() {
+            if path.exists() {
+                let node = self.register.clone().register(path.clone());
+                // Dropping an entry can leave the same directory twice in a row
+                if self.history.back() != Some(&node) {
+                    self.history.push_back(node);
+                }
+            }
+            if current.is_some_and(|current| i <= current) && !self.history.is_empty() {
+                position = Some(self.history.len() - 1);
+            }
+        }
+        let excess = self.history.len().saturating_sub(self.history_limit);
+        self.history.drain(..excess);
+        let position = position.unwrap_or(0).saturating_sub(excess);
+        self.history_index = self.history.len().saturating_sub(position + 1);
+        self.current = self.history.get(position).copied();
+        self.pipeline_config = saved.pipeline.clone();
+        self.selected = saved
+            .selected
+            .iter()
+            .filter(|path| path.exists())
+            .map(|path| self.register.clone().register(path.clone()))
+            .collect();
+    }
+
+    /// Entries of the current directory in display order; empty until its
+    /// listing arrives
+    pub fn listing(&self) -> &[FileNode] {
+        match &self.listing {
+            Some((parent, entries)) if self.current == Some(*parent) => entries,
+            _ => &[],
+        }
+    }
+
+    /// Take a new listing of `parent` and deselect what is no longer in it
+    ///
+    /// Listings of other directories than the current one are ignored.
+    /// Returns whether the selection or its size changed.
+    pub fn set_listing(&mut self, parent: NodeId, entries: Arc<[FileNode]>) -> bool {
+        if self.current != Some(parent) {
+            return false;
+        }
+        let size = self.selection_size();
+        let pruned = self.selected.prune(&entries);
+        self.listing = Some((parent, entries));
+        pruned || self.selection_size() != size
+    }
+
+    /// Change the selection against the current listing; whether it
+    /// changed
+    pub fn select(&mut self, edit: SelectionEdit) -> Result<bool, CoreError> {
+        let listing = match &self.listing {
+            Some((parent, entries)) if self.current == Some(*parent) => &**entries,
+            _ => &[],
+        };
+        self.selected.apply(edit, listing)
+    }
+
+    /// Total size of the selected files in the current listing
+    pub fn selection_size(&self) -> u64 {
+        self.selected.size(self.listing())
+    }
+}
+
+/// Items cut (`Move`) or copied, waiting to be pasted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clipboard {
+    pub mode: TransferMode,
+    pub nodes: Vec<NodeId>,
+}
+
+/// Views (panes or tabs) of one session
+///
+/// Each view has its own history, pipeline and selection. One view has
+/// focus; the view focused before it is the other pane, which copies and
+/// moves go to by default. The clipboard is shared by all views.
+#[derive(Debug)]
+pub struct SessionViews {
+    /// Stable name the session is saved under
+    pub name: Option<String>,
+    pub clipboard: Option<Clipboard>,
+    /// In tab order
+    views: Vec<(ViewId, NavigatorState)>,
+    focused: ViewId,
+    previous: Option<ViewId>,
+    register: NodeRegistry,
+}
+
+impl SessionViews {
+    /// A session with only the main view
+    pub fn new(reg: NodeRegistry) -> Self {
+        Self {
+            name: None,
+            clipboard: None,
+            views: vec![(ViewId::main(), NavigatorState::new(reg.clone()))],
+            focused: ViewId::main(),
+            previous: None,
+            register: reg,
+        }
+    }
+
+    /// View IDs in tab order
+    pub fn ids(&self) -> Vec<ViewId> {
+        self.views.iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    pub fn focused(&self) -> &ViewId {
+        &self.focused
+    }
+
+    /// The other pane: the view focused before, or else the next one in
+    /// tab order
+    pub fn other(&self) -> Option<&ViewId> {
+        if let Some(previous) = &self.previous {
+            return Some(previous);
+        }
+        let index = self.index(&self.focused)?;
+        let next = (index + 1) % self.views.len();
+        (next != index).then(|| &self.views[next].0)
+    }
+
+    /// `view`, or the focused view if `None`
+    pub fn target(&self, view: Option<&ViewId>) -> ViewId {
+        view.unwrap_or(&self.focused).clone()
+    }
+
+    pub fn get(&self, view: &ViewId) -> Option<&NavigatorState> {
+        self.views.iter().find(|(id, _)| id == view).map(|(_, state)| state)
+    }
+
+    pub fn get_mut(&mut self, view: &ViewId) -> Option<&mut NavigatorState> {
+        self.views.iter_mut().find(|(id, _)| id == view).map(|(_, state)| state)
+    }
+
+    /// Every view, in tab order
+    pub fn iter(&self) -> impl Iterator<Item = (&ViewId, &NavigatorState)> {
+        self.views.iter().map(|(id, state)| (id, state))
+    }
+
+    /// Add a view after the focused one, showing the same directory with
+    /// the same pipeline, and focus it
+    pub fn open(&mut self, view: ViewId) -> Result<&NavigatorState, CoreError> {
+        if self.index(&view).is_some() {
+            return Err(CoreError::Refused(format!("{view} is already open")));
+        }
+        let mut state = NavigatorState::new(self.register.clone());
+        if let Some(focused) = self.get(&self.focused) {
+            if let Some(current) = focused.current {
+                state.navigate(current);
+            }
+            state.pipeline_config = focused.pipeline_config.clone();
+        }
+        let at = self.index(&self.focused).map_or(self.views.len(), |i| i + 1);
+        self.views.insert(at, (view.clone(), state));
+        self.focus(&view)?;
+        Ok(&self.views[at].1)
+    }
+
+    /// Close a view; focus goes to the other pane if it had it
+    pub fn close(&mut self, view: &ViewId) -> Result<NavigatorState, CoreError> {
+        let index = self.index(view).ok_or_else(|| Self::unknown(view))?;
+        if self.views.len() == 1 {
+            return Err(CoreError::Refused("the last view cannot be closed".to_string()));
+        }
+        if self.focused == *view {
+            let next = self.other().cloned().unwrap_or_else(|| self.views[0].0.clone());
+            self.focused = next;
+            self.previous = None;
+        }
+        if self.previous.as_ref() == Some(view) {
+            self.previous = None;
         }
+        Ok(self.views.remove(index).1)
+    }
+
+    pub fn focus(&mut self, view: &ViewId) -> Result<(), CoreError> {
+        self.index(view).ok_or_else(|| Self::unknown(view))?;
+        if self.focused != *view {
+            self.previous = Some(std::mem::replace(&mut self.focused, view.clone()));
+        }
+        Ok(())
+    }
+
+    /// State to save, every view included
+    pub fn to_saved(&self) -> SavedSession {
+        SavedSession {
+            name: self.name.clone(),
+            views: self.views.iter().map(|(id, state)| state.to_saved(id)).collect(),
+            focused: self.focused.clone(),
+            other: self.previous.clone(),
+            saved_at: 0,
+        }
+    }
+
+    /// Replace the views with saved ones; see `NavigatorState::restore`
+    pub fn restore(&mut self, saved: &SavedSession) {
+        self.name = saved.name.clone();
+        self.views = saved
+            .views
+            .iter()
+            .map(|view| {
+                let mut state = NavigatorState::new(self.register.clone());
+                state.restore(view);
+                (view.view.clone(), state)
+            })
+            .collect();
+        if self.views.is_empty() {
+            self.views.push((ViewId::main(), NavigatorState::new(self.register.clone())));
+        }
+        self.focused = if self.index(&saved.focused).is_some() {
+            saved.focused.clone()
+        } else {
+            self.views[0].0.clone()
+        };
+        self.previous = saved
+            .other
+            .clone()
+            .filter(|other| *other != self.focused && self.index(other).is_some());
+    }
+
+    fn index(&self, view: &ViewId) -> Option<usize> {
+        self.views.iter().position(|(id, _)| id == view)
+    }
+
+    fn unknown(view: &ViewId) -> CoreError {
+        CoreError::InvalidPath(format!("no {view} in this session"))
     }
 }
 
@@ -230,9 +606,12 @@ pub struct Navigator {
     events: Sender<events::Event>,
     /// Scanner channel for triggering scans
     scanner_tx: Sender<scanner::ScanCommand>,
-    sessions: Arc<scc::HashMap<SessionId, NavigatorState>>,
+    sessions: Arc<scc::HashMap<SessionId, SessionViews>>,
     path_cache: Arc<scc::HashSet<NodeId>>,
     register: NodeRegistry,
+    store: Option<SessionStore>,
+    operations: Option<Sender<OpCommand>>,
+    poller: Option<Sender<PollCommand>>,
 }
 
 impl Navigator {
@@ -249,144 +628,175 @@ impl Navigator {
             sessions: Arc::new(scc::HashMap::new()),
             path_cache: Arc::new(scc::HashSet::new()),
             register: reg,
+            store: None,
+            operations: None,
+            poller: None,
         }
     }
 
+    /// Save named sessions to `store` after every change and keep closed
+    /// ones to reopen
+    pub fn with_sessions(mut self, store: SessionStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Send pastes and transfers to the Operations actor
+    pub fn with_operations(mut self, operations: Sender<OpCommand>) -> Self {
+        self.operations = Some(operations);
+        self
+    }
+
+    /// Report user commands to the Poller, which polls idle sessions less
+    pub fn with_poller(mut self, poller: Sender<PollCommand>) -> Self {
+        self.poller = Some(poller);
+        self
+    }
+
+    /// Tell the poller `command`'s session is active, or gone once closed
+    fn report_activity(&self, command: &NavCommand) {
+        let (Some(poller), Some(session)) = (&self.poller, command.user_session()) else {
+            return;
+        };
+        let _ = poller.send(match command {
+            NavCommand::CloseSession(_) => PollCommand::UnwatchAll(session),
+            _ => PollCommand::Activity(session),
+        });
+    }
+
     /// Handle a navigation command
+    #[allow(clippy::too_many_arguments)]
     async fn handle_command(
         cmd: NavCommand,
-        sessions: Arc<scc::HashMap<SessionId, NavigatorState>>,
+        sessions: Arc<scc::HashMap<SessionId, SessionViews>>,
         register: NodeRegistry,
         path_cache: Arc<scc::HashSet<NodeId>>,
+        store: Option<&SessionStore>,
+        operations: Option<&Sender<OpCommand>>,
         scanner_tx: &Sender<scanner::ScanCommand>,
         events: &Sender<events::Event>,
     ) {
+        // Commands that do not name a view act on the focused one
+        let mut view = None;
+        let mut cmd = cmd;
+        while let NavCommand::View { view: named, command } = cmd {
+            view = view.or(Some(named));
+            cmd = *command;
+        }
+        let view = view.as_ref();
+
         match cmd {
+            // Unwrapped above
+            NavCommand::View { .. } => {}
             NavCommand::Navigate { session, node } => {
-                sessions
-                    .update_async(&session, |_, v| {
-                        v.navigate(node);
-                        Self::trigger_scan(session, node, v, scanner_tx.clone());
-                    })
-                    .await;
+                Self::update_view(&sessions, session, view, store, events, |id, v| {
+                    v.navigate(node);
+                    Self::trigger_scan(session, id, node, v, &path_cache, scanner_tx.clone());
+                })
+                .await;
             }
             NavCommand::NavigateToPath { session, path } => {
-                sessions
-                    .update_async(&session, |_, v| {
-                        v.navigate(register.clone().register(path.clone()));
-                        Self::trigger_scan(
-                            session,
-                            register.clone().register(path),
-                            v,
-                            scanner_tx.clone(),
-                        );
-                    })
-                    .await;
+                Self::update_view(&sessions, session, view, store, events, |id, v| {
+                    let node = register.clone().register(path);
+                    v.navigate(node);
+                    Self::trigger_scan(session, id, node, v, &path_cache, scanner_tx.clone());
+                })
+                .await;
             }
             NavCommand::Back(session_id) => {
-                sessions
-                    .update_async(&session_id, |_, v| {
-                        if v.can_back() {
-                            let node = v.back(1).unwrap();
-                            Self::trigger_scan(session_id, node, v, scanner_tx.clone());
-                        } else {
-                            let _ = events.send(Event::Error {
-                                message: "Cant go back!".to_string(),
-                                recoverable: true,
-                                session: session_id,
-                            });
-                        }
-                    })
-                    .await;
+                Self::update_view(&sessions, session_id, view, store, events, |id, v| {
+                    if v.can_back() {
+                        let node = v.back(1).unwrap();
+                        Self::trigger_scan(session_id, id, node, v, &path_cache, scanner_tx.clone());
+                    } else {
+                        Self::error(events, "Cant go back!".to_string(), session_id);
+                    }
+                })
+                .await;
             }
             NavCommand::Forward(session_id) => {
-                sessions
-                    .update_async(&session_id, |_, v| {
-                        if v.can_forward() {
-                            let node = v.forward().unwrap();
-                            Self::trigger_scan(session_id, node, v, scanner_tx.clone());
-                        } else {
-                            let _ = events.send(Event::Error {
-                                message: "Cant go forward!".to_string(),
-                                recoverable: true,
-                                session: session_id,
-                            });
-                        }
-                    })
-                    .await;
+                Self::update_view(&sessions, session_id, view, store, events, |id, v| {
+                    if v.can_forward() {
+                        let node = v.forward().unwrap();
+                        Self::trigger_scan(session_id, id, node, v, &path_cache, scanner_tx.clone());
+                    } else {
+                        Self::error(events, "Cant go forward!".to_string(), session_id);
+                    }
+                })
+                .await;
             }
             NavCommand::Up(session_id) => {
-                sessions
-                    .update_async(&session_id, |_, v| {
-                        if v.current
-                            .map(|f| register.clone().have_par(f).is_some())
-                            .is_some()
-                        {
-                            let par = v
-                                .current
-                                .map(|f| register.clone().get_par(f))
-                                .flatten()
-                                .unwrap();
-                            let node = register.clone().register(par);
-                            v.navigate(node);
-                            Self::trigger_scan(session_id, node, v, scanner_tx.clone());
-                        } else {
-                            let _ = events.send(Event::Error {
-                                message: "Cant go up!".to_string(),
-                                recoverable: true,
-                                session: session_id,
-                            });
-                        }
-                    })
-                    .await;
+                Self::update_view(&sessions, session_id, view, store, events, |id, v| {
+                    let parent = v.current.and_then(|f| register.clone().get_par(f));
+                    if let Some(parent) = parent {
+                        let node = register.clone().register(parent);
+                        v.navigate(node);
+                        Self::trigger_scan(session_id, id, node, v, &path_cache, scanner_tx.clone());
+                    } else {
+                        Self::error(events, "Cant go up!".to_string(), session_id);
+                    }
+                })
+                .await;
             }
             NavCommand::Refresh(session_id) => {
-                sessions
-                    .read_async(&session_id, |_k, v| {
-                        if v.current.is_some() {
-                            let cur = v.current.unwrap();
-                            Self::trigger_scan(session_id, cur, v, scanner_tx.clone());
-                        } else {
-                            let _ = events.send(Event::Error {
-                                message: "Cant refresh!".to_string(),
-                                recoverable: true,
-                                session: session_id,
-                            });
-                        }
-                    })
-                    .await;
+                Self::read_view(&sessions, session_id, view, events, |id, v| {
+                    if let Some(cur) = v.current {
+                        // Goes to the provider even when the listing is cached
+                        Self::trigger_update(session_id, id, cur, v, scanner_tx.clone());
+                    } else {
+                        Self::error(events, "Cant refresh!".to_string(), session_id);
+                    }
+                })
+                .await;
             }
             NavCommand::SetPipeline { session, config } => {
-                sessions
-                    .update_async(&session, |_k, v| {
-                        v.pipeline_config = config;
-                    })
-                    .await;
+                Self::update_view(&sessions, session, view, store, events, |_, v| {
+                    v.pipeline_config = config;
+                })
+                .await;
             }
             NavCommand::SetSelected { session, nodes } => {
+                Self::select(&sessions, session, view, SelectionEdit::Replace(nodes), store, events).await;
+            }
+            NavCommand::Select { session, edit } => {
+                Self::select(&sessions, session, view, edit, store, events).await;
+            }
+            NavCommand::Listed {
+                session,
+                view: listed,
+                parent,
+                entries,
+            } => {
                 sessions
-                    .update_async(&session, |_k, v: &mut NavigatorState| {
-                        v.selected.extend(nodes.iter());
+                    .update_async(&session, |_, views| {
+                        let Some(v) = views.get_mut(&listed) else {
+                            return;
+                        };
+                        if v.set_listing(parent, entries) {
+                            Self::selection_changed(session, &listed, v, events);
+                            Self::persist(store, session, views, events);
+                        }
                     })
                     .await;
             }
             NavCommand::GetState(session_id) => {
-                sessions
-                    .read_async(&session_id, |_k, v| {
-                        events.send(Event::CurrentNavigateState {
-                            session: session_id,
-                            state: v.snapshot(),
-                        })
-                    })
-                    .await;
+                Self::read_view(&sessions, session_id, view, events, |id, v| {
+                    let _ = events.send(Event::CurrentNavigateState {
+                        session: session_id,
+                        view: id.clone(),
+                        state: v.snapshot(),
+                    });
+                })
+                .await;
             }
             NavCommand::Invalidate(node_id) => {
                 if path_cache.contains_async(&node_id).await {
                     sessions
-                        .iter_async(|k, v| {
-                            let verd = v.current.map(|c| c == node_id);
-                            if verd.is_some() && verd.unwrap() == true {
-                                Self::trigger_scan(k.clone(), node_id, v, scanner_tx.clone());
+                        .iter_async(|k, views| {
+                            for (id, v) in views.iter() {
+                                if v.current == Some(node_id) {
+                                    Self::trigger_update(*k, id, node_id, v, scanner_tx.clone());
+                                }
                             }
                             true
                         })
@@ -394,30 +804,396 @@ impl Navigator {
                 }
             }
             NavCommand::NewSession(session_id) => {
-                let _ = sessions.insert_async(session_id, NavigatorState::new(register.clone())).await;
+                let _ = sessions.insert_async(session_id, SessionViews::new(register.clone())).await;
             },
+            NavCommand::OpenSession { session, name } => {
+                let mut views = SessionViews::new(register.clone());
+                if let Some(saved) = store.and_then(|store| store.get(&name)) {
+                    views.restore(&saved);
+                }
+                views.name = Some(name);
+                Self::resume(session, views, &sessions, &path_cache, store, operations, scanner_tx, events).await;
+            }
+            NavCommand::CloseSession(session_id) => {
+                let Some((_, views)) = sessions.remove_async(&session_id).await else {
+                    return;
+                };
+                if let Some(store) = store
+                    && let Err(e) = store.close(views.to_saved())
+                {
+                    Self::error(events, format!("Unable to keep closed session: {e}"), session_id);
+                }
+                let _ = events.send(Event::SessionDestroyed(session_id));
+            }
+            NavCommand::ReopenClosed(session_id) => {
+                let reopened = store
+                    .ok_or_else(|| CoreError::Refused("sessions are not saved".to_string()))
+                    .and_then(|store| store.reopen());
+                match reopened {
+                    Ok(Some(saved)) => {
+                        let mut views = SessionViews::new(register.clone());
+                        views.restore(&saved);
+                        Self::resume(session_id, views, &sessions, &path_cache, store, operations, scanner_tx, events)
+                            .await;
+                    }
+                    Ok(None) => Self::error(events, "No closed session to reopen".to_string(), session_id),
+                    Err(e) => Self::error(events, e.to_string(), session_id),
+                }
+            }
+            NavCommand::OpenView { session, view: new } => {
+                Self::update_session(&sessions, session, store, events, |views| {
+                    let state = views.open(new.clone())?;
+                    if let Some(current) = state.current {
+                        Self::trigger_scan(session, &new, current, state, &path_cache, scanner_tx.clone());
+                    }
+                    let _ = events.send(Event::CurrentNavigateState {
+                        session,
+                        view: new,
+                        state: state.snapshot(),
+                    });
+                    Self::views_changed(session, views, events);
+                    Ok(())
+                })
+                .await;
+            }
+            NavCommand::CloseView { session, view: closed } => {
+                Self::update_session(&sessions, session, store, events, |views| {
+                    views.close(&closed)?;
+                    Self::views_changed(session, views, events);
+                    Ok(())
+                })
+                .await;
+            }
+            NavCommand::FocusView { session, view: focused } => {
+                Self::update_session(&sessions, session, store, events, |views| {
+                    views.focus(&focused)?;
+                    Self::views_changed(session, views, events);
+                    Ok(())
+                })
+                .await;
+            }
+            NavCommand::Clip { session, mode, nodes } => {
+                Self::update_session(&sessions, session, store, events, |views| {
+                    let nodes = Self::sources(views, view, nodes)?;
+                    views.clipboard = Some(Clipboard { mode, nodes });
+                    let _ = events.send(Event::ClipboardChanged {
+                        session,
+                        clipboard: views.clipboard.clone(),
+                    });
+                    Ok(())
+                })
+                .await;
+            }
+            NavCommand::ClearClipboard(session) => {
+                Self::update_session(&sessions, session, store, events, |views| {
+                    views.clipboard = None;
+                    let _ = events.send(Event::ClipboardChanged {
+                        session,
+                        clipboard: None,
+                    });
+                    Ok(())
+                })
+                .await;
+            }
+            NavCommand::Paste {
+                session,
+                destination,
+                options,
+            } => {
+                Self::update_session(&sessions, session, store, events, |views| {
+                    let clipboard = views
+                        .clipboard
+                        .clone()
+                        .ok_or_else(|| CoreError::Refused("the clipboard is empty".to_string()))?;
+                    let target = views.target(view);
+                    let destination = Self::destination(views, destination, Some(&target))?;
+                    Self::transfer(operations, clipboard.mode, clipboard.nodes, destination, options, session)?;
+                    // What was cut is gone from where it was
+                    if clipboard.mode == TransferMode::Move {
+                        views.clipboard = None;
+                        let _ = events.send(Event::ClipboardChanged {
+                            session,
+                            clipboard: None,
+                        });
+                    }
+                    Ok(())
+                })
+                .await;
+            }
+            NavCommand::Transfer {
+                session,
+                mode,
+                nodes,
+                destination,
+                options,
+            } => {
+                Self::update_session(&sessions, session, store, events, |views| {
+                    let sources = Self::sources(views, view, nodes)?;
+                    let other = views.other().cloned();
+                    let destination = Self::destination(views, destination, other.as_ref())?;
+                    Self::transfer(operations, mode, sources, destination, options, session)
+                })
+                .await;
+            }
         }
     }
 
+    /// Run `f` on one view of a session and save the session after
+    ///
+    /// Unknown sessions are ignored; an unknown view is reported.
+    async fn update_view(
+        sessions: &scc::HashMap<SessionId, SessionViews>,
+        session: SessionId,
+        view: Option<&ViewId>,
+        store: Option<&SessionStore>,
+        events: &Sender<events::Event>,
+        f: impl FnOnce(&ViewId, &mut NavigatorState),
+    ) {
+        Self::update_session(sessions, session, store, events, |views| {
+            let id = views.target(view);
+            let state = views
+                .get_mut(&id)
+                .ok_or_else(|| CoreError::InvalidPath(format!("no {id} in this session")))?;
+            f(&id, state);
+            Ok(())
+        })
+        .await;
+    }
+
+    /// Apply a selection edit to a view and report the new selection
+    async fn select(
+        sessions: &scc::HashMap<SessionId, SessionViews>,
+        session: SessionId,
+        view: Option<&ViewId>,
+        edit: SelectionEdit,
+        store: Option<&SessionStore>,
+        events: &Sender<events::Event>,
+    ) {
+        Self::update_view(sessions, session, view, store, events, |id, v| match v.select(edit) {
+            Ok(true) => Self::selection_changed(session, id, v, events),
+            Ok(false) => {}
+            Err(e) => Self::error(events, e.to_string(), session),
+        })
+        .await;
+    }
+
+    /// Like `update_view`, for commands that change nothing
+    async fn read_view(
+        sessions: &scc::HashMap<SessionId, SessionViews>,
+        session: SessionId,
+        view: Option<&ViewId>,
+        events: &Sender<events::Event>,
+        f: impl FnOnce(&ViewId, &NavigatorState),
+    ) {
+        sessions
+            .read_async(&session, |_, views| {
+                let id = views.target(view);
+                match views.get(&id) {
+                    Some(state) => f(&id, state),
+                    None => Self::error(events, format!("no {id} in this session"), session),
+                }
+            })
+            .await;
+    }
+
+    /// Run `f` on a session, saving it if `f` succeeds and reporting the
+    /// error otherwise
+    async fn update_session(
+        sessions: &scc::HashMap<SessionId, SessionViews>,
+        session: SessionId,
+        store: Option<&SessionStore>,
+        events: &Sender<events::Event>,
+        f: impl FnOnce(&mut SessionViews) -> Result<(), CoreError>,
+    ) {
+        sessions
+            .update_async(&session, |_, views| match f(views) {
+                Ok(()) => Self::persist(store, session, views, events),
+                Err(e) => Self::error(events, e.to_string(), session),
+            })
+            .await;
+    }
+
+    /// `nodes`, or the view's selection if there are none
+    fn sources(views: &SessionViews, view: Option<&ViewId>, nodes: Vec<NodeId>) -> Result<Vec<NodeId>, CoreError> {
+        if !nodes.is_empty() {
+            return Ok(nodes);
+        }
+        let id = views.target(view);
+        let selected: Vec<NodeId> = views
+            .get(&id)
+            .map(|state| state.selected.nodes().to_vec())
+            .unwrap_or_default();
+        if selected.is_empty() {
+            return Err(CoreError::Refused("nothing is selected".to_string()));
+        }
+        Ok(selected)
+    }
+
+    /// `destination`, or the directory `view` shows
+    fn destination(
+        views: &SessionViews,
+        destination: Option<NodeId>,
+        view: Option<&ViewId>,
+    ) -> Result<NodeId, CoreError> {
+        if let Some(destination) = destination {
+            return Ok(destination);
+        }
+        let view = view.ok_or_else(|| CoreError::Refused("there is no other view".to_string()))?;
+        views
+            .get(view)
+            .and_then(|state| state.current)
+            .ok_or_else(|| CoreError::Refused(format!("{view} shows no directory")))
+    }
+
+    fn transfer(
+        operations: Option<&Sender<OpCommand>>,
+        mode: TransferMode,
+        sources: Vec<NodeId>,
+        destination: NodeId,
+        options: TransferOptions,
+        session: SessionId,
+    ) -> Result<(), CoreError> {
+        let operations =
+            operations.ok_or_else(|| CoreError::Refused("file operations are not connected".to_string()))?;
+        let command = match mode {
+            TransferMode::Copy => OpCommand::Copy {
+                sources,
+                destination,
+                options,
+                session,
+            },
+            TransferMode::Move => OpCommand::Move {
+                sources,
+                destination,
+                options,
+                session,
+            },
+        };
+        operations
+            .send(command)
+            .map_err(|_| CoreError::Refused("file operations have stopped".to_string()))
+    }
+
+    /// Install restored views as `session`, rescan their directories and
+    /// report them
+    #[allow(clippy::too_many_arguments)]
+    async fn resume(
+        session: SessionId,
+        views: SessionViews,
+        sessions: &scc::HashMap<SessionId, SessionViews>,
+        path_cache: &scc::HashSet<NodeId>,
+        store: Option<&SessionStore>,
+        operations: Option<&Sender<OpCommand>>,
+        scanner_tx: &Sender<scanner::ScanCommand>,
+        events: &Sender<events::Event>,
+    ) {
+        for (id, state) in views.iter() {
+            if let Some(current) = state.current {
+                Self::trigger_scan(session, id, current, state, path_cache, scanner_tx.clone());
+            }
+            let _ = events.send(Event::CurrentNavigateState {
+                session,
+                view: id.clone(),
+                state: state.snapshot(),
+            });
+        }
+        Self::views_changed(session, &views, events);
+        // The undo history follows the name, not the id
+        if let (Some(operations), Some(name)) = (operations, &views.name) {
+            let _ = operations.send(OpCommand::NameSession {
+                session,
+                name: name.clone(),
+            });
+        }
+        // Paths dropped while restoring stay dropped
+        Self::persist(store, session, &views, events);
+        sessions.upsert_async(session, views).await;
+    }
+
+    fn selection_changed(session: SessionId, view: &ViewId, state: &NavigatorState, events: &Sender<events::Event>) {
+        let _ = events.send(Event::SelectionChanged {
+            session,
+            view: view.clone(),
+            selected: state.selected.nodes().to_vec(),
+            count: state.selected.len(),
+            size: state.selection_size(),
+        });
+    }
+
+    fn views_changed(session: SessionId, views: &SessionViews, events: &Sender<events::Event>) {
+        let _ = events.send(Event::ViewsChanged {
+            session,
+            views: views.ids(),
+            focused: views.focused().clone(),
+            other: views.other().cloned(),
+        });
+    }
+
+    /// Save a named session after a change
+    fn persist(store: Option<&SessionStore>, session: SessionId, views: &SessionViews, events: &Sender<events::Event>) {
+        let Some(store) = store.filter(|_| views.name.is_some()) else {
+            return;
+        };
+        if let Err(e) = store.save(views.to_saved()) {
+            Self::error(events, format!("Unable to save session: {e}"), session);
+        }
+    }
+
+    fn error(events: &Sender<events::Event>, message: String, session: SessionId) {
+        let _ = events.send(Event::Error {
+            message,
+            recoverable: true,
+            session,
+        });
+    }
+
     /// Trigger a scan of the current directory
+    ///
+    /// Scanned directories are remembered in `path_cache` so that
+    /// `Invalidate` from the watcher only rescans what is on screen.
     fn trigger_scan(
         session: SessionId,
+        view: &ViewId,
         node: NodeId,
         state: &NavigatorState,
+        path_cache: &scc::HashSet<NodeId>,
         scanner_tx: Sender<crate::actors::scanner::ScanCommand>,
     ) {
+        let _ = path_cache.insert_sync(node);
         let _ = scanner_tx.send(ScanCommand::ScanNode {
             node,
             session,
+            view: view.clone(),
+            pipeline: state.pipeline_config.clone(),
+        });
+    }
+
+    /// Rescan a directory that is already on screen
+    ///
+    /// The scanner replies with a `DirectoryDelta` instead of reloading it.
+    fn trigger_update(
+        session: SessionId,
+        view: &ViewId,
+        node: NodeId,
+        state: &NavigatorState,
+        scanner_tx: Sender<crate::actors::scanner::ScanCommand>,
+    ) {
+        let _ = scanner_tx.send(ScanCommand::Update {
+            node,
+            session,
+            view: view.clone(),
             pipeline: state.pipeline_config.clone(),
         });
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handler(
         cmd: NavCommand,
-        sessions: Arc<scc::HashMap<SessionId, NavigatorState>>,
+        sessions: Arc<scc::HashMap<SessionId, SessionViews>>,
         register: NodeRegistry,
         path_cache: Arc<scc::HashSet<NodeId>>,
+        store: Option<SessionStore>,
+        operations: Option<Sender<OpCommand>>,
         scanner_tx: Sender<scanner::ScanCommand>,
         events: Sender<events::Event>,
     ) {
@@ -427,6 +1203,8 @@ impl Navigator {
                 sessions.clone(),
                 register.clone(),
                 path_cache.clone(),
+                store.as_ref(),
+                operations.as_ref(),
                 &scanner_tx,
                 &events,
             )
@@ -440,11 +1218,14 @@ impl Actor for Navigator {
         loop {
             match self.commands.recv_async().await {
                 Ok(command) => {
+                    self.report_activity(&command);
                     Self::handler(
                         command,
                         self.sessions.clone(),
                         self.register.clone(),
                         self.path_cache.clone(),
+                        self.store.clone(),
+                        self.operations.clone(),
                         self.scanner_tx.clone(),
                         self.events.clone(),
                     );
@@ -0,0 +1,56 @@
+//! Compressing and extracting archives
+
+use crate::api::events::OperationKind;
+use crate::model::node::NodeId;
+use crate::model::session::SessionId;
+use crate::services::archive::{ArchiveFormat, ArchiveOptions, Compress, Extract, ExtractOptions};
+use crate::services::scheduler::IoClass;
+
+use super::{Operations, valid_name};
+
+impl Operations {
+    /// Pack `sources` into the archive `name` inside `destination`
+    ///
+    /// The format's extension is added to `name` if it is missing.
+    pub(super) fn compress(
+        &self,
+        sources: Vec<NodeId>,
+        destination: NodeId,
+        name: String,
+        options: ArchiveOptions,
+        session: SessionId,
+    ) {
+        let operation = OperationKind::Compress;
+        if let Err(e) = valid_name(&name) {
+            return self.fail(operation, e.to_string(), session);
+        }
+        let Some(dest_path) = self.registry.resolve(destination) else {
+            self.fail(operation, format!("Unable to resolve ID: {destination:?}"), session);
+            return;
+        };
+        let Some(paths) = self.resolve_all(operation.clone(), &sources, session) else {
+            return;
+        };
+
+        let mut target = dest_path.join(&name);
+        if ArchiveFormat::from_path(&target) != Some(options.format) {
+            target = dest_path.join(format!("{name}{}", options.format.extension()));
+        }
+        let devices = paths.iter().chain([&dest_path]).cloned().collect();
+        let compress = Compress::new(paths, target, options);
+        self.run_job(operation, devices, IoClass::Bulk, Vec::new(), session, move |control, observer| {
+            compress.run(control, observer)
+        });
+    }
+
+    pub(super) fn extract(&self, archive: NodeId, destination: NodeId, options: ExtractOptions, session: SessionId) {
+        let operation = OperationKind::Extract;
+        let Some(paths) = self.resolve_all(operation.clone(), &[archive, destination], session) else {
+            return;
+        };
+        let extract = Extract::new(paths[0].clone(), paths[1].clone(), options);
+        self.run_job(operation, paths, IoClass::Bulk, Vec::new(), session, move |control, observer| {
+            extract.run(control, observer)
+        });
+    }
+}
@@ -0,0 +1,80 @@
+//! Permission, extended attribute and ACL jobs
+
+use crate::api::events::{Event, OperationKind};
+use crate::model::node::NodeId;
+use crate::model::session::SessionId;
+use crate::services::metadata::{AttributeEdit, FileAttributes};
+use crate::services::permissions::{Accounts, PermissionEdit};
+use crate::services::scheduler::IoClass;
+use crate::services::transfer::TransferReport;
+
+use super::Operations;
+
+impl Operations {
+    /// Apply `edit` to `nodes`; failed items are reported with the rest
+    pub(super) fn change_permissions(&self, nodes: Vec<NodeId>, edit: PermissionEdit, session: SessionId) {
+        let operation = OperationKind::ChangePermissions;
+        if edit.is_empty() {
+            return self.fail(operation, "No permission change given".to_string(), session);
+        }
+        let Some(paths) = self.resolve_all(operation.clone(), &nodes, session) else {
+            return;
+        };
+        let devices = paths.clone();
+        self.run_job(operation, devices, IoClass::Bulk, nodes, session, move |control, observer| {
+            // Without readable account files only numeric IDs resolve
+            let accounts = Accounts::load().unwrap_or_default();
+            let report = edit.run(&paths, &accounts, control, observer)?;
+            let errors = report.errors;
+            let _ = observer.events.send(Event::PermissionsChanged {
+                job: observer.job,
+                report,
+                session,
+            });
+            Ok(TransferReport {
+                errors,
+                ..Default::default()
+            })
+        });
+    }
+
+    pub(super) fn load_attributes(&self, node: NodeId, session: SessionId) {
+        let Some(path) = self.registry.resolve(node) else {
+            return Self::error(&self.events, format!("Unable to resolve ID: {node:?}"), session);
+        };
+        let provider = self.provider.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            match FileAttributes::load(provider.as_ref(), &path).await {
+                Ok(attributes) => {
+                    let _ = events.send(Event::AttributesLoaded {
+                        node,
+                        attributes,
+                        session,
+                    });
+                }
+                Err(e) => Self::error(&events, e.to_string(), session),
+            }
+        });
+    }
+
+    pub(super) fn edit_attributes(&self, nodes: Vec<NodeId>, edit: AttributeEdit, session: SessionId) {
+        let operation = OperationKind::EditAttributes;
+        let provider = self.provider.clone();
+        if !provider.capabilities().xattrs {
+            let message = format!("{} has no extended attributes", provider.scheme());
+            return self.fail(operation, message, session);
+        }
+        self.spawn(operation, session, move |shared| {
+            edit.validate()?;
+            let handle = tokio::runtime::Handle::current();
+            let mut paths = Vec::with_capacity(nodes.len());
+            for node in &nodes {
+                let path = shared.resolve(*node)?;
+                handle.block_on(edit.apply(provider.as_ref(), &path))?;
+                paths.push(path);
+            }
+            Ok(paths)
+        });
+    }
+}
@@ -0,0 +1,92 @@
+//! Backup, prune and check jobs on a backup repository
+
+use std::path::PathBuf;
+
+use crate::api::events::{Event, OperationKind};
+use crate::model::node::NodeId;
+use crate::model::session::SessionId;
+use crate::services::backup::{Backup, Repository, RetentionPolicy};
+use crate::services::scheduler::IoClass;
+use crate::services::transfer::TransferReport;
+
+use super::Operations;
+
+impl Operations {
+    pub(super) fn backup(&self, nodes: Vec<NodeId>, repository: PathBuf, session: SessionId) {
+        let operation = OperationKind::Backup;
+        let Some(paths) = self.resolve_all(operation.clone(), &nodes, session) else {
+            return;
+        };
+        let provider = self.provider.clone();
+        let read_size = self.config.buffer_size;
+        let devices = paths.iter().chain([&repository]).cloned().collect();
+        self.run_job(operation, devices, IoClass::Bulk, Vec::new(), session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(async {
+                let repository = Repository::new(provider.clone(), repository).open_or_init().await?;
+                let backup = Backup::new(provider, repository).with_read_size(read_size);
+                backup.run(&paths, control, observer).await
+            })?;
+            let errors = report.errors;
+            let _ = observer.events.send(Event::BackupFinished {
+                job: observer.job,
+                report,
+                session,
+            });
+            Ok(TransferReport {
+                errors,
+                ..Default::default()
+            })
+        });
+    }
+
+    pub(super) fn prune_backup(&self, repository: PathBuf, policy: RetentionPolicy, dry_run: bool, session: SessionId) {
+        let operation = OperationKind::PruneBackup;
+        if policy.keeps_nothing() {
+            return self.fail(operation, "The retention policy keeps no snapshots".to_string(), session);
+        }
+        let provider = self.provider.clone();
+        let devices = vec![repository.clone()];
+        self.run_job(operation, devices, IoClass::Background, Vec::new(), session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(async {
+                let repository = Repository::new(provider, repository).open().await?;
+                repository.prune(&policy, dry_run, control, observer).await
+            })?;
+            let errors = report.errors;
+            let _ = observer.events.send(Event::BackupPruned {
+                job: observer.job,
+                report,
+                session,
+            });
+            Ok(TransferReport {
+                errors,
+                ..Default::default()
+            })
+        });
+    }
+
+    pub(super) fn check_backup(&self, repository: PathBuf, read_data: bool, session: SessionId) {
+        let operation = OperationKind::CheckBackup;
+        let provider = self.provider.clone();
+        let devices = vec![repository.clone()];
+        self.run_job(operation, devices, IoClass::Background, Vec::new(), session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(async {
+                let repository = Repository::new(provider, repository).open().await?;
+                repository.check(read_data, control, observer).await
+            })?;
+            // Missing and damaged chunks fail the job
+            let errors = report.errors + report.missing.len() + report.damaged.len();
+            let _ = observer.events.send(Event::BackupChecked {
+                job: observer.job,
+                report,
+                session,
+            });
+            Ok(TransferReport {
+                errors,
+                ..Default::default()
+            })
+        });
+    }
+}
@@ -0,0 +1,101 @@
+//! Checksum, verification and manifest jobs
+
+use crate::api::events::{Event, OperationKind};
+use crate::model::node::NodeId;
+use crate::model::session::SessionId;
+use crate::services::hash::{Checksums, HashAlgorithm};
+use crate::services::scheduler::IoClass;
+use crate::services::transfer::TransferReport;
+
+use super::{Operations, valid_name};
+
+impl Operations {
+    pub(super) fn checksum(&self, nodes: Vec<NodeId>, algorithms: Vec<HashAlgorithm>, session: SessionId) {
+        let operation = OperationKind::Checksum;
+        if algorithms.is_empty() {
+            return self.fail(operation, "No checksum algorithm given".to_string(), session);
+        }
+        let Some(paths) = self.resolve_all(operation.clone(), &nodes, session) else {
+            return;
+        };
+        let checksums = Checksums::new(self.provider.clone()).with_chunk_size(self.config.buffer_size);
+        let devices = paths.clone();
+        self.run_job(operation, devices, IoClass::Background, Vec::new(), session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(checksums.compute(&paths, &algorithms, control, observer))?;
+            let errors = report.errors;
+            let _ = observer.events.send(Event::ChecksumsComputed {
+                job: observer.job,
+                report,
+                session,
+            });
+            Ok(TransferReport {
+                errors,
+                ..Default::default()
+            })
+        });
+    }
+
+    pub(super) fn verify_checksums(&self, manifest: NodeId, nodes: Vec<NodeId>, session: SessionId) {
+        let operation = OperationKind::VerifyChecksums;
+        let Some(mut paths) = self.resolve_all(operation.clone(), &[manifest], session) else {
+            return;
+        };
+        let Some(selection) = self.resolve_all(operation.clone(), &nodes, session) else {
+            return;
+        };
+        let manifest = paths.remove(0);
+        let checksums = Checksums::new(self.provider.clone()).with_chunk_size(self.config.buffer_size);
+        let devices = selection.iter().chain([&manifest]).cloned().collect();
+        self.run_job(operation, devices, IoClass::Background, Vec::new(), session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(checksums.verify(&manifest, &selection, control, observer))?;
+            // Mismatches and missing files fail the job
+            let errors = report.errors + report.failed.len() + report.missing.len();
+            let _ = observer.events.send(Event::ChecksumsVerified {
+                job: observer.job,
+                report,
+                session,
+            });
+            Ok(TransferReport {
+                errors,
+                ..Default::default()
+            })
+        });
+    }
+
+    pub(super) fn create_manifest(
+        &self,
+        nodes: Vec<NodeId>,
+        algorithm: HashAlgorithm,
+        directory: NodeId,
+        name: Option<String>,
+        session: SessionId,
+    ) {
+        let operation = OperationKind::CreateManifest;
+        let name = name.unwrap_or_else(|| algorithm.manifest_name().to_string());
+        if let Err(e) = valid_name(&name) {
+            return self.fail(operation, e.to_string(), session);
+        }
+        let Some(dir) = self.registry.resolve(directory) else {
+            self.fail(operation, format!("Unable to resolve ID: {directory:?}"), session);
+            return;
+        };
+        let Some(paths) = self.resolve_all(operation.clone(), &nodes, session) else {
+            return;
+        };
+        let manifest = dir.join(name);
+        let checksums = Checksums::new(self.provider.clone()).with_chunk_size(self.config.buffer_size);
+        let devices = paths.iter().chain([&dir]).cloned().collect();
+        self.run_job(operation, devices, IoClass::Background, Vec::new(), session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report =
+                handle.block_on(checksums.create_manifest(&paths, algorithm, &manifest, control, observer))?;
+            Ok(TransferReport {
+                created: vec![manifest],
+                errors: report.errors,
+                ..Default::default()
+            })
+        });
+    }
+}
@@ -0,0 +1,113 @@
+//! Undo and redo through the journal, and what the journal is told
+
+use flume::Sender;
+
+use crate::api::events::{Event, OperationKind};
+use crate::errors::CoreError;
+use crate::model::session::SessionId;
+use crate::services::journal::{Journal, JournalOp, Relocation};
+
+use super::{Operations, Shared};
+
+impl Operations {
+    pub(super) fn undo(&self, session: SessionId) {
+        self.revert(OperationKind::Undo, session);
+    }
+
+    pub(super) fn redo(&self, session: SessionId) {
+        self.revert(OperationKind::Redo, session);
+    }
+
+    /// Let `session` take over the history its `name` left behind
+    pub(super) fn name_session(&self, session: SessionId, name: String) {
+        if let Some(journal) = &self.journal {
+            journal.claim(session, &name);
+            let _ = self.events.send(history_changed(journal, session));
+        }
+    }
+
+    fn revert(&self, operation: OperationKind, session: SessionId) {
+        let undo = matches!(operation, OperationKind::Undo);
+        self.spawn(operation, session, move |shared| {
+            let journal = shared.journal()?;
+            let reverted = if undo {
+                journal.undo(session)
+            } else {
+                journal.redo(session)
+            };
+            shared.history(session);
+            let reverted = reverted?;
+            // Tags go back with the items an undo moves back
+            let moved: Vec<_> = relocations(&reverted.entry.op)
+                .iter()
+                .map(|item| {
+                    if undo {
+                        (item.to.clone(), item.from.clone())
+                    } else {
+                        (item.from.clone(), item.to.clone())
+                    }
+                })
+                .collect();
+            shared.follow_tags(&moved, session);
+            Ok(reverted.affected)
+        });
+    }
+}
+
+impl Shared {
+    fn journal(&self) -> Result<&Journal, CoreError> {
+        self.journal
+            .as_ref()
+            .ok_or_else(|| CoreError::Refused("undo history is not enabled".to_string()))
+    }
+
+    /// Journal a completed operation; `op` is only built with a journal
+    pub(super) fn record(
+        &self,
+        session: SessionId,
+        op: impl FnOnce() -> Result<JournalOp, CoreError>,
+    ) {
+        if let Some(journal) = &self.journal {
+            record(journal, &self.events, session, op);
+        }
+    }
+
+    fn history(&self, session: SessionId) {
+        if let Some(journal) = &self.journal {
+            let _ = self.events.send(history_changed(journal, session));
+        }
+    }
+}
+
+/// Journal `op` and announce the new history, or report why it was not
+pub(super) fn record(
+    journal: &Journal,
+    events: &Sender<Event>,
+    session: SessionId,
+    op: impl FnOnce() -> Result<JournalOp, CoreError>,
+) {
+    match op().and_then(|op| journal.record(session, op)) {
+        Ok(_) => {
+            let _ = events.send(history_changed(journal, session));
+        }
+        Err(e) => Operations::error(events, format!("Not recorded for undo: {e}"), session),
+    }
+}
+
+/// Current undo/redo state of a session
+pub(crate) fn history_changed(journal: &Journal, session: SessionId) -> Event {
+    Event::HistoryChanged {
+        undo: journal.next_undo(session).map(|e| e.op.describe()),
+        redo: journal.next_redo(session).map(|e| e.op.describe()),
+        session,
+    }
+}
+
+/// Items a journaled rename or move relocated
+fn relocations(op: &JournalOp) -> &[Relocation] {
+    match op {
+        JournalOp::Rename(item) => std::slice::from_ref(item),
+        JournalOp::Renames(items) | JournalOp::Move(items) => items,
+        _ => &[],
+    }
+}
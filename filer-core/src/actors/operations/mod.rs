@@ -0,0 +1,839 @@
+//! Operations actor - runs transfers, file operations and jobs
+//!
+//! Every job gets a `JobId` and runs on the blocking pool, streaming
+//! throttled `Event::JobProgress`; jobs can be paused, resumed and
+//! cancelled by ID, and conflicts outside the job's policy wait for
+//! `OpCommand::Resolve`. With a `Journal` completed operations can be
+//! undone and redone; with an `IoScheduler` jobs first queue for the
+//! devices they touch. The submodules hold the handlers of each feature.
+
+mod archive;
+mod attributes;
+mod backup;
+mod checksums;
+mod history;
+mod rename;
+mod sync;
+mod tags;
+mod transfer;
+
+pub(crate) use history::history_changed;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use flume::{Receiver, Sender};
+
+use crate::actors::Actor;
+use crate::actors::cache::CacheCommand;
+use crate::actors::syncer::SyncerCommand;
+use crate::api::events::{ConflictNode, Event, JobOutcome, OperationKind, TransferProgress};
+use crate::errors::CoreError;
+use crate::model::job::JobId;
+use crate::model::node::NodeId;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::archive::{ArchiveOptions, ExtractOptions};
+use crate::services::backup::RetentionPolicy;
+use crate::services::bisync::SyncPair;
+use crate::services::compare::{CompareEntry, CompareObserver, CompareOptions, SyncPlan};
+use crate::services::hash::HashAlgorithm;
+use crate::services::journal::{Journal, JournalOp, Stamp};
+use crate::services::metadata::{AttributeEdit, MetadataRegistry};
+use crate::services::permissions::PermissionEdit;
+use crate::services::rename::RenameRule;
+use crate::services::scheduler::{DeviceKey, IoClass, IoPermit, IoScheduler, IoTicket};
+use crate::services::tags::{TagEdit, TagStore};
+use crate::services::transfer::{
+    Conflict, ConflictAnswer, ConflictSide, JobControl, ProgressTracker, TransferMode, TransferObserver,
+    TransferOptions, TransferReport,
+};
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::FsProvider;
+
+/// Commands for operations actor
+#[derive(Debug, Clone)]
+pub enum OpCommand {
+    Copy {
+        sources: Vec<NodeId>,
+        destination: NodeId,
+        options: TransferOptions,
+        session: SessionId,
+    },
+    Move {
+        sources: Vec<NodeId>,
+        destination: NodeId,
+        options: TransferOptions,
+        session: SessionId,
+    },
+    /// Pack nodes into a new archive named `name` in `destination`
+    Compress {
+        sources: Vec<NodeId>,
+        destination: NodeId,
+        name: String,
+        options: ArchiveOptions,
+        session: SessionId,
+    },
+    /// Unpack an archive into `destination`
+    Extract {
+        archive: NodeId,
+        destination: NodeId,
+        options: ExtractOptions,
+        session: SessionId,
+    },
+    /// Hash nodes (directories recursively) with each algorithm
+    Checksum {
+        nodes: Vec<NodeId>,
+        algorithms: Vec<HashAlgorithm>,
+        session: SessionId,
+    },
+    /// Check nodes against a manifest; all of its entries if `nodes` is empty
+    VerifyChecksums {
+        manifest: NodeId,
+        nodes: Vec<NodeId>,
+        session: SessionId,
+    },
+    /// Write a manifest of nodes into `directory`, by default named after
+    /// the algorithm
+    CreateManifest {
+        nodes: Vec<NodeId>,
+        algorithm: HashAlgorithm,
+        directory: NodeId,
+        name: Option<String>,
+        session: SessionId,
+    },
+    /// Compare two directory trees
+    Compare {
+        left: NodeId,
+        right: NodeId,
+        options: CompareOptions,
+        session: SessionId,
+    },
+    /// Run a sync plan between two directories
+    Synchronize {
+        left: NodeId,
+        right: NodeId,
+        plan: SyncPlan,
+        session: SessionId,
+    },
+    /// Two-way sync of a folder pair; `dry_run` only reports the changes
+    Bisync {
+        pair: SyncPair,
+        dry_run: bool,
+        session: SessionId,
+    },
+    /// Back up nodes as a new snapshot, creating the repository if needed
+    Backup {
+        nodes: Vec<NodeId>,
+        repository: PathBuf,
+        session: SessionId,
+    },
+    /// Apply a retention policy; `dry_run` only reports what would go
+    PruneBackup {
+        repository: PathBuf,
+        policy: RetentionPolicy,
+        dry_run: bool,
+        session: SessionId,
+    },
+    /// Check a repository, reading every chunk back with `read_data`
+    CheckBackup {
+        repository: PathBuf,
+        read_data: bool,
+        session: SessionId,
+    },
+    /// Change the mode, owner or group of nodes
+    ChangePermissions {
+        nodes: Vec<NodeId>,
+        edit: PermissionEdit,
+        session: SessionId,
+    },
+    /// Change the tags, label or note of nodes
+    EditTags {
+        nodes: Vec<NodeId>,
+        edit: TagEdit,
+        session: SessionId,
+    },
+    /// Read the extended attributes and ACLs of a node
+    LoadAttributes { node: NodeId, session: SessionId },
+    /// Set or remove an extended attribute or ACL of nodes
+    EditAttributes {
+        nodes: Vec<NodeId>,
+        edit: AttributeEdit,
+        session: SessionId,
+    },
+    Pause(JobId, SessionId),
+    Resume(JobId, SessionId),
+    /// Stop a job; items already transferred are kept
+    Cancel(JobId, SessionId),
+    /// Answer the job's pending conflict
+    Resolve {
+        job: JobId,
+        answer: ConflictAnswer,
+        session: SessionId,
+    },
+    Rename {
+        node: NodeId,
+        new_name: String,
+        session: SessionId,
+    },
+    /// Rename many nodes with one rule; `dry_run` only sends a preview
+    BatchRename {
+        nodes: Vec<NodeId>,
+        rule: RenameRule,
+        dry_run: bool,
+        session: SessionId,
+    },
+    CreateFolder {
+        parent: NodeId,
+        name: String,
+        session: SessionId,
+    },
+    CreateFile {
+        parent: NodeId,
+        name: String,
+        session: SessionId,
+    },
+    /// Reverse the session's latest journaled operation
+    Undo(SessionId),
+    /// Apply the session's latest undone operation again
+    Redo(SessionId),
+    /// The session was opened under a stable name; its undo history from
+    /// earlier runs becomes undoable again
+    NameSession { session: SessionId, name: String },
+}
+
+/// Operations tuning
+#[derive(Debug, Clone, Copy)]
+pub struct OperationsConfig {
+    /// Minimum time between two progress events of a job
+    pub progress_interval: Duration,
+    /// Copy chunk size
+    pub buffer_size: usize,
+}
+
+impl Default for OperationsConfig {
+    fn default() -> Self {
+        Self {
+            progress_interval: Duration::from_millis(100),
+            buffer_size: 1024 * 1024,
+        }
+    }
+}
+
+/// A job that has not finished yet
+struct RunningJob {
+    control: Arc<JobControl>,
+    answers: Sender<ConflictAnswer>,
+    /// Only this session may control the job
+    session: SessionId,
+}
+
+type Jobs = scc::HashMap<JobId, RunningJob>;
+
+/// Operations actor - copy/move jobs with progress and control
+pub struct Operations {
+    commands: Receiver<OpCommand>,
+    events: Sender<Event>,
+    registry: NodeRegistry,
+    cache: Option<Sender<CacheCommand>>,
+    journal: Option<Journal>,
+    tags: Option<TagStore>,
+    metadata: Option<Arc<MetadataRegistry>>,
+    scheduler: Option<IoScheduler>,
+    /// Reads and writes checksum, sync and backup jobs go through
+    provider: Arc<dyn FsProvider>,
+    /// Providers serving the paths below a root instead of `provider`
+    mounts: Vec<(PathBuf, Arc<dyn FsProvider>)>,
+    /// Where sync pairs keep their state (`SyncState::default_path` if unset)
+    sync_states: Option<PathBuf>,
+    syncer: Option<Sender<SyncerCommand>>,
+    config: OperationsConfig,
+    jobs: Arc<Jobs>,
+}
+
+impl Operations {
+    pub fn new(commands: Receiver<OpCommand>, events: Sender<Event>, registry: NodeRegistry) -> Self {
+        Self {
+            commands,
+            events,
+            provider: Arc::new(LocalFs::new(registry.clone())),
+            mounts: Vec::new(),
+            registry,
+            cache: None,
+            journal: None,
+            tags: None,
+            metadata: None,
+            scheduler: None,
+            sync_states: None,
+            syncer: None,
+            config: OperationsConfig::default(),
+            jobs: Arc::new(scc::HashMap::new()),
+        }
+    }
+
+    /// Also send `CacheCommand::Invalidate` for nodes a job changed
+    pub fn with_cache(mut self, cache: Sender<CacheCommand>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Record completed operations and enable undo/redo
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Enable tag edits and keep tags with the files renames and moves
+    /// relocate
+    pub fn with_tags(mut self, tags: TagStore) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Source of EXIF and audio tags for batch rename templates
+    pub fn with_metadata(mut self, metadata: Arc<MetadataRegistry>) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Queue transfers for device slots before they start
+    pub fn with_scheduler(mut self, scheduler: IoScheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Provider for checksum, sync and backup jobs outside every mount
+    /// (`LocalFs` by default)
+    pub fn with_provider(mut self, provider: Arc<dyn FsProvider>) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    /// Serve paths below `root` with `provider`
+    ///
+    /// Copies and moves between providers stream through this process.
+    pub fn with_mount(mut self, root: PathBuf, provider: Arc<dyn FsProvider>) -> Self {
+        self.mounts.push((root, provider));
+        self
+    }
+
+    /// Keep the state of sync pairs in `dir`
+    pub fn with_sync_states(mut self, dir: PathBuf) -> Self {
+        self.sync_states = Some(dir);
+        self
+    }
+
+    /// Send `SyncerCommand::Finished` when a two-way sync that writes ends
+    pub fn with_syncer(mut self, syncer: Sender<SyncerCommand>) -> Self {
+        self.syncer = Some(syncer);
+        self
+    }
+
+    pub fn with_config(mut self, config: OperationsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn handle_command(&self, cmd: OpCommand) {
+        match cmd {
+            OpCommand::Copy {
+                sources,
+                destination,
+                options,
+                session,
+            } => self.start(TransferMode::Copy, sources, destination, options, session),
+            OpCommand::Move {
+                sources,
+                destination,
+                options,
+                session,
+            } => self.start(TransferMode::Move, sources, destination, options, session),
+            OpCommand::Compress {
+                sources,
+                destination,
+                name,
+                options,
+                session,
+            } => self.compress(sources, destination, name, options, session),
+            OpCommand::Extract {
+                archive,
+                destination,
+                options,
+                session,
+            } => self.extract(archive, destination, options, session),
+            OpCommand::Checksum {
+                nodes,
+                algorithms,
+                session,
+            } => self.checksum(nodes, algorithms, session),
+            OpCommand::VerifyChecksums {
+                manifest,
+                nodes,
+                session,
+            } => self.verify_checksums(manifest, nodes, session),
+            OpCommand::CreateManifest {
+                nodes,
+                algorithm,
+                directory,
+                name,
+                session,
+            } => self.create_manifest(nodes, algorithm, directory, name, session),
+            OpCommand::Compare {
+                left,
+                right,
+                options,
+                session,
+            } => self.compare(left, right, options, session),
+            OpCommand::Synchronize {
+                left,
+                right,
+                plan,
+                session,
+            } => self.synchronize(left, right, plan, session),
+            OpCommand::Bisync {
+                pair,
+                dry_run,
+                session,
+            } => self.bisync(pair, dry_run, session),
+            OpCommand::Backup {
+                nodes,
+                repository,
+                session,
+            } => self.backup(nodes, repository, session),
+            OpCommand::PruneBackup {
+                repository,
+                policy,
+                dry_run,
+                session,
+            } => self.prune_backup(repository, policy, dry_run, session),
+            OpCommand::CheckBackup {
+                repository,
+                read_data,
+                session,
+            } => self.check_backup(repository, read_data, session),
+            OpCommand::ChangePermissions { nodes, edit, session } => {
+                self.change_permissions(nodes, edit, session)
+            }
+            OpCommand::EditTags { nodes, edit, session } => self.edit_tags(nodes, edit, session),
+            OpCommand::LoadAttributes { node, session } => self.load_attributes(node, session),
+            OpCommand::EditAttributes { nodes, edit, session } => self.edit_attributes(nodes, edit, session),
+            OpCommand::Pause(job, session) => {
+                if self.control(job, session, JobControl::pause) {
+                    let _ = self.events.send(Event::JobPaused(job, session));
+                }
+            }
+            OpCommand::Resume(job, session) => {
+                if self.control(job, session, JobControl::resume) {
+                    let _ = self.events.send(Event::JobResumed(job, session));
+                }
+            }
+            OpCommand::Cancel(job, session) => {
+                // Reported by JobFinished once the worker stops
+                self.control(job, session, |control| {
+                    control.cancel();
+                    true
+                });
+            }
+            OpCommand::Resolve { job, answer, session } => {
+                let sent = self.running(job, session, |running| running.answers.send(answer).is_ok());
+                if sent == Some(false) {
+                    Self::error(&self.events, format!("No running job {job}"), session);
+                }
+            }
+            OpCommand::Rename {
+                node,
+                new_name,
+                session,
+            } => self.rename(node, new_name, session),
+            OpCommand::BatchRename {
+                nodes,
+                rule,
+                dry_run,
+                session,
+            } => self.batch_rename(nodes, rule, dry_run, session),
+            OpCommand::CreateFolder {
+                parent,
+                name,
+                session,
+            } => self.spawn(OperationKind::CreateFolder, session, move |shared| {
+                valid_name(&name)?;
+                let path = shared.resolve(parent)?.join(&name);
+                fs::create_dir(&path).map_err(|e| CoreError::from_io_error(e, path.clone()))?;
+                shared.record(session, || {
+                    Stamp::of(&path).map(|stamp| JournalOp::CreateFolder {
+                        path: path.clone(),
+                        stamp,
+                    })
+                });
+                Ok(vec![path])
+            }),
+            OpCommand::CreateFile {
+                parent,
+                name,
+                session,
+            } => self.spawn(OperationKind::CreateFile, session, move |shared| {
+                valid_name(&name)?;
+                let path = shared.resolve(parent)?.join(&name);
+                fs::File::create_new(&path)
+                    .map_err(|e| CoreError::from_io_error(e, path.clone()))?;
+                shared.record(session, || {
+                    Stamp::of(&path).map(|stamp| JournalOp::CreateFile {
+                        path: path.clone(),
+                        stamp,
+                    })
+                });
+                Ok(vec![path])
+            }),
+            OpCommand::Undo(session) => self.undo(session),
+            OpCommand::Redo(session) => self.redo(session),
+            OpCommand::NameSession { session, name } => self.name_session(session, name),
+        }
+    }
+
+    /// Run a simple operation on the blocking pool and report its outcome
+    ///
+    /// `op` returns the paths to report as affected.
+    fn spawn(
+        &self,
+        operation: OperationKind,
+        session: SessionId,
+        op: impl FnOnce(&Shared) -> Result<Vec<PathBuf>, CoreError> + Send + 'static,
+    ) {
+        let shared = Shared {
+            events: self.events.clone(),
+            registry: self.registry.clone(),
+            cache: self.cache.clone(),
+            journal: self.journal.clone(),
+            tags: self.tags.clone(),
+        };
+        tokio::task::spawn_blocking(move || {
+            let (success, affected) = match op(&shared) {
+                Ok(paths) => {
+                    let ids: Vec<NodeId> = paths
+                        .into_iter()
+                        .map(|p| shared.registry.clone().register(p))
+                        .collect();
+                    shared.invalidate(&ids);
+                    (true, ids)
+                }
+                Err(e) => {
+                    Self::error(&shared.events, e.to_string(), session);
+                    (false, Vec::new())
+                }
+            };
+            let _ = shared.events.send(Event::OperationComplete {
+                operation,
+                success,
+                affected,
+                session,
+            });
+        });
+    }
+
+    /// Apply `action` to a running job; true if its state changed
+    fn control(&self, job: JobId, session: SessionId, action: impl FnOnce(&JobControl) -> bool) -> bool {
+        self.running(job, session, |running| action(&running.control))
+            .unwrap_or(false)
+    }
+
+    /// Apply `action` to a job of `session`
+    ///
+    /// Jobs of other sessions are reported as missing, like finished ones.
+    fn running<T>(&self, job: JobId, session: SessionId, action: impl FnOnce(&RunningJob) -> T) -> Option<T> {
+        let result = self
+            .jobs
+            .read_sync(&job, |_, running| (running.session == session).then(|| action(running)))
+            .flatten();
+        if result.is_none() {
+            Self::error(&self.events, format!("No running job {job}"), session);
+        }
+        result
+    }
+
+    /// Paths of `ids`, or `None` after reporting the first that is unknown
+    fn resolve_all(&self, operation: OperationKind, ids: &[NodeId], session: SessionId) -> Option<Vec<PathBuf>> {
+        let mut paths = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.registry.resolve(*id) {
+                Some(path) => paths.push(path),
+                None => {
+                    self.fail(operation, format!("Unable to resolve ID: {id:?}"), session);
+                    return None;
+                }
+            }
+        }
+        Some(paths)
+    }
+
+    /// Provider of the mount with the longest root containing `path`
+    fn mount(&self, path: &Path) -> Option<&Arc<dyn FsProvider>> {
+        self.mounts
+            .iter()
+            .filter(|(root, _)| path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map(|(_, provider)| provider)
+    }
+
+    fn provider_for(&self, path: &Path) -> Arc<dyn FsProvider> {
+        self.mount(path).unwrap_or(&self.provider).clone()
+    }
+
+    /// Register a job and run `work` for it on the blocking pool
+    ///
+    /// With a scheduler the job first waits for a `class` slot on the
+    /// devices of `devices`. Created items and `sources` are invalidated in the
+    /// cache once it ends.
+    fn run_job(
+        &self,
+        operation: OperationKind,
+        devices: Vec<PathBuf>,
+        class: IoClass,
+        sources: Vec<NodeId>,
+        session: SessionId,
+        work: impl FnOnce(&JobControl, &mut JobObserver) -> Result<TransferReport, CoreError> + Send + 'static,
+    ) {
+        let job = JobId::new();
+        let control = Arc::new(JobControl::new());
+        let (answer_tx, answers) = flume::unbounded();
+        let _ = self.jobs.insert_sync(
+            job,
+            RunningJob {
+                control: control.clone(),
+                answers: answer_tx,
+                session,
+            },
+        );
+        let _ = self.events.send(Event::JobStarted {
+            job,
+            operation: operation.clone(),
+            session,
+        });
+
+        let ticket = self.scheduler.as_ref().map(|scheduler| {
+            let ticket = devices
+                .iter()
+                .fold(IoTicket::new(class), |ticket, path| ticket.on(DeviceKey::of_path(path)))
+                .for_session(session);
+            (scheduler.clone(), ticket)
+        });
+        let jobs = self.jobs.clone();
+        let events = self.events.clone();
+        let registry = self.registry.clone();
+        let cache = self.cache.clone();
+        let interval = self.config.progress_interval;
+
+        tokio::task::spawn_blocking(move || {
+            let mut observer = JobObserver {
+                job,
+                session,
+                events: &events,
+                registry: &registry,
+                control: &control,
+                answers,
+                interval,
+                last: None,
+            };
+            let result = match ticket {
+                Some((scheduler, ticket)) => match acquire(&scheduler, ticket, &control) {
+                    Some(_permit) => work(&control, &mut observer),
+                    None => Err(CoreError::Cancelled),
+                },
+                None => work(&control, &mut observer),
+            };
+            jobs.remove_sync(&job);
+
+            let (outcome, affected) = match result {
+                Ok(report) => {
+                    let affected: Vec<NodeId> =
+                        report.created.into_iter().map(|p| registry.clone().register(p)).collect();
+                    let outcome = match report.errors {
+                        0 => JobOutcome::Completed,
+                        errors => JobOutcome::Failed { errors },
+                    };
+                    (outcome, affected)
+                }
+                Err(CoreError::Cancelled) => (JobOutcome::Cancelled, Vec::new()),
+                Err(e) => {
+                    Self::error(&events, e.to_string(), session);
+                    (JobOutcome::Failed { errors: 1 }, Vec::new())
+                }
+            };
+
+            if let Some(cache) = &cache {
+                for id in affected.iter().chain(&sources) {
+                    let _ = cache.send(CacheCommand::Invalidate(*id));
+                }
+            }
+
+            let success = outcome == JobOutcome::Completed;
+            let _ = events.send(Event::JobFinished { job, outcome, session });
+            let _ = events.send(Event::OperationComplete {
+                operation,
+                success,
+                affected,
+                session,
+            });
+        });
+    }
+
+    /// Report an operation rejected before a job was created
+    fn fail(&self, operation: OperationKind, message: String, session: SessionId) {
+        Self::error(&self.events, message, session);
+        let _ = self.events.send(Event::OperationComplete {
+            operation,
+            success: false,
+            affected: Vec::new(),
+            session,
+        });
+    }
+
+    fn error(events: &Sender<Event>, message: String, session: SessionId) {
+        let _ = events.send(Event::Error {
+            message,
+            recoverable: true,
+            session,
+        });
+    }
+}
+
+/// Handles a simple operation needs on the blocking pool
+struct Shared {
+    events: Sender<Event>,
+    registry: NodeRegistry,
+    cache: Option<Sender<CacheCommand>>,
+    journal: Option<Journal>,
+    tags: Option<TagStore>,
+}
+
+impl Shared {
+    fn resolve(&self, id: NodeId) -> Result<PathBuf, CoreError> {
+        self.registry
+            .resolve(id)
+            .ok_or_else(|| CoreError::InvalidPath(format!("Unable to resolve ID: {id:?}")))
+    }
+
+    fn invalidate(&self, ids: &[NodeId]) {
+        if let Some(cache) = &self.cache {
+            for id in ids {
+                let _ = cache.send(CacheCommand::Invalidate(*id));
+            }
+        }
+    }
+}
+
+/// Wait for a scheduler slot; `None` if the job is cancelled first
+fn acquire(scheduler: &IoScheduler, ticket: IoTicket, control: &JobControl) -> Option<IoPermit> {
+    let pending = scheduler.request(ticket);
+    loop {
+        if control.is_cancelled() {
+            return None;
+        }
+        if let Some(permit) = pending.wait_timeout(Duration::from_millis(50)) {
+            return Some(permit);
+        }
+    }
+}
+
+/// Reject names that are not a single path component
+fn valid_name(name: &str) -> Result<(), CoreError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+        return Err(CoreError::InvalidPath(format!(
+            "\"{name}\" is not a valid name"
+        )));
+    }
+    Ok(())
+}
+
+/// Forwards transfer progress as throttled events
+struct JobObserver<'a> {
+    job: JobId,
+    session: SessionId,
+    events: &'a Sender<Event>,
+    registry: &'a NodeRegistry,
+    control: &'a JobControl,
+    answers: Receiver<ConflictAnswer>,
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl CompareObserver for JobObserver<'_> {
+    fn entries(&mut self, entries: &[CompareEntry]) {
+        let _ = self.events.send(Event::CompareEntries {
+            job: self.job,
+            entries: entries.to_vec(),
+            session: self.session,
+        });
+    }
+}
+
+impl TransferObserver for JobObserver<'_> {
+    fn progress(&mut self, tracker: &ProgressTracker, current: &Path) {
+        let now = Instant::now();
+        let finished = tracker.files_done >= tracker.files_total && tracker.bytes_done >= tracker.bytes_total;
+        if !finished && self.last.is_some_and(|last| now.duration_since(last) < self.interval) {
+            return;
+        }
+        self.last = Some(now);
+        let progress = TransferProgress {
+            bytes_done: tracker.bytes_done,
+            bytes_total: tracker.bytes_total,
+            files_done: tracker.files_done,
+            files_total: tracker.files_total,
+            throughput: tracker.throughput(now),
+            eta: tracker.eta(now),
+            current: Some(self.registry.clone().register(current.to_path_buf())),
+        };
+        let _ = self.events.send(Event::JobProgress {
+            job: self.job,
+            progress,
+            session: self.session,
+        });
+    }
+
+    fn failed(&mut self, path: &Path, error: &CoreError) {
+        Operations::error(self.events, format!("{}: {error}", path.display()), self.session);
+    }
+
+    fn conflict(&mut self, conflict: &Conflict) -> Result<ConflictAnswer, CoreError> {
+        // Answers sent while nothing was pending do not count
+        self.answers.drain();
+        let _ = self.events.send(Event::OperationConflict {
+            job: self.job,
+            source: self.node(&conflict.source),
+            target: self.node(&conflict.target),
+            session: self.session,
+        });
+        loop {
+            match self.answers.recv_timeout(Duration::from_millis(50)) {
+                Ok(answer) => return Ok(answer),
+                Err(flume::RecvTimeoutError::Timeout) if !self.control.is_cancelled() => {}
+                Err(_) => return Err(CoreError::Cancelled),
+            }
+        }
+    }
+}
+
+impl JobObserver<'_> {
+    fn node(&self, side: &ConflictSide) -> ConflictNode {
+        ConflictNode {
+            node: self.registry.clone().register(side.path.clone()),
+            size: side.size,
+            modified: side.modified,
+            is_dir: side.is_dir,
+        }
+    }
+}
+
+impl Actor for Operations {
+    async fn run(self) {
+        while let Ok(cmd) = self.commands.recv_async().await {
+            self.handle_command(cmd);
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "operations"
+    }
+}
@@ -0,0 +1,106 @@
+//! Renames; batches are planned first and applied all-or-nothing
+
+use std::fs;
+
+use crate::api::events::{Event, OperationKind};
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeId};
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::journal::{JournalOp, Relocation};
+use crate::services::metadata::MetadataRegistry;
+use crate::services::mime::MimeDetector;
+use crate::services::rename::{RenamePlan, RenameRule, RenameStatus, RenameSubject, Renamer, rename_new};
+
+use super::{Operations, valid_name};
+
+impl Operations {
+    /// Rename one item; an existing item of the new name is never replaced
+    pub(super) fn rename(&self, node: NodeId, new_name: String, session: SessionId) {
+        self.spawn(OperationKind::Rename, session, move |shared| {
+            let path = shared.resolve(node)?;
+            valid_name(&new_name)?;
+            let target = path.with_file_name(&new_name);
+            rename_new(&path, &target)?;
+            shared.invalidate(&[node]);
+            shared.registry.unregister(node);
+            shared.follow_tags(&[(path.clone(), target.clone())], session);
+            shared.record(session, || Relocation::new(path, target.clone()).map(JournalOp::Rename));
+            Ok(vec![target])
+        });
+    }
+
+    pub(super) fn batch_rename(&self, nodes: Vec<NodeId>, rule: RenameRule, dry_run: bool, session: SessionId) {
+        let renamer = match Renamer::new(&rule) {
+            Ok(renamer) => renamer,
+            Err(e) => return self.fail(OperationKind::Rename, e.to_string(), session),
+        };
+        let metadata = self.metadata.clone().filter(|_| renamer.needs_metadata());
+        if dry_run {
+            let events = self.events.clone();
+            let registry = self.registry.clone();
+            tokio::task::spawn_blocking(move || {
+                match rename_subjects(&registry, &nodes, metadata.as_deref()) {
+                    Ok(subjects) => {
+                        let plan = RenamePlan::new(&renamer, &subjects);
+                        let _ = events.send(Event::RenamePreview { plan, session });
+                    }
+                    Err(e) => Self::error(&events, e.to_string(), session),
+                }
+            });
+            return;
+        }
+
+        self.spawn(OperationKind::Rename, session, move |shared| {
+            let subjects = rename_subjects(&shared.registry, &nodes, metadata.as_deref())?;
+            let plan = RenamePlan::new(&renamer, &subjects);
+            let renamed = plan.apply()?;
+            for item in plan.items.iter().filter(|i| i.status == RenameStatus::Ready) {
+                shared.invalidate(&[item.node]);
+                shared.registry.unregister(item.node);
+            }
+            shared.follow_tags(&renamed, session);
+            if !renamed.is_empty() {
+                shared.record(session, || {
+                    renamed
+                        .iter()
+                        .map(|(from, to)| Relocation::new(from.clone(), to.clone()))
+                        .collect::<Result<_, _>>()
+                        .map(JournalOp::Renames)
+                });
+            }
+            Ok(renamed.into_iter().map(|(_, to)| to).collect())
+        });
+    }
+}
+
+/// Describe `nodes` for a batch rename; symlinks are not followed
+fn rename_subjects(
+    registry: &NodeRegistry,
+    nodes: &[NodeId],
+    metadata: Option<&MetadataRegistry>,
+) -> Result<Vec<RenameSubject>, CoreError> {
+    nodes
+        .iter()
+        .map(|&id| {
+            let path = registry
+                .resolve(id)
+                .ok_or_else(|| CoreError::InvalidPath(format!("Unable to resolve ID: {id:?}")))?;
+            let meta = fs::symlink_metadata(&path).map_err(|e| CoreError::from_io_error(e, path.clone()))?;
+            let mut node = FileNode::from_metadata(meta, path.clone(), None)?;
+            // `from_metadata` canonicalizes, which resolves a symlink itself
+            node.id = id;
+            node.name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            node.path = path;
+            let subject = RenameSubject::new(node);
+            let Some(metadata) = metadata else { return Ok(subject) };
+            let category = MimeDetector::new().detect_from_path(&subject.node.path).category;
+            let extracted = tokio::runtime::Handle::current().block_on(metadata.extract(&subject.node.path, category));
+            // Items without metadata are reported by the plan
+            Ok(match extracted {
+                Ok(extended) => subject.with_metadata(extended),
+                Err(_) => subject,
+            })
+        })
+        .collect()
+}
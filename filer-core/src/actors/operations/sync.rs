@@ -0,0 +1,115 @@
+//! Comparing and synchronizing directories and two-way syncs of pairs
+
+use flume::Sender;
+
+use crate::actors::syncer::SyncerCommand;
+use crate::api::events::{Event, OperationKind};
+use crate::model::node::NodeId;
+use crate::model::session::SessionId;
+use crate::services::bisync::{Bisync, SyncPair, SyncState};
+use crate::services::compare::{Compare, CompareOptions, SyncPlan, Synchronize};
+use crate::services::scheduler::IoClass;
+use crate::services::transfer::TransferReport;
+
+use super::Operations;
+
+/// Tells the syncer that a run of its pair ended, however it ended
+struct RunEnded(Sender<SyncerCommand>, String);
+
+impl Drop for RunEnded {
+    fn drop(&mut self) {
+        let _ = self.0.send(SyncerCommand::Finished(std::mem::take(&mut self.1)));
+    }
+}
+
+impl Operations {
+    pub(super) fn compare(&self, left: NodeId, right: NodeId, options: CompareOptions, session: SessionId) {
+        let operation = OperationKind::Compare;
+        let Some(paths) = self.resolve_all(operation.clone(), &[left, right], session) else {
+            return;
+        };
+        let (left_fs, right_fs) = (self.provider_for(&paths[0]), self.provider_for(&paths[1]));
+        let compare = Compare::new(left_fs, paths[0].clone(), right_fs, paths[1].clone())
+            .with_options(options)
+            .with_chunk_size(self.config.buffer_size);
+        self.run_job(operation, paths, IoClass::Background, Vec::new(), session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(compare.run(control, observer))?;
+            let _ = observer.events.send(Event::CompareFinished {
+                job: observer.job,
+                summary: report.summary,
+                session,
+            });
+            Ok(TransferReport {
+                errors: report.summary.errors,
+                ..Default::default()
+            })
+        });
+    }
+
+    pub(super) fn synchronize(&self, left: NodeId, right: NodeId, plan: SyncPlan, session: SessionId) {
+        let operation = OperationKind::Synchronize;
+        let Some(paths) = self.resolve_all(operation.clone(), &[left, right], session) else {
+            return;
+        };
+        let sync = Synchronize::new(
+            self.provider_for(&paths[0]),
+            paths[0].clone(),
+            self.provider_for(&paths[1]),
+            paths[1].clone(),
+            plan,
+        )
+        .with_chunk_size(self.config.buffer_size);
+        self.run_job(operation, paths, IoClass::Bulk, vec![left, right], session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(sync.run(control, observer))?;
+            let errors = report.errors;
+            let _ = observer.events.send(Event::SyncFinished {
+                job: observer.job,
+                report,
+                session,
+            });
+            Ok(TransferReport {
+                errors,
+                ..Default::default()
+            })
+        });
+    }
+
+    pub(super) fn bisync(&self, pair: SyncPair, dry_run: bool, session: SessionId) {
+        let operation = OperationKind::Bisync;
+        let ended = self.syncer.clone().filter(|_| !dry_run).map(|syncer| RunEnded(syncer, pair.name.clone()));
+        let state = match &self.sync_states {
+            Some(dir) => SyncState::path_in(dir, &pair.name),
+            None => SyncState::default_path(&pair.name),
+        };
+        let state = match state {
+            Ok(state) => state,
+            Err(e) => return self.fail(operation, e.to_string(), session),
+        };
+        let provider = self.provider.clone();
+        let chunk_size = self.config.buffer_size;
+        let devices = vec![pair.left.clone(), pair.right.clone()];
+        self.run_job(operation, devices, IoClass::Bulk, Vec::new(), session, move |control, observer| {
+            let _ended = ended;
+            let name = pair.name.clone();
+            let state = SyncState::open(state, &pair)?;
+            let mut bisync = Bisync::new(pair, provider.clone(), provider, state)
+                .with_dry_run(dry_run)
+                .with_chunk_size(chunk_size);
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(bisync.run(control, observer))?;
+            let errors = report.errors;
+            let _ = observer.events.send(Event::BisyncFinished {
+                job: observer.job,
+                pair: name,
+                report,
+                session,
+            });
+            Ok(TransferReport {
+                errors,
+                ..Default::default()
+            })
+        });
+    }
+}
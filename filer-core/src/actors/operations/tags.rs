@@ -0,0 +1,58 @@
+//! Tag edits, and tags following the items they name
+
+use std::path::PathBuf;
+
+use flume::Sender;
+
+use crate::api::events::{Event, OperationKind};
+use crate::errors::CoreError;
+use crate::model::node::NodeId;
+use crate::model::session::SessionId;
+use crate::services::tags::{TagEdit, TagStore};
+
+use super::{Operations, Shared};
+
+impl Operations {
+    pub(super) fn edit_tags(&self, nodes: Vec<NodeId>, edit: TagEdit, session: SessionId) {
+        self.spawn(OperationKind::EditTags, session, move |shared| {
+            let tags = shared
+                .tags
+                .as_ref()
+                .ok_or_else(|| CoreError::Refused("tags are not enabled".to_string()))?;
+            edit.validate()?;
+            let mut paths = Vec::with_capacity(nodes.len());
+            for node in &nodes {
+                let path = shared.resolve(*node)?;
+                tags.edit(&path, &edit)?;
+                paths.push(path);
+            }
+            Ok(paths)
+        });
+    }
+}
+
+impl Shared {
+    pub(super) fn follow_tags(&self, moved: &[(PathBuf, PathBuf)], session: SessionId) {
+        if let Some(tags) = &self.tags {
+            follow_tags(tags, &self.events, moved, session);
+        }
+    }
+}
+
+/// Move the tag records of relocated items to their new paths
+pub(super) fn follow_tags(
+    tags: &TagStore,
+    events: &Sender<Event>,
+    moved: &[(PathBuf, PathBuf)],
+    session: SessionId,
+) {
+    for (from, to) in moved {
+        if let Err(e) = tags.moved(from, to) {
+            Operations::error(
+                events,
+                format!("Tags of {} not moved: {e}", from.display()),
+                session,
+            );
+        }
+    }
+}
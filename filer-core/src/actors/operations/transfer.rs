@@ -0,0 +1,146 @@
+//! Copy and move jobs, streamed when they cross providers
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use flume::Sender;
+
+use crate::api::events::{Event, OperationKind};
+use crate::model::node::NodeId;
+use crate::model::session::SessionId;
+use crate::services::journal::{Journal, JournalOp, Relocation};
+use crate::services::scheduler::IoClass;
+use crate::services::transfer::{StreamCopy, Transfer, TransferMode, TransferOptions, TransferReport};
+
+use super::Operations;
+use super::history::record;
+use super::tags::follow_tags;
+
+impl Operations {
+    pub(super) fn start(
+        &self,
+        mode: TransferMode,
+        sources: Vec<NodeId>,
+        destination: NodeId,
+        options: TransferOptions,
+        session: SessionId,
+    ) {
+        let operation = match mode {
+            TransferMode::Copy => OperationKind::Copy,
+            TransferMode::Move => OperationKind::Move,
+        };
+        let Some(dest_path) = self.registry.resolve(destination) else {
+            self.fail(operation, format!("Unable to resolve ID: {destination:?}"), session);
+            return;
+        };
+        let Some(paths) = self.resolve_all(operation.clone(), &sources, session) else {
+            return;
+        };
+
+        let mounted = paths.iter().chain([&dest_path]).any(|path| self.mount(path).is_some());
+        if mounted {
+            return self.stream(mode, sources, paths, dest_path, options, session);
+        }
+
+        let devices = paths.iter().chain([&dest_path]).cloned().collect();
+        let transfer = Transfer::new(mode, paths, dest_path)
+            .with_options(options)
+            .with_buffer_size(self.config.buffer_size);
+        let events = self.events.clone();
+        let registry = self.registry.clone();
+        let journal = self.journal.clone();
+        let tags = self.tags.clone();
+        self.run_job(operation, devices, IoClass::Bulk, sources.clone(), session, move |control, observer| {
+            let mut report = TransferReport::default();
+            let result = transfer.run_into(control, observer, &mut report);
+            if mode == TransferMode::Move {
+                for id in &sources {
+                    if registry.resolve(*id).is_some_and(|p| !p.exists()) {
+                        registry.unregister(*id);
+                    }
+                }
+            }
+            // What arrived before a cancel keeps its tags and can be undone
+            if let Some(tags) = &tags
+                && mode == TransferMode::Move
+            {
+                follow_tags(tags, &events, &report.fresh, session);
+            }
+            if let Some(journal) = &journal {
+                record_transfer(journal, &events, mode, &report.fresh, session);
+            }
+            result.map(|()| report)
+        });
+    }
+
+    /// Copy or move between providers, or within one that is not local
+    ///
+    /// Conflicts are settled as for local transfers. Moves delete a source
+    /// only once all of it arrived. Nothing is journaled since the journal
+    /// only knows local files.
+    pub(super) fn stream(
+        &self,
+        mode: TransferMode,
+        sources: Vec<NodeId>,
+        paths: Vec<PathBuf>,
+        dest_path: PathBuf,
+        options: TransferOptions,
+        session: SessionId,
+    ) {
+        let operation = match mode {
+            TransferMode::Copy => OperationKind::Copy,
+            TransferMode::Move => OperationKind::Move,
+        };
+        let source = self.provider_for(&paths[0]);
+        if paths.iter().any(|path| !Arc::ptr_eq(&self.provider_for(path), &source)) {
+            let message = "Sources must all be on the same provider".to_string();
+            return self.fail(operation, message, session);
+        }
+        let devices = paths.iter().chain([&dest_path]).cloned().collect();
+        let copy = StreamCopy::new(source.clone(), paths.clone(), self.provider_for(&dest_path), dest_path)
+            .with_chunk_size(self.config.buffer_size)
+            .with_conflict(options.conflict)
+            .with_preserve_mtime(options.preserve_mtime || mode == TransferMode::Move);
+        let registry = self.registry.clone();
+        self.run_job(operation, devices, IoClass::Bulk, sources.clone(), session, move |control, observer| {
+            let handle = tokio::runtime::Handle::current();
+            let report = handle.block_on(copy.run(control, observer))?;
+            if mode == TransferMode::Move {
+                for (id, path) in sources.iter().zip(&paths) {
+                    if report.complete.contains(path) {
+                        handle.block_on(source.remove(path))?;
+                        registry.unregister(*id);
+                    }
+                }
+            }
+            Ok(TransferReport {
+                created: report.created,
+                errors: report.errors,
+                skipped: report.skipped,
+                ..Default::default()
+            })
+        });
+    }
+}
+
+fn record_transfer(
+    journal: &Journal,
+    events: &Sender<Event>,
+    mode: TransferMode,
+    fresh: &[(PathBuf, PathBuf)],
+    session: SessionId,
+) {
+    if fresh.is_empty() {
+        return;
+    }
+    record(journal, events, session, || {
+        let items = fresh
+            .iter()
+            .map(|(from, to)| Relocation::new(from.clone(), to.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(match mode {
+            TransferMode::Copy => JournalOp::Copy(items),
+            TransferMode::Move => JournalOp::Move(items),
+        })
+    });
+}
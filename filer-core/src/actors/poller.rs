@@ -0,0 +1,331 @@
+//! Poller actor - change detection for providers without native watching
+//!
+//! Remote providers report `Capabilities.watch == false`. The poller lists
+//! each watched directory on an interval (with jitter), diffs it against the
+//! previous snapshot and emits the same `FsChanged` events as the Watcher.
+//! Polling backs off while every session using a directory is idle and
+//! stops entirely when nothing is watched. Each poll runs as its own task,
+//! so a slow remote directory holds up neither the others nor commands.
+//! With an `IoScheduler` polls wait for a background slot, behind listings
+//! and previews.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use flume::{Receiver, Sender};
+use tokio::time::Instant;
+
+use crate::actors::Actor;
+use crate::actors::cache::CacheCommand;
+use crate::actors::navigator::NavCommand;
+use crate::api::events::Event;
+use crate::errors::CoreError;
+use crate::model::fs_change::FsChangeKind;
+use crate::model::node::FileNode;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::scheduler::{IoClass, IoScheduler, IoTicket};
+use crate::services::watch::Snapshot;
+use crate::vfs::provider::FsProvider;
+
+/// Commands for poller actor
+#[derive(Debug, Clone)]
+pub enum PollCommand {
+    Watch(PathBuf, SessionId),
+    Unwatch(PathBuf, SessionId),
+    UnwatchAll(SessionId),
+    /// The session interacted with the UI; poll at the base rate again
+    Activity(SessionId),
+}
+
+/// Poller timing configuration
+#[derive(Debug, Clone, Copy)]
+pub struct PollerConfig {
+    /// Base polling interval
+    pub interval: Duration,
+    /// Random spread applied to each interval (0.1 = ±10%)
+    pub jitter: f64,
+    /// Sessions without activity for this long count as idle
+    pub idle_after: Duration,
+    /// Longest interval reached by backing off
+    pub max_interval: Duration,
+}
+
+impl Default for PollerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            jitter: 0.1,
+            idle_after: Duration::from_secs(60),
+            max_interval: Duration::from_secs(120),
+        }
+    }
+}
+
+impl PollerConfig {
+    /// Interval to use after a poll, given the current one and idleness
+    pub fn next_interval(&self, current: Duration, idle: bool) -> Duration {
+        if idle {
+            (current * 2).min(self.max_interval).max(self.interval)
+        } else {
+            self.interval
+        }
+    }
+
+    /// Apply jitter using a `unit` random value in [0, 1)
+    pub fn jittered(&self, interval: Duration, unit: f64) -> Duration {
+        let spread = self.jitter.clamp(0.0, 1.0) * (unit * 2.0 - 1.0);
+        interval.mul_f64(1.0 + spread)
+    }
+}
+
+/// A directory being polled
+struct PolledDir {
+    snapshot: Option<Snapshot>,
+    users: HashMap<SessionId, usize>,
+    interval: Duration,
+    next_poll: Instant,
+    failing: bool,
+    /// A poll is running; the directory is not due again until it ends
+    polling: bool,
+}
+
+/// Outcome of a poll task
+type PollResult = (PathBuf, Result<Vec<FileNode>, CoreError>);
+
+/// Poller actor - snapshot-diff change detection
+pub struct Poller {
+    commands: Receiver<PollCommand>,
+    events: Sender<Event>,
+    provider: Arc<dyn FsProvider>,
+    registry: NodeRegistry,
+    navigator: Option<Sender<NavCommand>>,
+    cache: Option<Sender<CacheCommand>>,
+    scheduler: Option<IoScheduler>,
+    config: PollerConfig,
+}
+
+impl Poller {
+    pub fn new(
+        commands: Receiver<PollCommand>,
+        events: Sender<Event>,
+        provider: Arc<dyn FsProvider>,
+        registry: NodeRegistry,
+    ) -> Self {
+        Self {
+            commands,
+            events,
+            provider,
+            registry,
+            navigator: None,
+            cache: None,
+            scheduler: None,
+            config: PollerConfig::default(),
+        }
+    }
+
+    /// Also send `NavCommand::Invalidate` for changed directories
+    pub fn with_navigator(mut self, navigator: Sender<NavCommand>) -> Self {
+        self.navigator = Some(navigator);
+        self
+    }
+
+    /// Also send `CacheCommand::Invalidate` for changed nodes
+    pub fn with_cache(mut self, cache: Sender<CacheCommand>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Take a background slot from the I/O scheduler for every poll
+    pub fn with_scheduler(mut self, scheduler: IoScheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    pub fn with_config(mut self, config: PollerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn handle_command(
+        &self,
+        dirs: &mut HashMap<PathBuf, PolledDir>,
+        activity: &mut HashMap<SessionId, Instant>,
+        cmd: PollCommand,
+    ) {
+        let now = Instant::now();
+        match cmd {
+            PollCommand::Watch(path, session) => {
+                activity.insert(session, now);
+                let dir = dirs.entry(path).or_insert_with(|| PolledDir {
+                    snapshot: None,
+                    users: HashMap::new(),
+                    interval: self.config.interval,
+                    // Take the baseline snapshot right away
+                    next_poll: now,
+                    failing: false,
+                    polling: false,
+                });
+                *dir.users.entry(session).or_default() += 1;
+            }
+            PollCommand::Unwatch(path, session) => {
+                if let Some(dir) = dirs.get_mut(&path) {
+                    if let Some(count) = dir.users.get_mut(&session) {
+                        *count -= 1;
+                        if *count == 0 {
+                            dir.users.remove(&session);
+                        }
+                    }
+                    if dir.users.is_empty() {
+                        dirs.remove(&path);
+                    }
+                }
+            }
+            PollCommand::UnwatchAll(session) => {
+                dirs.retain(|_, dir| {
+                    dir.users.remove(&session);
+                    !dir.users.is_empty()
+                });
+                activity.remove(&session);
+            }
+            PollCommand::Activity(session) => {
+                activity.insert(session, now);
+                for dir in dirs.values_mut().filter(|d| d.users.contains_key(&session)) {
+                    if dir.interval > self.config.interval {
+                        dir.interval = self.config.interval;
+                        dir.next_poll = dir.next_poll.min(now + self.config.interval);
+                    }
+                }
+            }
+        }
+    }
+
+    /// List `path` in a task of its own and report back on `done`
+    fn spawn_poll(&self, path: PathBuf, done: Sender<PollResult>) {
+        let provider = self.provider.clone();
+        let scheduler = self.scheduler.clone();
+        tokio::spawn(async move {
+            let _permit = match scheduler {
+                Some(scheduler) => {
+                    let ticket = IoTicket::new(IoClass::Background).on(provider.io_device(&path));
+                    Some(scheduler.request(ticket).granted().await)
+                }
+                None => None,
+            };
+            let result = provider.list(&path).await;
+            let _ = done.send((path, result));
+        });
+    }
+
+    /// Diff a finished poll against the last snapshot and schedule the next
+    fn finish(
+        &self,
+        path: &Path,
+        dir: &mut PolledDir,
+        result: Result<Vec<FileNode>, CoreError>,
+        activity: &HashMap<SessionId, Instant>,
+    ) {
+        dir.polling = false;
+        match result {
+            Ok(nodes) => {
+                let snapshot = Snapshot::from_nodes(&nodes);
+                if let Some(previous) = &dir.snapshot {
+                    let changes = previous.diff(&snapshot);
+                    if !changes.is_empty() {
+                        self.emit(path, dir, changes);
+                    }
+                }
+                dir.snapshot = Some(snapshot);
+                dir.failing = false;
+            }
+            Err(e) => {
+                // Report once per outage, not on every poll
+                if !dir.failing {
+                    for session in dir.users.keys() {
+                        let _ = self.events.send(Event::Error {
+                            message: format!("Failed to poll {}: {}", path.display(), e),
+                            recoverable: true,
+                            session: *session,
+                        });
+                    }
+                }
+                dir.failing = true;
+            }
+        }
+
+        let now = Instant::now();
+        let idle = dir.users.keys().all(|s| {
+            activity
+                .get(s)
+                .is_none_or(|last| now.duration_since(*last) >= self.config.idle_after)
+        });
+        dir.interval = self.config.next_interval(dir.interval, idle);
+        // Jitter only spreads polls of many directories apart, so it needs
+        // no real randomness: a `RandomState` is freshly keyed per thread
+        // and its keys advance with every call, which is enough.
+        let unit = RandomState::new().hash_one(now) as f64 / u64::MAX as f64;
+        dir.next_poll = now + self.config.jittered(dir.interval, unit);
+    }
+
+    fn emit(&self, root: &Path, dir: &PolledDir, changes: Vec<(PathBuf, FsChangeKind)>) {
+        for (path, kind) in changes {
+            let node = self.registry.clone().register(path);
+            if let Some(cache) = &self.cache {
+                let _ = cache.send(CacheCommand::Invalidate(node));
+                if let FsChangeKind::Renamed { from } = &kind {
+                    let _ = cache.send(CacheCommand::Invalidate(self.registry.clone().register(from.clone())));
+                }
+            }
+            for session in dir.users.keys() {
+                let _ = self.events.send(Event::FsChanged {
+                    node,
+                    kind: kind.clone(),
+                    session: *session,
+                });
+            }
+        }
+        if let Some(nav) = &self.navigator {
+            let _ = nav.send(NavCommand::Invalidate(self.registry.clone().register(root.to_path_buf())));
+        }
+    }
+}
+
+impl Actor for Poller {
+    async fn run(self) {
+        let mut dirs: HashMap<PathBuf, PolledDir> = HashMap::new();
+        let mut activity: HashMap<SessionId, Instant> = HashMap::new();
+        let (done_tx, done_rx) = flume::unbounded::<PollResult>();
+
+        loop {
+            let next = dirs.values().filter(|d| !d.polling).map(|d| d.next_poll).min();
+            tokio::select! {
+                cmd = self.commands.recv_async() => match cmd {
+                    Ok(cmd) => self.handle_command(&mut dirs, &mut activity, cmd),
+                    Err(_) => break,
+                },
+                // Without watched directories there is no timer at all
+                _ = tokio::time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                    let now = Instant::now();
+                    for (path, dir) in dirs.iter_mut().filter(|(_, d)| !d.polling && d.next_poll <= now) {
+                        dir.polling = true;
+                        self.spawn_poll(path.clone(), done_tx.clone());
+                    }
+                },
+                Ok((path, result)) = done_rx.recv_async() => {
+                    // Unwatched while the poll ran
+                    if let Some(dir) = dirs.get_mut(&path) {
+                        self.finish(&path, dir, result, &activity);
+                    }
+                },
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "poller"
+    }
+}
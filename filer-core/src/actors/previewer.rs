@@ -3,6 +3,7 @@ use std::path::PathBuf;
 
 use crate::actors::Actor;
 use crate::api::events::Event;
+use crate::services::diff::DiffOptions;
 use crate::services::preview::PreviewCache;
 use crate::vfs::provider::FsProvider;
 use crate::{MetadataRegistry, PreviewOptions, PreviewRegistry};
@@ -15,6 +16,12 @@ pub enum PreviewCommand {
         path: PathBuf,
         options: Option<PreviewOptions>,
     },
+    /// Diff two text files
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        options: Option<DiffOptions>,
+    },
     /// Load metadata for a file
     LoadMetadata(PathBuf),
     /// Cancel ongoing preview
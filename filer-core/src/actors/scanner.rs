@@ -1,21 +1,29 @@
 use flume::{Receiver, Sender};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::actors::Actor;
+use crate::actors::cache::{CacheCommand, ListingStamp};
+use crate::actors::navigator::NavCommand;
 use crate::api::events::Event;
-use crate::model::node::NodeId;
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeId};
 use crate::model::registry::NodeRegistry;
-use crate::model::session::SessionId;
-use crate::pipeline::{Pipeline, PipelineConfig, PipelineData};
+use crate::model::session::{SessionId, ViewId};
+use crate::pipeline::{GroupBy, Listing, ListingDelta, Pipeline, PipelineConfig};
+use crate::services::scheduler::{IoClass, IoScheduler, IoTicket};
+use crate::services::tags::TagStore;
 use crate::vfs::provider::FsProvider;
 
 /// Commands for scanner actor
 #[derive(Debug, Clone)]
 pub enum ScanCommand {
     Scan { path: PathBuf, session: SessionId, pipeline: PipelineConfig},
-    ScanNode {node: NodeId, session: SessionId, pipeline: PipelineConfig},
+    ScanNode {node: NodeId, session: SessionId, view: ViewId, pipeline: PipelineConfig},
+    /// Rescan and send a `DirectoryDelta` against the view's last listing
+    Update {node: NodeId, session: SessionId, view: ViewId, pipeline: PipelineConfig},
+    /// Cancel the scans of every view of the session
     Cancel(SessionId),
     Shutdown,
 }
@@ -42,13 +50,91 @@ impl CancellationToken {
     }
 }
 
+/// Where listings come from: the provider, fronted by the cache if any
+#[derive(Clone)]
+struct Source {
+    provider: Arc<dyn FsProvider>,
+    cache: Option<Sender<CacheCommand>>,
+    scheduler: Option<IoScheduler>,
+    tags: Option<TagStore>,
+}
+
+impl Source {
+    /// List a directory, serving it from the cache when `cached` allows
+    ///
+    /// A cached listing is only served while the directory still has the
+    /// modification time or etag it was listed at.
+    async fn list(&self, path: &Path, cached: bool, session: SessionId) -> Result<Vec<FileNode>, CoreError> {
+        let path = path.to_path_buf();
+        if let Some(cache) = &self.cache
+            && cached
+        {
+            let (tx, rx) = flume::bounded(1);
+            if cache.send(CacheCommand::GetListing(NodeId::from_path(&path), tx)).is_ok()
+                && let Ok(Some((entries, stamp))) = rx.recv_async().await
+                && self.provider.metadata(&path).await.is_ok_and(|dir| stamp.matches(&dir))
+            {
+                return Ok(self.annotate(entries));
+            }
+        }
+
+        let _permit = match &self.scheduler {
+            Some(scheduler) => {
+                let ticket = IoTicket::new(IoClass::Listing)
+                    .on(self.provider.io_device(&path))
+                    .for_session(session);
+                Some(scheduler.request(ticket).granted().await)
+            }
+            None => None,
+        };
+        // Stamped first, so a change during the listing shows next time
+        let stamp = match &self.cache {
+            Some(_) => self.provider.metadata(&path).await.ok().map(|dir| ListingStamp::of(&dir)),
+            None => None,
+        };
+        let entries = self.provider.list(&path).await?;
+        if let (Some(cache), Some(stamp)) = (&self.cache, stamp) {
+            let _ = cache.send(CacheCommand::StoreListing {
+                path,
+                entries: entries.clone(),
+                stamp,
+            });
+        }
+        Ok(self.annotate(entries))
+    }
+
+    /// Tags are read fresh for every listing, cached or not
+    fn annotate(&self, mut entries: Vec<FileNode>) -> Vec<FileNode> {
+        if let Some(tags) = &self.tags {
+            tags.annotate(&mut entries);
+        }
+        entries
+    }
+}
+
+/// Listings and scans are tracked per view; `Scan` by path is for the
+/// main view
+type ViewKey = (SessionId, ViewId);
+
+/// What a view was last sent for a directory
+struct LastListing {
+    parent: NodeId,
+    pipeline: PipelineConfig,
+    listing: Listing,
+}
+
 /// Scanner actor - handles directory traversal
 pub struct Scanner {
     commands: Receiver<ScanCommand>,
     events_sender: Sender<Event>,
     provider: Arc<dyn FsProvider>,  // Changed to Arc for sharing
+    cache: Option<Sender<CacheCommand>>,
+    scheduler: Option<IoScheduler>,
+    tags: Option<TagStore>,
+    navigator: Option<Sender<NavCommand>>,
     registry: NodeRegistry,
-    active_scans: Arc<scc::HashMap<SessionId, CancellationToken>>,
+    active_scans: Arc<scc::HashMap<ViewKey, CancellationToken>>,
+    listings: Arc<scc::HashMap<ViewKey, LastListing>>,
 }
 
 impl Scanner {
@@ -62,17 +148,65 @@ impl Scanner {
             commands,
             events_sender: events,
             provider,
+            cache: None,
+            scheduler: None,
+            tags: None,
+            navigator: None,
             registry,
             active_scans: Arc::new(scc::HashMap::new()),
+            listings: Arc::new(scc::HashMap::new()),
+        }
+    }
+
+    /// Serve listings from (and store them in) the Cache actor
+    ///
+    /// `Update` scans always go to the provider.
+    pub fn with_cache(mut self, cache: Sender<CacheCommand>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Take a listing slot from the I/O scheduler for every provider listing
+    pub fn with_scheduler(mut self, scheduler: IoScheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Fill in tags, labels and notes of listed nodes
+    ///
+    /// Only local files use extended attributes; tags of other providers'
+    /// files live in the store's database.
+    pub fn with_tags(mut self, tags: TagStore) -> Self {
+        let local = self.provider.scheme() == "file";
+        self.tags = Some(tags.with_xattrs(local));
+        self
+    }
+
+    /// Tell the Navigator what every view now shows, so selections can
+    /// follow the listing
+    pub fn with_navigator(mut self, navigator: Sender<NavCommand>) -> Self {
+        self.navigator = Some(navigator);
+        self
+    }
+
+    fn source(&self) -> Source {
+        Source {
+            provider: self.provider.clone(),
+            cache: self.cache.clone(),
+            scheduler: self.scheduler.clone(),
+            tags: self.tags.clone(),
         }
     }
 
     /// Spawn a scan task that runs concurrently
+    #[allow(clippy::too_many_arguments)]
     fn spawn_scan(
-        provider: Arc<dyn FsProvider>,
+        source: Source,
         registry: NodeRegistry,
         events_sender: Sender<Event>,
-        active_scans: Arc<scc::HashMap<SessionId, CancellationToken>>,
+        navigator: Option<Sender<NavCommand>>,
+        active_scans: Arc<scc::HashMap<ViewKey, CancellationToken>>,
+        listings: Arc<scc::HashMap<ViewKey, LastListing>>,
         path: PathBuf,
         session: SessionId,
         pipeline_config: PipelineConfig,
@@ -80,41 +214,48 @@ impl Scanner {
         tokio::spawn(async move {
             // Create and register cancellation token
             let cancel = CancellationToken::new();
+            let key = (session, ViewId::main());
             
-            // Cancel any existing scan for this session
-            if let Some((_, old)) = active_scans.remove_async(&session).await {
+            // Cancel any existing scan for this view
+            if let Some((_, old)) = active_scans.remove_async(&key).await {
                 old.cancel();
             }
-            let _ = active_scans.insert_async(session, cancel.clone()).await;
+            let _ = active_scans.insert_async(key.clone(), cancel.clone()).await;
 
             // Perform the scan
             Self::scan_directory_inner(
-                &provider,
+                &source,
                 &registry,
                 &events_sender,
+                navigator.as_ref(),
+                &listings,
                 &path,
-                session,
+                key.clone(),
                 pipeline_config,
                 &cancel,
             ).await;
 
             // Clean up
-            let _ = active_scans.remove_async(&session).await;
+            let _ = active_scans.remove_async(&key).await;
         });
     }
 
     /// Inner scan logic (static, doesn't need &self)
+    #[allow(clippy::too_many_arguments)]
     async fn scan_directory_inner(
-        provider: &Arc<dyn FsProvider>,
+        source: &Source,
         registry: &NodeRegistry,
         events_sender: &Sender<Event>,
+        navigator: Option<&Sender<NavCommand>>,
+        listings: &scc::HashMap<ViewKey, LastListing>,
         path: &PathBuf,
-        session: SessionId,
+        key: ViewKey,
         pipeline_config: PipelineConfig,
         cancel: &CancellationToken,
     ) {
+        let session = key.0;
         // 1. List directory
-        let entries = match provider.list(path).await {
+        let entries = match source.list(path, true, session).await {
             Ok(entries) => entries,
             Err(e) => {
                 let _ = events_sender
@@ -139,16 +280,7 @@ impl Scanner {
 
         // 4. Execute pipeline
         let pipeline = Pipeline::from_config(&pipeline_config);
-        let processed = pipeline.execute(entries);
-
-        let final_entries = match processed {
-            PipelineData::Flat(file_nodes) => file_nodes,
-            PipelineData::Grouped(grouped_nodes) => grouped_nodes
-                .groups
-                .into_iter()
-                .flat_map(|g| g.nodes)
-                .collect(),
-        };
+        let listing = Listing::from_data(pipeline.execute(entries));
 
         // 5. Check cancellation again
         if cancel.is_cancelled() {
@@ -156,68 +288,73 @@ impl Scanner {
         }
 
         // 6. Send result
-        let _ = events_sender
-            .send_async(Event::DirectoryLoaded {
-                parent: parent_id,
-                path: path.to_path_buf(),
-                entries: final_entries,
-                session,
-            })
-            .await;
+        Self::publish(listings, events_sender, navigator, parent_id, path, listing, pipeline_config, key, false).await;
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn spawn_scan_node(
-        provider: Arc<dyn FsProvider>,
+        source: Source,
         registry: NodeRegistry,
         events_sender: Sender<Event>,
-        active_scans: Arc<scc::HashMap<SessionId, CancellationToken>>,
+        navigator: Option<Sender<NavCommand>>,
+        active_scans: Arc<scc::HashMap<ViewKey, CancellationToken>>,
+        listings: Arc<scc::HashMap<ViewKey, LastListing>>,
         node: NodeId,
-        session: SessionId,
+        key: ViewKey,
         pipeline_config: PipelineConfig,
+        incremental: bool,
     ) {
         tokio::spawn(async move {
             // Create and register cancellation token
             let cancel = CancellationToken::new();
             
-            // Cancel any existing scan for this session
-            if let Some((_, old)) = active_scans.remove_async(&session).await {
+            // Cancel any existing scan for this view
+            if let Some((_, old)) = active_scans.remove_async(&key).await {
                 old.cancel();
             }
-            let _ = active_scans.insert_async(session, cancel.clone()).await;
+            let _ = active_scans.insert_async(key.clone(), cancel.clone()).await;
 
             // Perform the scan
             Self::scan_directory_inner_node(
-                &provider,
+                &source,
                 &registry,
                 &events_sender,
+                navigator.as_ref(),
+                &listings,
                 node,
-                session,
+                key.clone(),
                 pipeline_config,
+                incremental,
                 &cancel,
             ).await;
 
             // Clean up
-            let _ = active_scans.remove_async(&session).await;
+            let _ = active_scans.remove_async(&key).await;
         });
     }
 
     /// Inner scan logic (static, doesn't need &self)
+    #[allow(clippy::too_many_arguments)]
     async fn scan_directory_inner_node(
-        provider: &Arc<dyn FsProvider>,
+        source: &Source,
         registry: &NodeRegistry,
         events_sender: &Sender<Event>,
+        navigator: Option<&Sender<NavCommand>>,
+        listings: &scc::HashMap<ViewKey, LastListing>,
         node: NodeId,
-        session: SessionId,
+        key: ViewKey,
         pipeline_config: PipelineConfig,
+        incremental: bool,
         cancel: &CancellationToken,
     ) {
+        let session = key.0;
         let Some(path) = registry.resolve(node) else {
             debug_assert!(true);
             let _ = events_sender.send(Event::Error { message: format!("Unable to resolve ID: {node:?}"), recoverable: false, session });
             return;
         };
         // 1. List directory
-        let entries = match provider.list(&path).await {
+        let entries = match source.list(&path, !incremental, session).await {
             Ok(entries) => entries,
             Err(e) => {
                 let _ = events_sender
@@ -242,16 +379,7 @@ impl Scanner {
 
         // 4. Execute pipeline
         let pipeline = Pipeline::from_config(&pipeline_config);
-        let processed = pipeline.execute(entries);
-
-        let final_entries = match processed {
-            PipelineData::Flat(file_nodes) => file_nodes,
-            PipelineData::Grouped(grouped_nodes) => grouped_nodes
-                .groups
-                .into_iter()
-                .flat_map(|g| g.nodes)
-                .collect(),
-        };
+        let listing = Listing::from_data(pipeline.execute(entries));
 
         // 5. Check cancellation again
         if cancel.is_cancelled() {
@@ -259,20 +387,77 @@ impl Scanner {
         }
 
         // 6. Send result
-        let _ = events_sender
-            .send_async(Event::DirectoryLoaded {
-                parent: parent_id,
+        Self::publish(listings, events_sender, navigator, parent_id, &path, listing, pipeline_config, key, incremental).await;
+    }
+
+    /// Send a processed listing, as a delta when the view already has the
+    /// same directory under the same pipeline
+    #[allow(clippy::too_many_arguments)]
+    async fn publish(
+        listings: &scc::HashMap<ViewKey, LastListing>,
+        events_sender: &Sender<Event>,
+        navigator: Option<&Sender<NavCommand>>,
+        parent: NodeId,
+        path: &Path,
+        listing: Listing,
+        pipeline: PipelineConfig,
+        key: ViewKey,
+        incremental: bool,
+    ) {
+        // A delta cannot place a node that is in several tag groups
+        let by_tag = pipeline.group.is_some_and(|group| group.by == GroupBy::Tag);
+        let delta = match listings.get_async(&key).await {
+            Some(last) if incremental && !by_tag && last.parent == parent && last.pipeline == pipeline => {
+                Some(ListingDelta::between(&last.listing, &listing))
+            }
+            _ => None,
+        };
+
+        let event = match delta {
+            Some(delta) if delta.is_empty() => None,
+            Some(delta) => Some(Event::DirectoryDelta {
+                parent,
+                added: delta.added,
+                removed: delta.removed,
+                modified: delta.modified,
+                moved: delta.moved,
+                session: key.0,
+                view: key.1.clone(),
+            }),
+            None => Some(Event::DirectoryLoaded {
+                parent,
                 path: path.to_path_buf(),
-                entries: final_entries,
-                session,
-            })
+                entries: listing.nodes().to_vec(),
+                session: key.0,
+                view: key.1.clone(),
+            }),
+        };
+
+        if let Some(navigator) = navigator {
+            let _ = navigator.send(NavCommand::Listed {
+                session: key.0,
+                view: key.1.clone(),
+                parent,
+                entries: listing.nodes(),
+            });
+        }
+        let _ = listings
+            .upsert_async(key, LastListing { parent, pipeline, listing })
             .await;
+        if let Some(event) = event {
+            let _ = events_sender.send_async(event).await;
+        }
     }
 
     async fn cancel_scan(&self, session: SessionId) {
-        if let Some((_, token)) = self.active_scans.remove_async(&session).await {
-            token.cancel();
-        }
+        self.active_scans
+            .retain_async(|(s, _), token| {
+                if *s == session {
+                    token.cancel();
+                }
+                *s != session
+            })
+            .await;
     }
 }
 
@@ -283,24 +468,43 @@ impl Actor for Scanner {
                 Ok(ScanCommand::Scan { path, session, pipeline }) => {
                     // Clone what we need and spawn - doesn't block the command loop
                     Self::spawn_scan(
-                        self.provider.clone(),
+                        self.source(),
                         self.registry.clone(),
                         self.events_sender.clone(),
+                        self.navigator.clone(),
                         self.active_scans.clone(),
+                        self.listings.clone(),
                         path,
                         session,
                         pipeline,
                     );
                 }
-                Ok(ScanCommand::ScanNode { node, session, pipeline }) => {
+                Ok(ScanCommand::ScanNode { node, session, view, pipeline }) => {
                     Self::spawn_scan_node(
-                        self.provider.clone(),
+                        self.source(),
                         self.registry.clone(),
                         self.events_sender.clone(),
+                        self.navigator.clone(),
                         self.active_scans.clone(),
+                        self.listings.clone(),
                         node,
-                        session,
+                        (session, view),
+                        pipeline,
+                        false,
+                    );
+                }
+                Ok(ScanCommand::Update { node, session, view, pipeline }) => {
+                    Self::spawn_scan_node(
+                        self.source(),
+                        self.registry.clone(),
+                        self.events_sender.clone(),
+                        self.navigator.clone(),
+                        self.active_scans.clone(),
+                        self.listings.clone(),
+                        node,
+                        (session, view),
                         pipeline,
+                        true,
                     );
                 }
                 Ok(ScanCommand::Cancel(session)) => {
@@ -0,0 +1,263 @@
+//! Syncer actor - runs two-way syncs of folder pairs on their triggers
+//!
+//! Pairs with `watch` are synced once changes under either root have
+//! settled; pairs with an `interval` are synced that often. Each run is
+//! sent to the operations actor as an `OpCommand::Bisync` job. Scheduled
+//! runs report to the default session.
+//!
+//! Roots of watched pairs are watched through the watcher, which sends
+//! their changes back as `SyncerCommand::Changed`. Changes while a run
+//! writes, and for the settle period after it, are the run's own and do
+//! not trigger another; the operations actor reports the end of runs as
+//! `SyncerCommand::Finished`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use flume::{Receiver, Sender};
+use tokio::time::Instant;
+
+use crate::actors::Actor;
+use crate::actors::operations::OpCommand;
+#[cfg(target_os = "linux")]
+use crate::actors::watcher::WatchCommand;
+use crate::api::events::Event;
+use crate::model::session::SessionId;
+use crate::services::bisync::SyncPair;
+
+/// Commands for syncer actor
+#[derive(Debug, Clone)]
+pub enum SyncerCommand {
+    /// Add a pair, or replace the one with the same name
+    Add(Box<SyncPair>),
+    Remove(String),
+    /// Something changed at this path
+    Changed(PathBuf),
+    /// A run of the pair that writes has ended
+    Finished(String),
+    /// Sync a pair now
+    Run {
+        name: String,
+        dry_run: bool,
+        session: SessionId,
+    },
+}
+
+/// Syncer timing configuration
+#[derive(Debug, Clone, Copy)]
+pub struct SyncerConfig {
+    /// Quiet period after the last change before a watched pair syncs
+    ///
+    /// Also how long a run's own changes are ignored after it ends, so it
+    /// must be longer than the watcher's debounce.
+    pub settle: Duration,
+}
+
+impl Default for SyncerConfig {
+    fn default() -> Self {
+        Self {
+            settle: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A pair and when it is due
+struct Scheduled {
+    pair: SyncPair,
+    /// Latest change seen under either root since the last run
+    changed: Option<Instant>,
+    next_run: Option<Instant>,
+    /// A run that writes was started and has not finished
+    running: bool,
+    /// Changes before this are echoes of the last run
+    quiet_until: Option<Instant>,
+}
+
+impl Scheduled {
+    fn start(&mut self) {
+        self.running = true;
+        self.changed = None;
+    }
+
+    /// Whether a change now would be the pair's own doing
+    fn echoes(&self, now: Instant) -> bool {
+        self.running || self.quiet_until.is_some_and(|quiet| now < quiet)
+    }
+
+    fn due(&self, settle: Duration) -> Option<Instant> {
+        let settled = self.changed.map(|changed| changed + settle);
+        match (settled, self.next_run) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Syncer actor - triggers two-way syncs
+pub struct Syncer {
+    commands: Receiver<SyncerCommand>,
+    events: Sender<Event>,
+    operations: Sender<OpCommand>,
+    #[cfg(target_os = "linux")]
+    watcher: Option<Sender<WatchCommand>>,
+    /// Holds the syncer's watches
+    session: SessionId,
+    config: SyncerConfig,
+}
+
+impl Syncer {
+    pub fn new(
+        commands: Receiver<SyncerCommand>,
+        events: Sender<Event>,
+        operations: Sender<OpCommand>,
+    ) -> Self {
+        Self {
+            commands,
+            events,
+            operations,
+            #[cfg(target_os = "linux")]
+            watcher: None,
+            session: SessionId::new(),
+            config: SyncerConfig::default(),
+        }
+    }
+
+    /// Watch the roots of pairs with `watch`; the watcher needs
+    /// `with_syncer` to report their changes back
+    #[cfg(target_os = "linux")]
+    pub fn with_watcher(mut self, watcher: Sender<WatchCommand>) -> Self {
+        self.watcher = Some(watcher);
+        self
+    }
+
+    pub fn with_config(mut self, config: SyncerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn handle_command(&self, pairs: &mut HashMap<String, Scheduled>, cmd: SyncerCommand) {
+        let now = Instant::now();
+        match cmd {
+            SyncerCommand::Add(pair) => {
+                let pair = *pair;
+                if let Some(old) = pairs.remove(&pair.name) {
+                    self.watch(&old.pair, false);
+                }
+                self.watch(&pair, true);
+                let next_run = pair.interval.map(|interval| now + interval);
+                pairs.insert(
+                    pair.name.clone(),
+                    Scheduled {
+                        pair,
+                        changed: None,
+                        next_run,
+                        running: false,
+                        quiet_until: None,
+                    },
+                );
+            }
+            SyncerCommand::Remove(name) => {
+                if let Some(old) = pairs.remove(&name) {
+                    self.watch(&old.pair, false);
+                }
+            }
+            SyncerCommand::Changed(path) => {
+                for scheduled in pairs.values_mut() {
+                    let pair = &scheduled.pair;
+                    if pair.watch
+                        && (path.starts_with(&pair.left) || path.starts_with(&pair.right))
+                        && !scheduled.echoes(now)
+                    {
+                        scheduled.changed = Some(now);
+                    }
+                }
+            }
+            SyncerCommand::Finished(name) => {
+                if let Some(scheduled) = pairs.get_mut(&name).filter(|s| s.running) {
+                    scheduled.running = false;
+                    scheduled.quiet_until = Some(now + self.config.settle);
+                }
+            }
+            SyncerCommand::Run {
+                name,
+                dry_run,
+                session,
+            } => match pairs.get_mut(&name) {
+                Some(scheduled) => {
+                    if !dry_run {
+                        scheduled.start();
+                    }
+                    self.start(&scheduled.pair, dry_run, session);
+                }
+                None => {
+                    let _ = self.events.send(Event::Error {
+                        message: format!("No sync pair \"{name}\""),
+                        recoverable: true,
+                        session,
+                    });
+                }
+            },
+        }
+    }
+
+    fn start(&self, pair: &SyncPair, dry_run: bool, session: SessionId) {
+        let _ = self.operations.send(OpCommand::Bisync {
+            pair: pair.clone(),
+            dry_run,
+            session,
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    fn watch(&self, pair: &SyncPair, watch: bool) {
+        let Some(watcher) = self.watcher.as_ref().filter(|_| pair.watch) else {
+            return;
+        };
+        for root in [&pair.left, &pair.right] {
+            let cmd = match watch {
+                true => WatchCommand::Watch(root.clone(), self.session),
+                false => WatchCommand::Unwatch(root.clone(), self.session),
+            };
+            let _ = watcher.send(cmd);
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn watch(&self, _pair: &SyncPair, _watch: bool) {}
+}
+
+impl Actor for Syncer {
+    async fn run(self) {
+        let mut pairs: HashMap<String, Scheduled> = HashMap::new();
+
+        loop {
+            let next = pairs
+                .values()
+                .filter_map(|s| s.due(self.config.settle))
+                .min();
+            tokio::select! {
+                cmd = self.commands.recv_async() => match cmd {
+                    Ok(cmd) => self.handle_command(&mut pairs, cmd),
+                    Err(_) => break,
+                },
+                // Without pending changes or intervals there is no timer
+                _ = tokio::time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                    let now = Instant::now();
+                    for scheduled in pairs.values_mut() {
+                        if scheduled.due(self.config.settle).is_none_or(|due| due > now) {
+                            continue;
+                        }
+                        scheduled.start();
+                        self.start(&scheduled.pair, false, SessionId::DEFAULT);
+                        scheduled.next_run = scheduled.pair.interval.map(|interval| now + interval);
+                    }
+                },
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "syncer"
+    }
+}
@@ -0,0 +1,224 @@
+//! Actor wiring - spawns the actors and connects them to each other
+//!
+//! Every actor gets the senders of the actors it reports to: change
+//! detection invalidates the cache and the navigator, the scanner tells
+//! the navigator what each view shows, and the navigator hands transfers
+//! to the operations actor. Local directories are watched with inotify;
+//! directories of providers that cannot watch are polled. With a `Journal`
+//! both file operations and trashing can be undone. Listings, polls and
+//! jobs share one `IoScheduler`, so browsing stays ahead of big copies.
+//! The syncer watches the roots of its pairs through the watcher and
+//! hears from the operations actor when a run is over. Batch renames read
+//! EXIF dates and audio tags through a `MetadataRegistry` if one is given.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use flume::Sender;
+
+use crate::actors::Actor;
+use crate::actors::cache::Cache;
+use crate::actors::navigator::{NavCommand, Navigator};
+use crate::actors::operations::{OpCommand, Operations};
+use crate::actors::poller::{PollCommand, Poller, PollerConfig};
+use crate::actors::scanner::{ScanCommand, Scanner};
+use crate::actors::syncer::{Syncer, SyncerCommand, SyncerConfig};
+use crate::actors::trasher::{TrashCommand, Trasher};
+#[cfg(target_os = "linux")]
+use crate::actors::watcher::{WatchCommand, Watcher};
+use crate::api::events::Event;
+use crate::model::registry::NodeRegistry;
+use crate::services::journal::Journal;
+use crate::services::metadata::MetadataRegistry;
+use crate::services::scheduler::{IoScheduler, SchedulerConfig};
+use crate::services::trash::TrashBin;
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::FsProvider;
+
+/// Approximate bytes the cache may hold
+const CACHE_BYTES: usize = 64 << 20;
+
+/// Command channels of the running actors that clients talk to
+#[derive(Clone)]
+pub struct Actors {
+    pub navigator: Sender<NavCommand>,
+    pub operations: Sender<OpCommand>,
+    pub scanner: Sender<ScanCommand>,
+    pub poller: Sender<PollCommand>,
+    pub syncer: Sender<SyncerCommand>,
+    #[cfg(target_os = "linux")]
+    pub watcher: Sender<WatchCommand>,
+    /// Only spawned with a trash bin
+    pub trasher: Option<Sender<TrashCommand>>,
+    /// For other I/O such as thumbnails to queue with the actors'
+    pub scheduler: IoScheduler,
+}
+
+/// Builds the actors around one provider and spawns them
+pub struct System {
+    events: Sender<Event>,
+    registry: NodeRegistry,
+    provider: Arc<dyn FsProvider>,
+    poller: PollerConfig,
+    syncer: SyncerConfig,
+    sync_states: Option<PathBuf>,
+    journal: Option<Journal>,
+    trash: Option<TrashBin>,
+    metadata: Option<Arc<MetadataRegistry>>,
+    scheduler: IoScheduler,
+}
+
+impl System {
+    pub fn new(events: Sender<Event>, registry: NodeRegistry) -> Self {
+        Self {
+            events,
+            provider: Arc::new(LocalFs::new(registry.clone())),
+            registry,
+            poller: PollerConfig::default(),
+            syncer: SyncerConfig::default(),
+            sync_states: None,
+            journal: None,
+            trash: None,
+            metadata: None,
+            scheduler: IoScheduler::new(SchedulerConfig::default()),
+        }
+    }
+
+    pub fn with_poller_config(mut self, config: PollerConfig) -> Self {
+        self.poller = config;
+        self
+    }
+
+    pub fn with_syncer_config(mut self, config: SyncerConfig) -> Self {
+        self.syncer = config;
+        self
+    }
+
+    /// Keep the state of sync pairs in `dir` instead of the user's state
+    /// directory
+    pub fn with_sync_states(mut self, dir: PathBuf) -> Self {
+        self.sync_states = Some(dir);
+        self
+    }
+
+    /// Share `scheduler` instead of one with the default limits
+    pub fn with_scheduler(mut self, scheduler: IoScheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Record file operations and trashing so they can be undone
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Spawn a trasher moving nodes into `bin`
+    pub fn with_trash(mut self, bin: TrashBin) -> Self {
+        self.trash = Some(bin);
+        self
+    }
+
+    /// Fill batch rename tokens such as `{taken}` and `{artist}` from `metadata`
+    pub fn with_metadata(mut self, metadata: Arc<MetadataRegistry>) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Spawn every actor on the current runtime
+    pub fn spawn(self) -> Actors {
+        let (cache_tx, cache_rx) = flume::unbounded();
+        let (scan_tx, scan_rx) = flume::unbounded();
+        let (nav_tx, nav_rx) = flume::unbounded();
+        let (op_tx, op_rx) = flume::unbounded();
+        let (poll_tx, poll_rx) = flume::unbounded();
+        let (sync_tx, sync_rx) = flume::unbounded();
+        #[cfg(target_os = "linux")]
+        let (watch_tx, watch_rx) = flume::unbounded();
+        let journal = match (self.journal, &self.trash) {
+            (Some(journal), Some(bin)) => Some(journal.with_trash(bin.clone())),
+            (journal, _) => journal,
+        };
+
+        tokio::spawn(Cache::new(cache_rx, self.registry.clone(), CACHE_BYTES).run());
+        let scanner = Scanner::new(
+            scan_rx,
+            self.events.clone(),
+            self.provider.clone(),
+            self.registry.clone(),
+        )
+        .with_cache(cache_tx.clone())
+        .with_navigator(nav_tx.clone())
+        .with_scheduler(self.scheduler.clone());
+        tokio::spawn(scanner.run());
+        let navigator = Navigator::new(
+            nav_rx,
+            self.events.clone(),
+            scan_tx.clone(),
+            self.registry.clone(),
+        )
+        .with_operations(op_tx.clone())
+        .with_poller(poll_tx.clone());
+        tokio::spawn(navigator.run());
+        let mut operations = Operations::new(op_rx, self.events.clone(), self.registry.clone())
+            .with_cache(cache_tx.clone())
+            .with_scheduler(self.scheduler.clone())
+            .with_syncer(sync_tx.clone());
+        if let Some(journal) = &journal {
+            operations = operations.with_journal(journal.clone());
+        }
+        if let Some(dir) = self.sync_states {
+            operations = operations.with_sync_states(dir);
+        }
+        if let Some(metadata) = self.metadata {
+            operations = operations.with_metadata(metadata);
+        }
+        tokio::spawn(operations.run());
+        let trasher = self.trash.map(|bin| {
+            let (trash_tx, trash_rx) = flume::unbounded();
+            let mut trasher =
+                Trasher::new(trash_rx, self.events.clone(), bin, self.registry.clone());
+            if let Some(journal) = &journal {
+                trasher = trasher.with_journal(journal.clone());
+            }
+            tokio::spawn(trasher.run());
+            trash_tx
+        });
+        let poller = Poller::new(
+            poll_rx,
+            self.events.clone(),
+            self.provider.clone(),
+            self.registry.clone(),
+        )
+        .with_cache(cache_tx.clone())
+        .with_navigator(nav_tx.clone())
+        .with_scheduler(self.scheduler.clone())
+        .with_config(self.poller);
+        tokio::spawn(poller.run());
+        #[cfg(target_os = "linux")]
+        {
+            let watcher = Watcher::new(watch_rx, self.events.clone(), self.registry.clone())
+                .with_cache(cache_tx.clone())
+                .with_navigator(nav_tx.clone())
+                .with_syncer(sync_tx.clone());
+            tokio::spawn(watcher.run());
+        }
+        let syncer =
+            Syncer::new(sync_rx, self.events.clone(), op_tx.clone()).with_config(self.syncer);
+        #[cfg(target_os = "linux")]
+        let syncer = syncer.with_watcher(watch_tx.clone());
+        tokio::spawn(syncer.run());
+
+        Actors {
+            navigator: nav_tx,
+            operations: op_tx,
+            scanner: scan_tx,
+            poller: poll_tx,
+            syncer: sync_tx,
+            #[cfg(target_os = "linux")]
+            watcher: watch_tx,
+            trasher,
+            scheduler: self.scheduler,
+        }
+    }
+}
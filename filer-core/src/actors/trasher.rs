@@ -0,0 +1,235 @@
+//! Trasher actor - moves nodes to the trash and manages its contents
+//!
+//! Trash operations are blocking filesystem calls, so commands run one at
+//! a time on the blocking pool and report back through `Event`s. With a
+//! `Journal`, trashing is recorded so it can be undone.
+
+use std::path::PathBuf;
+
+use flume::{Receiver, Sender};
+
+use crate::actors::Actor;
+use crate::actors::operations::history_changed;
+use crate::api::events::{Event, OperationKind, TrashEntry};
+use crate::model::node::NodeId;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::journal::{Journal, JournalOp, Relocation};
+use crate::services::trash::{RestoreConflict, TrashBin};
+
+/// Commands for trasher actor
+#[derive(Debug, Clone)]
+pub enum TrashCommand {
+    /// Move nodes into the trash
+    Trash {
+        nodes: Vec<NodeId>,
+        session: SessionId,
+    },
+    /// List everything in the trash
+    List(SessionId),
+    /// Restore trashed items (NodeIds of their `trash://` entries)
+    Restore {
+        items: Vec<NodeId>,
+        conflict: RestoreConflict,
+        session: SessionId,
+    },
+    /// Permanently delete all items, or only those older than N days
+    Empty {
+        older_than_days: Option<u32>,
+        session: SessionId,
+    },
+}
+
+/// Trasher actor - FreeDesktop trash operations
+pub struct Trasher {
+    commands: Receiver<TrashCommand>,
+    events: Sender<Event>,
+    bin: TrashBin,
+    registry: NodeRegistry,
+    journal: Option<Journal>,
+}
+
+impl Trasher {
+    pub fn new(
+        commands: Receiver<TrashCommand>,
+        events: Sender<Event>,
+        bin: TrashBin,
+        registry: NodeRegistry,
+    ) -> Self {
+        Self {
+            commands,
+            events,
+            bin,
+            registry,
+            journal: None,
+        }
+    }
+
+    /// Record trashing so it can be undone
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    fn handle_command(
+        cmd: TrashCommand,
+        bin: &TrashBin,
+        registry: &NodeRegistry,
+        journal: Option<&Journal>,
+        events: &Sender<Event>,
+    ) {
+        match cmd {
+            TrashCommand::Trash { nodes, session } => {
+                let mut affected = Vec::new();
+                let mut trashed = Vec::new();
+                let mut success = true;
+                for node in nodes {
+                    let result = registry
+                        .resolve(node)
+                        .ok_or_else(|| format!("Unable to resolve ID: {node:?}"))
+                        .and_then(|path| bin.trash(&path).map_err(|e| e.to_string()));
+                    match result {
+                        Ok(item) => {
+                            registry.unregister(node);
+                            trashed.push((item.info.original_path.clone(), item.files_path()));
+                            affected.push(registry.clone().register(item.files_path()));
+                        }
+                        Err(message) => {
+                            success = false;
+                            Self::error(events, message, session);
+                        }
+                    }
+                }
+                if let Some(journal) = journal.filter(|_| !trashed.is_empty()) {
+                    Self::record(journal, trashed, events, session);
+                }
+                let _ = events.send(Event::OperationComplete {
+                    operation: OperationKind::Delete,
+                    success,
+                    affected,
+                    session,
+                });
+            }
+            TrashCommand::List(session) => {
+                let entries = bin
+                    .list()
+                    .into_iter()
+                    .map(|item| TrashEntry {
+                        node: registry.clone().register(item.files_path()),
+                        name: item.name,
+                        original_path: item.info.original_path,
+                        deleted_at: item.info.deletion_date,
+                        size: item.size,
+                        is_dir: item.is_dir,
+                    })
+                    .collect();
+                let _ = events.send(Event::TrashListed { entries, session });
+            }
+            TrashCommand::Restore {
+                items,
+                conflict,
+                session,
+            } => {
+                let mut affected = Vec::new();
+                let mut success = true;
+                for id in items {
+                    let result = registry
+                        .resolve(id)
+                        .and_then(|path| bin.find(&path))
+                        .ok_or_else(|| format!("Not in trash: {id:?}"))
+                        .and_then(|item| bin.restore(&item, conflict).map_err(|e| e.to_string()));
+                    match result {
+                        Ok(restored) => {
+                            registry.unregister(id);
+                            affected.push(registry.clone().register(restored));
+                        }
+                        Err(message) => {
+                            success = false;
+                            Self::error(events, message, session);
+                        }
+                    }
+                }
+                let _ = events.send(Event::OperationComplete {
+                    operation: OperationKind::Restore,
+                    success,
+                    affected,
+                    session,
+                });
+            }
+            TrashCommand::Empty {
+                older_than_days,
+                session,
+            } => {
+                let purge = match older_than_days {
+                    Some(days) => bin.purge_older_than(days),
+                    None => bin.empty(),
+                };
+                let success = purge.errors.is_empty();
+                for e in purge.errors {
+                    Self::error(events, e.to_string(), session);
+                }
+                let affected = purge
+                    .erased
+                    .iter()
+                    .filter_map(|item| registry.get_id(&item.files_path()))
+                    .inspect(|id| {
+                        registry.unregister(*id);
+                    })
+                    .collect();
+                let _ = events.send(Event::OperationComplete {
+                    operation: OperationKind::EmptyTrash,
+                    success,
+                    affected,
+                    session,
+                });
+            }
+        }
+    }
+
+    fn record(
+        journal: &Journal,
+        trashed: Vec<(PathBuf, PathBuf)>,
+        events: &Sender<Event>,
+        session: SessionId,
+    ) {
+        let result = trashed
+            .into_iter()
+            .map(|(from, to)| Relocation::new(from, to))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|items| journal.record(session, JournalOp::Trash(items)));
+        match result {
+            Ok(_) => {
+                let _ = events.send(history_changed(journal, session));
+            }
+            Err(e) => Self::error(events, format!("Not recorded for undo: {e}"), session),
+        }
+    }
+
+    fn error(events: &Sender<Event>, message: String, session: SessionId) {
+        let _ = events.send(Event::Error {
+            message,
+            recoverable: true,
+            session,
+        });
+    }
+}
+
+impl Actor for Trasher {
+    async fn run(self) {
+        while let Ok(cmd) = self.commands.recv_async().await {
+            let bin = self.bin.clone();
+            let registry = self.registry.clone();
+            let journal = self.journal.clone();
+            let events = self.events.clone();
+            // One at a time, so a listing or an emptying sees every trashing sent before it
+            let _ = tokio::task::spawn_blocking(move || {
+                Self::handle_command(cmd, &bin, &registry, journal.as_ref(), &events)
+            })
+            .await;
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "trasher"
+    }
+}
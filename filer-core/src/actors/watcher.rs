@@ -1,35 +1,501 @@
-use std::path::PathBuf;
+//! Watcher actor - monitors filesystem changes with inotify
+//!
+//! - Directories are watched recursively; new subdirectories are picked up
+//! - Raw events are debounced and coalesced per path
+//! - IN_MOVED_FROM/IN_MOVED_TO pairs become `FsChangeKind::Renamed`
+//! - Queue overflow produces `FsChangeKind::Rescan` for every watched root
+//! - Watches are reference-counted per session
+
+use std::collections::{HashMap, HashSet};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
 use flume::Sender;
+use tokio::io::unix::AsyncFd;
 
 use crate::actors::Actor;
+use crate::actors::cache::CacheCommand;
+use crate::actors::navigator::NavCommand;
+use crate::actors::syncer::SyncerCommand;
 use crate::api::events::Event;
+use crate::model::fs_change::FsChangeKind;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::watch::{Debouncer, Inotify, RawEvent, WatchDescriptor};
 
 /// Commands for watcher actor
 #[derive(Debug, Clone)]
 pub enum WatchCommand {
-    Watch(PathBuf),
-    Unwatch(PathBuf),
-    UnwatchAll,
+    Watch(PathBuf, SessionId),
+    Unwatch(PathBuf, SessionId),
+    /// Drop every watch held by a session (e.g. on session destroy)
+    UnwatchAll(SessionId),
+}
+
+/// Watcher timing configuration
+#[derive(Debug, Clone, Copy)]
+pub struct WatcherConfig {
+    /// Quiet period before a change is reported
+    pub debounce: Duration,
+    /// Upper bound on how long a continuously changing path is held back
+    pub max_delay: Duration,
+    /// How long an IN_MOVED_FROM waits for its IN_MOVED_TO
+    pub move_timeout: Duration,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            move_timeout: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Pending IN_MOVED_FROM waiting for its pair
+struct PendingMove {
+    from: PathBuf,
+    is_dir: bool,
+    at: Instant,
+}
+
+/// Inotify bookkeeping: watch descriptors, roots and their users
+struct WatchTable {
+    inotify: Inotify,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    by_path: HashMap<PathBuf, WatchDescriptor>,
+    /// Watched roots -> sessions -> reference count
+    roots: HashMap<PathBuf, HashMap<SessionId, usize>>,
+    moves: HashMap<u32, PendingMove>,
+    debouncer: Debouncer,
+    config: WatcherConfig,
+}
+
+impl WatchTable {
+    fn new(inotify: Inotify, config: WatcherConfig) -> Self {
+        Self {
+            inotify,
+            dirs: HashMap::new(),
+            by_path: HashMap::new(),
+            roots: HashMap::new(),
+            moves: HashMap::new(),
+            debouncer: Debouncer::new(config.debounce, config.max_delay),
+            config,
+        }
+    }
+
+    fn watch(&mut self, root: PathBuf, session: SessionId) -> std::io::Result<()> {
+        if !self.roots.contains_key(&root) {
+            self.add_tree(&root)?;
+        }
+        *self.roots.entry(root).or_default().entry(session).or_default() += 1;
+        Ok(())
+    }
+
+    fn unwatch(&mut self, root: &Path, session: SessionId) {
+        let Some(users) = self.roots.get_mut(root) else {
+            return;
+        };
+        if let Some(count) = users.get_mut(&session) {
+            *count -= 1;
+            if *count == 0 {
+                users.remove(&session);
+            }
+        }
+        if users.is_empty() {
+            self.roots.remove(root);
+            self.prune(root);
+        }
+    }
+
+    fn unwatch_all(&mut self, session: SessionId) {
+        let roots: Vec<PathBuf> = self
+            .roots
+            .iter()
+            .filter(|(_, users)| users.contains_key(&session))
+            .map(|(root, _)| root.clone())
+            .collect();
+        for root in roots {
+            if let Some(users) = self.roots.get_mut(&root) {
+                users.remove(&session);
+                if users.is_empty() {
+                    self.roots.remove(&root);
+                    self.prune(&root);
+                }
+            }
+        }
+    }
+
+    /// Watch `dir` and all of its subdirectories
+    fn add_tree(&mut self, dir: &Path) -> std::io::Result<()> {
+        if !self.by_path.contains_key(dir) {
+            let wd = self.inotify.add_watch(dir, Inotify::DIR_MASK)?;
+            self.dirs.insert(wd, dir.to_path_buf());
+            self.by_path.insert(dir.to_path_buf(), wd);
+        }
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                    // Subdirectories may vanish while we walk; that is fine
+                    let _ = self.add_tree(&entry.path());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove watches under `prefix` that no remaining root covers
+    fn prune(&mut self, prefix: &Path) {
+        let stale: Vec<PathBuf> = self
+            .by_path
+            .keys()
+            .filter(|p| p.starts_with(prefix))
+            .filter(|p| !self.roots.keys().any(|root| p.starts_with(root)))
+            .cloned()
+            .collect();
+        for path in stale {
+            if let Some(wd) = self.by_path.remove(&path) {
+                self.dirs.remove(&wd);
+                let _ = self.inotify.rm_watch(wd);
+            }
+        }
+    }
+
+    /// Forget bookkeeping for a directory tree the kernel no longer reports
+    fn forget_tree(&mut self, prefix: &Path) {
+        self.by_path.retain(|p, wd| {
+            let keep = !p.starts_with(prefix);
+            if !keep {
+                self.dirs.remove(wd);
+            }
+            keep
+        });
+    }
+
+    /// Rewrite watch paths after a directory was renamed inside the tree
+    fn rekey_tree(&mut self, from: &Path, to: &Path) {
+        let moved: Vec<(PathBuf, WatchDescriptor)> = self
+            .by_path
+            .iter()
+            .filter(|(p, _)| p.starts_with(from))
+            .map(|(p, wd)| (p.clone(), *wd))
+            .collect();
+        for (old, wd) in moved {
+            let new = to.join(old.strip_prefix(from).unwrap_or(Path::new("")));
+            self.by_path.remove(&old);
+            self.by_path.insert(new.clone(), wd);
+            self.dirs.insert(wd, new);
+        }
+    }
+
+    fn on_event(&mut self, ev: RawEvent, now: Instant) {
+        if ev.has(libc::IN_Q_OVERFLOW) {
+            let roots: Vec<PathBuf> = self.roots.keys().cloned().collect();
+            for root in roots {
+                let _ = self.add_tree(&root);
+                self.debouncer.push(root, FsChangeKind::Rescan, now);
+            }
+            return;
+        }
+        if ev.has(libc::IN_IGNORED) {
+            if let Some(path) = self.dirs.remove(&ev.wd) {
+                self.by_path.remove(&path);
+            }
+            return;
+        }
+        let Some(dir) = self.dirs.get(&ev.wd).cloned() else {
+            return;
+        };
+        let Some(name) = &ev.name else {
+            // Events about the watched directory itself
+            if ev.has(libc::IN_DELETE_SELF | libc::IN_MOVE_SELF) && self.roots.contains_key(&dir) {
+                self.debouncer.push(dir, FsChangeKind::Deleted, now);
+            }
+            return;
+        };
+        let path = dir.join(name);
+        let is_dir = ev.has(libc::IN_ISDIR);
+
+        if ev.has(libc::IN_CREATE) {
+            self.debouncer.push(path.clone(), FsChangeKind::Created, now);
+            if is_dir {
+                self.created_tree(&path, now);
+            }
+        } else if ev.has(libc::IN_DELETE) {
+            self.debouncer.push(path.clone(), FsChangeKind::Deleted, now);
+            if is_dir {
+                self.forget_tree(&path);
+            }
+        } else if ev.has(libc::IN_MOVED_FROM) {
+            self.moves.insert(ev.cookie, PendingMove { from: path, is_dir, at: now });
+        } else if ev.has(libc::IN_MOVED_TO) {
+            match self.moves.remove(&ev.cookie) {
+                Some(mv) => {
+                    if mv.is_dir {
+                        self.rekey_tree(&mv.from, &path);
+                    }
+                    self.debouncer.push(path, FsChangeKind::Renamed { from: mv.from }, now);
+                }
+                None => {
+                    // Moved in from outside the watched tree
+                    self.debouncer.push(path.clone(), FsChangeKind::Created, now);
+                    if is_dir {
+                        self.created_tree(&path, now);
+                    }
+                }
+            }
+        } else if ev.has(libc::IN_MODIFY | libc::IN_ATTRIB | libc::IN_CLOSE_WRITE) {
+            self.debouncer.push(path, FsChangeKind::Modified, now);
+        }
+    }
+
+    /// Watch a new directory and report entries created before the watch existed
+    fn created_tree(&mut self, dir: &Path, now: Instant) {
+        let _ = self.add_tree(dir);
+        let mut stack = vec![dir.to_path_buf()];
+        while let Some(current) = stack.pop() {
+            let Ok(entries) = std::fs::read_dir(&current) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                    stack.push(path.clone());
+                }
+                self.debouncer.push(path, FsChangeKind::Created, now);
+            }
+        }
+    }
+
+    /// Turn unpaired IN_MOVED_FROM into deletions (moved out of the tree)
+    fn expire_moves(&mut self, now: Instant) {
+        let timeout = self.config.move_timeout;
+        let expired: Vec<u32> = self
+            .moves
+            .iter()
+            .filter(|(_, mv)| now.duration_since(mv.at) >= timeout)
+            .map(|(cookie, _)| *cookie)
+            .collect();
+        for cookie in expired {
+            if let Some(mv) = self.moves.remove(&cookie) {
+                if mv.is_dir {
+                    self.prune_moved(&mv.from);
+                }
+                self.debouncer.push(mv.from, FsChangeKind::Deleted, mv.at);
+            }
+        }
+    }
+
+    fn prune_moved(&mut self, prefix: &Path) {
+        let moved: Vec<WatchDescriptor> = self
+            .by_path
+            .iter()
+            .filter(|(p, _)| p.starts_with(prefix))
+            .map(|(_, wd)| *wd)
+            .collect();
+        self.forget_tree(prefix);
+        for wd in moved {
+            let _ = self.inotify.rm_watch(wd);
+        }
+    }
+
+    /// Sessions watching a root that contains `path`
+    fn sessions_for(&self, path: &Path) -> HashSet<SessionId> {
+        self.roots
+            .iter()
+            .filter(|(root, _)| path.starts_with(root))
+            .flat_map(|(_, users)| users.keys().copied())
+            .collect()
+    }
 }
 
 /// Watcher actor - monitors filesystem changes
 pub struct Watcher {
     commands: flume::Receiver<WatchCommand>,
     events: Sender<Event>,
+    registry: NodeRegistry,
+    navigator: Option<Sender<NavCommand>>,
+    cache: Option<Sender<CacheCommand>>,
+    syncer: Option<Sender<SyncerCommand>>,
+    config: WatcherConfig,
 }
 
 impl Watcher {
-    pub fn new(commands: flume::Receiver<WatchCommand>, events: Sender<Event>) -> Self {
-        todo!()
+    pub fn new(
+        commands: flume::Receiver<WatchCommand>,
+        events: Sender<Event>,
+        registry: NodeRegistry,
+    ) -> Self {
+        Self {
+            commands,
+            events,
+            registry,
+            navigator: None,
+            cache: None,
+            syncer: None,
+            config: WatcherConfig::default(),
+        }
+    }
+
+    /// Also send `NavCommand::Invalidate` for changed directories
+    pub fn with_navigator(mut self, navigator: Sender<NavCommand>) -> Self {
+        self.navigator = Some(navigator);
+        self
+    }
+
+    /// Also send `CacheCommand::Invalidate` for changed nodes
+    pub fn with_cache(mut self, cache: Sender<CacheCommand>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Also send `SyncerCommand::Changed` for every change
+    pub fn with_syncer(mut self, syncer: Sender<SyncerCommand>) -> Self {
+        self.syncer = Some(syncer);
+        self
+    }
+
+    pub fn with_config(mut self, config: WatcherConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn handle_command(&self, table: &mut WatchTable, cmd: WatchCommand) {
+        match cmd {
+            WatchCommand::Watch(path, session) => {
+                if let Err(e) = table.watch(path.clone(), session) {
+                    let _ = self.events.send(Event::Error {
+                        message: format!("Failed to watch {}: {}", path.display(), e),
+                        recoverable: true,
+                        session,
+                    });
+                }
+            }
+            WatchCommand::Unwatch(path, session) => table.unwatch(&path, session),
+            WatchCommand::UnwatchAll(session) => table.unwatch_all(session),
+        }
+    }
+
+    fn emit(&self, table: &WatchTable, changes: Vec<(PathBuf, FsChangeKind)>) {
+        let mut dirty = HashSet::new();
+        for (path, kind) in changes {
+            let sessions = table.sessions_for(&path);
+            if sessions.is_empty() {
+                continue;
+            }
+            if let Some(syncer) = &self.syncer {
+                let _ = syncer.send(SyncerCommand::Changed(path.clone()));
+            }
+            let node = self.registry.clone().register(path.clone());
+            if let Some(cache) = &self.cache {
+                let _ = cache.send(CacheCommand::Invalidate(node));
+                if let FsChangeKind::Renamed { from } = &kind {
+                    let _ = cache.send(CacheCommand::Invalidate(self.registry.clone().register(from.clone())));
+                }
+            }
+            match &kind {
+                FsChangeKind::Rescan => {
+                    dirty.insert(node);
+                }
+                FsChangeKind::Renamed { from } => {
+                    dirty.extend(from.parent().map(|p| self.registry.clone().register(p.to_path_buf())));
+                    dirty.extend(path.parent().map(|p| self.registry.clone().register(p.to_path_buf())));
+                }
+                _ => {
+                    dirty.extend(path.parent().map(|p| self.registry.clone().register(p.to_path_buf())));
+                }
+            }
+            for session in sessions {
+                let _ = self.events.send(Event::FsChanged {
+                    node,
+                    kind: kind.clone(),
+                    session,
+                });
+            }
+        }
+        if let Some(nav) = &self.navigator {
+            for node in dirty {
+                let _ = nav.send(NavCommand::Invalidate(node));
+            }
+        }
+    }
+
+    /// Serve commands without change detection when inotify is unavailable
+    async fn run_unavailable(self, reason: std::io::Error) {
+        while let Ok(cmd) = self.commands.recv_async().await {
+            if let WatchCommand::Watch(path, session) = cmd {
+                let _ = self.events.send(Event::Error {
+                    message: format!("Cannot watch {}: inotify unavailable: {}", path.display(), reason),
+                    recoverable: false,
+                    session,
+                });
+            }
+        }
     }
 }
 
 impl Actor for Watcher {
     async fn run(self) {
-        todo!()
+        let inotify = match Inotify::init() {
+            Ok(inotify) => inotify,
+            Err(e) => return self.run_unavailable(e).await,
+        };
+        let mut table = WatchTable::new(inotify, self.config);
+        let readiness = match AsyncFd::new(table.inotify.as_raw_fd()) {
+            Ok(fd) => fd,
+            Err(e) => return self.run_unavailable(e).await,
+        };
+
+        let period = (self.config.debounce / 2).max(Duration::from_millis(5));
+        let mut tick = tokio::time::interval(period);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut buf = vec![0u8; 64 * 1024];
+
+        loop {
+            tokio::select! {
+                cmd = self.commands.recv_async() => match cmd {
+                    Ok(cmd) => self.handle_command(&mut table, cmd),
+                    Err(_) => break,
+                },
+                ready = readiness.readable() => {
+                    let Ok(mut guard) = ready else { break };
+                    loop {
+                        match table.inotify.read_events(&mut buf) {
+                            Ok(events) => {
+                                let now = Instant::now();
+                                for ev in events {
+                                    table.on_event(ev, now);
+                                }
+                            }
+                            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                            // Drained (WouldBlock) or failing: either way wait
+                            // for the next event, as a failing read left ready
+                            // would be retried at once, forever
+                            Err(_) => {
+                                guard.clear_ready();
+                                break;
+                            }
+                        }
+                    }
+                },
+                _ = tick.tick() => {
+                    let now = Instant::now();
+                    table.expire_moves(now);
+                    let changes = table.debouncer.drain_due(now);
+                    if !changes.is_empty() {
+                        self.emit(&table, changes);
+                    }
+                },
+            }
+        }
+
+        let changes = table.debouncer.flush();
+        self.emit(&table, changes);
     }
-    
+
     fn name(&self) -> &'static str {
         "watcher"
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,22 @@
 use std::path::PathBuf;
 
+use crate::model::job::JobId;
 use crate::model::node::NodeId;
+use crate::model::selection::SelectionEdit;
 use crate::PreviewOptions;
-use crate::model::session::SessionId;
+use crate::services::archive::{ArchiveOptions, ExtractOptions};
+use crate::services::backup::RetentionPolicy;
+use crate::services::bisync::SyncPair;
+use crate::services::compare::{CompareOptions, SyncPlan};
+use crate::services::diff::DiffOptions;
+use crate::services::hash::HashAlgorithm;
+use crate::services::metadata::AttributeEdit;
+use crate::services::permissions::PermissionEdit;
+use crate::services::rename::RenameRule;
+use crate::services::tags::TagEdit;
+use crate::services::transfer::{ConflictResolution, TransferMode, TransferOptions};
+use crate::services::trash::RestoreConflict;
+use crate::model::session::{SessionId, ViewId};
 
 /// Commands from UI to Core
 /// Uses NodeId for efficiency (8 bytes vs PathBuf's heap allocation)
@@ -38,6 +52,14 @@ pub enum Command {
         session: SessionId
     },
     
+    /// Diff two text files; answered with `Event::DiffReady`
+    LoadDiff {
+        old: NodeId,
+        new: NodeId,
+        options: Option<DiffOptions>,
+        session: SessionId
+    },
+    
     /// Cancel preview generation
     CancelPreview(NodeId, SessionId),
     
@@ -45,6 +67,7 @@ pub enum Command {
     Copy {
         sources: Vec<NodeId>,
         destination: NodeId,
+        options: TransferOptions,
         session: SessionId
     },
     
@@ -52,6 +75,148 @@ pub enum Command {
     Move {
         sources: Vec<NodeId>,
         destination: NodeId,
+        options: TransferOptions,
+        session: SessionId
+    },
+    
+    /// Pack nodes into a new archive `name` in `destination`
+    Compress {
+        sources: Vec<NodeId>,
+        destination: NodeId,
+        name: String,
+        options: ArchiveOptions,
+        session: SessionId
+    },
+
+    /// Unpack an archive into `destination`
+    Extract {
+        archive: NodeId,
+        destination: NodeId,
+        options: ExtractOptions,
+        session: SessionId
+    },
+
+    /// Hash nodes (directories recursively) with each algorithm
+    Checksum {
+        nodes: Vec<NodeId>,
+        algorithms: Vec<HashAlgorithm>,
+        session: SessionId
+    },
+
+    /// Check nodes against a manifest (all of its entries if `nodes` is empty)
+    VerifyChecksums {
+        manifest: NodeId,
+        nodes: Vec<NodeId>,
+        session: SessionId
+    },
+
+    /// Write a manifest of nodes into `directory`
+    ///
+    /// Named after the algorithm (`SHA256SUMS`) unless `name` is given
+    CreateManifest {
+        nodes: Vec<NodeId>,
+        algorithm: HashAlgorithm,
+        directory: NodeId,
+        name: Option<String>,
+        session: SessionId
+    },
+
+    /// Compare two directory trees; entries stream as `CompareEntries`
+    Compare {
+        left: NodeId,
+        right: NodeId,
+        options: CompareOptions,
+        session: SessionId
+    },
+
+    /// Run a reviewed sync plan between the roots of a comparison
+    Synchronize {
+        left: NodeId,
+        right: NodeId,
+        plan: SyncPlan,
+        session: SessionId
+    },
+
+    /// Add or replace a two-way sync pair; it runs on its triggers
+    AddSyncPair(SyncPair),
+
+    /// Stop syncing a pair; its state is kept
+    RemoveSyncPair(String),
+
+    /// Sync a pair now; with `dry_run` only the planned changes are sent
+    RunSyncPair {
+        name: String,
+        dry_run: bool,
+        session: SessionId
+    },
+
+    /// Back up nodes as a new snapshot in the repository at `repository`,
+    /// which is created if there is none
+    Backup {
+        nodes: Vec<NodeId>,
+        repository: PathBuf,
+        session: SessionId
+    },
+
+    /// Remove the snapshots `policy` does not keep and the chunks only they
+    /// used; with `dry_run` only report them
+    PruneBackup {
+        repository: PathBuf,
+        policy: RetentionPolicy,
+        dry_run: bool,
+        session: SessionId
+    },
+
+    /// Check a repository; with `read_data` every chunk is read back
+    CheckBackup {
+        repository: PathBuf,
+        read_data: bool,
+        session: SessionId
+    },
+
+    /// Change the mode, owner or group of nodes
+    ChangePermissions {
+        nodes: Vec<NodeId>,
+        edit: PermissionEdit,
+        session: SessionId
+    },
+
+    /// Add or remove tags, or set the color label or note, of nodes
+    EditTags {
+        nodes: Vec<NodeId>,
+        edit: TagEdit,
+        session: SessionId
+    },
+
+    /// Read the extended attributes and ACLs of a node; answered with
+    /// `Event::AttributesLoaded`
+    LoadAttributes {
+        node: NodeId,
+        session: SessionId
+    },
+
+    /// Set or remove an extended attribute or ACL of nodes
+    EditAttributes {
+        nodes: Vec<NodeId>,
+        edit: AttributeEdit,
+        session: SessionId
+    },
+
+    /// Pause a running copy/move job
+    PauseJob(JobId, SessionId),
+
+    /// Resume a paused job
+    ResumeJob(JobId, SessionId),
+
+    /// Cancel a job; finished items are kept
+    CancelJob(JobId, SessionId),
+
+    /// Answer a job's pending `OperationConflict`
+    ResolveConflict {
+        job: JobId,
+        resolution: ConflictResolution,
+        /// Reuse the answer for the job's remaining conflicts of this kind
+        apply_to_all: bool,
         session: SessionId
     },
     
@@ -62,12 +227,38 @@ pub enum Command {
         session: SessionId
     },
     
+    /// List everything in the trash
+    ListTrash(SessionId),
+
+    /// Restore trashed items to their original location
+    RestoreTrash {
+        items: Vec<NodeId>,
+        conflict: RestoreConflict,
+        session: SessionId
+    },
+
+    /// Permanently delete trashed items (all, or only older than N days)
+    EmptyTrash {
+        older_than_days: Option<u32>,
+        session: SessionId
+    },
+    
     /// Rename a node
     Rename {
         node: NodeId,
         new_name: String,
         session: SessionId
     },
+
+    /// Rename many nodes with one rule, numbered in the given order
+    ///
+    /// With `dry_run` nothing is renamed and `Event::RenamePreview` is sent
+    BatchRename {
+        nodes: Vec<NodeId>,
+        rule: RenameRule,
+        dry_run: bool,
+        session: SessionId
+    },
     
     /// Create folder in parent
     CreateFolder {
@@ -83,6 +274,12 @@ pub enum Command {
         session: SessionId
     },
     
+    /// Undo the session's latest file operation
+    Undo(SessionId),
+
+    /// Redo the session's latest undone file operation
+    Redo(SessionId),
+    
     /// Load basic metadata
     LoadMetadata(NodeId, SessionId),
     
@@ -97,5 +294,60 @@ pub enum Command {
 
     Handshake,
     
-    DestroySession(SessionId)
+    DestroySession(SessionId),
+
+    /// Give a session a stable name and restore what was saved under it
+    /// by an earlier run
+    OpenSession {
+        name: String,
+        session: SessionId
+    },
+
+    /// Restore the most recently destroyed session as `session`
+    ReopenClosedSession(SessionId),
+
+    /// Run `command` against one view instead of the focused one
+    InView {
+        view: ViewId,
+        command: Box<Command>
+    },
+
+    /// Open a view (pane or tab) where the focused one is
+    OpenView(ViewId, SessionId),
+
+    /// Close a view; the last one stays open
+    CloseView(ViewId, SessionId),
+
+    /// Focus a view; the one focused before becomes the other pane
+    FocusView(ViewId, SessionId),
+
+    /// Cut (`TransferMode::Move`) or copy nodes, the selection if empty
+    Clip {
+        mode: TransferMode,
+        nodes: Vec<NodeId>,
+        session: SessionId
+    },
+
+    /// Paste the clipboard, by default into the view's directory
+    Paste {
+        destination: Option<NodeId>,
+        options: TransferOptions,
+        session: SessionId
+    },
+
+    /// Copy or move nodes, the selection if empty, by default to the
+    /// other pane's directory
+    Transfer {
+        mode: TransferMode,
+        nodes: Vec<NodeId>,
+        destination: Option<NodeId>,
+        options: TransferOptions,
+        session: SessionId
+    },
+
+    /// Change the selection of the focused view (or the one `InView` names)
+    Select {
+        edit: SelectionEdit,
+        session: SessionId
+    }
 }
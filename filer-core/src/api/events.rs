@@ -1,9 +1,18 @@
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 
-use crate::actors::navigator::NavState;
+use crate::actors::navigator::{Clipboard, NavState};
+use crate::model::job::JobId;
 use crate::model::node::NodeId;
-use crate::model::session::SessionId;
-use crate::{BasicMetadata, ExtendedMetadata, FileNode, PreviewData, model::fs_change::FsChangeKind};
+use crate::model::session::{SessionId, ViewId};
+use crate::pipeline::DeltaEntry;
+use crate::services::backup::{BackupReport, CheckReport, PruneReport};
+use crate::services::bisync::BisyncReport;
+use crate::services::compare::{CompareEntry, CompareSummary, SyncReport};
+use crate::services::hash::{ChecksumReport, VerifyReport};
+use crate::services::permissions::PermissionReport;
+use crate::services::rename::RenamePlan;
+use crate::{BasicMetadata, ExtendedMetadata, FileAttributes, FileNode, PreviewData, model::fs_change::FsChangeKind};
 
 /// Events from Core to UI
 /// FileNode contains full data for batches (UI caches these)
@@ -15,7 +24,22 @@ pub enum Event {
         parent: NodeId,
         path: PathBuf,  // Keep path for display in breadcrumb
         entries: Vec<FileNode>,
-        session: SessionId
+        session: SessionId,
+        view: ViewId
+    },
+
+    /// Changes to the directory last loaded for the view
+    ///
+    /// Entries carry their position in the new listing so clients can
+    /// patch their view instead of reloading it.
+    DirectoryDelta {
+        parent: NodeId,
+        added: Vec<DeltaEntry>,
+        removed: Vec<NodeId>,
+        modified: Vec<DeltaEntry>,
+        moved: Vec<DeltaEntry>,
+        session: SessionId,
+        view: ViewId
     },
     
     /// Scan progress update
@@ -43,6 +67,41 @@ pub enum Event {
         session: SessionId
     },
     
+    /// A copy/move job was accepted and started
+    JobStarted {
+        job: JobId,
+        operation: OperationKind,
+        session: SessionId
+    },
+
+    /// Periodic progress of a running job
+    JobProgress {
+        job: JobId,
+        progress: TransferProgress,
+        session: SessionId
+    },
+
+    /// Job paused by the client
+    JobPaused(JobId, SessionId),
+
+    /// Job resumed by the client
+    JobResumed(JobId, SessionId),
+
+    /// A job found an existing target and waits for `ResolveConflict`
+    OperationConflict {
+        job: JobId,
+        source: ConflictNode,
+        target: ConflictNode,
+        session: SessionId
+    },
+
+    /// Job ended; followed by `OperationComplete` for the affected nodes
+    JobFinished {
+        job: JobId,
+        outcome: JobOutcome,
+        session: SessionId
+    },
+
     /// File operation completed
     OperationComplete {
         operation: OperationKind,
@@ -51,6 +110,90 @@ pub enum Event {
         session: SessionId
     },
     
+    /// Checksums of a selection computed
+    ChecksumsComputed {
+        job: JobId,
+        report: ChecksumReport,
+        session: SessionId
+    },
+
+    /// Selection checked against a checksum manifest
+    ChecksumsVerified {
+        job: JobId,
+        report: VerifyReport,
+        session: SessionId
+    },
+
+    /// Next classified entries of a running comparison
+    CompareEntries {
+        job: JobId,
+        entries: Vec<CompareEntry>,
+        session: SessionId
+    },
+
+    /// Comparison walked both trees
+    CompareFinished {
+        job: JobId,
+        summary: CompareSummary,
+        session: SessionId
+    },
+
+    /// Sync plan ran, with the outcome of every step
+    SyncFinished {
+        job: JobId,
+        report: SyncReport,
+        session: SessionId
+    },
+
+    /// Two-way sync of a pair ran (or, dry, was planned), with every change
+    BisyncFinished {
+        job: JobId,
+        pair: String,
+        report: BisyncReport,
+        session: SessionId
+    },
+
+    /// Backup written as a new snapshot
+    BackupFinished {
+        job: JobId,
+        report: BackupReport,
+        session: SessionId
+    },
+
+    /// Retention policy applied (or, dry, evaluated)
+    BackupPruned {
+        job: JobId,
+        report: PruneReport,
+        session: SessionId
+    },
+
+    /// Integrity check of a backup repository ran
+    BackupChecked {
+        job: JobId,
+        report: CheckReport,
+        session: SessionId
+    },
+
+    /// Modes and owners changed, with the outcome for every item
+    PermissionsChanged {
+        job: JobId,
+        report: PermissionReport,
+        session: SessionId
+    },
+
+    /// Dry run of a batch rename
+    RenamePreview {
+        plan: RenamePlan,
+        session: SessionId
+    },
+
+    /// What undo and redo would do next for the session
+    HistoryChanged {
+        undo: Option<String>,
+        redo: Option<String>,
+        session: SessionId
+    },
+    
     /// Error occurred
     Error {
         message: String,
@@ -72,6 +215,13 @@ pub enum Event {
         session: SessionId
     },
     
+    /// Extended attributes and ACLs of a node read
+    AttributesLoaded {
+        node: NodeId,
+        attributes: FileAttributes,
+        session: SessionId
+    },
+    
     /// Preview ready
     PreviewReady {
         node: NodeId,
@@ -79,6 +229,14 @@ pub enum Event {
         session: SessionId
     },
     
+    /// Diff of two files ready, as `PreviewData::Diff`
+    DiffReady {
+        old: NodeId,
+        new: NodeId,
+        preview: PreviewData,
+        session: SessionId
+    },
+    
     /// Preview generation failed
     PreviewFailed {
         node: NodeId,
@@ -86,13 +244,46 @@ pub enum Event {
         session: SessionId
     },
 
+    /// Trash contents listed
+    TrashListed {
+        entries: Vec<TrashEntry>,
+        session: SessionId
+    },
+
     SessionCreated(SessionId),
 
     SessionDestroyed(SessionId),
 
     CurrentNavigateState{
         session: SessionId,
+        view: ViewId,
         state: NavState
+    },
+
+    /// Views of a session opened, closed or focused
+    ViewsChanged {
+        session: SessionId,
+        views: Vec<ViewId>,
+        focused: ViewId,
+        /// Default destination of `NavCommand::Transfer`
+        other: Option<ViewId>
+    },
+
+    /// Items cut or copied, or the clipboard emptied
+    ClipboardChanged {
+        session: SessionId,
+        clipboard: Option<Clipboard>
+    },
+
+    /// Selection of a view changed, by an edit or because its listing did
+    SelectionChanged {
+        session: SessionId,
+        view: ViewId,
+        /// In the order they were selected
+        selected: Vec<NodeId>,
+        count: usize,
+        /// Total size of the selected files
+        size: u64
     }
 }
 
@@ -100,8 +291,71 @@ pub enum Event {
 pub enum OperationKind {
     Copy,
     Move,
+    Compress,
+    Extract,
+    Checksum,
+    VerifyChecksums,
+    CreateManifest,
+    ChangePermissions,
+    EditTags,
+    EditAttributes,
+    Compare,
+    Synchronize,
+    Bisync,
+    Backup,
+    PruneBackup,
+    CheckBackup,
     Delete,
     Rename,
     CreateFolder,
     CreateFile,
+    Restore,
+    EmptyTrash,
+    Undo,
+    Redo,
+}
+
+/// Snapshot of a transfer's progress
+#[derive(Clone, Debug, Default)]
+pub struct TransferProgress {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+    /// Bytes per second over the last few seconds
+    pub throughput: u64,
+    /// None until there is a rate to estimate from
+    pub eta: Option<Duration>,
+    /// Item being transferred
+    pub current: Option<NodeId>,
+}
+
+/// One side of an operation conflict
+#[derive(Clone, Debug)]
+pub struct ConflictNode {
+    pub node: NodeId,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub is_dir: bool,
+}
+
+/// How a job ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobOutcome {
+    Completed,
+    /// Ran to the end but some items failed
+    Failed { errors: usize },
+    Cancelled,
+}
+
+/// A trashed item as seen by the UI
+#[derive(Clone, Debug)]
+pub struct TrashEntry {
+    /// Node of the item inside `trash://`
+    pub node: NodeId,
+    pub name: String,
+    pub original_path: PathBuf,
+    pub deleted_at: chrono::NaiveDateTime,
+    pub size: u64,
+    pub is_dir: bool,
 }
@@ -11,15 +11,15 @@ use flume::Sender;
 use crate::api::events::Event;
 use crate::model::registry::NodeRegistry;
 use crate::model::session::SessionId;
-use crate::actors::navigator::NavigatorState;
+use crate::actors::navigator::SessionViews;
 
 /// A client session with its own state and event channel
 #[derive(Debug)]
 pub struct Session {
     /// Unique session identifier
     pub id: SessionId,
-    /// Navigation state for this session
-    pub navigator: NavigatorState,
+    /// Views (panes or tabs), each with its own navigation state
+    pub views: SessionViews,
     /// Channel to send events to this client
     pub event_tx: Sender<Event>,
     /// Session metadata
@@ -31,7 +31,7 @@ impl Session {
     pub fn new(id: SessionId, event_tx: Sender<Event>, reg: NodeRegistry) -> Self {
         Self {
             id,
-            navigator: NavigatorState::new(reg),
+            views: SessionViews::new(reg),
             event_tx,
             created_at: std::time::Instant::now(),
         }
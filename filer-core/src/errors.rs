@@ -26,6 +26,16 @@ pub enum CoreError {
     /// Operation cancelled
     Cancelled,
     
+    /// Operation refused because the filesystem no longer matches
+    /// what it expects (the message says why)
+    Refused(String),
+    
+    /// A conditional write found the target changed since it was read
+    PreconditionFailed {
+        path: PathBuf,
+        reason: String,
+    },
+    
     /// Actor error
     ActorError {
         actor: &'static str,
@@ -52,6 +62,8 @@ impl std::fmt::Display for CoreError {
             CoreError::InvalidPath(p) => write!(f,"Invalid Path on {}.",p),
             CoreError::ChannelClosed => write!(f,"This Channel closed!"),
             CoreError::Cancelled => write!(f,"The operation was cancelled!"),
+            CoreError::Refused(reason) => write!(f,"Refused: {}.",reason),
+            CoreError::PreconditionFailed { path, reason } => write!(f,"Precondition failed on {:?}: {}.",path, reason),
             CoreError::ActorError { actor, message } => write!(f,"Actor {} reported an Error: {}",actor,message),
             CoreError::NetworkError => write!(f,"Network error!"),
             CoreError::InvalidData => write!(f,"Invalid Data!"),
@@ -13,11 +13,19 @@ mod vfs;
 pub use api::{commands::Command as Command, events::Event as Event, handle::FilerCore as FilerCore};
 pub use errors::CoreError;
 pub use model::node::FileNode;
+pub use model::selection::{Selection, SelectionEdit};
+pub use actors::navigator::Clipboard;
 
 // Services
-pub use services::metadata::{BasicMetadata, ExtendedMetadata, MetadataRegistry};
+pub use services::metadata::{
+    AttributeEdit, BasicMetadata, ExtendedMetadata, FileAttributes, MetadataRegistry, Xattr, XattrNamespace,
+};
+pub use services::permissions::{Acl, AclEntry, AclKind, AclTag};
 pub use services::mime::{MimeCategory, MimeDetector, MimeInfo};
 pub use services::preview::{PreviewData, PreviewOptions, PreviewRegistry};
+pub use services::sessions::{SavedSession, SavedView, SessionStore};
+pub use services::tags::{ColorLabel, FileTags, TagEdit, TagStore};
+pub use services::trash::{RestoreConflict, TrashBin};
 
 // Crypto (feature-gated)
 #[cfg(feature = "crypto")]
@@ -26,6 +34,9 @@ pub use services::crypto::{Cipher, CipherAlgorithm, Vault, VaultConfig};
 // VFS providers
 pub use vfs::local::LocalFs;
 pub use vfs::provider::FsProvider;
+pub use vfs::snapshot::SnapshotFs;
+pub use vfs::tags::TagsFs;
+pub use vfs::trash::TrashFs;
 
 #[cfg(feature = "s3")]
 pub use vfs::s3::{S3Fs, S3Config};
@@ -1,9 +1,11 @@
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FsChangeKind {
     Created,
     Modified,
     Deleted,
     Renamed { from: PathBuf },
-}
\ No newline at end of file
+    /// Change events were lost (e.g. inotify queue overflow); re-list everything
+    Rescan,
+}
@@ -1,5 +1,7 @@
 pub mod fs_change;
+pub mod job;
 pub mod node;
 pub mod query;
 pub mod registry;
+pub mod selection;
 pub mod session;
\ No newline at end of file
@@ -8,6 +8,9 @@ use serde::{Deserialize, Serialize};
 use crate::CoreError;
 use crate::model::registry::NodeRegistry;
 use crate::pipeline::sort::SortBy;
+use crate::services::permissions::AclKind;
+use crate::services::tags::ColorLabel;
+use crate::utils::xattr;
 
 /// Unique identifier for a file node
 ///
@@ -41,6 +44,12 @@ pub struct NodeMeta {
     pub hidden: bool,
     pub readonly: bool,
     pub permissions: Option<u32>,
+    /// Opaque content version from remote providers (S3/WebDAV ETag)
+    pub etag: Option<String>,
+    /// Filled in from the `TagStore` when listed
+    pub tags: Vec<String>,
+    pub label: Option<ColorLabel>,
+    pub note: Option<String>,
 }
 
 impl FileNode {
@@ -129,6 +138,7 @@ impl FileNode {
                 hidden,
                 readonly,
                 permissions,
+                ..Default::default()
             },
         })
     }
@@ -205,6 +215,7 @@ impl FileNode {
                 hidden,
                 readonly,
                 permissions,
+                ..Default::default()
             },
         })
     }
@@ -226,6 +237,15 @@ impl FileNode {
             _ => None,
         }
     }
+
+    /// Whether an access ACL grants more than the mode bits say (`ls -l`
+    /// shows a `+`)
+    ///
+    /// Read from the file on every call rather than for every listed node.
+    /// The kernel only keeps an access ACL that says more than the mode.
+    pub fn has_acl(&self) -> bool {
+        xattr::get(&self.path, AclKind::Access.xattr_name()).is_ok_and(|value| value.is_some())
+    }
 }
 
 impl NodeId {
@@ -0,0 +1,228 @@
+//! Ordered selection of a view
+//!
+//! Edits that need to know what is on screen (ranges, select all, invert
+//! and selecting by pattern) work on the view's current listing, in the
+//! order the pipeline put it.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeId};
+use crate::pipeline::FilterConfig;
+use crate::utils::matches_glob;
+
+/// A change to a view's selection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionEdit {
+    /// Select exactly these nodes (a plain click)
+    Replace(Vec<NodeId>),
+    Add(Vec<NodeId>),
+    Remove(Vec<NodeId>),
+    /// Select those not selected and deselect the others (ctrl-click)
+    Toggle(Vec<NodeId>),
+    /// Select from the anchor to `to` (shift-click); `extend` keeps the
+    /// rest of the selection (ctrl+shift-click)
+    Range {
+        to: NodeId,
+        extend: bool,
+    },
+    All,
+    Clear,
+    /// Select exactly the entries that are not selected
+    Invert,
+    /// Add entries whose name matches a glob (`*`, `?`)
+    Glob(String),
+    /// Add entries the filter keeps
+    Filter(FilterConfig),
+}
+
+/// Selected nodes, in the order they were selected
+///
+/// The anchor is where ranges start from: the node last clicked, added or
+/// toggled. Ranges keep it, so successive shift-clicks pivot around the
+/// same node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    nodes: Vec<NodeId>,
+    members: HashSet<NodeId>,
+    anchor: Option<NodeId>,
+}
+
+impl Selection {
+    pub fn nodes(&self) -> &[NodeId] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.members.contains(&node)
+    }
+
+    pub fn anchor(&self) -> Option<NodeId> {
+        self.anchor
+    }
+
+    /// Select `node` after the others; `false` if it already was
+    pub fn insert(&mut self, node: NodeId) -> bool {
+        let added = self.members.insert(node);
+        if added {
+            self.nodes.push(node);
+        }
+        added
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.members.clear();
+        self.anchor = None;
+    }
+
+    /// Apply `edit`, with `listing` being what the view shows; whether
+    /// the selection changed
+    pub fn apply(&mut self, edit: SelectionEdit, listing: &[FileNode]) -> Result<bool, CoreError> {
+        let before = self.nodes.clone();
+        match edit {
+            SelectionEdit::Replace(nodes) => {
+                self.clear();
+                self.extend(&nodes);
+            }
+            SelectionEdit::Add(nodes) => self.extend(&nodes),
+            SelectionEdit::Remove(nodes) => {
+                let nodes: HashSet<NodeId> = nodes.into_iter().collect();
+                self.deselect(|node| nodes.contains(node));
+            }
+            SelectionEdit::Toggle(nodes) => {
+                let selected: HashSet<NodeId> = nodes
+                    .iter()
+                    .copied()
+                    .filter(|node| self.contains(*node))
+                    .collect();
+                self.deselect(|node| selected.contains(node));
+                for node in nodes.iter().filter(|node| !selected.contains(node)) {
+                    self.insert(*node);
+                }
+                self.anchor = nodes.last().copied().or(self.anchor);
+            }
+            SelectionEdit::Range { to, extend } => {
+                let position = |node: NodeId| listing.iter().position(|entry| entry.id == node);
+                let end = position(to).ok_or_else(|| not_listed(to))?;
+                let start = match self.anchor.and_then(position) {
+                    Some(start) => start,
+                    None => {
+                        self.anchor = Some(to);
+                        end
+                    }
+                };
+                if !extend {
+                    self.deselect(|_| true);
+                }
+                for entry in &listing[start.min(end)..=start.max(end)] {
+                    self.insert(entry.id);
+                }
+            }
+            SelectionEdit::All => {
+                for entry in listing {
+                    self.insert(entry.id);
+                }
+            }
+            SelectionEdit::Clear => self.clear(),
+            SelectionEdit::Invert => {
+                let inverted: Vec<NodeId> = listing
+                    .iter()
+                    .map(|entry| entry.id)
+                    .filter(|node| !self.contains(*node))
+                    .collect();
+                self.deselect(|_| true);
+                for node in inverted {
+                    self.insert(node);
+                }
+            }
+            SelectionEdit::Glob(pattern) => {
+                if pattern.is_empty() {
+                    return Err(CoreError::InvalidInput);
+                }
+                for entry in listing
+                    .iter()
+                    .filter(|entry| matches_glob(&pattern, &entry.name))
+                {
+                    self.insert(entry.id);
+                }
+            }
+            SelectionEdit::Filter(filter) => {
+                for entry in listing.iter().filter(|entry| filter.matches(entry)) {
+                    self.insert(entry.id);
+                }
+            }
+        }
+        Ok(self.nodes != before)
+    }
+
+    /// Drop nodes that are not in `listing` any more, the anchor included;
+    /// whether the selection changed
+    pub fn prune(&mut self, listing: &[FileNode]) -> bool {
+        let listed: HashSet<NodeId> = listing.iter().map(|entry| entry.id).collect();
+        if self.anchor.is_some_and(|anchor| !listed.contains(&anchor)) {
+            self.anchor = None;
+        }
+        let before = self.nodes.len();
+        self.deselect(|node| !listed.contains(node));
+        self.nodes.len() != before
+    }
+
+    /// Total size of the selected entries of `listing`
+    ///
+    /// Directories count as empty; their contents are not listed.
+    pub fn size(&self, listing: &[FileNode]) -> u64 {
+        listing
+            .iter()
+            .filter(|entry| !entry.is_dir() && self.contains(entry.id))
+            .map(|entry| entry.size)
+            .sum()
+    }
+
+    /// Select `nodes` in order and anchor on the last one
+    fn extend(&mut self, nodes: &[NodeId]) {
+        for node in nodes {
+            self.insert(*node);
+        }
+        if let Some(last) = nodes.last() {
+            self.anchor = Some(*last);
+        }
+    }
+
+    /// Deselect the nodes `f` picks, keeping the order of the rest
+    fn deselect(&mut self, f: impl Fn(&NodeId) -> bool) {
+        let members = &mut self.members;
+        self.nodes.retain(|node| {
+            let keep = !f(node);
+            if !keep {
+                members.remove(node);
+            }
+            keep
+        });
+    }
+}
+
+impl FromIterator<NodeId> for Selection {
+    fn from_iter<I: IntoIterator<Item = NodeId>>(nodes: I) -> Self {
+        let mut selection = Selection::default();
+        for node in nodes {
+            selection.insert(node);
+        }
+        selection
+    }
+}
+
+fn not_listed(node: NodeId) -> CoreError {
+    CoreError::Refused(format!("{node:?} is not in the listing"))
+}
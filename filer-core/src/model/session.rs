@@ -5,11 +5,13 @@
 
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use serde::{Deserialize, Serialize};
+
 /// Unique session identifier
 ///
 /// Each client connection gets a unique SessionId. Commands and events
 /// are tagged with SessionId to route to the correct client.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SessionId(pub u64);
 
 impl SessionId {
@@ -35,3 +37,33 @@ impl std::fmt::Display for SessionId {
     }
 }
 
+
+/// Name of a view (pane or tab) within a session
+///
+/// Every session starts with one view, `ViewId::main()`; commands that do
+/// not name a view act on the focused one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ViewId(pub String);
+
+impl ViewId {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    /// The view every session starts with
+    pub fn main() -> Self {
+        Self("main".to_string())
+    }
+}
+
+impl Default for ViewId {
+    fn default() -> Self {
+        Self::main()
+    }
+}
+
+impl std::fmt::Display for ViewId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "view:{}", self.0)
+    }
+}
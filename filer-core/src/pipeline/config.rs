@@ -6,7 +6,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::model::node::FileNode;
 use crate::pipeline::sort::{SortField, SortOrder};
+use crate::utils::{is_hidden, matches_glob};
 
 /// Pipeline configuration - small, serializable, sent to/from frontend
 ///
@@ -136,6 +138,15 @@ pub struct FilterConfig {
     /// Only show files matching this name pattern (glob)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name_pattern: Option<String>,
+
+    /// Hide files and directories whose name matches any of these
+    /// patterns (glob)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_patterns: Vec<String>,
+
+    /// Only show files carrying any of these tags (empty = show all)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl FilterConfig {
@@ -154,6 +165,40 @@ impl FilterConfig {
             ..Default::default()
         }
     }
+
+    /// Whether `node` passes every rule of the filter
+    pub fn matches(&self, node: &FileNode) -> bool {
+        if !self.show_hidden && is_hidden(&node.path) {
+            return false;
+        }
+        if self.exclude_patterns.iter().any(|p| matches_glob(p, &node.name)) {
+            return false;
+        }
+        let extension = node
+            .path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        if !self.include_extensions.is_empty()
+            && !self.include_extensions.iter().any(|e| e == extension)
+        {
+            return false;
+        }
+        if self.exclude_extensions.iter().any(|e| e == extension) {
+            return false;
+        }
+        if self.min_size.is_some_and(|min| node.size < min)
+            || self.max_size.is_some_and(|max| node.size > max)
+        {
+            return false;
+        }
+        if !self.tags.is_empty() && !node.meta.tags.iter().any(|tag| self.tags.contains(tag)) {
+            return false;
+        }
+        self.name_pattern
+            .as_deref()
+            .is_none_or(|pattern| matches_glob(pattern, &node.name))
+    }
 }
 
 /// Group configuration
@@ -180,4 +225,6 @@ pub enum GroupBy {
     FirstLetter,
     /// Group by MIME type category (images, documents, videos, etc.)
     Type,
+    /// Group by tag; files with several tags are in each of their groups
+    Tag,
 }
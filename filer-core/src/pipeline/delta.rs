@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::model::node::{FileNode, NodeId};
+use crate::pipeline::PipelineData;
+
+/// A processed directory listing in display order
+///
+/// This is the flattened form of `PipelineData` that is sent to the UI,
+/// with the group label of every entry kept alongside it. The nodes are
+/// shared, so handing them on copies no `FileNode`.
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    nodes: Arc<[FileNode]>,
+    groups: Vec<Option<String>>,
+}
+
+impl Listing {
+    pub fn from_data(data: PipelineData) -> Self {
+        let (nodes, groups): (Vec<FileNode>, Vec<Option<String>>) = match data {
+            PipelineData::Flat(nodes) => nodes.into_iter().map(|n| (n, None)).collect(),
+            PipelineData::Grouped(grouped) => grouped
+                .groups
+                .into_iter()
+                .flat_map(|g| {
+                    let label = g.label;
+                    g.nodes.into_iter().map(move |n| (n, Some(label.clone())))
+                })
+                .collect(),
+        };
+        Self {
+            nodes: nodes.into(),
+            groups,
+        }
+    }
+
+    /// Entries in display order
+    pub fn nodes(&self) -> Arc<[FileNode]> {
+        self.nodes.clone()
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&FileNode, &Option<String>)> {
+        self.nodes.iter().zip(&self.groups)
+    }
+}
+
+/// An entry placed in the new listing
+#[derive(Debug, Clone)]
+pub struct DeltaEntry {
+    pub node: FileNode,
+    /// Position in the new (flattened) listing
+    pub index: usize,
+    /// Group the entry belongs to when the listing is grouped
+    pub group: Option<String>,
+}
+
+/// Difference between two listings of the same directory
+///
+/// Applying it: drop `removed` and the old copies of `modified` and `moved`
+/// entries, then insert every placed entry at its `index` in ascending
+/// order. Entries not mentioned keep their relative order.
+#[derive(Debug, Clone, Default)]
+pub struct ListingDelta {
+    pub added: Vec<DeltaEntry>,
+    pub removed: Vec<NodeId>,
+    /// Entries whose data changed (re-placed, since sort keys may change)
+    pub modified: Vec<DeltaEntry>,
+    /// Unchanged entries that now sit elsewhere relative to the others
+    pub moved: Vec<DeltaEntry>,
+}
+
+impl ListingDelta {
+    /// Compute the changes that turn `old` into `new`
+    pub fn between(old: &Listing, new: &Listing) -> Self {
+        let old_pos: HashMap<NodeId, usize> = old
+            .entries()
+            .enumerate()
+            .map(|(i, (n, _))| (n.id, i))
+            .collect();
+        let new_ids: HashSet<NodeId> = new.nodes.iter().map(|n| n.id).collect();
+
+        let mut delta = ListingDelta {
+            removed: old
+                .nodes
+                .iter()
+                .map(|n| n.id)
+                .filter(|id| !new_ids.contains(id))
+                .collect(),
+            ..Default::default()
+        };
+
+        // Unchanged entries, in new order, with their old positions
+        let mut kept: Vec<(usize, usize)> = Vec::new();
+        for (index, (node, group)) in new.entries().enumerate() {
+            let entry = || DeltaEntry {
+                node: node.clone(),
+                index,
+                group: group.clone(),
+            };
+            match old_pos.get(&node.id) {
+                None => delta.added.push(entry()),
+                Some(&pos) => {
+                    if changed(&old.nodes[pos], node) || old.groups[pos] != *group {
+                        delta.modified.push(entry());
+                    } else {
+                        kept.push((index, pos));
+                    }
+                }
+            }
+        }
+
+        // The longest run of entries already in order stays put; the rest moved
+        let stable = longest_increasing(&kept.iter().map(|(_, pos)| *pos).collect::<Vec<_>>());
+        for (i, (index, _)) in kept.iter().enumerate() {
+            if !stable.contains(&i) {
+                delta.moved.push(DeltaEntry {
+                    node: new.nodes[*index].clone(),
+                    index: *index,
+                    group: new.groups[*index].clone(),
+                });
+            }
+        }
+        delta
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty() && self.moved.is_empty()
+    }
+
+    /// Patch an old listing in place the way a client would
+    pub fn apply(&self, old: &[FileNode]) -> Vec<FileNode> {
+        let dropped: HashSet<NodeId> = self
+            .removed
+            .iter()
+            .copied()
+            .chain(self.modified.iter().chain(&self.moved).map(|e| e.node.id))
+            .collect();
+        let mut out: Vec<FileNode> = old.iter().filter(|n| !dropped.contains(&n.id)).cloned().collect();
+
+        let mut placed: Vec<&DeltaEntry> = self.added.iter().chain(&self.modified).chain(&self.moved).collect();
+        placed.sort_by_key(|e| e.index);
+        for entry in placed {
+            out.insert(entry.index.min(out.len()), entry.node.clone());
+        }
+        out
+    }
+}
+
+/// Whether anything the UI displays differs between two versions of a node
+fn changed(a: &FileNode, b: &FileNode) -> bool {
+    a.name != b.name
+        || a.size != b.size
+        || a.modified != b.modified
+        || a.is_dir() != b.is_dir()
+        || a.meta.hidden != b.meta.hidden
+        || a.meta.readonly != b.meta.readonly
+        || a.meta.permissions != b.meta.permissions
+        || a.meta.etag != b.meta.etag
+        || a.meta.tags != b.meta.tags
+        || a.meta.label != b.meta.label
+        || a.meta.note != b.meta.note
+}
+
+/// Indices (into `seq`) of one longest strictly increasing subsequence
+fn longest_increasing(seq: &[usize]) -> HashSet<usize> {
+    // tails[k] = index in seq of the smallest tail of an increasing run of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = vec![None; seq.len()];
+    for (i, value) in seq.iter().enumerate() {
+        let k = tails.partition_point(|&t| seq[t] < *value);
+        if k > 0 {
+            prev[i] = Some(tails[k - 1]);
+        }
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut result = HashSet::new();
+    let mut cursor = tails.last().copied();
+    while let Some(i) = cursor {
+        result.insert(i);
+        cursor = prev[i];
+    }
+    result
+}
@@ -86,4 +86,42 @@ impl Stage for FilterByExtension {
     fn name(&self) -> &'static str {
         "filter_by_extension"
     }
-}
\ No newline at end of file
+}
+
+/// Keep files carrying any of the tags
+pub struct FilterByTag {
+    tags: Vec<String>,
+}
+
+impl FilterByTag {
+    pub fn new(tags: Vec<String>) -> Self {
+        Self { tags }
+    }
+
+    fn filter_nodes(&self, nodes: Vec<FileNode>) -> Vec<FileNode> {
+        nodes
+            .into_iter()
+            .filter(|f| f.meta.tags.iter().any(|tag| self.tags.contains(tag)))
+            .collect()
+    }
+}
+
+impl Stage for FilterByTag {
+    fn process(&self, input: PipelineData) -> PipelineData {
+        match input {
+            PipelineData::Flat(nodes) => PipelineData::Flat(self.filter_nodes(nodes)),
+            PipelineData::Grouped(mut grouped) => {
+                for group in &mut grouped.groups {
+                    group.nodes = self.filter_nodes(std::mem::take(&mut group.nodes));
+                }
+                grouped.groups.retain(|g| !g.nodes.is_empty());
+                grouped.total_count = grouped.groups.iter().map(|g| g.nodes.len()).sum();
+                PipelineData::Grouped(grouped)
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "filter_by_tag"
+    }
+}
@@ -11,6 +11,8 @@ pub enum GroupField {
     Date,
     Size,
     FirstLetter,
+    /// One group per tag, plus "Untagged"
+    Tag,
 }
 
 pub struct GroupBy {
@@ -54,6 +56,17 @@ impl Stage for GroupBy {
                         .to_uppercase()
                         .to_string()
                 }
+                GroupField::Tag => {
+                    // A node with several tags goes into each of their groups
+                    let Some((last, others)) = node.meta.tags.split_last() else {
+                        groups_map.entry("Untagged".to_string()).or_default().push(node);
+                        continue;
+                    };
+                    for tag in others {
+                        groups_map.entry(tag.clone()).or_default().push(node.clone());
+                    }
+                    last.clone()
+                }
             };
             
             groups_map.entry(key).or_default().push(node);
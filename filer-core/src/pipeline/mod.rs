@@ -1,4 +1,5 @@
 pub mod config;
+pub mod delta;
 pub mod filter;
 pub mod group;
 pub mod sort;
@@ -6,6 +7,7 @@ pub mod sort;
 use crate::model::node::FileNode;
 
 pub use config::{FilterConfig, GroupBy, GroupConfig, PipelineConfig, SortConfig};
+pub use delta::{DeltaEntry, Listing, ListingDelta};
 pub use sort::{SortField, SortOrder};
 
 /// Grouped file nodes with metadata
@@ -83,6 +85,10 @@ impl Pipeline {
                 ));
             }
             
+            if !filter_config.tags.is_empty() {
+                pipeline = pipeline.add(filter::FilterByTag::new(filter_config.tags.clone()));
+            }
+
             // - min_size / max_size
             // - name_pattern
         }
@@ -105,6 +111,7 @@ impl Pipeline {
                 GroupBy::Size => Some(group::GroupField::Size),
                 GroupBy::FirstLetter => Some(group::GroupField::FirstLetter),
                 GroupBy::Type => Some(group::GroupField::Extension), // Map to extension for now
+                GroupBy::Tag => Some(group::GroupField::Tag),
             };
 
             if let Some(field) = group_field {
@@ -0,0 +1,439 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+
+use chrono::{DateTime, Local};
+use flate2::write::GzEncoder;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::errors::CoreError;
+use crate::services::transfer::{
+    Conflict, ConflictResolution, ConflictResolver, ConflictSide, JobControl, ProgressTracker,
+    TransferObserver, TransferReport,
+};
+use crate::utils;
+
+use super::format::{ArchiveFormat, ArchiveOptions, SymlinkMode};
+
+enum Kind {
+    File,
+    Dir,
+    Symlink(PathBuf),
+}
+
+struct Entry {
+    source: PathBuf,
+    /// Path inside the archive
+    name: PathBuf,
+    kind: Kind,
+    meta: Metadata,
+}
+
+/// Builds an archive from a selection
+///
+/// Every source is stored under its own name at the top of the archive.
+/// The archive is written to a temporary file next to the target and
+/// renamed into place once complete.
+pub struct Compress {
+    sources: Vec<PathBuf>,
+    target: PathBuf,
+    options: ArchiveOptions,
+}
+
+impl Compress {
+    pub fn new(sources: Vec<PathBuf>, target: PathBuf, options: ArchiveOptions) -> Self {
+        Self {
+            sources,
+            target,
+            options,
+        }
+    }
+
+    pub fn run(
+        &self,
+        control: &JobControl,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<TransferReport, CoreError> {
+        let mut report = TransferReport::default();
+        let mut entries = Vec::new();
+        for source in &self.sources {
+            control.checkpoint()?;
+            let name = source
+                .file_name()
+                .ok_or_else(|| CoreError::InvalidPath(source.display().to_string()))?;
+            let mut ancestors = Vec::new();
+            self.walk(
+                source,
+                PathBuf::from(name),
+                &mut ancestors,
+                &mut entries,
+                &mut report,
+                observer,
+            );
+        }
+        let bytes = entries
+            .iter()
+            .filter(|e| matches!(e.kind, Kind::File))
+            .map(|e| e.meta.len())
+            .sum();
+        let mut tracker = ProgressTracker::new(bytes, entries.len() as u64);
+
+        let Some(target) = self.settle_target(bytes, observer)? else {
+            report.skipped += 1;
+            return Ok(report);
+        };
+        let part = part_path(&target)?;
+        let result = File::create_new(&part)
+            .map_err(|e| CoreError::from_io_error(e, part.clone()))
+            .and_then(|file| self.write(file, &entries, control, &mut tracker, observer))
+            .and_then(|file| {
+                file.sync_all()
+                    .map_err(|e| CoreError::from_io_error(e, part.clone()))
+            })
+            .and_then(|()| {
+                fs::rename(&part, &target).map_err(|e| CoreError::from_io_error(e, target.clone()))
+            });
+        if let Err(e) = result {
+            let _ = fs::remove_file(&part);
+            return Err(e);
+        }
+        report.created.push(target);
+        Ok(report)
+    }
+
+    /// Collect `path` and everything below it; failures are reported and
+    /// left out
+    fn walk(
+        &self,
+        path: &Path,
+        name: PathBuf,
+        ancestors: &mut Vec<(u64, u64)>,
+        entries: &mut Vec<Entry>,
+        report: &mut TransferReport,
+        observer: &mut dyn TransferObserver,
+    ) {
+        let meta = match self.options.symlinks {
+            SymlinkMode::Store => fs::symlink_metadata(path),
+            SymlinkMode::Follow => fs::metadata(path),
+        };
+        let meta = match meta {
+            Ok(meta) => meta,
+            Err(e) => {
+                observer.failed(path, &CoreError::from_io_error(e, path.to_path_buf()));
+                report.errors += 1;
+                return;
+            }
+        };
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(path).unwrap_or_default();
+            entries.push(Entry {
+                source: path.to_path_buf(),
+                name,
+                kind: Kind::Symlink(target),
+                meta,
+            });
+            return;
+        }
+        if !meta.is_dir() {
+            entries.push(Entry {
+                source: path.to_path_buf(),
+                name,
+                kind: Kind::File,
+                meta,
+            });
+            return;
+        }
+
+        let id = (meta.dev(), meta.ino());
+        if ancestors.contains(&id) {
+            let error = CoreError::InvalidPath(format!("{} (symlink loop)", path.display()));
+            observer.failed(path, &error);
+            report.errors += 1;
+            return;
+        }
+        let children = match fs::read_dir(path) {
+            Ok(dir) => {
+                let mut children: Vec<_> =
+                    dir.filter_map(|e| e.ok().map(|e| e.file_name())).collect();
+                children.sort();
+                children
+            }
+            Err(e) => {
+                observer.failed(path, &CoreError::from_io_error(e, path.to_path_buf()));
+                report.errors += 1;
+                return;
+            }
+        };
+        entries.push(Entry {
+            source: path.to_path_buf(),
+            name: name.clone(),
+            kind: Kind::Dir,
+            meta,
+        });
+        ancestors.push(id);
+        for child in children {
+            self.walk(
+                &path.join(&child),
+                name.join(&child),
+                ancestors,
+                entries,
+                report,
+                observer,
+            );
+        }
+        ancestors.pop();
+    }
+
+    /// Where the archive goes, or `None` if the conflict says skip
+    fn settle_target(
+        &self,
+        bytes: u64,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<Option<PathBuf>, CoreError> {
+        let Ok(existing) = fs::symlink_metadata(&self.target) else {
+            return Ok(Some(self.target.clone()));
+        };
+        let conflict = Conflict {
+            source: ConflictSide {
+                path: self.target.clone(),
+                size: bytes,
+                modified: Some(SystemTime::now()),
+                is_dir: false,
+            },
+            target: ConflictSide::new(self.target.clone(), &existing),
+        };
+        let mut resolver = ConflictResolver::new(self.options.conflict);
+        match resolver.resolve(&conflict, observer)? {
+            ConflictResolution::Overwrite if existing.is_dir() => Err(CoreError::InvalidPath(
+                format!("{} is a directory", self.target.display()),
+            )),
+            ConflictResolution::Overwrite => Ok(Some(self.target.clone())),
+            ConflictResolution::KeepBoth => Ok(Some(utils::path::unique_name(&self.target))),
+            _ => Ok(None),
+        }
+    }
+
+    fn write(
+        &self,
+        file: File,
+        entries: &[Entry],
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<File, CoreError> {
+        let level = self.options.format.level(self.options.level);
+        let mut writer = match self.options.format {
+            ArchiveFormat::Zip => Writer::Zip(ZipWriter::new(file)),
+            ArchiveFormat::Tar => Writer::Tar(tar_builder(Encoder::Plain(file))),
+            ArchiveFormat::TarGz => Writer::Tar(tar_builder(Encoder::Gz(GzEncoder::new(
+                file,
+                flate2::Compression::new(level as u32),
+            )))),
+            ArchiveFormat::TarZst => {
+                let encoder = zstd::Encoder::new(file, level).map_err(|e| self.io_error(e))?;
+                Writer::Tar(tar_builder(Encoder::Zst(encoder)))
+            }
+        };
+        for entry in entries {
+            control.checkpoint()?;
+            let mut source = Source {
+                file: None,
+                control,
+                tracker: &mut *tracker,
+                observer: &mut *observer,
+                path: &entry.source,
+                read: 0,
+            };
+            let result = writer.append(entry, level, &mut source);
+            let read = source.read;
+            if let Err(e) = result {
+                return Err(match control.is_cancelled() {
+                    true => CoreError::Cancelled,
+                    false => e,
+                });
+            }
+            if matches!(entry.kind, Kind::File) && read != entry.meta.len() {
+                return Err(CoreError::Io {
+                    path: entry.source.clone(),
+                    message: "changed while being archived".to_string(),
+                });
+            }
+            tracker.file_done();
+            observer.progress(tracker, &entry.source);
+        }
+        writer.finish().map_err(|e| self.io_error(e))
+    }
+
+    fn io_error(&self, e: io::Error) -> CoreError {
+        CoreError::from_io_error(e, self.target.clone())
+    }
+}
+
+/// Fresh temporary file next to `target`
+fn part_path(target: &Path) -> Result<PathBuf, CoreError> {
+    let name = target
+        .file_name()
+        .ok_or_else(|| CoreError::InvalidPath(target.display().to_string()))?;
+    Ok(target.with_file_name(format!(
+        ".{}.{}.filer-part",
+        name.to_string_lossy(),
+        std::process::id()
+    )))
+}
+
+fn tar_builder(encoder: Encoder) -> tar::Builder<Encoder> {
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+    builder
+}
+
+/// Compression layer under a tar stream
+enum Encoder {
+    Plain(File),
+    Gz(GzEncoder<File>),
+    Zst(zstd::Encoder<'static, File>),
+}
+
+impl Encoder {
+    fn finish(self) -> io::Result<File> {
+        match self {
+            Encoder::Plain(file) => Ok(file),
+            Encoder::Gz(encoder) => encoder.finish(),
+            Encoder::Zst(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(file) => file.write(buf),
+            Encoder::Gz(encoder) => encoder.write(buf),
+            Encoder::Zst(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(file) => file.flush(),
+            Encoder::Gz(encoder) => encoder.flush(),
+            Encoder::Zst(encoder) => encoder.flush(),
+        }
+    }
+}
+
+enum Writer {
+    Zip(ZipWriter<File>),
+    Tar(tar::Builder<Encoder>),
+}
+
+impl Writer {
+    fn append(&mut self, entry: &Entry, level: i32, source: &mut Source) -> Result<(), CoreError> {
+        let io_error = |e: io::Error| CoreError::from_io_error(e, entry.source.clone());
+        match self {
+            Writer::Zip(zip) => {
+                let zip_error = |e: zip::result::ZipError| CoreError::Io {
+                    path: entry.source.clone(),
+                    message: e.to_string(),
+                };
+                // Zip names are UTF-8 with `/` separators
+                let name = entry.name.to_string_lossy().into_owned();
+                let mut options = SimpleFileOptions::default()
+                    .unix_permissions(entry.meta.permissions().mode() & 0o7777)
+                    .large_file(entry.meta.len() >= u32::MAX as u64);
+                options = match level {
+                    0 => options.compression_method(CompressionMethod::Stored),
+                    level => options
+                        .compression_method(CompressionMethod::Deflated)
+                        .compression_level(Some(level as i64)),
+                };
+                if let Some(time) = entry.meta.modified().ok().and_then(zip_time) {
+                    options = options.last_modified_time(time);
+                }
+                match &entry.kind {
+                    Kind::Dir => zip
+                        .add_directory(format!("{name}/"), options)
+                        .map_err(zip_error),
+                    Kind::Symlink(target) => zip
+                        .add_symlink(name, target.to_string_lossy(), options)
+                        .map_err(zip_error),
+                    Kind::File => {
+                        zip.start_file(name, options).map_err(zip_error)?;
+                        source.open()?;
+                        io::copy(source, zip).map(drop).map_err(io_error)
+                    }
+                }
+            }
+            Writer::Tar(builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&entry.meta);
+                match &entry.kind {
+                    Kind::Dir => {
+                        header.set_entry_type(tar::EntryType::Directory);
+                        header.set_size(0);
+                        builder.append_data(&mut header, &entry.name, io::empty())
+                    }
+                    Kind::Symlink(target) => {
+                        header.set_entry_type(tar::EntryType::Symlink);
+                        header.set_size(0);
+                        builder.append_link(&mut header, &entry.name, target)
+                    }
+                    Kind::File => {
+                        header.set_entry_type(tar::EntryType::Regular);
+                        source.open()?;
+                        let size = entry.meta.len();
+                        builder.append_data(&mut header, &entry.name, source.take(size))
+                    }
+                }
+                .map_err(io_error)
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<File> {
+        match self {
+            Writer::Zip(zip) => zip.finish().map_err(io::Error::other),
+            Writer::Tar(builder) => builder.into_inner()?.finish(),
+        }
+    }
+}
+
+fn zip_time(time: SystemTime) -> Option<zip::DateTime> {
+    zip::DateTime::try_from(DateTime::<Local>::from(time).naive_local()).ok()
+}
+
+/// Reads a source file, reporting progress and honouring pause/cancel
+struct Source<'a> {
+    file: Option<File>,
+    control: &'a JobControl,
+    tracker: &'a mut ProgressTracker,
+    observer: &'a mut dyn TransferObserver,
+    path: &'a Path,
+    read: u64,
+}
+
+impl Source<'_> {
+    fn open(&mut self) -> Result<(), CoreError> {
+        let file = File::open(self.path)
+            .map_err(|e| CoreError::from_io_error(e, self.path.to_path_buf()))?;
+        self.file = Some(file);
+        Ok(())
+    }
+}
+
+impl Read for Source<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.control.checkpoint().map_err(io::Error::other)?;
+        let Some(file) = &mut self.file else {
+            return Ok(0);
+        };
+        let n = file.read(buf)?;
+        self.read += n as u64;
+        self.tracker.add_bytes(n as u64, Instant::now());
+        self.observer.progress(self.tracker, self.path);
+        Ok(n)
+    }
+}
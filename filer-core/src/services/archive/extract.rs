@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
+
+use chrono::{Local, TimeZone};
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::errors::CoreError;
+use crate::services::transfer::{
+    Conflict, ConflictResolution, ConflictResolver, ConflictSide, JobControl, ProgressTracker,
+    TransferObserver, TransferReport,
+};
+use crate::utils;
+
+use super::format::{ArchiveFormat, ExtractLimits, ExtractOptions};
+
+/// Ratio limit only applies once this much has been written
+const RATIO_GRACE: u64 = 1024 * 1024;
+
+/// Longest symlink target read from a zip entry
+const MAX_LINK_LEN: u64 = 4096;
+
+enum Kind {
+    File,
+    Dir,
+    Symlink(PathBuf),
+}
+
+/// An archive entry as its header describes it
+struct Entry {
+    name: PathBuf,
+    kind: Kind,
+    mode: Option<u32>,
+    modified: Option<SystemTime>,
+    size: u64,
+}
+
+/// Unpacks an archive into a directory
+///
+/// Entries are streamed one at a time. Names that are absolute or climb
+/// out of the destination are refused, as are symlinks pointing outside
+/// it and anything that would be written through an existing symlink.
+/// Content is written to a temporary file and renamed into place, and
+/// extraction stops with `CoreError::Refused` once a limit is exceeded.
+pub struct Extract {
+    archive: PathBuf,
+    destination: PathBuf,
+    options: ExtractOptions,
+}
+
+impl Extract {
+    pub fn new(archive: PathBuf, destination: PathBuf, options: ExtractOptions) -> Self {
+        Self {
+            archive,
+            destination,
+            options,
+        }
+    }
+
+    pub fn run(
+        &self,
+        control: &JobControl,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<TransferReport, CoreError> {
+        let io_error = |e: io::Error| CoreError::from_io_error(e, self.archive.clone());
+        let mut file = File::open(&self.archive).map_err(io_error)?;
+        let archive_len = file.metadata().map_err(io_error)?.len();
+        let format = match ArchiveFormat::from_path(&self.archive) {
+            Some(format) => format,
+            None => {
+                let format = ArchiveFormat::sniff(&mut file);
+                file = File::open(&self.archive).map_err(io_error)?;
+                format.ok_or_else(|| CoreError::Io {
+                    path: self.archive.clone(),
+                    message: "not a supported archive".to_string(),
+                })?
+            }
+        };
+        let destination = fs::canonicalize(&self.destination)
+            .map_err(|e| CoreError::from_io_error(e, self.destination.clone()))?;
+
+        let mut extractor = Extractor {
+            archive: &self.archive,
+            destination,
+            archive_len,
+            limits: self.options.limits,
+            resolver: ConflictResolver::new(self.options.conflict),
+            control,
+            observer,
+            tracker: ProgressTracker::new(0, 0),
+            consumed: None,
+            written: 0,
+            entries: 0,
+            renamed: HashMap::new(),
+            modes: Vec::new(),
+            report: TransferReport::default(),
+        };
+        match format {
+            ArchiveFormat::Zip => extractor.zip(file)?,
+            ArchiveFormat::Tar => extractor.tar(file, |r| Ok(Box::new(r)))?,
+            ArchiveFormat::TarGz => extractor.tar(file, |r| Ok(Box::new(GzDecoder::new(r))))?,
+            ArchiveFormat::TarZst => {
+                extractor.tar(file, |r| Ok(Box::new(zstd::Decoder::new(r)?)))?
+            }
+        }
+        extractor.restore_modes();
+        Ok(extractor.report)
+    }
+}
+
+/// How far the tar reader got into the (compressed) archive
+struct Counting<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+struct Extractor<'a> {
+    archive: &'a Path,
+    /// Canonical destination directory
+    destination: PathBuf,
+    archive_len: u64,
+    limits: ExtractLimits,
+    resolver: ConflictResolver,
+    control: &'a JobControl,
+    observer: &'a mut dyn TransferObserver,
+    tracker: ProgressTracker,
+    /// Archive bytes read so far when progress follows the archive
+    consumed: Option<Arc<AtomicU64>>,
+    written: u64,
+    entries: u64,
+    /// Archive directories extracted elsewhere (`Some`) or skipped (`None`)
+    renamed: HashMap<PathBuf, Option<PathBuf>>,
+    /// Directory modes, applied last so read-only ones can be filled
+    modes: Vec<(PathBuf, u32)>,
+    report: TransferReport,
+}
+
+impl Extractor<'_> {
+    fn zip(&mut self, file: File) -> Result<(), CoreError> {
+        let mut zip = ZipArchive::new(file).map_err(|e| self.corrupt(e))?;
+        let total = (0..zip.len())
+            .filter_map(|i| zip.by_index_raw(i).ok().map(|f| f.size()))
+            .fold(0u64, u64::saturating_add);
+        self.tracker = ProgressTracker::new(total, zip.len() as u64);
+        for i in 0..zip.len() {
+            self.control.checkpoint()?;
+            let mut file = zip.by_index(i).map_err(|e| self.corrupt(e))?;
+            let name = PathBuf::from(file.name().replace('\\', "/"));
+            let modified = file
+                .last_modified()
+                .and_then(|t| chrono::NaiveDateTime::try_from(t).ok())
+                .and_then(|t| Local.from_local_datetime(&t).earliest())
+                .map(SystemTime::from);
+            let kind = if file.is_dir() {
+                Kind::Dir
+            } else if file.is_symlink() {
+                let mut target = Vec::new();
+                (&mut file)
+                    .take(MAX_LINK_LEN)
+                    .read_to_end(&mut target)
+                    .map_err(|e| self.corrupt(e))?;
+                Kind::Symlink(PathBuf::from(OsStr::from_bytes(&target)))
+            } else {
+                Kind::File
+            };
+            let entry = Entry {
+                name,
+                kind,
+                mode: file.unix_mode(),
+                modified,
+                size: file.size(),
+            };
+            self.entry(entry, &mut file)?;
+        }
+        Ok(())
+    }
+
+    fn tar(
+        &mut self,
+        file: File,
+        decoder: impl FnOnce(Counting<File>) -> io::Result<Box<dyn Read>>,
+    ) -> Result<(), CoreError> {
+        let consumed = Arc::new(AtomicU64::new(0));
+        let counting = Counting {
+            inner: file,
+            count: consumed.clone(),
+        };
+        let mut archive = tar::Archive::new(decoder(counting).map_err(|e| self.corrupt(e))?);
+        self.tracker = ProgressTracker::new(self.archive_len, 0);
+        self.consumed = Some(consumed);
+        for entry in archive.entries().map_err(|e| self.corrupt(e))? {
+            self.control.checkpoint()?;
+            let mut entry = entry.map_err(|e| self.corrupt(e))?;
+            let header = entry.header();
+            let kind = match header.entry_type() {
+                tar::EntryType::Regular | tar::EntryType::Continuous => Kind::File,
+                tar::EntryType::Directory => Kind::Dir,
+                tar::EntryType::Symlink => match entry.link_name().map_err(|e| self.corrupt(e))? {
+                    Some(target) => Kind::Symlink(target.into_owned()),
+                    None => Kind::Symlink(PathBuf::new()),
+                },
+                tar::EntryType::XGlobalHeader => continue,
+                other => {
+                    let name = PathBuf::from(OsStr::from_bytes(&entry.path_bytes()));
+                    self.fail(&name, format!("unsupported entry type {other:?}"));
+                    continue;
+                }
+            };
+            let info = Entry {
+                name: PathBuf::from(OsStr::from_bytes(&entry.path_bytes())),
+                kind,
+                mode: header.mode().ok(),
+                modified: header
+                    .mtime()
+                    .ok()
+                    .map(|t| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(t)),
+                size: entry.size(),
+            };
+            self.entry(info, &mut entry)?;
+        }
+        Ok(())
+    }
+
+    /// Extract one entry; problems with it alone are reported and skipped
+    fn entry(&mut self, entry: Entry, content: &mut dyn Read) -> Result<(), CoreError> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(CoreError::Refused(format!(
+                "archive has more than {} entries",
+                self.limits.max_entries
+            )));
+        }
+        let name = match sanitize(&entry.name) {
+            Ok(name) => name,
+            Err(reason) => {
+                self.fail(&entry.name, reason);
+                return Ok(());
+            }
+        };
+        if name.as_os_str().is_empty() {
+            return Ok(());
+        }
+        // Inside a skipped directory, which was counted already
+        let Some(relative) = self.relocate(&name) else {
+            return Ok(());
+        };
+        if let Kind::Symlink(target) = &entry.kind
+            && !link_stays_inside(&relative, target)
+        {
+            self.fail(
+                &entry.name,
+                format!("symlink to {} leaves the destination", target.display()),
+            );
+            return Ok(());
+        }
+        if let Err(reason) = self.prepare_parents(&relative) {
+            self.fail(&entry.name, reason);
+            return Ok(());
+        }
+
+        let mut target = self.destination.join(&relative);
+        let mut replace = false;
+        if let Ok(existing) = fs::symlink_metadata(&target) {
+            let is_dir = matches!(entry.kind, Kind::Dir);
+            let conflict = Conflict {
+                source: ConflictSide {
+                    path: self.archive.join(&name),
+                    size: entry.size,
+                    modified: entry.modified,
+                    is_dir,
+                },
+                target: ConflictSide::new(target.clone(), &existing),
+            };
+            let resolution = match self.resolver.resolve(&conflict, &mut *self.observer) {
+                Ok(resolution) => resolution,
+                Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                Err(e) => {
+                    self.observer.failed(&target, &e);
+                    self.report.errors += 1;
+                    ConflictResolution::Skip
+                }
+            };
+            match resolution {
+                ConflictResolution::Merge => {
+                    self.count_top_level(&relative);
+                    return Ok(());
+                }
+                ConflictResolution::Overwrite => replace = true,
+                ConflictResolution::KeepBoth => {
+                    target = utils::path::unique_name(&target);
+                    if is_dir && let Ok(moved) = target.strip_prefix(&self.destination) {
+                        self.renamed.insert(name.clone(), Some(moved.to_path_buf()));
+                    }
+                }
+                _ => {
+                    if is_dir {
+                        self.renamed.insert(name.clone(), None);
+                    }
+                    self.report.skipped += 1;
+                    return Ok(());
+                }
+            }
+        }
+
+        let result = match &entry.kind {
+            Kind::Dir => self.create_dir(&target, replace, entry.mode),
+            Kind::Symlink(link) => self.create_symlink(&target, link, replace),
+            Kind::File => self.create_file(&target, replace, &entry, content),
+        };
+        match result {
+            Ok(()) => {
+                if let Ok(relative) = target.strip_prefix(&self.destination) {
+                    let relative = relative.to_path_buf();
+                    self.count_top_level(&relative);
+                }
+            }
+            Err(e @ (CoreError::Cancelled | CoreError::Refused(_))) => return Err(e),
+            Err(e) => {
+                self.observer.failed(&target, &e);
+                self.report.errors += 1;
+            }
+        }
+        self.tracker.file_done();
+        self.report_progress(&target);
+        Ok(())
+    }
+
+    /// Destination path of `name` after skipped and renamed directories
+    fn relocate(&self, name: &Path) -> Option<PathBuf> {
+        let mut ancestor = name.parent();
+        while let Some(dir) = ancestor {
+            match self.renamed.get(dir) {
+                Some(Some(moved)) => return Some(moved.join(name.strip_prefix(dir).ok()?)),
+                Some(None) => return None,
+                None => ancestor = dir.parent(),
+            }
+        }
+        Some(name.to_path_buf())
+    }
+
+    /// Create missing parent directories, refusing to pass through symlinks
+    /// or anything but a directory
+    fn prepare_parents(&mut self, relative: &Path) -> Result<(), String> {
+        let Some(parent) = relative.parent() else {
+            return Ok(());
+        };
+        let mut dir = self.destination.clone();
+        for component in parent.components() {
+            dir.push(component);
+            match fs::symlink_metadata(&dir) {
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    return Err(format!("{} is a symlink", dir.display()));
+                }
+                Ok(meta) if !meta.is_dir() => {
+                    return Err(format!("{} is not a directory", dir.display()));
+                }
+                Ok(_) => {}
+                Err(_) => fs::create_dir(&dir).map_err(|e| e.to_string())?,
+            }
+        }
+        Ok(())
+    }
+
+    fn create_dir(
+        &mut self,
+        target: &Path,
+        replace: bool,
+        mode: Option<u32>,
+    ) -> Result<(), CoreError> {
+        let io_error = |e: io::Error| CoreError::from_io_error(e, target.to_path_buf());
+        if replace {
+            remove(target).map_err(io_error)?;
+        }
+        fs::create_dir(target).map_err(io_error)?;
+        if let Some(mode) = mode {
+            self.modes.push((target.to_path_buf(), mode & 0o777));
+        }
+        Ok(())
+    }
+
+    fn create_symlink(
+        &mut self,
+        target: &Path,
+        link: &Path,
+        replace: bool,
+    ) -> Result<(), CoreError> {
+        let io_error = |e: io::Error| CoreError::from_io_error(e, target.to_path_buf());
+        if replace {
+            remove(target).map_err(io_error)?;
+        }
+        std::os::unix::fs::symlink(link, target).map_err(io_error)
+    }
+
+    fn create_file(
+        &mut self,
+        target: &Path,
+        replace: bool,
+        entry: &Entry,
+        content: &mut dyn Read,
+    ) -> Result<(), CoreError> {
+        let part = part_path(target);
+        let io_error = |e: io::Error| CoreError::from_io_error(e, part.clone());
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&part)
+            .map_err(io_error)?;
+        let result = self.fill(file, target, content).and_then(|file| {
+            // Never restore setuid/setgid/sticky bits from an archive
+            let mode = entry.mode.unwrap_or(0o644) & 0o777;
+            file.set_permissions(Permissions::from_mode(mode))
+                .map_err(io_error)?;
+            if let Some(modified) = entry.modified {
+                let _ = file.set_modified(modified);
+            }
+            if replace {
+                let is_dir = fs::symlink_metadata(target).is_ok_and(|m| m.is_dir());
+                if is_dir {
+                    fs::remove_dir_all(target).map_err(io_error)?;
+                }
+            } else if fs::symlink_metadata(target).is_ok() {
+                // Showed up while this entry was written
+                return Err(CoreError::InvalidPath(format!(
+                    "{} already exists",
+                    target.display()
+                )));
+            }
+            fs::rename(&part, target).map_err(|e| CoreError::from_io_error(e, target.to_path_buf()))
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&part);
+        }
+        result
+    }
+
+    /// Copy entry content into `file`, enforcing the limits as bytes arrive
+    fn fill(
+        &mut self,
+        mut file: File,
+        target: &Path,
+        content: &mut dyn Read,
+    ) -> Result<File, CoreError> {
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            self.control.checkpoint()?;
+            let n = match content.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(self.corrupt(e)),
+            };
+            self.written += n as u64;
+            self.check_limits()?;
+            file.write_all(&buf[..n])
+                .map_err(|e| CoreError::from_io_error(e, target.to_path_buf()))?;
+            if self.consumed.is_none() {
+                self.tracker.add_bytes(n as u64, Instant::now());
+            }
+            self.report_progress(target);
+        }
+        Ok(file)
+    }
+
+    fn check_limits(&self) -> Result<(), CoreError> {
+        if self.written > self.limits.max_bytes {
+            return Err(CoreError::Refused(format!(
+                "archive expands to more than {} bytes",
+                self.limits.max_bytes
+            )));
+        }
+        if self.written > RATIO_GRACE
+            && self.written / self.archive_len.max(1) > self.limits.max_ratio
+        {
+            return Err(CoreError::Refused(format!(
+                "archive expands more than {} times its size",
+                self.limits.max_ratio
+            )));
+        }
+        Ok(())
+    }
+
+    fn report_progress(&mut self, current: &Path) {
+        if let Some(consumed) = &self.consumed {
+            let consumed = consumed.load(Ordering::Relaxed);
+            let delta = consumed.saturating_sub(self.tracker.bytes_done);
+            self.tracker.add_bytes(delta, Instant::now());
+        }
+        self.observer.progress(&self.tracker, current);
+    }
+
+    /// Remember the top-level item `relative` lives under
+    fn count_top_level(&mut self, relative: &Path) {
+        let Some(first) = relative.components().next() else {
+            return;
+        };
+        let top = self.destination.join(first);
+        if !self.report.created.contains(&top) {
+            self.report.created.push(top);
+        }
+    }
+
+    fn restore_modes(&mut self) {
+        for (dir, mode) in self.modes.iter().rev() {
+            let _ = fs::set_permissions(dir, Permissions::from_mode(*mode));
+        }
+    }
+
+    fn fail(&mut self, name: &Path, reason: String) {
+        let error = CoreError::InvalidPath(format!("{}: {reason}", name.display()));
+        self.observer.failed(&self.archive.join(name), &error);
+        self.report.errors += 1;
+    }
+
+    fn corrupt(&self, e: impl std::fmt::Display) -> CoreError {
+        CoreError::Io {
+            path: self.archive.to_path_buf(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Entry name as a plain relative path, or why it is refused
+fn sanitize(name: &Path) -> Result<PathBuf, String> {
+    let bytes = name.as_os_str().as_bytes();
+    if bytes.get(1) == Some(&b':') && bytes[0].is_ascii_alphabetic() {
+        return Err("has a drive prefix".to_string());
+    }
+    let mut clean = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => return Err("climbs out of the destination".to_string()),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err("is an absolute path".to_string());
+            }
+        }
+    }
+    Ok(clean)
+}
+
+/// Whether a link at `relative` pointing to `target` resolves inside the
+/// destination, judged on the names alone
+fn link_stays_inside(relative: &Path, target: &Path) -> bool {
+    let mut depth = relative.components().count().saturating_sub(1);
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+    !target.as_os_str().is_empty()
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(_) => Ok(()),
+    }
+}
+
+fn part_path(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    target.with_file_name(format!(".{name}.{}.filer-part", std::process::id()))
+}
@@ -0,0 +1,139 @@
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::transfer::ConflictPolicy;
+
+/// Supported archive containers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// Guess from the file name (`.zip`, `.tar`, `.tar.gz`/`.tgz`, `.tar.zst`/`.tzst`)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        [
+            (".zip", ArchiveFormat::Zip),
+            (".tar", ArchiveFormat::Tar),
+            (".tar.gz", ArchiveFormat::TarGz),
+            (".tgz", ArchiveFormat::TarGz),
+            (".tar.zst", ArchiveFormat::TarZst),
+            (".tzst", ArchiveFormat::TarZst),
+        ]
+        .into_iter()
+        .find(|(suffix, _)| name.ends_with(suffix))
+        .map(|(_, format)| format)
+    }
+
+    /// Recognize the format from the first bytes of the file
+    pub(crate) fn sniff(mut reader: impl Read) -> Option<Self> {
+        let mut head = [0u8; 512];
+        let mut len = 0;
+        while len < head.len() {
+            match reader.read(&mut head[len..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => len += n,
+            }
+        }
+        let head = &head[..len];
+        if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else if head.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(ArchiveFormat::TarZst)
+        } else if head.get(257..262) == Some(b"ustar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// File name suffix, including the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => ".zip",
+            ArchiveFormat::Tar => ".tar",
+            ArchiveFormat::TarGz => ".tar.gz",
+            ArchiveFormat::TarZst => ".tar.zst",
+        }
+    }
+
+    /// `level` clamped to what the codec accepts, or its default
+    pub(crate) fn level(self, level: Option<i32>) -> i32 {
+        let (min, max, default) = match self {
+            ArchiveFormat::Zip | ArchiveFormat::TarGz => (0, 9, 6),
+            ArchiveFormat::Tar => (0, 0, 0),
+            ArchiveFormat::TarZst => (1, 22, 3),
+        };
+        level.map_or(default, |level| level.clamp(min, max))
+    }
+}
+
+/// How symbolic links among the sources are archived
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymlinkMode {
+    /// Store the link itself
+    #[default]
+    Store,
+    /// Store what the link points to
+    Follow,
+}
+
+/// Settings for creating an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveOptions {
+    pub format: ArchiveFormat,
+    /// Compression level (codec default if `None`; clamped to its range)
+    pub level: Option<i32>,
+    pub symlinks: SymlinkMode,
+    /// What to do if the archive file already exists
+    pub conflict: ConflictPolicy,
+}
+
+impl ArchiveOptions {
+    pub fn new(format: ArchiveFormat) -> Self {
+        Self {
+            format,
+            level: None,
+            symlinks: SymlinkMode::default(),
+            conflict: ConflictPolicy::default(),
+        }
+    }
+}
+
+/// Guards against archive bombs; extraction stops when one is exceeded
+///
+/// Sizes are counted as content is written, not taken from the headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractLimits {
+    /// Total bytes written
+    pub max_bytes: u64,
+    pub max_entries: u64,
+    /// Bytes written per byte of archive (checked past the first MiB)
+    pub max_ratio: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024 * 1024 * 1024,
+            max_entries: 1_000_000,
+            max_ratio: 1_000,
+        }
+    }
+}
+
+/// Settings for extracting an archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractOptions {
+    /// What to do with entries whose target already exists
+    pub conflict: ConflictPolicy,
+    pub limits: ExtractLimits,
+}
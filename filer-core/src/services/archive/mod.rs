@@ -0,0 +1,13 @@
+//! Archive creation and extraction as file operations
+//!
+//! - `Compress`: packs a selection into a zip or (compressed) tar
+//! - `Extract`: unpacks an archive with path-traversal and bomb protection
+//! - `ArchiveFormat`: supported containers, recognized by name or content
+
+mod create;
+mod extract;
+mod format;
+
+pub use create::Compress;
+pub use extract::Extract;
+pub use format::{ArchiveFormat, ArchiveOptions, ExtractLimits, ExtractOptions, SymlinkMode};
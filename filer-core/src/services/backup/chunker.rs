@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// Random values for the gear hash, one per byte value
+const GEAR: [u64; 256] = gear_table();
+
+/// SplitMix64 over a fixed seed, so chunk boundaries never change
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x6a09_e667_f3bc_c908;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Chunk size limits of a repository
+///
+/// Fixed when the repository is created: other limits cut the same data
+/// differently and nothing would deduplicate against older snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkerConfig {
+    pub min_size: usize,
+    /// Rounded up to a power of two
+    pub avg_size: usize,
+    pub max_size: usize,
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        Self {
+            min_size: 512 * 1024,
+            avg_size: 1024 * 1024,
+            max_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+impl ChunkerConfig {
+    pub(crate) fn is_valid(&self) -> bool {
+        0 < self.min_size && self.min_size <= self.avg_size && self.avg_size <= self.max_size
+    }
+
+    /// Length of the chunk at the start of `data`
+    ///
+    /// Boundaries depend only on the bytes before them, so an insertion
+    /// shifts the chunks around it instead of every chunk after it.
+    /// `None` if `data` is too short to tell; with `eof` the rest is the
+    /// last chunk.
+    pub(crate) fn cut(&self, data: &[u8], eof: bool) -> Option<usize> {
+        let mask = self.avg_size.next_power_of_two() as u64 - 1;
+        let end = data.len().min(self.max_size);
+        if data.len() > self.min_size {
+            // Only the last 64 bytes affect the hash
+            let mut hash: u64 = 0;
+            for (i, byte) in data[..end]
+                .iter()
+                .enumerate()
+                .skip(self.min_size.saturating_sub(64))
+            {
+                hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+                if i + 1 >= self.min_size && hash & mask == 0 {
+                    return Some(i + 1);
+                }
+            }
+        }
+        if data.len() >= self.max_size {
+            Some(self.max_size)
+        } else if eof && !data.is_empty() {
+            Some(data.len())
+        } else {
+            None
+        }
+    }
+}
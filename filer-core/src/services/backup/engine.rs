@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+use crate::vfs::provider::FsProvider;
+
+use super::{ChunkRef, Repository, Snapshot, SnapshotEntry};
+
+/// Bytes read from the source per request
+const READ_SIZE: usize = 1024 * 1024;
+
+/// Result of a backup run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupReport {
+    /// ID of the snapshot written
+    pub snapshot: String,
+    pub files: usize,
+    pub dirs: usize,
+    /// Total size of the files in the snapshot
+    pub bytes: u64,
+    /// Files taken over from the parent snapshot without reading them
+    pub unchanged: usize,
+    pub bytes_read: u64,
+    /// Chunks the repository did not hold yet
+    pub new_chunks: usize,
+    /// Bytes written for them, after compression
+    pub bytes_stored: u64,
+    /// Number of items that could not be backed up
+    pub errors: usize,
+}
+
+/// Backs up files and directories from a provider into a `Repository`
+///
+/// Files are split into content-defined chunks and only chunks the
+/// repository does not hold are stored. Files whose size and modification
+/// time match the latest snapshot of the same sources are not read again.
+/// Each source appears in the snapshot under its own name.
+pub struct Backup {
+    source: Arc<dyn FsProvider>,
+    repository: Repository,
+    read_size: usize,
+}
+
+impl Backup {
+    pub fn new(source: Arc<dyn FsProvider>, repository: Repository) -> Self {
+        Self {
+            source,
+            repository,
+            read_size: READ_SIZE,
+        }
+    }
+
+    pub fn with_read_size(mut self, read_size: usize) -> Self {
+        self.read_size = read_size.max(1);
+        self
+    }
+
+    pub fn repository(&self) -> &Repository {
+        &self.repository
+    }
+
+    /// Back up `sources` as a new snapshot
+    pub async fn run(
+        &self,
+        sources: &[PathBuf],
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<BackupReport, CoreError> {
+        if sources.is_empty() {
+            return Err(CoreError::InvalidInput);
+        }
+        let mut names = HashSet::new();
+        for source in sources {
+            let name = source.file_name().ok_or_else(|| {
+                CoreError::InvalidPath(format!("{} has no name", source.display()))
+            })?;
+            if !names.insert(name) {
+                return Err(CoreError::Refused(format!(
+                    "two sources are named {}",
+                    name.to_string_lossy()
+                )));
+            }
+        }
+
+        let mut report = BackupReport::default();
+        let items = self
+            .walk(sources, control, observer, &mut report.errors)
+            .await?;
+        let previous = self.repository.snapshots().await?;
+        let parent = previous
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.sources == sources);
+        let earlier: HashMap<&Path, &SnapshotEntry> = parent
+            .iter()
+            .flat_map(|snapshot| &snapshot.entries)
+            .map(|entry| (entry.path.as_path(), entry))
+            .collect();
+        let mut known = self.repository.chunk_ids().await?;
+
+        let reusable = |entry: &SnapshotEntry| {
+            earlier.get(entry.path.as_path()).filter(|old| {
+                !old.is_dir
+                    && old.size == entry.size
+                    && old.modified.is_some()
+                    && old.modified == entry.modified
+                    && old.chunks.iter().all(|chunk| known.contains(&chunk.id))
+            })
+        };
+        let files: Vec<&SnapshotEntry> = items
+            .iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| !entry.is_dir)
+            .collect();
+        let mut tracker = ProgressTracker::new(
+            files
+                .iter()
+                .filter(|entry| reusable(entry).is_none())
+                .map(|entry| entry.size)
+                .sum(),
+            files.len() as u64,
+        );
+        let reused: Vec<Option<Vec<ChunkRef>>> = items
+            .iter()
+            .map(|(_, entry)| reusable(entry).map(|old| old.chunks.clone()))
+            .collect();
+
+        let mut entries = Vec::with_capacity(items.len());
+        for ((path, mut entry), reused) in items.into_iter().zip(reused) {
+            control.checkpoint_async().await?;
+            if entry.is_dir {
+                report.dirs += 1;
+                entries.push(entry);
+                continue;
+            }
+            match reused {
+                Some(chunks) => {
+                    entry.chunks = chunks;
+                    report.unchanged += 1;
+                }
+                None => match self
+                    .store(
+                        &path,
+                        &mut known,
+                        control,
+                        &mut tracker,
+                        observer,
+                        &mut report,
+                    )
+                    .await
+                {
+                    Ok(chunks) => {
+                        entry.size = chunks.iter().map(|chunk| chunk.size).sum();
+                        entry.chunks = chunks;
+                    }
+                    Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                    Err(e) => {
+                        observer.failed(&path, &e);
+                        report.errors += 1;
+                        tracker.file_done();
+                        continue;
+                    }
+                },
+            }
+            report.files += 1;
+            report.bytes += entry.size;
+            entries.push(entry);
+            tracker.file_done();
+            observer.progress(&tracker, &path);
+        }
+
+        let time = SystemTime::now();
+        let snapshot = Snapshot {
+            id: snapshot_id(time, sources),
+            time,
+            sources: sources.to_vec(),
+            parent: parent.map(|snapshot| snapshot.id.clone()),
+            entries,
+        };
+        control.checkpoint_async().await?;
+        self.repository.save_snapshot(&snapshot).await?;
+        report.snapshot = snapshot.id;
+        Ok(report)
+    }
+
+    /// Every item under `sources`, parents first, with their snapshot paths
+    async fn walk(
+        &self,
+        sources: &[PathBuf],
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+        errors: &mut usize,
+    ) -> Result<Vec<(PathBuf, SnapshotEntry)>, CoreError> {
+        let mut items = Vec::new();
+        let mut pending: Vec<(PathBuf, PathBuf)> = sources
+            .iter()
+            .rev()
+            .map(|source| {
+                (
+                    source.clone(),
+                    PathBuf::from(source.file_name().unwrap_or_default()),
+                )
+            })
+            .collect();
+        while let Some((path, relative)) = pending.pop() {
+            control.checkpoint_async().await?;
+            let node = match self.source.metadata(&path).await {
+                Ok(node) => node,
+                Err(e) => {
+                    observer.failed(&path, &e);
+                    *errors += 1;
+                    continue;
+                }
+            };
+            let is_dir = node.is_dir();
+            if is_dir {
+                match self.source.list(&path).await {
+                    Ok(mut children) => {
+                        children.sort_by(|a, b| b.name.cmp(&a.name));
+                        pending.extend(
+                            children
+                                .into_iter()
+                                .map(|child| (path.join(&child.name), relative.join(&child.name))),
+                        );
+                    }
+                    Err(e) => {
+                        observer.failed(&path, &e);
+                        *errors += 1;
+                        continue;
+                    }
+                }
+            }
+            let entry = SnapshotEntry {
+                path: relative,
+                is_dir,
+                size: if is_dir { 0 } else { node.size },
+                modified: node.modified,
+                permissions: node.meta.permissions,
+                chunks: Vec::new(),
+            };
+            items.push((path, entry));
+        }
+        Ok(items)
+    }
+
+    /// Chunk the file at `path` and store the chunks the repository lacks
+    async fn store(
+        &self,
+        path: &Path,
+        known: &mut HashSet<String>,
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut (dyn TransferObserver + Send),
+        report: &mut BackupReport,
+    ) -> Result<Vec<ChunkRef>, CoreError> {
+        let chunker = self.repository.chunker();
+        let mut chunks = Vec::new();
+        let mut buffer = Vec::new();
+        let mut offset = 0;
+        let mut eof = false;
+        loop {
+            while !eof && buffer.len() < chunker.max_size {
+                control.checkpoint_async().await?;
+                let data = self
+                    .source
+                    .read_range(path, offset, self.read_size as u64)
+                    .await?;
+                if data.is_empty() {
+                    eof = true;
+                    break;
+                }
+                offset += data.len() as u64;
+                report.bytes_read += data.len() as u64;
+                tracker.add_bytes(data.len() as u64, Instant::now());
+                observer.progress(tracker, path);
+                buffer.extend_from_slice(&data);
+            }
+            let Some(cut) = chunker.cut(&buffer, eof) else {
+                break;
+            };
+            let id = self.repository.chunk_id(&buffer[..cut]);
+            if !known.contains(&id) {
+                report.bytes_stored += self.repository.write_chunk(&id, &buffer[..cut]).await?;
+                report.new_chunks += 1;
+                known.insert(id.clone());
+            }
+            chunks.push(ChunkRef {
+                id,
+                size: cut as u64,
+            });
+            buffer.drain(..cut);
+        }
+        Ok(chunks)
+    }
+}
+
+/// Creation time in UTC and a hash that tells same-second snapshots apart
+fn snapshot_id(time: SystemTime, sources: &[PathBuf]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    let nanos = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    hasher.update(&nanos.to_le_bytes());
+    for source in sources {
+        hasher.update(source.as_os_str().as_encoded_bytes());
+    }
+    let stamp = chrono::DateTime::<chrono::Utc>::from(time).format("%Y%m%d-%H%M%S");
+    format!("{stamp}-{}", &hasher.finalize().to_hex()[..8])
+}
@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+
+use super::{ChunkRef, Repository, RetentionPolicy};
+
+/// Result of applying a retention policy
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub kept: Vec<String>,
+    /// Snapshots removed, or that would be with `dry_run`
+    pub removed: Vec<String>,
+    /// Chunks no kept snapshot refers to any more
+    pub chunks_removed: usize,
+    pub dry_run: bool,
+    /// Number of snapshots or chunks that could not be removed
+    pub errors: usize,
+}
+
+/// Result of an integrity check
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckReport {
+    pub snapshots: usize,
+    pub chunks: usize,
+    /// Chunks a snapshot refers to that are not stored
+    pub missing: Vec<String>,
+    /// Chunks that did not decode to their hash (only checked with
+    /// `read_data`)
+    pub damaged: Vec<String>,
+    /// Stored chunks no snapshot refers to
+    pub unreferenced: usize,
+    /// Number of snapshots or chunks that could not be read
+    pub errors: usize,
+}
+
+impl CheckReport {
+    /// Every snapshot can be restored in full
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.damaged.is_empty() && self.errors == 0
+    }
+}
+
+impl Repository {
+    /// Remove the snapshots `policy` does not keep, then the chunks only
+    /// they referred to
+    ///
+    /// Refuses a policy that keeps nothing. Any snapshot that cannot be
+    /// read stops the prune, since its chunks cannot be told apart.
+    pub async fn prune(
+        &self,
+        policy: &RetentionPolicy,
+        dry_run: bool,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<PruneReport, CoreError> {
+        if policy.keeps_nothing() {
+            return Err(CoreError::Refused(
+                "the retention policy keeps no snapshots".to_string(),
+            ));
+        }
+        let snapshots = self.snapshots().await?;
+        let keep = policy.select(&snapshots);
+        let mut report = PruneReport {
+            dry_run,
+            ..Default::default()
+        };
+        let mut referenced = HashSet::new();
+        for snapshot in &snapshots {
+            control.checkpoint_async().await?;
+            let removed = if keep.contains(&snapshot.id) {
+                false
+            } else if dry_run {
+                true
+            } else {
+                match self.remove_snapshot(&snapshot.id).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        observer.failed(&self.snapshot_path(&snapshot.id)?, &e);
+                        report.errors += 1;
+                        false
+                    }
+                }
+            };
+            if removed {
+                report.removed.push(snapshot.id.clone());
+            } else {
+                report.kept.push(snapshot.id.clone());
+                referenced.extend(snapshot.chunk_ids().map(str::to_string));
+            }
+        }
+
+        let mut unreferenced: Vec<String> = self
+            .chunk_ids()
+            .await?
+            .into_iter()
+            .filter(|id| !referenced.contains(id))
+            .collect();
+        unreferenced.sort();
+        for id in unreferenced {
+            control.checkpoint_async().await?;
+            if dry_run {
+                report.chunks_removed += 1;
+                continue;
+            }
+            match self.remove_chunk(&id).await {
+                Ok(()) => report.chunks_removed += 1,
+                Err(e) => {
+                    observer.failed(&self.chunk_path(&id), &e);
+                    report.errors += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Check that every chunk a snapshot refers to is stored
+    ///
+    /// With `read_data` every referenced chunk is also read back,
+    /// decrypted and hashed.
+    pub async fn check(
+        &self,
+        read_data: bool,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<CheckReport, CoreError> {
+        let mut report = CheckReport::default();
+        let mut referenced: Vec<ChunkRef> = Vec::new();
+        let mut seen = HashSet::new();
+        for id in self.snapshot_ids().await? {
+            control.checkpoint_async().await?;
+            match self.snapshot(&id).await {
+                Ok(snapshot) => {
+                    report.snapshots += 1;
+                    for chunk in snapshot.entries.into_iter().flat_map(|entry| entry.chunks) {
+                        if seen.insert(chunk.id.clone()) {
+                            referenced.push(chunk);
+                        }
+                    }
+                }
+                Err(e) => {
+                    observer.failed(&self.snapshot_path(&id)?, &e);
+                    report.errors += 1;
+                }
+            }
+        }
+
+        let stored = self.chunk_ids().await?;
+        report.chunks = stored.len();
+        report.unreferenced = stored.iter().filter(|id| !seen.contains(*id)).count();
+        referenced.sort_by(|a, b| a.id.cmp(&b.id));
+        let (present, missing): (Vec<ChunkRef>, Vec<ChunkRef>) = referenced
+            .into_iter()
+            .partition(|chunk| stored.contains(&chunk.id));
+        report.missing = missing.into_iter().map(|chunk| chunk.id).collect();
+        if !read_data {
+            return Ok(report);
+        }
+
+        let bytes = present.iter().map(|chunk| chunk.size).sum();
+        let mut tracker = ProgressTracker::new(bytes, present.len() as u64);
+        for chunk in present {
+            control.checkpoint_async().await?;
+            let path = self.chunk_path(&chunk.id);
+            match self.read_chunk(&chunk).await {
+                Ok(_) => {}
+                Err(CoreError::InvalidData) => report.damaged.push(chunk.id),
+                Err(e) => {
+                    observer.failed(&path, &e);
+                    report.errors += 1;
+                }
+            }
+            tracker.add_bytes(chunk.size, Instant::now());
+            tracker.file_done();
+            observer.progress(&tracker, &path);
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,24 @@
+//! Incremental, deduplicated and versioned backups
+//!
+//! - `Repository`: chunk store and snapshot list on any `FsProvider`,
+//!   compressed and optionally encrypted with a `Cipher`; prunes by a
+//!   `RetentionPolicy` and checks its own integrity
+//! - `Backup`: splits files into content-defined chunks and records a
+//!   `Snapshot`, storing only chunks the repository lacks
+//! - `RetentionPolicy`: keep the last N and daily/weekly/monthly snapshots
+//!
+//! `vfs::snapshot::SnapshotFs` browses the snapshots as directories.
+
+mod chunker;
+mod engine;
+mod maintenance;
+mod repository;
+mod retention;
+mod snapshot;
+
+pub use chunker::ChunkerConfig;
+pub use engine::{Backup, BackupReport};
+pub use maintenance::{CheckReport, PruneReport};
+pub use repository::Repository;
+pub use retention::RetentionPolicy;
+pub use snapshot::{ChunkRef, Snapshot, SnapshotEntry};
@@ -0,0 +1,422 @@
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+#[cfg(feature = "crypto")]
+use crate::services::crypto::{Cipher, CipherAlgorithm, EncryptedData};
+use crate::vfs::provider::{FsProvider, Precondition};
+
+use super::{ChunkRef, ChunkerConfig, Snapshot, SnapshotEntry};
+
+const CONFIG: &str = "config.json";
+const CHUNKS: &str = "chunks";
+const SNAPSHOTS: &str = "snapshots";
+const VERSION: u32 = 1;
+/// Encrypted into the config to tell a wrong key from damaged data
+const KEY_CHECK: &[u8] = b"filer backup repository";
+const ID_KEY_LEN: usize = 32;
+
+/// Leading byte of every stored object
+const PLAIN: u8 = 0;
+#[cfg(feature = "crypto")]
+const ENCRYPTED: u8 = 1;
+
+/// `config.json`, the only object that is never compressed or encrypted
+#[derive(Debug, Serialize, Deserialize)]
+struct RepoConfig {
+    version: u32,
+    chunker: ChunkerConfig,
+    encrypted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_check: Option<Vec<u8>>,
+    /// Sealed key for chunk IDs of an encrypted repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id_key: Option<Vec<u8>>,
+}
+
+/// Deduplicated chunk store and snapshot list on any `FsProvider`
+///
+/// Layout under the root: `config.json`, `chunks/<ab>/<id>` with each
+/// chunk stored once under the BLAKE3 of its content, and
+/// `snapshots/<id>`. An encrypted repository keys that hash with a secret
+/// sealed in the config, so chunk IDs reveal nothing about the content. Chunks and snapshots are compressed with zstd and,
+/// with a cipher, encrypted. Chunks are written before the snapshot that
+/// refers to them, so an interrupted backup leaves only unreferenced
+/// chunks behind for `prune` to remove.
+pub struct Repository {
+    provider: Arc<dyn FsProvider>,
+    root: PathBuf,
+    chunker: ChunkerConfig,
+    encrypted: bool,
+    id_key: Option<[u8; ID_KEY_LEN]>,
+    #[cfg(feature = "crypto")]
+    cipher: Option<Cipher>,
+}
+
+impl Repository {
+    /// A repository at `root`; call `init` or `open` before use
+    pub fn new(provider: Arc<dyn FsProvider>, root: PathBuf) -> Self {
+        Self {
+            provider,
+            root,
+            chunker: ChunkerConfig::default(),
+            encrypted: false,
+            id_key: None,
+            #[cfg(feature = "crypto")]
+            cipher: None,
+        }
+    }
+
+    /// Chunk limits for `init`; an opened repository keeps its own
+    pub fn with_chunker(mut self, chunker: ChunkerConfig) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    /// Encrypt a new repository, or the key to open an encrypted one
+    #[cfg(feature = "crypto")]
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Create an empty repository; fails if `root` already holds one
+    pub async fn init(mut self) -> Result<Self, CoreError> {
+        if self.read_config().await?.is_some() {
+            return Err(CoreError::Refused(format!(
+                "{} already holds a backup repository",
+                self.root.display()
+            )));
+        }
+        if !self.chunker.is_valid() {
+            return Err(CoreError::InvalidInput);
+        }
+        self.encrypted = self.has_cipher();
+        let (key_check, id_key) = match self.encrypted {
+            true => {
+                let key = random_key()?;
+                self.id_key = Some(key);
+                (Some(self.seal(KEY_CHECK)?), Some(self.seal(&key)?))
+            }
+            false => (None, None),
+        };
+        let config = RepoConfig {
+            version: VERSION,
+            chunker: self.chunker,
+            encrypted: self.encrypted,
+            key_check,
+            id_key,
+        };
+        for dir in [
+            &self.root,
+            &self.root.join(CHUNKS),
+            &self.root.join(SNAPSHOTS),
+        ] {
+            self.provider.create_dir(dir).await?;
+        }
+        let data = serde_json::to_vec_pretty(&config).map_err(|_| CoreError::InvalidData)?;
+        self.provider
+            .write(&self.root.join(CONFIG), &data, &Precondition::absent())
+            .await?;
+        Ok(self)
+    }
+
+    /// Open an existing repository
+    pub async fn open(mut self) -> Result<Self, CoreError> {
+        let Some(config) = self.read_config().await? else {
+            return Err(CoreError::NotFound(self.root.join(CONFIG)));
+        };
+        self.load(config)?;
+        Ok(self)
+    }
+
+    /// Open the repository at the root, creating it if there is none
+    pub async fn open_or_init(mut self) -> Result<Self, CoreError> {
+        match self.read_config().await? {
+            Some(config) => {
+                self.load(config)?;
+                Ok(self)
+            }
+            None => self.init().await,
+        }
+    }
+
+    async fn read_config(&self) -> Result<Option<RepoConfig>, CoreError> {
+        match self.provider.read(&self.root.join(CONFIG)).await {
+            Ok(data) => serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(|_| CoreError::InvalidData),
+            Err(CoreError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn load(&mut self, config: RepoConfig) -> Result<(), CoreError> {
+        let root = self.root.display();
+        if config.version != VERSION {
+            return Err(CoreError::Refused(format!(
+                "{root} has repository version {}",
+                config.version
+            )));
+        }
+        #[cfg(feature = "crypto")]
+        match (config.encrypted, &self.cipher) {
+            (true, Some(_)) => {
+                let check = config.key_check.as_deref().unwrap_or_default();
+                if !matches!(self.unseal(check), Ok(data) if data == KEY_CHECK) {
+                    return Err(CoreError::Refused(format!("wrong key for {root}")));
+                }
+                let sealed = config.id_key.as_deref().ok_or(CoreError::InvalidData)?;
+                let key = self.unseal(sealed)?;
+                self.id_key = Some(key.try_into().map_err(|_| CoreError::InvalidData)?);
+            }
+            (false, Some(_)) => {
+                return Err(CoreError::Refused(format!("{root} is not encrypted")));
+            }
+            _ => {}
+        }
+        if config.encrypted && !self.has_cipher() {
+            return Err(CoreError::Refused(format!(
+                "{root} is encrypted; a key is needed"
+            )));
+        }
+        self.chunker = config.chunker;
+        self.encrypted = config.encrypted;
+        Ok(())
+    }
+
+    #[cfg(feature = "crypto")]
+    fn has_cipher(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    #[cfg(not(feature = "crypto"))]
+    fn has_cipher(&self) -> bool {
+        false
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn chunker(&self) -> ChunkerConfig {
+        self.chunker
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Compress, then encrypt if the repository is encrypted
+    fn seal(&self, data: &[u8]) -> Result<Vec<u8>, CoreError> {
+        let compressed = zstd::bulk::compress(data, 3).map_err(|_| CoreError::InvalidData)?;
+        #[cfg(feature = "crypto")]
+        if self.encrypted {
+            let cipher = self.cipher.as_ref().ok_or(CoreError::InvalidInput)?;
+            return Ok(encode(&cipher.encrypt(&compressed)?));
+        }
+        let mut object = Vec::with_capacity(compressed.len() + 1);
+        object.push(PLAIN);
+        object.extend_from_slice(&compressed);
+        Ok(object)
+    }
+
+    fn unseal(&self, object: &[u8]) -> Result<Vec<u8>, CoreError> {
+        let (&format, body) = object.split_first().ok_or(CoreError::InvalidData)?;
+        let compressed = match format {
+            PLAIN => body.to_vec(),
+            #[cfg(feature = "crypto")]
+            ENCRYPTED => {
+                let cipher = self.cipher.as_ref().ok_or(CoreError::InvalidInput)?;
+                cipher.decrypt(&decode(body)?)?
+            }
+            _ => return Err(CoreError::InvalidData),
+        };
+        zstd::decode_all(compressed.as_slice()).map_err(|_| CoreError::InvalidData)
+    }
+
+    /// ID of a chunk with this content
+    pub(crate) fn chunk_id(&self, data: &[u8]) -> String {
+        match &self.id_key {
+            Some(key) => blake3::keyed_hash(key, data).to_hex().to_string(),
+            None => blake3::hash(data).to_hex().to_string(),
+        }
+    }
+
+    pub(crate) fn chunk_path(&self, id: &str) -> PathBuf {
+        self.root.join(CHUNKS).join(&id[..2.min(id.len())]).join(id)
+    }
+
+    pub(crate) fn snapshot_path(&self, id: &str) -> Result<PathBuf, CoreError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(CoreError::NotFound(self.root.join(SNAPSHOTS).join(id)));
+        }
+        Ok(self.root.join(SNAPSHOTS).join(id))
+    }
+
+    /// IDs of every stored chunk
+    pub(crate) async fn chunk_ids(&self) -> Result<HashSet<String>, CoreError> {
+        let mut ids = HashSet::new();
+        let chunks = self.root.join(CHUNKS);
+        for dir in self.provider.list(&chunks).await? {
+            if dir.is_dir() {
+                let listed = self.provider.list(&chunks.join(&dir.name)).await?;
+                ids.extend(listed.into_iter().map(|chunk| chunk.name));
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Store a chunk; returns the bytes written
+    pub(crate) async fn write_chunk(&self, id: &str, data: &[u8]) -> Result<u64, CoreError> {
+        let path = self.chunk_path(id);
+        let object = self.seal(data)?;
+        if let Some(dir) = path.parent() {
+            self.provider.create_dir(dir).await?;
+        }
+        self.provider
+            .write(&path, &object, &Precondition::default())
+            .await?;
+        Ok(object.len() as u64)
+    }
+
+    /// Content of a chunk; fails with `InvalidData` if it is damaged
+    pub async fn read_chunk(&self, chunk: &ChunkRef) -> Result<Vec<u8>, CoreError> {
+        let object = self.provider.read(&self.chunk_path(&chunk.id)).await?;
+        let data = self.unseal(&object)?;
+        if self.chunk_id(&data) != chunk.id {
+            return Err(CoreError::InvalidData);
+        }
+        Ok(data)
+    }
+
+    pub(crate) async fn remove_chunk(&self, id: &str) -> Result<(), CoreError> {
+        self.provider.remove(&self.chunk_path(id)).await
+    }
+
+    /// `len` bytes of a file in a snapshot from `start`, like `read_range`
+    pub async fn read_range(
+        &self,
+        entry: &SnapshotEntry,
+        start: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, CoreError> {
+        let end = start.saturating_add(len).min(entry.size);
+        let mut data = Vec::new();
+        let mut offset = 0;
+        for chunk in &entry.chunks {
+            let chunk_end = offset + chunk.size;
+            if chunk_end > start && offset < end {
+                let content = self.read_chunk(chunk).await?;
+                let from = start.saturating_sub(offset) as usize;
+                let to = ((end - offset) as usize).min(content.len());
+                data.extend_from_slice(&content[from.min(to)..to]);
+            }
+            if chunk_end >= end {
+                break;
+            }
+            offset = chunk_end;
+        }
+        Ok(data)
+    }
+
+    /// IDs of every snapshot, oldest first
+    pub async fn snapshot_ids(&self) -> Result<Vec<String>, CoreError> {
+        let mut ids: Vec<String> = self
+            .provider
+            .list(&self.root.join(SNAPSHOTS))
+            .await?
+            .into_iter()
+            .filter(|node| !node.is_dir())
+            .map(|node| node.name)
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    pub async fn snapshot(&self, id: &str) -> Result<Snapshot, CoreError> {
+        let object = self.provider.read(&self.snapshot_path(id)?).await?;
+        serde_json::from_slice(&self.unseal(&object)?).map_err(|_| CoreError::InvalidData)
+    }
+
+    /// Every snapshot, oldest first
+    pub async fn snapshots(&self) -> Result<Vec<Snapshot>, CoreError> {
+        let mut snapshots = Vec::new();
+        for id in self.snapshot_ids().await? {
+            snapshots.push(self.snapshot(&id).await?);
+        }
+        snapshots.sort_by_key(|snapshot| snapshot.time);
+        Ok(snapshots)
+    }
+
+    pub(crate) async fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), CoreError> {
+        let data = serde_json::to_vec(snapshot).map_err(|_| CoreError::InvalidData)?;
+        let path = self.snapshot_path(&snapshot.id)?;
+        self.provider
+            .write(&path, &self.seal(&data)?, &Precondition::absent())
+            .await
+    }
+
+    pub(crate) async fn remove_snapshot(&self, id: &str) -> Result<(), CoreError> {
+        self.provider.remove(&self.snapshot_path(id)?).await
+    }
+}
+
+/// Fresh secret for the chunk IDs of an encrypted repository
+fn random_key() -> Result<[u8; ID_KEY_LEN], CoreError> {
+    let urandom = PathBuf::from("/dev/urandom");
+    let mut key = [0; ID_KEY_LEN];
+    std::fs::File::open(&urandom)
+        .and_then(|mut file| file.read_exact(&mut key))
+        .map_err(|e| CoreError::from_io_error(e, urandom))?;
+    Ok(key)
+}
+
+/// Format byte, algorithm, nonce, tag and ciphertext
+#[cfg(feature = "crypto")]
+fn encode(encrypted: &EncryptedData) -> Vec<u8> {
+    let algorithm = match encrypted.algorithm {
+        CipherAlgorithm::Aes256Gcm => 0,
+        CipherAlgorithm::ChaCha20Poly1305 => 1,
+        CipherAlgorithm::XChaCha20Poly1305 => 2,
+    };
+    let mut object = vec![ENCRYPTED, algorithm, encrypted.nonce.len() as u8];
+    object.extend_from_slice(&encrypted.nonce);
+    object.push(encrypted.tag.len() as u8);
+    object.extend_from_slice(&encrypted.tag);
+    object.extend_from_slice(&encrypted.ciphertext);
+    object
+}
+
+#[cfg(feature = "crypto")]
+fn decode(body: &[u8]) -> Result<EncryptedData, CoreError> {
+    let (&algorithm, rest) = body.split_first().ok_or(CoreError::InvalidData)?;
+    let algorithm = match algorithm {
+        0 => CipherAlgorithm::Aes256Gcm,
+        1 => CipherAlgorithm::ChaCha20Poly1305,
+        2 => CipherAlgorithm::XChaCha20Poly1305,
+        _ => return Err(CoreError::InvalidData),
+    };
+    let (nonce, rest) = take_prefixed(rest)?;
+    let (tag, ciphertext) = take_prefixed(rest)?;
+    Ok(EncryptedData {
+        algorithm,
+        nonce: nonce.to_vec(),
+        tag: tag.to_vec(),
+        ciphertext: ciphertext.to_vec(),
+    })
+}
+
+/// Split off a field preceded by its one-byte length
+#[cfg(feature = "crypto")]
+fn take_prefixed(data: &[u8]) -> Result<(&[u8], &[u8]), CoreError> {
+    let (&len, rest) = data.split_first().ok_or(CoreError::InvalidData)?;
+    if rest.len() < len as usize {
+        return Err(CoreError::InvalidData);
+    }
+    Ok(rest.split_at(len as usize))
+}
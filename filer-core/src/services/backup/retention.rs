@@ -0,0 +1,71 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Datelike, Local};
+use serde::{Deserialize, Serialize};
+
+use super::Snapshot;
+
+/// Key of the day, week or month a snapshot falls in
+type Bucket = fn(DateTime<Local>) -> (i32, u32);
+
+/// Which snapshots `prune` keeps
+///
+/// A snapshot is kept if any rule keeps it. The daily, weekly and monthly
+/// rules keep the newest snapshot of each of the latest N days, ISO weeks
+/// and months (local time) that have one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// The N newest snapshots
+    pub keep_last: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+}
+
+impl RetentionPolicy {
+    /// A policy that would remove every snapshot
+    pub fn keeps_nothing(&self) -> bool {
+        self.keep_last == 0
+            && self.keep_daily == 0
+            && self.keep_weekly == 0
+            && self.keep_monthly == 0
+    }
+
+    /// IDs of the snapshots to keep
+    pub fn select(&self, snapshots: &[Snapshot]) -> HashSet<String> {
+        let mut newest: Vec<&Snapshot> = snapshots.iter().collect();
+        newest.sort_by_key(|snapshot| Reverse(snapshot.time));
+
+        let mut keep: HashSet<String> = newest
+            .iter()
+            .take(self.keep_last)
+            .map(|snapshot| snapshot.id.clone())
+            .collect();
+        let rules: [(usize, Bucket); 3] = [
+            (self.keep_daily, |t| (t.year(), t.ordinal())),
+            (self.keep_weekly, |t| {
+                let week = t.iso_week();
+                (week.year(), week.week())
+            }),
+            (self.keep_monthly, |t| (t.year(), t.month())),
+        ];
+        for (count, bucket) in rules {
+            let mut seen = HashSet::new();
+            for snapshot in &newest {
+                if seen.len() == count {
+                    break;
+                }
+                if seen.insert(bucket(local(snapshot.time))) {
+                    keep.insert(snapshot.id.clone());
+                }
+            }
+        }
+        keep
+    }
+}
+
+fn local(time: SystemTime) -> DateTime<Local> {
+    DateTime::<Local>::from(time)
+}
@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+/// A piece of a file's content, stored once in the repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRef {
+    /// BLAKE3 of the content, hex
+    pub id: String,
+    pub size: u64,
+}
+
+/// A file or directory in a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    /// Relative to the snapshot, starting with the source's name
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub permissions: Option<u32>,
+    /// The file's content in order; empty for directories
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<ChunkRef>,
+}
+
+/// The state of the backed-up sources at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub time: SystemTime,
+    pub sources: Vec<PathBuf>,
+    /// Snapshot of the same sources whose unchanged files were reused
+    pub parent: Option<String>,
+    /// Parents before their children
+    pub entries: Vec<SnapshotEntry>,
+}
+
+impl Snapshot {
+    pub fn entry(&self, path: &Path) -> Option<&SnapshotEntry> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+
+    /// Total size of the files
+    pub fn size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    pub(crate) fn chunk_ids(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .flat_map(|entry| &entry.chunks)
+            .map(|chunk| chunk.id.as_str())
+    }
+}
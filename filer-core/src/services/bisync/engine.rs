@@ -0,0 +1,451 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeKind};
+use crate::services::compare::{EntryInfo, Side, unchanged};
+use crate::services::hash::{Checksums, HashAlgorithm};
+use crate::services::transfer::{JobControl, ProgressTracker, StreamCopy, TransferObserver};
+use crate::vfs::provider::FsProvider;
+
+use super::plan::{self, Sides};
+use super::{BisyncAction, BisyncChange, SyncPair, SyncState, SyncedEntry};
+
+/// Changes applied between two saves of the state
+const SAVE_EVERY: usize = 32;
+
+/// Outcome of one change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BisyncResult {
+    pub change: BisyncChange,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BisyncReport {
+    /// Every change, in the order it ran (or would run)
+    pub results: Vec<BisyncResult>,
+    /// Paths changed on both sides, kept in both versions
+    pub conflicts: usize,
+    /// Directories that could not be listed and changes that failed
+    pub errors: usize,
+    /// Nothing was changed and the state was not updated
+    pub dry_run: bool,
+}
+
+/// Two-way sync of a `SyncPair`
+///
+/// Both trees are listed and compared with the pair's state to find what
+/// changed on each side since the last sync; those changes are carried
+/// over, and conflicting ones keep both versions. The state is saved as
+/// changes apply, so an interrupted run picks up where it stopped. Paths
+/// whose change failed are tried again next time.
+pub struct Bisync {
+    pair: SyncPair,
+    left: Arc<dyn FsProvider>,
+    right: Arc<dyn FsProvider>,
+    state: SyncState,
+    dry_run: bool,
+    chunk_size: Option<usize>,
+}
+
+impl Bisync {
+    pub fn new(
+        pair: SyncPair,
+        left: Arc<dyn FsProvider>,
+        right: Arc<dyn FsProvider>,
+        state: SyncState,
+    ) -> Self {
+        Self {
+            pair,
+            left,
+            right,
+            state,
+            dry_run: false,
+            chunk_size: None,
+        }
+    }
+
+    /// Only report the changes a sync would make
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub fn state(&self) -> &SyncState {
+        &self.state
+    }
+
+    pub async fn run(
+        &mut self,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<BisyncReport, CoreError> {
+        for side in [Side::Left, Side::Right] {
+            let (provider, root) = self.side(side);
+            let node = provider.metadata(root).await?;
+            if !matches!(node.kind, NodeKind::Directory { .. }) {
+                return Err(CoreError::InvalidPath(format!(
+                    "{} is not a directory",
+                    root.display()
+                )));
+            }
+        }
+
+        let mut report = BisyncReport {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        let mut skipped = Vec::new();
+        let listing = self
+            .list(control, observer, &mut skipped, &mut report)
+            .await?;
+        if skipped.is_empty() {
+            self.check_not_emptied(&listing)?;
+        }
+        let stamp = chrono::Local::now().format("%Y-%m-%d %H%M%S").to_string();
+        let mut reconciled = plan::reconcile(
+            &listing,
+            &self.state,
+            &skipped,
+            self.pair.mtime_tolerance,
+            &stamp,
+        );
+        self.settle_conflicts(&mut reconciled, control, observer)
+            .await?;
+
+        let changes = reconciled.changes;
+        report.conflicts = changes
+            .iter()
+            .filter(|c| matches!(c.action, BisyncAction::KeepBoth { .. }))
+            .count();
+        if self.dry_run {
+            report.results = changes
+                .into_iter()
+                .map(|change| BisyncResult {
+                    change,
+                    error: None,
+                })
+                .collect();
+            return Ok(report);
+        }
+
+        for (path, entry) in reconciled.adopt {
+            self.state.set(path, entry);
+        }
+        for path in reconciled.forget {
+            self.state.remove(&path);
+        }
+        let mut tracker = ProgressTracker::new(0, changes.len() as u64);
+        for (i, change) in changes.into_iter().enumerate() {
+            if i % SAVE_EVERY == SAVE_EVERY - 1 {
+                self.state.save()?;
+            }
+            if let Err(e) = control.checkpoint_async().await {
+                self.state.save()?;
+                return Err(e);
+            }
+            let current = self.pair.left.join(&change.path);
+            let error = match self.apply(&change, control, observer).await {
+                Ok(()) => None,
+                Err(CoreError::Cancelled) => {
+                    self.state.save()?;
+                    return Err(CoreError::Cancelled);
+                }
+                Err(e) => {
+                    let side = match change.action {
+                        BisyncAction::Copy { to } => to,
+                        BisyncAction::CreateDir { on } => on,
+                        BisyncAction::Delete { from } => from,
+                        BisyncAction::KeepBoth { loser, .. } => loser,
+                    };
+                    observer.failed(&self.side(side).1.join(&change.path), &e);
+                    report.errors += 1;
+                    Some(e.to_string())
+                }
+            };
+            report.results.push(BisyncResult { change, error });
+            tracker.file_done();
+            observer.progress(&tracker, &current);
+        }
+        self.state.save()?;
+        Ok(report)
+    }
+
+    /// Both trees, with filtered entries left out on both sides
+    async fn list(
+        &self,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+        skipped: &mut Vec<PathBuf>,
+        report: &mut BisyncReport,
+    ) -> Result<BTreeMap<PathBuf, Sides>, CoreError> {
+        let mut listing = BTreeMap::new();
+        let mut pending = vec![(PathBuf::new(), true, true)];
+        while let Some((dir, on_left, on_right)) = pending.pop() {
+            control.checkpoint_async().await?;
+            let listed = tokio::join!(
+                list_dir(&self.left, &self.pair.left, &dir, on_left),
+                list_dir(&self.right, &self.pair.right, &dir, on_right)
+            );
+            let (left, right) = match listed {
+                (Ok(left), Ok(right)) => (left, right),
+                (Err(e), _) | (_, Err(e)) => {
+                    observer.failed(&self.pair.left.join(&dir), &e);
+                    report.errors += 1;
+                    skipped.push(dir);
+                    continue;
+                }
+            };
+
+            let mut entries: BTreeMap<String, Sides> = BTreeMap::new();
+            for node in left {
+                entries.entry(node.name.clone()).or_default().0 = Some(EntryInfo::of(&node));
+            }
+            for node in right {
+                entries.entry(node.name.clone()).or_default().1 = Some(EntryInfo::of(&node));
+            }
+            for (name, (left, right)) in entries.into_iter().rev() {
+                let path = dir.join(name);
+                let included = [&left, &right]
+                    .into_iter()
+                    .flatten()
+                    .all(|entry| self.pair.includes(&path, entry));
+                if !included {
+                    continue;
+                }
+                let on_left = left.as_ref().is_some_and(EntryInfo::is_dir);
+                let on_right = right.as_ref().is_some_and(EntryInfo::is_dir);
+                if on_left || on_right {
+                    pending.push((path.clone(), on_left, on_right));
+                }
+                listing.insert(path, (left, right));
+            }
+        }
+        Ok(listing)
+    }
+
+    /// Refuse when one side lost everything it had; that is far more
+    /// often an unmounted drive or a wrong root than a real deletion
+    fn check_not_emptied(&self, listing: &BTreeMap<PathBuf, Sides>) -> Result<(), CoreError> {
+        if self.state.is_empty() {
+            return Ok(());
+        }
+        for side in [Side::Left, Side::Right] {
+            let emptied = listing.values().all(|(left, right)| match side {
+                Side::Left => left.is_none(),
+                Side::Right => right.is_none(),
+            });
+            if emptied {
+                return Err(CoreError::Refused(format!(
+                    "{} is empty; not deleting everything on the other side",
+                    self.side(side).1.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Files changed on both sides to the same content are no conflict,
+    /// even if their times differ
+    async fn settle_conflicts(
+        &self,
+        reconciled: &mut plan::Reconciled,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<(), CoreError> {
+        let mut changes = Vec::with_capacity(reconciled.changes.len());
+        for change in std::mem::take(&mut reconciled.changes) {
+            let (BisyncAction::KeepBoth { .. }, Some(left), Some(right)) =
+                (&change.action, &change.left, &change.right)
+            else {
+                changes.push(change);
+                continue;
+            };
+            if left.is_dir() || right.is_dir() || left.size != right.size {
+                changes.push(change);
+                continue;
+            }
+            let mut tracker = ProgressTracker::new(left.size * 2, 0);
+            let mut digests = Vec::new();
+            for side in [Side::Left, Side::Right] {
+                let (provider, root) = self.side(side);
+                let mut checksums = Checksums::new(provider.clone());
+                if let Some(chunk_size) = self.chunk_size {
+                    checksums = checksums.with_chunk_size(chunk_size);
+                }
+                let path = root.join(&change.path);
+                let digest = checksums
+                    .hash(
+                        &path,
+                        &[HashAlgorithm::Xxh3],
+                        control,
+                        &mut tracker,
+                        observer,
+                    )
+                    .await;
+                digests.push(match digest {
+                    Ok(mut digest) => Some(digest.remove(0).1),
+                    Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                    // Unreadable files are left to the conflict copy
+                    Err(_) => None,
+                });
+            }
+            match digests[0].is_some() && digests[0] == digests[1] {
+                true => reconciled.adopt.push((
+                    change.path.clone(),
+                    SyncedEntry {
+                        left: left.clone(),
+                        right: right.clone(),
+                    },
+                )),
+                false => changes.push(change),
+            }
+        }
+        reconciled.changes = changes;
+        Ok(())
+    }
+
+    async fn apply(
+        &mut self,
+        change: &BisyncChange,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<(), CoreError> {
+        let path = &change.path;
+        match &change.action {
+            BisyncAction::Delete { from } => {
+                let (provider, root) = self.side(*from);
+                let target = root.join(path);
+                unchanged(provider, &target, change.side(*from)).await?;
+                provider.remove(&target).await?;
+            }
+            BisyncAction::CreateDir { on } => {
+                let (provider, root) = self.side(*on);
+                let target = root.join(path);
+                unchanged(provider, &target, change.side(*on)).await?;
+                provider.create_dir(&target).await?;
+            }
+            BisyncAction::Copy { to } => {
+                let from = to.other();
+                self.expect(change, from).await?;
+                self.expect(change, *to).await?;
+                self.copy((from, path), (*to, path), control, observer)
+                    .await?;
+            }
+            BisyncAction::KeepBoth { loser, renamed } => {
+                let winner = loser.other();
+                self.expect(change, *loser).await?;
+                self.expect(change, winner).await?;
+                self.copy((*loser, path), (*loser, renamed), control, observer)
+                    .await?;
+                self.copy((*loser, path), (winner, renamed), control, observer)
+                    .await?;
+                if change.side(winner).is_some_and(EntryInfo::is_dir) {
+                    let (provider, root) = self.side(*loser);
+                    provider.remove(&root.join(path)).await?;
+                    provider.create_dir(&root.join(path)).await?;
+                } else {
+                    self.copy((winner, path), (*loser, path), control, observer)
+                        .await?;
+                }
+                self.record(renamed).await?;
+            }
+        }
+        self.record(path).await
+    }
+
+    async fn expect(&self, change: &BisyncChange, side: Side) -> Result<(), CoreError> {
+        let (provider, root) = self.side(side);
+        unchanged(provider, &root.join(&change.path), change.side(side)).await
+    }
+
+    /// Copy a file onto `to`, replacing what is there, with its
+    /// modification time kept
+    async fn copy(
+        &self,
+        from: (Side, &Path),
+        to: (Side, &Path),
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<(), CoreError> {
+        let (source, source_root) = self.side(from.0);
+        let (target, target_root) = self.side(to.0);
+        let dest = target_root.join(to.1);
+        let dest_dir = dest.parent().unwrap_or(target_root).to_path_buf();
+        let name = dest
+            .file_name()
+            .ok_or_else(|| CoreError::InvalidPath(dest.display().to_string()))?
+            .to_string_lossy()
+            .into_owned();
+        target.create_dir(&dest_dir).await?;
+        let mut copy = StreamCopy::new(
+            source.clone(),
+            vec![source_root.join(from.1)],
+            target.clone(),
+            dest_dir,
+        )
+        .with_name(name)
+        .with_overwrite(true)
+        .with_preserve_mtime(true);
+        if let Some(chunk_size) = self.chunk_size {
+            copy = copy.with_chunk_size(chunk_size);
+        }
+        match copy.run(control, observer).await?.errors {
+            0 => Ok(()),
+            errors => Err(CoreError::Io {
+                path: dest,
+                message: format!("{errors} items could not be copied"),
+            }),
+        }
+    }
+
+    /// Store what both sides now hold at `path`
+    async fn record(&mut self, path: &Path) -> Result<(), CoreError> {
+        let (left_path, right_path) = (self.pair.left.join(path), self.pair.right.join(path));
+        let (left, right) =
+            tokio::join!(stat(&self.left, &left_path), stat(&self.right, &right_path));
+        match (left?, right?) {
+            (Some(left), Some(right)) => self
+                .state
+                .set(path.to_path_buf(), SyncedEntry { left, right }),
+            _ => self.state.remove(path),
+        }
+        Ok(())
+    }
+
+    fn side(&self, side: Side) -> (&Arc<dyn FsProvider>, &Path) {
+        match side {
+            Side::Left => (&self.left, &self.pair.left),
+            Side::Right => (&self.right, &self.pair.right),
+        }
+    }
+}
+
+async fn list_dir(
+    provider: &Arc<dyn FsProvider>,
+    root: &Path,
+    dir: &Path,
+    exists: bool,
+) -> Result<Vec<FileNode>, CoreError> {
+    match exists {
+        true => provider.list(&root.join(dir)).await,
+        false => Ok(Vec::new()),
+    }
+}
+
+async fn stat(provider: &Arc<dyn FsProvider>, path: &Path) -> Result<Option<EntryInfo>, CoreError> {
+    match provider.metadata(path).await {
+        Ok(node) => Ok(Some(EntryInfo::of(&node))),
+        Err(CoreError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
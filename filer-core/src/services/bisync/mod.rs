@@ -0,0 +1,18 @@
+//! Two-way synchronization of folder pairs
+//!
+//! - `SyncPair`: two roots, possibly on different providers, with a
+//!   filter and what triggers a sync
+//! - `SyncState`: persistent record of each path as last synced, which
+//!   tells an edit on one side from a deletion on the other
+//! - `Bisync`: lists both sides, carries changes over in both directions
+//!   and keeps both versions of conflicting ones; can run dry
+
+mod engine;
+mod pair;
+mod plan;
+mod state;
+
+pub use engine::{Bisync, BisyncReport, BisyncResult};
+pub use pair::SyncPair;
+pub use plan::{BisyncAction, BisyncChange};
+pub use state::{SyncState, SyncedEntry};
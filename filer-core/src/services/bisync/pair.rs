@@ -0,0 +1,110 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pipeline::config::FilterConfig;
+use crate::services::compare::EntryInfo;
+use crate::utils::{is_hidden, matches_glob};
+
+/// Two folders kept in step, possibly on different providers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncPair {
+    /// Names the pair's state database; a single path component
+    pub name: String,
+    pub left: PathBuf,
+    pub right: PathBuf,
+    /// Entries left out on both sides; excluded entries are never copied
+    /// or deleted
+    pub filter: FilterConfig,
+    /// Modification times at most this far apart count as equal
+    pub mtime_tolerance: Duration,
+    /// Sync soon after the watcher reports a change under either root
+    pub watch: bool,
+    /// Sync this often, whether or not anything changed
+    pub interval: Option<Duration>,
+}
+
+impl SyncPair {
+    /// A pair synced on request only, with hidden files included
+    pub fn new(name: String, left: PathBuf, right: PathBuf) -> Self {
+        Self {
+            name,
+            left,
+            right,
+            filter: FilterConfig {
+                show_hidden: true,
+                ..Default::default()
+            },
+            mtime_tolerance: Duration::from_secs(2),
+            watch: false,
+            interval: None,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: FilterConfig) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_mtime_tolerance(mut self, tolerance: Duration) -> Self {
+        self.mtime_tolerance = tolerance;
+        self
+    }
+
+    pub fn with_watch(mut self, watch: bool) -> Self {
+        self.watch = watch;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Whether the entry at `path` (relative to a root) takes part
+    ///
+    /// Extension, size and name pattern rules only apply to files, so
+    /// directories are descended into unless hidden or excluded.
+    pub fn includes(&self, path: &Path, entry: &EntryInfo) -> bool {
+        let filter = &self.filter;
+        if !filter.show_hidden && is_hidden(path) {
+            return false;
+        }
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if filter
+            .exclude_patterns
+            .iter()
+            .any(|p| matches_glob(p, &name))
+        {
+            return false;
+        }
+        if entry.is_dir() {
+            return true;
+        }
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_string();
+        if !filter.include_extensions.is_empty() && !filter.include_extensions.contains(&extension)
+        {
+            return false;
+        }
+        if filter.exclude_extensions.contains(&extension) {
+            return false;
+        }
+        if filter.min_size.is_some_and(|min| entry.size < min)
+            || filter.max_size.is_some_and(|max| entry.size > max)
+        {
+            return false;
+        }
+        filter
+            .name_pattern
+            .as_deref()
+            .is_none_or(|pattern| matches_glob(pattern, &name))
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::compare::{EntryInfo, Side};
+
+use super::{SyncState, SyncedEntry};
+
+/// What one side and then the other hold at a path
+pub(crate) type Sides = (Option<EntryInfo>, Option<EntryInfo>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BisyncAction {
+    /// Copy the file from the other side onto `to`
+    Copy {
+        to: Side,
+    },
+    CreateDir {
+        on: Side,
+    },
+    /// Delete a file, or a directory with everything in it
+    Delete {
+        from: Side,
+    },
+    /// Both sides changed: the loser's version is kept as `renamed` on
+    /// both sides and the other version takes the path
+    KeepBoth {
+        loser: Side,
+        renamed: PathBuf,
+    },
+}
+
+/// One change a sync makes, with both sides as they were listed
+///
+/// A change is refused if a side it touches no longer matches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BisyncChange {
+    /// Relative to both roots
+    pub path: PathBuf,
+    pub action: BisyncAction,
+    pub left: Option<EntryInfo>,
+    pub right: Option<EntryInfo>,
+}
+
+impl BisyncChange {
+    pub fn side(&self, side: Side) -> Option<&EntryInfo> {
+        match side {
+            Side::Left => self.left.as_ref(),
+            Side::Right => self.right.as_ref(),
+        }
+    }
+
+    /// Whether the change writes to `side`
+    pub fn touches(&self, side: Side) -> bool {
+        match self.action {
+            BisyncAction::Copy { to } => to == side,
+            BisyncAction::CreateDir { on } => on == side,
+            BisyncAction::Delete { from } => from == side,
+            BisyncAction::KeepBoth { .. } => true,
+        }
+    }
+}
+
+/// Changes to make, and state updates that need none
+#[derive(Debug, Default)]
+pub(crate) struct Reconciled {
+    pub changes: Vec<BisyncChange>,
+    /// Paths that are already equal on both sides
+    pub adopt: Vec<(PathBuf, SyncedEntry)>,
+    /// Paths gone from both sides
+    pub forget: Vec<PathBuf>,
+}
+
+/// Decide what to do with every listed or previously synced path
+///
+/// A side changed when it no longer matches the state. A change on one
+/// side is carried over to the other; when both changed, equal results
+/// are accepted, a modification beats a deletion and anything else is a
+/// conflict. Paths under `skipped` were not listed and are left alone.
+pub(crate) fn reconcile(
+    listing: &BTreeMap<PathBuf, Sides>,
+    state: &SyncState,
+    skipped: &[PathBuf],
+    tolerance: Duration,
+    stamp: &str,
+) -> Reconciled {
+    let paths: BTreeSet<&PathBuf> = listing
+        .keys()
+        .chain(state.entries().map(|(path, _)| path))
+        .filter(|path| !skipped.iter().any(|dir| path.starts_with(dir)))
+        .collect();
+    let mut out = Reconciled::default();
+    let mut changes = Vec::new();
+    for path in paths {
+        let (left, right) = listing.get(path).cloned().unwrap_or_default();
+        let saved = state.get(path);
+        let left_changed = !matches(saved.map(|s| &s.left), left.as_ref(), tolerance);
+        let right_changed = !matches(saved.map(|s| &s.right), right.as_ref(), tolerance);
+        let change = |action| BisyncChange {
+            path: path.clone(),
+            action,
+            left: left.clone(),
+            right: right.clone(),
+        };
+        match (&left, &right) {
+            (None, None) => {
+                if saved.is_some() {
+                    out.forget.push(path.clone());
+                }
+            }
+            _ if !left_changed && !right_changed => {}
+            (Some(l), Some(r)) if same(l, r, tolerance) => {
+                out.adopt.push((
+                    path.clone(),
+                    SyncedEntry {
+                        left: l.clone(),
+                        right: r.clone(),
+                    },
+                ));
+            }
+            (Some(l), Some(r)) if left_changed && right_changed => {
+                let loser = match (l.is_dir(), r.is_dir()) {
+                    (true, false) => Side::Right,
+                    (false, true) => Side::Left,
+                    _ if r.modified > l.modified => Side::Left,
+                    _ => Side::Right,
+                };
+                let renamed = conflict_name(path, stamp);
+                changes.push(change(BisyncAction::KeepBoth { loser, renamed }));
+            }
+            _ => {
+                // Carry over the side that changed; when both did, the
+                // one that still exists
+                let from = match (left_changed, &left) {
+                    (true, Some(_)) => Side::Left,
+                    (true, None) if right_changed => Side::Right,
+                    (true, None) => Side::Left,
+                    (false, _) => Side::Right,
+                };
+                let to = from.other();
+                let (source, target) = match from {
+                    Side::Left => (&left, &right),
+                    Side::Right => (&right, &left),
+                };
+                let Some(source) = source else {
+                    changes.push(change(BisyncAction::Delete { from: to }));
+                    continue;
+                };
+                let create = match source.is_dir() {
+                    true => BisyncAction::CreateDir { on: to },
+                    false => BisyncAction::Copy { to },
+                };
+                match target {
+                    Some(target) if target.is_dir() || source.is_dir() => {
+                        // Another kind of entry is in the way
+                        changes.push(change(BisyncAction::Delete { from: to }));
+                        let mut create = change(create);
+                        match to {
+                            Side::Left => create.left = None,
+                            Side::Right => create.right = None,
+                        }
+                        changes.push(create);
+                    }
+                    _ => changes.push(change(create)),
+                }
+            }
+        }
+    }
+    out.changes = fold_deletes(changes);
+    out
+}
+
+/// Let a directory delete cover everything below it, unless something
+/// below it is still wanted on that side; then the directory is
+/// recreated on the side it went missing from instead
+fn fold_deletes(changes: Vec<BisyncChange>) -> Vec<BisyncChange> {
+    let mut out: Vec<BisyncChange> = Vec::with_capacity(changes.len());
+    let mut deleted: Option<PathBuf> = None;
+    for (i, mut change) in changes.iter().cloned().enumerate() {
+        if let Some(dir) = &deleted {
+            match change.path.starts_with(dir) {
+                // What replaces the directory still has to be created
+                true if change.path == *dir => {}
+                true => continue,
+                false => deleted = None,
+            }
+        }
+        let BisyncAction::Delete { from } = change.action else {
+            out.push(change);
+            continue;
+        };
+        if !change.side(from).is_some_and(EntryInfo::is_dir) {
+            out.push(change);
+            continue;
+        }
+        let wanted = changes[i + 1..]
+            .iter()
+            .take_while(|c| c.path.starts_with(&change.path))
+            .any(|c| c.path != change.path && c.touches(from.other()));
+        match wanted {
+            true => change.action = BisyncAction::CreateDir { on: from.other() },
+            false => deleted = Some(change.path.clone()),
+        }
+        out.push(change);
+    }
+    out
+}
+
+/// Whether `current` is still what the state recorded
+fn matches(saved: Option<&EntryInfo>, current: Option<&EntryInfo>, tolerance: Duration) -> bool {
+    match (saved, current) {
+        (None, None) => true,
+        (Some(saved), Some(current)) => same(saved, current, tolerance),
+        _ => false,
+    }
+}
+
+/// Directories only need to be directories; their times change with
+/// their contents
+pub(crate) fn same(a: &EntryInfo, b: &EntryInfo, tolerance: Duration) -> bool {
+    if a.is_dir() || b.is_dir() {
+        return a.is_dir() && b.is_dir();
+    }
+    a.kind == b.kind && a.size == b.size && close(a.modified, b.modified, tolerance)
+}
+
+fn close(a: Option<SystemTime>, b: Option<SystemTime>, tolerance: Duration) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let apart = a.duration_since(b).or_else(|_| b.duration_since(a));
+            apart.is_ok_and(|apart| apart <= tolerance)
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// `notes.txt` -> `notes (conflict <stamp>).txt`
+fn conflict_name(path: &Path, stamp: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem} (conflict {stamp}).{}", ext.to_string_lossy()),
+        None => format!("{stem} (conflict {stamp})"),
+    };
+    path.with_file_name(name)
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::services::compare::EntryInfo;
+use crate::services::journal::Journal;
+
+use super::SyncPair;
+
+/// What both sides held at a path when it was last in sync
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncedEntry {
+    pub left: EntryInfo,
+    pub right: EntryInfo,
+}
+
+/// On-disk format
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StateFile {
+    left: PathBuf,
+    right: PathBuf,
+    entries: BTreeMap<PathBuf, SyncedEntry>,
+}
+
+/// Persistent record of a pair's last synced state
+///
+/// Comparing each side against it tells an edit on one side from a
+/// deletion on the other. A state saved for other roots is discarded, so
+/// re-pointing a pair starts over instead of deleting anything.
+#[derive(Debug)]
+pub struct SyncState {
+    file: StateFile,
+    path: Option<PathBuf>,
+}
+
+impl SyncState {
+    /// State that is not saved anywhere
+    pub fn in_memory(pair: &SyncPair) -> Self {
+        Self {
+            file: StateFile {
+                left: pair.left.clone(),
+                right: pair.right.clone(),
+                entries: BTreeMap::new(),
+            },
+            path: None,
+        }
+    }
+
+    /// Load the state of `pair` from `path` (created on first save)
+    pub fn open(path: PathBuf, pair: &SyncPair) -> Result<Self, CoreError> {
+        let mut state = Self::in_memory(pair);
+        match fs::read(&path) {
+            Ok(data) => {
+                let file: StateFile =
+                    serde_json::from_slice(&data).map_err(|_| CoreError::InvalidData)?;
+                if file.left == pair.left && file.right == pair.right {
+                    state.file = file;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(CoreError::from_io_error(e, path)),
+        }
+        state.path = Some(path);
+        Ok(state)
+    }
+
+    /// `<name>.json` in `dir`; `name` must be a single path component
+    pub fn path_in(dir: &Path, name: &str) -> Result<PathBuf, CoreError> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+            return Err(CoreError::InvalidPath(format!(
+                "\"{name}\" is not a valid pair name"
+            )));
+        }
+        Ok(dir.join(format!("{name}.json")))
+    }
+
+    /// `sync/<name>.json` next to the journal
+    pub fn default_path(name: &str) -> Result<PathBuf, CoreError> {
+        Self::path_in(&Journal::default_path()?.with_file_name("sync"), name)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&SyncedEntry> {
+        self.file.entries.get(path)
+    }
+
+    /// Every synced path, parents first
+    pub fn entries(&self) -> impl Iterator<Item = (&PathBuf, &SyncedEntry)> {
+        self.file.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.file.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.file.entries.is_empty()
+    }
+
+    pub(crate) fn set(&mut self, path: PathBuf, entry: SyncedEntry) {
+        self.file.entries.insert(path, entry);
+    }
+
+    /// Forget `path` and everything below it
+    pub(crate) fn remove(&mut self, path: &Path) {
+        self.file.entries.retain(|p, _| !p.starts_with(path));
+    }
+
+    /// Write the state atomically
+    pub(crate) fn save(&self) -> Result<(), CoreError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir).map_err(|e| CoreError::from_io_error(e, dir.to_path_buf()))?;
+        let data = serde_json::to_vec(&self.file).map_err(|_| CoreError::InvalidData)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(|e| CoreError::from_io_error(e, tmp.clone()))?;
+        fs::rename(&tmp, path).map_err(|e| CoreError::from_io_error(e, path.clone()))
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use super::ApproxSize;
+
+struct Slot<V> {
+    value: V,
+    bytes: usize,
+    tick: u64,
+}
+
+/// Least-recently-used cache bounded by approximate memory size
+///
+/// Inserting past the budget evicts the least recently used entries.
+/// A single value larger than the whole budget is not stored.
+pub struct LruCache<K, V> {
+    budget: usize,
+    used: usize,
+    tick: u64,
+    slots: HashMap<K, Slot<V>>,
+    /// Recency order: oldest tick first
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Hash + Eq + Clone, V: ApproxSize> LruCache<K, V> {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            used: 0,
+            tick: 0,
+            slots: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    /// Get a value and mark it as recently used
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let tick = self.next_tick();
+        let slot = self.slots.get_mut(key)?;
+        self.order.remove(&slot.tick);
+        self.order.insert(tick, key.clone());
+        slot.tick = tick;
+        Some(&slot.value)
+    }
+
+    /// Get a value without touching its recency
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.slots.get(key).map(|s| &s.value)
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.slots.contains_key(key)
+    }
+
+    /// Insert or replace a value, evicting old entries to stay in budget
+    ///
+    /// Returns false when the value alone exceeds the budget.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        let kept = key.clone();
+        !self.push(key, value).iter().any(|(key, _)| *key == kept)
+    }
+
+    /// Like `insert`, but hands back what did not stay: the evicted
+    /// entries, or the new one itself when it exceeds the budget
+    pub fn push(&mut self, key: K, value: V) -> Vec<(K, V)> {
+        self.remove(&key);
+        let bytes = value.approx_size();
+        if bytes > self.budget {
+            return vec![(key, value)];
+        }
+        let mut evicted = Vec::new();
+        while self.used + bytes > self.budget {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+            if let Some(slot) = self.slots.remove(&oldest) {
+                self.used -= slot.bytes;
+                evicted.push((oldest, slot.value));
+            }
+        }
+
+        let tick = self.next_tick();
+        self.order.insert(tick, key.clone());
+        self.slots.insert(key, Slot { value, bytes, tick });
+        self.used += bytes;
+        evicted
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let slot = self.slots.remove(key)?;
+        self.order.remove(&slot.tick);
+        self.used -= slot.bytes;
+        Some(slot.value)
+    }
+
+    /// Keep only the entries for which `keep` returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &V) -> bool) {
+        let doomed: Vec<K> = self
+            .slots
+            .iter()
+            .filter(|(k, s)| !keep(k, &s.value))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in doomed {
+            self.remove(&key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.order.clear();
+        self.used = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Approximate bytes currently held
+    pub fn used_bytes(&self) -> usize {
+        self.used
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
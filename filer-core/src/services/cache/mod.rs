@@ -0,0 +1,10 @@
+//! In-memory caching building blocks
+//!
+//! - `LruCache`: least-recently-used map bounded by approximate bytes
+//! - `ApproxSize`: rough heap footprint of cached values
+
+mod lru;
+mod size;
+
+pub use lru::LruCache;
+pub use size::ApproxSize;
@@ -0,0 +1,37 @@
+use std::mem::size_of;
+
+use crate::model::node::{FileNode, NodeKind};
+
+/// Rough memory footprint, used to keep caches within a byte budget
+///
+/// Counts the value itself plus the heap data it owns. Allocator overhead
+/// is ignored, so real usage is somewhat higher.
+pub trait ApproxSize {
+    fn approx_size(&self) -> usize;
+}
+
+impl ApproxSize for FileNode {
+    fn approx_size(&self) -> usize {
+        let kind = match &self.kind {
+            NodeKind::File { extension } => extension.as_ref().map_or(0, String::capacity),
+            NodeKind::Directory { .. } => 0,
+            NodeKind::Symlink { target } => target.as_os_str().len(),
+        };
+        size_of::<FileNode>()
+            + self.name.capacity()
+            + self.path.as_os_str().len()
+            + self.meta.etag.as_ref().map_or(0, String::capacity)
+            + self.meta.tags.capacity() * size_of::<String>()
+            + self.meta.tags.iter().map(String::capacity).sum::<usize>()
+            + self.meta.note.as_ref().map_or(0, String::capacity)
+            + kind
+    }
+}
+
+impl<T: ApproxSize> ApproxSize for Vec<T> {
+    fn approx_size(&self) -> usize {
+        size_of::<Vec<T>>()
+            + self.iter().map(ApproxSize::approx_size).sum::<usize>()
+            + (self.capacity() - self.len()) * size_of::<T>()
+    }
+}
@@ -0,0 +1,19 @@
+//! Directory comparison and synchronization
+//!
+//! - `Compare`: walks two trees, possibly on different providers, and
+//!   classifies every path as only-left, only-right, identical or
+//!   differing
+//! - `SyncPlan`: reviewable steps derived from a comparison (mirror,
+//!   update)
+//! - `Synchronize`: runs a plan, refusing steps whose paths changed since
+//!   the comparison
+
+mod sync;
+mod tree;
+
+pub(crate) use sync::unchanged;
+pub use sync::{SyncAction, SyncPlan, SyncReport, SyncResult, SyncStep, Synchronize};
+pub use tree::{
+    Compare, CompareEntry, CompareObserver, CompareOptions, CompareReport, CompareStatus,
+    CompareSummary, Difference, EntryInfo, EntryKind, Side,
+};
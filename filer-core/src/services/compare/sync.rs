@@ -0,0 +1,288 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::services::transfer::{JobControl, ProgressTracker, StreamCopy, TransferObserver};
+use crate::vfs::provider::FsProvider;
+
+use super::{CompareEntry, CompareStatus, Difference, EntryInfo, Side};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncAction {
+    /// Copy the entry from the other side onto `to`
+    Copy {
+        to: Side,
+    },
+    Delete {
+        from: Side,
+    },
+}
+
+/// One action of a sync plan
+///
+/// Carries the state each side had when the trees were compared; the
+/// step is refused if a side it touches no longer matches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncStep {
+    /// Relative to both roots
+    pub path: PathBuf,
+    pub action: SyncAction,
+    pub left: Option<EntryInfo>,
+    pub right: Option<EntryInfo>,
+}
+
+impl SyncStep {
+    pub fn new(entry: &CompareEntry, action: SyncAction) -> Self {
+        Self {
+            path: entry.path.clone(),
+            action,
+            left: entry.left.clone(),
+            right: entry.right.clone(),
+        }
+    }
+
+    fn expected(&self, side: Side) -> Option<&EntryInfo> {
+        match side {
+            Side::Left => self.left.as_ref(),
+            Side::Right => self.right.as_ref(),
+        }
+    }
+}
+
+/// Actions that bring two compared trees together
+///
+/// Plans are built from comparison entries and meant to be shown before
+/// they run; steps can be removed or added freely in between.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncPlan {
+    pub steps: Vec<SyncStep>,
+}
+
+impl SyncPlan {
+    /// Make `to` a copy of the other side: missing and differing entries
+    /// are copied over and entries only on `to` are deleted
+    pub fn mirror(entries: &[CompareEntry], to: Side) -> Self {
+        let mut steps = Vec::new();
+        for entry in entries {
+            match entry.status {
+                CompareStatus::Identical => {}
+                _ if entry.side(to.other()).is_none() => {
+                    steps.push(SyncStep::new(entry, SyncAction::Delete { from: to }));
+                }
+                _ => steps.extend(copy_steps(entry, to)),
+            }
+        }
+        Self { steps }
+    }
+
+    /// Copy entries missing on `to`, and those newer on the other side;
+    /// nothing is deleted, so entries of different kinds are left out
+    pub fn update(entries: &[CompareEntry], to: Side) -> Self {
+        let from = to.other();
+        let mut steps = Vec::new();
+        for entry in entries {
+            let wanted = match entry.status {
+                CompareStatus::Differs {
+                    difference: Difference::Kind,
+                    ..
+                } => false,
+                CompareStatus::Differs { newer, .. } => newer == Some(from),
+                CompareStatus::Identical => false,
+                _ => entry.side(to).is_none(),
+            };
+            if wanted {
+                steps.extend(copy_steps(entry, to));
+            }
+        }
+        Self { steps }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// A copy onto `to`, after deleting what is there if it is of another kind
+fn copy_steps(entry: &CompareEntry, to: Side) -> Vec<SyncStep> {
+    let mut copy = SyncStep::new(entry, SyncAction::Copy { to });
+    let replaced = match (entry.side(to), entry.side(to.other())) {
+        (Some(target), Some(source)) => target.kind != source.kind,
+        _ => false,
+    };
+    if !replaced {
+        return vec![copy];
+    }
+    let delete = SyncStep::new(entry, SyncAction::Delete { from: to });
+    match to {
+        Side::Left => copy.left = None,
+        Side::Right => copy.right = None,
+    }
+    vec![delete, copy]
+}
+
+/// Outcome of one step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncResult {
+    pub step: SyncStep,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub results: Vec<SyncResult>,
+    /// Number of steps that failed
+    pub errors: usize,
+}
+
+/// Runs a `SyncPlan` between two roots
+///
+/// Files are copied with `StreamCopy`, verified and with their
+/// modification times kept, so a later comparison finds them identical.
+/// A failed step does not stop the plan.
+pub struct Synchronize {
+    left: Arc<dyn FsProvider>,
+    left_root: PathBuf,
+    right: Arc<dyn FsProvider>,
+    right_root: PathBuf,
+    plan: SyncPlan,
+    chunk_size: Option<usize>,
+}
+
+impl Synchronize {
+    pub fn new(
+        left: Arc<dyn FsProvider>,
+        left_root: PathBuf,
+        right: Arc<dyn FsProvider>,
+        right_root: PathBuf,
+        plan: SyncPlan,
+    ) -> Self {
+        Self {
+            left,
+            left_root,
+            right,
+            right_root,
+            plan,
+            chunk_size: None,
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub async fn run(
+        &self,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<SyncReport, CoreError> {
+        let mut report = SyncReport::default();
+        let mut tracker = ProgressTracker::new(0, self.plan.steps.len() as u64);
+        for step in &self.plan.steps {
+            control.checkpoint_async().await?;
+            let error = match self.apply(step, control, observer).await {
+                Ok(()) => None,
+                Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                Err(e) => {
+                    let (_, root) = self.side(touched(step.action));
+                    observer.failed(&root.join(&step.path), &e);
+                    report.errors += 1;
+                    Some(e.to_string())
+                }
+            };
+            report.results.push(SyncResult {
+                step: step.clone(),
+                error,
+            });
+            tracker.file_done();
+            observer.progress(&tracker, &step.path);
+        }
+        Ok(report)
+    }
+
+    async fn apply(
+        &self,
+        step: &SyncStep,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<(), CoreError> {
+        match step.action {
+            SyncAction::Delete { from } => {
+                let (provider, root) = self.side(from);
+                let path = root.join(&step.path);
+                unchanged(provider, &path, step.expected(from)).await?;
+                provider.remove(&path).await
+            }
+            SyncAction::Copy { to } => {
+                let (source, source_root) = self.side(to.other());
+                let (target, target_root) = self.side(to);
+                let from = source_root.join(&step.path);
+                let dest = target_root.join(&step.path);
+                unchanged(source, &from, step.expected(to.other())).await?;
+                unchanged(target, &dest, step.expected(to)).await?;
+                let dest_dir = dest.parent().unwrap_or(target_root).to_path_buf();
+                target.create_dir(&dest_dir).await?;
+                let mut copy =
+                    StreamCopy::new(source.clone(), vec![from], target.clone(), dest_dir)
+                        .with_overwrite(true)
+                        .with_preserve_mtime(true);
+                if let Some(chunk_size) = self.chunk_size {
+                    copy = copy.with_chunk_size(chunk_size);
+                }
+                match copy.run(control, observer).await?.errors {
+                    0 => Ok(()),
+                    errors => Err(CoreError::Io {
+                        path: dest,
+                        message: format!("{errors} items could not be copied"),
+                    }),
+                }
+            }
+        }
+    }
+
+    fn side(&self, side: Side) -> (&Arc<dyn FsProvider>, &Path) {
+        match side {
+            Side::Left => (&self.left, &self.left_root),
+            Side::Right => (&self.right, &self.right_root),
+        }
+    }
+}
+
+/// The side a step writes to
+fn touched(action: SyncAction) -> Side {
+    match action {
+        SyncAction::Copy { to } => to,
+        SyncAction::Delete { from } => from,
+    }
+}
+
+/// Refuse when `path` is not what the comparison saw
+///
+/// Directories only need to still be directories, as their times change
+/// with their contents.
+pub(crate) async fn unchanged(
+    provider: &Arc<dyn FsProvider>,
+    path: &Path,
+    expected: Option<&EntryInfo>,
+) -> Result<(), CoreError> {
+    let current = match provider.metadata(path).await {
+        Ok(node) => Some(EntryInfo::of(&node)),
+        Err(CoreError::NotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
+    let same = match (expected, &current) {
+        (None, None) => true,
+        (Some(expected), Some(current)) if expected.is_dir() => current.is_dir(),
+        (Some(expected), Some(current)) => expected == current,
+        _ => false,
+    };
+    match same {
+        true => Ok(()),
+        false => Err(CoreError::Refused(format!(
+            "{} changed since the comparison",
+            path.display()
+        ))),
+    }
+}
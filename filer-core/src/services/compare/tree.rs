@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeKind};
+use crate::services::hash::{Checksums, HashAlgorithm};
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+use crate::vfs::provider::FsProvider;
+
+/// One of the two trees of a comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn other(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink(PathBuf),
+}
+
+/// What a comparison saw of an entry on one side
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryInfo {
+    pub kind: EntryKind,
+    /// 0 for directories
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl EntryInfo {
+    pub(crate) fn of(node: &FileNode) -> Self {
+        let kind = match &node.kind {
+            NodeKind::File { .. } => EntryKind::File,
+            NodeKind::Directory { .. } => EntryKind::Directory,
+            NodeKind::Symlink { target } => EntryKind::Symlink(target.clone()),
+        };
+        Self {
+            size: if kind == EntryKind::Directory {
+                0
+            } else {
+                node.size
+            },
+            kind,
+            modified: node.modified,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+}
+
+/// Why two entries at the same path are not identical
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difference {
+    /// A file on one side, a directory on the other, or similar
+    Kind,
+    Size,
+    /// Same size, modification times further apart than the tolerance
+    Modified,
+    /// Same size, different content hash
+    Content,
+    LinkTarget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompareStatus {
+    /// Only in the left tree; directories are not descended into
+    OnlyLeft,
+    OnlyRight,
+    Identical,
+    Differs {
+        difference: Difference,
+        /// Side with the later modification time, if they are further
+        /// apart than the tolerance
+        newer: Option<Side>,
+    },
+}
+
+/// One path of a comparison
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompareEntry {
+    /// Relative to both roots
+    pub path: PathBuf,
+    pub status: CompareStatus,
+    pub left: Option<EntryInfo>,
+    pub right: Option<EntryInfo>,
+}
+
+impl CompareEntry {
+    pub fn side(&self, side: Side) -> Option<&EntryInfo> {
+        match side {
+            Side::Left => self.left.as_ref(),
+            Side::Right => self.right.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompareOptions {
+    /// Hash files of equal size instead of comparing modification times
+    pub content: Option<HashAlgorithm>,
+    /// Modification times at most this far apart count as equal; FAT
+    /// and many remote stores keep them at coarse resolution
+    pub mtime_tolerance: Duration,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            content: None,
+            mtime_tolerance: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Counts of a finished comparison
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompareSummary {
+    pub only_left: usize,
+    pub only_right: usize,
+    pub identical: usize,
+    pub differing: usize,
+    /// Directories that could not be listed and files that could not be
+    /// hashed
+    pub errors: usize,
+}
+
+impl CompareSummary {
+    fn add(&mut self, status: &CompareStatus) {
+        match status {
+            CompareStatus::OnlyLeft => self.only_left += 1,
+            CompareStatus::OnlyRight => self.only_right += 1,
+            CompareStatus::Identical => self.identical += 1,
+            CompareStatus::Differs { .. } => self.differing += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompareReport {
+    pub entries: Vec<CompareEntry>,
+    pub summary: CompareSummary,
+}
+
+/// Receives classified entries while a comparison runs
+pub trait CompareObserver: TransferObserver {
+    /// Entries of one directory, in name order
+    fn entries(&mut self, entries: &[CompareEntry]);
+}
+
+/// Walks two directory trees side by side and classifies every path
+///
+/// The trees may live on different providers. Directories present on
+/// both sides are descended into and not reported themselves; a
+/// directory on one side only is reported once, without its contents.
+pub struct Compare {
+    left: Arc<dyn FsProvider>,
+    left_root: PathBuf,
+    right: Arc<dyn FsProvider>,
+    right_root: PathBuf,
+    options: CompareOptions,
+    chunk_size: Option<usize>,
+}
+
+impl Compare {
+    pub fn new(
+        left: Arc<dyn FsProvider>,
+        left_root: PathBuf,
+        right: Arc<dyn FsProvider>,
+        right_root: PathBuf,
+    ) -> Self {
+        Self {
+            left,
+            left_root,
+            right,
+            right_root,
+            options: CompareOptions::default(),
+            chunk_size: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: CompareOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Read size when hashing content
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub async fn run(
+        &self,
+        control: &JobControl,
+        observer: &mut (dyn CompareObserver + Send),
+    ) -> Result<CompareReport, CoreError> {
+        for (provider, root) in [
+            (&self.left, &self.left_root),
+            (&self.right, &self.right_root),
+        ] {
+            let node = provider.metadata(root).await?;
+            if !matches!(node.kind, NodeKind::Directory { .. }) {
+                return Err(CoreError::InvalidPath(format!(
+                    "{} is not a directory",
+                    root.display()
+                )));
+            }
+        }
+
+        let mut report = CompareReport::default();
+        let mut tracker = ProgressTracker::new(0, 0);
+        let mut pending = vec![PathBuf::new()];
+        while let Some(dir) = pending.pop() {
+            control.checkpoint_async().await?;
+            let (left_dir, right_dir) = (self.left_root.join(&dir), self.right_root.join(&dir));
+            let listed = tokio::join!(self.left.list(&left_dir), self.right.list(&right_dir));
+            let (left, right) = match listed {
+                (Ok(left), Ok(right)) => (by_name(left), by_name(right)),
+                (Err(e), _) | (_, Err(e)) => {
+                    observer.failed(&left_dir, &e);
+                    report.summary.errors += 1;
+                    continue;
+                }
+            };
+
+            let mut names: Vec<&String> = left.keys().chain(right.keys()).collect();
+            names.sort();
+            names.dedup();
+            tracker.files_total += names.len() as u64;
+            let mut batch = Vec::new();
+            let mut subdirs = Vec::new();
+            for name in names {
+                let path = dir.join(name);
+                let left = left.get(name).map(EntryInfo::of);
+                let right = right.get(name).map(EntryInfo::of);
+                let status = match (&left, &right) {
+                    (Some(l), Some(r)) if l.is_dir() && r.is_dir() => {
+                        subdirs.push(path);
+                        tracker.file_done();
+                        continue;
+                    }
+                    (Some(l), Some(r)) => {
+                        match self
+                            .classify(&path, l, r, control, &mut tracker, observer)
+                            .await
+                        {
+                            Ok(status) => status,
+                            Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                            Err(e) => {
+                                observer.failed(&self.left_root.join(&path), &e);
+                                report.summary.errors += 1;
+                                tracker.file_done();
+                                continue;
+                            }
+                        }
+                    }
+                    (Some(_), None) => CompareStatus::OnlyLeft,
+                    (None, _) => CompareStatus::OnlyRight,
+                };
+                tracker.file_done();
+                report.summary.add(&status);
+                batch.push(CompareEntry {
+                    path,
+                    status,
+                    left,
+                    right,
+                });
+            }
+            pending.extend(subdirs.into_iter().rev());
+
+            observer.progress(&tracker, &left_dir);
+            if !batch.is_empty() {
+                observer.entries(&batch);
+                report.entries.append(&mut batch);
+            }
+        }
+        Ok(report)
+    }
+
+    async fn classify(
+        &self,
+        path: &Path,
+        left: &EntryInfo,
+        right: &EntryInfo,
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut (dyn CompareObserver + Send),
+    ) -> Result<CompareStatus, CoreError> {
+        let newer = self.newer(left, right);
+        let differs = |difference| CompareStatus::Differs { difference, newer };
+        let difference = match (&left.kind, &right.kind) {
+            (EntryKind::Symlink(l), EntryKind::Symlink(r)) => {
+                (l != r).then_some(Difference::LinkTarget)
+            }
+            (l, r) if l != r => Some(Difference::Kind),
+            _ if left.size != right.size => Some(Difference::Size),
+            _ => match self.options.content {
+                Some(algorithm) => {
+                    tracker.bytes_total += left.size * 2;
+                    let observer: &mut (dyn TransferObserver + Send) = observer;
+                    let left = self
+                        .digest(
+                            &self.left,
+                            &self.left_root.join(path),
+                            algorithm,
+                            control,
+                            tracker,
+                            observer,
+                        )
+                        .await?;
+                    let right = self
+                        .digest(
+                            &self.right,
+                            &self.right_root.join(path),
+                            algorithm,
+                            control,
+                            tracker,
+                            observer,
+                        )
+                        .await?;
+                    (left != right).then_some(Difference::Content)
+                }
+                None => newer.map(|_| Difference::Modified),
+            },
+        };
+        Ok(difference.map_or(CompareStatus::Identical, differs))
+    }
+
+    fn newer(&self, left: &EntryInfo, right: &EntryInfo) -> Option<Side> {
+        let (l, r) = (left.modified?, right.modified?);
+        let tolerance = self.options.mtime_tolerance;
+        match (l.duration_since(r), r.duration_since(l)) {
+            (Ok(ahead), _) if ahead > tolerance => Some(Side::Left),
+            (_, Ok(ahead)) if ahead > tolerance => Some(Side::Right),
+            _ => None,
+        }
+    }
+
+    async fn digest(
+        &self,
+        provider: &Arc<dyn FsProvider>,
+        path: &Path,
+        algorithm: HashAlgorithm,
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<String, CoreError> {
+        let mut checksums = Checksums::new(provider.clone());
+        if let Some(chunk_size) = self.chunk_size {
+            checksums = checksums.with_chunk_size(chunk_size);
+        }
+        let mut digests = checksums
+            .hash(path, &[algorithm], control, tracker, observer)
+            .await?;
+        Ok(digests.remove(0).1)
+    }
+}
+
+fn by_name(nodes: Vec<FileNode>) -> BTreeMap<String, FileNode> {
+    nodes
+        .into_iter()
+        .map(|node| (node.name.clone(), node))
+        .collect()
+}
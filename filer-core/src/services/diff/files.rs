@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::errors::CoreError;
+use crate::services::preview::PreviewData;
+use crate::vfs::provider::FsProvider;
+
+use super::{DiffOptions, TextDiff};
+
+/// Bytes checked for NUL when deciding whether content is binary
+const SNIFF_LEN: usize = 8000;
+
+/// Diff of two files, each read through its own provider
+///
+/// Either side may be anywhere a provider reaches, such as a file inside
+/// an archive against a local one. Content with a NUL byte near the start
+/// counts as binary; invalid UTF-8 is shown with replacement characters.
+pub struct FileDiff {
+    old: Arc<dyn FsProvider>,
+    old_path: PathBuf,
+    new: Arc<dyn FsProvider>,
+    new_path: PathBuf,
+    options: DiffOptions,
+}
+
+impl FileDiff {
+    pub fn new(
+        old: Arc<dyn FsProvider>,
+        old_path: PathBuf,
+        new: Arc<dyn FsProvider>,
+        new_path: PathBuf,
+    ) -> Self {
+        Self {
+            old,
+            old_path,
+            new,
+            new_path,
+            options: DiffOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: DiffOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub async fn run(&self) -> Result<TextDiff, CoreError> {
+        let (old, new) = tokio::join!(
+            self.read(&self.old, &self.old_path),
+            self.read(&self.new, &self.new_path)
+        );
+        let ((old, old_cut), (new, new_cut)) = (old?, new?);
+        let truncated = old_cut || new_cut;
+
+        let mut diff = if is_binary(&old) || is_binary(&new) {
+            TextDiff::binary(old == new)
+        } else {
+            let old = String::from_utf8_lossy(&old);
+            let new = String::from_utf8_lossy(&new);
+            TextDiff::compute(&old, &new, &self.options)
+        };
+        diff.truncated = truncated;
+        Ok(diff)
+    }
+
+    pub async fn preview(&self) -> Result<PreviewData, CoreError> {
+        Ok(PreviewData::Diff {
+            diff: self.run().await?,
+        })
+    }
+
+    /// Up to `max_bytes`, cut back to a line boundary; `true` if cut
+    async fn read(
+        &self,
+        provider: &Arc<dyn FsProvider>,
+        path: &Path,
+    ) -> Result<(Vec<u8>, bool), CoreError> {
+        let max = self.options.max_bytes;
+        let mut data = provider.read_range(path, 0, max.saturating_add(1)).await?;
+        if data.len() as u64 <= max {
+            return Ok((data, false));
+        }
+        data.truncate(max as usize);
+        if let Some(end) = data.iter().rposition(|&b| b == b'\n') {
+            data.truncate(end + 1);
+        }
+        Ok((data, true))
+    }
+}
+
+fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(SNIFF_LEN)].contains(&0)
+}
@@ -0,0 +1,12 @@
+//! Text diffs between two files
+//!
+//! - `TextDiff`: line diff in hunks with line numbers and changed words,
+//!   rendered unified or side by side
+//! - `FileDiff`: reads two files through their providers, detects binary
+//!   content and caps how much is compared
+
+mod files;
+mod text;
+
+pub use files::FileDiff;
+pub use text::{DiffHunk, DiffLine, DiffLineKind, DiffOptions, DiffSpan, TextDiff};
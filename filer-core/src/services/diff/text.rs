@@ -0,0 +1,266 @@
+use std::time::Duration;
+
+use similar::{ChangeTag, DiffOp};
+
+/// Give up looking for a minimal diff after this long
+const DEADLINE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Unchanged lines shown around each change
+    pub context: usize,
+    /// Bytes read from each file; anything after is not compared
+    pub max_bytes: u64,
+    /// Mark the changed words within changed lines
+    pub word_changes: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            context: 3,
+            max_bytes: 4 * 1024 * 1024,
+            word_changes: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+/// Part of a line; `changed` marks words that differ from the paired line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffSpan {
+    pub text: String,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// 1-based; `None` for added lines
+    pub old_line: Option<usize>,
+    /// 1-based; `None` for removed lines
+    pub new_line: Option<usize>,
+    /// The line without its line ending
+    pub spans: Vec<DiffSpan>,
+}
+
+impl DiffLine {
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+}
+
+/// A run of changes with its context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// As in a unified diff header: 1-based, or the line before an empty
+    /// range
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+impl DiffHunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )
+    }
+
+    /// Rows for a two-column view: removed lines are paired with the
+    /// added lines that replace them
+    pub fn side_by_side(&self) -> Vec<(Option<&DiffLine>, Option<&DiffLine>)> {
+        let mut rows = Vec::new();
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for line in &self.lines {
+            match line.kind {
+                DiffLineKind::Removed => {
+                    if !added.is_empty() {
+                        pair(&mut rows, &mut removed, &mut added);
+                    }
+                    removed.push(line);
+                }
+                DiffLineKind::Added => added.push(line),
+                DiffLineKind::Context => {
+                    pair(&mut rows, &mut removed, &mut added);
+                    rows.push((Some(line), Some(line)));
+                }
+            }
+        }
+        pair(&mut rows, &mut removed, &mut added);
+        rows
+    }
+}
+
+/// Move pending removed and added lines into rows, side by side
+fn pair<'a>(
+    rows: &mut Vec<(Option<&'a DiffLine>, Option<&'a DiffLine>)>,
+    removed: &mut Vec<&'a DiffLine>,
+    added: &mut Vec<&'a DiffLine>,
+) {
+    for i in 0..removed.len().max(added.len()) {
+        rows.push((removed.get(i).copied(), added.get(i).copied()));
+    }
+    removed.clear();
+    added.clear();
+}
+
+/// Line diff of two texts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextDiff {
+    pub hunks: Vec<DiffHunk>,
+    /// Either side is binary; there are no hunks
+    pub binary: bool,
+    /// The compared bytes are equal
+    pub identical: bool,
+    /// A side was longer than `DiffOptions::max_bytes`
+    pub truncated: bool,
+}
+
+impl TextDiff {
+    pub fn compute(old: &str, new: &str, options: &DiffOptions) -> Self {
+        let diff = similar::TextDiff::configure()
+            .timeout(DEADLINE)
+            .diff_lines(old, new);
+        let hunks = diff
+            .grouped_ops(options.context)
+            .iter()
+            .map(|ops| hunk(&diff, ops, options))
+            .collect();
+        Self {
+            hunks,
+            identical: old == new,
+            ..Default::default()
+        }
+    }
+
+    /// A diff of binary content, which is only equal or not
+    pub fn binary(identical: bool) -> Self {
+        Self {
+            binary: true,
+            identical,
+            ..Default::default()
+        }
+    }
+
+    /// Render as a unified diff, as `diff -u` prints it
+    pub fn unified(&self, old_name: &str, new_name: &str) -> String {
+        if self.binary {
+            return match self.identical {
+                true => String::new(),
+                false => format!("Binary files {old_name} and {new_name} differ\n"),
+            };
+        }
+        if self.hunks.is_empty() {
+            return String::new();
+        }
+        let mut out = format!("--- {old_name}\n+++ {new_name}\n");
+        for hunk in &self.hunks {
+            out.push_str(&hunk.header());
+            out.push('\n');
+            for line in &hunk.lines {
+                out.push(match line.kind {
+                    DiffLineKind::Context => ' ',
+                    DiffLineKind::Removed => '-',
+                    DiffLineKind::Added => '+',
+                });
+                out.push_str(&line.text());
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+fn hunk<'a>(
+    diff: &'a similar::TextDiff<'a, 'a, 'a, str>,
+    ops: &[DiffOp],
+    options: &DiffOptions,
+) -> DiffHunk {
+    let (first, last) = (&ops[0], &ops[ops.len() - 1]);
+    let old = first.old_range().start..last.old_range().end;
+    let new = first.new_range().start..last.new_range().end;
+    let start = |range: &std::ops::Range<usize>| match range.is_empty() {
+        true => range.start,
+        false => range.start + 1,
+    };
+
+    let mut lines = Vec::new();
+    for op in ops {
+        if options.word_changes {
+            for change in diff.iter_inline_changes(op) {
+                let spans = change
+                    .iter_strings_lossy()
+                    .map(|(changed, text)| (changed, text.into_owned()))
+                    .collect();
+                lines.push(line(
+                    change.tag(),
+                    change.old_index(),
+                    change.new_index(),
+                    spans,
+                ));
+            }
+        } else {
+            for change in diff.iter_changes(op) {
+                let spans = vec![(false, change.value().to_string())];
+                lines.push(line(
+                    change.tag(),
+                    change.old_index(),
+                    change.new_index(),
+                    spans,
+                ));
+            }
+        }
+    }
+    DiffHunk {
+        old_start: start(&old),
+        old_len: old.len(),
+        new_start: start(&new),
+        new_len: new.len(),
+        lines,
+    }
+}
+
+fn line(
+    tag: ChangeTag,
+    old: Option<usize>,
+    new: Option<usize>,
+    parts: Vec<(bool, String)>,
+) -> DiffLine {
+    let kind = match tag {
+        ChangeTag::Equal => DiffLineKind::Context,
+        ChangeTag::Delete => DiffLineKind::Removed,
+        ChangeTag::Insert => DiffLineKind::Added,
+    };
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    for (changed, text) in parts {
+        match spans.last_mut() {
+            Some(last) if last.changed == changed => last.text.push_str(&text),
+            _ => spans.push(DiffSpan { text, changed }),
+        }
+    }
+    if let Some(last) = spans.last_mut() {
+        let trimmed = last.text.strip_suffix('\n').unwrap_or(&last.text);
+        let trimmed = trimmed.strip_suffix('\r').unwrap_or(trimmed);
+        last.text.truncate(trimmed.len());
+        if last.text.is_empty() {
+            spans.pop();
+        }
+    }
+    DiffLine {
+        kind,
+        old_line: old.map(|i| i + 1),
+        new_line: new.map(|i| i + 1),
+        spans,
+    }
+}
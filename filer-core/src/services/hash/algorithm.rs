@@ -0,0 +1,141 @@
+use std::hash::Hasher as _;
+use std::path::Path;
+
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest as _, Sha256};
+use twox_hash::XxHash3_64;
+
+/// Supported checksum algorithms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    /// 64-bit XXH3; fast, not cryptographic
+    Xxh3,
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 5] = [
+        HashAlgorithm::Xxh3,
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha1,
+        HashAlgorithm::Md5,
+        HashAlgorithm::Blake3,
+    ];
+
+    /// Tag used by BSD-style manifest lines, e.g. `SHA256 (file) = ...`
+    pub fn tag(self) -> &'static str {
+        match self {
+            HashAlgorithm::Xxh3 => "XXH3",
+            HashAlgorithm::Sha256 => "SHA256",
+            HashAlgorithm::Sha1 => "SHA1",
+            HashAlgorithm::Md5 => "MD5",
+            HashAlgorithm::Blake3 => "BLAKE3",
+        }
+    }
+
+    /// Conventional manifest file name, e.g. `SHA256SUMS`
+    pub fn manifest_name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Xxh3 => "XXH3SUMS",
+            HashAlgorithm::Sha256 => "SHA256SUMS",
+            HashAlgorithm::Sha1 => "SHA1SUMS",
+            HashAlgorithm::Md5 => "MD5SUMS",
+            HashAlgorithm::Blake3 => "B3SUMS",
+        }
+    }
+
+    /// Length of the digest in hex digits
+    pub fn hex_len(self) -> usize {
+        match self {
+            HashAlgorithm::Xxh3 => 16,
+            HashAlgorithm::Md5 => 32,
+            HashAlgorithm::Sha1 => 40,
+            HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 64,
+        }
+    }
+
+    /// Guess from a manifest's name (`SHA256SUMS`, `release.sha256`, `x.md5`)
+    pub fn from_manifest_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_uppercase();
+        let extension = name.rsplit_once('.').map(|(_, ext)| ext);
+        Self::ALL.into_iter().find(|algorithm| {
+            let sums = algorithm.manifest_name();
+            let short = sums.trim_end_matches("SUMS");
+            name.starts_with(sums)
+                || extension.is_some_and(|ext| ext == short || ext == format!("{short}SUM"))
+        })
+    }
+
+    /// Algorithm named by a BSD-style tag
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.to_ascii_uppercase();
+        Self::ALL.into_iter().find(|algorithm| {
+            algorithm.tag() == tag || (*algorithm == HashAlgorithm::Blake3 && tag == "B3")
+        })
+    }
+
+    /// Best guess for a bare digest; SHA-256 wins over BLAKE3
+    pub(crate) fn from_hex_len(len: usize) -> Option<Self> {
+        [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Md5,
+            HashAlgorithm::Xxh3,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.hex_len() == len)
+    }
+
+    pub(crate) fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::default()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+        }
+    }
+}
+
+/// Running state of one algorithm
+pub(crate) enum Hasher {
+    Xxh3(Box<XxHash3_64>),
+    Sha256(Sha256),
+    Sha1(Sha1),
+    Md5(Md5),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Xxh3(hasher) => hasher.write(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Lowercase hex digest
+    pub(crate) fn finish(self) -> String {
+        match self {
+            Hasher::Xxh3(hasher) => format!("{:016x}", hasher.finish()),
+            Hasher::Sha256(hasher) => hex(&hasher.finalize()),
+            Hasher::Sha1(hasher) => hex(&hasher.finalize()),
+            Hasher::Md5(hasher) => hex(&hasher.finalize()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,336 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::node::NodeKind;
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+use crate::vfs::provider::{FsProvider, Precondition};
+
+use super::algorithm::Hasher;
+use super::{HashAlgorithm, Manifest, ManifestEntry};
+
+/// Bytes read from the provider per request
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Digests of one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChecksums {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Lowercase hex, in the order the algorithms were asked for
+    pub digests: Vec<(HashAlgorithm, String)>,
+}
+
+impl FileChecksums {
+    pub fn get(&self, algorithm: HashAlgorithm) -> Option<&str> {
+        self.digests
+            .iter()
+            .find(|(a, _)| *a == algorithm)
+            .map(|(_, digest)| digest.as_str())
+    }
+}
+
+/// Result of hashing a selection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChecksumReport {
+    pub files: Vec<FileChecksums>,
+    /// Number of items that could not be hashed
+    pub errors: usize,
+}
+
+/// A file whose content does not match its manifest entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChecksumMismatch {
+    pub path: PathBuf,
+    pub algorithm: HashAlgorithm,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Result of checking files against a manifest
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyReport {
+    pub passed: Vec<PathBuf>,
+    pub failed: Vec<ChecksumMismatch>,
+    /// Listed in the manifest but not found
+    pub missing: Vec<PathBuf>,
+    /// Selected files the manifest does not list
+    pub unlisted: Vec<PathBuf>,
+    /// Number of files that could not be read
+    pub errors: usize,
+}
+
+impl VerifyReport {
+    /// Everything listed was found and matched
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty() && self.missing.is_empty() && self.errors == 0
+    }
+}
+
+/// Computes and checks file checksums through an `FsProvider`
+///
+/// Files are streamed in chunks and every requested algorithm is fed in
+/// the same pass; the next chunk is read while the current one is hashed.
+/// Directories in a selection are hashed recursively.
+pub struct Checksums {
+    provider: Arc<dyn FsProvider>,
+    chunk_size: usize,
+}
+
+impl Checksums {
+    pub fn new(provider: Arc<dyn FsProvider>) -> Self {
+        Self {
+            provider,
+            chunk_size: CHUNK_SIZE,
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Hash every file in `paths` with each of `algorithms`
+    pub async fn compute(
+        &self,
+        paths: &[PathBuf],
+        algorithms: &[HashAlgorithm],
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<ChecksumReport, CoreError> {
+        let mut report = ChecksumReport::default();
+        let files = self
+            .files(paths, control, observer, &mut report.errors)
+            .await?;
+        let mut tracker =
+            ProgressTracker::new(files.iter().map(|(_, size)| size).sum(), files.len() as u64);
+        for (path, size) in files {
+            control.checkpoint_async().await?;
+            match self
+                .hash(&path, algorithms, control, &mut tracker, observer)
+                .await
+            {
+                Ok(digests) => report.files.push(FileChecksums {
+                    path: path.clone(),
+                    size,
+                    digests,
+                }),
+                Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                Err(e) => {
+                    observer.failed(&path, &e);
+                    report.errors += 1;
+                }
+            }
+            tracker.file_done();
+            observer.progress(&tracker, &path);
+        }
+        Ok(report)
+    }
+
+    /// Check files against the manifest at `manifest`
+    ///
+    /// With an empty `selection` every entry is checked; otherwise only
+    /// entries at or below a selected path, and selected files the
+    /// manifest does not list are reported as unlisted.
+    pub async fn verify(
+        &self,
+        manifest: &Path,
+        selection: &[PathBuf],
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<VerifyReport, CoreError> {
+        let data = self.provider.read(manifest).await?;
+        let parsed = Manifest::parse(&data, HashAlgorithm::from_manifest_path(manifest), manifest)?;
+        let base = manifest.parent().unwrap_or(Path::new(""));
+        let selected =
+            |path: &Path| selection.is_empty() || selection.iter().any(|s| path.starts_with(s));
+
+        let mut report = VerifyReport::default();
+        let mut pending = Vec::new();
+        let mut listed = HashSet::new();
+        for entry in parsed.entries {
+            let path = base.join(&entry.path);
+            if !selected(&path) {
+                continue;
+            }
+            listed.insert(path.clone());
+            match self.provider.metadata(&path).await {
+                Ok(node) => pending.push((path, node.size, entry)),
+                Err(CoreError::NotFound(_)) => report.missing.push(path),
+                Err(e) => {
+                    observer.failed(&path, &e);
+                    report.errors += 1;
+                }
+            }
+        }
+        if !selection.is_empty() {
+            let files = self
+                .files(selection, control, observer, &mut report.errors)
+                .await?;
+            report.unlisted = files
+                .into_iter()
+                .map(|(path, _)| path)
+                .filter(|path| path != manifest && !listed.contains(path))
+                .collect();
+        }
+
+        let bytes = pending.iter().map(|(_, size, _)| size).sum();
+        let mut tracker = ProgressTracker::new(bytes, pending.len() as u64);
+        for (path, _, entry) in pending {
+            control.checkpoint_async().await?;
+            match self
+                .hash(&path, &[entry.algorithm], control, &mut tracker, observer)
+                .await
+            {
+                Ok(mut digests) => {
+                    let (_, actual) = digests.remove(0);
+                    if actual == entry.digest {
+                        report.passed.push(path.clone());
+                    } else {
+                        report.failed.push(ChecksumMismatch {
+                            path: path.clone(),
+                            algorithm: entry.algorithm,
+                            expected: entry.digest,
+                            actual,
+                        });
+                    }
+                }
+                Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                Err(CoreError::NotFound(_)) => report.missing.push(path.clone()),
+                Err(e) => {
+                    observer.failed(&path, &e);
+                    report.errors += 1;
+                }
+            }
+            tracker.file_done();
+            observer.progress(&tracker, &path);
+        }
+        Ok(report)
+    }
+
+    /// Hash `paths` and write them as a manifest to `manifest`
+    ///
+    /// Entries are relative to the manifest's directory and sorted; files
+    /// outside that directory and the manifest itself are left out. An
+    /// existing manifest is replaced.
+    pub async fn create_manifest(
+        &self,
+        paths: &[PathBuf],
+        algorithm: HashAlgorithm,
+        manifest: &Path,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<ChecksumReport, CoreError> {
+        let base = manifest.parent().unwrap_or(Path::new(""));
+        let mut report = self.compute(paths, &[algorithm], control, observer).await?;
+        report.files.retain(|file| {
+            if file.path == manifest {
+                return false;
+            }
+            if file.path.starts_with(base) {
+                return true;
+            }
+            let error = CoreError::InvalidPath(format!(
+                "{} is outside {}",
+                file.path.display(),
+                base.display()
+            ));
+            observer.failed(&file.path, &error);
+            report.errors += 1;
+            false
+        });
+        report.files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let entries = report
+            .files
+            .iter()
+            .map(|file| ManifestEntry {
+                path: file
+                    .path
+                    .strip_prefix(base)
+                    .unwrap_or(&file.path)
+                    .to_path_buf(),
+                algorithm,
+                digest: file.digests[0].1.clone(),
+            })
+            .collect();
+        let data = Manifest { entries }.render();
+        control.checkpoint_async().await?;
+        self.provider
+            .write(manifest, &data, &Precondition::default())
+            .await?;
+        Ok(report)
+    }
+
+    /// Files in `paths`, directories expanded, with their sizes
+    async fn files(
+        &self,
+        paths: &[PathBuf],
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+        errors: &mut usize,
+    ) -> Result<Vec<(PathBuf, u64)>, CoreError> {
+        let mut files = Vec::new();
+        let mut pending: Vec<PathBuf> = paths.iter().rev().cloned().collect();
+        while let Some(path) = pending.pop() {
+            control.checkpoint_async().await?;
+            let listed = match self.provider.metadata(&path).await {
+                Ok(node) if matches!(node.kind, NodeKind::Directory { .. }) => {
+                    self.provider.list(&path).await.map(|mut children| {
+                        children.sort_by(|a, b| b.name.cmp(&a.name));
+                        pending.extend(children.into_iter().map(|child| path.join(child.name)));
+                    })
+                }
+                Ok(node) => {
+                    files.push((path.clone(), node.size));
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = listed {
+                observer.failed(&path, &e);
+                *errors += 1;
+            }
+        }
+        Ok(files)
+    }
+
+    pub(crate) async fn hash(
+        &self,
+        path: &Path,
+        algorithms: &[HashAlgorithm],
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<Vec<(HashAlgorithm, String)>, CoreError> {
+        let mut hashers: Vec<Hasher> = algorithms.iter().map(|a| a.hasher()).collect();
+        let len = self.chunk_size as u64;
+        let mut offset = 0;
+        let mut chunk = self.provider.read_range(path, 0, len).await?;
+        while !chunk.is_empty() {
+            control.checkpoint_async().await?;
+            let next_offset = offset + chunk.len() as u64;
+            let ((), next) = tokio::join!(
+                async {
+                    for hasher in &mut hashers {
+                        hasher.update(&chunk);
+                    }
+                },
+                self.provider.read_range(path, next_offset, len)
+            );
+            tracker.add_bytes(chunk.len() as u64, Instant::now());
+            observer.progress(tracker, path);
+            offset = next_offset;
+            chunk = next?;
+        }
+        Ok(algorithms
+            .iter()
+            .copied()
+            .zip(hashers.into_iter().map(Hasher::finish))
+            .collect())
+    }
+}
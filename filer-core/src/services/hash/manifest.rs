@@ -0,0 +1,139 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+
+use super::HashAlgorithm;
+
+/// One line of a checksum manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// As written; relative paths are relative to the manifest's directory
+    pub path: PathBuf,
+    pub algorithm: HashAlgorithm,
+    /// Lowercase hex
+    pub digest: String,
+}
+
+/// Checksum list in the format of `sha256sum` and friends
+///
+/// Reads both the GNU layout (`<digest>  <name>`, with `*` marking binary
+/// mode and a leading `\` for escaped names) and the BSD layout
+/// (`SHA256 (<name>) = <digest>`). Written manifests use the GNU layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Parse `data`; `algorithm` is used for GNU lines, guessed from the
+    /// digest length if `None`
+    pub fn parse(
+        data: &[u8],
+        algorithm: Option<HashAlgorithm>,
+        source: &Path,
+    ) -> Result<Self, CoreError> {
+        let mut entries = Vec::new();
+        for (index, line) in data.split(|&b| b == b'\n').enumerate() {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.iter().all(u8::is_ascii_whitespace) || line.starts_with(b"#") {
+                continue;
+            }
+            let entry = parse_bsd(line)
+                .or_else(|| parse_gnu(line, algorithm))
+                .ok_or_else(|| CoreError::Io {
+                    path: source.to_path_buf(),
+                    message: format!("line {} is not a checksum line", index + 1),
+                })?;
+            entries.push(entry);
+        }
+        Ok(Self { entries })
+    }
+
+    /// GNU layout, one line per entry
+    pub fn render(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for entry in &self.entries {
+            let name = entry.path.as_os_str().as_bytes();
+            let escape = name.iter().any(|&b| b == b'\\' || b == b'\n');
+            if escape {
+                out.push(b'\\');
+            }
+            out.extend_from_slice(entry.digest.as_bytes());
+            out.extend_from_slice(b"  ");
+            if escape {
+                for &b in name {
+                    match b {
+                        b'\\' => out.extend_from_slice(b"\\\\"),
+                        b'\n' => out.extend_from_slice(b"\\n"),
+                        b => out.push(b),
+                    }
+                }
+            } else {
+                out.extend_from_slice(name);
+            }
+            out.push(b'\n');
+        }
+        out
+    }
+}
+
+/// `<digest> <space|*><name>`
+fn parse_gnu(line: &[u8], algorithm: Option<HashAlgorithm>) -> Option<ManifestEntry> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let split = line.iter().position(|&b| b == b' ')?;
+    let digest = std::str::from_utf8(&line[..split]).ok()?;
+    let name = line
+        .get(split + 2..)
+        .filter(|_| matches!(line.get(split + 1), Some(b' ' | b'*')))?;
+    let algorithm = algorithm.or_else(|| HashAlgorithm::from_hex_len(digest.len()))?;
+    let name = if escaped {
+        unescape(name)?
+    } else {
+        name.to_vec()
+    };
+    entry(name, algorithm, digest)
+}
+
+/// `<TAG> (<name>) = <digest>`
+fn parse_bsd(line: &[u8]) -> Option<ManifestEntry> {
+    let open = line.iter().position(|&b| b == b'(')?;
+    let tag = std::str::from_utf8(&line[..open]).ok()?.trim_end();
+    let algorithm = HashAlgorithm::from_tag(tag)?;
+    let close = line.windows(4).rposition(|w| w == b") = ")?;
+    let digest = std::str::from_utf8(&line[close + 4..]).ok()?.trim();
+    entry(line.get(open + 1..close)?.to_vec(), algorithm, digest)
+}
+
+fn entry(name: Vec<u8>, algorithm: HashAlgorithm, digest: &str) -> Option<ManifestEntry> {
+    let valid =
+        digest.len() == algorithm.hex_len() && digest.bytes().all(|b| b.is_ascii_hexdigit());
+    (valid && !name.is_empty()).then(|| ManifestEntry {
+        path: PathBuf::from(OsStr::from_bytes(&name)),
+        algorithm,
+        digest: digest.to_ascii_lowercase(),
+    })
+}
+
+fn unescape(name: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(name.len());
+    let mut bytes = name.iter();
+    while let Some(&b) = bytes.next() {
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        match bytes.next()? {
+            b'\\' => out.push(b'\\'),
+            b'n' => out.push(b'\n'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
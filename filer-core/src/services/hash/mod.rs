@@ -0,0 +1,14 @@
+//! File checksums and checksum manifests
+//!
+//! - `HashAlgorithm`: XXH3, SHA-256, SHA-1, MD5 and BLAKE3
+//! - `Checksums`: hashes a selection through an `FsProvider`, verifies it
+//!   against a manifest and writes manifests
+//! - `Manifest`: reads and writes `SHA256SUMS`-style files
+
+mod algorithm;
+mod checksums;
+mod manifest;
+
+pub use algorithm::HashAlgorithm;
+pub use checksums::{ChecksumMismatch, ChecksumReport, Checksums, FileChecksums, VerifyReport};
+pub use manifest::{Manifest, ManifestEntry};
@@ -0,0 +1,175 @@
+use std::fs;
+use std::hash::Hasher;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use twox_hash::XxHash3_64;
+
+use crate::errors::CoreError;
+use crate::model::session::SessionId;
+
+/// Identity and content fingerprint of a file or directory
+///
+/// Taken after every change the journal makes, so undo/redo can tell
+/// whether anyone touched the item since. Directories include a digest of
+/// everything below them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub ino: u64,
+    pub size: u64,
+    pub mtime: i64,
+    pub mtime_nsec: i64,
+    /// Digest of names, sizes and mtimes below a directory (0 otherwise)
+    pub tree: u64,
+}
+
+impl Stamp {
+    pub fn of(path: &Path) -> Result<Self, CoreError> {
+        let meta = fs::symlink_metadata(path)
+            .map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))?;
+        let tree = if meta.is_dir() {
+            let mut hasher = XxHash3_64::default();
+            digest_tree(path, Path::new(""), &mut hasher)?;
+            hasher.finish()
+        } else {
+            0
+        };
+        Ok(Self {
+            ino: meta.ino(),
+            size: meta.len(),
+            mtime: meta.mtime(),
+            mtime_nsec: meta.mtime_nsec(),
+            tree,
+        })
+    }
+
+    /// Why `current` does not describe the same, unchanged item
+    pub fn difference(&self, current: &Stamp) -> Option<&'static str> {
+        if self.ino != current.ino {
+            Some("was replaced")
+        } else if self != current {
+            Some("was modified")
+        } else {
+            None
+        }
+    }
+}
+
+fn digest_tree(dir: &Path, relative: &Path, hasher: &mut XxHash3_64) -> Result<(), CoreError> {
+    let mut children: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| CoreError::from_io_error(e, dir.to_path_buf()))?
+        .filter_map(|entry| entry.ok().map(|e| e.file_name()))
+        .collect();
+    children.sort();
+    for name in children {
+        let path = dir.join(&name);
+        let relative = relative.join(&name);
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        hasher.write(relative.as_os_str().as_encoded_bytes());
+        hasher.write_u8(0);
+        if meta.is_dir() {
+            hasher.write_u8(b'd');
+            digest_tree(&path, &relative, hasher)?;
+        } else {
+            hasher.write_u64(meta.len());
+            hasher.write_i64(meta.mtime());
+            hasher.write_i64(meta.mtime_nsec());
+        }
+    }
+    Ok(())
+}
+
+/// An item that went from one path to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relocation {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Fingerprint of the item where it currently is
+    pub stamp: Stamp,
+}
+
+impl Relocation {
+    pub fn new(from: PathBuf, to: PathBuf) -> Result<Self, CoreError> {
+        let stamp = Stamp::of(&to)?;
+        Ok(Self { from, to, stamp })
+    }
+}
+
+/// A reversible operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalOp {
+    Rename(Relocation),
+    /// Batch rename applied as one transaction
+    Renames(Vec<Relocation>),
+    Move(Vec<Relocation>),
+    /// `from` is the original, `to` the copy
+    Copy(Vec<Relocation>),
+    CreateFolder {
+        path: PathBuf,
+        stamp: Stamp,
+    },
+    CreateFile {
+        path: PathBuf,
+        stamp: Stamp,
+    },
+    /// `from` is the original location, `to` the data inside the trash
+    Trash(Vec<Relocation>),
+}
+
+impl JournalOp {
+    /// Short description for undo/redo menus
+    pub fn describe(&self) -> String {
+        let count = |items: &[Relocation], verb: &str| match items {
+            [one] => format!("{verb} \"{}\"", file_name(&one.from)),
+            many => format!("{verb} {} items", many.len()),
+        };
+        match self {
+            JournalOp::Rename(item) => format!("Rename \"{}\"", file_name(&item.from)),
+            JournalOp::Renames(items) => count(items, "Rename"),
+            JournalOp::Move(items) => count(items, "Move"),
+            JournalOp::Copy(items) => count(items, "Copy"),
+            JournalOp::CreateFolder { path, .. } => {
+                format!("Create folder \"{}\"", file_name(path))
+            }
+            JournalOp::CreateFile { path, .. } => format!("Create file \"{}\"", file_name(path)),
+            JournalOp::Trash(items) => count(items, "Trash"),
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// A recorded operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub session: SessionId,
+    /// Stable name of the session that recorded it; a session opened under
+    /// the same name in a later run can claim the entry back
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Seconds since the Unix epoch
+    pub recorded_at: u64,
+    pub op: JournalOp,
+    /// Undone and waiting to be redone
+    pub undone: bool,
+    /// Recorded by an earlier run and not claimed yet; kept as history but
+    /// no session of this run can undo or redo it
+    #[serde(default)]
+    pub orphaned: bool,
+}
+
+impl JournalEntry {
+    /// Whether `session` may undo or redo this entry
+    pub(crate) fn owned_by(&self, session: SessionId) -> bool {
+        self.session == session && !self.orphaned
+    }
+}
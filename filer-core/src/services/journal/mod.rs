@@ -0,0 +1,12 @@
+//! Undo/redo journal of file operations
+//!
+//! Completed renames, moves, copies, creations and trash operations are
+//! recorded with fingerprints of the affected items. Undo and redo check
+//! the fingerprints first and refuse if anything changed in between.
+
+mod entry;
+mod revert;
+mod store;
+
+pub use entry::{JournalEntry, JournalOp, Relocation, Stamp};
+pub use store::{Journal, Reverted};
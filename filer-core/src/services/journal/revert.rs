@@ -0,0 +1,339 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::CoreError;
+use crate::services::rename;
+use crate::services::transfer::{
+    JobControl, ProgressTracker, Transfer, TransferMode, TransferObserver,
+};
+use crate::services::trash::{RestoreConflict, TrashBin};
+use crate::utils;
+
+use super::entry::{JournalOp, Relocation, Stamp};
+
+/// Reverse `op`, updating its stamps; returns the paths now holding items
+///
+/// Everything is checked before anything is changed, and items already
+/// reverted are put back if a later one fails.
+pub(crate) fn undo(op: &mut JournalOp, bin: Option<&TrashBin>) -> Result<Vec<PathBuf>, CoreError> {
+    match op {
+        JournalOp::Rename(item) => relocate_back(std::slice::from_mut(item), TransferMode::Move),
+        JournalOp::Renames(items) => {
+            for item in items.iter() {
+                expect_unchanged(&item.to, &item.stamp)?;
+                if !items.iter().any(|other| other.to == item.from) {
+                    expect_free(&item.from)?;
+                }
+            }
+            let pairs: Vec<_> = items.iter().map(|i| (i.to.clone(), i.from.clone())).collect();
+            rename::rename_all(&pairs)?;
+            for item in items.iter_mut() {
+                item.stamp = Stamp::of(&item.from)?;
+            }
+            Ok(items.iter().map(|i| i.from.clone()).collect())
+        }
+        JournalOp::Move(items) => relocate_back(items, TransferMode::Move),
+        JournalOp::Copy(items) => {
+            for item in items.iter() {
+                expect_unchanged(&item.to, &item.stamp)?;
+            }
+            for item in items.iter() {
+                remove_all(&item.to)?;
+            }
+            Ok(items
+                .iter()
+                .filter_map(|i| i.to.parent().map(Path::to_path_buf))
+                .collect())
+        }
+        JournalOp::CreateFolder { path, stamp } => {
+            // Later operations may have come and gone inside the folder, so
+            // only its identity and emptiness matter
+            let current = Stamp::of(path)
+                .map_err(|_| CoreError::Refused(format!("{} no longer exists", path.display())))?;
+            if current.ino != stamp.ino {
+                return Err(CoreError::Refused(format!(
+                    "{} was replaced since",
+                    path.display()
+                )));
+            }
+            if fs::read_dir(&*path)
+                .map_err(|e| CoreError::from_io_error(e, path.clone()))?
+                .next()
+                .is_some()
+            {
+                return Err(CoreError::Refused(format!(
+                    "{} is not empty",
+                    path.display()
+                )));
+            }
+            fs::remove_dir(&*path).map_err(|e| CoreError::from_io_error(e, path.clone()))?;
+            Ok(path.parent().map(Path::to_path_buf).into_iter().collect())
+        }
+        JournalOp::CreateFile { path, stamp } => {
+            expect_unchanged(path, stamp)?;
+            fs::remove_file(&*path).map_err(|e| CoreError::from_io_error(e, path.clone()))?;
+            Ok(path.parent().map(Path::to_path_buf).into_iter().collect())
+        }
+        JournalOp::Trash(items) => {
+            let bin =
+                bin.ok_or_else(|| CoreError::Refused("the trash is not available".to_string()))?;
+            let mut trashed = Vec::with_capacity(items.len());
+            for item in items.iter() {
+                expect_unchanged(&item.to, &item.stamp)?;
+                expect_free(&item.from)?;
+                let found = bin.find(&item.to).ok_or_else(|| {
+                    CoreError::Refused(format!("{} is no longer in the trash", item.from.display()))
+                })?;
+                trashed.push(found);
+            }
+            let mut restored = Vec::with_capacity(items.len());
+            for found in trashed {
+                match bin.restore(&found, RestoreConflict::Fail) {
+                    Ok(path) => restored.push(path),
+                    Err(e) => {
+                        for (item, path) in items.iter_mut().zip(&restored) {
+                            if let Ok(again) = bin.trash(path) {
+                                item.to = again.files_path();
+                                let to = item.to.clone();
+                                restamp(item, &to);
+                            }
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+            for (item, path) in items.iter_mut().zip(&restored) {
+                item.stamp = Stamp::of(path)?;
+            }
+            Ok(restored)
+        }
+    }
+}
+
+/// Apply an undone `op` again, updating its stamps
+pub(crate) fn redo(op: &mut JournalOp, bin: Option<&TrashBin>) -> Result<Vec<PathBuf>, CoreError> {
+    match op {
+        JournalOp::Rename(item) => relocate_forward(std::slice::from_mut(item), TransferMode::Move),
+        JournalOp::Renames(items) => {
+            for item in items.iter() {
+                expect_unchanged(&item.from, &item.stamp)?;
+                if !items.iter().any(|other| other.from == item.to) {
+                    expect_free(&item.to)?;
+                }
+            }
+            let pairs: Vec<_> = items.iter().map(|i| (i.from.clone(), i.to.clone())).collect();
+            rename::rename_all(&pairs)?;
+            for item in items.iter_mut() {
+                item.stamp = Stamp::of(&item.to)?;
+            }
+            Ok(items.iter().map(|i| i.to.clone()).collect())
+        }
+        JournalOp::Move(items) => relocate_forward(items, TransferMode::Move),
+        JournalOp::Copy(items) => {
+            for item in items.iter() {
+                if fs::symlink_metadata(&item.from).is_err() {
+                    return Err(CoreError::Refused(format!(
+                        "{} no longer exists",
+                        item.from.display()
+                    )));
+                }
+                expect_free(&item.to)?;
+            }
+            for (done, item) in items.iter().enumerate() {
+                if let Err(e) = transfer(&item.from, &item.to, TransferMode::Copy) {
+                    for item in &items[..done] {
+                        let _ = remove_all(&item.to);
+                    }
+                    return Err(e);
+                }
+            }
+            for item in items.iter_mut() {
+                item.stamp = Stamp::of(&item.to)?;
+            }
+            Ok(items.iter().map(|i| i.to.clone()).collect())
+        }
+        JournalOp::CreateFolder { path, stamp } => {
+            expect_free(path)?;
+            fs::create_dir(&*path).map_err(|e| CoreError::from_io_error(e, path.clone()))?;
+            *stamp = Stamp::of(path)?;
+            Ok(vec![path.clone()])
+        }
+        JournalOp::CreateFile { path, stamp } => {
+            expect_free(path)?;
+            fs::File::create_new(&*path).map_err(|e| CoreError::from_io_error(e, path.clone()))?;
+            *stamp = Stamp::of(path)?;
+            Ok(vec![path.clone()])
+        }
+        JournalOp::Trash(items) => {
+            let bin =
+                bin.ok_or_else(|| CoreError::Refused("the trash is not available".to_string()))?;
+            for item in items.iter() {
+                expect_unchanged(&item.from, &item.stamp)?;
+            }
+            for done in 0..items.len() {
+                match bin.trash(&items[done].from) {
+                    Ok(trashed) => items[done].to = trashed.files_path(),
+                    Err(e) => {
+                        for item in &mut items[..done] {
+                            let restored = bin
+                                .find(&item.to)
+                                .and_then(|found| bin.restore(&found, RestoreConflict::Fail).ok());
+                            if let Some(path) = restored {
+                                restamp(item, &path);
+                            }
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+            for item in items.iter_mut() {
+                item.stamp = Stamp::of(&item.to)?;
+            }
+            Ok(items
+                .iter()
+                .filter_map(|i| i.from.parent().map(Path::to_path_buf))
+                .collect())
+        }
+    }
+}
+
+fn relocate_back(items: &mut [Relocation], mode: TransferMode) -> Result<Vec<PathBuf>, CoreError> {
+    let pairs: Vec<_> = items.iter().map(|i| (i.to.clone(), i.from.clone())).collect();
+    relocate(items, &pairs, mode)
+}
+
+fn relocate_forward(
+    items: &mut [Relocation],
+    mode: TransferMode,
+) -> Result<Vec<PathBuf>, CoreError> {
+    let pairs: Vec<_> = items.iter().map(|i| (i.from.clone(), i.to.clone())).collect();
+    relocate(items, &pairs, mode)
+}
+
+/// Transfer each item from the first path of its pair to the second
+///
+/// If one fails, those already transferred go back where they were.
+fn relocate(
+    items: &mut [Relocation],
+    pairs: &[(PathBuf, PathBuf)],
+    mode: TransferMode,
+) -> Result<Vec<PathBuf>, CoreError> {
+    for (item, (from, to)) in items.iter().zip(pairs) {
+        expect_unchanged(from, &item.stamp)?;
+        expect_free(to)?;
+    }
+    for (done, (from, to)) in pairs.iter().enumerate() {
+        if let Err(e) = transfer(from, to, mode) {
+            for (item, (from, to)) in items.iter_mut().zip(&pairs[..done]).rev() {
+                if transfer(to, from, mode).is_ok() {
+                    restamp(item, from);
+                }
+            }
+            return Err(e);
+        }
+    }
+    for (item, (_, to)) in items.iter_mut().zip(pairs) {
+        item.stamp = Stamp::of(to)?;
+    }
+    Ok(pairs.iter().map(|(_, to)| to.clone()).collect())
+}
+
+/// Refresh the stamp of an item put back by a rollback, which a copy
+/// across devices gives a new identity
+fn restamp(item: &mut Relocation, path: &Path) {
+    if let Ok(stamp) = Stamp::of(path) {
+        item.stamp = stamp;
+    }
+}
+
+/// Refuse unless `path` still holds the item described by `stamp`
+fn expect_unchanged(path: &Path, stamp: &Stamp) -> Result<(), CoreError> {
+    let current = match Stamp::of(path) {
+        Ok(current) => current,
+        Err(CoreError::NotFound(_)) => {
+            return Err(CoreError::Refused(format!(
+                "{} no longer exists",
+                path.display()
+            )));
+        }
+        Err(e) => return Err(e),
+    };
+    match stamp.difference(&current) {
+        Some(reason) => Err(CoreError::Refused(format!(
+            "{} {reason} since",
+            path.display()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Refuse if something else now occupies `path`
+fn expect_free(path: &Path) -> Result<(), CoreError> {
+    if fs::symlink_metadata(path).is_ok() {
+        return Err(CoreError::Refused(format!(
+            "{} already exists",
+            path.display()
+        )));
+    }
+    match path.parent() {
+        Some(parent) if !parent.is_dir() => Err(CoreError::Refused(format!(
+            "{} no longer exists",
+            parent.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Move or copy `from` to exactly `to`
+///
+/// Renames when possible; otherwise the transfer engine copies into a
+/// staging directory next to `to`, so the final name can differ.
+fn transfer(from: &Path, to: &Path, mode: TransferMode) -> Result<(), CoreError> {
+    if mode == TransferMode::Move {
+        match fs::rename(from, to) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {}
+            Err(e) => return Err(CoreError::from_io_error(e, from.to_path_buf())),
+        }
+    }
+
+    let parent = to
+        .parent()
+        .ok_or_else(|| CoreError::InvalidPath(to.display().to_string()))?;
+    let staging = utils::unique_name(&parent.join(".filer-journal"));
+    fs::create_dir(&staging).map_err(|e| CoreError::from_io_error(e, staging.clone()))?;
+    let mut observer = FirstError(None);
+    let result = Transfer::new(mode, vec![from.to_path_buf()], staging.clone())
+        .run(&JobControl::new(), &mut observer)
+        .and_then(|report| match report.created.first() {
+            Some(staged) => {
+                fs::rename(staged, to).map_err(|e| CoreError::from_io_error(e, to.to_path_buf()))
+            }
+            None => Err(CoreError::Io {
+                path: from.to_path_buf(),
+                message: observer.0.take().unwrap_or_default(),
+            }),
+        });
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// Keeps the first item error of a transfer
+struct FirstError(Option<String>);
+
+impl TransferObserver for FirstError {
+    fn progress(&mut self, _tracker: &ProgressTracker, _current: &Path) {}
+
+    fn failed(&mut self, path: &Path, error: &CoreError) {
+        self.0
+            .get_or_insert_with(|| format!("{}: {error}", path.display()));
+    }
+}
+
+fn remove_all(path: &Path) -> Result<(), CoreError> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        _ => fs::remove_file(path),
+    };
+    result.map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+}
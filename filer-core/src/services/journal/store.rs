@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::session::SessionId;
+use crate::services::trash::TrashBin;
+
+use super::entry::{JournalEntry, JournalOp};
+use super::revert;
+
+/// On-disk format
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JournalFile {
+    next_id: u64,
+    entries: Vec<JournalEntry>,
+    /// Stable names of this run's sessions
+    #[serde(skip)]
+    names: HashMap<SessionId, String>,
+    /// Entries being undone or redone right now
+    #[serde(skip)]
+    busy: HashSet<u64>,
+}
+
+/// Outcome of an undo or redo
+#[derive(Debug, Clone)]
+pub struct Reverted {
+    pub entry: JournalEntry,
+    /// Paths that now hold the affected items (or their parents, for
+    /// items that no longer exist)
+    pub affected: Vec<PathBuf>,
+}
+
+/// Persistent undo/redo history of file operations
+///
+/// Each session has its own undo and redo stack. Session ids only mean
+/// something within one run, so entries loaded from disk are orphaned:
+/// listed, but not undone or redone until a session opened under the same
+/// stable name claims them (see `claim`). Recording a new operation
+/// drops whatever the session could still redo. Cheap to clone; clones
+/// share the same history.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    state: Arc<Mutex<JournalFile>>,
+    path: Option<PathBuf>,
+    limit: usize,
+    bin: Option<TrashBin>,
+}
+
+impl Journal {
+    /// History that is not saved anywhere
+    pub fn in_memory() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(JournalFile {
+                next_id: 1,
+                ..Default::default()
+            })),
+            path: None,
+            limit: 200,
+            bin: None,
+        }
+    }
+
+    /// Load the history from `path` (created on first save)
+    pub fn open(path: PathBuf) -> Result<Self, CoreError> {
+        let mut file: JournalFile = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|_| CoreError::InvalidData)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => JournalFile {
+                next_id: 1,
+                ..Default::default()
+            },
+            Err(e) => return Err(CoreError::from_io_error(e, path)),
+        };
+        for entry in &mut file.entries {
+            entry.orphaned = true;
+        }
+        let journal = Self::in_memory();
+        *journal.lock() = file;
+        Ok(Self {
+            path: Some(path),
+            ..journal
+        })
+    }
+
+    /// `$XDG_STATE_HOME/filer/journal.json` (or `~/.local/state/...`)
+    pub fn default_path() -> Result<PathBuf, CoreError> {
+        let state_home = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+            .ok_or_else(|| {
+                CoreError::InvalidPath("$XDG_STATE_HOME and $HOME are unset".to_string())
+            })?;
+        Ok(state_home.join("filer").join("journal.json"))
+    }
+
+    /// Keep at most `limit` entries across all sessions
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+
+    /// Trash used to undo and redo trash operations
+    pub fn with_trash(mut self, bin: TrashBin) -> Self {
+        self.bin = Some(bin);
+        self
+    }
+
+    /// Bind `session` to the stable `name` a client opened it under
+    ///
+    /// Entries recorded under that name, by an earlier run or by a session
+    /// of this run that was closed since, move to `session` and can be
+    /// undone and redone again. Operations it records from now on carry
+    /// the name.
+    pub fn claim(&self, session: SessionId, name: &str) {
+        let mut state = self.lock();
+        state.names.insert(session, name.to_string());
+        for entry in &mut state.entries {
+            if entry.owner.as_deref() == Some(name) {
+                entry.session = session;
+                entry.orphaned = false;
+            }
+        }
+    }
+
+    /// Record a completed operation
+    pub fn record(&self, session: SessionId, op: JournalOp) -> Result<u64, CoreError> {
+        let mut state = self.lock();
+        state
+            .entries
+            .retain(|e| !(e.owned_by(session) && e.undone));
+        let id = state.next_id;
+        state.next_id += 1;
+        let owner = state.names.get(&session).cloned();
+        state.entries.push(JournalEntry {
+            id,
+            session,
+            owner,
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            op,
+            undone: false,
+            orphaned: false,
+        });
+        let excess = state.entries.len().saturating_sub(self.limit);
+        state.entries.drain(..excess);
+        self.save(&state)?;
+        Ok(id)
+    }
+
+    /// Entry the next undo would reverse
+    pub fn next_undo(&self, session: SessionId) -> Option<JournalEntry> {
+        let state = self.lock();
+        Self::undo_index(&state, session).map(|i| state.entries[i].clone())
+    }
+
+    /// Entry the next redo would apply
+    pub fn next_redo(&self, session: SessionId) -> Option<JournalEntry> {
+        let state = self.lock();
+        Self::redo_index(&state, session).map(|i| state.entries[i].clone())
+    }
+
+    /// Reverse the session's latest operation
+    ///
+    /// Fails with `CoreError::Refused` if there is nothing to undo or the
+    /// affected items changed since. The history stays usable by other
+    /// sessions while the items are moved back.
+    pub fn undo(&self, session: SessionId) -> Result<Reverted, CoreError> {
+        let mut entry = self.take(session, Self::undo_index, "nothing to undo")?;
+        let result = revert::undo(&mut entry.op, self.bin.as_ref());
+        self.settle(entry, result, true)
+    }
+
+    /// Apply the session's most recently undone operation again
+    pub fn redo(&self, session: SessionId) -> Result<Reverted, CoreError> {
+        let mut entry = self.take(session, Self::redo_index, "nothing to redo")?;
+        let result = revert::redo(&mut entry.op, self.bin.as_ref());
+        self.settle(entry, result, false)
+    }
+
+    /// Every entry, oldest first
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        self.lock().entries.clone()
+    }
+
+    /// Copy of the entry `index` picks, marked busy so the lock need not be
+    /// held while it is reverted
+    fn take(
+        &self,
+        session: SessionId,
+        index: fn(&JournalFile, SessionId) -> Option<usize>,
+        nothing: &str,
+    ) -> Result<JournalEntry, CoreError> {
+        let mut state = self.lock();
+        let entry = index(&state, session)
+            .map(|i| state.entries[i].clone())
+            .ok_or_else(|| CoreError::Refused(nothing.to_string()))?;
+        if !state.busy.insert(entry.id) {
+            return Err(CoreError::Refused(
+                "an undo or redo of this operation is already running".to_string(),
+            ));
+        }
+        Ok(entry)
+    }
+
+    /// Store what reverting a `take`n entry did to it
+    ///
+    /// The op's stamps are kept even on failure, since a partial revert
+    /// changed them. The entry may have been trimmed meanwhile.
+    fn settle(
+        &self,
+        mut entry: JournalEntry,
+        result: Result<Vec<PathBuf>, CoreError>,
+        undone: bool,
+    ) -> Result<Reverted, CoreError> {
+        let mut state = self.lock();
+        state.busy.remove(&entry.id);
+        if result.is_ok() {
+            entry.undone = undone;
+        }
+        if let Some(stored) = state.entries.iter_mut().find(|e| e.id == entry.id) {
+            stored.op = entry.op.clone();
+            stored.undone = entry.undone;
+            entry.session = stored.session;
+        }
+        let affected = result?;
+        self.save(&state)?;
+        Ok(Reverted { entry, affected })
+    }
+
+    fn undo_index(state: &JournalFile, session: SessionId) -> Option<usize> {
+        state
+            .entries
+            .iter()
+            .rposition(|e| e.owned_by(session) && !e.undone)
+    }
+
+    /// Undone entries of a session form a suffix; the oldest of them was
+    /// undone last
+    fn redo_index(state: &JournalFile, session: SessionId) -> Option<usize> {
+        let first_undone = state
+            .entries
+            .iter()
+            .rposition(|e| e.owned_by(session) && !e.undone)
+            .map_or(0, |i| i + 1);
+        state.entries[first_undone..]
+            .iter()
+            .position(|e| e.owned_by(session) && e.undone)
+            .map(|i| first_undone + i)
+    }
+
+    /// Write the history atomically
+    fn save(&self, state: &JournalFile) -> Result<(), CoreError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir).map_err(|e| CoreError::from_io_error(e, dir.to_path_buf()))?;
+        let data = serde_json::to_vec(state).map_err(|_| CoreError::InvalidData)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(|e| CoreError::from_io_error(e, tmp.clone()))?;
+        fs::rename(&tmp, path).map_err(|e| CoreError::from_io_error(e, path.clone()))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, JournalFile> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::services::permissions::{Acl, AclKind};
+use crate::vfs::provider::FsProvider;
+
+/// Namespace of an extended attribute, from its name's prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum XattrNamespace {
+    /// Free for applications and users
+    User,
+    /// Security modules (SELinux labels, file capabilities); read-only here
+    Security,
+    /// Kept by the kernel, such as POSIX ACLs
+    System,
+    /// Only visible to root
+    Trusted,
+}
+
+impl XattrNamespace {
+    pub fn of(name: &str) -> Option<Self> {
+        let (prefix, rest) = name.split_once('.')?;
+        if rest.is_empty() {
+            return None;
+        }
+        match prefix {
+            "user" => Some(XattrNamespace::User),
+            "security" => Some(XattrNamespace::Security),
+            "system" => Some(XattrNamespace::System),
+            "trusted" => Some(XattrNamespace::Trusted),
+            _ => None,
+        }
+    }
+}
+
+/// Attributes that can be listed: `user.*`, `security.*` and the ACLs
+pub fn is_visible(name: &str) -> bool {
+    matches!(
+        XattrNamespace::of(name),
+        Some(XattrNamespace::User | XattrNamespace::Security)
+    ) || is_acl(name)
+}
+
+/// Attributes that may be set and removed: `user.*` and the ACLs
+pub fn is_writable(name: &str) -> bool {
+    XattrNamespace::of(name) == Some(XattrNamespace::User) || is_acl(name)
+}
+
+fn is_acl(name: &str) -> bool {
+    [AclKind::Access, AclKind::Default]
+        .iter()
+        .any(|kind| kind.xattr_name() == name)
+}
+
+/// One extended attribute with its raw value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Xattr {
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+impl Xattr {
+    pub fn namespace(&self) -> Option<XattrNamespace> {
+        XattrNamespace::of(&self.name)
+    }
+
+    /// The value as text, if it is UTF-8 (a trailing NUL is dropped)
+    pub fn text(&self) -> Option<&str> {
+        let value = self.value.strip_suffix(&[0]).unwrap_or(&self.value);
+        std::str::from_utf8(value).ok()
+    }
+
+    pub fn is_writable(&self) -> bool {
+        is_writable(&self.name)
+    }
+}
+
+/// Extended attributes and POSIX ACLs of one item
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileAttributes {
+    /// Sorted by name; the ACL attributes are parsed into the fields below
+    /// instead
+    pub xattrs: Vec<Xattr>,
+    /// `None` when the mode bits say everything
+    pub access_acl: Option<Acl>,
+    /// Only directories have one
+    pub default_acl: Option<Acl>,
+}
+
+impl FileAttributes {
+    /// Read everything `provider` has for `path`
+    pub async fn load(provider: &dyn FsProvider, path: &Path) -> Result<Self, CoreError> {
+        let mut names = provider.list_xattrs(path).await?;
+        names.sort();
+        let mut attributes = FileAttributes::default();
+        for name in names {
+            // Removed in between
+            let Some(value) = provider.get_xattr(path, &name).await? else {
+                continue;
+            };
+            if name == AclKind::Access.xattr_name() {
+                attributes.access_acl = Some(Acl::from_xattr(&value)?);
+            } else if name == AclKind::Default.xattr_name() {
+                attributes.default_acl = Some(Acl::from_xattr(&value)?);
+            } else {
+                attributes.xattrs.push(Xattr { name, value });
+            }
+        }
+        Ok(attributes)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Xattr> {
+        self.xattrs.iter().find(|xattr| xattr.name == name)
+    }
+}
+
+/// A change to the extended attributes or ACLs of items
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeEdit {
+    /// Set a `user.*` attribute
+    Set { name: String, value: Vec<u8> },
+    /// Remove a `user.*` attribute
+    Remove(String),
+    /// Replace an ACL; setting the access ACL also updates the mode
+    SetAcl { kind: AclKind, acl: Acl },
+    /// Drop an ACL, leaving the mode bits
+    RemoveAcl(AclKind),
+}
+
+impl AttributeEdit {
+    /// Refuse names outside `user.*` and ACLs the kernel would reject
+    ///
+    /// ACL attributes are only changed through `SetAcl` and `RemoveAcl`,
+    /// which check them first.
+    pub fn validate(&self) -> Result<(), CoreError> {
+        match self {
+            AttributeEdit::Set { name, .. } | AttributeEdit::Remove(name)
+                if XattrNamespace::of(name) != Some(XattrNamespace::User) =>
+            {
+                Err(CoreError::Refused(format!(
+                    "only user.* attributes can be changed, not {name}"
+                )))
+            }
+            AttributeEdit::SetAcl { acl, .. } => acl.validate(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply the edit to `path` through `provider`
+    pub async fn apply(&self, provider: &dyn FsProvider, path: &Path) -> Result<(), CoreError> {
+        self.validate()?;
+        match self {
+            AttributeEdit::Set { name, value } => provider.set_xattr(path, name, value).await,
+            AttributeEdit::Remove(name) => provider.remove_xattr(path, name).await,
+            AttributeEdit::SetAcl { kind, acl } => {
+                if *kind == AclKind::Default && !provider.metadata(path).await?.is_dir() {
+                    return Err(CoreError::Refused(format!(
+                        "{} is not a directory and cannot have a default ACL",
+                        path.display()
+                    )));
+                }
+                provider
+                    .set_xattr(path, kind.xattr_name(), &acl.to_xattr())
+                    .await
+            }
+            AttributeEdit::RemoveAcl(kind) => provider.remove_xattr(path, kind.xattr_name()).await,
+        }
+    }
+}
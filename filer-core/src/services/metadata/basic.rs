@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 use std::time::SystemTime;
 
+use super::FileAttributes;
+
 /// Basic filesystem metadata (always available)
 #[derive(Debug, Clone)]
 pub struct BasicMetadata {
@@ -12,6 +14,8 @@ pub struct BasicMetadata {
     pub permissions: Permissions,
     pub is_symlink: bool,
     pub symlink_target: Option<PathBuf>,
+    /// Extended attributes and ACLs, if the provider has them
+    pub attributes: Option<FileAttributes>,
 }
 
 #[derive(Debug, Clone)]
@@ -1,5 +1,4 @@
 use std::path::Path;
-use std::collections::HashMap;
 use async_trait::async_trait;
 
 use crate::errors::CoreError;
@@ -27,13 +26,14 @@ pub trait MetadataExtractor: Send + Sync {
 
 /// Registry of metadata extractors
 pub struct MetadataRegistry {
-    extractors: HashMap<MimeCategory, Vec<Box<dyn MetadataExtractor>>>,
+    /// In registration order; an extractor may serve several categories
+    extractors: Vec<Box<dyn MetadataExtractor>>,
 }
 
 impl MetadataRegistry {
     pub fn new() -> Self {
         Self {
-            extractors: HashMap::new(),
+            extractors: Vec::new(),
         }
     }
 
@@ -44,17 +44,25 @@ impl MetadataRegistry {
 
     /// Register an extractor
     pub fn register(&mut self, extractor: Box<dyn MetadataExtractor>) {
-        todo!()
+        self.extractors.push(extractor);
     }
 
-    /// Get extractor for a category
+    /// Get extractor for a category; the first registered one wins
     pub fn get(&self, category: MimeCategory) -> Option<&dyn MetadataExtractor> {
-        todo!()
+        self.extractors
+            .iter()
+            .find(|e| e.supported_categories().contains(&category))
+            .map(|e| e.as_ref())
     }
 
     /// Extract metadata using appropriate extractor
+    ///
+    /// Categories without an extractor have `ExtendedMetadata::None`.
     pub async fn extract(&self, path: &Path, category: MimeCategory) -> Result<ExtendedMetadata, CoreError> {
-        todo!()
+        match self.get(category) {
+            Some(extractor) => extractor.extract(path).await,
+            None => Ok(ExtendedMetadata::None),
+        }
     }
 }
 
@@ -1,8 +1,10 @@
+mod attributes;
 mod basic;
 mod extended;
 mod extractor;
 pub mod extractors;
 
+pub use attributes::{AttributeEdit, FileAttributes, Xattr, XattrNamespace, is_visible, is_writable};
 pub use basic::BasicMetadata;
 pub use extended::*;
 pub use extractor::{MetadataExtractor, MetadataRegistry};
\ No newline at end of file
@@ -30,7 +30,20 @@ impl MimeDetector {
 
     /// Detect MIME type from file path (extension-based, fast)
     pub fn detect_from_path(&self, path: &Path) -> MimeInfo {
-        todo!()
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        let mime_type = EXTENSIONS
+            .iter()
+            .find(|(known, _)| *known == ext)
+            .map_or("application/octet-stream", |(_, mime)| mime);
+        MimeInfo {
+            mime_type: mime_type.to_string(),
+            category: Self::categorize(mime_type),
+            encoding: None,
+        }
     }
 
     /// Detect MIME type from file contents (magic bytes, accurate)
@@ -45,10 +58,66 @@ impl MimeDetector {
 
     /// Get category from MIME type string
     pub fn categorize(mime_type: &str) -> MimeCategory {
-        todo!()
+        let (kind, subtype) = mime_type.split_once('/').unwrap_or((mime_type, ""));
+        match kind {
+            "text" => MimeCategory::Text,
+            "image" => MimeCategory::Image,
+            "audio" => MimeCategory::Audio,
+            "video" => MimeCategory::Video,
+            _ if ARCHIVES.contains(&subtype) => MimeCategory::Archive,
+            _ if DOCUMENTS.contains(&subtype) => MimeCategory::Document,
+            "application" => MimeCategory::Binary,
+            _ => MimeCategory::Unknown,
+        }
     }
 }
 
+/// MIME types of common extensions
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("html", "text/html"),
+    ("css", "text/css"),
+    ("rs", "text/x-rust"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("png", "image/png"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("heic", "image/heic"),
+    ("svg", "image/svg+xml"),
+    ("mp3", "audio/mpeg"),
+    ("flac", "audio/flac"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("aac", "audio/aac"),
+    ("m4a", "audio/mp4"),
+    ("mp4", "video/mp4"),
+    ("mkv", "video/x-matroska"),
+    ("webm", "video/webm"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    ("pdf", "application/pdf"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("zip", "application/zip"),
+    ("tar", "application/x-tar"),
+    ("gz", "application/gzip"),
+    ("zst", "application/zstd"),
+    ("7z", "application/x-7z-compressed"),
+];
+
+const ARCHIVES: &[&str] = &["zip", "x-tar", "gzip", "zstd", "x-7z-compressed"];
+
+const DOCUMENTS: &[&str] = &[
+    "pdf",
+    "vnd.oasis.opendocument.text",
+    "vnd.openxmlformats-officedocument.wordprocessingml.document",
+];
+
 impl Default for MimeDetector {
     fn default() -> Self {
         Self::new()
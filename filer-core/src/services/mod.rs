@@ -1,6 +1,22 @@
 #[cfg(feature = "crypto")]
 pub mod crypto;
 
+pub mod archive;
+pub mod backup;
+pub mod bisync;
+pub mod cache;
+pub mod compare;
+pub mod diff;
+pub mod hash;
+pub mod journal;
 pub mod metadata;
 pub mod mime;
-pub mod preview;
\ No newline at end of file
+pub mod permissions;
+pub mod preview;
+pub mod rename;
+pub mod scheduler;
+pub mod sessions;
+pub mod tags;
+pub mod transfer;
+pub mod trash;
+pub mod watch;
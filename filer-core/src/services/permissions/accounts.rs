@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::errors::CoreError;
+
+/// User and group names from `/etc/passwd` and `/etc/group`
+///
+/// Only the local files are read; accounts that come from NSS services
+/// such as LDAP are not known by name but can still be given by ID.
+#[derive(Debug, Clone, Default)]
+pub struct Accounts {
+    users: HashMap<String, u32>,
+    groups: HashMap<String, u32>,
+    user_names: HashMap<u32, String>,
+    group_names: HashMap<u32, String>,
+}
+
+impl Accounts {
+    /// Read the system's account files
+    pub fn load() -> Result<Self, CoreError> {
+        Self::from_files(Path::new("/etc/passwd"), Path::new("/etc/group"))
+    }
+
+    pub fn from_files(passwd: &Path, group: &Path) -> Result<Self, CoreError> {
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+        };
+        Ok(Self::parse(&read(passwd)?, &read(group)?))
+    }
+
+    /// Accounts from the contents of a passwd and a group file
+    pub fn parse(passwd: &str, group: &str) -> Self {
+        let mut accounts = Self::default();
+        for (name, id) in entries(passwd) {
+            accounts
+                .user_names
+                .entry(id)
+                .or_insert_with(|| name.clone());
+            accounts.users.entry(name).or_insert(id);
+        }
+        for (name, id) in entries(group) {
+            accounts
+                .group_names
+                .entry(id)
+                .or_insert_with(|| name.clone());
+            accounts.groups.entry(name).or_insert(id);
+        }
+        accounts
+    }
+
+    /// UID for a user name or a numeric ID
+    pub fn uid(&self, user: &str) -> Result<u32, CoreError> {
+        resolve(&self.users, user, "user")
+    }
+
+    /// GID for a group name or a numeric ID
+    pub fn gid(&self, group: &str) -> Result<u32, CoreError> {
+        resolve(&self.groups, group, "group")
+    }
+
+    pub fn user_name(&self, uid: u32) -> Option<&str> {
+        self.user_names.get(&uid).map(String::as_str)
+    }
+
+    pub fn group_name(&self, gid: u32) -> Option<&str> {
+        self.group_names.get(&gid).map(String::as_str)
+    }
+}
+
+/// `name:x:id:...` lines; comments and malformed lines are skipped
+fn entries(text: &str) -> impl Iterator<Item = (String, u32)> + '_ {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next().filter(|name| !name.is_empty())?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((name.to_string(), id))
+        })
+}
+
+/// Names win over numbers, as with `chown`
+fn resolve(names: &HashMap<String, u32>, name: &str, kind: &str) -> Result<u32, CoreError> {
+    names
+        .get(name)
+        .copied()
+        .or_else(|| name.parse().ok())
+        .ok_or_else(|| CoreError::InvalidPath(format!("unknown {kind} \"{name}\"")))
+}
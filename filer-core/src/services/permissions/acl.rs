@@ -0,0 +1,269 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+
+use super::Accounts;
+
+/// Version of the `system.posix_acl_*` attribute format
+const ACL_VERSION: u32 = 2;
+/// ID stored for entries that have none
+const UNDEFINED_ID: u32 = u32::MAX;
+
+const TAG_USER_OBJ: u16 = 0x01;
+const TAG_USER: u16 = 0x02;
+const TAG_GROUP_OBJ: u16 = 0x04;
+const TAG_GROUP: u16 = 0x08;
+const TAG_MASK: u16 = 0x10;
+const TAG_OTHER: u16 = 0x20;
+
+/// Which of a file's ACLs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AclKind {
+    /// Checked on access
+    Access,
+    /// Inherited by new items in a directory
+    Default,
+}
+
+impl AclKind {
+    /// Extended attribute the kernel keeps the ACL in
+    pub fn xattr_name(self) -> &'static str {
+        match self {
+            AclKind::Access => "system.posix_acl_access",
+            AclKind::Default => "system.posix_acl_default",
+        }
+    }
+}
+
+/// Whom an ACL entry is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AclTag {
+    /// The owner
+    UserObj,
+    User(u32),
+    /// The owning group
+    GroupObj,
+    Group(u32),
+    /// Upper bound for every named entry and the owning group
+    Mask,
+    Other,
+}
+
+/// One entry with its `rwx` bits (4, 2, 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AclEntry {
+    pub tag: AclTag,
+    pub perms: u8,
+}
+
+/// A POSIX access or default ACL
+///
+/// Entries are kept in the kernel's order: owner, named users, owning
+/// group, named groups, mask, other.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Acl {
+    pub entries: Vec<AclEntry>,
+}
+
+impl Acl {
+    pub fn new(mut entries: Vec<AclEntry>) -> Self {
+        entries.sort_by_key(|entry| entry.tag);
+        Self { entries }
+    }
+
+    /// Decode the value of a `system.posix_acl_*` attribute
+    pub fn from_xattr(value: &[u8]) -> Result<Self, CoreError> {
+        let (header, body) = value.split_at_checked(4).ok_or(CoreError::InvalidData)?;
+        if u32::from_le_bytes(header.try_into().unwrap()) != ACL_VERSION || body.len() % 8 != 0 {
+            return Err(CoreError::InvalidData);
+        }
+        let entries = body
+            .chunks_exact(8)
+            .map(|raw| {
+                let tag = u16::from_le_bytes([raw[0], raw[1]]);
+                let perms = u16::from_le_bytes([raw[2], raw[3]]);
+                let id = u32::from_le_bytes([raw[4], raw[5], raw[6], raw[7]]);
+                let tag = match tag {
+                    TAG_USER_OBJ => AclTag::UserObj,
+                    TAG_USER => AclTag::User(id),
+                    TAG_GROUP_OBJ => AclTag::GroupObj,
+                    TAG_GROUP => AclTag::Group(id),
+                    TAG_MASK => AclTag::Mask,
+                    TAG_OTHER => AclTag::Other,
+                    _ => return Err(CoreError::InvalidData),
+                };
+                Ok(AclEntry {
+                    tag,
+                    perms: (perms & 0o7) as u8,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(entries))
+    }
+
+    /// Encode as the value of a `system.posix_acl_*` attribute
+    pub fn to_xattr(&self) -> Vec<u8> {
+        let mut value = ACL_VERSION.to_le_bytes().to_vec();
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|entry| entry.tag);
+        for entry in &entries {
+            let (tag, id) = match entry.tag {
+                AclTag::UserObj => (TAG_USER_OBJ, UNDEFINED_ID),
+                AclTag::User(uid) => (TAG_USER, uid),
+                AclTag::GroupObj => (TAG_GROUP_OBJ, UNDEFINED_ID),
+                AclTag::Group(gid) => (TAG_GROUP, gid),
+                AclTag::Mask => (TAG_MASK, UNDEFINED_ID),
+                AclTag::Other => (TAG_OTHER, UNDEFINED_ID),
+            };
+            value.extend_from_slice(&tag.to_le_bytes());
+            value.extend_from_slice(&u16::from(entry.perms & 0o7).to_le_bytes());
+            value.extend_from_slice(&id.to_le_bytes());
+        }
+        value
+    }
+
+    /// Parse the short text form `getfacl -c` prints, one entry per line
+    /// or comma-separated (`user::rw-,user:alice:r--,group::r--,mask::r--,other::---`)
+    ///
+    /// Users and groups may be names or numeric IDs.
+    pub fn parse(text: &str, accounts: &Accounts) -> Result<Self, CoreError> {
+        let invalid =
+            |entry: &str| CoreError::InvalidPath(format!("invalid ACL entry \"{entry}\""));
+        let mut entries = Vec::new();
+        for entry in text
+            .split([',', '\n'])
+            .map(|entry| entry.split('#').next().unwrap_or_default().trim())
+            .filter(|entry| !entry.is_empty())
+        {
+            let mut parts = entry.splitn(3, ':');
+            let (Some(kind), Some(qualifier), Some(perms)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(invalid(entry));
+            };
+            let tag = match (kind, qualifier) {
+                ("u" | "user", "") => AclTag::UserObj,
+                ("u" | "user", user) => AclTag::User(accounts.uid(user)?),
+                ("g" | "group", "") => AclTag::GroupObj,
+                ("g" | "group", group) => AclTag::Group(accounts.gid(group)?),
+                ("m" | "mask", "") => AclTag::Mask,
+                ("o" | "other", "") => AclTag::Other,
+                _ => return Err(invalid(entry)),
+            };
+            let perms = parse_perms(perms).ok_or_else(|| invalid(entry))?;
+            entries.push(AclEntry { tag, perms });
+        }
+        let acl = Self::new(entries);
+        acl.validate()?;
+        Ok(acl)
+    }
+
+    /// Short text form, with names where `accounts` knows them
+    pub fn to_text(&self, accounts: &Accounts) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let perms = perms_text(entry.perms);
+                match entry.tag {
+                    AclTag::UserObj => format!("user::{perms}"),
+                    AclTag::User(uid) => match accounts.user_name(uid) {
+                        Some(name) => format!("user:{name}:{perms}"),
+                        None => format!("user:{uid}:{perms}"),
+                    },
+                    AclTag::GroupObj => format!("group::{perms}"),
+                    AclTag::Group(gid) => match accounts.group_name(gid) {
+                        Some(name) => format!("group:{name}:{perms}"),
+                        None => format!("group:{gid}:{perms}"),
+                    },
+                    AclTag::Mask => format!("mask::{perms}"),
+                    AclTag::Other => format!("other::{perms}"),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Refuse what the kernel would: the owner, owning group and other
+    /// entries once each, a mask if there are named entries, no user or
+    /// group twice
+    pub fn validate(&self) -> Result<(), CoreError> {
+        let invalid = |reason: &str| Err(CoreError::InvalidPath(format!("invalid ACL: {reason}")));
+        let count = |tag: AclTag| self.entries.iter().filter(|e| e.tag == tag).count();
+        for (tag, name) in [
+            (AclTag::UserObj, "owner"),
+            (AclTag::GroupObj, "owning group"),
+            (AclTag::Other, "other"),
+        ] {
+            if count(tag) != 1 {
+                return invalid(&format!("needs exactly one {name} entry"));
+            }
+        }
+        if count(AclTag::Mask) > 1 {
+            return invalid("more than one mask");
+        }
+        let mut named: Vec<AclTag> = self
+            .entries
+            .iter()
+            .map(|e| e.tag)
+            .filter(|tag| matches!(tag, AclTag::User(_) | AclTag::Group(_)))
+            .collect();
+        if !named.is_empty() && count(AclTag::Mask) == 0 {
+            return invalid("named entries need a mask");
+        }
+        let total = named.len();
+        named.sort();
+        named.dedup();
+        if named.len() != total {
+            return invalid("a user or group is listed twice");
+        }
+        Ok(())
+    }
+
+    /// Only the owner, owning group and other entries, which the mode
+    /// bits already say
+    pub fn is_minimal(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|e| matches!(e.tag, AclTag::UserObj | AclTag::GroupObj | AclTag::Other))
+    }
+
+    /// Permissions of the entry for `tag`
+    pub fn get(&self, tag: AclTag) -> Option<u8> {
+        self.entries.iter().find(|e| e.tag == tag).map(|e| e.perms)
+    }
+}
+
+impl fmt::Display for Acl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_text(&Accounts::default()))
+    }
+}
+
+/// `rwx`, `r-x`, `rw`, or octal `5`
+fn parse_perms(text: &str) -> Option<u8> {
+    if let Ok(octal) = text.parse::<u8>() {
+        return (octal <= 7).then_some(octal);
+    }
+    let mut perms = 0;
+    for c in text.chars() {
+        perms |= match c {
+            'r' => 4,
+            'w' => 2,
+            'x' => 1,
+            '-' => 0,
+            _ => return None,
+        };
+    }
+    (!text.is_empty()).then_some(perms)
+}
+
+fn perms_text(perms: u8) -> String {
+    [(4, 'r'), (2, 'w'), (1, 'x')]
+        .iter()
+        .map(|&(bit, c)| if perms & bit != 0 { c } else { '-' })
+        .collect()
+}
@@ -0,0 +1,280 @@
+use std::fs::{self, Metadata, Permissions};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+
+use super::{Accounts, ModeChange};
+
+/// Outcome for one item of a permission edit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionResult {
+    pub path: PathBuf,
+    /// Permission bits after the edit, or as last read if it failed
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub error: Option<String>,
+}
+
+/// Result of a permission edit, one entry per item in walk order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionReport {
+    pub results: Vec<PermissionResult>,
+    /// Number of items that failed
+    pub errors: usize,
+}
+
+/// A change of mode, owner and group for a selection
+///
+/// Files and directories take separate mode changes so a recursive edit
+/// can do the usual "directories 755, files 644" fixup. Owners and groups
+/// are names or numeric IDs.
+///
+/// Symbolic links are never followed while recursing and their own mode is
+/// left alone; only their owner and group change.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionEdit {
+    file_mode: Option<ModeChange>,
+    dir_mode: Option<ModeChange>,
+    owner: Option<String>,
+    group: Option<String>,
+    recursive: bool,
+}
+
+impl PermissionEdit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Same mode change for files and directories
+    pub fn with_mode(self, mode: ModeChange) -> Self {
+        self.with_file_mode(mode.clone()).with_dir_mode(mode)
+    }
+
+    pub fn with_file_mode(mut self, mode: ModeChange) -> Self {
+        self.file_mode = Some(mode);
+        self
+    }
+
+    pub fn with_dir_mode(mut self, mode: ModeChange) -> Self {
+        self.dir_mode = Some(mode);
+        self
+    }
+
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Apply to everything below selected directories too
+    pub fn with_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.file_mode.is_none()
+            && self.dir_mode.is_none()
+            && self.owner.is_none()
+            && self.group.is_none()
+    }
+
+    /// Apply the edit to `paths`
+    ///
+    /// Unknown owners or groups fail before anything changes; failures on
+    /// single items are reported and the edit carries on. Items are changed
+    /// deepest first so taking away access to a directory does not lock the
+    /// edit out of its contents; a directory the edit opens up is changed
+    /// before its contents are listed so it can get in.
+    pub fn run(
+        &self,
+        paths: &[PathBuf],
+        accounts: &Accounts,
+        control: &JobControl,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<PermissionReport, CoreError> {
+        if self.is_empty() {
+            return Err(CoreError::InvalidPath("nothing to change".to_string()));
+        }
+        let uid = self.owner.as_deref().map(|o| accounts.uid(o)).transpose()?;
+        let gid = self.group.as_deref().map(|g| accounts.gid(g)).transpose()?;
+
+        let mut items = Vec::new();
+        for path in paths {
+            control.checkpoint()?;
+            self.walk(path, uid, gid, control, &mut items)?;
+        }
+        let mut tracker = ProgressTracker::new(0, items.len() as u64);
+        let mut results = Vec::with_capacity(items.len());
+        let mut errors = 0;
+        for (path, item) in items.into_iter().rev() {
+            control.checkpoint()?;
+            let outcome = match item {
+                Item::Pending(meta) => self.apply(&path, &meta, uid, gid),
+                Item::Done(outcome) => outcome,
+            };
+            let result = match outcome {
+                Ok(meta) => PermissionResult {
+                    path,
+                    mode: meta.mode() & 0o7777,
+                    uid: meta.uid(),
+                    gid: meta.gid(),
+                    error: None,
+                },
+                Err((e, last)) => {
+                    observer.failed(&path, &e);
+                    errors += 1;
+                    let (mode, uid, gid) = last.unwrap_or_default();
+                    PermissionResult {
+                        path,
+                        mode,
+                        uid,
+                        gid,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            tracker.file_done();
+            observer.progress(&tracker, &result.path);
+            results.push(result);
+        }
+        results.reverse();
+        Ok(PermissionReport { results, errors })
+    }
+
+    /// Collect `path` and, when recursive, everything below it
+    ///
+    /// Directories whose access the edit widens are changed right away.
+    fn walk(
+        &self,
+        path: &Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        control: &JobControl,
+        items: &mut Vec<(PathBuf, Item)>,
+    ) -> Result<(), CoreError> {
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(path) = pending.pop() {
+            control.checkpoint()?;
+            let meta = match fs::symlink_metadata(&path) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    let error = CoreError::from_io_error(e, path.clone());
+                    items.push((path, Item::Done(Err((error, None)))));
+                    continue;
+                }
+            };
+            if !self.recursive || !meta.is_dir() {
+                items.push((path, Item::Pending(meta)));
+                continue;
+            }
+            let item = match self.opens(&meta) {
+                true => Item::Done(self.apply(&path, &meta, uid, gid)),
+                false => Item::Pending(meta),
+            };
+            match fs::read_dir(&path) {
+                Ok(dir) => {
+                    let mut children: Vec<_> =
+                        dir.filter_map(|e| e.ok().map(|e| e.file_name())).collect();
+                    children.sort_by(|a, b| b.cmp(a));
+                    pending.extend(children.into_iter().map(|child| path.join(child)));
+                    items.push((path, item));
+                }
+                Err(e) => {
+                    // The directory itself still changes; only its contents
+                    // are out of reach
+                    let error = CoreError::from_io_error(e, path.clone());
+                    let outcome = match item {
+                        Item::Pending(meta) => self.apply(&path, &meta, uid, gid),
+                        Item::Done(outcome) => outcome,
+                    };
+                    let failure = match outcome {
+                        Ok(meta) => (error, Some(owner_and_mode(&meta))),
+                        Err(failure) => failure,
+                    };
+                    items.push((path, Item::Done(Err(failure))));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the edit only adds read or search permission to a directory
+    fn opens(&self, meta: &Metadata) -> bool {
+        let Some(change) = &self.dir_mode else {
+            return false;
+        };
+        let current = meta.mode() & 0o555;
+        let mode = change.apply(meta.mode(), true) & 0o555;
+        mode & !current != 0 && current & !mode == 0
+    }
+
+    /// Change one item; owner first, as `chown` may clear setuid/setgid
+    fn apply(
+        &self,
+        path: &Path,
+        meta: &Metadata,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> Result<Metadata, Failure> {
+        let io = |e| {
+            (
+                CoreError::from_io_error(e, path.to_path_buf()),
+                Some(owner_and_mode(meta)),
+            )
+        };
+        let is_link = meta.file_type().is_symlink();
+        let owner = uid.filter(|&uid| uid != meta.uid());
+        let group = gid.filter(|&gid| gid != meta.gid());
+        if owner.is_some() || group.is_some() {
+            let changed = if is_link {
+                std::os::unix::fs::lchown(path, owner, group)
+            } else {
+                std::os::unix::fs::chown(path, owner, group)
+            };
+            changed.map_err(io)?;
+        }
+
+        let change = if meta.is_dir() {
+            &self.dir_mode
+        } else {
+            &self.file_mode
+        };
+        if let Some(change) = change.as_ref().filter(|_| !is_link) {
+            // Re-read: a new owner may have cleared bits
+            let current = match owner.or(group) {
+                Some(_) => fs::symlink_metadata(path).map_err(io)?.mode(),
+                None => meta.mode(),
+            };
+            let mode = change.apply(current, meta.is_dir());
+            if mode != current & 0o7777 {
+                fs::set_permissions(path, Permissions::from_mode(mode)).map_err(io)?;
+            }
+        }
+        fs::symlink_metadata(path).map_err(io)
+    }
+}
+
+/// An item's error and its mode, UID and GID, if they could be read
+type Failure = (CoreError, Option<(u32, u32, u32)>);
+
+/// An item found by the walk
+enum Item {
+    /// To be changed once everything below it is
+    Pending(Metadata),
+    /// Changed (or failed) while walking
+    Done(Result<Metadata, Failure>),
+}
+
+fn owner_and_mode(meta: &Metadata) -> (u32, u32, u32) {
+    (meta.mode() & 0o7777, meta.uid(), meta.gid())
+}
@@ -0,0 +1,19 @@
+//! Changing modes, owners and groups
+//!
+//! - `ModeChange`: octal or symbolic (`u+x,g-w`) mode changes, as `chmod`
+//!   takes them
+//! - `Accounts`: user and group names from `/etc/passwd` and `/etc/group`
+//! - `PermissionEdit`: applies a change to a selection, optionally
+//!   recursively with separate file and directory modes
+//! - `Acl`: POSIX access and default ACLs, as kept in the
+//!   `system.posix_acl_*` extended attributes
+
+mod accounts;
+mod acl;
+mod edit;
+mod mode;
+
+pub use accounts::Accounts;
+pub use acl::{Acl, AclEntry, AclKind, AclTag};
+pub use edit::{PermissionEdit, PermissionReport, PermissionResult};
+pub use mode::{Clause, ModeChange, ModeError};
@@ -0,0 +1,194 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+const SETUID: u32 = 0o4000;
+const SETGID: u32 = 0o2000;
+const STICKY: u32 = 0o1000;
+
+const USER: u32 = 0o700 | SETUID;
+const GROUP: u32 = 0o070 | SETGID;
+const OTHER: u32 = 0o007 | STICKY;
+
+/// A mode string that does not parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeError {
+    pub message: String,
+    /// Byte offset in the mode string
+    pub position: usize,
+}
+
+impl fmt::Display for ModeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid mode at {}: {}.", self.position, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Op {
+    Add,
+    Remove,
+    Set,
+}
+
+/// What an operation applies, before the `who` mask
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Perms {
+    /// Fixed bits (`r`, `w`, `x`, `s`, `t`), plus `X`
+    Bits { bits: u32, exec_if_searchable: bool },
+    /// Another class's current bits (`g=u`)
+    Copy(u32),
+}
+
+/// One comma-separated part of a symbolic mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clause {
+    who: u32,
+    ops: Vec<(Op, Perms)>,
+}
+
+/// A change to a Unix mode, as given to `chmod`
+///
+/// Either octal (`755`, `0644`) or symbolic clauses separated by commas
+/// (`u+x,g-w`, `a=rX`, `go=u`, `+t`). Without a class a clause applies to
+/// everyone; the umask is not consulted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModeChange {
+    Absolute(u32),
+    Symbolic(Vec<Clause>),
+}
+
+impl ModeChange {
+    pub fn parse(input: &str) -> Result<Self, ModeError> {
+        let error = |message: &str, position: usize| ModeError {
+            message: message.to_string(),
+            position,
+        };
+        if input.is_empty() {
+            return Err(error("empty mode", 0));
+        }
+        if input.bytes().all(|b| b.is_ascii_digit()) {
+            return match u32::from_str_radix(input, 8) {
+                Ok(mode) if input.len() <= 5 && mode <= 0o7777 => Ok(ModeChange::Absolute(mode)),
+                _ => Err(error("not an octal mode up to 7777", 0)),
+            };
+        }
+
+        let bytes = input.as_bytes();
+        let mut clauses = Vec::new();
+        let mut i = 0;
+        loop {
+            let mut who = 0;
+            while let Some(mask) = bytes
+                .get(i)
+                .and_then(|&b| class(b).or((b == b'a').then_some(USER | GROUP | OTHER)))
+            {
+                who |= mask;
+                i += 1;
+            }
+            let who = if who == 0 { USER | GROUP | OTHER } else { who };
+            let mut ops = Vec::new();
+            while let Some(op) = bytes.get(i).and_then(|&b| match b {
+                b'+' => Some(Op::Add),
+                b'-' => Some(Op::Remove),
+                b'=' => Some(Op::Set),
+                _ => None,
+            }) {
+                i += 1;
+                if let Some(source) = bytes.get(i).and_then(|&b| class(b)) {
+                    ops.push((op, Perms::Copy(source & 0o777)));
+                    i += 1;
+                    continue;
+                }
+                let mut bits = 0;
+                let mut exec_if_searchable = false;
+                while let Some(&b) = bytes.get(i) {
+                    match b {
+                        b'r' => bits |= 0o444,
+                        b'w' => bits |= 0o222,
+                        b'x' => bits |= 0o111,
+                        b'X' => exec_if_searchable = true,
+                        b's' => bits |= SETUID | SETGID,
+                        b't' => bits |= STICKY,
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                ops.push((
+                    op,
+                    Perms::Bits {
+                        bits,
+                        exec_if_searchable,
+                    },
+                ));
+            }
+            if ops.is_empty() {
+                return Err(error("expected one of + - =", i));
+            }
+            clauses.push(Clause { who, ops });
+            match bytes.get(i) {
+                None => break,
+                Some(b',') => i += 1,
+                Some(_) => return Err(error("unexpected character", i)),
+            }
+        }
+        Ok(ModeChange::Symbolic(clauses))
+    }
+
+    /// The new mode (permission bits only) for an item with `mode`
+    pub fn apply(&self, mode: u32, is_dir: bool) -> u32 {
+        let mut mode = mode & 0o7777;
+        let clauses = match self {
+            ModeChange::Absolute(absolute) => return *absolute,
+            ModeChange::Symbolic(clauses) => clauses,
+        };
+        for clause in clauses {
+            for (op, perms) in &clause.ops {
+                let bits = match *perms {
+                    Perms::Bits {
+                        bits,
+                        exec_if_searchable,
+                    } => {
+                        let searchable = is_dir || mode & 0o111 != 0;
+                        bits | if exec_if_searchable && searchable {
+                            0o111
+                        } else {
+                            0
+                        }
+                    }
+                    Perms::Copy(source) => {
+                        let class = (mode & source) >> source.trailing_zeros();
+                        class * 0o111
+                    }
+                };
+                let bits = bits & clause.who;
+                mode = match op {
+                    Op::Add => mode | bits,
+                    Op::Remove => mode & !bits,
+                    // Directories keep setuid/setgid unless named, like chmod
+                    Op::Set if is_dir => (mode & !(clause.who & !(SETUID | SETGID))) | bits,
+                    Op::Set => (mode & !clause.who) | bits,
+                };
+            }
+        }
+        mode
+    }
+}
+
+impl FromStr for ModeChange {
+    type Err = ModeError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::parse(input)
+    }
+}
+
+fn class(b: u8) -> Option<u32> {
+    match b {
+        b'u' => Some(USER),
+        b'g' => Some(GROUP),
+        b'o' => Some(OTHER),
+        _ => None,
+    }
+}
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-use super::provider::PreviewData;
+use super::provider::{ImageFormat, PreviewData};
+use super::thumbnail::{ThumbnailSize, ThumbnailStore};
 
 /// Cached preview entry
 struct CacheEntry {
@@ -12,11 +13,16 @@ struct CacheEntry {
 }
 
 /// LRU cache for previews
+///
+/// Memory is the first tier. With `with_thumbnails`, PNG image previews
+/// are also written to and read from the shared freedesktop thumbnail
+/// cache, so they survive restarts.
 pub struct PreviewCache {
     entries: HashMap<PathBuf, CacheEntry>,
     max_size_bytes: usize,
     current_size_bytes: usize,
     ttl: Duration,
+    thumbnails: Option<(ThumbnailStore, ThumbnailSize)>,
 }
 
 impl PreviewCache {
@@ -26,36 +32,169 @@ impl PreviewCache {
             max_size_bytes,
             current_size_bytes: 0,
             ttl,
+            thumbnails: None,
         }
     }
 
+    /// Add the on-disk tier, looking up thumbnails of at least `size`
+    pub fn with_thumbnails(mut self, store: ThumbnailStore, size: ThumbnailSize) -> Self {
+        self.thumbnails = Some((store, size));
+        self
+    }
+
     /// Get cached preview if valid
     pub fn get(&self, path: &PathBuf) -> Option<&PreviewData> {
-        todo!()
+        self.entries
+            .get(path)
+            .filter(|e| e.created.elapsed() < self.ttl)
+            .map(|e| &e.data)
+    }
+
+    /// Get a preview from memory, falling back to the thumbnail cache
+    ///
+    /// Disk hits are promoted into memory.
+    pub fn load(&mut self, path: &Path) -> Option<PreviewData> {
+        if let Some(data) = self.get(&path.to_path_buf()) {
+            return Some(data.clone());
+        }
+        let (store, size) = self.thumbnails.as_ref()?;
+        let thumb = store.lookup(path, *size).ok()??;
+        let data = PreviewData::Image {
+            width: thumb.width,
+            height: thumb.height,
+            // The spec does not require the original size to be recorded
+            original_width: thumb.width,
+            original_height: thumb.height,
+            data: thumb.data,
+            format: ImageFormat::Png,
+        };
+        self.insert(path.to_path_buf(), data.clone());
+        Some(data)
     }
 
     /// Store preview in cache
     pub fn put(&mut self, path: PathBuf, data: PreviewData) {
-        todo!()
+        if let (
+            Some((store, _)),
+            PreviewData::Image {
+                data: png,
+                format: ImageFormat::Png,
+                width,
+                height,
+                ..
+            },
+        ) = (&self.thumbnails, &data)
+            && let Some(size) = ThumbnailSize::for_edge(*width.max(height))
+        {
+            let _ = store.store(&path, size, png);
+        }
+        self.insert(path, data);
+    }
+
+    /// Whether generating a preview for `path` failed before
+    pub fn has_failed(&self, path: &Path) -> bool {
+        self.thumbnails
+            .as_ref()
+            .is_some_and(|(store, _)| store.has_failed(path))
+    }
+
+    /// Remember that no preview can be made for `path` until it changes
+    pub fn mark_failed(&self, path: &Path) {
+        if let Some((store, _)) = &self.thumbnails {
+            let _ = store.mark_failed(path);
+        }
     }
 
     /// Invalidate cache entry
     pub fn invalidate(&mut self, path: &PathBuf) {
-        todo!()
+        if let Some(entry) = self.entries.remove(path) {
+            self.current_size_bytes -= entry.size_bytes;
+        }
+        if let Some((store, _)) = &self.thumbnails {
+            store.remove(path);
+        }
     }
 
     /// Clear all entries
     pub fn clear(&mut self) {
-        todo!()
+        self.entries.clear();
+        self.current_size_bytes = 0;
+    }
+
+    fn insert(&mut self, path: PathBuf, data: PreviewData) {
+        let size_bytes = Self::estimate_size(&data);
+        if size_bytes > self.max_size_bytes {
+            return;
+        }
+        if let Some(old) = self.entries.remove(&path) {
+            self.current_size_bytes -= old.size_bytes;
+        }
+        self.evict(size_bytes);
+        self.current_size_bytes += size_bytes;
+        self.entries.insert(
+            path,
+            CacheEntry {
+                data,
+                created: Instant::now(),
+                size_bytes,
+            },
+        );
     }
 
     /// Evict oldest entries to make room
     fn evict(&mut self, needed_bytes: usize) {
-        todo!()
+        let now = Instant::now();
+        let expired: Vec<PathBuf> = self
+            .entries
+            .iter()
+            .filter(|(_, e)| now.duration_since(e.created) >= self.ttl)
+            .map(|(p, _)| p.clone())
+            .collect();
+        for path in expired {
+            if let Some(entry) = self.entries.remove(&path) {
+                self.current_size_bytes -= entry.size_bytes;
+            }
+        }
+
+        while self.current_size_bytes + needed_bytes > self.max_size_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.created)
+                .map(|(p, _)| p.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.current_size_bytes -= entry.size_bytes;
+            }
+        }
     }
 
     /// Estimate size of preview data
     fn estimate_size(data: &PreviewData) -> usize {
-        todo!()
+        let payload = match data {
+            PreviewData::Text { content, .. } => content.len(),
+            PreviewData::HighlightedText { content, language, theme, .. } => {
+                content.len() + language.len() + theme.len()
+            }
+            PreviewData::Image { data, .. } => data.len(),
+            PreviewData::Audio { waveform, album_art, .. } => {
+                waveform.as_ref().map_or(0, |w| w.len() * size_of::<f32>())
+                    + album_art.as_ref().map_or(0, Vec::len)
+            }
+            PreviewData::Video { thumbnails, .. } => thumbnails.iter().map(|t| t.data.len()).sum(),
+            PreviewData::Document { pages, .. } => pages.iter().map(|p| p.image.len()).sum(),
+            PreviewData::Archive { entries, .. } => entries.iter().map(|e| e.path.len() + 24).sum(),
+            PreviewData::Diff { diff } => diff
+                .hunks
+                .iter()
+                .flat_map(|h| &h.lines)
+                .map(|l| l.spans.iter().map(|s| s.text.len() + 32).sum::<usize>() + 48)
+                .sum(),
+            PreviewData::Binary { hex_dump, .. } => hex_dump.len(),
+            PreviewData::Unsupported { mime_type, reason } => mime_type.len() + reason.len(),
+        };
+        size_of::<PreviewData>() + payload
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,11 @@
 mod cache;
+mod png;
 mod provider;
 pub mod providers;
 mod registry;
+mod thumbnail;
 
 pub use cache::PreviewCache;
-pub use provider::{PreviewData, PreviewOptions, PreviewProvider};
-pub use registry::PreviewRegistry;
\ No newline at end of file
+pub use provider::{ImageFormat, PreviewData, PreviewOptions, PreviewProvider};
+pub use registry::PreviewRegistry;
+pub use thumbnail::{Thumbnail, ThumbnailSize, ThumbnailStore};
\ No newline at end of file
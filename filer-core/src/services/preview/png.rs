@@ -0,0 +1,121 @@
+//! Minimal PNG chunk handling for thumbnail metadata
+//!
+//! Only the container is parsed; pixel data is never decoded.
+
+use std::collections::HashMap;
+
+use crate::errors::CoreError;
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+struct Chunk<'a> {
+    kind: [u8; 4],
+    data: &'a [u8],
+    /// The whole chunk including length, type and CRC
+    raw: &'a [u8],
+}
+
+fn chunks(png: &[u8]) -> Result<Vec<Chunk<'_>>, CoreError> {
+    if !png.starts_with(&SIGNATURE) {
+        return Err(CoreError::InvalidData);
+    }
+    let mut out = Vec::new();
+    let mut pos = SIGNATURE.len();
+    while pos < png.len() {
+        let header = png.get(pos..pos + 8).ok_or(CoreError::InvalidData)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let end = pos + 12 + len;
+        let raw = png.get(pos..end).ok_or(CoreError::InvalidData)?;
+        out.push(Chunk {
+            kind,
+            data: &raw[8..8 + len],
+            raw,
+        });
+        pos = end;
+        if &kind == b"IEND" {
+            return Ok(out);
+        }
+    }
+    Err(CoreError::InvalidData)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/// Image width and height from the IHDR chunk
+pub(crate) fn dimensions(png: &[u8]) -> Result<(u32, u32), CoreError> {
+    let chunks = chunks(png)?;
+    let ihdr = chunks
+        .first()
+        .filter(|c| &c.kind == b"IHDR" && c.data.len() >= 8)
+        .ok_or(CoreError::InvalidData)?;
+    let d = ihdr.data;
+    Ok((
+        u32::from_be_bytes([d[0], d[1], d[2], d[3]]),
+        u32::from_be_bytes([d[4], d[5], d[6], d[7]]),
+    ))
+}
+
+/// All `tEXt` key/value pairs (Latin-1 decoded)
+pub(crate) fn text_chunks(png: &[u8]) -> Result<HashMap<String, String>, CoreError> {
+    let latin1 = |bytes: &[u8]| bytes.iter().map(|&b| b as char).collect::<String>();
+    Ok(chunks(png)?
+        .into_iter()
+        .filter(|c| &c.kind == b"tEXt")
+        .filter_map(|c| {
+            let split = c.data.iter().position(|&b| b == 0)?;
+            Some((latin1(&c.data[..split]), latin1(&c.data[split + 1..])))
+        })
+        .collect())
+}
+
+/// Copy of `png` with the given `tEXt` entries set (replacing same keys)
+pub(crate) fn with_text_chunks(png: &[u8], entries: &[(&str, String)]) -> Result<Vec<u8>, CoreError> {
+    let chunks = chunks(png)?;
+    let mut out = Vec::with_capacity(png.len() + 256);
+    out.extend_from_slice(&SIGNATURE);
+    for chunk in chunks {
+        if &chunk.kind == b"IEND" {
+            for (key, value) in entries {
+                let mut data = latin1_bytes(key);
+                data.push(0);
+                data.extend(latin1_bytes(value));
+                write_chunk(&mut out, b"tEXt", &data);
+            }
+        } else if &chunk.kind == b"tEXt" {
+            let key = chunk.data.split(|&b| b == 0).next().unwrap_or_default();
+            if entries.iter().any(|(k, _)| latin1_bytes(k) == key) {
+                continue;
+            }
+        }
+        out.extend_from_slice(chunk.raw);
+    }
+    Ok(out)
+}
+
+/// A valid 1x1 transparent PNG (used for `fail/` markers)
+pub(crate) fn empty_image() -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+    // 1x1, 8-bit RGBA, no interlace
+    write_chunk(&mut out, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+    // zlib stream with one stored block: filter byte + 4 zero bytes
+    let idat = [
+        0x78, 0x01, 0x01, 0x05, 0x00, 0xFA, 0xFF, 0, 0, 0, 0, 0, 0x00, 0x05, 0x00, 0x01,
+    ];
+    write_chunk(&mut out, b"IDAT", &idat);
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// tEXt is Latin-1; characters outside it become '?'
+fn latin1_bytes(s: &str) -> Vec<u8> {
+    s.chars().map(|c| u8::try_from(c).unwrap_or(b'?')).collect()
+}
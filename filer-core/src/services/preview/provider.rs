@@ -2,6 +2,7 @@ use std::path::Path;
 use async_trait::async_trait;
 
 use crate::errors::CoreError;
+use crate::services::diff::TextDiff;
 use crate::services::mime::MimeCategory;
 
 /// Generated preview data
@@ -58,6 +59,11 @@ pub enum PreviewData {
         truncated: bool,
     },
 
+    /// Differences between two files
+    Diff {
+        diff: TextDiff,
+    },
+
     /// Binary hex dump
     Binary {
         hex_dump: String,
@@ -0,0 +1,286 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use md5::{Digest, Md5};
+
+use crate::errors::CoreError;
+use crate::services::scheduler::{DeviceKey, IoClass, IoPermit, IoScheduler, IoTicket};
+
+use super::png;
+
+const KEY_URI: &str = "Thumb::URI";
+const KEY_MTIME: &str = "Thumb::MTime";
+const KEY_SIZE: &str = "Thumb::Size";
+
+/// Thumbnail size buckets from the freedesktop thumbnail spec
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ThumbnailSize {
+    /// 128x128
+    Normal,
+    /// 256x256
+    Large,
+    /// 512x512
+    XLarge,
+}
+
+impl ThumbnailSize {
+    pub const ALL: [ThumbnailSize; 3] = [ThumbnailSize::Normal, ThumbnailSize::Large, ThumbnailSize::XLarge];
+
+    /// Maximum edge length in pixels
+    pub fn pixels(self) -> u32 {
+        match self {
+            ThumbnailSize::Normal => 128,
+            ThumbnailSize::Large => 256,
+            ThumbnailSize::XLarge => 512,
+        }
+    }
+
+    /// Directory name under the thumbnail root
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            ThumbnailSize::Normal => "normal",
+            ThumbnailSize::Large => "large",
+            ThumbnailSize::XLarge => "x-large",
+        }
+    }
+
+    /// Smallest bucket that holds an image with this longest edge
+    pub fn for_edge(edge: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| edge <= s.pixels())
+    }
+}
+
+/// A thumbnail read from disk
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    /// PNG bytes
+    pub data: Vec<u8>,
+    pub size: ThumbnailSize,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Shared on-disk thumbnail cache (`$XDG_CACHE_HOME/thumbnails`)
+///
+/// Thumbnails are PNGs named by the MD5 of the file URI and tagged with
+/// `Thumb::URI` and `Thumb::MTime`, so thumbnails written by other desktop
+/// applications are reused and theirs can reuse ours. Files that could not
+/// be thumbnailed get a marker under `fail/<app>/`. With an `IoScheduler`
+/// each access first waits for a preview slot.
+#[derive(Debug, Clone)]
+pub struct ThumbnailStore {
+    root: PathBuf,
+    app: String,
+    scheduler: Option<IoScheduler>,
+}
+
+impl ThumbnailStore {
+    /// Open the user's thumbnail cache
+    pub fn new() -> Result<Self, CoreError> {
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+            .ok_or_else(|| CoreError::InvalidPath("$XDG_CACHE_HOME and $HOME are unset".to_string()))?;
+        Ok(Self::with_root(cache_home.join("thumbnails")))
+    }
+
+    /// Use a custom thumbnail root directory
+    pub fn with_root(root: PathBuf) -> Self {
+        Self {
+            root,
+            app: "filer".to_string(),
+            scheduler: None,
+        }
+    }
+
+    /// Take a preview slot from the I/O scheduler before touching the disk
+    pub fn with_scheduler(mut self, scheduler: IoScheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// `file://` URI of a path, escaped the way GLib does
+    pub fn uri_for(path: &Path) -> String {
+        let mut uri = String::from("file://");
+        for &b in path.as_os_str().as_encoded_bytes() {
+            if b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&b) {
+                uri.push(b as char);
+            } else {
+                uri.push_str(&format!("%{b:02X}"));
+            }
+        }
+        uri
+    }
+
+    /// Where the thumbnail of `path` at `size` lives
+    pub fn thumbnail_path(&self, path: &Path, size: ThumbnailSize) -> PathBuf {
+        self.root.join(size.dir_name()).join(Self::file_name(path))
+    }
+
+    /// Where the failure marker of `path` lives
+    pub fn fail_path(&self, path: &Path) -> PathBuf {
+        self.root.join("fail").join(&self.app).join(Self::file_name(path))
+    }
+
+    /// Find a valid thumbnail of at least `size`
+    ///
+    /// Stale thumbnails (the file changed since) are deleted on the way.
+    pub fn lookup(&self, path: &Path, size: ThumbnailSize) -> Result<Option<Thumbnail>, CoreError> {
+        let path = absolute(path)?;
+        let _permit = self.slot(&path);
+        let (mtime, file_size) = file_stamp(&path)?;
+        let uri = Self::uri_for(&path);
+
+        for bucket in ThumbnailSize::ALL.into_iter().filter(|s| *s >= size) {
+            let thumb_path = self.thumbnail_path(&path, bucket);
+            let Ok(data) = fs::read(&thumb_path) else { continue };
+            if !is_current(&data, &uri, mtime, file_size) {
+                let _ = fs::remove_file(&thumb_path);
+                continue;
+            }
+            let Ok((width, height)) = png::dimensions(&data) else { continue };
+            return Ok(Some(Thumbnail {
+                data,
+                size: bucket,
+                width,
+                height,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Save a PNG thumbnail for `path`, tagging it with the spec metadata
+    pub fn store(&self, path: &Path, size: ThumbnailSize, png_data: &[u8]) -> Result<PathBuf, CoreError> {
+        let path = absolute(path)?;
+        let _permit = self.slot(&path);
+        let target = self.thumbnail_path(&path, size);
+        self.write_tagged(&path, &target, png_data)?;
+        Ok(target)
+    }
+
+    /// Whether thumbnailing `path` already failed for its current version
+    pub fn has_failed(&self, path: &Path) -> bool {
+        let Ok(path) = absolute(path) else { return false };
+        let _permit = self.slot(&path);
+        let Ok((mtime, file_size)) = file_stamp(&path) else { return false };
+        let marker = self.fail_path(&path);
+        match fs::read(&marker) {
+            Ok(data) if is_current(&data, &Self::uri_for(&path), mtime, file_size) => true,
+            Ok(_) => {
+                let _ = fs::remove_file(&marker);
+                false
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Remember that `path` cannot be thumbnailed until it changes
+    pub fn mark_failed(&self, path: &Path) -> Result<(), CoreError> {
+        let path = absolute(path)?;
+        let _permit = self.slot(&path);
+        let target = self.fail_path(&path);
+        self.write_tagged(&path, &target, &png::empty_image())
+    }
+
+    /// Delete every thumbnail and failure marker of `path`
+    pub fn remove(&self, path: &Path) {
+        let Ok(path) = absolute(path) else { return };
+        for size in ThumbnailSize::ALL {
+            let _ = fs::remove_file(self.thumbnail_path(&path, size));
+        }
+        let _ = fs::remove_file(self.fail_path(&path));
+    }
+
+    /// Preview slot on the devices of `path` and of the cache
+    fn slot(&self, path: &Path) -> Option<IoPermit> {
+        let scheduler = self.scheduler.as_ref()?;
+        let ticket = IoTicket::new(IoClass::Preview)
+            .on(DeviceKey::of_path(path))
+            .on(DeviceKey::of_path(&self.root));
+        Some(scheduler.request(ticket).wait())
+    }
+
+    fn file_name(path: &Path) -> String {
+        let digest = Md5::digest(Self::uri_for(path).as_bytes());
+        let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+        format!("{hex}.png")
+    }
+
+    /// Tag `png_data` and write it atomically to `target` (mode 0600)
+    fn write_tagged(&self, path: &Path, target: &Path, png_data: &[u8]) -> Result<(), CoreError> {
+        let (mtime, file_size) = file_stamp(path)?;
+        let tagged = png::with_text_chunks(
+            png_data,
+            &[
+                (KEY_URI, Self::uri_for(path)),
+                (KEY_MTIME, mtime.to_string()),
+                (KEY_SIZE, file_size.to_string()),
+            ],
+        )?;
+
+        let dir = target.parent().ok_or_else(|| CoreError::InvalidPath(target.display().to_string()))?;
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .map_err(|e| CoreError::from_io_error(e, dir.to_path_buf()))?;
+
+        let tmp = dir.join(format!(
+            ".{}.{}.tmp",
+            target.file_name().unwrap_or_default().to_string_lossy(),
+            std::process::id()
+        ));
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)
+            .map_err(|e| CoreError::from_io_error(e, tmp.clone()))?;
+        file.write_all(&tagged)
+            .map_err(|e| CoreError::from_io_error(e, tmp.clone()))?;
+        drop(file);
+        fs::rename(&tmp, target).map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            CoreError::from_io_error(e, target.to_path_buf())
+        })
+    }
+}
+
+fn absolute(path: &Path) -> Result<PathBuf, CoreError> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        path.canonicalize()
+            .map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+    }
+}
+
+/// Modification time (whole seconds) and size of a file
+fn file_stamp(path: &Path) -> Result<(u64, u64), CoreError> {
+    let meta = fs::metadata(path).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    Ok((mtime, meta.len()))
+}
+
+/// Whether a cached PNG still describes the file
+fn is_current(data: &[u8], uri: &str, mtime: u64, file_size: u64) -> bool {
+    let Ok(text) = png::text_chunks(data) else { return false };
+    let matches = |key: &str, expected: &str| text.get(key).is_some_and(|v| v == expected);
+    matches(KEY_URI, uri)
+        && matches(KEY_MTIME, &mtime.to_string())
+        // Optional in the spec, but must agree when present
+        && text.get(KEY_SIZE).is_none_or(|v| *v == file_size.to_string())
+}
@@ -0,0 +1,14 @@
+//! Batch rename
+//!
+//! A `RenameRule` derives new names through find/replace, a token
+//! template, counters and case transforms. `RenamePlan` previews the
+//! result for a batch and flags collisions and cycles without touching
+//! the filesystem; applying it renames everything or nothing.
+
+mod plan;
+mod rule;
+mod template;
+
+pub(crate) use plan::{rename_all, rename_new};
+pub use plan::{RenameItem, RenamePlan, RenameStatus};
+pub use rule::{CaseTransform, Counter, FindReplace, RenameRule, RenameSubject, Renamer, RuleError};
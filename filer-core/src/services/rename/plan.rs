@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::errors::CoreError;
+use crate::model::node::NodeId;
+use crate::utils;
+
+use super::rule::{RenameSubject, Renamer};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameStatus {
+    /// Will be renamed
+    Ready,
+    /// The new name is the current one
+    Unchanged,
+    /// No valid name could be built (the reason says why)
+    Invalid(String),
+    /// Another item of the batch gets the same name
+    Duplicate,
+    /// Something outside the batch already has the name
+    Exists,
+}
+
+/// One line of a rename preview
+#[derive(Debug, Clone)]
+pub struct RenameItem {
+    pub node: NodeId,
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub status: RenameStatus,
+}
+
+/// Dry run of a batch rename
+///
+/// Built without touching the filesystem. `apply` only proceeds when no
+/// item is `Invalid`, `Duplicate` or `Exists`.
+#[derive(Debug, Clone)]
+pub struct RenamePlan {
+    pub items: Vec<RenameItem>,
+    /// Items whose new names form a cycle (a→b, b→a), as indices into
+    /// `items`; they go through a temporary name
+    pub cycles: Vec<Vec<usize>>,
+}
+
+impl RenamePlan {
+    /// Plan renaming `subjects`, numbered in the given order
+    pub fn new(renamer: &Renamer, subjects: &[RenameSubject]) -> Self {
+        let mut seen = HashSet::new();
+        let mut items: Vec<RenameItem> = subjects
+            .iter()
+            .enumerate()
+            .map(|(index, subject)| {
+                let from = subject.node.path.clone();
+                let (to, status) = match renamer.name(subject, index) {
+                    _ if !seen.insert(from.clone()) => (
+                        from.clone(),
+                        RenameStatus::Invalid("listed twice".to_string()),
+                    ),
+                    Ok(name) => match check_name(&name) {
+                        Ok(()) if name == subject.node.name => {
+                            (from.clone(), RenameStatus::Unchanged)
+                        }
+                        Ok(()) => (from.with_file_name(&name), RenameStatus::Ready),
+                        Err(reason) => (from.clone(), RenameStatus::Invalid(reason)),
+                    },
+                    Err(reason) => (from.clone(), RenameStatus::Invalid(reason)),
+                };
+                RenameItem {
+                    node: subject.node.id,
+                    from,
+                    to,
+                    status,
+                }
+            })
+            .collect();
+
+        let mut claimed: HashMap<&Path, usize> = HashMap::new();
+        for item in items.iter().filter(|i| i.status == RenameStatus::Ready) {
+            *claimed.entry(&item.to).or_default() += 1;
+        }
+        let duplicates: HashSet<PathBuf> = claimed
+            .into_iter()
+            .filter(|&(_, count)| count > 1)
+            .map(|(path, _)| path.to_path_buf())
+            .collect();
+        for item in &mut items {
+            if item.status == RenameStatus::Ready && duplicates.contains(&item.to) {
+                item.status = RenameStatus::Duplicate;
+            }
+        }
+
+        // A taken name is fine if its owner is renamed away as part of the
+        // batch; blocking one item can block the item waiting for its name
+        loop {
+            let leaving: HashSet<&Path> = items
+                .iter()
+                .filter(|i| i.status == RenameStatus::Ready)
+                .map(|i| i.from.as_path())
+                .collect();
+            let blocked: Vec<usize> = items
+                .iter()
+                .enumerate()
+                .filter(|(_, i)| {
+                    i.status == RenameStatus::Ready && !leaving.contains(i.to.as_path())
+                })
+                .filter(|(_, i)| is_taken(&i.from, &i.to))
+                .map(|(index, _)| index)
+                .collect();
+            if blocked.is_empty() {
+                break;
+            }
+            for index in blocked {
+                items[index].status = RenameStatus::Exists;
+            }
+        }
+
+        let cycles = find_cycles(&items);
+        Self { items, cycles }
+    }
+
+    /// Whether `apply` would proceed
+    pub fn is_clean(&self) -> bool {
+        self.items
+            .iter()
+            .all(|i| matches!(i.status, RenameStatus::Ready | RenameStatus::Unchanged))
+    }
+
+    /// `(from, to)` of the items that will be renamed
+    pub fn renames(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.items
+            .iter()
+            .filter(|i| i.status == RenameStatus::Ready)
+            .map(|i| (i.from.clone(), i.to.clone()))
+            .collect()
+    }
+
+    /// Rename every `Ready` item, or nothing at all
+    pub fn apply(&self) -> Result<Vec<(PathBuf, PathBuf)>, CoreError> {
+        if !self.is_clean() {
+            let blocked = self
+                .items
+                .iter()
+                .filter(|i| !matches!(i.status, RenameStatus::Ready | RenameStatus::Unchanged));
+            return Err(CoreError::InvalidPath(format!(
+                "{} of the new names cannot be used",
+                blocked.count()
+            )));
+        }
+        let renames = self.renames();
+        rename_all(&renames)?;
+        Ok(renames)
+    }
+}
+
+/// Reject names that are not a single path component
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." {
+        Err(format!("\"{name}\" is not a valid name"))
+    } else if name.contains(['/', '\0']) {
+        Err(format!("\"{name}\" contains a path separator"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether `to` exists and is not `from` itself (a case-only rename on a
+/// case-insensitive filesystem)
+fn is_taken(from: &Path, to: &Path) -> bool {
+    match (fs::symlink_metadata(from), fs::symlink_metadata(to)) {
+        (Ok(a), Ok(b)) => (a.dev(), a.ino()) != (b.dev(), b.ino()),
+        (_, to) => to.is_ok(),
+    }
+}
+
+fn find_cycles(items: &[RenameItem]) -> Vec<Vec<usize>> {
+    let ready: Vec<usize> = (0..items.len())
+        .filter(|&i| items[i].status == RenameStatus::Ready)
+        .collect();
+    let by_source: HashMap<&Path, usize> = ready
+        .iter()
+        .map(|&i| (items[i].from.as_path(), i))
+        .collect();
+    let mut visited = HashSet::new();
+    let mut cycles = Vec::new();
+    for &start in &ready {
+        let mut path = Vec::new();
+        let mut current = Some(start);
+        while let Some(index) = current.filter(|i| !visited.contains(i)) {
+            visited.insert(index);
+            path.push(index);
+            current = by_source.get(items[index].to.as_path()).copied();
+        }
+        // Ended on an item of this walk: everything from it on is a cycle
+        if let Some(end) = current
+            && let Some(at) = path.iter().position(|&i| i == end)
+        {
+            cycles.push(path.split_off(at));
+        }
+    }
+    cycles
+}
+
+/// Rename every `(from, to)` as one transaction
+///
+/// Targets must be distinct and either free or the source of another
+/// pair. Chains are renamed back to front and cycles through a temporary
+/// name. If any rename fails, the ones already done are reverted.
+pub(crate) fn rename_all(pairs: &[(PathBuf, PathBuf)]) -> Result<(), CoreError> {
+    let by_target: HashMap<&Path, usize> = pairs
+        .iter()
+        .enumerate()
+        .map(|(i, (_, to))| (to.as_path(), i))
+        .collect();
+    let sources: HashSet<&Path> = pairs.iter().map(|(from, _)| from.as_path()).collect();
+    // Where each item currently is; `None` once renamed
+    let mut current: Vec<Option<PathBuf>> =
+        pairs.iter().map(|(from, _)| Some(from.clone())).collect();
+    let mut done: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+    let mut ready: Vec<usize> = (0..pairs.len())
+        .filter(|&i| !sources.contains(pairs[i].1.as_path()))
+        .collect();
+    let mut result = Ok(());
+    'outer: loop {
+        while let Some(index) = ready.pop() {
+            let Some(at) = current[index].take() else {
+                continue;
+            };
+            let to = &pairs[index].1;
+            if let Err(e) = rename_new(&at, to) {
+                result = Err(e);
+                break 'outer;
+            }
+            done.push((at, to.clone()));
+            // The original name is free now
+            if let Some(&next) = by_target.get(pairs[index].0.as_path()) {
+                ready.push(next);
+            }
+        }
+        // What is left are cycles: park one member to unblock the rest
+        let Some(index) = (0..pairs.len()).find(|&i| current[i].is_some()) else {
+            break;
+        };
+        let from = current[index].take().unwrap_or_default();
+        let parked = utils::unique_name(&from.with_file_name(format!(
+            ".{}.filer-rename",
+            from.file_name().unwrap_or_default().to_string_lossy()
+        )));
+        if let Err(e) = rename_new(&from, &parked) {
+            result = Err(e);
+            break;
+        }
+        done.push((from.clone(), parked.clone()));
+        current[index] = Some(parked);
+        if let Some(&next) = by_target.get(from.as_path()) {
+            ready.push(next);
+        }
+    }
+
+    if result.is_err() {
+        for (from, to) in done.iter().rev() {
+            let _ = fs::rename(to, from);
+        }
+    }
+    result
+}
+
+/// `fs::rename` that refuses to replace an existing item
+///
+/// The check is part of the rename, so an item created at `to` meanwhile
+/// is never lost. Case-only renames of one item are plain renames.
+pub(crate) fn rename_new(from: &Path, to: &Path) -> Result<(), CoreError> {
+    // `to` is `from` itself if only the case changes on a case-insensitive
+    // filesystem
+    let itself = !is_taken(from, to) && fs::symlink_metadata(to).is_ok();
+    let result = match itself {
+        true => fs::rename(from, to),
+        false => utils::rename::rename_noreplace(from, to),
+    };
+    result.map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => CoreError::InvalidPath(format!("{} already exists", to.display())),
+        _ => CoreError::from_io_error(e, from.to_path_buf()),
+    })
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::path::Path;
+
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::model::node::{FileNode, NodeKind};
+use crate::services::metadata::ExtendedMetadata;
+
+use super::template::{Context, Template};
+
+/// How to derive new names, applied in field order
+///
+/// `template` tokens:
+/// - `{name}` name without extension, `{ext}` extension with its dot
+/// - `{parent}` name of the containing folder
+/// - `{n}` / `{n:WIDTH}` counter, see `Counter`
+/// - `{size}` size in bytes
+/// - `{mtime}` / `{mtime:FORMAT}` modification time (strftime, local time)
+/// - `{taken}` / `{taken:FORMAT}` EXIF date taken
+/// - `{artist}`, `{album}`, `{title}`, `{year}`, `{track}` / `{track:WIDTH}` audio tags
+///
+/// `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenameRule {
+    /// Applied to the current name
+    pub find: Option<FindReplace>,
+    /// New name built from tokens; `None` keeps the (replaced) name
+    pub template: Option<String>,
+    pub case: CaseTransform,
+    pub counter: Counter,
+}
+
+/// Replace every match in the name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindReplace {
+    pub find: String,
+    /// With `regex`, `$1` or `${name}` insert capture groups
+    pub replace: String,
+    pub regex: bool,
+    pub ignore_case: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaseTransform {
+    #[default]
+    Keep,
+    Lower,
+    Upper,
+    /// First letter of every word upper case, the rest lower case
+    Title,
+}
+
+/// Numbering for `{n}`: the i-th item gets `start + i * step`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counter {
+    pub start: u64,
+    pub step: u64,
+    /// Zero-padded to this many digits
+    pub width: usize,
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self {
+            start: 1,
+            step: 1,
+            width: 1,
+        }
+    }
+}
+
+/// An item to rename and what is known about it
+#[derive(Debug, Clone)]
+pub struct RenameSubject {
+    pub node: FileNode,
+    pub metadata: Option<ExtendedMetadata>,
+}
+
+impl RenameSubject {
+    pub fn new(node: FileNode) -> Self {
+        Self {
+            node,
+            metadata: None,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: ExtendedMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
+
+/// A rule that does not compile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    pub message: String,
+    /// Byte offset in the template
+    pub position: Option<usize>,
+}
+
+impl RuleError {
+    pub(crate) fn at(message: impl Into<String>, position: usize) -> Self {
+        Self {
+            message: message.into(),
+            position: Some(position),
+        }
+    }
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "Invalid rename rule at {}: {}.", position, self.message),
+            None => write!(f, "Invalid rename rule: {}.", self.message),
+        }
+    }
+}
+
+/// A compiled `RenameRule`
+#[derive(Debug, Clone)]
+pub struct Renamer {
+    find: Option<(Regex, String, bool)>,
+    template: Option<Template>,
+    case: CaseTransform,
+    counter: Counter,
+}
+
+impl Renamer {
+    pub fn new(rule: &RenameRule) -> Result<Self, RuleError> {
+        let find = match &rule.find {
+            Some(fr) if fr.find.is_empty() => {
+                return Err(RuleError {
+                    message: "nothing to find".to_string(),
+                    position: None,
+                });
+            }
+            Some(fr) => {
+                let pattern = if fr.regex {
+                    fr.find.clone()
+                } else {
+                    regex::escape(&fr.find)
+                };
+                let regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(fr.ignore_case)
+                    .build()
+                    .map_err(|e| RuleError {
+                        message: e.to_string(),
+                        position: None,
+                    })?;
+                Some((regex, fr.replace.clone(), fr.regex))
+            }
+            None => None,
+        };
+        let template = rule.template.as_deref().map(Template::parse).transpose()?;
+        Ok(Self {
+            find,
+            template,
+            case: rule.case,
+            counter: rule.counter,
+        })
+    }
+
+    /// Whether names depend on `RenameSubject::metadata`
+    pub fn needs_metadata(&self) -> bool {
+        self.template.as_ref().is_some_and(Template::needs_metadata)
+    }
+
+    /// New name of the `index`-th subject of a batch
+    ///
+    /// Fails with a reason when a token has no value for the subject.
+    pub fn name(&self, subject: &RenameSubject, index: usize) -> Result<String, String> {
+        let mut name = subject.node.name.clone();
+        if let Some((regex, replace, expand)) = &self.find {
+            name = if *expand {
+                regex.replace_all(&name, replace.as_str()).into_owned()
+            } else {
+                regex.replace_all(&name, NoExpand(replace)).into_owned()
+            };
+        }
+        if let Some(template) = &self.template {
+            let (stem, ext) = split_name(&name, &subject.node.kind);
+            let counter = self
+                .counter
+                .step
+                .saturating_mul(index as u64)
+                .saturating_add(self.counter.start);
+            name = template.render(&Context {
+                stem,
+                ext,
+                subject,
+                counter,
+                counter_width: self.counter.width,
+            })?;
+        }
+        Ok(match self.case {
+            CaseTransform::Keep => name,
+            CaseTransform::Lower => name.to_lowercase(),
+            CaseTransform::Upper => name.to_uppercase(),
+            CaseTransform::Title => title_case(&name),
+        })
+    }
+}
+
+/// Directories have no extension
+fn split_name<'a>(name: &'a str, kind: &NodeKind) -> (&'a str, &'a str) {
+    if matches!(kind, NodeKind::Directory { .. }) {
+        return (name, "");
+    }
+    match Path::new(name).extension() {
+        Some(ext) => name.split_at(name.len() - ext.len() - 1),
+        None => (name, ""),
+    }
+}
+
+fn title_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut word_start = true;
+    for c in name.chars() {
+        if word_start {
+            out.extend(c.to_uppercase());
+        } else {
+            out.extend(c.to_lowercase());
+        }
+        word_start = !c.is_alphanumeric() && !matches!(c, '\'' | '.');
+    }
+    out
+}
@@ -0,0 +1,243 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDateTime};
+
+use crate::services::metadata::{AudioTags, ExifData, ExtendedMetadata};
+
+use super::rule::{RenameSubject, RuleError};
+
+/// A parsed name template such as `{taken:%Y-%m-%d}_{n:3}{ext}`
+#[derive(Debug, Clone)]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Token(Token),
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Name,
+    Ext,
+    Parent,
+    /// Zero-padded to at least this width (the rule's width otherwise)
+    Counter(Option<usize>),
+    Size,
+    Modified(String),
+    Taken(String),
+    Artist,
+    Album,
+    Title,
+    Year,
+    Track(usize),
+}
+
+/// Values a template is rendered from
+pub(crate) struct Context<'a> {
+    /// Name without extension, after find/replace
+    pub stem: &'a str,
+    /// Extension including its dot, or empty
+    pub ext: &'a str,
+    pub subject: &'a RenameSubject,
+    pub counter: u64,
+    pub counter_width: usize,
+}
+
+const DEFAULT_DATE: &str = "%Y-%m-%d";
+
+impl Template {
+    pub(crate) fn parse(input: &str) -> Result<Self, RuleError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = input.char_indices().peekable();
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, c)| c == '{').is_some() => literal.push('{'),
+                '}' if chars.next_if(|&(_, c)| c == '}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut body = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, c)) => body.push(c),
+                            None => return Err(RuleError::at("unclosed '{'", position)),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Token(Token::parse(&body, position)?));
+                }
+                '}' => return Err(RuleError::at("unmatched '}'", position)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Whether rendering reads `ExtendedMetadata`
+    pub(crate) fn needs_metadata(&self) -> bool {
+        self.parts.iter().any(|part| {
+            matches!(
+                part,
+                Part::Token(
+                    Token::Taken(_)
+                        | Token::Artist
+                        | Token::Album
+                        | Token::Title
+                        | Token::Year
+                        | Token::Track(_)
+                )
+            )
+        })
+    }
+
+    /// Fails with a reason when a token has no value for the subject
+    pub(crate) fn render(&self, ctx: &Context) -> Result<String, String> {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Token(token) => out.push_str(&token.render(ctx)?),
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Token {
+    fn parse(body: &str, position: usize) -> Result<Self, RuleError> {
+        let (name, arg) = match body.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (body, None),
+        };
+        let width = |default: Option<usize>| match arg {
+            None => Ok(default),
+            Some(arg) => arg
+                .parse()
+                .map(Some)
+                .map_err(|_| RuleError::at(format!("invalid width \"{arg}\""), position)),
+        };
+        let date = || {
+            let format = arg.unwrap_or(DEFAULT_DATE);
+            if format.is_empty()
+                || StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+            {
+                return Err(RuleError::at(
+                    format!("invalid date format \"{format}\""),
+                    position,
+                ));
+            }
+            Ok(format.to_string())
+        };
+        let token = match name {
+            "name" => Token::Name,
+            "ext" => Token::Ext,
+            "parent" => Token::Parent,
+            "n" => Token::Counter(width(None)?),
+            "size" => Token::Size,
+            "mtime" => Token::Modified(date()?),
+            "taken" => Token::Taken(date()?),
+            "artist" => Token::Artist,
+            "album" => Token::Album,
+            "title" => Token::Title,
+            "year" => Token::Year,
+            "track" => Token::Track(width(Some(2))?.unwrap_or(2)),
+            _ => {
+                return Err(RuleError::at(
+                    format!("unknown token \"{{{body}}}\""),
+                    position,
+                ));
+            }
+        };
+        let takes_arg = matches!(
+            token,
+            Token::Counter(_) | Token::Modified(_) | Token::Taken(_) | Token::Track(_)
+        );
+        if arg.is_some() && !takes_arg {
+            return Err(RuleError::at(
+                format!("\"{{{name}}}\" takes no argument"),
+                position,
+            ));
+        }
+        Ok(token)
+    }
+
+    fn render(&self, ctx: &Context) -> Result<String, String> {
+        let node = &ctx.subject.node;
+        Ok(match self {
+            Token::Name => ctx.stem.to_string(),
+            Token::Ext => ctx.ext.to_string(),
+            Token::Parent => node
+                .path
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Token::Counter(width) => {
+                format!(
+                    "{:0width$}",
+                    ctx.counter,
+                    width = width.unwrap_or(ctx.counter_width)
+                )
+            }
+            Token::Size => node.size.to_string(),
+            Token::Modified(format) => {
+                let modified = node.modified.ok_or("no modification time")?;
+                DateTime::<Local>::from(modified).format(format).to_string()
+            }
+            Token::Taken(format) => {
+                let taken = exif(ctx)
+                    .and_then(|exif| exif.date_taken.as_deref())
+                    .ok_or("no EXIF date taken")?;
+                parse_exif_date(taken)
+                    .ok_or_else(|| format!("unreadable EXIF date \"{taken}\""))?
+                    .format(format)
+                    .to_string()
+            }
+            Token::Artist => tag(ctx, "artist", |t| t.artist.clone())?,
+            Token::Album => tag(ctx, "album", |t| t.album.clone())?,
+            Token::Title => tag(ctx, "title", |t| t.title.clone())?,
+            Token::Year => tag(ctx, "year", |t| t.year.map(|y| y.to_string()))?,
+            Token::Track(width) => {
+                let track = tag(ctx, "track", |t| t.track.map(|n| n.to_string()))?;
+                format!("{track:0>width$}")
+            }
+        })
+    }
+}
+
+fn exif<'a>(ctx: &Context<'a>) -> Option<&'a ExifData> {
+    match &ctx.subject.metadata {
+        Some(ExtendedMetadata::Image(image)) => image.exif.as_ref(),
+        _ => None,
+    }
+}
+
+fn tag(
+    ctx: &Context,
+    name: &str,
+    get: impl FnOnce(&AudioTags) -> Option<String>,
+) -> Result<String, String> {
+    match &ctx.subject.metadata {
+        Some(ExtendedMetadata::Audio(audio)) => get(&audio.tags),
+        _ => None,
+    }
+    .ok_or_else(|| format!("no {name} tag"))
+}
+
+/// EXIF writes `2023:07:14 18:22:05`; some tools use ISO 8601 instead
+fn parse_exif_date(value: &str) -> Option<NaiveDateTime> {
+    [
+        "%Y:%m:%d %H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value.trim(), format).ok())
+}
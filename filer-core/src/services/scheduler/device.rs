@@ -0,0 +1,52 @@
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// What a concurrency limit applies to: a local block device or a host
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeviceKey {
+    /// `st_dev` of a local filesystem
+    Local(u64),
+    /// Host name (and port) of a remote provider
+    Remote(String),
+}
+
+impl DeviceKey {
+    /// Device holding `path`, or its nearest existing ancestor for paths
+    /// that are about to be created
+    pub fn of_path(path: &Path) -> Self {
+        let dev = path
+            .ancestors()
+            .find_map(|p| fs::metadata(p).ok())
+            .map_or(0, |meta| meta.dev());
+        DeviceKey::Local(dev)
+    }
+
+    pub fn remote(host: impl Into<String>) -> Self {
+        DeviceKey::Remote(host.into())
+    }
+
+    /// Host part of a URL such as `https://user@dav.example.com:8443/files`
+    pub fn of_url(url: &str) -> Self {
+        let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        DeviceKey::Remote(host.to_ascii_lowercase())
+    }
+
+    pub fn is_remote(&self) -> bool {
+        matches!(self, DeviceKey::Remote(_))
+    }
+}
+
+impl fmt::Display for DeviceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceKey::Local(dev) => write!(f, "dev:{dev:x}"),
+            DeviceKey::Remote(host) => write!(f, "host:{host}"),
+        }
+    }
+}
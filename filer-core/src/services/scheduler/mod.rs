@@ -0,0 +1,15 @@
+//! Global I/O scheduler
+//!
+//! Listings, previews, indexing and transfers all hit the same disks.
+//! Actors ask `IoScheduler` for a slot before doing I/O so that a big copy
+//! cannot starve directory browsing:
+//!
+//! - priority classes: listings > previews > background > bulk transfers
+//! - concurrency limits per local device (`st_dev`) or remote host
+//! - fairness between sessions within a class
+
+mod device;
+mod queue;
+
+pub use device::DeviceKey;
+pub use queue::{IoClass, IoPermit, IoScheduler, IoTicket, PendingIo, SchedulerConfig};
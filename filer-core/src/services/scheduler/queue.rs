@@ -0,0 +1,355 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use flume::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::session::SessionId;
+
+use super::device::DeviceKey;
+
+/// Priority classes, highest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IoClass {
+    /// Directory listings someone is waiting for
+    Listing,
+    /// Previews and thumbnails of visible items
+    Preview,
+    /// Indexing, hashing and other work nobody waits for
+    Background,
+    /// Copies, moves and other long transfers
+    Bulk,
+}
+
+impl IoClass {
+    fn rank(self) -> u8 {
+        match self {
+            IoClass::Listing => 3,
+            IoClass::Preview => 2,
+            IoClass::Background => 1,
+            IoClass::Bulk => 0,
+        }
+    }
+
+    /// Kept out of the reserved slots
+    fn is_deferrable(self) -> bool {
+        matches!(self, IoClass::Background | IoClass::Bulk)
+    }
+}
+
+/// What a piece of I/O work needs before it may start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoTicket {
+    pub class: IoClass,
+    /// Every device it touches; a slot is taken on each
+    pub devices: Vec<DeviceKey>,
+    /// Session to charge for fairness (`None` for internal work)
+    pub session: Option<SessionId>,
+}
+
+impl IoTicket {
+    pub fn new(class: IoClass) -> Self {
+        Self {
+            class,
+            devices: Vec::new(),
+            session: None,
+        }
+    }
+
+    pub fn on(mut self, device: DeviceKey) -> Self {
+        if !self.devices.contains(&device) {
+            self.devices.push(device);
+        }
+        self
+    }
+
+    pub fn for_session(mut self, session: SessionId) -> Self {
+        self.session = Some(session);
+        self
+    }
+}
+
+/// Scheduler limits
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Concurrent work items per local device
+    pub local_slots: usize,
+    /// Concurrent work items per remote host
+    pub remote_slots: usize,
+    /// Slots per device that background and bulk work may not take, so
+    /// listings and previews never queue behind a big copy
+    pub reserved: usize,
+    /// Per-device overrides of `local_slots` / `remote_slots`
+    pub limits: HashMap<DeviceKey, usize>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            local_slots: 4,
+            remote_slots: 2,
+            reserved: 1,
+            limits: HashMap::new(),
+        }
+    }
+}
+
+/// Global I/O queue shared by all actors
+///
+/// Work asks for a slot on every device it touches with an `IoTicket`.
+/// When a slot frees up, the waiting ticket of the highest class gets it;
+/// within a class, the session with the least running work goes first,
+/// then the oldest request. Cheap to clone; clones share the queue.
+#[derive(Clone)]
+pub struct IoScheduler {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for IoScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IoScheduler")
+            .field("queued", &self.queued())
+            .finish_non_exhaustive()
+    }
+}
+
+struct Inner {
+    state: Mutex<State>,
+}
+
+struct State {
+    config: SchedulerConfig,
+    next_id: u64,
+    in_use: HashMap<DeviceKey, usize>,
+    per_session: HashMap<Option<SessionId>, usize>,
+    waiting: Vec<Waiter>,
+}
+
+struct Waiter {
+    id: u64,
+    ticket: IoTicket,
+    grant: Sender<IoPermit>,
+}
+
+impl IoScheduler {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    config,
+                    next_id: 0,
+                    in_use: HashMap::new(),
+                    per_session: HashMap::new(),
+                    waiting: Vec::new(),
+                }),
+            }),
+        }
+    }
+
+    /// Queue `ticket`; the returned handle resolves to a permit
+    ///
+    /// Dropping the handle before it is granted leaves the queue.
+    pub fn request(&self, ticket: IoTicket) -> PendingIo {
+        let (grant, granted) = flume::bounded(1);
+        let mut state = self.inner.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiting.push(Waiter { id, ticket, grant });
+        let grants = self.inner.dispatch(&mut state);
+        drop(state);
+        deliver(grants);
+        PendingIo {
+            id,
+            granted,
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Run blocking `work` once `ticket` is granted
+    pub async fn run<T: Send + 'static>(
+        &self,
+        ticket: IoTicket,
+        work: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, CoreError> {
+        let permit = self.request(ticket).granted().await;
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            work()
+        })
+        .await
+        .map_err(|e| CoreError::ActorError {
+            actor: "scheduler",
+            message: e.to_string(),
+        })
+    }
+
+    /// Change the number of slots of one device
+    pub fn set_limit(&self, device: DeviceKey, slots: usize) {
+        let mut state = self.inner.lock();
+        state.config.limits.insert(device, slots);
+        let grants = self.inner.dispatch(&mut state);
+        drop(state);
+        deliver(grants);
+    }
+
+    /// Work currently holding a slot on `device`
+    pub fn running(&self, device: &DeviceKey) -> usize {
+        self.inner.lock().in_use.get(device).copied().unwrap_or(0)
+    }
+
+    /// Requests waiting for a slot
+    pub fn queued(&self) -> usize {
+        self.inner.lock().waiting.len()
+    }
+}
+
+impl Default for IoScheduler {
+    fn default() -> Self {
+        Self::new(SchedulerConfig::default())
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Grant every waiter that fits, best first
+    ///
+    /// Permits must be delivered (or dropped) after the lock is released.
+    fn dispatch(self: &Arc<Self>, state: &mut State) -> Vec<(Sender<IoPermit>, IoPermit)> {
+        let mut grants = Vec::new();
+        loop {
+            let best = state
+                .waiting
+                .iter()
+                .enumerate()
+                .filter(|(_, w)| state.fits(&w.ticket))
+                .max_by_key(|(_, w)| {
+                    let running = state
+                        .per_session
+                        .get(&w.ticket.session)
+                        .copied()
+                        .unwrap_or(0);
+                    (w.ticket.class.rank(), Reverse(running), Reverse(w.id))
+                })
+                .map(|(index, _)| index);
+            let Some(index) = best else { break };
+            let waiter = state.waiting.remove(index);
+            for device in &waiter.ticket.devices {
+                *state.in_use.entry(device.clone()).or_default() += 1;
+            }
+            *state.per_session.entry(waiter.ticket.session).or_default() += 1;
+            let permit = IoPermit {
+                inner: self.clone(),
+                ticket: waiter.ticket,
+            };
+            grants.push((waiter.grant, permit));
+        }
+        grants
+    }
+}
+
+impl State {
+    fn fits(&self, ticket: &IoTicket) -> bool {
+        ticket.devices.iter().all(|device| {
+            let limit = self
+                .config
+                .limits
+                .get(device)
+                .copied()
+                .unwrap_or(if device.is_remote() {
+                    self.config.remote_slots
+                } else {
+                    self.config.local_slots
+                });
+            let limit = limit.max(1);
+            let allowed = if ticket.class.is_deferrable() {
+                limit.saturating_sub(self.config.reserved).max(1)
+            } else {
+                limit
+            };
+            self.in_use.get(device).copied().unwrap_or(0) < allowed
+        })
+    }
+}
+
+fn deliver(grants: Vec<(Sender<IoPermit>, IoPermit)>) {
+    for (grant, permit) in grants {
+        // A requester that gave up drops the permit here, which frees it
+        let _ = grant.send(permit);
+    }
+}
+
+/// A queued request
+pub struct PendingIo {
+    id: u64,
+    granted: Receiver<IoPermit>,
+    inner: Arc<Inner>,
+}
+
+impl PendingIo {
+    pub async fn granted(self) -> IoPermit {
+        self.granted
+            .recv_async()
+            .await
+            .expect("requests stay queued until granted")
+    }
+
+    /// Block the current thread until granted
+    pub fn wait(self) -> IoPermit {
+        self.granted
+            .recv()
+            .expect("requests stay queued until granted")
+    }
+
+    /// Block for at most `timeout`
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<IoPermit> {
+        self.granted.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for PendingIo {
+    fn drop(&mut self) {
+        self.inner.lock().waiting.retain(|w| w.id != self.id);
+    }
+}
+
+/// A granted slot, released on drop
+#[must_use = "the slot is released when the permit is dropped"]
+pub struct IoPermit {
+    inner: Arc<Inner>,
+    ticket: IoTicket,
+}
+
+impl IoPermit {
+    pub fn ticket(&self) -> &IoTicket {
+        &self.ticket
+    }
+}
+
+impl Drop for IoPermit {
+    fn drop(&mut self) {
+        let mut state = self.inner.lock();
+        for device in &self.ticket.devices {
+            if let Some(count) = state.in_use.get_mut(device) {
+                *count -= 1;
+                if *count == 0 {
+                    state.in_use.remove(device);
+                }
+            }
+        }
+        if let Some(count) = state.per_session.get_mut(&self.ticket.session) {
+            *count -= 1;
+            if *count == 0 {
+                state.per_session.remove(&self.ticket.session);
+            }
+        }
+        let grants = self.inner.dispatch(&mut state);
+        drop(state);
+        deliver(grants);
+    }
+}
@@ -0,0 +1,10 @@
+//! Navigation state of sessions saved across restarts
+//!
+//! Sessions are saved under a stable name the client chooses (a window ID,
+//! say) with all their views, each with history and selection as paths so
+//! they survive the registry. Destroyed sessions are kept for a while so they can be
+//! reopened.
+
+mod store;
+
+pub use store::{SavedSession, SavedView, SessionStore};
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::session::ViewId;
+use crate::pipeline::PipelineConfig;
+
+/// Navigation state of one view, with paths instead of node IDs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedView {
+    pub view: ViewId,
+    /// Visited directories, oldest first
+    pub history: Vec<PathBuf>,
+    /// Steps back from the newest history entry to the current one
+    pub history_index: usize,
+    pub pipeline: PipelineConfig,
+    pub selected: Vec<PathBuf>,
+}
+
+impl SavedView {
+    /// Directory the view was showing
+    pub fn current(&self) -> Option<&PathBuf> {
+        let last = self.history.len().checked_sub(1)?;
+        self.history.get(last.checked_sub(self.history_index)?)
+    }
+}
+
+/// A session's views, in tab order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedSession {
+    /// Stable name given by the client; `None` for sessions that were
+    /// never named, which can only be reopened
+    pub name: Option<String>,
+    pub views: Vec<SavedView>,
+    pub focused: ViewId,
+    /// View focused before, the other pane
+    pub other: Option<ViewId>,
+    /// Seconds since the epoch
+    pub saved_at: u64,
+}
+
+impl SavedSession {
+    pub fn view(&self, view: &ViewId) -> Option<&SavedView> {
+        self.views.iter().find(|saved| saved.view == *view)
+    }
+}
+
+/// On-disk format
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionsFile {
+    sessions: BTreeMap<String, SavedSession>,
+    /// Destroyed sessions, most recent last
+    closed: Vec<SavedSession>,
+}
+
+/// Saved sessions by name, and the most recently closed ones
+///
+/// Cheap to clone; clones share the same file.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    state: Arc<Mutex<SessionsFile>>,
+    path: Option<PathBuf>,
+    closed_limit: usize,
+}
+
+impl SessionStore {
+    /// Sessions that are not saved anywhere
+    pub fn in_memory() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SessionsFile::default())),
+            path: None,
+            closed_limit: 10,
+        }
+    }
+
+    /// Load the sessions from `path` (created on first save)
+    pub fn open(path: PathBuf) -> Result<Self, CoreError> {
+        let file = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|_| CoreError::InvalidData)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => SessionsFile::default(),
+            Err(e) => return Err(CoreError::from_io_error(e, path)),
+        };
+        Ok(Self {
+            state: Arc::new(Mutex::new(file)),
+            path: Some(path),
+            closed_limit: 10,
+        })
+    }
+
+    /// `$XDG_STATE_HOME/filer/sessions.json` (or `~/.local/state/...`)
+    pub fn default_path() -> Result<PathBuf, CoreError> {
+        let state_home = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+            .ok_or_else(|| {
+                CoreError::InvalidPath("$XDG_STATE_HOME and $HOME are unset".to_string())
+            })?;
+        Ok(state_home.join("filer").join("sessions.json"))
+    }
+
+    /// Keep at most `limit` closed sessions to reopen
+    pub fn with_closed_limit(mut self, limit: usize) -> Self {
+        self.closed_limit = limit;
+        let mut state = self.lock();
+        let excess = state.closed.len().saturating_sub(limit);
+        state.closed.drain(..excess);
+        drop(state);
+        self
+    }
+
+    /// State saved under `name`
+    pub fn get(&self, name: &str) -> Option<SavedSession> {
+        self.lock().sessions.get(name).cloned()
+    }
+
+    /// Names of the saved sessions
+    pub fn names(&self) -> Vec<String> {
+        self.lock().sessions.keys().cloned().collect()
+    }
+
+    /// Save a named session, replacing what was saved under its name
+    pub fn save(&self, mut session: SavedSession) -> Result<(), CoreError> {
+        let name = session.name.clone().ok_or_else(|| {
+            CoreError::InvalidPath("only named sessions can be saved".to_string())
+        })?;
+        session.saved_at = now();
+        let mut state = self.lock();
+        state.sessions.insert(name, session);
+        self.write(&state)
+    }
+
+    /// Keep a session that was destroyed to reopen
+    ///
+    /// A named session stays saved under its name as it was at closing.
+    pub fn close(&self, mut session: SavedSession) -> Result<(), CoreError> {
+        session.saved_at = now();
+        let mut state = self.lock();
+        if let Some(name) = &session.name {
+            state.sessions.insert(name.clone(), session.clone());
+        }
+        state.closed.push(session);
+        let excess = state.closed.len().saturating_sub(self.closed_limit);
+        state.closed.drain(..excess);
+        self.write(&state)
+    }
+
+    /// Closed sessions, most recent first
+    pub fn closed(&self) -> Vec<SavedSession> {
+        self.lock().closed.iter().rev().cloned().collect()
+    }
+
+    /// Take the most recently closed session back, saved under its name
+    /// again
+    pub fn reopen(&self) -> Result<Option<SavedSession>, CoreError> {
+        let mut state = self.lock();
+        let Some(session) = state.closed.pop() else {
+            return Ok(None);
+        };
+        if let Some(name) = &session.name {
+            state.sessions.insert(name.clone(), session.clone());
+        }
+        self.write(&state)?;
+        Ok(Some(session))
+    }
+
+    /// Drop the session saved under `name`
+    pub fn remove(&self, name: &str) -> Result<Option<SavedSession>, CoreError> {
+        let mut state = self.lock();
+        let removed = state.sessions.remove(name);
+        if removed.is_some() {
+            self.write(&state)?;
+        }
+        Ok(removed)
+    }
+
+    /// Write the file atomically; in-memory stores skip it
+    fn write(&self, state: &SessionsFile) -> Result<(), CoreError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir).map_err(|e| CoreError::from_io_error(e, dir.to_path_buf()))?;
+        let data = serde_json::to_vec(state).map_err(|_| CoreError::InvalidData)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(|e| CoreError::from_io_error(e, tmp.clone()))?;
+        fs::rename(&tmp, path).map_err(|e| CoreError::from_io_error(e, path.clone()))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SessionsFile> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+
+/// Color label of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorLabel {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Gray,
+}
+
+impl ColorLabel {
+    pub const ALL: [ColorLabel; 7] = [
+        ColorLabel::Red,
+        ColorLabel::Orange,
+        ColorLabel::Yellow,
+        ColorLabel::Green,
+        ColorLabel::Blue,
+        ColorLabel::Purple,
+        ColorLabel::Gray,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorLabel::Red => "red",
+            ColorLabel::Orange => "orange",
+            ColorLabel::Yellow => "yellow",
+            ColorLabel::Green => "green",
+            ColorLabel::Blue => "blue",
+            ColorLabel::Purple => "purple",
+            ColorLabel::Gray => "gray",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|label| label.name() == name)
+    }
+}
+
+/// Everything attached to one file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileTags {
+    /// Sorted, without duplicates
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<ColorLabel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl FileTags {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.label.is_none() && self.note.is_none()
+    }
+
+    pub fn has(&self, tag: &str) -> bool {
+        self.tags.binary_search_by(|t| t.as_str().cmp(tag)).is_ok()
+    }
+
+    /// Parse the comma-separated `user.xdg.tags` value
+    pub(crate) fn parse_tags(value: &[u8]) -> Vec<String> {
+        let mut tags: Vec<String> = String::from_utf8_lossy(value)
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+}
+
+/// A change to the tags of files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagEdit {
+    Add(String),
+    Remove(String),
+    /// Replace all tags
+    Set(Vec<String>),
+    Label(Option<ColorLabel>),
+    /// An empty note removes it
+    Note(Option<String>),
+}
+
+impl TagEdit {
+    /// Refuse tags that cannot be stored or listed under `tags://`
+    pub fn validate(&self) -> Result<(), CoreError> {
+        let tags = match self {
+            TagEdit::Add(tag) | TagEdit::Remove(tag) => std::slice::from_ref(tag),
+            TagEdit::Set(tags) => tags.as_slice(),
+            TagEdit::Label(_) | TagEdit::Note(_) => &[],
+        };
+        if tags.iter().all(|tag| valid_tag(tag)) {
+            Ok(())
+        } else {
+            Err(CoreError::InvalidInput)
+        }
+    }
+
+    /// `tags` with the edit applied
+    pub fn apply(&self, mut tags: FileTags) -> FileTags {
+        match self {
+            TagEdit::Add(tag) => tags.tags.push(tag.trim().to_string()),
+            TagEdit::Remove(tag) => tags.tags.retain(|t| t != tag.trim()),
+            TagEdit::Set(new) => tags.tags = new.iter().map(|t| t.trim().to_string()).collect(),
+            TagEdit::Label(label) => tags.label = *label,
+            TagEdit::Note(note) => {
+                tags.note = note.clone().filter(|note| !note.is_empty());
+            }
+        }
+        tags.tags.sort();
+        tags.tags.dedup();
+        tags
+    }
+}
+
+/// Not empty, no `,` (the attribute separator) and no `/` (a `tags://`
+/// path component)
+fn valid_tag(tag: &str) -> bool {
+    let tag = tag.trim();
+    !tag.is_empty() && !tag.contains([',', '/'])
+}
@@ -0,0 +1,12 @@
+//! Tags, color labels and notes on files
+//!
+//! They live in the files' extended attributes where the filesystem has
+//! them (`user.xdg.tags` and `user.xdg.comment`, as other desktop tools
+//! use) and in a sidecar database otherwise. The database also indexes
+//! every tagged path, which is what `vfs::tags::TagsFs` lists.
+
+mod entry;
+mod store;
+
+pub use entry::{ColorLabel, FileTags, TagEdit};
+pub use store::TagStore;
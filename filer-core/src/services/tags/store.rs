@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::node::FileNode;
+use crate::utils::xattr;
+
+use super::entry::{ColorLabel, FileTags, TagEdit};
+
+/// Comma-separated tags, as written by other desktop tools
+const TAGS_XATTR: &str = "user.xdg.tags";
+const NOTE_XATTR: &str = "user.xdg.comment";
+const LABEL_XATTR: &str = "user.filer.label";
+
+/// On-disk format
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TagsFile {
+    files: BTreeMap<PathBuf, Record>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Record {
+    #[serde(flatten)]
+    tags: FileTags,
+    /// The file's own attributes hold the tags and this is only the
+    /// index copy
+    #[serde(default)]
+    xattr: bool,
+}
+
+/// Tags, labels and notes of files, by path
+///
+/// Written to the file's extended attributes when its filesystem has
+/// them, to the database otherwise. Either way the database remembers
+/// the path so `tagged` can find it. Cheap to clone; clones share the
+/// same database.
+#[derive(Debug, Clone)]
+pub struct TagStore {
+    state: Arc<Mutex<TagsFile>>,
+    path: Option<PathBuf>,
+    xattrs: bool,
+}
+
+impl TagStore {
+    /// Database that is not saved anywhere
+    pub fn in_memory() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TagsFile::default())),
+            path: None,
+            xattrs: true,
+        }
+    }
+
+    /// Load the database from `path` (created on first save)
+    pub fn open(path: PathBuf) -> Result<Self, CoreError> {
+        let file = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|_| CoreError::InvalidData)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => TagsFile::default(),
+            Err(e) => return Err(CoreError::from_io_error(e, path)),
+        };
+        Ok(Self {
+            state: Arc::new(Mutex::new(file)),
+            path: Some(path),
+            xattrs: true,
+        })
+    }
+
+    /// `$XDG_DATA_HOME/filer/tags.json` (or `~/.local/share/...`)
+    pub fn default_path() -> Result<PathBuf, CoreError> {
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+            .ok_or_else(|| {
+                CoreError::InvalidPath("$XDG_DATA_HOME and $HOME are unset".to_string())
+            })?;
+        Ok(data_home.join("filer").join("tags.json"))
+    }
+
+    /// Whether to use extended attributes at all; without them everything
+    /// goes to the database, as for providers that have none
+    pub fn with_xattrs(mut self, xattrs: bool) -> Self {
+        self.xattrs = xattrs;
+        self
+    }
+
+    /// What is attached to `path`
+    pub fn get(&self, path: &Path) -> FileTags {
+        let record = self.lock().files.get(path).cloned();
+        if self.xattrs
+            && record.as_ref().is_none_or(|record| record.xattr)
+            && let Ok(found) = self.read_xattrs(path)
+        {
+            return found.unwrap_or_default();
+        }
+        record.map(|record| record.tags).unwrap_or_default()
+    }
+
+    /// Apply `edit` to `path` and return the result
+    pub fn edit(&self, path: &Path, edit: &TagEdit) -> Result<FileTags, CoreError> {
+        edit.validate()?;
+        let tags = edit.apply(self.get(path));
+        self.set(path, tags.clone())?;
+        Ok(tags)
+    }
+
+    /// Replace what is attached to `path`
+    pub fn set(&self, path: &Path, tags: FileTags) -> Result<(), CoreError> {
+        fs::metadata(path).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))?;
+        let xattr = self.write_xattrs(path, &tags)?;
+        let mut state = self.lock();
+        if tags.is_empty() {
+            state.files.remove(path);
+        } else {
+            state
+                .files
+                .insert(path.to_path_buf(), Record { tags, xattr });
+        }
+        self.save(&state)
+    }
+
+    /// Fill in the tags, label and note of `nodes`
+    ///
+    /// Files tagged by other tools are added to the index on the way.
+    pub fn annotate(&self, nodes: &mut [FileNode]) {
+        let mut learned = Vec::new();
+        for node in nodes.iter_mut() {
+            let tags = self.get(&node.path);
+            if self.xattrs && !tags.is_empty() {
+                learned.push((node.path.clone(), tags.clone()));
+            }
+            node.meta.tags = tags.tags;
+            node.meta.label = tags.label;
+            node.meta.note = tags.note;
+        }
+        if learned.is_empty() {
+            return;
+        }
+        let mut state = self.lock();
+        let mut changed = false;
+        for (path, tags) in learned {
+            let record = Record { tags, xattr: true };
+            if state
+                .files
+                .get(&path)
+                .is_none_or(|old| old.xattr && *old != record)
+            {
+                state.files.insert(path, record);
+                changed = true;
+            }
+        }
+        if changed {
+            let _ = self.save(&state);
+        }
+    }
+
+    /// Existing files carrying `tag`, by path
+    pub fn tagged(&self, tag: &str) -> Vec<PathBuf> {
+        let candidates: Vec<PathBuf> = self
+            .lock()
+            .files
+            .iter()
+            .filter(|(_, record)| record.tags.has(tag))
+            .map(|(path, _)| path.clone())
+            .collect();
+        candidates
+            .into_iter()
+            .filter(|path| path.exists() && self.get(path).has(tag))
+            .collect()
+    }
+
+    /// Every tag in the index with the number of paths carrying it
+    pub fn tags(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        let state = self.lock();
+        for record in state.files.values() {
+            for tag in &record.tags.tags {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+        let mut tags: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(tag, count)| (tag.to_string(), count))
+            .collect();
+        tags.sort();
+        tags
+    }
+
+    /// Whether `path` is, or is inside, a tagged path
+    pub fn covers(&self, path: &Path) -> bool {
+        self.lock()
+            .files
+            .keys()
+            .any(|tagged| path.starts_with(tagged))
+    }
+
+    /// Follow `from` (and everything below it) to `to` after a rename or
+    /// move
+    ///
+    /// Attributes lost by a move across filesystems are written again.
+    pub fn moved(&self, from: &Path, to: &Path) -> Result<(), CoreError> {
+        let mut state = self.lock();
+        let inside: Vec<PathBuf> = state
+            .files
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+        if inside.is_empty() {
+            return Ok(());
+        }
+        for old in inside {
+            let Some(mut record) = state.files.remove(&old) else {
+                continue;
+            };
+            let new = match old.strip_prefix(from) {
+                Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                _ => to.to_path_buf(),
+            };
+            if record.xattr && !matches!(self.read_xattrs(&new), Ok(Some(_))) {
+                record.xattr = self.write_xattrs(&new, &record.tags).unwrap_or(false);
+            }
+            state.files.insert(new, record);
+        }
+        self.save(&state)
+    }
+
+    /// `None` if none of the attributes is set
+    fn read_xattrs(&self, path: &Path) -> io::Result<Option<FileTags>> {
+        if !self.xattrs {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+        let tags = xattr::get(path, TAGS_XATTR)?;
+        let label = xattr::get(path, LABEL_XATTR)?;
+        let note = xattr::get(path, NOTE_XATTR)?;
+        if tags.is_none() && label.is_none() && note.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(FileTags {
+            tags: tags
+                .map(|value| FileTags::parse_tags(&value))
+                .unwrap_or_default(),
+            label: label.and_then(|value| ColorLabel::from_name(&String::from_utf8_lossy(&value))),
+            note: note
+                .map(|value| String::from_utf8_lossy(&value).into_owned())
+                .filter(|note| !note.is_empty()),
+        }))
+    }
+
+    /// `false` if the filesystem has no extended attributes
+    fn write_xattrs(&self, path: &Path, tags: &FileTags) -> Result<bool, CoreError> {
+        if !self.xattrs {
+            return Ok(false);
+        }
+        let values = [
+            (
+                TAGS_XATTR,
+                Some(tags.tags.join(",")).filter(|v| !v.is_empty()),
+            ),
+            (
+                LABEL_XATTR,
+                tags.label.map(|label| label.name().to_string()),
+            ),
+            (NOTE_XATTR, tags.note.clone()),
+        ];
+        for (name, value) in values {
+            let result = match value {
+                Some(value) => xattr::set(path, name, value.as_bytes()),
+                None => xattr::remove(path, name),
+            };
+            match result {
+                Ok(()) => {}
+                Err(e) if xattr::is_unsupported(&e) => return Ok(false),
+                Err(e) => return Err(CoreError::from_io_error(e, path.to_path_buf())),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Write the database atomically
+    fn save(&self, state: &TagsFile) -> Result<(), CoreError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir).map_err(|e| CoreError::from_io_error(e, dir.to_path_buf()))?;
+        let data = serde_json::to_vec(state).map_err(|_| CoreError::InvalidData)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(|e| CoreError::from_io_error(e, tmp.clone()))?;
+        fs::rename(&tmp, path).map_err(|e| CoreError::from_io_error(e, path.clone()))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TagsFile> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs::Metadata;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::model::node::FileNode;
+
+use super::TransferObserver;
+
+/// What to do when a transfer target already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictResolution {
+    /// Leave the target alone and do not transfer this item
+    Skip,
+    /// Replace the target
+    Overwrite,
+    /// Replace the target only if the source was modified later
+    OverwriteIfNewer,
+    /// Transfer next to the target as "name (2).ext"
+    KeepBoth,
+    /// Copy into the existing directory, resolving its children one by one
+    ///
+    /// Only meaningful for two directories; otherwise the item is skipped.
+    Merge,
+}
+
+/// An answer to a conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictAnswer {
+    pub resolution: ConflictResolution,
+    /// Use the same resolution for the rest of the job's conflicts of this
+    /// kind (file or directory)
+    pub apply_to_all: bool,
+}
+
+/// Resolutions to apply without asking; `None` asks the observer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictPolicy {
+    /// Conflicts involving at least one non-directory
+    pub files: Option<ConflictResolution>,
+    /// Conflicts between two directories
+    pub directories: Option<ConflictResolution>,
+}
+
+impl ConflictPolicy {
+    /// Resolve every conflict the same way
+    pub fn always(resolution: ConflictResolution) -> Self {
+        Self {
+            files: Some(resolution),
+            directories: Some(resolution),
+        }
+    }
+}
+
+/// One side of a conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictSide {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub is_dir: bool,
+}
+
+impl ConflictSide {
+    pub(crate) fn new(path: PathBuf, meta: &Metadata) -> Self {
+        Self {
+            path,
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            is_dir: meta.is_dir(),
+        }
+    }
+
+    /// Side described by a provider's node, for streamed transfers
+    pub(crate) fn of_node(path: PathBuf, node: &FileNode) -> Self {
+        Self {
+            path,
+            size: node.size,
+            modified: node.modified,
+            is_dir: node.is_dir(),
+        }
+    }
+}
+
+/// A source whose target already exists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub source: ConflictSide,
+    pub target: ConflictSide,
+}
+
+impl Conflict {
+    pub fn is_directory_pair(&self) -> bool {
+        self.source.is_dir && self.target.is_dir
+    }
+
+    /// Turn `OverwriteIfNewer` and `Merge` into a concrete action
+    fn settle(&self, resolution: ConflictResolution) -> ConflictResolution {
+        match resolution {
+            ConflictResolution::OverwriteIfNewer => match (self.source.modified, self.target.modified) {
+                (Some(source), Some(target)) if source > target => ConflictResolution::Overwrite,
+                _ => ConflictResolution::Skip,
+            },
+            ConflictResolution::Merge if !self.is_directory_pair() => ConflictResolution::Skip,
+            other => other,
+        }
+    }
+}
+
+/// Applies the job's policy, asks the observer otherwise and remembers
+/// "apply to all" answers
+pub(crate) struct ConflictResolver {
+    policy: ConflictPolicy,
+}
+
+impl ConflictResolver {
+    pub(crate) fn new(policy: ConflictPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Settled resolution: one of `Skip`, `Overwrite`, `KeepBoth`, `Merge`
+    pub(crate) fn resolve(
+        &mut self,
+        conflict: &Conflict,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<ConflictResolution, CoreError> {
+        let slot = if conflict.is_directory_pair() {
+            &mut self.policy.directories
+        } else {
+            &mut self.policy.files
+        };
+        let resolution = match slot {
+            Some(resolution) => *resolution,
+            None => {
+                let answer = observer.conflict(conflict)?;
+                if answer.apply_to_all {
+                    *slot = Some(answer.resolution);
+                }
+                answer.resolution
+            }
+        };
+        Ok(conflict.settle(resolution))
+    }
+}
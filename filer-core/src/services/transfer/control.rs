@@ -0,0 +1,95 @@
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::errors::CoreError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// Pause/resume/cancel switch shared between a job and its controller
+///
+/// The worker calls `checkpoint` between chunks; it blocks while paused
+/// and fails with `CoreError::Cancelled` once cancelled.
+#[derive(Debug)]
+pub struct JobControl {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl JobControl {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::Running),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Pause a running job; false if it was not running
+    pub fn pause(&self) -> bool {
+        self.transition(State::Running, State::Paused)
+    }
+
+    /// Resume a paused job; false if it was not paused
+    pub fn resume(&self) -> bool {
+        self.transition(State::Paused, State::Running)
+    }
+
+    /// Cancel the job (also wakes a paused worker)
+    pub fn cancel(&self) {
+        *self.lock() = State::Cancelled;
+        self.changed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.lock() == State::Paused
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.lock() == State::Cancelled
+    }
+
+    /// Wait out a pause, then report whether the job may continue
+    pub fn checkpoint(&self) -> Result<(), CoreError> {
+        let mut state = self.lock();
+        while *state == State::Paused {
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        match *state {
+            State::Cancelled => Err(CoreError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// `checkpoint` for async workers; polls the pause instead of blocking
+    /// the runtime
+    pub async fn checkpoint_async(&self) -> Result<(), CoreError> {
+        while self.is_paused() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.checkpoint()
+    }
+
+    fn transition(&self, from: State, to: State) -> bool {
+        let mut state = self.lock();
+        if *state != from {
+            return false;
+        }
+        *state = to;
+        self.changed.notify_all();
+        true
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for JobControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,691 @@
+use std::fs::{self, File, FileTimes, Metadata};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::utils;
+
+use super::conflict::{Conflict, ConflictAnswer, ConflictPolicy, ConflictResolution, ConflictResolver, ConflictSide};
+use super::{JobControl, ProgressTracker};
+
+/// What to carry over from the source besides content
+///
+/// Moves always keep permissions and times, whether or not they fall back
+/// to copying.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferOptions {
+    /// Keep modification and access times
+    pub preserve_mtime: bool,
+    /// Keep permission bits instead of applying the umask
+    pub preserve_permissions: bool,
+    /// Conflicts resolved without asking the observer
+    pub conflict: ConflictPolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferMode {
+    Copy,
+    Move,
+}
+
+/// Receives progress, per-item failures and conflicts while a transfer runs
+pub trait TransferObserver {
+    /// Called after every chunk and every finished item
+    fn progress(&mut self, tracker: &ProgressTracker, current: &Path);
+
+    /// An item failed; the transfer carries on with the next one
+    fn failed(&mut self, path: &Path, error: &CoreError);
+
+    /// A target exists and the job's policy does not cover it
+    ///
+    /// Returning an error fails the item (`Cancelled` stops the job). The
+    /// default is for observers that cannot ask anyone.
+    fn conflict(&mut self, conflict: &Conflict) -> Result<ConflictAnswer, CoreError> {
+        Err(CoreError::InvalidPath(format!(
+            "{} already exists",
+            conflict.target.path.display()
+        )))
+    }
+}
+
+/// Result of a transfer that ran to the end
+#[derive(Debug, Clone, Default)]
+pub struct TransferReport {
+    /// Top-level targets that were transferred (fully or merged)
+    pub created: Vec<PathBuf>,
+    /// Number of items that failed
+    pub errors: usize,
+    /// Number of items skipped because of a conflict
+    pub skipped: usize,
+    /// (source, target) of top-level items that landed on a new path
+    ///
+    /// Merged and overwritten targets are left out since they cannot be
+    /// taken apart again.
+    pub fresh: Vec<(PathBuf, PathBuf)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ItemKind {
+    Dir,
+    File,
+    Symlink,
+}
+
+impl ItemKind {
+    fn of(meta: &Metadata) -> Self {
+        if meta.is_dir() {
+            ItemKind::Dir
+        } else if meta.file_type().is_symlink() {
+            ItemKind::Symlink
+        } else {
+            ItemKind::File
+        }
+    }
+}
+
+struct Item {
+    source: PathBuf,
+    target: PathBuf,
+    kind: ItemKind,
+    meta: Metadata,
+    /// Index of the top-level source this item belongs to
+    root: usize,
+    /// Replace whatever is at the target
+    replace: bool,
+    done: bool,
+}
+
+struct Root {
+    source: PathBuf,
+    target: PathBuf,
+    meta: Metadata,
+    replace: bool,
+    /// Finished without copying (renamed in place)
+    done: bool,
+    failed: bool,
+    /// Skipped entirely because of a conflict
+    skipped: bool,
+    /// Something below it was skipped
+    partial: bool,
+    /// Target did not exist before
+    fresh: bool,
+}
+
+/// Planning state: the item list plus conflict handling
+struct Plan<'a> {
+    items: Vec<Item>,
+    roots: Vec<Root>,
+    resolver: ConflictResolver,
+    observer: &'a mut dyn TransferObserver,
+    errors: usize,
+    skipped: usize,
+}
+
+impl Plan<'_> {
+    fn fail(&mut self, root: usize, path: &Path, error: &CoreError) {
+        self.observer.failed(path, error);
+        self.errors += 1;
+        self.roots[root].failed = true;
+    }
+
+    /// Decide where `source` goes if `target` is taken
+    ///
+    /// Returns the target and whether to replace it, or `None` to leave the
+    /// item out.
+    fn resolve(
+        &mut self,
+        source: &Path,
+        meta: &Metadata,
+        target: PathBuf,
+        root: usize,
+    ) -> Result<Option<(PathBuf, bool)>, CoreError> {
+        let Ok(existing) = fs::symlink_metadata(&target) else {
+            return Ok(Some((target, false)));
+        };
+        let conflict = Conflict {
+            source: ConflictSide::new(source.to_path_buf(), meta),
+            target: ConflictSide::new(target.clone(), &existing),
+        };
+        match self.resolver.resolve(&conflict, &mut *self.observer) {
+            Ok(ConflictResolution::Overwrite) => Ok(Some((target, true))),
+            Ok(ConflictResolution::KeepBoth) => Ok(Some((utils::unique_name(&target), false))),
+            // Directories only; `create_dir` accepts the existing one
+            Ok(ConflictResolution::Merge) => Ok(Some((target, false))),
+            Ok(_) => {
+                self.skipped += 1;
+                self.roots[root].partial = true;
+                Ok(None)
+            }
+            Err(CoreError::Cancelled) => Err(CoreError::Cancelled),
+            Err(e) => {
+                self.fail(root, source, &e);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Copy or move a set of sources into a destination directory
+///
+/// Same-device moves are a single `rename(2)`; everything else is copied
+/// in chunks (so the job can be paused, cancelled and report progress)
+/// and, for moves, the source is deleted once its copy is complete.
+/// Existing targets are resolved through the `ConflictPolicy` or the
+/// observer before anything is written.
+pub struct Transfer {
+    mode: TransferMode,
+    sources: Vec<PathBuf>,
+    destination: PathBuf,
+    options: TransferOptions,
+    buffer_size: usize,
+}
+
+impl Transfer {
+    pub fn new(mode: TransferMode, sources: Vec<PathBuf>, destination: PathBuf) -> Self {
+        Self {
+            mode,
+            sources,
+            destination,
+            options: TransferOptions::default(),
+            buffer_size: 1024 * 1024,
+        }
+    }
+
+    pub fn with_options(mut self, options: TransferOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Chunk size; also the granularity of pause/cancel and progress
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.max(1);
+        self
+    }
+
+    /// Run the transfer on the current (blocking) thread
+    ///
+    /// Returns `CoreError::Cancelled` if the control is cancelled; items
+    /// finished before that are left in place.
+    pub fn run(&self, control: &JobControl, observer: &mut dyn TransferObserver) -> Result<TransferReport, CoreError> {
+        let mut report = TransferReport::default();
+        self.run_into(control, observer, &mut report)?;
+        Ok(report)
+    }
+
+    /// Like `run`, but `report` also describes a transfer that was cancelled
+    ///
+    /// A cancelled report only lists the sources that arrived whole: moved
+    /// by a rename or, when copying, written out completely.
+    pub fn run_into(
+        &self,
+        control: &JobControl,
+        observer: &mut dyn TransferObserver,
+        report: &mut TransferReport,
+    ) -> Result<(), CoreError> {
+        let dest_meta =
+            fs::metadata(&self.destination).map_err(|e| CoreError::from_io_error(e, self.destination.clone()))?;
+        if !dest_meta.is_dir() {
+            return Err(CoreError::InvalidPath(format!(
+                "{} is not a directory",
+                self.destination.display()
+            )));
+        }
+
+        let mut plan = Plan {
+            items: Vec::new(),
+            roots: Vec::new(),
+            resolver: ConflictResolver::new(self.options.conflict),
+            observer,
+            errors: 0,
+            skipped: 0,
+        };
+        for source in &self.sources {
+            match self.add_root(source, &mut plan) {
+                Ok(()) => {}
+                Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                Err(e) => {
+                    plan.observer.failed(source, &e);
+                    plan.errors += 1;
+                }
+            }
+        }
+
+        if self.mode == TransferMode::Move {
+            for index in 0..plan.roots.len() {
+                let root = &mut plan.roots[index];
+                if root.done || root.failed || root.skipped {
+                    continue;
+                }
+                if let Err(e) = Self::try_rename(root, &dest_meta) {
+                    let source = root.source.clone();
+                    plan.fail(index, &source, &e);
+                }
+            }
+        }
+
+        for index in 0..plan.roots.len() {
+            let root = &plan.roots[index];
+            if root.done || root.failed || root.skipped {
+                continue;
+            }
+            let (source, target, meta, replace) =
+                (root.source.clone(), root.target.clone(), root.meta.clone(), root.replace);
+            if let Err(e) = Self::plan(&source, meta, target, replace, index, &mut plan) {
+                (report.errors, report.skipped) = (plan.errors, plan.skipped);
+                self.report_whole(&plan.roots, None, report);
+                return Err(e);
+            }
+        }
+
+        let Plan {
+            mut items,
+            mut roots,
+            observer,
+            errors,
+            skipped,
+            ..
+        } = plan;
+        (report.errors, report.skipped) = (errors, skipped);
+
+        let bytes_total = items.iter().filter(|i| i.kind == ItemKind::File).map(|i| i.meta.len()).sum();
+        let files_total = items.iter().filter(|i| i.kind != ItemKind::Dir).count() + roots.iter().filter(|r| r.done).count();
+        let mut tracker = ProgressTracker::new(bytes_total, files_total as u64);
+        for root in roots.iter().filter(|r| r.done) {
+            tracker.file_done();
+            observer.progress(&tracker, &root.source);
+        }
+
+        let mut dirs_to_stamp = Vec::new();
+        for index in 0..items.len() {
+            if let Err(e) = control.checkpoint() {
+                self.report_whole(&roots, Some(&items), report);
+                return Err(e);
+            }
+            let item = &mut items[index];
+            let result = match item.kind {
+                ItemKind::Dir => Self::create_dir(item),
+                ItemKind::File => self.copy_file(item, control, &mut tracker, observer),
+                ItemKind::Symlink => Self::copy_symlink(item),
+            };
+            match result {
+                Ok(()) if item.kind == ItemKind::Dir => dirs_to_stamp.push(index),
+                Ok(()) => tracker.file_done(),
+                Err(CoreError::Cancelled) => {
+                    self.report_whole(&roots, Some(&items), report);
+                    return Err(CoreError::Cancelled);
+                }
+                Err(e) => {
+                    observer.failed(&item.source, &e);
+                    report.errors += 1;
+                    roots[item.root].failed = true;
+                    if item.kind != ItemKind::Dir {
+                        tracker.file_done();
+                    }
+                    observer.progress(&tracker, &item.source);
+                    continue;
+                }
+            }
+            observer.progress(&tracker, &item.source);
+            item.done = true;
+        }
+
+        // Children are written after their directory, so stamp dirs last
+        for index in dirs_to_stamp.into_iter().rev() {
+            let _ = self.apply_metadata_dir(&items[index]);
+        }
+
+        if self.mode == TransferMode::Move {
+            for (index, root) in roots.iter_mut().enumerate() {
+                if root.done || root.skipped {
+                    continue;
+                }
+                if !root.failed && !root.partial {
+                    if let Err(e) = remove_all(&root.source) {
+                        observer.failed(&root.source, &e);
+                        report.errors += 1;
+                        root.failed = true;
+                    }
+                    continue;
+                }
+                // Keep whatever was not transferred; children come last
+                for item in items.iter().rev().filter(|i| i.root == index && i.done) {
+                    if item.kind == ItemKind::Dir {
+                        // Still holds skipped or failed items if this fails
+                        let _ = fs::remove_dir(&item.source);
+                    } else if let Err(e) = fs::remove_file(&item.source) {
+                        observer.failed(&item.source, &CoreError::from_io_error(e, item.source.clone()));
+                        report.errors += 1;
+                    }
+                }
+            }
+        }
+
+        report.fresh = roots
+            .iter()
+            .filter(|r| r.fresh && !r.failed && !r.skipped && !r.partial)
+            .map(|r| (r.source.clone(), r.target.clone()))
+            .collect();
+        report.created = roots
+            .into_iter()
+            .filter(|r| !r.failed && !r.skipped)
+            .map(|r| r.target)
+            .collect();
+        Ok(())
+    }
+
+    /// Fill in the roots that arrived whole before the transfer stopped
+    ///
+    /// `items` is `None` before anything was copied. Copied roots of a move
+    /// still have their sources, so only renamed ones count there.
+    fn report_whole(&self, roots: &[Root], items: Option<&[Item]>, report: &mut TransferReport) {
+        let whole = |index: usize, root: &Root| {
+            if root.failed || root.skipped || root.partial {
+                return false;
+            }
+            root.done
+                || (self.mode == TransferMode::Copy
+                    && items.is_some_and(|items| items.iter().filter(|i| i.root == index).all(|i| i.done)))
+        };
+        for (_, root) in roots.iter().enumerate().filter(|(index, root)| whole(*index, root)) {
+            if root.fresh {
+                report.fresh.push((root.source.clone(), root.target.clone()));
+            }
+            report.created.push(root.target.clone());
+        }
+    }
+
+    /// Validate a top-level source and settle its target
+    fn add_root(&self, source: &Path, plan: &mut Plan) -> Result<(), CoreError> {
+        let name = source
+            .file_name()
+            .ok_or_else(|| CoreError::InvalidPath(source.display().to_string()))?;
+        let meta = fs::symlink_metadata(source).map_err(|e| CoreError::from_io_error(e, source.to_path_buf()))?;
+        let mut target = self.destination.join(name);
+        let mut done = false;
+
+        if target == source {
+            match self.mode {
+                // Moving something to where it already is
+                TransferMode::Move => done = true,
+                // Copying next to itself always keeps both
+                TransferMode::Copy => target = utils::unique_name(&target),
+            }
+        } else if self.destination.starts_with(source) {
+            return Err(CoreError::InvalidPath(format!(
+                "cannot {} {} into itself",
+                if self.mode == TransferMode::Copy { "copy" } else { "move" },
+                source.display()
+            )));
+        }
+
+        let index = plan.roots.len();
+        plan.roots.push(Root {
+            source: source.to_path_buf(),
+            target: target.clone(),
+            meta: meta.clone(),
+            replace: false,
+            done,
+            failed: false,
+            skipped: false,
+            partial: false,
+            fresh: false,
+        });
+        if done {
+            return Ok(());
+        }
+        match plan.resolve(source, &meta, target, index)? {
+            Some((target, replace)) => {
+                let root = &mut plan.roots[index];
+                root.fresh = !replace && fs::symlink_metadata(&target).is_err();
+                root.target = target;
+                root.replace = replace;
+            }
+            None => plan.roots[index].skipped = true,
+        }
+        Ok(())
+    }
+
+    /// Rename a root if it is on the destination's device
+    fn try_rename(root: &mut Root, dest_meta: &Metadata) -> Result<(), CoreError> {
+        if root.meta.dev() != dest_meta.dev() {
+            return Ok(());
+        }
+        if let Ok(existing) = fs::symlink_metadata(&root.target) {
+            if !root.replace {
+                // Merging into an existing directory
+                return Ok(());
+            }
+            // rename(2) only replaces non-directories atomically
+            if existing.is_dir() || root.meta.is_dir() {
+                remove_all(&root.target)?;
+            }
+        }
+        match fs::rename(&root.source, &root.target) {
+            Ok(()) => {
+                root.done = true;
+                Ok(())
+            }
+            // Bind mounts share st_dev but still refuse renames
+            Err(e) if e.raw_os_error() == Some(libc::EXDEV) => Ok(()),
+            Err(e) => Err(CoreError::from_io_error(e, root.source.clone())),
+        }
+    }
+
+    /// List everything under `source` in creation order (parents first)
+    ///
+    /// Conflicts of children are resolved here, before anything is written.
+    fn plan(
+        source: &Path,
+        meta: Metadata,
+        target: PathBuf,
+        replace: bool,
+        root: usize,
+        plan: &mut Plan,
+    ) -> Result<(), CoreError> {
+        let kind = ItemKind::of(&meta);
+        plan.items.push(Item {
+            source: source.to_path_buf(),
+            target: target.clone(),
+            kind,
+            meta,
+            root,
+            replace,
+            done: false,
+        });
+        if kind != ItemKind::Dir {
+            return Ok(());
+        }
+
+        let mut children: Vec<PathBuf> = match fs::read_dir(source) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect(),
+            Err(e) => {
+                plan.fail(root, source, &CoreError::from_io_error(e, source.to_path_buf()));
+                return Ok(());
+            }
+        };
+        children.sort();
+        for child in children {
+            let meta = match fs::symlink_metadata(&child) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    plan.fail(root, &child, &CoreError::from_io_error(e, child.clone()));
+                    continue;
+                }
+            };
+            let child_target = target.join(child.file_name().unwrap_or_default());
+            // A replaced directory starts out empty, so nothing can conflict
+            let resolved = if replace {
+                Some((child_target, false))
+            } else {
+                plan.resolve(&child, &meta, child_target, root)?
+            };
+            if let Some((child_target, child_replace)) = resolved {
+                Self::plan(&child, meta, child_target, child_replace, root, plan)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn create_dir(item: &Item) -> Result<(), CoreError> {
+        if item.replace {
+            clear(&item.target)?;
+        }
+        match fs::create_dir(&item.target) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists && item.target.is_dir() => Ok(()),
+            Err(e) => Err(CoreError::from_io_error(e, item.target.clone())),
+        }
+    }
+
+    fn copy_symlink(item: &Item) -> Result<(), CoreError> {
+        let link = fs::read_link(&item.source).map_err(|e| CoreError::from_io_error(e, item.source.clone()))?;
+        if item.replace {
+            clear(&item.target)?;
+        }
+        std::os::unix::fs::symlink(link, &item.target).map_err(|e| CoreError::from_io_error(e, item.target.clone()))
+    }
+
+    /// Copy a regular file
+    ///
+    /// A file replacing another file is written next to it and renamed over
+    /// it, so the old content survives a failed or cancelled copy.
+    fn copy_file(
+        &self,
+        item: &Item,
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<(), CoreError> {
+        let mut src = File::open(&item.source).map_err(|e| CoreError::from_io_error(e, item.source.clone()))?;
+        let swap = item.replace && fs::symlink_metadata(&item.target).is_ok_and(|m| m.is_file());
+        let write_path = if swap {
+            let name = item.target.file_name().unwrap_or_default().to_string_lossy();
+            let partial = item.target.with_file_name(format!(".{name}.filer-part"));
+            clear(&partial)?;
+            partial
+        } else {
+            if item.replace {
+                clear(&item.target)?;
+            }
+            item.target.clone()
+        };
+
+        let mode = if self.keeps_permissions() {
+            item.meta.mode() & 0o7777
+        } else {
+            item.meta.mode() & 0o777
+        };
+        let mut dst = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(&write_path)
+            .map_err(|e| CoreError::from_io_error(e, write_path.clone()))?;
+
+        let result = self.copy_contents(&mut src, &mut dst, item, control, tracker, observer);
+        let result = result.and_then(|()| self.apply_metadata(&dst, item));
+        drop(dst);
+        let result = result.and_then(|()| {
+            if swap {
+                fs::rename(&write_path, &item.target).map_err(|e| CoreError::from_io_error(e, item.target.clone()))
+            } else {
+                Ok(())
+            }
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&write_path);
+        }
+        result
+    }
+
+    fn copy_contents(
+        &self,
+        src: &mut File,
+        dst: &mut File,
+        item: &Item,
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<(), CoreError> {
+        let mut buf = vec![0u8; self.buffer_size];
+        loop {
+            control.checkpoint()?;
+            let n = match src.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(CoreError::from_io_error(e, item.source.clone())),
+            };
+            dst.write_all(&buf[..n])
+                .map_err(|e| CoreError::from_io_error(e, item.target.clone()))?;
+            tracker.add_bytes(n as u64, Instant::now());
+            observer.progress(tracker, &item.source);
+        }
+    }
+
+    fn keeps_permissions(&self) -> bool {
+        self.options.preserve_permissions || self.mode == TransferMode::Move
+    }
+
+    fn keeps_mtime(&self) -> bool {
+        self.options.preserve_mtime || self.mode == TransferMode::Move
+    }
+
+    fn apply_metadata(&self, file: &File, item: &Item) -> Result<(), CoreError> {
+        let io = |e| CoreError::from_io_error(e, item.target.clone());
+        if self.keeps_permissions() {
+            // The create mode was filtered by the umask
+            file.set_permissions(fs::Permissions::from_mode(item.meta.mode() & 0o7777))
+                .map_err(io)?;
+        }
+        if self.keeps_mtime() {
+            file.set_times(file_times(&item.meta)).map_err(io)?;
+        }
+        Ok(())
+    }
+
+    fn apply_metadata_dir(&self, item: &Item) -> Result<(), CoreError> {
+        let io = |e| CoreError::from_io_error(e, item.target.clone());
+        if self.keeps_permissions() {
+            fs::set_permissions(&item.target, fs::Permissions::from_mode(item.meta.mode() & 0o7777)).map_err(io)?;
+        }
+        if self.keeps_mtime() {
+            File::open(&item.target).and_then(|d| d.set_times(file_times(&item.meta))).map_err(io)?;
+        }
+        Ok(())
+    }
+}
+
+fn file_times(meta: &Metadata) -> FileTimes {
+    let mut times = FileTimes::new();
+    if let Ok(modified) = meta.modified() {
+        times = times.set_modified(modified);
+    }
+    if let Ok(accessed) = meta.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    times
+}
+
+fn remove_all(path: &Path) -> Result<(), CoreError> {
+    let meta = fs::symlink_metadata(path).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))?;
+    let result = if meta.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    result.map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+}
+
+/// Remove `path` if it exists
+fn clear(path: &Path) -> Result<(), CoreError> {
+    match fs::symlink_metadata(path) {
+        Ok(_) => remove_all(path),
+        Err(_) => Ok(()),
+    }
+}
@@ -0,0 +1,20 @@
+//! File transfer engine behind copy and move jobs
+//!
+//! - `Transfer`: plans and runs a copy/move into a destination directory
+//! - `ConflictPolicy`: how existing targets are settled without asking
+//! - `JobControl`: pause/resume/cancel switch checked between chunks
+//! - `ProgressTracker`: byte/file counters with throughput and ETA
+//! - `StreamCopy`: copy between two `FsProvider`s with checksum verification
+
+mod conflict;
+mod control;
+mod engine;
+mod progress;
+mod stream;
+
+pub(crate) use conflict::ConflictResolver;
+pub use conflict::{Conflict, ConflictAnswer, ConflictPolicy, ConflictResolution, ConflictSide};
+pub use control::JobControl;
+pub use engine::{Transfer, TransferMode, TransferObserver, TransferOptions, TransferReport};
+pub use progress::ProgressTracker;
+pub use stream::{StreamCopy, StreamReport, StreamedFile, Verification};
@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Samples older than this no longer count towards throughput
+const WINDOW: Duration = Duration::from_secs(3);
+
+/// Byte and file counters with windowed throughput
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    pub bytes_total: u64,
+    pub bytes_done: u64,
+    pub files_total: u64,
+    pub files_done: u64,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl ProgressTracker {
+    pub fn new(bytes_total: u64, files_total: u64) -> Self {
+        Self {
+            bytes_total,
+            bytes_done: 0,
+            files_total,
+            files_done: 0,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record transferred bytes
+    pub fn add_bytes(&mut self, bytes: u64, now: Instant) {
+        self.bytes_done += bytes;
+        self.samples.push_back((now, self.bytes_done));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Record a finished (or skipped) file
+    pub fn file_done(&mut self) {
+        self.files_done += 1;
+    }
+
+    /// Bytes per second over the recent window
+    pub fn throughput(&self, now: Instant) -> u64 {
+        let Some((first_at, first_bytes)) = self.samples.front() else {
+            return 0;
+        };
+        let elapsed = now.duration_since(*first_at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0;
+        }
+        ((self.bytes_done - first_bytes) as f64 / elapsed) as u64
+    }
+
+    /// Time left at the current throughput
+    pub fn eta(&self, now: Instant) -> Option<Duration> {
+        let rate = self.throughput(now);
+        if rate == 0 {
+            return None;
+        }
+        let remaining = self.bytes_total.saturating_sub(self.bytes_done);
+        Some(Duration::from_secs_f64(remaining as f64 / rate as f64))
+    }
+
+    /// Forget throughput history (e.g. after a pause)
+    pub fn reset_rate(&mut self) {
+        self.samples.clear();
+    }
+}
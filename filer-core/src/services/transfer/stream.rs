@@ -0,0 +1,559 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+use md5::{Digest, Md5};
+
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeKind};
+use crate::utils;
+use crate::vfs::provider::{FsProvider, Precondition, WriteStream};
+
+use super::{
+    Conflict, ConflictPolicy, ConflictResolution, ConflictResolver, ConflictSide, JobControl,
+    ProgressTracker, TransferObserver,
+};
+
+/// Bytes read from the source per request
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// How a streamed file was checked after it was written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// Copied by the provider itself; no bytes passed through here
+    ServerSide,
+    /// Destination reported an MD5 (ETag) matching what was sent
+    Etag,
+    /// Read back from the destination and hashed
+    Reread,
+    /// Destination cannot be read back
+    Unverified,
+}
+
+/// A file that reached the destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamedFile {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub bytes: u64,
+    /// MD5 of the content sent (`None` for server-side copies)
+    pub md5: Option<[u8; 16]>,
+    pub verification: Verification,
+}
+
+/// Result of a provider-to-provider copy that ran to the end
+#[derive(Debug, Clone, Default)]
+pub struct StreamReport {
+    pub files: Vec<StreamedFile>,
+    /// Top-level targets that were transferred (fully or merged)
+    pub created: Vec<PathBuf>,
+    /// Sources that arrived whole: nothing in them failed or was skipped
+    pub complete: Vec<PathBuf>,
+    /// Number of items that failed
+    pub errors: usize,
+    /// Number of items skipped because of a conflict
+    pub skipped: usize,
+}
+
+struct Item {
+    source: PathBuf,
+    target: PathBuf,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+    /// Write over the file at the target
+    replace: bool,
+    /// Delete the target first: a directory is replaced, or replaces one
+    clear: bool,
+    /// Index of the top-level source this item belongs to
+    root: usize,
+}
+
+struct Root {
+    source: PathBuf,
+    target: PathBuf,
+    failed: bool,
+    /// Skipped entirely because of a conflict
+    skipped: bool,
+    /// Something below it was skipped
+    partial: bool,
+}
+
+/// Where a planned item goes once its conflict is settled
+struct Placement {
+    target: PathBuf,
+    replace: bool,
+    clear: bool,
+    /// Copying into an existing directory, whose children may conflict too
+    merge: bool,
+}
+
+/// Planning state: the item list plus conflict handling
+struct Plan<'a> {
+    items: Vec<Item>,
+    roots: Vec<Root>,
+    resolver: ConflictResolver,
+    observer: &'a mut (dyn TransferObserver + Send),
+    errors: usize,
+    skipped: usize,
+}
+
+impl Plan<'_> {
+    fn fail(&mut self, root: usize, path: &Path, error: &CoreError) {
+        self.observer.failed(path, error);
+        self.errors += 1;
+        self.roots[root].failed = true;
+    }
+}
+
+/// Copy between two providers, e.g. from `S3Fs` into `LocalFs`
+///
+/// Content streams through at most two chunks held in memory: the next
+/// chunk is read while the current one is written. Every file is hashed
+/// on the way and checked against the destination afterwards, by its
+/// ETag when it reports an MD5 or by reading it back otherwise. When both
+/// sides are the same provider, files are copied server-side if it can.
+/// Existing targets are resolved through the `ConflictPolicy` or the
+/// observer before anything is written, as in `Transfer`.
+pub struct StreamCopy {
+    source: Arc<dyn FsProvider>,
+    sources: Vec<PathBuf>,
+    destination: Arc<dyn FsProvider>,
+    dest_dir: PathBuf,
+    chunk_size: usize,
+    conflict: ConflictPolicy,
+    verify: bool,
+    preserve_mtime: bool,
+    name: Option<String>,
+}
+
+impl StreamCopy {
+    /// Copy `sources` of `source` into `dest_dir` of `destination`
+    pub fn new(
+        source: Arc<dyn FsProvider>,
+        sources: Vec<PathBuf>,
+        destination: Arc<dyn FsProvider>,
+        dest_dir: PathBuf,
+    ) -> Self {
+        Self {
+            source,
+            sources,
+            destination,
+            dest_dir,
+            chunk_size: CHUNK_SIZE,
+            conflict: ConflictPolicy::default(),
+            verify: true,
+            preserve_mtime: false,
+            name: None,
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Replace existing files instead of failing them
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.conflict.files = overwrite.then_some(ConflictResolution::Overwrite);
+        self
+    }
+
+    /// Conflicts resolved without asking the observer
+    pub fn with_conflict(mut self, conflict: ConflictPolicy) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    /// Read files back when the destination reports no MD5 (default on)
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Give copied files their source's modification time, where the
+    /// destination supports setting it
+    pub fn with_preserve_mtime(mut self, preserve_mtime: bool) -> Self {
+        self.preserve_mtime = preserve_mtime;
+        self
+    }
+
+    /// Name the copy `name` instead of after its source; only used with a
+    /// single source
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub async fn run(
+        &self,
+        control: &JobControl,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<StreamReport, CoreError> {
+        let mut plan = Plan {
+            items: Vec::new(),
+            roots: Vec::new(),
+            resolver: ConflictResolver::new(self.conflict),
+            observer,
+            errors: 0,
+            skipped: 0,
+        };
+        for path in &self.sources {
+            control.checkpoint_async().await?;
+            let name = match &self.name {
+                Some(name) if self.sources.len() == 1 => name.as_ref(),
+                _ => path
+                    .file_name()
+                    .ok_or_else(|| CoreError::InvalidPath(path.display().to_string()))?,
+            };
+            let root = plan.roots.len();
+            plan.roots.push(Root {
+                source: path.clone(),
+                target: self.dest_dir.join(name),
+                failed: false,
+                skipped: false,
+                partial: false,
+            });
+            match self.plan(root, &mut plan, control).await {
+                Ok(()) => {}
+                Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                Err(e) => plan.fail(root, path, &e),
+            }
+        }
+
+        let Plan {
+            items,
+            mut roots,
+            observer,
+            errors,
+            skipped,
+            ..
+        } = plan;
+        let mut report = StreamReport {
+            errors,
+            skipped,
+            ..StreamReport::default()
+        };
+        let files = items.iter().filter(|item| !item.is_dir).count() as u64;
+        let bytes = items.iter().map(|item| item.size).sum();
+        let mut tracker = ProgressTracker::new(bytes, files);
+        for item in &items {
+            control.checkpoint_async().await?;
+            let cleared = match item.clear {
+                true => self.destination.remove(&item.target).await,
+                false => Ok(()),
+            };
+            let result = match cleared {
+                Err(e) => Err(e),
+                Ok(()) if item.is_dir => self.destination.create_dir(&item.target).await,
+                Ok(()) => self
+                    .copy_file(item, control, &mut tracker, observer)
+                    .await
+                    .map(|file| report.files.push(file)),
+            };
+            match result {
+                Ok(()) => {}
+                Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+                Err(e) => {
+                    observer.failed(&item.source, &e);
+                    report.errors += 1;
+                    roots[item.root].failed = true;
+                }
+            }
+            if !item.is_dir {
+                tracker.file_done();
+                observer.progress(&tracker, &item.source);
+            }
+        }
+
+        roots.retain(|root| !root.failed && !root.skipped);
+        report.complete = roots
+            .iter()
+            .filter(|root| !root.partial)
+            .map(|root| root.source.clone())
+            .collect();
+        report.created = roots.into_iter().map(|root| root.target).collect();
+        Ok(report)
+    }
+
+    /// Add a top-level source and everything below it, parents first
+    async fn plan(
+        &self,
+        root: usize,
+        plan: &mut Plan<'_>,
+        control: &JobControl,
+    ) -> Result<(), CoreError> {
+        let source = plan.roots[root].source.clone();
+        let target = plan.roots[root].target.clone();
+        let mut pending = vec![(source, target, true)];
+        while let Some((source, target, check)) = pending.pop() {
+            control.checkpoint_async().await?;
+            let node = self.source.metadata(&source).await?;
+            let is_dir = matches!(node.kind, NodeKind::Directory { .. });
+            let placement = match check {
+                true => match self.settle(&source, &node, target, root, plan).await? {
+                    Some(placement) => placement,
+                    None => continue,
+                },
+                false => Placement {
+                    target,
+                    replace: false,
+                    clear: false,
+                    merge: false,
+                },
+            };
+            if source == plan.roots[root].source {
+                plan.roots[root].target = placement.target.clone();
+            }
+            if is_dir {
+                let mut children = self.source.list(&source).await?;
+                children.sort_by(|a, b| b.name.cmp(&a.name));
+                for child in children {
+                    pending.push((
+                        source.join(&child.name),
+                        placement.target.join(&child.name),
+                        placement.merge,
+                    ));
+                }
+            }
+            plan.items.push(Item {
+                source,
+                target: placement.target,
+                is_dir,
+                size: if is_dir { 0 } else { node.size },
+                modified: node.modified,
+                replace: placement.replace,
+                clear: placement.clear,
+                root,
+            });
+        }
+        Ok(())
+    }
+
+    /// Decide where `source` goes if `target` is taken
+    ///
+    /// Returns `None` to leave the item out.
+    async fn settle(
+        &self,
+        source: &Path,
+        node: &FileNode,
+        target: PathBuf,
+        root: usize,
+        plan: &mut Plan<'_>,
+    ) -> Result<Option<Placement>, CoreError> {
+        let mut placement = Placement {
+            target,
+            replace: false,
+            clear: false,
+            merge: false,
+        };
+        if !self.destination.exists(&placement.target).await? {
+            return Ok(Some(placement));
+        }
+        let existing = self.destination.metadata(&placement.target).await?;
+        let conflict = Conflict {
+            source: ConflictSide::of_node(source.to_path_buf(), node),
+            target: ConflictSide::of_node(placement.target.clone(), &existing),
+        };
+        match plan.resolver.resolve(&conflict, &mut *plan.observer) {
+            Ok(ConflictResolution::Overwrite) => {
+                placement.replace = true;
+                placement.clear = node.is_dir() || existing.is_dir();
+            }
+            Ok(ConflictResolution::KeepBoth) => {
+                placement.target = self.unique_target(&placement.target).await?;
+            }
+            // Directories only; `create_dir` accepts the existing one
+            Ok(ConflictResolution::Merge) => placement.merge = true,
+            Ok(_) => {
+                plan.skipped += 1;
+                match source == plan.roots[root].source {
+                    true => plan.roots[root].skipped = true,
+                    false => plan.roots[root].partial = true,
+                }
+                return Ok(None);
+            }
+            Err(CoreError::Cancelled) => return Err(CoreError::Cancelled),
+            Err(e) => {
+                plan.fail(root, source, &e);
+                return Ok(None);
+            }
+        }
+        Ok(Some(placement))
+    }
+
+    /// First "name (N)" next to `target` that the destination does not have
+    async fn unique_target(&self, target: &Path) -> Result<PathBuf, CoreError> {
+        let mut n = 2;
+        loop {
+            let candidate = utils::numbered_name(target, n);
+            if !self.destination.exists(&candidate).await? {
+                return Ok(candidate);
+            }
+            n += 1;
+        }
+    }
+
+    async fn copy_file(
+        &self,
+        item: &Item,
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<StreamedFile, CoreError> {
+        // Catches a target that appears after planning
+        let precondition = match item.replace {
+            true => Precondition::default(),
+            false => Precondition::absent(),
+        };
+        if self.same_provider()
+            && self
+                .destination
+                .copy_within(&item.source, &item.target, &precondition)
+                .await?
+        {
+            tracker.add_bytes(item.size, Instant::now());
+            self.keep_mtime(item).await?;
+            return Ok(StreamedFile {
+                source: item.source.clone(),
+                target: item.target.clone(),
+                bytes: item.size,
+                md5: None,
+                verification: Verification::ServerSide,
+            });
+        }
+
+        let mut upload = self.destination.create(&item.target, &precondition).await?;
+        let (bytes, md5) = match self
+            .stream(item, upload.as_mut(), control, tracker, observer)
+            .await
+        {
+            Ok(sent) => sent,
+            Err(e) => {
+                upload.abort().await;
+                return Err(e);
+            }
+        };
+        upload.finish().await?;
+        let verification = self
+            .verify_target(&item.target, bytes, &md5, control)
+            .await?;
+        self.keep_mtime(item).await?;
+        Ok(StreamedFile {
+            source: item.source.clone(),
+            target: item.target.clone(),
+            bytes,
+            md5: Some(md5),
+            verification,
+        })
+    }
+
+    /// Pump the source into `upload`; returns bytes sent and their MD5
+    async fn stream(
+        &self,
+        item: &Item,
+        upload: &mut (dyn WriteStream + '_),
+        control: &JobControl,
+        tracker: &mut ProgressTracker,
+        observer: &mut (dyn TransferObserver + Send),
+    ) -> Result<(u64, [u8; 16]), CoreError> {
+        let len = self.chunk_size as u64;
+        let mut hasher = Md5::new();
+        let mut offset = 0;
+        let mut chunk = self.source.read_range(&item.source, 0, len).await?;
+        while !chunk.is_empty() {
+            control.checkpoint_async().await?;
+            hasher.update(&chunk);
+            let next_offset = offset + chunk.len() as u64;
+            let (written, next) = tokio::join!(
+                upload.write(&chunk),
+                self.source.read_range(&item.source, next_offset, len)
+            );
+            written?;
+            tracker.add_bytes(chunk.len() as u64, Instant::now());
+            observer.progress(tracker, &item.source);
+            offset = next_offset;
+            chunk = next?;
+        }
+        Ok((offset, hasher.finalize().into()))
+    }
+
+    async fn verify_target(
+        &self,
+        target: &Path,
+        bytes: u64,
+        md5: &[u8; 16],
+        control: &JobControl,
+    ) -> Result<Verification, CoreError> {
+        let mismatch = |found: String| CoreError::Io {
+            path: target.to_path_buf(),
+            message: format!(
+                "checksum mismatch: sent {}, destination has {found}",
+                hex(md5)
+            ),
+        };
+        let node = self.destination.metadata(target).await?;
+        if node.size != bytes {
+            return Err(mismatch(format!("{} bytes instead of {bytes}", node.size)));
+        }
+        if let Some(etag) = node.meta.etag.as_deref().and_then(etag_md5) {
+            return match etag == *md5 {
+                true => Ok(Verification::Etag),
+                false => Err(mismatch(hex(&etag))),
+            };
+        }
+        if !self.verify || !self.destination.capabilities().read {
+            return Ok(Verification::Unverified);
+        }
+
+        let len = self.chunk_size as u64;
+        let mut hasher = Md5::new();
+        let mut offset = 0;
+        loop {
+            control.checkpoint_async().await?;
+            let chunk = self.destination.read_range(target, offset, len).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            hasher.update(&chunk);
+            offset += chunk.len() as u64;
+        }
+        let reread: [u8; 16] = hasher.finalize().into();
+        match reread == *md5 {
+            true => Ok(Verification::Reread),
+            false => Err(mismatch(hex(&reread))),
+        }
+    }
+
+    async fn keep_mtime(&self, item: &Item) -> Result<(), CoreError> {
+        match item.modified.filter(|_| self.preserve_mtime) {
+            Some(modified) => self
+                .destination
+                .set_modified(&item.target, modified)
+                .await
+                .map(drop),
+            None => Ok(()),
+        }
+    }
+
+    fn same_provider(&self) -> bool {
+        std::ptr::addr_eq(Arc::as_ptr(&self.source), Arc::as_ptr(&self.destination))
+    }
+}
+
+/// MD5 in an ETag; multipart uploads and most servers use other values
+fn etag_md5(etag: &str) -> Option<[u8; 16]> {
+    let etag = etag.trim_start_matches("W/").trim_matches('"');
+    if etag.len() != 32 {
+        return None;
+    }
+    let mut md5 = [0; 16];
+    for (i, byte) in md5.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(etag.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(md5)
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
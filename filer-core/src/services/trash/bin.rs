@@ -0,0 +1,422 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use chrono::{Duration, Local, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CoreError;
+use crate::utils;
+
+use super::info::TrashInfo;
+use super::sizes::DirectorySizes;
+
+/// What to do when the original location of a restored item is taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreConflict {
+    /// Leave the item in the trash and report an error
+    #[default]
+    Fail,
+    /// Replace whatever is at the original location
+    Overwrite,
+    /// Restore next to the existing entry as "name (2).ext"
+    KeepBoth,
+}
+
+/// An item currently in one of the trash directories
+#[derive(Debug, Clone)]
+pub struct TrashedItem {
+    /// Trash directory holding the item (contains `files/` and `info/`)
+    pub trash_dir: PathBuf,
+    /// Volume top directory for per-volume trash (None for home trash)
+    pub topdir: Option<PathBuf>,
+    /// Name of the entry inside `files/`
+    pub name: String,
+    /// Parsed `.trashinfo`
+    pub info: TrashInfo,
+    /// Size in bytes (recursive for directories)
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+impl TrashedItem {
+    /// Location of the trashed data
+    pub fn files_path(&self) -> PathBuf {
+        self.trash_dir.join("files").join(&self.name)
+    }
+
+    /// Location of the `.trashinfo` file
+    pub fn info_path(&self) -> PathBuf {
+        info_path(&self.trash_dir, &self.name)
+    }
+}
+
+/// What emptying or purging the trash removed, and what went wrong
+#[derive(Debug, Default)]
+pub struct Purge {
+    /// Items whose data was deleted
+    pub erased: Vec<TrashedItem>,
+    pub errors: Vec<CoreError>,
+}
+
+/// Access to the user's trash directories
+#[derive(Debug, Clone)]
+pub struct TrashBin {
+    home: PathBuf,
+    uid: u32,
+}
+
+impl TrashBin {
+    /// Home trash from `$XDG_DATA_HOME/Trash` (or `~/.local/share/Trash`)
+    pub fn new() -> Result<Self, CoreError> {
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+            .ok_or_else(|| CoreError::InvalidPath("$XDG_DATA_HOME and $HOME are unset".to_string()))?;
+        Ok(Self::with_home(data_home.join("Trash")))
+    }
+
+    /// Use a custom home trash directory
+    pub fn with_home(home: PathBuf) -> Self {
+        Self {
+            home,
+            // SAFETY: getuid has no preconditions and cannot fail
+            uid: unsafe { libc::getuid() },
+        }
+    }
+
+    /// Home trash directory
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    /// Move a file or directory into the matching trash directory
+    pub fn trash(&self, path: &Path) -> Result<TrashedItem, CoreError> {
+        let path = absolute_no_follow(path)?;
+        let meta = fs::symlink_metadata(&path).map_err(|e| CoreError::from_io_error(e, path.clone()))?;
+        if path.starts_with(&self.home) {
+            return Err(CoreError::InvalidPath(format!("{} is already in the trash", path.display())));
+        }
+
+        let (trash_dir, topdir) = self.trash_dir_for(&path, meta.dev())?;
+        ensure_dir(&trash_dir.join("files"))?;
+        ensure_dir(&trash_dir.join("info"))?;
+
+        let now = Local::now().naive_local();
+        let info = TrashInfo::new(path.clone(), now.with_nanosecond(0).unwrap_or(now));
+        let (name, info_file) = reserve_name(&trash_dir, &path, &info, topdir.as_deref())?;
+
+        let files_path = trash_dir.join("files").join(&name);
+        if let Err(e) = fs::rename(&path, &files_path) {
+            let _ = fs::remove_file(&info_file);
+            return Err(CoreError::from_io_error(e, path));
+        }
+
+        let is_dir = meta.is_dir();
+        let size = if is_dir {
+            let size = dir_size(&files_path);
+            let mut sizes = DirectorySizes::load(&trash_dir);
+            sizes.insert(name.clone(), size, mtime_secs(&info_file));
+            sizes.save(&trash_dir)?;
+            size
+        } else {
+            meta.len()
+        };
+
+        Ok(TrashedItem {
+            trash_dir,
+            topdir,
+            name,
+            info,
+            size,
+            is_dir,
+        })
+    }
+
+    /// All items across the home trash and every mounted volume's trash
+    pub fn list(&self) -> Vec<TrashedItem> {
+        self.trash_dirs()
+            .into_iter()
+            .flat_map(|(dir, topdir)| list_dir(&dir, topdir.as_deref()))
+            .collect()
+    }
+
+    /// Find an item by the path of its data inside `files/`
+    pub fn find(&self, files_path: &Path) -> Option<TrashedItem> {
+        self.list().into_iter().find(|item| item.files_path() == files_path)
+    }
+
+    /// Move an item back to its original location
+    ///
+    /// Returns the path the item was restored to.
+    pub fn restore(&self, item: &TrashedItem, conflict: RestoreConflict) -> Result<PathBuf, CoreError> {
+        let mut target = item.info.original_path.clone();
+        if fs::symlink_metadata(&target).is_ok() {
+            match conflict {
+                RestoreConflict::Fail => {
+                    return Err(CoreError::InvalidPath(format!("{} already exists", target.display())));
+                }
+                RestoreConflict::Overwrite => remove_any(&target)?,
+                RestoreConflict::KeepBoth => target = utils::unique_name(&target),
+            }
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| CoreError::from_io_error(e, parent.to_path_buf()))?;
+        }
+
+        let source = item.files_path();
+        fs::rename(&source, &target).map_err(|e| CoreError::from_io_error(e, source))?;
+        self.forget(item)?;
+        Ok(target)
+    }
+
+    /// Permanently delete a single item
+    pub fn erase(&self, item: &TrashedItem) -> Result<(), CoreError> {
+        let data = item.files_path();
+        if fs::symlink_metadata(&data).is_ok() {
+            remove_any(&data)?;
+        }
+        self.forget(item)
+    }
+
+    /// Permanently delete everything in every trash directory
+    pub fn empty(&self) -> Purge {
+        self.erase_where(|_| true)
+    }
+
+    /// Permanently delete items trashed more than `days` days ago
+    pub fn purge_older_than(&self, days: u32) -> Purge {
+        let cutoff = Local::now().naive_local() - Duration::days(days as i64);
+        self.erase_where(|item| item.info.deletion_date < cutoff)
+    }
+
+    /// Erase every item matching `pred`, carrying on past failures
+    fn erase_where(&self, pred: impl Fn(&TrashedItem) -> bool) -> Purge {
+        let mut purge = Purge::default();
+        for item in self.list().into_iter().filter(|i| pred(i)) {
+            match self.erase(&item) {
+                Ok(()) => purge.erased.push(item),
+                Err(e) => {
+                    // Data that is gone stays gone even if its bookkeeping failed
+                    if fs::symlink_metadata(item.files_path()).is_err() {
+                        purge.erased.push(item);
+                    }
+                    purge.errors.push(e);
+                }
+            }
+        }
+        purge
+    }
+
+    /// Drop the info file and directorysizes entry of an item
+    fn forget(&self, item: &TrashedItem) -> Result<(), CoreError> {
+        let info = item.info_path();
+        fs::remove_file(&info).map_err(|e| CoreError::from_io_error(e, info))?;
+        if item.is_dir {
+            let mut sizes = DirectorySizes::load(&item.trash_dir);
+            if sizes.remove(&item.name) {
+                sizes.save(&item.trash_dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pick the trash directory for a path on device `dev`
+    fn trash_dir_for(&self, path: &Path, dev: u64) -> Result<(PathBuf, Option<PathBuf>), CoreError> {
+        if existing_ancestor_dev(&self.home) == Some(dev) {
+            ensure_dir(&self.home)?;
+            return Ok((self.home.clone(), None));
+        }
+
+        let topdir = mount_point(path, dev);
+        let shared = topdir.join(".Trash");
+        if let Ok(meta) = fs::symlink_metadata(&shared) {
+            let sticky = meta.permissions().mode() & libc::S_ISVTX != 0;
+            if meta.is_dir() && sticky {
+                let dir = shared.join(self.uid.to_string());
+                if ensure_dir(&dir).is_ok() {
+                    return Ok((dir, Some(topdir)));
+                }
+            }
+        }
+        let dir = topdir.join(format!(".Trash-{}", self.uid));
+        ensure_dir(&dir)?;
+        Ok((dir, Some(topdir)))
+    }
+
+    /// Home trash plus every existing per-volume trash directory
+    fn trash_dirs(&self) -> Vec<(PathBuf, Option<PathBuf>)> {
+        let mut dirs = vec![(self.home.clone(), None)];
+        for top in mount_points() {
+            for dir in [top.join(".Trash").join(self.uid.to_string()), top.join(format!(".Trash-{}", self.uid))] {
+                if dir.join("info").is_dir() && dir != self.home {
+                    dirs.push((dir, Some(top.clone())));
+                }
+            }
+        }
+        dirs
+    }
+}
+
+fn info_path(trash_dir: &Path, name: &str) -> PathBuf {
+    trash_dir.join("info").join(format!("{name}.trashinfo"))
+}
+
+/// Atomically claim a name in `info/` by creating its `.trashinfo` exclusively
+fn reserve_name(
+    trash_dir: &Path,
+    path: &Path,
+    info: &TrashInfo,
+    topdir: Option<&Path>,
+) -> Result<(String, PathBuf), CoreError> {
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| CoreError::InvalidPath(path.display().to_string()))?;
+    let stem = utils::get_stem(Path::new(file_name)).unwrap_or(file_name);
+    let ext = utils::get_extension(Path::new(file_name));
+
+    for n in 1.. {
+        let name = match (n, ext) {
+            (1, _) => file_name.to_string(),
+            (_, Some(ext)) => format!("{stem}.{n}.{ext}"),
+            (_, None) => format!("{stem}.{n}"),
+        };
+        // A dangling symlink is taken too
+        if fs::symlink_metadata(trash_dir.join("files").join(&name)).is_ok() {
+            continue;
+        }
+        let info_file = info_path(trash_dir, &name);
+        match fs::OpenOptions::new().write(true).create_new(true).open(&info_file) {
+            Ok(mut f) => {
+                f.write_all(info.to_string_relative(topdir).as_bytes())
+                    .and_then(|_| f.sync_all())
+                    .map_err(|e| CoreError::from_io_error(e, info_file.clone()))?;
+                return Ok((name, info_file));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(CoreError::from_io_error(e, info_file)),
+        }
+    }
+    unreachable!()
+}
+
+fn list_dir(trash_dir: &Path, topdir: Option<&Path>) -> Vec<TrashedItem> {
+    let Ok(entries) = fs::read_dir(trash_dir.join("info")) else {
+        return Vec::new();
+    };
+    let sizes = DirectorySizes::load(trash_dir);
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name();
+            let name = file_name.to_str()?.strip_suffix(".trashinfo")?.to_string();
+            let info = TrashInfo::parse(&fs::read_to_string(entry.path()).ok()?, topdir).ok()?;
+            let data = trash_dir.join("files").join(&name);
+            let meta = fs::symlink_metadata(&data).ok()?;
+            let is_dir = meta.is_dir();
+            let size = if is_dir {
+                sizes.get(&name).unwrap_or_else(|| dir_size(&data))
+            } else {
+                meta.len()
+            };
+            Some(TrashedItem {
+                trash_dir: trash_dir.to_path_buf(),
+                topdir: topdir.map(Path::to_path_buf),
+                name,
+                info,
+                size,
+                is_dir,
+            })
+        })
+        .collect()
+}
+
+fn ensure_dir(dir: &Path) -> Result<(), CoreError> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|e| CoreError::from_io_error(e, dir.to_path_buf()))
+}
+
+fn remove_any(path: &Path) -> Result<(), CoreError> {
+    let meta = fs::symlink_metadata(path).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))?;
+    let res = if meta.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    res.map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+}
+
+/// Make a path absolute without resolving a trailing symlink
+fn absolute_no_follow(path: &Path) -> Result<PathBuf, CoreError> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| CoreError::InvalidPath(path.display().to_string()))?;
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let parent = parent
+        .canonicalize()
+        .map_err(|e| CoreError::from_io_error(e, parent))?;
+    Ok(parent.join(name))
+}
+
+/// Apparent size of a directory tree (symlinks are not followed)
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|e| match e.metadata() {
+            Ok(m) if m.is_dir() => dir_size(&e.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn mtime_secs(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn existing_ancestor_dev(path: &Path) -> Option<u64> {
+    path.ancestors().find_map(|p| fs::metadata(p).ok()).map(|m| m.dev())
+}
+
+/// Topmost directory of `path` that still lives on device `dev`
+fn mount_point(path: &Path, dev: u64) -> PathBuf {
+    let mut top = path.parent().unwrap_or(path).to_path_buf();
+    while let Some(parent) = top.parent() {
+        match fs::metadata(parent) {
+            Ok(m) if m.dev() == dev => top = parent.to_path_buf(),
+            _ => break,
+        }
+    }
+    top
+}
+
+/// Mount points from `/proc/self/mounts`
+fn mount_points() -> Vec<PathBuf> {
+    let Ok(mounts) = fs::read_to_string("/proc/self/mounts") else {
+        return Vec::new();
+    };
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|p| PathBuf::from(p.replace("\\040", " ").replace("\\011", "\t").replace("\\134", "\\")))
+        .collect()
+}
@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+
+use crate::errors::CoreError;
+
+const HEADER: &str = "[Trash Info]";
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Contents of a `.trashinfo` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashInfo {
+    /// Absolute path the item was trashed from
+    pub original_path: PathBuf,
+    /// Local time of deletion
+    pub deletion_date: NaiveDateTime,
+}
+
+impl TrashInfo {
+    pub fn new(original_path: PathBuf, deletion_date: NaiveDateTime) -> Self {
+        Self {
+            original_path,
+            deletion_date,
+        }
+    }
+
+    /// Parse a `.trashinfo` file body
+    ///
+    /// `topdir` is used to resolve relative paths found in per-volume trash.
+    pub fn parse(content: &str, topdir: Option<&Path>) -> Result<Self, CoreError> {
+        let mut lines = content.lines().map(str::trim).filter(|l| !l.is_empty());
+        if lines.next() != Some(HEADER) {
+            return Err(CoreError::InvalidData);
+        }
+
+        let mut path = None;
+        let mut date = None;
+        for line in lines {
+            if line.starts_with('[') {
+                break;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "Path" if path.is_none() => path = Some(percent_decode(value.trim())?),
+                "DeletionDate" if date.is_none() => {
+                    date = NaiveDateTime::parse_from_str(value.trim(), DATE_FORMAT).ok()
+                }
+                _ => {}
+            }
+        }
+
+        let path = PathBuf::from(path.ok_or(CoreError::InvalidData)?);
+        let original_path = match topdir {
+            Some(top) if path.is_relative() => top.join(path),
+            _ => path,
+        };
+        Ok(Self {
+            original_path,
+            deletion_date: date.ok_or(CoreError::InvalidData)?,
+        })
+    }
+
+    /// Serialize to `.trashinfo` format
+    ///
+    /// When `topdir` is given the path is stored relative to it, as the
+    /// specification recommends for per-volume trash directories.
+    pub fn to_string_relative(&self, topdir: Option<&Path>) -> String {
+        let path = match topdir {
+            Some(top) => self
+                .original_path
+                .strip_prefix(top)
+                .unwrap_or(&self.original_path),
+            None => &self.original_path,
+        };
+        format!(
+            "{HEADER}\nPath={}\nDeletionDate={}\n",
+            percent_encode(path.to_string_lossy().as_bytes()),
+            self.deletion_date.format(DATE_FORMAT)
+        )
+    }
+}
+
+/// Percent-encode a path, keeping `/` and RFC 3986 unreserved characters
+pub(crate) fn percent_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || matches!(b, b'/' | b'-' | b'_' | b'.' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+/// Decode a percent-encoded string
+pub(crate) fn percent_decode(s: &str) -> Result<String, CoreError> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3).ok_or(CoreError::InvalidData)?;
+            out.push(u8::from_str_radix(hex, 16).map_err(|_| CoreError::InvalidData)?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| CoreError::InvalidData)
+}
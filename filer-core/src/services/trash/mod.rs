@@ -0,0 +1,12 @@
+//! FreeDesktop.org Trash specification support
+//!
+//! Implements the home trash (`$XDG_DATA_HOME/Trash`) and per-volume
+//! trash directories (`$topdir/.Trash/$uid` and `$topdir/.Trash-$uid`).
+
+mod bin;
+mod info;
+mod sizes;
+
+pub use bin::{Purge, RestoreConflict, TrashBin, TrashedItem};
+pub use info::TrashInfo;
+pub use sizes::DirectorySizes;
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::errors::CoreError;
+
+use super::info::{percent_decode, percent_encode};
+
+/// Cached sizes of trashed directories (`$trash/directorysizes`)
+///
+/// Each line is `<size> <trashinfo mtime> <percent-encoded name>`.
+#[derive(Debug, Clone, Default)]
+pub struct DirectorySizes {
+    entries: BTreeMap<String, (u64, u64)>,
+}
+
+impl DirectorySizes {
+    /// Load the cache from a trash directory (missing file = empty cache)
+    pub fn load(trash_dir: &Path) -> Self {
+        let Ok(content) = fs::read_to_string(Self::file(trash_dir)) else {
+            return Self::default();
+        };
+        let entries = content
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, ' ');
+                let size = parts.next()?.parse().ok()?;
+                let mtime = parts.next()?.parse().ok()?;
+                let name = percent_decode(parts.next()?).ok()?;
+                Some((name, (size, mtime)))
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Write the cache atomically (temp file + rename)
+    pub fn save(&self, trash_dir: &Path) -> Result<(), CoreError> {
+        let target = Self::file(trash_dir);
+        let tmp = trash_dir.join(format!("directorysizes.{}", std::process::id()));
+        let write = || -> std::io::Result<()> {
+            let mut f = fs::File::create(&tmp)?;
+            for (name, (size, mtime)) in &self.entries {
+                writeln!(f, "{size} {mtime} {}", percent_encode(name.as_bytes()))?;
+            }
+            f.sync_all()?;
+            fs::rename(&tmp, &target)
+        };
+        write().map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            CoreError::from_io_error(e, target.clone())
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.entries.get(name).map(|(size, _)| *size)
+    }
+
+    pub fn insert(&mut self, name: String, size: u64, info_mtime: u64) {
+        self.entries.insert(name, (size, info_mtime));
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn file(trash_dir: &Path) -> PathBuf {
+        trash_dir.join("directorysizes")
+    }
+}
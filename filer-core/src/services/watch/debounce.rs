@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::model::fs_change::FsChangeKind;
+
+struct Pending {
+    kind: FsChangeKind,
+    first: Instant,
+    last: Instant,
+}
+
+/// Coalesces bursts of changes per path
+///
+/// A change is released once its path has been quiet for `quiet`, or
+/// at the latest `max_delay` after the first change, so a build writing
+/// a file 1000 times yields a single `Modified`.
+pub struct Debouncer {
+    quiet: Duration,
+    max_delay: Duration,
+    pending: HashMap<PathBuf, Pending>,
+}
+
+impl Debouncer {
+    pub fn new(quiet: Duration, max_delay: Duration) -> Self {
+        Self {
+            quiet,
+            max_delay,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Record a change, merging it with any pending change for the same path
+    pub fn push(&mut self, path: PathBuf, kind: FsChangeKind, now: Instant) {
+        // A rename of something created in this window is just a create
+        if let FsChangeKind::Renamed { from } = &kind
+            && let Some(prev) = self.pending.remove(from)
+            && prev.kind == FsChangeKind::Created
+        {
+            self.insert(path, FsChangeKind::Created, prev.first, now);
+            return;
+        }
+
+        let Some(prev) = self.pending.remove(&path) else {
+            self.insert(path, kind, now, now);
+            return;
+        };
+
+        let merged = match (prev.kind, kind) {
+            (FsChangeKind::Rescan, _) | (_, FsChangeKind::Rescan) => Some(FsChangeKind::Rescan),
+            (FsChangeKind::Created, FsChangeKind::Deleted) => None,
+            (FsChangeKind::Created, FsChangeKind::Modified) => Some(FsChangeKind::Created),
+            (FsChangeKind::Deleted, FsChangeKind::Created) => Some(FsChangeKind::Modified),
+            (FsChangeKind::Renamed { from }, FsChangeKind::Modified) => {
+                Some(FsChangeKind::Renamed { from })
+            }
+            (FsChangeKind::Renamed { from }, FsChangeKind::Deleted) => {
+                // The client only ever saw the old name
+                self.insert(from, FsChangeKind::Deleted, prev.first, now);
+                None
+            }
+            (_, kind) => Some(kind),
+        };
+        if let Some(kind) = merged {
+            self.insert(path, kind, prev.first, now);
+        }
+    }
+
+    /// Take every change that is due at `now`, oldest first
+    pub fn drain_due(&mut self, now: Instant) -> Vec<(PathBuf, FsChangeKind)> {
+        let due: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| {
+                now.duration_since(p.last) >= self.quiet || now.duration_since(p.first) >= self.max_delay
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.take(due)
+    }
+
+    /// Take every pending change regardless of timing
+    pub fn flush(&mut self) -> Vec<(PathBuf, FsChangeKind)> {
+        let all = self.pending.keys().cloned().collect();
+        self.take(all)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn insert(&mut self, path: PathBuf, kind: FsChangeKind, first: Instant, last: Instant) {
+        self.pending.insert(path, Pending { kind, first, last });
+    }
+
+    fn take(&mut self, paths: Vec<PathBuf>) -> Vec<(PathBuf, FsChangeKind)> {
+        let mut out: Vec<(Instant, PathBuf, FsChangeKind)> = paths
+            .into_iter()
+            .filter_map(|path| {
+                let p = self.pending.remove(&path)?;
+                Some((p.first, path, p.kind))
+            })
+            .collect();
+        out.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        out.into_iter().map(|(_, path, kind)| (path, kind)).collect()
+    }
+}
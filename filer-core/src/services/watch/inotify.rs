@@ -0,0 +1,117 @@
+use std::ffi::{CString, OsString};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+/// Watch descriptor returned by `inotify_add_watch`
+pub type WatchDescriptor = i32;
+
+/// Size of the fixed `struct inotify_event` header
+const EVENT_HEADER: usize = 16;
+
+/// Decoded `struct inotify_event`
+#[derive(Debug, Clone)]
+pub struct RawEvent {
+    /// Watch the event belongs to (-1 for queue overflow)
+    pub wd: WatchDescriptor,
+    pub mask: u32,
+    /// Pairs IN_MOVED_FROM with IN_MOVED_TO
+    pub cookie: u32,
+    /// Entry name relative to the watched directory
+    pub name: Option<OsString>,
+}
+
+impl RawEvent {
+    pub fn has(&self, flag: u32) -> bool {
+        self.mask & flag != 0
+    }
+}
+
+/// Non-blocking inotify instance
+#[derive(Debug)]
+pub struct Inotify {
+    fd: OwnedFd,
+}
+
+impl Inotify {
+    /// Mask used for directory watches
+    pub const DIR_MASK: u32 = libc::IN_MODIFY
+        | libc::IN_ATTRIB
+        | libc::IN_CLOSE_WRITE
+        | libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO
+        | libc::IN_DELETE_SELF
+        | libc::IN_MOVE_SELF
+        | libc::IN_ONLYDIR
+        | libc::IN_EXCL_UNLINK;
+
+    pub fn init() -> io::Result<Self> {
+        // SAFETY: plain syscall, the returned fd is checked before use
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: fd is a freshly created descriptor we exclusively own
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    pub fn add_watch(&self, path: &Path, mask: u32) -> io::Result<WatchDescriptor> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: c_path is a valid NUL-terminated string for the call duration
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), c_path.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wd)
+    }
+
+    pub fn rm_watch(&self, wd: WatchDescriptor) -> io::Result<()> {
+        // SAFETY: plain syscall on our own fd
+        if unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), wd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Read all queued events (`WouldBlock` when the queue is empty)
+    pub fn read_events(&self, buf: &mut [u8]) -> io::Result<Vec<RawEvent>> {
+        // SAFETY: buf is valid for writes of buf.len() bytes
+        let n = unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(parse_events(&buf[..n as usize]))
+    }
+}
+
+impl AsRawFd for Inotify {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+fn parse_events(mut bytes: &[u8]) -> Vec<RawEvent> {
+    let mut events = Vec::new();
+    while bytes.len() >= EVENT_HEADER {
+        let field = |i: usize| [bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]];
+        let wd = i32::from_ne_bytes(field(0));
+        let mask = u32::from_ne_bytes(field(4));
+        let cookie = u32::from_ne_bytes(field(8));
+        let len = u32::from_ne_bytes(field(12)) as usize;
+        let end = (EVENT_HEADER + len).min(bytes.len());
+
+        let raw_name = &bytes[EVENT_HEADER..end];
+        let name_len = raw_name.iter().position(|&b| b == 0).unwrap_or(raw_name.len());
+        let name = (name_len > 0).then(|| OsString::from_vec(raw_name[..name_len].to_vec()));
+
+        events.push(RawEvent { wd, mask, cookie, name });
+        bytes = &bytes[end..];
+    }
+    events
+}
@@ -0,0 +1,15 @@
+//! Filesystem change detection building blocks
+//!
+//! - `Inotify`: thin wrapper over the Linux inotify API
+//! - `Debouncer`: coalesces bursts of raw changes into single events
+//! - `Snapshot`: listing snapshots diffed by the polling watcher
+
+mod debounce;
+#[cfg(target_os = "linux")]
+mod inotify;
+mod poll;
+
+pub use debounce::Debouncer;
+pub use poll::{EntrySignature, Snapshot};
+#[cfg(target_os = "linux")]
+pub use inotify::{Inotify, RawEvent, WatchDescriptor};
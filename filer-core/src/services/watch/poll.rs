@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::model::fs_change::FsChangeKind;
+use crate::model::node::FileNode;
+
+/// What a poll remembers about one entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntrySignature {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub etag: Option<String>,
+    pub is_dir: bool,
+}
+
+impl EntrySignature {
+    fn from_node(node: &FileNode) -> Self {
+        Self {
+            size: node.size,
+            modified: node.modified,
+            etag: node.meta.etag.clone(),
+            is_dir: node.is_dir(),
+        }
+    }
+
+    /// Same content under a different name (only provable with an ETag)
+    fn same_content(&self, other: &Self) -> bool {
+        self.etag.is_some() && self.etag == other.etag && self.size == other.size && self.is_dir == other.is_dir
+    }
+}
+
+/// Listing snapshot used for polling-based change detection
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    entries: HashMap<PathBuf, EntrySignature>,
+}
+
+impl Snapshot {
+    pub fn from_nodes(nodes: &[FileNode]) -> Self {
+        Self {
+            entries: nodes
+                .iter()
+                .map(|n| (n.path.clone(), EntrySignature::from_node(n)))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Changes that turn `self` into `newer`
+    ///
+    /// A vanished entry and a new entry with the same ETag and size are
+    /// reported as a rename.
+    pub fn diff(&self, newer: &Snapshot) -> Vec<(PathBuf, FsChangeKind)> {
+        let mut removed: Vec<(&PathBuf, &EntrySignature)> = self
+            .entries
+            .iter()
+            .filter(|(path, _)| !newer.entries.contains_key(*path))
+            .collect();
+        removed.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut added: Vec<(&PathBuf, &EntrySignature)> = newer
+            .entries
+            .iter()
+            .filter(|(path, _)| !self.entries.contains_key(*path))
+            .collect();
+        added.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut changes = Vec::new();
+        for (path, sig) in added {
+            match removed.iter().position(|(_, old)| old.same_content(sig)) {
+                Some(idx) => {
+                    let (from, _) = removed.remove(idx);
+                    changes.push((path.clone(), FsChangeKind::Renamed { from: from.clone() }));
+                }
+                None => changes.push((path.clone(), FsChangeKind::Created)),
+            }
+        }
+        changes.extend(removed.into_iter().map(|(path, _)| (path.clone(), FsChangeKind::Deleted)));
+
+        let mut modified: Vec<PathBuf> = newer
+            .entries
+            .iter()
+            .filter(|(path, sig)| self.entries.get(*path).is_some_and(|old| old != *sig))
+            .map(|(path, _)| path.clone())
+            .collect();
+        modified.sort();
+        changes.extend(modified.into_iter().map(|path| (path, FsChangeKind::Modified)));
+        changes
+    }
+}
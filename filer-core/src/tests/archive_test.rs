@@ -0,0 +1,599 @@
+//! Tests for archive creation and extraction
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+use crate::actors::operations::OpCommand;
+use crate::api::events::{Event, JobOutcome, OperationKind};
+use crate::errors::CoreError;
+use crate::services::archive::{
+    ArchiveFormat, ArchiveOptions, Compress, Extract, ExtractLimits, ExtractOptions, SymlinkMode,
+};
+use crate::services::transfer::{
+    ConflictPolicy, ConflictResolution, JobControl, ProgressTracker, TransferObserver,
+    TransferReport,
+};
+use crate::tests::common::{self, spawn_operations};
+
+#[derive(Default)]
+struct Recorder {
+    last_bytes: u64,
+    failed: Vec<(PathBuf, String)>,
+}
+
+impl TransferObserver for Recorder {
+    fn progress(&mut self, tracker: &ProgressTracker, _current: &Path) {
+        assert!(
+            tracker.bytes_done >= self.last_bytes,
+            "progress went backwards"
+        );
+        self.last_bytes = tracker.bytes_done;
+    }
+
+    fn failed(&mut self, path: &Path, error: &CoreError) {
+        self.failed.push((path.to_path_buf(), error.to_string()));
+    }
+}
+
+/// The shared tree with `a.txt` at a mode the umask would not produce
+fn tree(root: &Path) -> PathBuf {
+    let src = common::tree(root);
+    fs::set_permissions(src.join("a.txt"), fs::Permissions::from_mode(0o640)).unwrap();
+    src
+}
+
+fn compress(sources: Vec<PathBuf>, target: &Path, options: ArchiveOptions) -> TransferReport {
+    let mut recorder = Recorder::default();
+    let report = Compress::new(sources, target.to_path_buf(), options)
+        .run(&JobControl::new(), &mut recorder)
+        .unwrap();
+    assert_eq!(report.errors, 0, "{:?}", recorder.failed);
+    report
+}
+
+fn extract(
+    archive: &Path,
+    dest: &Path,
+    options: ExtractOptions,
+) -> (Result<TransferReport, CoreError>, Recorder) {
+    let mut recorder = Recorder::default();
+    let result = Extract::new(archive.to_path_buf(), dest.to_path_buf(), options)
+        .run(&JobControl::new(), &mut recorder);
+    (result, recorder)
+}
+
+fn fresh_dir(root: &Path, name: &str) -> PathBuf {
+    let dir = root.join(name);
+    fs::create_dir(&dir).unwrap();
+    dir.canonicalize().unwrap()
+}
+
+fn mode(path: &Path) -> u32 {
+    fs::symlink_metadata(path).unwrap().permissions().mode() & 0o7777
+}
+
+fn assert_tree(root: &Path) {
+    assert_eq!(fs::read(root.join("a.txt")).unwrap(), b"alpha");
+    assert_eq!(fs::read(root.join("sub/b.bin")).unwrap(), vec![7u8; 10_000]);
+    assert_eq!(fs::read(root.join("sub/deeper/c")).unwrap(), b"");
+    assert_eq!(mode(&root.join("a.txt")), 0o640);
+}
+
+/// Zip whose entries are written with exactly the given names
+fn raw_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+/// Tar entry with an unchecked name
+fn raw_tar_entry(
+    builder: &mut tar::Builder<File>,
+    name: &str,
+    kind: tar::EntryType,
+    link: &str,
+    data: &[u8],
+) {
+    let mut header = tar::Header::new_gnu();
+    header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+    header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+    header.set_entry_type(kind);
+    header.set_mode(0o644);
+    header.set_size(data.len() as u64);
+    header.set_cksum();
+    builder.append(&header, data).unwrap();
+}
+
+fn part_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.to_string_lossy().ends_with(".filer-part"))
+        .collect()
+}
+
+// ===== Format Tests =====
+
+#[test]
+fn test_archive_format_from_path_and_level() {
+    let format = |name: &str| ArchiveFormat::from_path(Path::new(name));
+    assert_eq!(format("a.ZIP"), Some(ArchiveFormat::Zip));
+    assert_eq!(format("a.tar"), Some(ArchiveFormat::Tar));
+    assert_eq!(format("a.tgz"), Some(ArchiveFormat::TarGz));
+    assert_eq!(format("a.tar.zst"), Some(ArchiveFormat::TarZst));
+    assert_eq!(format("a.zst"), None);
+
+    assert_eq!(ArchiveFormat::TarZst.level(None), 3);
+    assert_eq!(ArchiveFormat::TarZst.level(Some(99)), 22);
+    assert_eq!(ArchiveFormat::Zip.level(Some(-1)), 0);
+}
+
+// ===== Round Trip Tests =====
+
+#[test]
+fn test_zip_round_trip_stores_symlinks() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let archive = dir.path().join("out.zip");
+
+    let report = compress(vec![src], &archive, ArchiveOptions::new(ArchiveFormat::Zip));
+    assert_eq!(report.created, vec![archive.clone()]);
+    assert!(part_files(dir.path()).is_empty());
+
+    let dest = fresh_dir(dir.path(), "dest");
+    let (result, recorder) = extract(&archive, &dest, ExtractOptions::default());
+    let report = result.unwrap();
+    assert_eq!(report.errors, 0, "{:?}", recorder.failed);
+    assert_eq!(report.created, vec![dest.join("src")]);
+    assert_eq!(recorder.last_bytes, 10_005);
+    assert_tree(&dest.join("src"));
+    assert_eq!(
+        fs::read_link(dest.join("src/link")).unwrap(),
+        Path::new("a.txt")
+    );
+}
+
+#[test]
+fn test_tar_zst_round_trip_with_level() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let archive = dir.path().join("out.tar.zst");
+
+    let mut options = ArchiveOptions::new(ArchiveFormat::TarZst);
+    options.level = Some(19);
+    compress(vec![src.join("a.txt"), src.join("sub")], &archive, options);
+
+    let dest = fresh_dir(dir.path(), "dest");
+    let (result, recorder) = extract(&archive, &dest, ExtractOptions::default());
+    let report = result.unwrap();
+    assert_eq!(report.errors, 0, "{:?}", recorder.failed);
+    assert_eq!(report.created, vec![dest.join("a.txt"), dest.join("sub")]);
+    assert_eq!(fs::read(dest.join("a.txt")).unwrap(), b"alpha");
+    assert_eq!(fs::read(dest.join("sub/b.bin")).unwrap(), vec![7u8; 10_000]);
+    // Progress follows the compressed stream up to its end
+    assert_eq!(recorder.last_bytes, fs::metadata(&archive).unwrap().len());
+}
+
+#[test]
+fn test_extract_recognizes_format_from_content() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let archive = dir.path().join("out.tar.gz");
+    compress(
+        vec![src],
+        &archive,
+        ArchiveOptions::new(ArchiveFormat::TarGz),
+    );
+    let renamed = dir.path().join("download.bin");
+    fs::rename(&archive, &renamed).unwrap();
+
+    let dest = fresh_dir(dir.path(), "dest");
+    let (result, _) = extract(&renamed, &dest, ExtractOptions::default());
+    assert_eq!(result.unwrap().errors, 0);
+    assert_tree(&dest.join("src"));
+
+    fs::write(&renamed, b"not an archive").unwrap();
+    let (result, _) = extract(&renamed, &dest, ExtractOptions::default());
+    assert!(matches!(result, Err(CoreError::Io { .. })));
+}
+
+#[test]
+fn test_compress_follows_symlinks() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    std::os::unix::fs::symlink(&src, src.join("sub/loop")).unwrap();
+    let archive = dir.path().join("out.tar");
+
+    let mut options = ArchiveOptions::new(ArchiveFormat::Tar);
+    options.symlinks = SymlinkMode::Follow;
+    let mut recorder = Recorder::default();
+    let report = Compress::new(vec![src], archive.clone(), options)
+        .run(&JobControl::new(), &mut recorder)
+        .unwrap();
+    // The loop back to `src` is left out, the rest is archived
+    assert_eq!(report.errors, 1);
+    assert!(recorder.failed[0].1.contains("symlink loop"));
+
+    let dest = fresh_dir(dir.path(), "dest");
+    let (result, _) = extract(&archive, &dest, ExtractOptions::default());
+    assert_eq!(result.unwrap().errors, 0);
+    let link = dest.join("src/link");
+    assert!(!fs::symlink_metadata(&link).unwrap().is_symlink());
+    assert_eq!(fs::read(link).unwrap(), b"alpha");
+    assert!(!dest.join("src/sub/loop").exists());
+}
+
+// ===== Extraction Safety Tests =====
+
+#[test]
+fn test_extract_rejects_traversal_and_absolute_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("evil.zip");
+    raw_zip(
+        &archive,
+        &[
+            ("../evil.txt", b"x"),
+            ("ok/../../evil2.txt", b"x"),
+            ("/tmp/abs.txt", b"x"),
+            ("C:/windows.txt", b"x"),
+            ("..\\backslash.txt", b"x"),
+            ("./ok.txt", b"fine"),
+        ],
+    );
+    let dest = fresh_dir(dir.path(), "dest");
+
+    let (result, recorder) = extract(&archive, &dest, ExtractOptions::default());
+    let report = result.unwrap();
+    assert_eq!(report.errors, 5, "{:?}", recorder.failed);
+    assert_eq!(report.created, vec![dest.join("ok.txt")]);
+    assert_eq!(fs::read(dest.join("ok.txt")).unwrap(), b"fine");
+    assert!(!dir.path().join("evil.txt").exists());
+    assert!(!dir.path().join("evil2.txt").exists());
+    assert!(!dir.path().join("backslash.txt").exists());
+    assert!(recorder.failed[0].1.contains("climbs out"));
+    assert!(recorder.failed[2].1.contains("absolute"));
+}
+
+#[test]
+fn test_extract_rejects_escaping_symlinks_and_writes_through_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let outside = fresh_dir(dir.path(), "outside");
+    let archive = dir.path().join("evil.tar");
+    let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+    raw_tar_entry(
+        &mut builder,
+        "up",
+        tar::EntryType::Symlink,
+        "../outside",
+        b"",
+    );
+    raw_tar_entry(&mut builder, "abs", tar::EntryType::Symlink, "/etc", b"");
+    raw_tar_entry(&mut builder, "sub/", tar::EntryType::Directory, "", b"");
+    raw_tar_entry(&mut builder, "inner", tar::EntryType::Symlink, "sub", b"");
+    raw_tar_entry(
+        &mut builder,
+        "inner/x",
+        tar::EntryType::Regular,
+        "",
+        b"data",
+    );
+    raw_tar_entry(&mut builder, "hard", tar::EntryType::Link, "sub", b"");
+    builder.finish().unwrap();
+
+    let dest = fresh_dir(dir.path(), "dest");
+    // Planted before extraction, pointing outside
+    std::os::unix::fs::symlink(&outside, dest.join("planted")).unwrap();
+    let mut planted = tar::Builder::new(File::create(dir.path().join("p.tar")).unwrap());
+    raw_tar_entry(
+        &mut planted,
+        "planted/x",
+        tar::EntryType::Regular,
+        "",
+        b"data",
+    );
+    planted.finish().unwrap();
+
+    let (result, recorder) = extract(&archive, &dest, ExtractOptions::default());
+    let report = result.unwrap();
+    assert_eq!(report.errors, 4, "{:?}", recorder.failed);
+    assert!(!dest.join("up").exists() && !dest.join("abs").exists());
+    // A symlink inside the destination is fine, writing through it is not
+    assert_eq!(fs::read_link(dest.join("inner")).unwrap(), Path::new("sub"));
+    assert!(!dest.join("sub/x").exists());
+    assert!(recorder.failed[2].1.contains("is a symlink"));
+    assert!(recorder.failed[3].1.contains("unsupported"));
+
+    let (result, recorder) = extract(&dir.path().join("p.tar"), &dest, ExtractOptions::default());
+    assert_eq!(result.unwrap().errors, 1);
+    assert!(recorder.failed[0].1.contains("is a symlink"));
+    assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+}
+
+#[test]
+fn test_extract_strips_special_mode_bits() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = fresh_dir(dir.path(), "src");
+    fs::write(src.join("tool"), b"#!/bin/sh").unwrap();
+    fs::set_permissions(src.join("tool"), fs::Permissions::from_mode(0o4755)).unwrap();
+    let archive = dir.path().join("out.tar");
+    compress(
+        vec![src.join("tool")],
+        &archive,
+        ArchiveOptions::new(ArchiveFormat::Tar),
+    );
+
+    let dest = fresh_dir(dir.path(), "dest");
+    let (result, _) = extract(&archive, &dest, ExtractOptions::default());
+    assert_eq!(result.unwrap().errors, 0);
+    assert_eq!(mode(&dest.join("tool")), 0o755);
+}
+
+#[test]
+fn test_extract_limits_stop_archive_bombs() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = fresh_dir(dir.path(), "src");
+    fs::write(src.join("zeros"), vec![0u8; 4 * 1024 * 1024]).unwrap();
+    fs::write(src.join("small"), b"small").unwrap();
+    let archive = dir.path().join("bomb.tar.zst");
+    compress(
+        vec![src.join("small"), src.join("zeros")],
+        &archive,
+        ArchiveOptions::new(ArchiveFormat::TarZst),
+    );
+    let limited = |limits: ExtractLimits| ExtractOptions {
+        limits,
+        ..Default::default()
+    };
+
+    let dest = fresh_dir(dir.path(), "ratio");
+    let (result, _) = extract(
+        &archive,
+        &dest,
+        limited(ExtractLimits {
+            max_ratio: 100,
+            ..Default::default()
+        }),
+    );
+    assert!(matches!(result, Err(CoreError::Refused(m)) if m.contains("times its size")));
+    // What was complete stays, the refused file leaves nothing behind
+    assert_eq!(fs::read(dest.join("small")).unwrap(), b"small");
+    assert!(!dest.join("zeros").exists());
+    assert!(part_files(&dest).is_empty());
+
+    let dest = fresh_dir(dir.path(), "bytes");
+    let (result, _) = extract(
+        &archive,
+        &dest,
+        limited(ExtractLimits {
+            max_bytes: 1024,
+            ..Default::default()
+        }),
+    );
+    assert!(matches!(result, Err(CoreError::Refused(m)) if m.contains("bytes")));
+
+    let dest = fresh_dir(dir.path(), "entries");
+    let (result, _) = extract(
+        &archive,
+        &dest,
+        limited(ExtractLimits {
+            max_entries: 1,
+            ..Default::default()
+        }),
+    );
+    assert!(matches!(result, Err(CoreError::Refused(m)) if m.contains("entries")));
+
+    // Zeros compress far beyond the default ratio
+    let dest = fresh_dir(dir.path(), "unlimited");
+    let (result, _) = extract(
+        &archive,
+        &dest,
+        limited(ExtractLimits {
+            max_ratio: u64::MAX,
+            ..Default::default()
+        }),
+    );
+    assert_eq!(result.unwrap().errors, 0);
+    assert_eq!(
+        fs::metadata(dest.join("zeros")).unwrap().len(),
+        4 * 1024 * 1024
+    );
+}
+
+// ===== Conflict Tests =====
+
+#[test]
+fn test_extract_conflicts_follow_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let archive = dir.path().join("out.zip");
+    compress(vec![src], &archive, ArchiveOptions::new(ArchiveFormat::Zip));
+    let dest = fresh_dir(dir.path(), "dest");
+    fs::create_dir(dest.join("src")).unwrap();
+    fs::write(dest.join("src/a.txt"), b"old").unwrap();
+    fs::write(dest.join("src/mine"), b"mine").unwrap();
+
+    // Nobody to ask: the file conflict fails
+    let policy = ConflictPolicy {
+        files: None,
+        directories: Some(ConflictResolution::Merge),
+    };
+    let (result, recorder) = extract(
+        &archive,
+        &dest,
+        ExtractOptions {
+            conflict: policy,
+            ..Default::default()
+        },
+    );
+    assert_eq!(result.unwrap().errors, 1);
+    assert!(recorder.failed[0].1.contains("already exists"));
+    assert_eq!(fs::read(dest.join("src/a.txt")).unwrap(), b"old");
+
+    // Merge into the directory, replacing files
+    let policy = ConflictPolicy {
+        files: Some(ConflictResolution::Overwrite),
+        directories: Some(ConflictResolution::Merge),
+    };
+    let (result, _) = extract(
+        &archive,
+        &dest,
+        ExtractOptions {
+            conflict: policy,
+            ..Default::default()
+        },
+    );
+    assert_eq!(result.unwrap().errors, 0);
+    assert_tree(&dest.join("src"));
+    assert_eq!(fs::read(dest.join("src/mine")).unwrap(), b"mine");
+
+    // Keep both: the whole tree lands next to the existing one
+    let (result, _) = extract(
+        &archive,
+        &dest,
+        ExtractOptions {
+            conflict: ConflictPolicy::always(ConflictResolution::KeepBoth),
+            ..Default::default()
+        },
+    );
+    let report = result.unwrap();
+    assert_eq!(report.created, vec![dest.join("src (2)")]);
+    assert_tree(&dest.join("src (2)"));
+    assert!(!dest.join("src (2)/mine").exists());
+
+    // Skip: nothing below the existing directory is touched
+    fs::write(dest.join("src/a.txt"), b"changed").unwrap();
+    let (result, _) = extract(
+        &archive,
+        &dest,
+        ExtractOptions {
+            conflict: ConflictPolicy::always(ConflictResolution::Skip),
+            ..Default::default()
+        },
+    );
+    let report = result.unwrap();
+    assert_eq!(report.skipped, 1);
+    assert_eq!(fs::read(dest.join("src/a.txt")).unwrap(), b"changed");
+}
+
+#[test]
+fn test_compress_existing_target_follows_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let archive = dir.path().join("out.zip");
+    fs::write(&archive, b"old").unwrap();
+
+    let mut options = ArchiveOptions::new(ArchiveFormat::Zip);
+    let mut recorder = Recorder::default();
+    let result = Compress::new(vec![src.clone()], archive.clone(), options)
+        .run(&JobControl::new(), &mut recorder);
+    assert!(matches!(result, Err(CoreError::InvalidPath(m)) if m.contains("already exists")));
+
+    options.conflict = ConflictPolicy::always(ConflictResolution::Skip);
+    let report = compress(vec![src.clone()], &archive, options);
+    assert_eq!((report.skipped, report.created.len()), (1, 0));
+    assert_eq!(fs::read(&archive).unwrap(), b"old");
+
+    options.conflict = ConflictPolicy::always(ConflictResolution::KeepBoth);
+    let report = compress(vec![src.clone()], &archive, options);
+    assert_eq!(report.created, vec![dir.path().join("out (2).zip")]);
+
+    options.conflict = ConflictPolicy::always(ConflictResolution::Overwrite);
+    compress(vec![src], &archive, options);
+    assert_ne!(fs::read(&archive).unwrap(), b"old");
+}
+
+#[test]
+fn test_compress_cancelled_leaves_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let archive = dir.path().join("out.zip");
+    let control = JobControl::new();
+    control.cancel();
+
+    let result = Compress::new(
+        vec![src],
+        archive.clone(),
+        ArchiveOptions::new(ArchiveFormat::Zip),
+    )
+    .run(&control, &mut Recorder::default());
+    assert!(matches!(result, Err(CoreError::Cancelled)));
+    assert!(!archive.exists());
+    assert!(part_files(dir.path()).is_empty());
+}
+
+// ===== Operations Tests =====
+
+#[tokio::test]
+async fn test_operations_compress_and_extract_jobs() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let h = spawn_operations(|ops| ops);
+    let (registry, session) = (&h.registry, h.session);
+
+    h.commands
+        .send(OpCommand::Compress {
+            sources: vec![registry.clone().register(src.clone())],
+            destination: registry.clone().register(dir.path().to_path_buf()),
+            name: "backup".to_string(),
+            options: ArchiveOptions::new(ArchiveFormat::TarZst),
+            session,
+        })
+        .unwrap();
+    let archive = dir.path().join("backup.tar.zst");
+    match h.finished().await {
+        (
+            _,
+            JobOutcome::Completed,
+            Event::OperationComplete {
+                operation: OperationKind::Compress,
+                success: true,
+                affected,
+                ..
+            },
+        ) => assert_eq!(registry.resolve(affected[0]), Some(archive.clone())),
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+
+    let dest = fresh_dir(dir.path(), "dest");
+    h.commands
+        .send(OpCommand::Extract {
+            archive: registry.clone().register(archive),
+            destination: registry.clone().register(dest.clone()),
+            options: ExtractOptions::default(),
+            session,
+        })
+        .unwrap();
+    match h.finished().await {
+        (
+            _,
+            JobOutcome::Completed,
+            Event::OperationComplete {
+                operation: OperationKind::Extract,
+                success: true,
+                ..
+            },
+        ) => {}
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+    assert_tree(&dest.join("src"));
+
+    h.commands
+        .send(OpCommand::Compress {
+            sources: vec![registry.clone().register(src)],
+            destination: registry.clone().register(dest),
+            name: "../escape".to_string(),
+            options: ArchiveOptions::new(ArchiveFormat::Zip),
+            session,
+        })
+        .unwrap();
+    let event = h.next().await;
+    assert!(matches!(event, Event::Error { .. }), "{event:?}");
+}
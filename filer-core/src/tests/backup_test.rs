@@ -0,0 +1,517 @@
+//! Tests for deduplicated backups, snapshots and retention
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::actors::operations::OpCommand;
+use crate::api::events::{Event, JobOutcome, OperationKind};
+use crate::errors::CoreError;
+use crate::services::backup::{
+    Backup, BackupReport, ChunkerConfig, Repository, RetentionPolicy, Snapshot,
+};
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+use crate::tests::common::{local, spawn_operations, write};
+use crate::vfs::provider::FsProvider;
+use crate::vfs::snapshot::SnapshotFs;
+
+#[derive(Default)]
+struct Recorder {
+    failed: Vec<PathBuf>,
+}
+
+impl TransferObserver for Recorder {
+    fn progress(&mut self, _tracker: &ProgressTracker, _current: &Path) {}
+
+    fn failed(&mut self, path: &Path, _error: &CoreError) {
+        self.failed.push(path.to_path_buf());
+    }
+}
+
+/// Small chunks so a few kilobytes span many of them
+fn chunker() -> ChunkerConfig {
+    ChunkerConfig {
+        min_size: 256,
+        avg_size: 1024,
+        max_size: 4096,
+    }
+}
+
+/// Deterministic bytes that do not compress
+fn noise(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed * 2 + 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn at(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+}
+
+/// A documents folder and the repository it is backed up to
+struct Fixture {
+    _dir: tempfile::TempDir,
+    docs: PathBuf,
+    repo: PathBuf,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("docs");
+        write(&docs.join("report.bin"), noise(1, 40_000), at(0));
+        write(&docs.join("notes/todo.txt"), b"buy milk\n", at(0));
+        fs::create_dir_all(docs.join("empty")).unwrap();
+        Self {
+            repo: dir.path().join("repo"),
+            docs,
+            _dir: dir,
+        }
+    }
+
+    async fn repository(&self) -> Repository {
+        Repository::new(local(), self.repo.clone())
+            .with_chunker(chunker())
+            .open_or_init()
+            .await
+            .unwrap()
+    }
+
+    async fn backup(&self) -> BackupReport {
+        let backup = Backup::new(local(), self.repository().await).with_read_size(3000);
+        let mut recorder = Recorder::default();
+        let report = backup
+            .run(
+                std::slice::from_ref(&self.docs),
+                &JobControl::new(),
+                &mut recorder,
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.errors, 0, "{:?}", recorder.failed);
+        report
+    }
+}
+
+#[tokio::test]
+async fn test_backup_snapshot_and_incremental_run() {
+    let fx = Fixture::new();
+    let first = fx.backup().await;
+    assert_eq!((first.files, first.dirs, first.unchanged), (2, 3, 0));
+    assert_eq!(first.bytes, 40_009);
+    assert_eq!(first.bytes_read, 40_009);
+    assert!(first.new_chunks > 10, "{first:?}");
+
+    let second = fx.backup().await;
+    assert_eq!((second.files, second.unchanged), (2, 2));
+    assert_eq!((second.bytes_read, second.new_chunks), (0, 0));
+    assert_ne!(first.snapshot, second.snapshot);
+
+    let repository = fx.repository().await;
+    let snapshot = repository.snapshot(&second.snapshot).await.unwrap();
+    assert_eq!(snapshot.parent.as_deref(), Some(first.snapshot.as_str()));
+    let paths: Vec<&Path> = snapshot.entries.iter().map(|e| e.path.as_path()).collect();
+    assert_eq!(
+        paths,
+        [
+            Path::new("docs"),
+            Path::new("docs/empty"),
+            Path::new("docs/notes"),
+            Path::new("docs/notes/todo.txt"),
+            Path::new("docs/report.bin"),
+        ]
+    );
+    let todo = snapshot.entry(Path::new("docs/notes/todo.txt")).unwrap();
+    assert_eq!(todo.modified, Some(at(0)));
+    assert_eq!(repository.snapshots().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_backup_stores_shifted_content_once() {
+    let fx = Fixture::new();
+    let first = fx.backup().await;
+
+    // Content-defined boundaries resynchronise after an insertion
+    let mut edited = b"a new first line\n".to_vec();
+    edited.extend(noise(1, 40_000));
+    write(&fx.docs.join("report.bin"), &edited, at(100));
+    // A second copy of known content costs nothing
+    write(&fx.docs.join("copy.bin"), noise(1, 40_000), at(100));
+
+    let second = fx.backup().await;
+    assert_eq!(second.unchanged, 1);
+    assert_eq!(second.bytes_read, 80_017);
+    assert!(
+        second.new_chunks <= 2,
+        "{} new of {} chunks",
+        second.new_chunks,
+        first.new_chunks
+    );
+}
+
+#[tokio::test]
+async fn test_snapshot_fs_browses_past_versions() {
+    let fx = Fixture::new();
+    let first = fx.backup().await;
+    write(&fx.docs.join("notes/todo.txt"), b"nothing\n", at(100));
+    let second = fx.backup().await;
+
+    let fs = SnapshotFs::new(fx.repository().await);
+    let ids: Vec<String> = fs
+        .list(Path::new("/"))
+        .await
+        .unwrap()
+        .into_iter()
+        .inspect(|node| assert!(node.is_dir()))
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(ids, [first.snapshot.clone(), second.snapshot.clone()]);
+
+    let old = Path::new("/").join(&first.snapshot);
+    let top = fs.list(&old).await.unwrap();
+    assert_eq!(top.len(), 1);
+    assert_eq!(top[0].path, old.join("docs"));
+    let mut names: Vec<String> = fs
+        .list(&old.join("docs"))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    names.sort();
+    assert_eq!(names, ["empty", "notes", "report.bin"]);
+
+    let todo = old.join("docs/notes/todo.txt");
+    assert_eq!(fs.read(&todo).await.unwrap(), b"buy milk\n");
+    let new_todo = Path::new("/")
+        .join(&second.snapshot)
+        .join("docs/notes/todo.txt");
+    assert_eq!(fs.read(&new_todo).await.unwrap(), b"nothing\n");
+
+    let node = fs.metadata(&old.join("docs/report.bin")).await.unwrap();
+    assert!(node.is_file() && node.meta.readonly);
+    assert_eq!((node.size, node.modified), (40_000, Some(at(0))));
+    let range = fs
+        .read_range(&old.join("docs/report.bin"), 1000, 9000)
+        .await
+        .unwrap();
+    assert_eq!(range, noise(1, 40_000)[1000..10_000]);
+    let tail = fs
+        .read_range(&old.join("docs/report.bin"), 39_990, 100)
+        .await
+        .unwrap();
+    assert_eq!(tail.len(), 10);
+
+    assert!(!fs.exists(&old.join("docs/missing")).await.unwrap());
+    assert!(fs.exists(&old.join("docs/empty")).await.unwrap());
+    assert!(matches!(
+        fs.read(&old.join("docs/../../etc")).await,
+        Err(CoreError::NotFound(_))
+    ));
+    assert!(matches!(
+        fs.list(&todo).await,
+        Err(CoreError::InvalidPath(_))
+    ));
+    assert!(matches!(
+        fs.list(Path::new("/no-such-snapshot")).await,
+        Err(CoreError::NotFound(_))
+    ));
+}
+
+fn snapshot(id: &str, day: u64, hour: u64) -> Snapshot {
+    Snapshot {
+        id: id.to_string(),
+        // Midday, so the date is the same in every time zone up to ±11h
+        time: SystemTime::UNIX_EPOCH
+            + Duration::from_secs(1_704_110_400 + day * 86_400 + hour * 60),
+        sources: Vec::new(),
+        parent: None,
+        entries: Vec::new(),
+    }
+}
+
+#[test]
+fn test_retention_policy_selection() {
+    // 2024-01-01 (a Monday) onwards
+    let snapshots = vec![
+        snapshot("jan01-a", 0, 0),
+        snapshot("jan01-b", 0, 1),
+        snapshot("jan02", 1, 0),
+        snapshot("jan08", 7, 0),
+        snapshot("jan09", 8, 0),
+        snapshot("feb01", 31, 0),
+        snapshot("feb02-a", 32, 0),
+        snapshot("feb02-b", 32, 1),
+    ];
+    let keep = |policy: RetentionPolicy| {
+        let mut kept: Vec<String> = policy.select(&snapshots).into_iter().collect();
+        kept.sort();
+        kept
+    };
+
+    let last = RetentionPolicy {
+        keep_last: 2,
+        ..Default::default()
+    };
+    assert_eq!(keep(last), ["feb02-a", "feb02-b"]);
+    let daily = RetentionPolicy {
+        keep_daily: 3,
+        ..Default::default()
+    };
+    assert_eq!(keep(daily), ["feb01", "feb02-b", "jan09"]);
+    let weekly = RetentionPolicy {
+        keep_weekly: 3,
+        ..Default::default()
+    };
+    assert_eq!(keep(weekly), ["feb02-b", "jan02", "jan09"]);
+    let monthly = RetentionPolicy {
+        keep_monthly: 5,
+        ..Default::default()
+    };
+    assert_eq!(keep(monthly), ["feb02-b", "jan09"]);
+    let combined = RetentionPolicy {
+        keep_last: 1,
+        keep_monthly: 2,
+        ..Default::default()
+    };
+    assert_eq!(keep(combined), ["feb02-b", "jan09"]);
+    assert!(RetentionPolicy::default().keeps_nothing());
+}
+
+#[tokio::test]
+async fn test_prune_removes_snapshots_and_unused_chunks() {
+    let fx = Fixture::new();
+    let first = fx.backup().await;
+    write(&fx.docs.join("report.bin"), noise(2, 40_000), at(100));
+    let second = fx.backup().await;
+    write(&fx.docs.join("report.bin"), noise(3, 40_000), at(200));
+    let third = fx.backup().await;
+
+    let repository = fx.repository().await;
+    let control = JobControl::new();
+    let policy = RetentionPolicy {
+        keep_last: 1,
+        ..Default::default()
+    };
+    let dry = repository
+        .prune(&policy, true, &control, &mut Recorder::default())
+        .await
+        .unwrap();
+    assert!(dry.dry_run);
+    assert_eq!(dry.removed.len(), 2);
+    assert_eq!(repository.snapshots().await.unwrap().len(), 3);
+
+    let report = repository
+        .prune(&policy, false, &control, &mut Recorder::default())
+        .await
+        .unwrap();
+    assert_eq!(report.kept, vec![third.snapshot.clone()]);
+    assert_eq!(report.removed.len(), 2);
+    assert!(report.removed.contains(&second.snapshot));
+    assert_eq!(report.chunks_removed, dry.chunks_removed);
+    // All but the chunk of the unchanged todo.txt
+    assert_eq!(
+        report.chunks_removed,
+        first.new_chunks - 1 + second.new_chunks
+    );
+
+    let check = repository
+        .check(true, &control, &mut Recorder::default())
+        .await
+        .unwrap();
+    assert!(check.is_ok(), "{check:?}");
+    assert_eq!((check.snapshots, check.unreferenced), (1, 0));
+    let fs = SnapshotFs::new(repository);
+    let path = Path::new("/").join(&third.snapshot).join("docs/report.bin");
+    assert_eq!(fs.read(&path).await.unwrap(), noise(3, 40_000));
+
+    let repository = fx.repository().await;
+    let nothing = repository
+        .prune(
+            &RetentionPolicy::default(),
+            false,
+            &control,
+            &mut Recorder::default(),
+        )
+        .await;
+    assert!(matches!(nothing, Err(CoreError::Refused(_))));
+}
+
+#[tokio::test]
+async fn test_check_finds_missing_and_damaged_chunks() {
+    let fx = Fixture::new();
+    let report = fx.backup().await;
+    let repository = fx.repository().await;
+    let snapshot = repository.snapshot(&report.snapshot).await.unwrap();
+    let chunks = &snapshot.entry(Path::new("docs/report.bin")).unwrap().chunks;
+    let (lost, damaged) = (&chunks[0].id, &chunks[1].id);
+    let chunk_file = |id: &str| fx.repo.join("chunks").join(&id[..2]).join(id);
+    fs::remove_file(chunk_file(lost)).unwrap();
+    fs::write(chunk_file(damaged), b"\0not zstd").unwrap();
+
+    let control = JobControl::new();
+    let quick = repository
+        .check(false, &control, &mut Recorder::default())
+        .await
+        .unwrap();
+    assert_eq!(quick.missing, vec![lost.clone()]);
+    assert!(quick.damaged.is_empty());
+    let full = repository
+        .check(true, &control, &mut Recorder::default())
+        .await
+        .unwrap();
+    assert_eq!(full.missing, vec![lost.clone()]);
+    assert_eq!(full.damaged, vec![damaged.clone()]);
+    assert!(!full.is_ok());
+
+    // The next backup stores the lost chunk again instead of reusing it
+    let again = fx.backup().await;
+    assert_eq!(again.unchanged, 1);
+    let check = fx
+        .repository()
+        .await
+        .check(false, &control, &mut Recorder::default())
+        .await
+        .unwrap();
+    assert!(!check.missing.contains(lost));
+}
+
+#[tokio::test]
+async fn test_repository_open_and_init() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("repo");
+    let missing = Repository::new(local(), root.clone()).open().await;
+    assert!(matches!(missing, Err(CoreError::NotFound(_))));
+
+    let repository = Repository::new(local(), root.clone())
+        .with_chunker(chunker())
+        .init()
+        .await
+        .unwrap();
+    assert!(!repository.is_encrypted());
+    let again = Repository::new(local(), root.clone()).init().await;
+    assert!(matches!(again, Err(CoreError::Refused(_))));
+    // An opened repository keeps the limits it was created with
+    let opened = Repository::new(local(), root.clone()).open().await.unwrap();
+    assert_eq!(opened.chunker(), chunker());
+
+    let invalid = ChunkerConfig {
+        min_size: 4096,
+        avg_size: 1024,
+        max_size: 4096,
+    };
+    let result = Repository::new(local(), dir.path().join("other"))
+        .with_chunker(invalid)
+        .init()
+        .await;
+    assert!(matches!(result, Err(CoreError::InvalidInput)));
+}
+
+#[cfg(not(feature = "crypto"))]
+#[tokio::test]
+async fn test_encrypted_repository_needs_a_key() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("config.json"),
+        br#"{"version":1,"chunker":{"min_size":256,"avg_size":1024,"max_size":4096},"encrypted":true,"key_check":[1,2,3]}"#,
+    )
+    .unwrap();
+    let result = Repository::new(local(), dir.path().to_path_buf())
+        .open()
+        .await;
+    assert!(matches!(result, Err(CoreError::Refused(_))));
+}
+
+#[cfg(feature = "crypto")]
+#[tokio::test]
+async fn test_encrypted_chunk_ids_are_keyed() {
+    use crate::services::crypto::{Cipher, CipherAlgorithm};
+    let dir = tempfile::tempdir().unwrap();
+    let cipher = || Cipher::new(CipherAlgorithm::XChaCha20Poly1305, vec![7; 32]).unwrap();
+    let repository = Repository::new(local(), dir.path().join("repo"))
+        .with_chunker(chunker())
+        .with_cipher(cipher())
+        .init()
+        .await
+        .unwrap();
+    let id = repository.chunk_id(b"alpha");
+    assert_ne!(id, blake3::hash(b"alpha").to_hex().as_str());
+    // The ID key is sealed in the config and survives a reopen
+    let opened = Repository::new(local(), dir.path().join("repo"))
+        .with_cipher(cipher())
+        .open()
+        .await
+        .unwrap();
+    assert_eq!(opened.chunk_id(b"alpha"), id);
+    let other = Repository::new(local(), dir.path().join("other"))
+        .with_cipher(cipher())
+        .init()
+        .await
+        .unwrap();
+    assert_ne!(other.chunk_id(b"alpha"), id);
+}
+
+#[tokio::test]
+async fn test_operations_backup_jobs() {
+    let fx = Fixture::new();
+    let h = spawn_operations(|ops| ops);
+    let docs = h.registry.clone().register(fx.docs.clone());
+    let session = h.session;
+
+    h.commands
+        .send(OpCommand::Backup {
+            nodes: vec![docs],
+            repository: fx.repo.clone(),
+            session,
+        })
+        .unwrap();
+    let report = loop {
+        if let Event::BackupFinished { report, .. } = h.next().await {
+            break report;
+        }
+    };
+    assert_eq!(report.files, 2);
+    assert_eq!(h.finished().await.1, JobOutcome::Completed);
+
+    h.commands
+        .send(OpCommand::CheckBackup {
+            repository: fx.repo.clone(),
+            read_data: true,
+            session,
+        })
+        .unwrap();
+    let check = loop {
+        if let Event::BackupChecked { report, .. } = h.next().await {
+            break report;
+        }
+    };
+    assert!(check.is_ok() && check.snapshots == 1);
+    assert_eq!(h.finished().await.1, JobOutcome::Completed);
+
+    h.commands
+        .send(OpCommand::PruneBackup {
+            repository: fx.repo.clone(),
+            policy: RetentionPolicy::default(),
+            dry_run: false,
+            session,
+        })
+        .unwrap();
+    loop {
+        if let Event::OperationComplete {
+            operation: OperationKind::PruneBackup,
+            success,
+            ..
+        } = h.next().await
+        {
+            assert!(!success);
+            break;
+        }
+    }
+    assert_eq!(fx.repository().await.snapshots().await.unwrap().len(), 1);
+}
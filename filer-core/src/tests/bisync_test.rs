@@ -0,0 +1,568 @@
+//! Tests for two-way sync of folder pairs
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::operations::{OpCommand, Operations};
+use crate::actors::syncer::{Syncer, SyncerCommand, SyncerConfig};
+use crate::api::events::{Event, JobOutcome};
+use crate::errors::CoreError;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::pipeline::config::FilterConfig;
+use crate::services::bisync::{Bisync, BisyncAction, BisyncReport, SyncPair, SyncState};
+use crate::services::compare::Side;
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+use crate::tests::common::{local, next, write};
+
+struct Quiet;
+
+impl TransferObserver for Quiet {
+    fn progress(&mut self, _tracker: &ProgressTracker, _current: &Path) {}
+
+    fn failed(&mut self, _path: &Path, _error: &CoreError) {}
+}
+
+fn at(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+/// A laptop folder on the left, a backup drive on the right
+struct Fixture {
+    _dir: tempfile::TempDir,
+    left: PathBuf,
+    right: PathBuf,
+    state: PathBuf,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let (left, right) = (dir.path().join("laptop"), dir.path().join("backup"));
+        fs::create_dir_all(&left).unwrap();
+        fs::create_dir_all(&right).unwrap();
+        let state = dir.path().join("state/docs.json");
+        Self {
+            _dir: dir,
+            left,
+            right,
+            state,
+        }
+    }
+
+    fn pair(&self) -> SyncPair {
+        SyncPair::new("docs".to_string(), self.left.clone(), self.right.clone())
+    }
+
+    async fn sync(&self, pair: SyncPair, dry_run: bool) -> Result<BisyncReport, CoreError> {
+        let state = SyncState::open(self.state.clone(), &pair).unwrap();
+        Bisync::new(pair, local(), local(), state)
+            .with_dry_run(dry_run)
+            .run(&JobControl::new(), &mut Quiet)
+            .await
+    }
+
+    async fn run(&self) -> BisyncReport {
+        let report = self.sync(self.pair(), false).await.unwrap();
+        assert_eq!(report.errors, 0, "{report:?}");
+        report
+    }
+}
+
+fn actions(report: &BisyncReport) -> Vec<(String, BisyncAction)> {
+    report
+        .results
+        .iter()
+        .map(|r| (r.change.path.display().to_string(), r.change.action.clone()))
+        .collect()
+}
+
+fn conflict_copies(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.contains("(conflict "))
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn test_bisync_first_run_merges_both_sides() {
+    let fx = Fixture::new();
+    write(&fx.left.join("notes.txt"), "notes", at(0));
+    write(&fx.left.join("photos/cat.jpg"), "cat", at(0));
+    write(&fx.right.join("taxes.pdf"), "taxes", at(0));
+    write(&fx.left.join("same.txt"), "same", at(0));
+    write(&fx.right.join("same.txt"), "same", at(1));
+
+    let report = fx.run().await;
+    assert_eq!(
+        actions(&report),
+        vec![
+            (
+                "notes.txt".to_string(),
+                BisyncAction::Copy { to: Side::Right }
+            ),
+            (
+                "photos".to_string(),
+                BisyncAction::CreateDir { on: Side::Right }
+            ),
+            (
+                "photos/cat.jpg".to_string(),
+                BisyncAction::Copy { to: Side::Right }
+            ),
+            (
+                "taxes.pdf".to_string(),
+                BisyncAction::Copy { to: Side::Left }
+            ),
+        ]
+    );
+    assert_eq!(read(&fx.right.join("photos/cat.jpg")), "cat");
+    assert_eq!(read(&fx.left.join("taxes.pdf")), "taxes");
+    let copied = fs::metadata(fx.right.join("notes.txt")).unwrap();
+    assert_eq!(copied.modified().unwrap(), at(0));
+
+    let state = SyncState::open(fx.state.clone(), &fx.pair()).unwrap();
+    assert_eq!(state.len(), 5);
+    assert!(fx.run().await.results.is_empty());
+}
+
+#[tokio::test]
+async fn test_bisync_carries_edits_and_deletions_over() {
+    let fx = Fixture::new();
+    write(&fx.left.join("notes.txt"), "v1", at(0));
+    write(&fx.left.join("old/a.txt"), "a", at(0));
+    write(&fx.left.join("old/deep/b.txt"), "b", at(0));
+    write(&fx.left.join("draft.txt"), "draft", at(0));
+    fx.run().await;
+
+    write(&fx.left.join("notes.txt"), "v2 from the laptop", at(100));
+    fs::remove_dir_all(fx.left.join("old")).unwrap();
+    fs::remove_file(fx.right.join("draft.txt")).unwrap();
+
+    let report = fx.run().await;
+    assert_eq!(
+        actions(&report),
+        vec![
+            (
+                "draft.txt".to_string(),
+                BisyncAction::Delete { from: Side::Left }
+            ),
+            (
+                "notes.txt".to_string(),
+                BisyncAction::Copy { to: Side::Right }
+            ),
+            (
+                "old".to_string(),
+                BisyncAction::Delete { from: Side::Right }
+            ),
+        ]
+    );
+    assert_eq!(read(&fx.right.join("notes.txt")), "v2 from the laptop");
+    assert!(!fx.left.join("draft.txt").exists());
+    assert!(!fx.right.join("old").exists());
+    let state = SyncState::open(fx.state.clone(), &fx.pair()).unwrap();
+    assert_eq!(state.len(), 1);
+}
+
+#[tokio::test]
+async fn test_bisync_conflicts_keep_both_versions() {
+    let fx = Fixture::new();
+    write(&fx.left.join("plan.txt"), "v1", at(0));
+    write(&fx.left.join("todo.txt"), "v1", at(0));
+    fx.run().await;
+
+    write(&fx.left.join("plan.txt"), "laptop edit", at(100));
+    write(&fx.right.join("plan.txt"), "backup edit, later", at(200));
+    // An edit beats a deletion
+    write(&fx.left.join("todo.txt"), "still needed", at(100));
+    fs::remove_file(fx.right.join("todo.txt")).unwrap();
+
+    let report = fx.run().await;
+    assert_eq!(report.conflicts, 1);
+    assert!(matches!(
+        &report.results[0].change.action,
+        BisyncAction::KeepBoth {
+            loser: Side::Left,
+            ..
+        }
+    ));
+    for side in [&fx.left, &fx.right] {
+        assert_eq!(read(&side.join("plan.txt")), "backup edit, later");
+        let copies = conflict_copies(side);
+        assert_eq!(copies.len(), 1);
+        assert!(copies[0].starts_with("plan (conflict ") && copies[0].ends_with(").txt"));
+        assert_eq!(read(&side.join(&copies[0])), "laptop edit");
+        assert_eq!(read(&side.join("todo.txt")), "still needed");
+    }
+    assert!(fx.run().await.results.is_empty());
+}
+
+#[tokio::test]
+async fn test_bisync_equal_edits_are_no_conflict() {
+    let fx = Fixture::new();
+    write(&fx.left.join("a.txt"), "v1", at(0));
+    fx.run().await;
+
+    write(&fx.left.join("a.txt"), "same fix", at(100));
+    write(&fx.right.join("a.txt"), "same fix", at(500));
+    let report = fx.run().await;
+    assert!(report.results.is_empty());
+    assert_eq!(report.conflicts, 0);
+    assert!(conflict_copies(&fx.left).is_empty());
+}
+
+#[tokio::test]
+async fn test_bisync_directory_kept_when_other_side_added_to_it() {
+    let fx = Fixture::new();
+    write(&fx.left.join("project/readme.md"), "readme", at(0));
+    write(&fx.left.join("project/old.md"), "old", at(0));
+    write(&fx.left.join("index.md"), "index", at(0));
+    fx.run().await;
+
+    fs::remove_dir_all(fx.left.join("project")).unwrap();
+    write(&fx.right.join("project/new.md"), "new", at(100));
+
+    let report = fx.run().await;
+    assert_eq!(
+        actions(&report),
+        vec![
+            (
+                "project".to_string(),
+                BisyncAction::CreateDir { on: Side::Left }
+            ),
+            (
+                "project/new.md".to_string(),
+                BisyncAction::Copy { to: Side::Left }
+            ),
+            (
+                "project/old.md".to_string(),
+                BisyncAction::Delete { from: Side::Right }
+            ),
+            (
+                "project/readme.md".to_string(),
+                BisyncAction::Delete { from: Side::Right }
+            ),
+        ]
+    );
+    assert_eq!(read(&fx.left.join("project/new.md")), "new");
+    assert!(!fx.right.join("project/readme.md").exists());
+}
+
+#[tokio::test]
+async fn test_bisync_replaces_entries_of_another_kind() {
+    let fx = Fixture::new();
+    write(&fx.left.join("data"), "a file", at(0));
+    fx.run().await;
+
+    fs::remove_file(fx.left.join("data")).unwrap();
+    write(&fx.left.join("data/part.txt"), "now a folder", at(100));
+
+    let report = fx.run().await;
+    assert_eq!(
+        actions(&report),
+        vec![
+            (
+                "data".to_string(),
+                BisyncAction::Delete { from: Side::Right }
+            ),
+            (
+                "data".to_string(),
+                BisyncAction::CreateDir { on: Side::Right }
+            ),
+            (
+                "data/part.txt".to_string(),
+                BisyncAction::Copy { to: Side::Right }
+            ),
+        ]
+    );
+    assert_eq!(read(&fx.right.join("data/part.txt")), "now a folder");
+}
+
+#[tokio::test]
+async fn test_bisync_dry_run_changes_nothing() {
+    let fx = Fixture::new();
+    write(&fx.left.join("a.txt"), "a", at(0));
+    write(&fx.right.join("b.txt"), "b", at(0));
+
+    let report = fx.sync(fx.pair(), true).await.unwrap();
+    assert!(report.dry_run);
+    assert_eq!(report.results.len(), 2);
+    assert!(report.results.iter().all(|r| r.error.is_none()));
+    assert!(!fx.right.join("a.txt").exists());
+    assert!(!fx.left.join("b.txt").exists());
+    assert!(!fx.state.exists());
+}
+
+#[tokio::test]
+async fn test_bisync_filter_leaves_excluded_entries_alone() {
+    let fx = Fixture::new();
+    let pair = fx.pair().with_filter(FilterConfig {
+        show_hidden: false,
+        exclude_patterns: vec!["*.tmp".to_string(), "node_modules".to_string()],
+        ..Default::default()
+    });
+    write(&fx.left.join("app.js"), "app", at(0));
+    write(&fx.left.join("main.js"), "main", at(0));
+    write(&fx.left.join("cache.tmp"), "tmp", at(0));
+    write(&fx.left.join(".env"), "secret", at(0));
+    write(&fx.left.join("node_modules/dep/index.js"), "dep", at(0));
+    write(&fx.right.join("only-here.tmp"), "keep me", at(0));
+
+    let report = fx.sync(pair.clone(), false).await.unwrap();
+    assert_eq!(
+        actions(&report),
+        vec![
+            ("app.js".to_string(), BisyncAction::Copy { to: Side::Right }),
+            (
+                "main.js".to_string(),
+                BisyncAction::Copy { to: Side::Right }
+            ),
+        ]
+    );
+    assert!(!fx.right.join("cache.tmp").exists());
+    assert!(!fx.right.join(".env").exists());
+    assert!(!fx.right.join("node_modules").exists());
+
+    // Once excluded, deleting a synced file on one side is not carried over
+    fs::remove_file(fx.left.join("app.js")).unwrap();
+    let report = fx.sync(pair, false).await.unwrap();
+    assert_eq!(
+        actions(&report),
+        vec![(
+            "app.js".to_string(),
+            BisyncAction::Delete { from: Side::Right }
+        )]
+    );
+    assert_eq!(read(&fx.right.join("only-here.tmp")), "keep me");
+}
+
+#[tokio::test]
+async fn test_bisync_refuses_to_propagate_an_emptied_side() {
+    let fx = Fixture::new();
+    write(&fx.left.join("a.txt"), "a", at(0));
+    write(&fx.left.join("b/c.txt"), "c", at(0));
+    fx.run().await;
+
+    fs::remove_dir_all(&fx.right).unwrap();
+    fs::create_dir(&fx.right).unwrap();
+    let result = fx.sync(fx.pair(), false).await;
+    assert!(matches!(result, Err(CoreError::Refused(_))));
+    assert_eq!(read(&fx.left.join("b/c.txt")), "c");
+}
+
+#[tokio::test]
+async fn test_bisync_resumes_an_interrupted_run() {
+    let fx = Fixture::new();
+    write(&fx.left.join("a.txt"), "a", at(0));
+    fx.run().await;
+
+    // A run copied these but stopped before saving the state
+    write(&fx.left.join("a.txt"), "a2", at(100));
+    write(&fx.right.join("a.txt"), "a2", at(100));
+    write(&fx.left.join("b.txt"), "b", at(0));
+    write(&fx.right.join("b.txt"), "b", at(0));
+    write(&fx.left.join("c.txt"), "not copied yet", at(0));
+
+    let report = fx.run().await;
+    assert_eq!(
+        actions(&report),
+        vec![("c.txt".to_string(), BisyncAction::Copy { to: Side::Right })]
+    );
+    let state = SyncState::open(fx.state.clone(), &fx.pair()).unwrap();
+    assert_eq!(state.len(), 3);
+}
+
+#[tokio::test]
+async fn test_bisync_state_is_per_pair_of_roots() {
+    let fx = Fixture::new();
+    write(&fx.left.join("a.txt"), "a", at(0));
+    fx.run().await;
+    assert_eq!(
+        SyncState::open(fx.state.clone(), &fx.pair()).unwrap().len(),
+        1
+    );
+
+    let moved = SyncPair::new(
+        "docs".to_string(),
+        fx.left.clone(),
+        fx.left.join("elsewhere"),
+    );
+    assert!(
+        SyncState::open(fx.state.clone(), &moved)
+            .unwrap()
+            .is_empty()
+    );
+    assert!(matches!(
+        SyncState::path_in(Path::new("/tmp"), "../escape"),
+        Err(CoreError::InvalidPath(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_operations_bisync_job() {
+    let fx = Fixture::new();
+    write(&fx.left.join("a.txt"), "a", at(0));
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, events) = flume::unbounded();
+    let (syncer_tx, syncer) = flume::unbounded();
+    let states = fx.state.parent().unwrap().to_path_buf();
+    tokio::spawn(
+        Operations::new(cmd_rx, evt_tx, NodeRegistry::new())
+            .with_sync_states(states)
+            .with_syncer(syncer_tx)
+            .run(),
+    );
+    let session = SessionId::new();
+
+    cmd_tx
+        .send(OpCommand::Bisync {
+            pair: fx.pair(),
+            dry_run: false,
+            session,
+        })
+        .unwrap();
+    let (pair, report) = loop {
+        if let Event::BisyncFinished { pair, report, .. } = next(&events).await {
+            break (pair, report);
+        }
+    };
+    assert_eq!(pair, "docs");
+    assert_eq!(report.results.len(), 1);
+    loop {
+        if let Event::JobFinished { outcome, .. } = next(&events).await {
+            assert_eq!(outcome, JobOutcome::Completed);
+            break;
+        }
+    }
+    assert_eq!(read(&fx.right.join("a.txt")), "a");
+    assert!(fx.state.exists());
+    assert!(matches!(
+        syncer.try_recv(),
+        Ok(SyncerCommand::Finished(name)) if name == "docs"
+    ));
+}
+
+async fn next_op(ops: &flume::Receiver<OpCommand>) -> OpCommand {
+    timeout(Duration::from_secs(5), ops.recv_async())
+        .await
+        .expect("timed out waiting for a sync")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_syncer_triggers() {
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, events) = flume::unbounded();
+    let (ops_tx, ops) = flume::unbounded();
+    let syncer = Syncer::new(cmd_rx, evt_tx, ops_tx).with_config(SyncerConfig {
+        settle: Duration::from_millis(50),
+    });
+    tokio::spawn(syncer.run());
+
+    let watched =
+        SyncPair::new("watched".to_string(), "/data/a".into(), "/data/b".into()).with_watch(true);
+    let timed = SyncPair::new("timed".to_string(), "/srv/a".into(), "/srv/b".into())
+        .with_interval(Duration::from_millis(100));
+    cmd_tx.send(SyncerCommand::Add(Box::new(watched))).unwrap();
+    cmd_tx.send(SyncerCommand::Add(Box::new(timed))).unwrap();
+
+    // Changes outside any watched root are ignored
+    cmd_tx
+        .send(SyncerCommand::Changed("/srv/a/x.txt".into()))
+        .unwrap();
+    cmd_tx
+        .send(SyncerCommand::Changed("/data/b/notes.txt".into()))
+        .unwrap();
+    let OpCommand::Bisync {
+        pair,
+        dry_run,
+        session,
+    } = next_op(&ops).await
+    else {
+        panic!("expected a sync");
+    };
+    assert_eq!((pair.name.as_str(), dry_run), ("watched", false));
+    assert_eq!(session, SessionId::DEFAULT);
+    let OpCommand::Bisync { pair, .. } = next_op(&ops).await else {
+        panic!("expected a sync");
+    };
+    assert_eq!(pair.name, "timed");
+
+    cmd_tx
+        .send(SyncerCommand::Remove("timed".to_string()))
+        .unwrap();
+    let session = SessionId::new();
+    cmd_tx
+        .send(SyncerCommand::Run {
+            name: "watched".to_string(),
+            dry_run: true,
+            session,
+        })
+        .unwrap();
+    let OpCommand::Bisync {
+        pair,
+        dry_run,
+        session: by,
+    } = next_op(&ops).await
+    else {
+        panic!("expected a sync");
+    };
+    assert_eq!(
+        (pair.name.as_str(), dry_run, by),
+        ("watched", true, session)
+    );
+
+    cmd_tx
+        .send(SyncerCommand::Run {
+            name: "timed".to_string(),
+            dry_run: false,
+            session,
+        })
+        .unwrap();
+    assert!(matches!(next(&events).await, Event::Error { .. }));
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(ops.is_empty(), "removed pairs are not synced");
+}
+
+#[tokio::test]
+async fn test_syncer_ignores_its_own_writes() {
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, _events) = flume::unbounded();
+    let (ops_tx, ops) = flume::unbounded();
+    let settle = Duration::from_millis(50);
+    tokio::spawn(
+        Syncer::new(cmd_rx, evt_tx, ops_tx)
+            .with_config(SyncerConfig { settle })
+            .run(),
+    );
+    let pair =
+        SyncPair::new("docs".to_string(), "/data/a".into(), "/data/b".into()).with_watch(true);
+    cmd_tx.send(SyncerCommand::Add(Box::new(pair))).unwrap();
+    let changed = || SyncerCommand::Changed("/data/b/notes.txt".into());
+
+    cmd_tx.send(changed()).unwrap();
+    assert!(matches!(next_op(&ops).await, OpCommand::Bisync { .. }));
+
+    // Written by the run, or right after it
+    cmd_tx.send(changed()).unwrap();
+    tokio::time::sleep(settle * 3).await;
+    cmd_tx
+        .send(SyncerCommand::Finished("docs".to_string()))
+        .unwrap();
+    cmd_tx.send(changed()).unwrap();
+    tokio::time::sleep(settle * 3).await;
+    assert!(ops.is_empty(), "the run triggered itself");
+
+    cmd_tx.send(changed()).unwrap();
+    assert!(matches!(next_op(&ops).await, OpCommand::Bisync { .. }));
+}
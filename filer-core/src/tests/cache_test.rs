@@ -0,0 +1,273 @@
+//! Tests for the Cache actor and the byte-budgeted LRU
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use flume::Sender;
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::cache::{Cache, CacheCommand, ListingStamp};
+use crate::actors::scanner::{ScanCommand, Scanner};
+use crate::api::events::Event;
+use crate::model::node::{FileNode, NodeId, NodeKind, NodeMeta};
+use crate::model::registry::NodeRegistry;
+use crate::model::session::{SessionId, ViewId};
+use crate::pipeline::PipelineConfig;
+use crate::services::cache::{ApproxSize, LruCache};
+use crate::vfs::local::LocalFs;
+
+fn node(reg: &NodeRegistry, path: &str) -> FileNode {
+    let path = PathBuf::from(path);
+    FileNode {
+        id: reg.clone().register(path.clone()),
+        name: path.file_name().unwrap().to_string_lossy().into_owned(),
+        path,
+        kind: NodeKind::File { extension: None },
+        size: 1,
+        modified: None,
+        created: None,
+        meta: NodeMeta::default(),
+    }
+}
+
+// ===== LruCache Tests =====
+
+#[test]
+fn test_lru_evicts_least_recently_used() {
+    let reg = NodeRegistry::new();
+    let one = node(&reg, "/a").approx_size();
+    let mut lru = LruCache::new(one * 2);
+
+    lru.insert("a", node(&reg, "/a"));
+    lru.insert("b", node(&reg, "/b"));
+    // Touch "a" so "b" is the oldest
+    assert!(lru.get(&"a").is_some());
+    lru.insert("c", node(&reg, "/c"));
+
+    assert!(lru.contains(&"a"));
+    assert!(!lru.contains(&"b"));
+    assert!(lru.contains(&"c"));
+    assert!(lru.used_bytes() <= lru.budget());
+}
+
+#[test]
+fn test_lru_tracks_bytes() {
+    let reg = NodeRegistry::new();
+    let mut lru = LruCache::new(1 << 20);
+    lru.insert(1, node(&reg, "/one"));
+    lru.insert(2, node(&reg, "/two"));
+    let both = lru.used_bytes();
+
+    // Replacing a key does not double count
+    lru.insert(1, node(&reg, "/one"));
+    assert_eq!(lru.used_bytes(), both);
+
+    lru.remove(&1);
+    lru.remove(&2);
+    assert_eq!(lru.used_bytes(), 0);
+    assert!(lru.is_empty());
+}
+
+#[test]
+fn test_lru_rejects_oversized_value() {
+    let reg = NodeRegistry::new();
+    let mut lru = LruCache::new(16);
+    assert!(!lru.insert(1, node(&reg, "/too/big")));
+    assert!(lru.is_empty());
+}
+
+#[test]
+fn test_lru_listing_size_grows_with_entries() {
+    let reg = NodeRegistry::new();
+    let small: Vec<FileNode> = (0..10).map(|i| node(&reg, &format!("/d/{i}"))).collect();
+    let large: Vec<FileNode> = (0..1000).map(|i| node(&reg, &format!("/d/{i}"))).collect();
+    assert!(large.approx_size() > small.approx_size() * 50);
+}
+
+#[test]
+fn test_node_size_counts_tags_and_note() {
+    let reg = NodeRegistry::new();
+    let plain = node(&reg, "/t");
+    let mut tagged = plain.clone();
+    tagged.meta.tags = vec!["work".repeat(100), "urgent".to_string()];
+    tagged.meta.note = Some("n".repeat(1000));
+    assert!(tagged.approx_size() >= plain.approx_size() + 1400);
+}
+
+// ===== Cache Actor Tests =====
+
+fn spawn_cache(reg: &NodeRegistry, capacity: usize) -> Sender<CacheCommand> {
+    let (tx, rx) = flume::unbounded();
+    tokio::spawn(Cache::new(rx, reg.clone(), capacity).run());
+    tx
+}
+
+async fn get(cache: &Sender<CacheCommand>, id: NodeId) -> Option<FileNode> {
+    let (tx, rx) = flume::bounded(1);
+    cache.send(CacheCommand::Get(id, tx)).unwrap();
+    rx.recv_async().await.unwrap()
+}
+
+async fn get_listing(cache: &Sender<CacheCommand>, id: NodeId) -> Option<Vec<FileNode>> {
+    let (tx, rx) = flume::bounded(1);
+    cache.send(CacheCommand::GetListing(id, tx)).unwrap();
+    rx.recv_async().await.unwrap().map(|(entries, _)| entries)
+}
+
+#[tokio::test]
+async fn test_cache_get_replies() {
+    let reg = NodeRegistry::new();
+    let cache = spawn_cache(&reg, 1 << 20);
+    let file = node(&reg, "/dir/file");
+
+    assert!(get(&cache, file.id).await.is_none());
+    cache.send(CacheCommand::Store(file.clone())).unwrap();
+    assert_eq!(get(&cache, file.id).await.unwrap().path, file.path);
+
+    cache.send(CacheCommand::Clear).unwrap();
+    assert!(get(&cache, file.id).await.is_none());
+}
+
+#[tokio::test]
+async fn test_cache_invalidate_drops_parent_listing() {
+    let reg = NodeRegistry::new();
+    let cache = spawn_cache(&reg, 1 << 20);
+    let dir = reg.clone().register(PathBuf::from("/dir"));
+    let other = reg.clone().register(PathBuf::from("/other"));
+    let file = node(&reg, "/dir/file");
+
+    cache
+        .send(CacheCommand::StoreListing {
+            path: PathBuf::from("/dir"),
+            entries: vec![file.clone()],
+            stamp: ListingStamp::default(),
+        })
+        .unwrap();
+    cache
+        .send(CacheCommand::StoreListing {
+            path: PathBuf::from("/other"),
+            entries: vec![],
+            stamp: ListingStamp::default(),
+        })
+        .unwrap();
+    assert_eq!(get_listing(&cache, dir).await.unwrap().len(), 1);
+
+    cache.send(CacheCommand::Invalidate(file.id)).unwrap();
+    assert!(get_listing(&cache, dir).await.is_none());
+    assert!(get_listing(&cache, other).await.is_some());
+}
+
+#[tokio::test]
+async fn test_cache_invalidate_directory_drops_subtree() {
+    let reg = NodeRegistry::new();
+    let cache = spawn_cache(&reg, 1 << 20);
+    let dir = reg.clone().register(PathBuf::from("/dir"));
+    let sub = reg.clone().register(PathBuf::from("/dir/sub"));
+    let deep = node(&reg, "/dir/sub/deep");
+
+    cache
+        .send(CacheCommand::StoreListing {
+            path: PathBuf::from("/dir/sub"),
+            entries: vec![deep.clone()],
+            stamp: ListingStamp::default(),
+        })
+        .unwrap();
+    cache.send(CacheCommand::Store(deep.clone())).unwrap();
+
+    cache.send(CacheCommand::Invalidate(dir)).unwrap();
+    assert!(get_listing(&cache, sub).await.is_none());
+    assert!(get(&cache, deep.id).await.is_none());
+}
+
+#[tokio::test]
+async fn test_cache_invalidate_directory_keeps_siblings() {
+    let reg = NodeRegistry::new();
+    let cache = spawn_cache(&reg, 1 << 20);
+    let dir = reg.clone().register(PathBuf::from("/dir"));
+    let inside = node(&reg, "/dir/a");
+    let sibling = node(&reg, "/dir-b");
+    let beside = node(&reg, "/dir.txt");
+    for n in [&inside, &sibling, &beside] {
+        cache.send(CacheCommand::Store(n.clone())).unwrap();
+    }
+
+    cache.send(CacheCommand::Invalidate(dir)).unwrap();
+    assert!(get(&cache, inside.id).await.is_none());
+    assert!(get(&cache, sibling.id).await.is_some());
+    assert!(get(&cache, beside.id).await.is_some());
+}
+
+#[tokio::test]
+async fn test_cache_respects_budget() {
+    let reg = NodeRegistry::new();
+    let one = node(&reg, "/n/0").approx_size();
+    let cache = spawn_cache(&reg, one * 10);
+    let nodes: Vec<FileNode> = (0..100).map(|i| node(&reg, &format!("/n/{i}"))).collect();
+    cache.send(CacheCommand::StoreBatch(nodes.clone())).unwrap();
+
+    let mut hits = 0;
+    for n in &nodes {
+        if get(&cache, n.id).await.is_some() {
+            hits += 1;
+        }
+    }
+    assert!(hits <= 10, "{hits} nodes cached");
+    // The most recent ones survive
+    assert!(get(&cache, nodes[99].id).await.is_some());
+}
+
+// ===== Scanner Integration Tests =====
+
+#[tokio::test]
+async fn test_scanner_serves_listing_from_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+
+    let reg = NodeRegistry::new();
+    let cache = spawn_cache(&reg, 1 << 20);
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let scanner = Scanner::new(cmd_rx, evt_tx, Arc::new(LocalFs::new(reg.clone())), reg.clone())
+        .with_cache(cache.clone());
+    tokio::spawn(scanner.run());
+
+    let session = SessionId::new();
+    let node = reg.clone().register(root.clone());
+    let scan = |tx: &Sender<ScanCommand>| {
+        tx.send(ScanCommand::ScanNode {
+            node,
+            session,
+            view: ViewId::main(),
+            pipeline: PipelineConfig::default(),
+        })
+        .unwrap()
+    };
+    let loaded = |ev: Event| match ev {
+        Event::DirectoryLoaded { entries, .. } => entries.iter().map(|e| (e.name.clone(), e.size)).collect::<Vec<_>>(),
+        other => panic!("unexpected event: {other:?}"),
+    };
+    let next = || async { timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap() };
+
+    scan(&cmd_tx);
+    assert_eq!(loaded(next().await), vec![("a.txt".to_string(), 1)]);
+
+    // Rewriting a file leaves the directory as it was: the cached listing
+    // is served
+    fs::write(root.join("a.txt"), b"aa").unwrap();
+    scan(&cmd_tx);
+    assert_eq!(loaded(next().await), vec![("a.txt".to_string(), 1)]);
+
+    // Once the change is reported the provider is asked again
+    cache.send(CacheCommand::Invalidate(reg.clone().register(root.join("a.txt")))).unwrap();
+    scan(&cmd_tx);
+    assert_eq!(loaded(next().await), vec![("a.txt".to_string(), 2)]);
+
+    // A new entry changes the directory, which a re-list notices unreported
+    fs::write(root.join("b.txt"), b"b").unwrap();
+    scan(&cmd_tx);
+    assert_eq!(loaded(next().await).len(), 2);
+}
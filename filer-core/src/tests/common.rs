@@ -0,0 +1,226 @@
+//! Helpers shared by the test modules
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use flume::{Receiver, Sender};
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::navigator::{NavCommand, Navigator};
+use crate::actors::operations::{OpCommand, Operations};
+use crate::actors::scanner::ScanCommand;
+use crate::api::events::{Event, JobOutcome, OperationKind};
+use crate::model::node::NodeId;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::sessions::SessionStore;
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::FsProvider;
+
+/// Temporary directory and its canonical path
+pub fn tempdir() -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    (dir, root)
+}
+
+/// `root/src` with `a.txt`, a 10 kB `sub/b.bin`, an empty `sub/deeper/c`
+/// and `link` pointing at `a.txt`
+pub fn tree(root: &Path) -> PathBuf {
+    let src = root.join("src");
+    fs::create_dir_all(src.join("sub/deeper")).unwrap();
+    fs::write(src.join("a.txt"), b"alpha").unwrap();
+    fs::write(src.join("sub/b.bin"), vec![7u8; 10_000]).unwrap();
+    fs::write(src.join("sub/deeper/c"), b"").unwrap();
+    std::os::unix::fs::symlink("a.txt", src.join("link")).unwrap();
+    src
+}
+
+/// Directories `names` under `root`
+pub fn mkdirs(root: &Path, names: &[&str]) -> Vec<PathBuf> {
+    names
+        .iter()
+        .map(|name| {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            dir
+        })
+        .collect()
+}
+
+/// A local provider with a registry of its own
+pub fn local() -> Arc<dyn FsProvider> {
+    Arc::new(LocalFs::new(NodeRegistry::new()))
+}
+
+/// Write `path` (creating its parents) and set its modification time
+pub fn write(path: &Path, content: impl AsRef<[u8]>, modified: SystemTime) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+/// Next event, failing the test after five seconds
+pub async fn next(events: &Receiver<Event>) -> Event {
+    timeout(Duration::from_secs(5), events.recv_async())
+        .await
+        .expect("timed out waiting for event")
+        .unwrap()
+}
+
+/// A running Operations actor
+pub struct Harness {
+    pub commands: Sender<OpCommand>,
+    pub events: Receiver<Event>,
+    pub registry: NodeRegistry,
+    pub session: SessionId,
+}
+
+/// Spawn the Operations actor `configure` sets up
+pub fn spawn_operations(configure: impl FnOnce(Operations) -> Operations) -> Harness {
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    tokio::spawn(configure(Operations::new(cmd_rx, evt_tx, registry.clone())).run());
+    Harness {
+        commands: cmd_tx,
+        events: evt_rx,
+        registry,
+        session: SessionId::new(),
+    }
+}
+
+impl Harness {
+    pub async fn next(&self) -> Event {
+        next(&self.events).await
+    }
+
+    /// Events up to and including the next `OperationComplete`
+    pub async fn until_complete(&self) -> Vec<Event> {
+        let mut seen = Vec::new();
+        loop {
+            let event = self.next().await;
+            let done = matches!(event, Event::OperationComplete { .. });
+            seen.push(event);
+            if done {
+                return seen;
+            }
+        }
+    }
+
+    /// Send `command`; success of the next completed operation
+    pub async fn complete(&self, command: OpCommand) -> (bool, OperationKind) {
+        self.commands.send(command).unwrap();
+        loop {
+            if let Event::OperationComplete {
+                success, operation, ..
+            } = self.next().await
+            {
+                return (success, operation);
+            }
+        }
+    }
+
+    /// Events of the next job besides its progress, its outcome and the
+    /// final `OperationComplete`
+    pub async fn finished(&self) -> (Vec<Event>, JobOutcome, Event) {
+        let mut seen = Vec::new();
+        let mut outcome = None;
+        loop {
+            match self.next().await {
+                Event::JobFinished { outcome: o, .. } => outcome = Some(o),
+                event @ Event::OperationComplete { .. } => {
+                    return (seen, outcome.unwrap(), event);
+                }
+                Event::JobProgress { .. } | Event::JobStarted { .. } => {}
+                event => seen.push(event),
+            }
+        }
+    }
+}
+
+/// A running Navigator and what it sends the scanner and operations
+pub struct NavHarness {
+    pub commands: Sender<NavCommand>,
+    pub events: Receiver<Event>,
+    pub scans: Receiver<ScanCommand>,
+    pub operations: Receiver<OpCommand>,
+    pub registry: NodeRegistry,
+}
+
+/// Spawn a Navigator saving sessions to `store`, connected to operations
+/// if `operations` is set
+pub fn spawn_navigator(store: SessionStore, operations: bool) -> NavHarness {
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let (scan_tx, scan_rx) = flume::unbounded();
+    let (op_tx, op_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    let mut navigator =
+        Navigator::new(cmd_rx, evt_tx, scan_tx, registry.clone()).with_sessions(store);
+    if operations {
+        navigator = navigator.with_operations(op_tx);
+    }
+    tokio::spawn(navigator.run());
+    NavHarness {
+        commands: cmd_tx,
+        events: evt_rx,
+        scans: scan_rx,
+        operations: op_rx,
+        registry,
+    }
+}
+
+impl NavHarness {
+    /// Send a command and give the actor time to handle it
+    pub async fn send(&self, command: NavCommand) {
+        self.commands.send(command).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    pub async fn next_event(&self) -> Event {
+        timeout(Duration::from_secs(1), self.events.recv_async())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    pub async fn next_error(&self) -> String {
+        loop {
+            if let Event::Error { message, .. } = self.next_event().await {
+                return message;
+            }
+        }
+    }
+
+    /// Directory of the next scan
+    pub async fn next_scan(&self) -> PathBuf {
+        let scan = timeout(Duration::from_secs(1), self.scans.recv_async())
+            .await
+            .unwrap()
+            .unwrap();
+        match scan {
+            ScanCommand::ScanNode { node, .. } => self.registry.resolve(node).unwrap(),
+            other => panic!("expected a scan, got {other:?}"),
+        }
+    }
+
+    pub async fn next_operation(&self) -> OpCommand {
+        timeout(Duration::from_secs(1), self.operations.recv_async())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    pub fn node(&self, path: &Path) -> NodeId {
+        self.registry.clone().register(path.to_path_buf())
+    }
+}
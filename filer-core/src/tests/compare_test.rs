@@ -0,0 +1,445 @@
+//! Tests for directory comparison and sync plans
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+
+use crate::actors::Actor;
+use crate::actors::operations::{OpCommand, Operations};
+use crate::api::events::{Event, JobOutcome};
+use crate::errors::CoreError;
+use crate::model::node::FileNode;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::compare::{
+    Compare, CompareEntry, CompareObserver, CompareOptions, CompareReport, CompareStatus,
+    Difference, Side, SyncAction, SyncPlan, Synchronize,
+};
+use crate::services::hash::HashAlgorithm;
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+use crate::tests::common::{local, next, spawn_operations, write};
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::{Capabilities, FsProvider};
+
+#[derive(Default)]
+struct Recorder {
+    batches: Vec<Vec<PathBuf>>,
+    failed: Vec<PathBuf>,
+}
+
+impl TransferObserver for Recorder {
+    fn progress(&mut self, _tracker: &ProgressTracker, _current: &Path) {}
+
+    fn failed(&mut self, path: &Path, _error: &CoreError) {
+        self.failed.push(path.to_path_buf());
+    }
+}
+
+impl CompareObserver for Recorder {
+    fn entries(&mut self, entries: &[CompareEntry]) {
+        self.batches
+            .push(entries.iter().map(|e| e.path.clone()).collect());
+    }
+}
+
+/// Build output on the left, deploy folder on the right
+fn trees(root: &Path) -> (PathBuf, PathBuf) {
+    let (left, right) = (root.join("build"), root.join("deploy"));
+    let t = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let later = t + Duration::from_secs(60);
+    write(&left.join("same.txt"), "same", t);
+    write(&right.join("same.txt"), "same", t + Duration::from_secs(1));
+    write(&left.join("size.txt"), "longer", t);
+    write(&right.join("size.txt"), "short", t);
+    write(&left.join("newer.txt"), "aaaa", later);
+    write(&right.join("newer.txt"), "bbbb", t);
+    write(&left.join("new/inner.txt"), "new", t);
+    write(&right.join("stale.txt"), "old", t);
+    write(&left.join("nested/deep/a.txt"), "a", t);
+    write(&right.join("nested/deep/a.txt"), "a", t);
+    write(&left.join("kind"), "file", t);
+    fs::create_dir_all(right.join("kind")).unwrap();
+    (left, right)
+}
+
+async fn compare(left: &Path, right: &Path, options: CompareOptions) -> CompareReport {
+    Compare::new(local(), left.to_path_buf(), local(), right.to_path_buf())
+        .with_options(options)
+        .run(&JobControl::new(), &mut Recorder::default())
+        .await
+        .unwrap()
+}
+
+fn status(report: &CompareReport, path: &str) -> CompareStatus {
+    report
+        .entries
+        .iter()
+        .find(|e| e.path == Path::new(path))
+        .unwrap_or_else(|| panic!("no entry for {path}"))
+        .status
+}
+
+#[tokio::test]
+async fn test_compare_classifies_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = trees(dir.path());
+    let mut observer = Recorder::default();
+    let report = Compare::new(local(), left.clone(), local(), right.clone())
+        .run(&JobControl::new(), &mut observer)
+        .await
+        .unwrap();
+
+    assert_eq!(status(&report, "same.txt"), CompareStatus::Identical);
+    assert_eq!(
+        status(&report, "size.txt"),
+        CompareStatus::Differs {
+            difference: Difference::Size,
+            newer: None
+        }
+    );
+    assert_eq!(
+        status(&report, "newer.txt"),
+        CompareStatus::Differs {
+            difference: Difference::Modified,
+            newer: Some(Side::Left)
+        }
+    );
+    assert_eq!(status(&report, "new"), CompareStatus::OnlyLeft);
+    assert_eq!(status(&report, "stale.txt"), CompareStatus::OnlyRight);
+    assert_eq!(
+        status(&report, "nested/deep/a.txt"),
+        CompareStatus::Identical
+    );
+    assert!(matches!(
+        status(&report, "kind"),
+        CompareStatus::Differs {
+            difference: Difference::Kind,
+            ..
+        }
+    ));
+    // One-sided directories are not descended into; shared ones are not
+    // reported themselves
+    for path in ["new/inner.txt", "nested", "nested/deep"] {
+        assert!(report.entries.iter().all(|e| e.path != Path::new(path)));
+    }
+
+    let summary = report.summary;
+    assert_eq!(
+        (
+            summary.only_left,
+            summary.only_right,
+            summary.identical,
+            summary.differing
+        ),
+        (1, 1, 2, 3)
+    );
+    assert_eq!(
+        observer.batches,
+        vec![
+            [
+                "kind",
+                "new",
+                "newer.txt",
+                "same.txt",
+                "size.txt",
+                "stale.txt"
+            ]
+            .map(PathBuf::from)
+            .to_vec(),
+            vec![PathBuf::from("nested/deep/a.txt")],
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_compare_tolerance_and_content() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = trees(dir.path());
+
+    let strict = CompareOptions {
+        mtime_tolerance: Duration::ZERO,
+        ..Default::default()
+    };
+    let report = compare(&left, &right, strict).await;
+    assert_eq!(
+        status(&report, "same.txt"),
+        CompareStatus::Differs {
+            difference: Difference::Modified,
+            newer: Some(Side::Right)
+        }
+    );
+
+    let by_content = CompareOptions {
+        content: Some(HashAlgorithm::Xxh3),
+        ..Default::default()
+    };
+    let report = compare(&left, &right, by_content).await;
+    assert_eq!(status(&report, "same.txt"), CompareStatus::Identical);
+    assert_eq!(
+        status(&report, "newer.txt"),
+        CompareStatus::Differs {
+            difference: Difference::Content,
+            newer: Some(Side::Left)
+        }
+    );
+
+    // Same size and time, different bytes: only hashing notices
+    let t = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    write(&left.join("twin"), "left", t);
+    write(&right.join("twin"), "rite", t);
+    assert_eq!(
+        status(
+            &compare(&left, &right, CompareOptions::default()).await,
+            "twin"
+        ),
+        CompareStatus::Identical
+    );
+    assert!(matches!(
+        status(&compare(&left, &right, by_content).await, "twin"),
+        CompareStatus::Differs {
+            difference: Difference::Content,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_compare_rejects_non_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = trees(dir.path());
+    let result = Compare::new(local(), left.join("same.txt"), local(), right)
+        .run(&JobControl::new(), &mut Recorder::default())
+        .await;
+    assert!(matches!(result, Err(CoreError::InvalidPath(_))));
+}
+
+#[tokio::test]
+async fn test_mirror_plan_makes_trees_identical() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = trees(dir.path());
+    let report = compare(&left, &right, CompareOptions::default()).await;
+    let plan = SyncPlan::mirror(&report.entries, Side::Right);
+
+    let actions: Vec<_> = plan
+        .steps
+        .iter()
+        .map(|s| (s.path.to_str().unwrap(), s.action))
+        .collect();
+    let copy = SyncAction::Copy { to: Side::Right };
+    let delete = SyncAction::Delete { from: Side::Right };
+    assert_eq!(
+        actions,
+        vec![
+            ("kind", delete),
+            ("kind", copy),
+            ("new", copy),
+            ("newer.txt", copy),
+            ("size.txt", copy),
+            ("stale.txt", delete),
+        ]
+    );
+
+    let sync = Synchronize::new(local(), left.clone(), local(), right.clone(), plan)
+        .run(&JobControl::new(), &mut Recorder::default())
+        .await
+        .unwrap();
+    assert_eq!(sync.errors, 0, "{:?}", sync.results);
+    assert!(!right.join("stale.txt").exists());
+    assert_eq!(
+        fs::read_to_string(right.join("new/inner.txt")).unwrap(),
+        "new"
+    );
+    assert_eq!(fs::read_to_string(right.join("kind")).unwrap(), "file");
+
+    let again = compare(&left, &right, CompareOptions::default()).await;
+    assert!(
+        again
+            .entries
+            .iter()
+            .all(|e| e.status == CompareStatus::Identical),
+        "{:?}",
+        again.entries
+    );
+}
+
+#[tokio::test]
+async fn test_update_plan_copies_newer_and_never_deletes() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = trees(dir.path());
+    let report = compare(&left, &right, CompareOptions::default()).await;
+
+    let to_left = SyncPlan::update(&report.entries, Side::Left);
+    let paths: Vec<_> = to_left.steps.iter().map(|s| s.path.clone()).collect();
+    assert_eq!(paths, vec![PathBuf::from("stale.txt")]);
+
+    let to_right = SyncPlan::update(&report.entries, Side::Right);
+    let paths: Vec<_> = to_right.steps.iter().map(|s| s.path.clone()).collect();
+    assert_eq!(paths, ["new", "newer.txt"].map(PathBuf::from).to_vec());
+    assert!(
+        to_right
+            .steps
+            .iter()
+            .all(|s| s.action == SyncAction::Copy { to: Side::Right })
+    );
+}
+
+#[tokio::test]
+async fn test_sync_refuses_steps_changed_since_comparison() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = trees(dir.path());
+    let report = compare(&left, &right, CompareOptions::default()).await;
+    let plan = SyncPlan::mirror(&report.entries, Side::Right);
+
+    // Someone edits the extra file and the target before the plan runs
+    fs::write(right.join("stale.txt"), "edited meanwhile").unwrap();
+    fs::write(right.join("size.txt"), "edited meanwhile").unwrap();
+    let mut observer = Recorder::default();
+    let sync = Synchronize::new(local(), left.clone(), local(), right.clone(), plan)
+        .run(&JobControl::new(), &mut observer)
+        .await
+        .unwrap();
+
+    assert_eq!(sync.errors, 2);
+    assert_eq!(
+        observer.failed,
+        vec![right.join("size.txt"), right.join("stale.txt")]
+    );
+    let failed = sync
+        .results
+        .iter()
+        .find(|r| r.step.path == Path::new("stale.txt"))
+        .unwrap();
+    assert!(failed.error.as_deref().unwrap().contains("changed since"));
+    assert_eq!(
+        fs::read_to_string(right.join("stale.txt")).unwrap(),
+        "edited meanwhile"
+    );
+    assert_eq!(fs::read_to_string(right.join("newer.txt")).unwrap(), "aaaa");
+}
+
+#[tokio::test]
+async fn test_operations_compare_and_synchronize() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = trees(dir.path());
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, events) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    tokio::spawn(Operations::new(cmd_rx, evt_tx, registry.clone()).run());
+    let session = SessionId::new();
+    let (left_id, right_id) = (
+        registry.clone().register(left.clone()),
+        registry.clone().register(right.clone()),
+    );
+
+    cmd_tx
+        .send(OpCommand::Compare {
+            left: left_id,
+            right: right_id,
+            options: CompareOptions::default(),
+            session,
+        })
+        .unwrap();
+    let mut entries = Vec::new();
+    let summary = loop {
+        match next(&events).await {
+            Event::CompareEntries { entries: batch, .. } => entries.extend(batch),
+            Event::CompareFinished { summary, .. } => break summary,
+            _ => {}
+        }
+    };
+    assert_eq!(entries.len(), 7);
+    assert_eq!(summary.differing, 3);
+    loop {
+        if let Event::JobFinished { outcome, .. } = next(&events).await {
+            assert_eq!(outcome, JobOutcome::Completed);
+            break;
+        }
+    }
+
+    cmd_tx
+        .send(OpCommand::Synchronize {
+            left: left_id,
+            right: right_id,
+            plan: SyncPlan::mirror(&entries, Side::Right),
+            session,
+        })
+        .unwrap();
+    let report = loop {
+        if let Event::SyncFinished { report, .. } = next(&events).await {
+            break report;
+        }
+    };
+    assert_eq!(report.errors, 0);
+    assert_eq!(report.results.len(), 6);
+    assert!(!right.join("stale.txt").exists());
+}
+
+/// Local directory served as if it were a remote under `/remote`
+struct Remote(PathBuf, LocalFs);
+
+impl Remote {
+    fn local(&self, path: &Path) -> PathBuf {
+        self.0.join(path.strip_prefix("/remote").unwrap())
+    }
+}
+
+#[async_trait]
+impl FsProvider for Remote {
+    fn scheme(&self) -> &'static str {
+        "remote"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.1.capabilities()
+    }
+
+    async fn list(&self, path: &Path) -> Result<Vec<FileNode>, CoreError> {
+        self.1.list(&self.local(path)).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, CoreError> {
+        self.1.read(&self.local(path)).await
+    }
+
+    async fn read_range(&self, path: &Path, start: u64, len: u64) -> Result<Vec<u8>, CoreError> {
+        self.1.read_range(&self.local(path), start, len).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, CoreError> {
+        self.1.exists(&self.local(path)).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
+        self.1.metadata(&self.local(path)).await
+    }
+}
+
+#[tokio::test]
+async fn test_operations_compare_across_providers() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = trees(dir.path());
+    let remote = Remote(right, LocalFs::new(NodeRegistry::new()));
+    let h = spawn_operations(|ops| ops.with_mount(PathBuf::from("/remote"), Arc::new(remote)));
+
+    h.commands
+        .send(OpCommand::Compare {
+            left: h.registry.clone().register(left),
+            right: h.registry.clone().register(PathBuf::from("/remote")),
+            options: CompareOptions::default(),
+            session: h.session,
+        })
+        .unwrap();
+    match h.finished().await {
+        (seen, JobOutcome::Completed, _) => {
+            let summary = seen.iter().find_map(|event| match event {
+                Event::CompareFinished { summary, .. } => Some(summary),
+                _ => None,
+            });
+            assert_eq!(summary.unwrap().differing, 3);
+        }
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+}
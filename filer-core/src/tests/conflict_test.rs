@@ -0,0 +1,367 @@
+//! Tests for conflict resolution in copy/move transfers
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::operations::{OpCommand, Operations};
+use crate::api::events::{Event, JobOutcome};
+use crate::errors::CoreError;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::transfer::{
+    Conflict, ConflictAnswer, ConflictPolicy, ConflictResolution, JobControl, ProgressTracker, Transfer,
+    TransferMode, TransferObserver, TransferOptions, TransferReport,
+};
+
+/// Answers every conflict with a fixed answer and records what it was asked
+struct Scripted {
+    answer: ConflictAnswer,
+    asked: Vec<Conflict>,
+}
+
+impl Scripted {
+    fn new(resolution: ConflictResolution, apply_to_all: bool) -> Self {
+        Self {
+            answer: ConflictAnswer {
+                resolution,
+                apply_to_all,
+            },
+            asked: Vec::new(),
+        }
+    }
+}
+
+impl TransferObserver for Scripted {
+    fn progress(&mut self, _tracker: &ProgressTracker, _current: &Path) {}
+
+    fn failed(&mut self, path: &Path, error: &CoreError) {
+        panic!("{} failed: {error}", path.display());
+    }
+
+    fn conflict(&mut self, conflict: &Conflict) -> Result<ConflictAnswer, CoreError> {
+        self.asked.push(conflict.clone());
+        Ok(self.answer)
+    }
+}
+
+struct Fixture {
+    _dir: tempfile::TempDir,
+    src: PathBuf,
+    dest: PathBuf,
+}
+
+/// `src/` and an existing `dest/src/` that overlap on `a.txt` and `sub/b.txt`
+fn fixture() -> Fixture {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("a.txt"), b"new a").unwrap();
+    fs::write(src.join("sub/b.txt"), b"new b").unwrap();
+    fs::write(src.join("sub/only-src.txt"), b"src").unwrap();
+
+    let dest = dir.path().join("dest");
+    fs::create_dir_all(dest.join("src/sub")).unwrap();
+    fs::write(dest.join("src/a.txt"), b"old a").unwrap();
+    fs::write(dest.join("src/sub/b.txt"), b"old b").unwrap();
+    fs::write(dest.join("src/sub/only-dest.txt"), b"dest").unwrap();
+    Fixture { _dir: dir, src, dest }
+}
+
+fn set_mtime(path: &Path, secs: u64) {
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+        .unwrap();
+}
+
+fn run(mode: TransferMode, sources: Vec<PathBuf>, dest: &Path, policy: ConflictPolicy) -> TransferReport {
+    let mut observer = Scripted::new(ConflictResolution::Skip, false);
+    let report = Transfer::new(mode, sources, dest.to_path_buf())
+        .with_options(TransferOptions {
+            conflict: policy,
+            ..TransferOptions::default()
+        })
+        .run(&JobControl::new(), &mut observer)
+        .unwrap();
+    assert!(observer.asked.is_empty(), "policy should cover every conflict");
+    report
+}
+
+fn merge_then(files: ConflictResolution) -> ConflictPolicy {
+    ConflictPolicy {
+        files: Some(files),
+        directories: Some(ConflictResolution::Merge),
+    }
+}
+
+// ===== Policy Tests =====
+
+#[test]
+fn test_conflict_skip_leaves_target() {
+    let f = fixture();
+    let report = run(
+        TransferMode::Copy,
+        vec![f.src.clone()],
+        &f.dest,
+        ConflictPolicy::always(ConflictResolution::Skip),
+    );
+    assert_eq!(report.skipped, 1);
+    assert!(report.created.is_empty());
+    assert_eq!(fs::read(f.dest.join("src/a.txt")).unwrap(), b"old a");
+    assert!(!f.dest.join("src/sub/only-src.txt").exists());
+}
+
+#[test]
+fn test_conflict_merge_directories() {
+    let f = fixture();
+    let report = run(
+        TransferMode::Copy,
+        vec![f.src.clone()],
+        &f.dest,
+        merge_then(ConflictResolution::Overwrite),
+    );
+    assert_eq!(report.created, vec![f.dest.join("src")]);
+    assert_eq!(fs::read(f.dest.join("src/a.txt")).unwrap(), b"new a");
+    assert_eq!(fs::read(f.dest.join("src/sub/b.txt")).unwrap(), b"new b");
+    assert!(f.dest.join("src/sub/only-src.txt").exists());
+    assert!(f.dest.join("src/sub/only-dest.txt").exists());
+    // Overwrites go through a temporary file
+    assert!(!f.dest.join("src/.a.txt.filer-part").exists());
+}
+
+#[test]
+fn test_conflict_overwrite_replaces_directory() {
+    let f = fixture();
+    run(
+        TransferMode::Copy,
+        vec![f.src.clone()],
+        &f.dest,
+        ConflictPolicy::always(ConflictResolution::Overwrite),
+    );
+    assert!(f.dest.join("src/sub/only-src.txt").exists());
+    assert!(!f.dest.join("src/sub/only-dest.txt").exists());
+}
+
+#[test]
+fn test_conflict_overwrite_if_newer() {
+    let f = fixture();
+    set_mtime(&f.src.join("a.txt"), 2_000_000_000);
+    set_mtime(&f.dest.join("src/a.txt"), 1_000_000_000);
+    set_mtime(&f.src.join("sub/b.txt"), 1_000_000_000);
+    set_mtime(&f.dest.join("src/sub/b.txt"), 2_000_000_000);
+
+    let report = run(
+        TransferMode::Copy,
+        vec![f.src.clone()],
+        &f.dest,
+        merge_then(ConflictResolution::OverwriteIfNewer),
+    );
+    assert_eq!(report.skipped, 1);
+    assert_eq!(fs::read(f.dest.join("src/a.txt")).unwrap(), b"new a");
+    assert_eq!(fs::read(f.dest.join("src/sub/b.txt")).unwrap(), b"old b");
+}
+
+#[test]
+fn test_conflict_keep_both() {
+    let f = fixture();
+    let report = run(
+        TransferMode::Copy,
+        vec![f.src.join("a.txt"), f.src.clone()],
+        &f.dest.join("src"),
+        ConflictPolicy::always(ConflictResolution::KeepBoth),
+    );
+    assert_eq!(
+        report.created,
+        vec![f.dest.join("src/a (2).txt"), f.dest.join("src/src")]
+    );
+    assert_eq!(fs::read(f.dest.join("src/a (2).txt")).unwrap(), b"new a");
+    assert_eq!(fs::read(f.dest.join("src/a.txt")).unwrap(), b"old a");
+
+    // Once more: the first free number is used
+    run(
+        TransferMode::Copy,
+        vec![f.src.join("a.txt")],
+        &f.dest.join("src"),
+        ConflictPolicy::always(ConflictResolution::KeepBoth),
+    );
+    assert!(f.dest.join("src/a (3).txt").exists());
+}
+
+#[test]
+fn test_conflict_merge_on_files_skips() {
+    let f = fixture();
+    let report = run(
+        TransferMode::Copy,
+        vec![f.src.join("a.txt")],
+        &f.dest.join("src"),
+        ConflictPolicy::always(ConflictResolution::Merge),
+    );
+    assert_eq!(report.skipped, 1);
+    assert_eq!(fs::read(f.dest.join("src/a.txt")).unwrap(), b"old a");
+}
+
+#[test]
+fn test_copy_onto_itself_keeps_both() {
+    let f = fixture();
+    let report = run(
+        TransferMode::Copy,
+        vec![f.src.join("a.txt")],
+        &f.src,
+        ConflictPolicy::default(),
+    );
+    assert_eq!(report.created, vec![f.src.join("a (2).txt")]);
+}
+
+#[test]
+fn test_move_merge_keeps_skipped_sources() {
+    let f = fixture();
+    let report = run(
+        TransferMode::Move,
+        vec![f.src.clone()],
+        &f.dest,
+        merge_then(ConflictResolution::Skip),
+    );
+    assert_eq!(report.skipped, 2);
+    assert_eq!(report.errors, 0);
+    assert!(f.dest.join("src/sub/only-src.txt").exists());
+    assert!(!f.src.join("sub/only-src.txt").exists());
+    // Only what was not moved is left behind
+    assert!(f.src.join("a.txt").exists());
+    assert!(f.src.join("sub/b.txt").exists());
+}
+
+#[test]
+fn test_move_overwrite_renames_over_file() {
+    let f = fixture();
+    run(
+        TransferMode::Move,
+        vec![f.src.join("a.txt")],
+        &f.dest.join("src"),
+        ConflictPolicy::always(ConflictResolution::Overwrite),
+    );
+    assert!(!f.src.join("a.txt").exists());
+    assert_eq!(fs::read(f.dest.join("src/a.txt")).unwrap(), b"new a");
+}
+
+// ===== Asking Tests =====
+
+#[test]
+fn test_conflict_apply_to_all_asks_once_per_kind() {
+    let f = fixture();
+    let mut observer = Scripted::new(ConflictResolution::Merge, true);
+    let report = Transfer::new(TransferMode::Copy, vec![f.src.clone()], f.dest.clone())
+        .run(&JobControl::new(), &mut observer)
+        .unwrap();
+
+    // The directory answer does not cover files, so a.txt is asked too
+    assert_eq!(observer.asked.len(), 2);
+    assert!(observer.asked[0].is_directory_pair());
+    assert_eq!(observer.asked[1].source.size, 5);
+    assert_eq!(observer.asked[1].target.path, f.dest.join("src/a.txt"));
+    // "Merge" for files settles as skip
+    assert_eq!(report.skipped, 2);
+}
+
+#[test]
+fn test_conflict_default_observer_fails_item() {
+    struct Silent;
+    impl TransferObserver for Silent {
+        fn progress(&mut self, _tracker: &ProgressTracker, _current: &Path) {}
+        fn failed(&mut self, _path: &Path, _error: &CoreError) {}
+    }
+
+    let f = fixture();
+    let report = Transfer::new(TransferMode::Copy, vec![f.src.join("a.txt")], f.dest.join("src"))
+        .run(&JobControl::new(), &mut Silent)
+        .unwrap();
+    assert_eq!(report.errors, 1);
+    assert!(report.created.is_empty());
+}
+
+#[tokio::test]
+async fn test_operations_conflict_roundtrip() {
+    let f = fixture();
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    tokio::spawn(Operations::new(cmd_rx, evt_tx, registry.clone()).run());
+    let session = SessionId::new();
+
+    let next = || async {
+        loop {
+            let event = timeout(Duration::from_secs(5), evt_rx.recv_async()).await.unwrap().unwrap();
+            if !matches!(event, Event::JobProgress { .. }) {
+                return event;
+            }
+        }
+    };
+
+    cmd_tx
+        .send(OpCommand::Copy {
+            sources: vec![registry.clone().register(f.src.join("a.txt"))],
+            destination: registry.clone().register(f.dest.join("src")),
+            options: TransferOptions::default(),
+            session,
+        })
+        .unwrap();
+    let Event::JobStarted { job, .. } = next().await else {
+        panic!("expected JobStarted")
+    };
+    match next().await {
+        Event::OperationConflict { job: j, source, target, .. } => {
+            assert_eq!(j, job);
+            assert_eq!(registry.resolve(source.node), Some(f.src.join("a.txt")));
+            assert_eq!(registry.resolve(target.node), Some(f.dest.join("src/a.txt")));
+            assert_eq!((source.size, target.size), (5, 5));
+            assert!(source.modified.is_some() && !target.is_dir);
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+
+    cmd_tx
+        .send(OpCommand::Resolve {
+            job,
+            answer: ConflictAnswer {
+                resolution: ConflictResolution::Overwrite,
+                apply_to_all: false,
+            },
+            session,
+        })
+        .unwrap();
+    assert!(matches!(
+        next().await,
+        Event::JobFinished {
+            outcome: JobOutcome::Completed,
+            ..
+        }
+    ));
+    assert_eq!(fs::read(f.dest.join("src/a.txt")).unwrap(), b"new a");
+
+    // Cancelling releases a job waiting for an answer
+    cmd_tx
+        .send(OpCommand::Copy {
+            sources: vec![registry.clone().register(f.src.join("a.txt"))],
+            destination: registry.clone().register(f.dest.join("src")),
+            options: TransferOptions::default(),
+            session,
+        })
+        .unwrap();
+    let job = loop {
+        if let Event::OperationConflict { job, .. } = next().await {
+            break job;
+        }
+    };
+    cmd_tx.send(OpCommand::Cancel(job, session)).unwrap();
+    loop {
+        if let Event::JobFinished { outcome, .. } = next().await {
+            assert_eq!(outcome, JobOutcome::Cancelled);
+            break;
+        }
+    }
+}
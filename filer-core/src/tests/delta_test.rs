@@ -0,0 +1,247 @@
+//! Tests for incremental directory deltas
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::scanner::{ScanCommand, Scanner};
+use crate::api::events::Event;
+use crate::model::node::{FileNode, NodeId, NodeKind, NodeMeta};
+use crate::model::registry::NodeRegistry;
+use crate::model::session::{SessionId, ViewId};
+use crate::pipeline::{
+    GroupBy, GroupConfig, Listing, ListingDelta, Pipeline, PipelineConfig, SortConfig, SortField, SortOrder,
+};
+use crate::vfs::local::LocalFs;
+
+fn node(id: u64, name: &str, size: u64) -> FileNode {
+    FileNode {
+        id: NodeId(id),
+        name: name.to_string(),
+        path: PathBuf::from(format!("/test/{name}")),
+        kind: NodeKind::File { extension: None },
+        size,
+        modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1)),
+        created: None,
+        meta: NodeMeta::default(),
+    }
+}
+
+fn sorted_by(field: SortField) -> PipelineConfig {
+    PipelineConfig {
+        sort: Some(SortConfig {
+            field,
+            order: SortOrder::Ascending,
+            directories_first: true,
+        }),
+        filter: None,
+        group: None,
+    }
+}
+
+fn listing(config: &PipelineConfig, nodes: Vec<FileNode>) -> Listing {
+    Listing::from_data(Pipeline::from_config(config).execute(nodes))
+}
+
+fn names(nodes: &[FileNode]) -> Vec<&str> {
+    nodes.iter().map(|n| n.name.as_str()).collect()
+}
+
+/// The delta must turn the old listing into exactly the new one
+fn assert_patches(old: &Listing, new: &Listing, delta: &ListingDelta) {
+    let patched = delta.apply(&old.nodes());
+    assert_eq!(names(&patched), names(&new.nodes()));
+}
+
+// ===== ListingDelta Tests =====
+
+#[test]
+fn test_delta_identical_listings_is_empty() {
+    let config = sorted_by(SortField::Name);
+    let old = listing(&config, vec![node(1, "a", 1), node(2, "b", 2)]);
+    let new = listing(&config, vec![node(2, "b", 2), node(1, "a", 1)]);
+    assert!(ListingDelta::between(&old, &new).is_empty());
+}
+
+#[test]
+fn test_delta_places_added_entry_in_sort_order() {
+    let config = sorted_by(SortField::Name);
+    let old = listing(&config, vec![node(1, "a", 1), node(3, "c", 1)]);
+    let new = listing(&config, vec![node(1, "a", 1), node(2, "b", 1), node(3, "c", 1)]);
+
+    let delta = ListingDelta::between(&old, &new);
+    assert_eq!(delta.added.len(), 1);
+    assert_eq!(delta.added[0].node.name, "b");
+    assert_eq!(delta.added[0].index, 1);
+    assert!(delta.removed.is_empty() && delta.modified.is_empty() && delta.moved.is_empty());
+    assert_patches(&old, &new, &delta);
+}
+
+#[test]
+fn test_delta_reports_removed_ids() {
+    let config = sorted_by(SortField::Name);
+    let old = listing(&config, vec![node(1, "a", 1), node(2, "b", 1)]);
+    let new = listing(&config, vec![node(2, "b", 1)]);
+
+    let delta = ListingDelta::between(&old, &new);
+    assert_eq!(delta.removed, vec![NodeId(1)]);
+    assert_patches(&old, &new, &delta);
+}
+
+#[test]
+fn test_delta_modified_entry_is_replaced() {
+    // Sorted by size: growing "a" moves it past "b"
+    let config = sorted_by(SortField::Size);
+    let old = listing(&config, vec![node(1, "a", 1), node(2, "b", 5)]);
+    let new = listing(&config, vec![node(1, "a", 10), node(2, "b", 5)]);
+
+    let delta = ListingDelta::between(&old, &new);
+    assert_eq!(delta.modified.len(), 1);
+    assert_eq!(delta.modified[0].node.size, 10);
+    assert_eq!(delta.modified[0].index, 1);
+    assert!(delta.moved.is_empty());
+    assert_patches(&old, &new, &delta);
+}
+
+#[test]
+fn test_delta_reorders_unchanged_entries_minimally() {
+    // Same data, different order (e.g. ties broken differently)
+    let config = PipelineConfig::default();
+    let old = listing(&config, vec![node(1, "a", 1), node(2, "b", 1), node(3, "c", 1), node(4, "d", 1)]);
+    let new = listing(&config, vec![node(4, "d", 1), node(1, "a", 1), node(2, "b", 1), node(3, "c", 1)]);
+
+    let delta = ListingDelta::between(&old, &new);
+    assert_eq!(delta.moved.len(), 1);
+    assert_eq!(delta.moved[0].node.name, "d");
+    assert_eq!(delta.moved[0].index, 0);
+    assert_patches(&old, &new, &delta);
+}
+
+#[test]
+fn test_delta_carries_group_labels() {
+    let config = PipelineConfig {
+        group: Some(GroupConfig {
+            by: GroupBy::FirstLetter,
+        }),
+        ..sorted_by(SortField::Name)
+    };
+    let old = listing(&config, vec![node(1, "apple", 1), node(2, "cherry", 1)]);
+    let new = listing(
+        &config,
+        vec![node(1, "apple", 1), node(3, "banana", 1), node(2, "cherry", 1)],
+    );
+
+    let delta = ListingDelta::between(&old, &new);
+    assert_eq!(delta.added.len(), 1);
+    assert_eq!(delta.added[0].group.as_deref(), Some("B"));
+    assert_eq!(delta.added[0].index, 1);
+    assert_patches(&old, &new, &delta);
+}
+
+#[test]
+fn test_delta_mixed_changes_patch_correctly() {
+    let config = sorted_by(SortField::Size);
+    let old = listing(
+        &config,
+        (1..=20).map(|i| node(i, &format!("f{i:02}"), i)).collect(),
+    );
+    let mut next: Vec<FileNode> = (1..=20)
+        .filter(|i| i % 5 != 0)
+        .map(|i| node(i, &format!("f{i:02}"), if i % 3 == 0 { 100 - i } else { i }))
+        .collect();
+    next.push(node(50, "new", 7));
+    let new = listing(&config, next);
+
+    let delta = ListingDelta::between(&old, &new);
+    assert_eq!(delta.removed.len(), 4);
+    assert_eq!(delta.added.len(), 1);
+    assert_patches(&old, &new, &delta);
+}
+
+// ===== Scanner Integration Tests =====
+
+#[tokio::test]
+async fn test_scanner_update_sends_delta() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+    fs::write(root.join("c.txt"), b"c").unwrap();
+
+    let reg = NodeRegistry::new();
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let scanner = Scanner::new(cmd_rx, evt_tx, Arc::new(LocalFs::new(reg.clone())), reg.clone());
+    tokio::spawn(scanner.run());
+
+    let session = SessionId::new();
+    let node = reg.clone().register(root.clone());
+    let pipeline = sorted_by(SortField::Name);
+    cmd_tx
+        .send(ScanCommand::ScanNode { node, session, view: ViewId::main(), pipeline: pipeline.clone() })
+        .unwrap();
+    match timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap() {
+        Event::DirectoryLoaded { entries, .. } => assert_eq!(names(&entries), vec!["a.txt", "c.txt"]),
+        other => panic!("unexpected event: {other:?}"),
+    }
+
+    fs::write(root.join("b.txt"), b"b").unwrap();
+    fs::remove_file(root.join("a.txt")).unwrap();
+    cmd_tx
+        .send(ScanCommand::Update { node, session, view: ViewId::main(), pipeline: pipeline.clone() })
+        .unwrap();
+    match timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap() {
+        Event::DirectoryDelta { parent, added, removed, modified, moved, session: s, view } => {
+            assert_eq!(view, ViewId::main());
+            assert_eq!(parent, node);
+            assert_eq!(s, session);
+            assert_eq!(added.len(), 1);
+            assert_eq!(added[0].node.name, "b.txt");
+            assert_eq!(added[0].index, 0);
+            assert_eq!(removed, vec![reg.get_id(&root.join("a.txt")).unwrap()]);
+            assert!(modified.is_empty() && moved.is_empty());
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+
+    // A different pipeline cannot be patched, so it reloads
+    cmd_tx
+        .send(ScanCommand::Update { node, session, view: ViewId::main(), pipeline: sorted_by(SortField::Size) })
+        .unwrap();
+    assert!(matches!(
+        timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap(),
+        Event::DirectoryLoaded { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_scanner_update_without_changes_is_silent() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+
+    let reg = NodeRegistry::new();
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let scanner = Scanner::new(cmd_rx, evt_tx, Arc::new(LocalFs::new(reg.clone())), reg.clone());
+    tokio::spawn(scanner.run());
+
+    let session = SessionId::new();
+    let node = reg.clone().register(root);
+    let pipeline = PipelineConfig::default();
+
+    // Without a previous listing an update is a full load
+    cmd_tx
+        .send(ScanCommand::Update { node, session, view: ViewId::main(), pipeline: pipeline.clone() })
+        .unwrap();
+    assert!(matches!(
+        timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap(),
+        Event::DirectoryLoaded { .. }
+    ));
+
+    cmd_tx.send(ScanCommand::Update { node, session, view: ViewId::main(), pipeline }).unwrap();
+    assert!(timeout(Duration::from_millis(300), evt_rx.recv_async()).await.is_err());
+}
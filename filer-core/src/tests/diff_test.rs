@@ -0,0 +1,264 @@
+//! Tests for text diffs
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::errors::CoreError;
+use crate::model::node::FileNode;
+use crate::model::registry::NodeRegistry;
+use crate::services::diff::{DiffLineKind, DiffOptions, DiffSpan, FileDiff, TextDiff};
+use crate::services::preview::PreviewData;
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::{Capabilities, FsProvider};
+
+const OLD: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+const NEW: &str = "one\ntwo\nthree\nfour\nFIVE\nsix\nseven\neight\nnine\nten\neleven\n";
+
+/// Files held in memory, standing in for an archive provider
+struct Memory(HashMap<PathBuf, Vec<u8>>);
+
+#[async_trait]
+impl FsProvider for Memory {
+    fn scheme(&self) -> &'static str {
+        "memory"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            write: false,
+            watch: false,
+            search: false,
+            xattrs: false,
+        }
+    }
+
+    async fn list(&self, _path: &Path) -> Result<Vec<FileNode>, CoreError> {
+        Ok(Vec::new())
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, CoreError> {
+        self.0
+            .get(path)
+            .cloned()
+            .ok_or_else(|| CoreError::NotFound(path.to_path_buf()))
+    }
+
+    async fn read_range(&self, path: &Path, start: u64, len: u64) -> Result<Vec<u8>, CoreError> {
+        let data = self.read(path).await?;
+        let start = (start as usize).min(data.len());
+        let end = start.saturating_add(len as usize).min(data.len());
+        Ok(data[start..end].to_vec())
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, CoreError> {
+        Ok(self.0.contains_key(path))
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
+        Err(CoreError::NotFound(path.to_path_buf()))
+    }
+}
+
+fn memory(files: &[(&str, &[u8])]) -> Arc<dyn FsProvider> {
+    Arc::new(Memory(
+        files
+            .iter()
+            .map(|(path, data)| (PathBuf::from(path), data.to_vec()))
+            .collect(),
+    ))
+}
+
+#[test]
+fn test_diff_hunks_and_line_numbers() {
+    let diff = TextDiff::compute(OLD, NEW, &DiffOptions::default());
+    assert!(!diff.identical && !diff.binary && !diff.truncated);
+    assert_eq!(diff.hunks.len(), 1);
+    let hunk = &diff.hunks[0];
+    assert_eq!(hunk.header(), "@@ -2,9 +2,10 @@");
+
+    let changed: Vec<_> = hunk
+        .lines
+        .iter()
+        .filter(|l| l.kind != DiffLineKind::Context)
+        .map(|l| (l.kind, l.old_line, l.new_line, l.text()))
+        .collect();
+    assert_eq!(
+        changed,
+        vec![
+            (DiffLineKind::Removed, Some(5), None, "five".to_string()),
+            (DiffLineKind::Added, None, Some(5), "FIVE".to_string()),
+            (DiffLineKind::Added, None, Some(11), "eleven".to_string()),
+        ]
+    );
+    assert_eq!(hunk.lines[0].old_line, Some(2));
+    assert_eq!(hunk.lines[0].new_line, Some(2));
+}
+
+#[test]
+fn test_diff_separate_hunks_with_context() {
+    let options = DiffOptions {
+        context: 1,
+        ..Default::default()
+    };
+    let diff = TextDiff::compute(OLD, NEW, &options);
+    let headers: Vec<_> = diff.hunks.iter().map(|h| h.header()).collect();
+    assert_eq!(headers, vec!["@@ -4,3 +4,3 @@", "@@ -10,1 +10,2 @@"]);
+}
+
+#[test]
+fn test_diff_unified_output() {
+    let diff = TextDiff::compute("a\nb\nc\n", "a\nB\nc\nd\n", &DiffOptions::default());
+    assert_eq!(
+        diff.unified("old.txt", "new.txt"),
+        "--- old.txt\n+++ new.txt\n@@ -1,3 +1,4 @@\n a\n-b\n+B\n c\n+d\n"
+    );
+    let same = TextDiff::compute("a\n", "a\n", &DiffOptions::default());
+    assert!(same.identical && same.hunks.is_empty());
+    assert_eq!(same.unified("a", "b"), "");
+}
+
+#[test]
+fn test_diff_marks_changed_words() {
+    let diff = TextDiff::compute(
+        "let total = price * count;\n",
+        "let total = price * amount;\n",
+        &DiffOptions::default(),
+    );
+    let lines = &diff.hunks[0].lines;
+    let changed = |spans: &[DiffSpan]| -> Vec<String> {
+        spans
+            .iter()
+            .filter(|s| s.changed)
+            .map(|s| s.text.clone())
+            .collect()
+    };
+    assert_eq!(changed(&lines[0].spans), vec!["count;"]);
+    assert_eq!(changed(&lines[1].spans), vec!["amount;"]);
+    assert_eq!(lines[1].text(), "let total = price * amount;");
+
+    let plain = TextDiff::compute(
+        "let total = price * count;\n",
+        "let total = price * amount;\n",
+        &DiffOptions {
+            word_changes: false,
+            ..Default::default()
+        },
+    );
+    assert!(
+        plain.hunks[0]
+            .lines
+            .iter()
+            .all(|l| l.spans.len() == 1 && !l.spans[0].changed)
+    );
+}
+
+#[test]
+fn test_diff_side_by_side_pairs_replacements() {
+    let diff = TextDiff::compute("a\nb\nc\nd\n", "a\nB\nC\nX\nd\n", &DiffOptions::default());
+    let rows: Vec<_> = diff.hunks[0]
+        .side_by_side()
+        .into_iter()
+        .map(|(old, new)| (old.map(|l| l.text()), new.map(|l| l.text())))
+        .collect();
+    let s = |t: &str| Some(t.to_string());
+    assert_eq!(
+        rows,
+        vec![
+            (s("a"), s("a")),
+            (s("b"), s("B")),
+            (s("c"), s("C")),
+            (None, s("X")),
+            (s("d"), s("d")),
+        ]
+    );
+}
+
+#[test]
+fn test_diff_handles_crlf_and_missing_final_newline() {
+    let diff = TextDiff::compute("a\r\nb\r\n", "a\r\nb", &DiffOptions::default());
+    let texts: Vec<_> = diff.hunks[0].lines.iter().map(|l| l.text()).collect();
+    assert_eq!(texts, vec!["a", "b", "b"]);
+}
+
+#[tokio::test]
+async fn test_file_diff_across_providers() {
+    let dir = tempfile::tempdir().unwrap();
+    let local_path = dir.path().join("config.ini");
+    fs::write(&local_path, NEW).unwrap();
+    let archive = memory(&[("/app.zip/config.ini", OLD.as_bytes())]);
+    let local: Arc<dyn FsProvider> = Arc::new(LocalFs::new(NodeRegistry::new()));
+
+    let preview = FileDiff::new(archive, "/app.zip/config.ini".into(), local, local_path)
+        .preview()
+        .await
+        .unwrap();
+    match preview {
+        PreviewData::Diff { diff } => {
+            assert_eq!(diff.hunks.len(), 1);
+            assert!(!diff.binary);
+        }
+        other => panic!("unexpected preview: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_file_diff_detects_binary() {
+    let provider = memory(&[
+        ("a.bin", b"\x89PNG\0\0data"),
+        ("b.bin", b"\x89PNG\0\0date"),
+        ("c.bin", b"\x89PNG\0\0data"),
+        ("text", b"plain\n"),
+    ]);
+    let diff =
+        |a: &str, b: &str| FileDiff::new(provider.clone(), a.into(), provider.clone(), b.into());
+
+    let different = diff("a.bin", "b.bin").run().await.unwrap();
+    assert!(different.binary && !different.identical && different.hunks.is_empty());
+    assert_eq!(
+        different.unified("a.bin", "b.bin"),
+        "Binary files a.bin and b.bin differ\n"
+    );
+    assert!(diff("a.bin", "c.bin").run().await.unwrap().identical);
+    assert!(diff("text", "a.bin").run().await.unwrap().binary);
+    assert!(matches!(
+        diff("text", "missing").run().await,
+        Err(CoreError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_file_diff_caps_size_at_line_boundary() {
+    let old = "same\n".repeat(100) + "old tail\n";
+    let new = "same\n".repeat(100) + "new tail\n";
+    let provider = memory(&[("old", old.as_bytes()), ("new", new.as_bytes())]);
+    let capped = FileDiff::new(
+        provider.clone(),
+        "old".into(),
+        provider.clone(),
+        "new".into(),
+    )
+    .with_options(DiffOptions {
+        max_bytes: 503,
+        ..Default::default()
+    })
+    .run()
+    .await
+    .unwrap();
+    assert!(capped.truncated);
+    assert!(
+        capped.hunks.is_empty(),
+        "partial last lines are not compared"
+    );
+
+    let full = FileDiff::new(provider.clone(), "old".into(), provider, "new".into())
+        .run()
+        .await
+        .unwrap();
+    assert!(!full.truncated);
+    assert_eq!(full.hunks[0].header(), "@@ -98,4 +98,4 @@");
+}
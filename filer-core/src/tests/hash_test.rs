@@ -0,0 +1,409 @@
+//! Tests for checksums and checksum manifests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::actors::operations::OpCommand;
+use crate::api::events::{Event, JobOutcome, OperationKind};
+use crate::errors::CoreError;
+use crate::model::registry::NodeRegistry;
+use crate::services::hash::{Checksums, HashAlgorithm, Manifest, ManifestEntry};
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+use crate::tests::common::spawn_operations;
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::FsProvider;
+
+const ABC: [(HashAlgorithm, &str); 5] = [
+    (HashAlgorithm::Xxh3, "78af5f94892f3950"),
+    (
+        HashAlgorithm::Sha256,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    ),
+    (
+        HashAlgorithm::Sha1,
+        "a9993e364706816aba3e25717850c26c9cd0d89d",
+    ),
+    (HashAlgorithm::Md5, "900150983cd24fb0d6963f7d28e17f72"),
+    (
+        HashAlgorithm::Blake3,
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+    ),
+];
+
+#[derive(Default)]
+struct Recorder {
+    last_bytes: u64,
+    updates: usize,
+    failed: Vec<PathBuf>,
+}
+
+impl TransferObserver for Recorder {
+    fn progress(&mut self, tracker: &ProgressTracker, _current: &Path) {
+        assert!(
+            tracker.bytes_done >= self.last_bytes,
+            "progress went backwards"
+        );
+        self.last_bytes = tracker.bytes_done;
+        self.updates += 1;
+    }
+
+    fn failed(&mut self, path: &Path, _error: &CoreError) {
+        self.failed.push(path.to_path_buf());
+    }
+}
+
+fn checksums() -> Checksums {
+    Checksums::new(Arc::new(LocalFs::new(NodeRegistry::new())))
+}
+
+fn tree(root: &Path) -> PathBuf {
+    let src = root.join("release");
+    fs::create_dir_all(src.join("bin")).unwrap();
+    fs::write(src.join("abc.txt"), b"abc").unwrap();
+    fs::write(src.join("bin/tool"), vec![1u8; 5000]).unwrap();
+    fs::write(src.join("notes"), b"").unwrap();
+    src
+}
+
+// ===== Algorithm Tests =====
+
+#[tokio::test]
+async fn test_checksums_known_vectors_in_small_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("abc.txt");
+    fs::write(&file, b"abc").unwrap();
+    let algorithms: Vec<_> = ABC.iter().map(|(a, _)| *a).collect();
+
+    let mut recorder = Recorder::default();
+    let report = checksums()
+        .with_chunk_size(1)
+        .compute(
+            std::slice::from_ref(&file),
+            &algorithms,
+            &JobControl::new(),
+            &mut recorder,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(report.errors, 0);
+    assert_eq!(report.files[0].path, file);
+    assert_eq!(report.files[0].size, 3);
+    for (algorithm, expected) in ABC {
+        assert_eq!(
+            report.files[0].get(algorithm),
+            Some(expected),
+            "{algorithm:?}"
+        );
+    }
+    assert_eq!(recorder.last_bytes, 3);
+    assert!(recorder.updates >= 4);
+}
+
+#[tokio::test]
+async fn test_checksums_walk_directories_and_report_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let missing = dir.path().join("missing");
+
+    let mut recorder = Recorder::default();
+    let report = checksums()
+        .compute(
+            &[src.clone(), missing.clone()],
+            &[HashAlgorithm::Md5],
+            &JobControl::new(),
+            &mut recorder,
+        )
+        .await
+        .unwrap();
+
+    let paths: Vec<_> = report.files.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![src.join("abc.txt"), src.join("bin/tool"), src.join("notes")]
+    );
+    assert_eq!(
+        report.files[2].get(HashAlgorithm::Md5),
+        Some("d41d8cd98f00b204e9800998ecf8427e")
+    );
+    assert_eq!(report.errors, 1);
+    assert_eq!(recorder.failed, vec![missing]);
+    assert_eq!(recorder.last_bytes, 5003);
+}
+
+#[tokio::test]
+async fn test_checksums_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let control = JobControl::new();
+    control.cancel();
+
+    let result = checksums()
+        .compute(
+            &[src],
+            &[HashAlgorithm::Sha256],
+            &control,
+            &mut Recorder::default(),
+        )
+        .await;
+    assert!(matches!(result, Err(CoreError::Cancelled)));
+}
+
+// ===== Manifest Tests =====
+
+#[test]
+fn test_hash_algorithm_from_manifest_path() {
+    let guess = |name: &str| HashAlgorithm::from_manifest_path(Path::new(name));
+    assert_eq!(guess("SHA256SUMS"), Some(HashAlgorithm::Sha256));
+    assert_eq!(guess("SHA1SUMS.txt"), Some(HashAlgorithm::Sha1));
+    assert_eq!(guess("release.tar.sha256"), Some(HashAlgorithm::Sha256));
+    assert_eq!(guess("image.iso.md5"), Some(HashAlgorithm::Md5));
+    assert_eq!(guess("x.sha256sum"), Some(HashAlgorithm::Sha256));
+    assert_eq!(guess("B3SUMS"), Some(HashAlgorithm::Blake3));
+    assert_eq!(guess("checksums.txt"), None);
+}
+
+#[test]
+fn test_manifest_parses_gnu_and_bsd_lines() {
+    let sha = ABC[1].1;
+    let md5 = ABC[3].1;
+    let text = format!(
+        "# release checksums\r\n\
+         {sha}  plain.txt\r\n\
+         {} *binary.bin\n\
+         \\{sha}  back\\\\slash\\nline\n\
+         \n\
+         MD5 (bsd name.txt) = {md5}\n",
+        sha.to_uppercase()
+    );
+
+    let manifest = Manifest::parse(text.as_bytes(), None, Path::new("SUMS")).unwrap();
+    let entry = |path: &str, algorithm, digest: &str| ManifestEntry {
+        path: PathBuf::from(path),
+        algorithm,
+        digest: digest.to_string(),
+    };
+    assert_eq!(
+        manifest.entries,
+        vec![
+            entry("plain.txt", HashAlgorithm::Sha256, sha),
+            entry("binary.bin", HashAlgorithm::Sha256, sha),
+            entry("back\\slash\nline", HashAlgorithm::Sha256, sha),
+            entry("bsd name.txt", HashAlgorithm::Md5, md5),
+        ]
+    );
+
+    // Written back in GNU layout, escapes included
+    let rendered = Manifest::parse(&manifest.render(), None, Path::new("SUMS")).unwrap();
+    assert_eq!(rendered.entries[2], manifest.entries[2]);
+    assert_eq!(rendered.entries[3].path, Path::new("bsd name.txt"));
+}
+
+#[test]
+fn test_manifest_rejects_malformed_lines() {
+    let source = Path::new("/x/SHA256SUMS");
+    let bad = [
+        "not a checksum line\n".to_string(),
+        format!("{}  short\n", &ABC[1].1[..10]),
+        format!("{} missing-second-space\n", ABC[1].1),
+        // The algorithm is known, so an MD5-length digest does not fit
+        format!("{}  x\n", ABC[3].1),
+    ];
+    for text in bad {
+        let result = Manifest::parse(text.as_bytes(), Some(HashAlgorithm::Sha256), source);
+        assert!(
+            matches!(&result, Err(CoreError::Io { message, .. }) if message.contains("line 1")),
+            "{text}: {result:?}"
+        );
+    }
+}
+
+// ===== Manifest Round Trip Tests =====
+
+#[tokio::test]
+async fn test_create_and_verify_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let manifest = src.join("SHA256SUMS");
+    let control = JobControl::new();
+
+    let report = checksums()
+        .create_manifest(
+            std::slice::from_ref(&src),
+            HashAlgorithm::Sha256,
+            &manifest,
+            &control,
+            &mut Recorder::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.files.len(), 3);
+    let text = fs::read_to_string(&manifest).unwrap();
+    assert_eq!(text.lines().count(), 3);
+    assert!(text.starts_with(&format!("{}  abc.txt\n", ABC[1].1)));
+    assert!(text.contains("  bin/tool\n"));
+
+    // Regenerating leaves the manifest itself out
+    let report = checksums()
+        .create_manifest(
+            std::slice::from_ref(&src),
+            HashAlgorithm::Sha256,
+            &manifest,
+            &control,
+            &mut Recorder::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.files.len(), 3);
+
+    let report = checksums()
+        .verify(&manifest, &[], &control, &mut Recorder::default())
+        .await
+        .unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.passed.len(), 3);
+
+    fs::write(src.join("abc.txt"), b"abd").unwrap();
+    fs::remove_file(src.join("notes")).unwrap();
+    fs::write(src.join("bin/new"), b"new").unwrap();
+    let report = checksums()
+        .verify(
+            &manifest,
+            std::slice::from_ref(&src),
+            &control,
+            &mut Recorder::default(),
+        )
+        .await
+        .unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.passed, vec![src.join("bin/tool")]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].path, src.join("abc.txt"));
+    assert_eq!(report.failed[0].expected, ABC[1].1);
+    assert_eq!(report.missing, vec![src.join("notes")]);
+    assert_eq!(report.unlisted, vec![src.join("bin/new")]);
+
+    // Only entries under the selection are checked
+    let report = checksums()
+        .verify(
+            &manifest,
+            &[src.join("bin")],
+            &control,
+            &mut Recorder::default(),
+        )
+        .await
+        .unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.passed, vec![src.join("bin/tool")]);
+}
+
+#[tokio::test]
+async fn test_create_manifest_leaves_out_files_outside_its_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let outside = dir.path().join("outside.txt");
+    fs::write(&outside, b"x").unwrap();
+
+    let mut recorder = Recorder::default();
+    let report = checksums()
+        .create_manifest(
+            &[src.join("abc.txt"), outside.clone()],
+            HashAlgorithm::Md5,
+            &src.join("MD5SUMS"),
+            &JobControl::new(),
+            &mut recorder,
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.errors, 1);
+    assert_eq!(recorder.failed, vec![outside]);
+    assert_eq!(
+        fs::read_to_string(src.join("MD5SUMS")).unwrap(),
+        format!("{}  abc.txt\n", ABC[3].1)
+    );
+}
+
+// ===== Operations Tests =====
+
+#[tokio::test]
+async fn test_operations_checksum_jobs() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let provider: Arc<dyn FsProvider> = Arc::new(LocalFs::new(NodeRegistry::new()));
+    let h = spawn_operations(|ops| ops.with_provider(provider));
+    let (registry, session) = (&h.registry, h.session);
+    let node = |path: &Path| registry.clone().register(path.to_path_buf());
+
+    h.commands
+        .send(OpCommand::Checksum {
+            nodes: vec![node(&src.join("abc.txt"))],
+            algorithms: vec![HashAlgorithm::Blake3, HashAlgorithm::Sha1],
+            session,
+        })
+        .unwrap();
+    match h.finished().await {
+        (seen, JobOutcome::Completed, Event::OperationComplete { success: true, .. }) => {
+            match &seen[..] {
+                [Event::ChecksumsComputed { report, .. }] => {
+                    assert_eq!(
+                        report.files[0].digests[0],
+                        (HashAlgorithm::Blake3, ABC[4].1.to_string())
+                    );
+                    assert_eq!(
+                        report.files[0].digests[1],
+                        (HashAlgorithm::Sha1, ABC[2].1.to_string())
+                    );
+                }
+                other => panic!("unexpected events: {other:?}"),
+            }
+        }
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+
+    h.commands
+        .send(OpCommand::CreateManifest {
+            nodes: vec![node(&src)],
+            algorithm: HashAlgorithm::Sha256,
+            directory: node(&src),
+            name: None,
+            session,
+        })
+        .unwrap();
+    let manifest = src.join("SHA256SUMS");
+    match h.finished().await {
+        (
+            _,
+            JobOutcome::Completed,
+            Event::OperationComplete {
+                operation: OperationKind::CreateManifest,
+                affected,
+                ..
+            },
+        ) => assert_eq!(registry.resolve(affected[0]), Some(manifest.clone())),
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+
+    fs::write(src.join("abc.txt"), b"changed").unwrap();
+    h.commands
+        .send(OpCommand::VerifyChecksums {
+            manifest: node(&manifest),
+            nodes: Vec::new(),
+            session,
+        })
+        .unwrap();
+    match h.finished().await {
+        (
+            seen,
+            JobOutcome::Failed { errors: 1 },
+            Event::OperationComplete { success: false, .. },
+        ) => match &seen[..] {
+            [Event::ChecksumsVerified { report, .. }] => {
+                assert_eq!(report.passed.len(), 2);
+                assert_eq!(report.failed[0].path, src.join("abc.txt"));
+            }
+            other => panic!("unexpected events: {other:?}"),
+        },
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+}
@@ -0,0 +1,547 @@
+//! Tests for the undo/redo journal and its use by the Operations actor
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::actors::operations::OpCommand;
+use crate::api::events::{Event, OperationKind};
+use crate::errors::CoreError;
+use crate::model::session::SessionId;
+use crate::services::journal::{Journal, JournalOp, Relocation, Stamp};
+use crate::services::transfer::TransferOptions;
+use crate::services::trash::TrashBin;
+use crate::tests::common::{spawn_operations, tempdir};
+
+fn create_folder(journal: &Journal, session: SessionId, path: &Path) {
+    fs::create_dir(path).unwrap();
+    let op = JournalOp::CreateFolder {
+        path: path.to_path_buf(),
+        stamp: Stamp::of(path).unwrap(),
+    };
+    journal.record(session, op).unwrap();
+}
+
+fn rename(journal: &Journal, session: SessionId, from: &Path, to: &Path) {
+    fs::rename(from, to).unwrap();
+    let op = JournalOp::Rename(Relocation::new(from.to_path_buf(), to.to_path_buf()).unwrap());
+    journal.record(session, op).unwrap();
+}
+
+fn refusal(result: Result<impl std::fmt::Debug, CoreError>) -> String {
+    match result {
+        Err(CoreError::Refused(reason)) => reason,
+        other => panic!("expected refusal, got {other:?}"),
+    }
+}
+
+// ===== Stack Tests =====
+
+#[test]
+fn test_journal_stacks_are_per_session() {
+    let (_dir, root) = tempdir();
+    let journal = Journal::in_memory();
+    let (a, b) = (SessionId::new(), SessionId::new());
+    create_folder(&journal, a, &root.join("a1"));
+    create_folder(&journal, b, &root.join("b1"));
+    create_folder(&journal, a, &root.join("a2"));
+
+    journal.undo(a).unwrap();
+    assert!(!root.join("a2").exists());
+    journal.undo(a).unwrap();
+    assert!(!root.join("a1").exists());
+    assert!(root.join("b1").exists());
+    assert!(journal.next_undo(a).is_none());
+    refusal(journal.undo(a));
+
+    // Redo goes back in the reverse order
+    assert_eq!(
+        journal.next_redo(a).unwrap().op.describe(),
+        "Create folder \"a1\""
+    );
+    journal.redo(a).unwrap();
+    assert!(root.join("a1").exists() && !root.join("a2").exists());
+
+    // A new operation drops what could still be redone
+    create_folder(&journal, a, &root.join("a3"));
+    assert!(journal.next_redo(a).is_none());
+    assert_eq!(
+        journal.next_undo(b).unwrap().op.describe(),
+        "Create folder \"b1\""
+    );
+}
+
+#[test]
+fn test_journal_limit_drops_oldest() {
+    let (_dir, root) = tempdir();
+    let journal = Journal::in_memory().with_limit(2);
+    let session = SessionId::new();
+    for name in ["one", "two", "three"] {
+        create_folder(&journal, session, &root.join(name));
+    }
+    let entries = journal.entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].op.describe(), "Create folder \"two\"");
+}
+
+#[test]
+fn test_journal_persists() {
+    let (_dir, root) = tempdir();
+    let file = root.join("state/journal.json");
+    let session = SessionId::new();
+    {
+        let journal = Journal::open(file.clone()).unwrap();
+        rename(
+            &journal,
+            session,
+            &touch(&root.join("old.txt")),
+            &root.join("new.txt"),
+        );
+    }
+
+    let journal = Journal::open(file).unwrap();
+    let entries = journal.entries();
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0].orphaned);
+}
+
+#[test]
+fn test_journal_reopened_entries_are_not_undoable() {
+    let (_dir, root) = tempdir();
+    let file = root.join("state/journal.json");
+    let session = SessionId::new();
+    {
+        let journal = Journal::open(file.clone()).unwrap();
+        rename(
+            &journal,
+            session,
+            &touch(&root.join("old.txt")),
+            &root.join("new.txt"),
+        );
+    }
+
+    // Session ids restart with every run, so the same id may be a stranger
+    let journal = Journal::open(file).unwrap();
+    assert_eq!(refusal(journal.undo(session)), "nothing to undo");
+    assert_eq!(refusal(journal.undo(SessionId::new())), "nothing to undo");
+    assert!(root.join("new.txt").exists());
+
+    // A fresh entry of the same id is undoable and leaves the old one be
+    create_folder(&journal, session, &root.join("fresh"));
+    journal.undo(session).unwrap();
+    assert!(!root.join("fresh").exists());
+    assert_eq!(refusal(journal.undo(session)), "nothing to undo");
+}
+
+#[test]
+fn test_journal_claimed_entries_are_undoable_after_reopen() {
+    let (_dir, root) = tempdir();
+    let file = root.join("state/journal.json");
+    {
+        let journal = Journal::open(file.clone()).unwrap();
+        let session = SessionId::new();
+        journal.claim(session, "work");
+        rename(
+            &journal,
+            session,
+            &touch(&root.join("old.txt")),
+            &root.join("new.txt"),
+        );
+        create_folder(&journal, SessionId::new(), &root.join("unnamed"));
+    }
+
+    let journal = Journal::open(file).unwrap();
+    let other = SessionId::new();
+    journal.claim(other, "play");
+    assert_eq!(refusal(journal.undo(other)), "nothing to undo");
+
+    let session = SessionId::new();
+    journal.claim(session, "work");
+    assert_eq!(journal.entries()[0].owner.as_deref(), Some("work"));
+    journal.undo(session).unwrap();
+    assert!(root.join("old.txt").exists() && !root.join("new.txt").exists());
+    // Entries of sessions without a name stay orphaned
+    assert_eq!(refusal(journal.undo(session)), "nothing to undo");
+    assert!(root.join("unnamed").exists());
+    journal.redo(session).unwrap();
+    assert!(root.join("new.txt").exists());
+}
+
+fn touch(path: &Path) -> PathBuf {
+    fs::write(path, b"content").unwrap();
+    path.to_path_buf()
+}
+
+// ===== Undo/Redo Tests =====
+
+#[test]
+fn test_undo_redo_rename() {
+    let (_dir, root) = tempdir();
+    let journal = Journal::in_memory();
+    let session = SessionId::new();
+    rename(
+        &journal,
+        session,
+        &touch(&root.join("a.txt")),
+        &root.join("b.txt"),
+    );
+
+    let reverted = journal.undo(session).unwrap();
+    assert_eq!(reverted.affected, vec![root.join("a.txt")]);
+    assert!(root.join("a.txt").exists() && !root.join("b.txt").exists());
+
+    journal.redo(session).unwrap();
+    assert!(!root.join("a.txt").exists() && root.join("b.txt").exists());
+}
+
+#[test]
+fn test_undo_refuses_modified_file() {
+    let (_dir, root) = tempdir();
+    let journal = Journal::in_memory();
+    let session = SessionId::new();
+    rename(
+        &journal,
+        session,
+        &touch(&root.join("a.txt")),
+        &root.join("b.txt"),
+    );
+    fs::write(root.join("b.txt"), b"edited afterwards").unwrap();
+
+    let reason = refusal(journal.undo(session));
+    assert!(reason.contains("b.txt was modified"), "{reason}");
+    // Nothing moved and the entry is still there
+    assert!(root.join("b.txt").exists());
+    assert!(journal.next_undo(session).is_some());
+}
+
+#[test]
+fn test_undo_refuses_replaced_or_taken_paths() {
+    let (_dir, root) = tempdir();
+    let journal = Journal::in_memory();
+    let session = SessionId::new();
+    rename(
+        &journal,
+        session,
+        &touch(&root.join("a.txt")),
+        &root.join("b.txt"),
+    );
+
+    touch(&root.join("a.txt"));
+    assert!(refusal(journal.undo(session)).contains("a.txt already exists"));
+
+    fs::remove_file(root.join("a.txt")).unwrap();
+    fs::rename(touch(&root.join("c.txt")), root.join("b.txt")).unwrap();
+    assert!(refusal(journal.undo(session)).contains("was replaced"));
+
+    fs::remove_file(root.join("b.txt")).unwrap();
+    assert!(refusal(journal.undo(session)).contains("no longer exists"));
+}
+
+#[test]
+fn test_undo_detects_changes_deep_in_directories() {
+    let (_dir, root) = tempdir();
+    fs::create_dir_all(root.join("dir/nested")).unwrap();
+    touch(&root.join("dir/nested/file"));
+    let journal = Journal::in_memory();
+    let session = SessionId::new();
+    rename(&journal, session, &root.join("dir"), &root.join("moved"));
+
+    fs::write(root.join("moved/nested/file"), b"changed!").unwrap();
+    assert!(refusal(journal.undo(session)).contains("moved was modified"));
+}
+
+#[test]
+fn test_undo_redo_copy() {
+    let (_dir, root) = tempdir();
+    fs::create_dir_all(root.join("src/sub")).unwrap();
+    touch(&root.join("src/sub/file"));
+    fs::create_dir(root.join("dest")).unwrap();
+    crate::services::transfer::Transfer::new(
+        crate::services::transfer::TransferMode::Copy,
+        vec![root.join("src")],
+        root.join("dest"),
+    )
+    .run(&crate::services::transfer::JobControl::new(), &mut Quiet)
+    .unwrap();
+    let journal = Journal::in_memory();
+    let session = SessionId::new();
+    let op = JournalOp::Copy(vec![
+        Relocation::new(root.join("src"), root.join("dest/src")).unwrap(),
+    ]);
+    journal.record(session, op).unwrap();
+
+    journal.undo(session).unwrap();
+    assert!(!root.join("dest/src").exists());
+    assert!(root.join("src/sub/file").exists());
+
+    journal.redo(session).unwrap();
+    assert_eq!(
+        fs::read(root.join("dest/src/sub/file")).unwrap(),
+        b"content"
+    );
+    // No staging directory is left behind
+    assert_eq!(fs::read_dir(root.join("dest")).unwrap().count(), 1);
+
+    fs::write(root.join("dest/src/sub/new"), b"").unwrap();
+    assert!(refusal(journal.undo(session)).contains("was modified"));
+}
+
+#[test]
+fn test_undo_move_rolls_back_on_partial_failure() {
+    let (_dir, root) = tempdir();
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::create_dir_all(root.join("b/d")).unwrap();
+    touch(&root.join("b/d/f"));
+    let journal = Journal::in_memory();
+    let session = SessionId::new();
+    // The second item is inside the first, so it is gone by the time its
+    // turn comes
+    let op = JournalOp::Move(vec![
+        Relocation::new(root.join("a/d"), root.join("b/d")).unwrap(),
+        Relocation::new(root.join("a/f"), root.join("b/d/f")).unwrap(),
+    ]);
+    journal.record(session, op).unwrap();
+
+    assert!(journal.undo(session).is_err());
+    assert!(root.join("b/d/f").exists());
+    assert!(!root.join("a/d").exists());
+    // Still recorded as done, with stamps that match
+    assert!(journal.next_undo(session).is_some_and(|e| !e.undone));
+    assert!(journal.undo(session).is_err());
+    assert!(root.join("b/d/f").exists());
+}
+
+struct Quiet;
+
+impl crate::services::transfer::TransferObserver for Quiet {
+    fn progress(&mut self, _tracker: &crate::services::transfer::ProgressTracker, _current: &Path) {
+    }
+    fn failed(&mut self, path: &Path, error: &CoreError) {
+        panic!("{}: {error}", path.display());
+    }
+}
+
+#[test]
+fn test_undo_redo_trash() {
+    let (_dir, root) = tempdir();
+    let bin = TrashBin::with_home(root.join("Trash"));
+    let journal = Journal::in_memory().with_trash(bin.clone());
+    let session = SessionId::new();
+    let file = touch(&root.join("doc.txt"));
+
+    let item = bin.trash(&file).unwrap();
+    let op = JournalOp::Trash(vec![
+        Relocation::new(file.clone(), item.files_path()).unwrap(),
+    ]);
+    journal.record(session, op).unwrap();
+
+    journal.undo(session).unwrap();
+    assert!(file.exists());
+    assert!(bin.list().is_empty());
+
+    journal.redo(session).unwrap();
+    assert!(!file.exists());
+    assert_eq!(bin.list().len(), 1);
+
+    // Without a trash the journal refuses instead of guessing
+    let bare = Journal::in_memory();
+    let op = JournalOp::Trash(vec![
+        Relocation::new(file, bin.list()[0].files_path()).unwrap(),
+    ]);
+    bare.record(session, op).unwrap();
+    refusal(bare.undo(session));
+}
+
+// ===== Operations Actor Tests =====
+
+fn history(events: &[Event]) -> Option<(Option<String>, Option<String>)> {
+    events.iter().rev().find_map(|e| match e {
+        Event::HistoryChanged { undo, redo, .. } => Some((undo.clone(), redo.clone())),
+        _ => None,
+    })
+}
+
+fn completed(events: &[Event]) -> (bool, OperationKind) {
+    match events.last() {
+        Some(Event::OperationComplete {
+            success, operation, ..
+        }) => (*success, operation.clone()),
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_operations_rename_and_undo() {
+    let (_dir, root) = tempdir();
+    let h = spawn_operations(|ops| ops.with_journal(Journal::in_memory()));
+    let node = h.registry.clone().register(touch(&root.join("a.txt")));
+
+    h.commands
+        .send(OpCommand::Rename {
+            node,
+            new_name: "b.txt".to_string(),
+            session: h.session,
+        })
+        .unwrap();
+    let events = h.until_complete().await;
+    assert!(completed(&events).0);
+    assert_eq!(
+        history(&events),
+        Some((Some("Rename \"a.txt\"".to_string()), None))
+    );
+    assert!(root.join("b.txt").exists());
+
+    h.commands.send(OpCommand::Undo(h.session)).unwrap();
+    let events = h.until_complete().await;
+    assert!(matches!(completed(&events), (true, OperationKind::Undo)));
+    assert_eq!(
+        history(&events),
+        Some((None, Some("Rename \"a.txt\"".to_string())))
+    );
+    assert!(root.join("a.txt").exists());
+
+    // Nothing left to undo: refused with an explanation
+    h.commands.send(OpCommand::Undo(h.session)).unwrap();
+    let events = h.until_complete().await;
+    assert!(!completed(&events).0);
+    assert!(
+        events.iter().any(
+            |e| matches!(e, Event::Error { message, .. } if message.contains("nothing to undo"))
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_operations_rename_rejects_bad_names() {
+    let (_dir, root) = tempdir();
+    let h = spawn_operations(|ops| ops.with_journal(Journal::in_memory()));
+    let node = h.registry.clone().register(touch(&root.join("a.txt")));
+    touch(&root.join("taken.txt"));
+    // Not seen by `exists`, but still never replaced
+    std::os::unix::fs::symlink(root.join("missing"), root.join("dangling")).unwrap();
+
+    for name in ["", "..", "x/y", "taken.txt", "dangling"] {
+        h.commands
+            .send(OpCommand::Rename {
+                node,
+                new_name: name.to_string(),
+                session: h.session,
+            })
+            .unwrap();
+        assert!(
+            !completed(&h.until_complete().await).0,
+            "{name:?} was accepted"
+        );
+    }
+    assert!(root.join("a.txt").exists());
+    assert!(fs::symlink_metadata(root.join("dangling")).unwrap().is_symlink());
+}
+
+#[tokio::test]
+async fn test_operations_create_and_copy_are_undoable() {
+    let (_dir, root) = tempdir();
+    let h = spawn_operations(|ops| ops.with_journal(Journal::in_memory()));
+    let parent = h.registry.clone().register(root.clone());
+
+    h.commands
+        .send(OpCommand::CreateFolder {
+            parent,
+            name: "folder".to_string(),
+            session: h.session,
+        })
+        .unwrap();
+    assert!(completed(&h.until_complete().await).0);
+    h.commands
+        .send(OpCommand::CreateFile {
+            parent,
+            name: "file.txt".to_string(),
+            session: h.session,
+        })
+        .unwrap();
+    assert!(completed(&h.until_complete().await).0);
+    assert!(root.join("folder").is_dir() && root.join("file.txt").is_file());
+
+    h.commands
+        .send(OpCommand::Copy {
+            sources: vec![h.registry.clone().register(root.join("file.txt"))],
+            destination: h.registry.clone().register(root.join("folder")),
+            options: TransferOptions::default(),
+            session: h.session,
+        })
+        .unwrap();
+    let events = h.until_complete().await;
+    assert!(completed(&events).0);
+    assert_eq!(
+        history(&events).unwrap().0.as_deref(),
+        Some("Copy \"file.txt\"")
+    );
+
+    for _ in 0..3 {
+        h.commands.send(OpCommand::Undo(h.session)).unwrap();
+        assert!(completed(&h.until_complete().await).0);
+    }
+    assert!(!root.join("folder").exists() && !root.join("file.txt").exists());
+}
+
+#[tokio::test]
+async fn test_operations_cancelled_copy_records_what_arrived() {
+    let (_dir, root) = tempdir();
+    let dest = root.join("dest");
+    fs::create_dir(&dest).unwrap();
+    let done = touch(&root.join("done.txt"));
+    let stream = root.join("stream");
+    let c_path = std::ffi::CString::new(stream.as_os_str().as_encoded_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+    // Reads of the fifo block until we write, holding the job after done.txt
+    let mut writer = fs::File::options().read(true).write(true).open(&stream).unwrap();
+    let h = spawn_operations(|ops| ops.with_journal(Journal::in_memory()));
+
+    h.commands
+        .send(OpCommand::Copy {
+            sources: vec![
+                h.registry.clone().register(done),
+                h.registry.clone().register(stream),
+            ],
+            destination: h.registry.clone().register(dest.clone()),
+            options: TransferOptions::default(),
+            session: h.session,
+        })
+        .unwrap();
+    let Event::JobStarted { job, .. } = h.next().await else {
+        panic!("expected JobStarted")
+    };
+    while !dest.join("done.txt").exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    // Unblock the pending read once the cancel is in, as the job is
+    // waiting on the fifo by now
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    h.commands.send(OpCommand::Cancel(job, h.session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    writer.write_all(b"1").unwrap();
+
+    let events = h.until_complete().await;
+    assert!(!completed(&events).0);
+    assert_eq!(
+        history(&events).unwrap().0.as_deref(),
+        Some("Copy \"done.txt\"")
+    );
+    h.commands.send(OpCommand::Undo(h.session)).unwrap();
+    assert!(completed(&h.until_complete().await).0);
+    assert!(!dest.join("done.txt").exists());
+}
+
+#[test]
+fn test_undo_create_folder_requires_it_empty() {
+    let (_dir, root) = tempdir();
+    let journal = Journal::in_memory();
+    let session = SessionId::new();
+    create_folder(&journal, session, &root.join("new"));
+    touch(&root.join("new/inside"));
+
+    assert!(refusal(journal.undo(session)).contains("is not empty"));
+    fs::remove_file(root.join("new/inside")).unwrap();
+    journal.undo(session).unwrap();
+    assert!(!root.join("new").exists());
+}
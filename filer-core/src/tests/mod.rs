@@ -1,13 +1,39 @@
 mod actor_test;
+mod archive_test;
+mod backup_test;
+mod bisync_test;
+mod compare_test;
+mod common;
 mod scanner_test;
 mod bus_test;
+mod cache_test;
+mod conflict_test;
 mod crypto_test;
+mod delta_test;
+mod diff_test;
 mod error_test;
+mod hash_test;
+mod permissions_test;
+mod journal_test;
 mod mime_test;
 mod model_test;
 mod navigator_test;
+mod sessions_test;
+mod views_test;
+mod selection_test;
+mod operations_test;
 mod pipeline_test;
+mod poller_test;
+mod rename_test;
+mod scheduler_test;
 mod session_manager_test;
 mod session_test;
+mod stream_test;
+mod system_test;
+mod thumbnail_test;
+mod trash_test;
+mod tags_test;
+mod xattr_test;
+mod watcher_test;
 mod utils_test;
 mod vfs_test;
@@ -0,0 +1,403 @@
+//! Tests for the transfer engine and the Operations actor
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use flume::Receiver;
+use tokio::time::timeout;
+
+use crate::actors::operations::{OpCommand, OperationsConfig};
+use crate::api::events::{Event, JobOutcome};
+use crate::errors::CoreError;
+use crate::model::session::SessionId;
+use crate::services::transfer::{
+    ConflictAnswer, ConflictResolution, JobControl, ProgressTracker, Transfer, TransferMode,
+    TransferObserver, TransferOptions,
+};
+use crate::tests::common::{spawn_operations, tree};
+
+#[derive(Default)]
+struct Recorder {
+    updates: usize,
+    last_bytes: u64,
+    failed: Vec<PathBuf>,
+}
+
+impl TransferObserver for Recorder {
+    fn progress(&mut self, tracker: &ProgressTracker, _current: &Path) {
+        assert!(tracker.bytes_done >= self.last_bytes, "progress went backwards");
+        self.updates += 1;
+        self.last_bytes = tracker.bytes_done;
+    }
+
+    fn failed(&mut self, path: &Path, _error: &CoreError) {
+        self.failed.push(path.to_path_buf());
+    }
+}
+
+// ===== JobControl / ProgressTracker Tests =====
+
+#[test]
+fn test_job_control_transitions() {
+    let control = JobControl::new();
+    assert!(control.checkpoint().is_ok());
+    assert!(!control.resume());
+    assert!(control.pause());
+    assert!(control.is_paused());
+    assert!(!control.pause());
+    assert!(control.resume());
+
+    control.cancel();
+    assert!(control.is_cancelled());
+    assert!(!control.pause());
+    assert!(matches!(control.checkpoint(), Err(CoreError::Cancelled)));
+}
+
+#[test]
+fn test_job_control_checkpoint_blocks_while_paused() {
+    let control = Arc::new(JobControl::new());
+    control.pause();
+    let worker = {
+        let control = control.clone();
+        std::thread::spawn(move || control.checkpoint())
+    };
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(!worker.is_finished());
+
+    // Cancelling wakes the paused worker
+    control.cancel();
+    assert!(matches!(worker.join().unwrap(), Err(CoreError::Cancelled)));
+}
+
+#[test]
+fn test_progress_tracker_rate_and_eta() {
+    let start = Instant::now();
+    let mut tracker = ProgressTracker::new(3000, 2);
+    assert_eq!(tracker.eta(start), None);
+
+    tracker.add_bytes(1000, start);
+    tracker.add_bytes(1000, start + Duration::from_secs(1));
+    assert_eq!(tracker.bytes_done, 2000);
+    assert_eq!(tracker.throughput(start + Duration::from_secs(1)), 1000);
+    assert_eq!(tracker.eta(start + Duration::from_secs(1)), Some(Duration::from_secs(1)));
+
+    tracker.file_done();
+    assert_eq!(tracker.files_done, 1);
+}
+
+// ===== Transfer Tests =====
+
+#[test]
+fn test_copy_tree_preserving_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    fs::File::options().write(true).open(src.join("a.txt")).unwrap().set_modified(old).unwrap();
+    fs::set_permissions(src.join("a.txt"), fs::Permissions::from_mode(0o640)).unwrap();
+    fs::set_permissions(src.join("sub"), fs::Permissions::from_mode(0o750)).unwrap();
+
+    let mut recorder = Recorder::default();
+    let report = Transfer::new(TransferMode::Copy, vec![src.clone()], dest.clone())
+        .with_options(TransferOptions {
+            preserve_mtime: true,
+            preserve_permissions: true,
+            ..TransferOptions::default()
+        })
+        .with_buffer_size(4096)
+        .run(&JobControl::new(), &mut recorder)
+        .unwrap();
+
+    let copy = dest.join("src");
+    assert_eq!(report.created, vec![copy.clone()]);
+    assert_eq!(report.errors, 0);
+    assert_eq!(fs::read(copy.join("a.txt")).unwrap(), b"alpha");
+    assert_eq!(fs::read(copy.join("sub/b.bin")).unwrap().len(), 10_000);
+    assert!(copy.join("sub/deeper/c").exists());
+    assert_eq!(fs::read_link(copy.join("link")).unwrap(), PathBuf::from("a.txt"));
+
+    let meta = fs::metadata(copy.join("a.txt")).unwrap();
+    assert_eq!(meta.modified().unwrap(), old);
+    assert_eq!(meta.mode() & 0o777, 0o640);
+    assert_eq!(fs::metadata(copy.join("sub")).unwrap().mode() & 0o777, 0o750);
+
+    assert_eq!(recorder.last_bytes, 10_005);
+    assert!(recorder.updates > 3);
+    // Sources are untouched
+    assert!(src.join("sub/b.bin").exists());
+}
+
+#[test]
+fn test_copy_keeps_exec_bit_without_preserving_permissions() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("run.sh");
+    fs::write(&script, b"#!/bin/sh\n").unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+
+    Transfer::new(TransferMode::Copy, vec![script], dest.clone())
+        .run(&JobControl::new(), &mut Recorder::default())
+        .unwrap();
+
+    assert_ne!(fs::metadata(dest.join("run.sh")).unwrap().mode() & 0o100, 0);
+}
+
+#[test]
+fn test_copy_reports_item_errors_and_continues() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let other = dir.path().join("other.txt");
+    fs::write(&other, b"x").unwrap();
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    // Taken target
+    fs::write(dest.join("other.txt"), b"existing").unwrap();
+
+    let mut recorder = Recorder::default();
+    let report = Transfer::new(TransferMode::Copy, vec![other.clone(), src.clone()], dest.clone())
+        .run(&JobControl::new(), &mut recorder)
+        .unwrap();
+
+    assert_eq!(report.errors, 1);
+    assert_eq!(recorder.failed, vec![other]);
+    assert_eq!(fs::read(dest.join("other.txt")).unwrap(), b"existing");
+    assert!(dest.join("src/a.txt").exists());
+}
+
+#[test]
+fn test_copy_into_itself_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+
+    let mut recorder = Recorder::default();
+    let report = Transfer::new(TransferMode::Copy, vec![src.clone()], src.join("sub"))
+        .run(&JobControl::new(), &mut recorder)
+        .unwrap();
+
+    assert_eq!(report.errors, 1);
+    assert!(report.created.is_empty());
+    assert!(!src.join("sub/src").exists());
+}
+
+#[test]
+fn test_move_same_device_renames() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let inode = fs::metadata(&src).unwrap().ino();
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+
+    let mut recorder = Recorder::default();
+    let report = Transfer::new(TransferMode::Move, vec![src.clone()], dest.clone())
+        .run(&JobControl::new(), &mut recorder)
+        .unwrap();
+
+    assert_eq!(report.created, vec![dest.join("src")]);
+    assert!(!src.exists());
+    // Same inode: renamed, not copied
+    assert_eq!(fs::metadata(dest.join("src")).unwrap().ino(), inode);
+    assert_eq!(fs::read(dest.join("src/sub/b.bin")).unwrap().len(), 10_000);
+}
+
+#[test]
+fn test_cancel_removes_partial_file() {
+    struct CancelOnFirstChunk<'a>(&'a JobControl);
+    impl TransferObserver for CancelOnFirstChunk<'_> {
+        fn progress(&mut self, tracker: &ProgressTracker, _current: &Path) {
+            if tracker.bytes_done > 0 {
+                self.0.cancel();
+            }
+        }
+        fn failed(&mut self, _path: &Path, _error: &CoreError) {}
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("big.bin");
+    fs::write(&src, vec![1u8; 64 * 1024]).unwrap();
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+
+    let control = JobControl::new();
+    let result = Transfer::new(TransferMode::Copy, vec![src.clone()], dest.clone())
+        .with_buffer_size(1024)
+        .run(&control, &mut CancelOnFirstChunk(&control));
+
+    assert!(matches!(result, Err(CoreError::Cancelled)));
+    assert!(!dest.join("big.bin").exists());
+    assert!(src.exists());
+}
+
+// ===== Operations Actor Tests =====
+
+fn config(buffer_size: usize) -> OperationsConfig {
+    OperationsConfig {
+        progress_interval: Duration::ZERO,
+        buffer_size,
+    }
+}
+
+/// Next event that is not a progress update
+async fn next_event(events: &Receiver<Event>) -> Event {
+    loop {
+        let event = timeout(Duration::from_secs(5), events.recv_async())
+            .await
+            .expect("timed out waiting for event")
+            .unwrap();
+        if !matches!(event, Event::JobProgress { .. }) {
+            return event;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_operations_copy_job_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    let h = spawn_operations(|ops| ops.with_config(config(1024)));
+
+    h.commands
+        .send(OpCommand::Copy {
+            sources: vec![h.registry.clone().register(src.clone())],
+            destination: h.registry.clone().register(dest.clone()),
+            options: TransferOptions::default(),
+            session: h.session,
+        })
+        .unwrap();
+
+    let mut progress = Vec::new();
+    let job = match timeout(Duration::from_secs(5), h.events.recv_async()).await.unwrap().unwrap() {
+        Event::JobStarted { job, .. } => job,
+        other => panic!("unexpected event: {other:?}"),
+    };
+    let outcome = loop {
+        match timeout(Duration::from_secs(5), h.events.recv_async()).await.unwrap().unwrap() {
+            Event::JobProgress { job: j, progress: p, .. } if j == job => progress.push(p),
+            Event::JobFinished { job: j, outcome, .. } if j == job => break outcome,
+            other => panic!("unexpected event: {other:?}"),
+        }
+    };
+    assert_eq!(outcome, JobOutcome::Completed);
+
+    let last = progress.last().unwrap();
+    assert_eq!((last.bytes_done, last.bytes_total), (10_005, 10_005));
+    assert_eq!((last.files_done, last.files_total), (4, 4));
+    assert!(last.current.is_some());
+
+    match next_event(&h.events).await {
+        Event::OperationComplete { success, affected, .. } => {
+            assert!(success);
+            assert_eq!(h.registry.resolve(affected[0]), Some(dest.join("src")));
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_operations_pause_resume_cancel() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("stream");
+    let c_path = std::ffi::CString::new(src.as_os_str().as_encoded_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+    // Read-write open does not block; the job's reads block until we write
+    let mut writer = fs::File::options().read(true).write(true).open(&src).unwrap();
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    let h = spawn_operations(|ops| ops.with_config(config(4)));
+
+    writer.write_all(b"1234").unwrap();
+    h.commands
+        .send(OpCommand::Copy {
+            sources: vec![h.registry.clone().register(src.clone())],
+            destination: h.registry.clone().register(dest.clone()),
+            options: TransferOptions::default(),
+            session: h.session,
+        })
+        .unwrap();
+    let Event::JobStarted { job, .. } = next_event(&h.events).await else {
+        panic!("expected JobStarted")
+    };
+
+    // Other sessions cannot see the job
+    let other = SessionId::new();
+    h.commands.send(OpCommand::Pause(job, other)).unwrap();
+    h.commands.send(OpCommand::Cancel(job, other)).unwrap();
+    h.commands
+        .send(OpCommand::Resolve {
+            job,
+            answer: ConflictAnswer {
+                resolution: ConflictResolution::Skip,
+                apply_to_all: false,
+            },
+            session: other,
+        })
+        .unwrap();
+    for _ in 0..3 {
+        assert!(matches!(
+            next_event(&h.events).await,
+            Event::Error { session, .. } if session == other
+        ));
+    }
+
+    h.commands.send(OpCommand::Pause(job, h.session)).unwrap();
+    assert!(matches!(next_event(&h.events).await, Event::JobPaused(j, _) if j == job));
+    h.commands.send(OpCommand::Resume(job, h.session)).unwrap();
+    assert!(matches!(next_event(&h.events).await, Event::JobResumed(j, _) if j == job));
+
+    // Unblock the pending read so the worker reaches its next checkpoint
+    h.commands.send(OpCommand::Cancel(job, h.session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    writer.write_all(b"5").unwrap();
+
+    match next_event(&h.events).await {
+        Event::JobFinished { outcome, .. } => assert_eq!(outcome, JobOutcome::Cancelled),
+        other => panic!("unexpected event: {other:?}"),
+    }
+    assert!(matches!(
+        next_event(&h.events).await,
+        Event::OperationComplete { success: false, .. }
+    ));
+    assert!(!dest.join("stream").exists());
+
+    // The job is gone
+    h.commands.send(OpCommand::Pause(job, h.session)).unwrap();
+    assert!(matches!(next_event(&h.events).await, Event::Error { .. }));
+}
+
+#[tokio::test]
+async fn test_operations_move_unregisters_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("file.txt");
+    fs::write(&src, b"content").unwrap();
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    let h = spawn_operations(|ops| ops.with_config(config(1024)));
+    let source = h.registry.clone().register(src.clone());
+
+    h.commands
+        .send(OpCommand::Move {
+            sources: vec![source],
+            destination: h.registry.clone().register(dest.clone()),
+            options: TransferOptions::default(),
+            session: h.session,
+        })
+        .unwrap();
+
+    loop {
+        if let Event::OperationComplete { success, affected, .. } = next_event(&h.events).await {
+            assert!(success);
+            assert_eq!(h.registry.resolve(affected[0]), Some(dest.join("file.txt")));
+            break;
+        }
+    }
+    assert!(h.registry.resolve(source).is_none());
+    assert_eq!(fs::read(dest.join("file.txt")).unwrap(), b"content");
+}
@@ -0,0 +1,428 @@
+//! Tests for mode, owner and group editing
+
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt, symlink};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::operations::{OpCommand, Operations};
+use crate::api::events::{Event, JobOutcome, OperationKind};
+use crate::errors::CoreError;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::permissions::{Accounts, ModeChange, PermissionEdit, PermissionReport};
+use crate::services::transfer::{JobControl, ProgressTracker, TransferObserver};
+
+#[derive(Default)]
+struct Recorder {
+    done: u64,
+    failed: Vec<PathBuf>,
+}
+
+impl TransferObserver for Recorder {
+    fn progress(&mut self, tracker: &ProgressTracker, _current: &Path) {
+        self.done = tracker.files_done;
+    }
+
+    fn failed(&mut self, path: &Path, _error: &CoreError) {
+        self.failed.push(path.to_path_buf());
+    }
+}
+
+fn mode(input: &str) -> ModeChange {
+    ModeChange::parse(input).unwrap()
+}
+
+fn mode_of(path: &Path) -> u32 {
+    fs::symlink_metadata(path).unwrap().mode() & 0o7777
+}
+
+/// root/{a.txt, sub/{b.sh, deep/}, link -> a.txt}
+fn tree(root: &Path) -> PathBuf {
+    let top = root.join("top");
+    fs::create_dir_all(top.join("sub/deep")).unwrap();
+    fs::write(top.join("a.txt"), b"a").unwrap();
+    fs::write(top.join("sub/b.sh"), b"b").unwrap();
+    symlink("a.txt", top.join("link")).unwrap();
+    for (path, mode) in [
+        ("", 0o700),
+        ("a.txt", 0o600),
+        ("sub", 0o700),
+        ("sub/b.sh", 0o700),
+        ("sub/deep", 0o700),
+    ] {
+        fs::set_permissions(top.join(path), fs::Permissions::from_mode(mode)).unwrap();
+    }
+    top
+}
+
+#[test]
+fn test_mode_change_octal() {
+    assert_eq!(mode("755"), ModeChange::Absolute(0o755));
+    assert_eq!(mode("0644").apply(0o777, false), 0o644);
+    assert_eq!(mode("4755").apply(0, false), 0o4755);
+    assert!(ModeChange::parse("8").is_err());
+    assert!(ModeChange::parse("17777").is_err());
+}
+
+#[test]
+fn test_mode_change_symbolic() {
+    assert_eq!(mode("u+x,g-w").apply(0o664, false), 0o744);
+    assert_eq!(mode("a=r").apply(0o777, false), 0o444);
+    assert_eq!(mode("+x").apply(0o644, false), 0o755);
+    assert_eq!(mode("go-rwx").apply(0o755, false), 0o700);
+    assert_eq!(mode("o=u").apply(0o640, false), 0o646);
+    assert_eq!(mode("g=u-w").apply(0o700, false), 0o750);
+    assert_eq!(mode("u+s,+t").apply(0o755, false), 0o5755);
+    assert_eq!(mode("ug=rw,o=").apply(0o777, false), 0o660);
+}
+
+#[test]
+fn test_mode_change_capital_x() {
+    let change = mode("a+X");
+    assert_eq!(change.apply(0o644, false), 0o644);
+    assert_eq!(change.apply(0o744, false), 0o755);
+    assert_eq!(change.apply(0o600, true), 0o711);
+}
+
+#[test]
+fn test_mode_change_keeps_setgid_on_directories() {
+    assert_eq!(mode("g=rx").apply(0o2775, true), 0o2755);
+    assert_eq!(mode("g=rx").apply(0o2775, false), 0o755);
+}
+
+#[test]
+fn test_mode_change_errors() {
+    for (input, position) in [("", 0), ("u", 1), ("u+x,", 4), ("u+q", 2), ("z+x", 0)] {
+        let error = ModeChange::parse(input).unwrap_err();
+        assert_eq!(error.position, position, "{input}");
+    }
+    assert_eq!(
+        ModeChange::parse("u+q").unwrap_err().to_string(),
+        "Invalid mode at 2: unexpected character."
+    );
+}
+
+#[test]
+fn test_accounts_resolve_names_and_ids() {
+    let accounts = Accounts::parse(
+        "# comment\nroot:x:0:0:root:/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\nbroken\n",
+        "root:x:0:\nstaff:x:50:alice\n",
+    );
+    assert_eq!(accounts.uid("alice").unwrap(), 1000);
+    assert_eq!(accounts.uid("4242").unwrap(), 4242);
+    assert_eq!(accounts.gid("staff").unwrap(), 50);
+    assert_eq!(accounts.user_name(0), Some("root"));
+    assert_eq!(accounts.group_name(50), Some("staff"));
+    assert!(matches!(
+        accounts.uid("bob"),
+        Err(CoreError::InvalidPath(_))
+    ));
+    assert!(accounts.gid("broken").is_err());
+
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("passwd"), "carol:x:7:7::/:/bin/sh\n").unwrap();
+    fs::write(dir.path().join("group"), "wheel:x:10:\n").unwrap();
+    let accounts =
+        Accounts::from_files(&dir.path().join("passwd"), &dir.path().join("group")).unwrap();
+    assert_eq!(accounts.uid("carol").unwrap(), 7);
+    assert_eq!(accounts.gid("wheel").unwrap(), 10);
+    assert!(Accounts::from_files(&dir.path().join("missing"), &dir.path().join("group")).is_err());
+}
+
+#[test]
+fn test_recursive_file_and_directory_modes() {
+    let dir = tempfile::tempdir().unwrap();
+    let top = tree(dir.path());
+    let link_mode = mode_of(&top.join("link"));
+    let edit = PermissionEdit::new()
+        .with_file_mode(mode("644"))
+        .with_dir_mode(mode("755"))
+        .with_recursive(true);
+    let mut observer = Recorder::default();
+    let report = edit
+        .run(
+            std::slice::from_ref(&top),
+            &Accounts::default(),
+            &JobControl::new(),
+            &mut observer,
+        )
+        .unwrap();
+
+    assert_eq!(report.errors, 0);
+    assert_eq!(observer.done, 6);
+    let paths: Vec<_> = report
+        .results
+        .iter()
+        .map(|r| r.path.strip_prefix(&top).unwrap())
+        .collect();
+    let expected = ["", "a.txt", "link", "sub", "sub/b.sh", "sub/deep"];
+    assert_eq!(paths, expected.map(Path::new));
+    for (path, expected) in [
+        ("", 0o755),
+        ("a.txt", 0o644),
+        ("sub", 0o755),
+        ("sub/b.sh", 0o644),
+        ("sub/deep", 0o755),
+    ] {
+        assert_eq!(mode_of(&top.join(path)), expected, "{path}");
+    }
+    assert_eq!(mode_of(&top.join("link")), link_mode);
+    assert_eq!(report.results[1].mode, 0o644);
+}
+
+#[test]
+fn test_non_recursive_edit_touches_only_selection() {
+    let dir = tempfile::tempdir().unwrap();
+    let top = tree(dir.path());
+    let edit = PermissionEdit::new().with_mode(mode("go+rX"));
+    let report = edit
+        .run(
+            std::slice::from_ref(&top),
+            &Accounts::default(),
+            &JobControl::new(),
+            &mut Recorder::default(),
+        )
+        .unwrap();
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(mode_of(&top), 0o755);
+    assert_eq!(mode_of(&top.join("sub")), 0o700);
+}
+
+#[test]
+fn test_failures_are_reported_per_item() {
+    let dir = tempfile::tempdir().unwrap();
+    let top = tree(dir.path());
+    let missing = dir.path().join("missing");
+    let mut observer = Recorder::default();
+    let report = PermissionEdit::new()
+        .with_mode(mode("u+x"))
+        .run(
+            &[missing.clone(), top.join("a.txt")],
+            &Accounts::default(),
+            &JobControl::new(),
+            &mut observer,
+        )
+        .unwrap();
+    assert_eq!(report.errors, 1);
+    assert_eq!(observer.failed, vec![missing.clone()]);
+    assert_eq!(report.results[0].path, missing);
+    assert!(report.results[0].error.is_some());
+    assert_eq!(report.results[1].error, None);
+    assert_eq!(mode_of(&top.join("a.txt")), 0o700);
+}
+
+/// Run `edit` as an unprivileged user that owns everything under `dir`
+///
+/// Root reads any directory, so it drops to another filesystem UID on a
+/// thread of its own; `setfsuid` only affects the calling thread.
+fn run_as_owner(dir: &Path, paths: &[PathBuf], edit: &PermissionEdit) -> PermissionReport {
+    let run = || {
+        edit.run(
+            paths,
+            &Accounts::default(),
+            &JobControl::new(),
+            &mut Recorder::default(),
+        )
+        .unwrap()
+    };
+    // SAFETY: plain syscalls without pointers
+    if unsafe { libc::geteuid() } != 0 {
+        return run();
+    }
+    const NOBODY: u32 = 65534;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        std::os::unix::fs::lchown(&path, Some(NOBODY), Some(NOBODY)).unwrap();
+        if fs::symlink_metadata(&path).unwrap().is_dir() {
+            pending.extend(fs::read_dir(&path).unwrap().map(|e| e.unwrap().path()));
+        }
+    }
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                // SAFETY: as above
+                unsafe {
+                    libc::setfsgid(NOBODY);
+                    libc::setfsuid(NOBODY);
+                }
+                run()
+            })
+            .join()
+            .unwrap()
+    })
+}
+
+#[test]
+fn test_recursive_edit_of_unreadable_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let top = tree(dir.path());
+    let sub = top.join("sub");
+    fs::set_permissions(&sub, fs::Permissions::from_mode(0o000)).unwrap();
+
+    // Opening a directory up comes before its contents are listed
+    let edit = PermissionEdit::new()
+        .with_dir_mode(mode("u+rwx"))
+        .with_file_mode(mode("u-x"))
+        .with_recursive(true);
+    let report = run_as_owner(dir.path(), std::slice::from_ref(&top), &edit);
+    assert_eq!(report.errors, 0);
+    assert_eq!(report.results.len(), 6);
+    assert_eq!(mode_of(&sub), 0o700);
+    assert_eq!(mode_of(&sub.join("b.sh")), 0o600);
+
+    // Closing one comes after
+    let edit = PermissionEdit::new()
+        .with_dir_mode(mode("a-rwx"))
+        .with_recursive(true);
+    let report = run_as_owner(dir.path(), std::slice::from_ref(&sub), &edit);
+    assert_eq!(report.errors, 0);
+    assert_eq!(mode_of(&sub), 0o000);
+    assert_eq!(mode_of(&sub.join("deep")), 0o000);
+
+    // One that stays closed still changes, only its contents fail
+    let edit = PermissionEdit::new()
+        .with_dir_mode(mode("g+w"))
+        .with_recursive(true);
+    let report = run_as_owner(dir.path(), std::slice::from_ref(&sub), &edit);
+    assert_eq!(report.errors, 1);
+    assert_eq!(mode_of(&sub), 0o020);
+    assert_eq!(report.results[0].mode, 0o020);
+    assert!(report.results[0].error.is_some());
+    assert_eq!(mode_of(&sub.join("deep")), 0o000);
+}
+
+#[test]
+fn test_owner_and_group_by_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let top = tree(dir.path());
+    let meta = fs::metadata(&top).unwrap();
+    let accounts = Accounts::parse(
+        &format!("me:x:{}:{}::/:/bin/sh\n", meta.uid(), meta.gid()),
+        &format!("us:x:{}:\n", meta.gid()),
+    );
+    let report = PermissionEdit::new()
+        .with_owner("me")
+        .with_group("us")
+        .with_recursive(true)
+        .run(
+            std::slice::from_ref(&top),
+            &accounts,
+            &JobControl::new(),
+            &mut Recorder::default(),
+        )
+        .unwrap();
+    assert_eq!(report.errors, 0);
+    assert!(
+        report
+            .results
+            .iter()
+            .all(|r| r.uid == meta.uid() && r.gid == meta.gid())
+    );
+
+    let unknown = PermissionEdit::new().with_owner("nobody-here").run(
+        std::slice::from_ref(&top),
+        &accounts,
+        &JobControl::new(),
+        &mut Recorder::default(),
+    );
+    assert!(matches!(unknown, Err(CoreError::InvalidPath(_))));
+    assert!(matches!(
+        PermissionEdit::new().run(
+            &[top],
+            &accounts,
+            &JobControl::new(),
+            &mut Recorder::default()
+        ),
+        Err(CoreError::InvalidPath(_))
+    ));
+}
+
+#[test]
+fn test_cancelled_edit_changes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let top = tree(dir.path());
+    let control = JobControl::new();
+    control.cancel();
+    let result = PermissionEdit::new()
+        .with_mode(mode("777"))
+        .with_recursive(true)
+        .run(
+            std::slice::from_ref(&top),
+            &Accounts::default(),
+            &control,
+            &mut Recorder::default(),
+        );
+    assert!(matches!(result, Err(CoreError::Cancelled)));
+    assert_eq!(mode_of(&top), 0o700);
+}
+
+#[tokio::test]
+async fn test_operations_change_permissions_job() {
+    let dir = tempfile::tempdir().unwrap();
+    let top = tree(dir.path());
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, events) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    tokio::spawn(Operations::new(cmd_rx, evt_tx, registry.clone()).run());
+    let session = SessionId::new();
+    let missing = dir.path().join("missing");
+
+    cmd_tx
+        .send(OpCommand::ChangePermissions {
+            nodes: vec![
+                registry.clone().register(top.clone()),
+                registry.clone().register(missing),
+            ],
+            edit: PermissionEdit::new()
+                .with_file_mode(mode("a=rX"))
+                .with_dir_mode(mode("u=rwx,go=rx"))
+                .with_recursive(true),
+            session,
+        })
+        .unwrap();
+
+    let mut report = None;
+    let outcome = loop {
+        let event = timeout(Duration::from_secs(5), events.recv_async())
+            .await
+            .expect("timed out waiting for event")
+            .unwrap();
+        match event {
+            Event::PermissionsChanged { report: r, .. } => report = Some(r),
+            Event::JobFinished { outcome, .. } => break outcome,
+            _ => {}
+        }
+    };
+    assert_eq!(outcome, JobOutcome::Failed { errors: 1 });
+    let report = report.unwrap();
+    assert_eq!(report.results.len(), 7);
+    assert_eq!(mode_of(&top.join("sub/b.sh")), 0o555);
+    assert_eq!(mode_of(&top.join("a.txt")), 0o444);
+    assert_eq!(mode_of(&top.join("sub/deep")), 0o755);
+
+    cmd_tx
+        .send(OpCommand::ChangePermissions {
+            nodes: vec![registry.clone().register(top)],
+            edit: PermissionEdit::new(),
+            session,
+        })
+        .unwrap();
+    loop {
+        let event = timeout(Duration::from_secs(5), events.recv_async())
+            .await
+            .expect("timed out waiting for event")
+            .unwrap();
+        if let Event::OperationComplete {
+            operation, success, ..
+        } = event
+        {
+            assert!(matches!(operation, OperationKind::ChangePermissions));
+            assert!(!success);
+            break;
+        }
+    }
+}
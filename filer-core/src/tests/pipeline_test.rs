@@ -27,6 +27,7 @@ fn make_file(name: &str, size: u64, hidden: bool) -> FileNode {
             hidden,
             readonly: false,
             permissions: None,
+            ..Default::default()
         },
     }
 }
@@ -46,6 +47,7 @@ fn make_file_with_ext(name: &str, ext: Option<&str>, size: u64) -> FileNode {
             hidden: false,
             readonly: false,
             permissions: None,
+            ..Default::default()
         },
     }
 }
@@ -65,6 +67,7 @@ fn make_dir(name: &str, hidden: bool) -> FileNode {
             hidden,
             readonly: false,
             permissions: None,
+            ..Default::default()
         },
     }
 }
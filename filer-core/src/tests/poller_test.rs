@@ -0,0 +1,377 @@
+//! Tests for polling-based change detection
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::navigator::{NavCommand, Navigator};
+use crate::actors::poller::{PollCommand, Poller, PollerConfig};
+use crate::api::events::Event;
+use crate::errors::CoreError;
+use crate::model::fs_change::FsChangeKind;
+use crate::model::node::{FileNode, NodeId, NodeKind, NodeMeta};
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::scheduler::{DeviceKey, IoClass, IoScheduler, IoTicket, SchedulerConfig};
+use crate::services::watch::Snapshot;
+use crate::vfs::provider::{Capabilities, FsProvider};
+
+fn remote_file(name: &str, size: u64, mtime: u64, etag: Option<&str>) -> FileNode {
+    FileNode {
+        id: NodeId(0),
+        name: name.to_string(),
+        path: PathBuf::from(format!("/bucket/{name}")),
+        kind: NodeKind::File { extension: None },
+        size,
+        modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)),
+        created: None,
+        meta: NodeMeta {
+            etag: etag.map(str::to_string),
+            ..Default::default()
+        },
+    }
+}
+
+/// Remote-like provider whose listing can be swapped between polls
+#[derive(Clone, Default)]
+struct RemoteMock {
+    listing: Arc<Mutex<Vec<FileNode>>>,
+    calls: Arc<Mutex<usize>>,
+    /// Listing this directory never finishes
+    stuck: Arc<Mutex<Option<PathBuf>>>,
+    stuck_calls: Arc<Mutex<usize>>,
+}
+
+impl RemoteMock {
+    fn set(&self, nodes: Vec<FileNode>) {
+        *self.listing.lock().unwrap() = nodes;
+    }
+
+    fn calls(&self) -> usize {
+        *self.calls.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl FsProvider for RemoteMock {
+    fn scheme(&self) -> &'static str {
+        "mock-remote"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            write: false,
+            watch: false,
+            search: false,
+            xattrs: false,
+        }
+    }
+
+    async fn list(&self, path: &Path) -> Result<Vec<FileNode>, CoreError> {
+        if self.stuck.lock().unwrap().as_deref() == Some(path) {
+            *self.stuck_calls.lock().unwrap() += 1;
+            std::future::pending::<()>().await;
+        }
+        *self.calls.lock().unwrap() += 1;
+        Ok(self.listing.lock().unwrap().clone())
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, CoreError> {
+        Err(CoreError::NotFound(path.to_path_buf()))
+    }
+
+    async fn read_range(&self, path: &Path, _start: u64, _len: u64) -> Result<Vec<u8>, CoreError> {
+        Err(CoreError::NotFound(path.to_path_buf()))
+    }
+
+    async fn exists(&self, _path: &Path) -> Result<bool, CoreError> {
+        Ok(false)
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
+        Err(CoreError::NotFound(path.to_path_buf()))
+    }
+}
+
+// ===== Snapshot Tests =====
+
+#[test]
+fn test_snapshot_diff_created_deleted_modified() {
+    let before = Snapshot::from_nodes(&[
+        remote_file("keep", 1, 1, None),
+        remote_file("gone", 1, 1, None),
+        remote_file("edit", 1, 1, None),
+    ]);
+    let after = Snapshot::from_nodes(&[
+        remote_file("keep", 1, 1, None),
+        remote_file("edit", 2, 5, None),
+        remote_file("new", 1, 1, None),
+    ]);
+
+    assert_eq!(
+        before.diff(&after),
+        vec![
+            (PathBuf::from("/bucket/new"), FsChangeKind::Created),
+            (PathBuf::from("/bucket/gone"), FsChangeKind::Deleted),
+            (PathBuf::from("/bucket/edit"), FsChangeKind::Modified),
+        ]
+    );
+}
+
+#[test]
+fn test_snapshot_diff_etag_change_is_modification() {
+    let before = Snapshot::from_nodes(&[remote_file("obj", 10, 1, Some("a"))]);
+    let after = Snapshot::from_nodes(&[remote_file("obj", 10, 1, Some("b"))]);
+    assert_eq!(
+        before.diff(&after),
+        vec![(PathBuf::from("/bucket/obj"), FsChangeKind::Modified)]
+    );
+}
+
+#[test]
+fn test_snapshot_diff_detects_rename_by_etag() {
+    let before = Snapshot::from_nodes(&[remote_file("a.bin", 10, 1, Some("e1"))]);
+    let after = Snapshot::from_nodes(&[remote_file("b.bin", 10, 2, Some("e1"))]);
+    assert_eq!(
+        before.diff(&after),
+        vec![(
+            PathBuf::from("/bucket/b.bin"),
+            FsChangeKind::Renamed {
+                from: PathBuf::from("/bucket/a.bin")
+            }
+        )]
+    );
+
+    // Without an ETag a rename cannot be proven
+    let before = Snapshot::from_nodes(&[remote_file("a.bin", 10, 1, None)]);
+    let after = Snapshot::from_nodes(&[remote_file("b.bin", 10, 1, None)]);
+    assert_eq!(before.diff(&after).len(), 2);
+}
+
+#[test]
+fn test_snapshot_diff_identical_is_empty() {
+    let nodes = [remote_file("x", 1, 1, Some("e"))];
+    assert!(Snapshot::from_nodes(&nodes).diff(&Snapshot::from_nodes(&nodes)).is_empty());
+}
+
+// ===== PollerConfig Tests =====
+
+#[test]
+fn test_poller_backoff_when_idle() {
+    let config = PollerConfig {
+        interval: Duration::from_secs(5),
+        jitter: 0.0,
+        idle_after: Duration::from_secs(60),
+        max_interval: Duration::from_secs(30),
+    };
+    let mut interval = config.interval;
+    interval = config.next_interval(interval, true);
+    assert_eq!(interval, Duration::from_secs(10));
+    interval = config.next_interval(interval, true);
+    interval = config.next_interval(interval, true);
+    assert_eq!(interval, Duration::from_secs(30));
+    assert_eq!(config.next_interval(interval, false), Duration::from_secs(5));
+}
+
+#[test]
+fn test_poller_jitter_bounds() {
+    let config = PollerConfig {
+        jitter: 0.2,
+        ..Default::default()
+    };
+    let base = Duration::from_secs(10);
+    assert_eq!(config.jittered(base, 0.0), Duration::from_secs(8));
+    assert_eq!(config.jittered(base, 0.5), base);
+    assert!(config.jittered(base, 0.999) <= Duration::from_secs(12));
+}
+
+// ===== Poller Actor Tests =====
+
+fn fast_config() -> PollerConfig {
+    PollerConfig {
+        interval: Duration::from_millis(30),
+        jitter: 0.0,
+        idle_after: Duration::from_secs(60),
+        max_interval: Duration::from_millis(200),
+    }
+}
+
+#[tokio::test]
+async fn test_poller_emits_changes_and_invalidates() {
+    let provider = RemoteMock::default();
+    provider.set(vec![remote_file("a", 1, 1, None)]);
+    let reg = NodeRegistry::new();
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let (nav_tx, nav_rx) = flume::unbounded();
+    let poller = Poller::new(cmd_rx, evt_tx, Arc::new(provider.clone()), reg.clone())
+        .with_navigator(nav_tx)
+        .with_config(fast_config());
+    tokio::spawn(poller.run());
+
+    let session = SessionId::new();
+    let root = PathBuf::from("/bucket");
+    cmd_tx.send(PollCommand::Watch(root.clone(), session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    provider.set(vec![remote_file("a", 1, 1, None), remote_file("b", 1, 1, None)]);
+    match timeout(Duration::from_secs(1), evt_rx.recv_async()).await.unwrap().unwrap() {
+        Event::FsChanged { node, kind, session: s } => {
+            assert_eq!(reg.resolve(node), Some(PathBuf::from("/bucket/b")));
+            assert_eq!(kind, FsChangeKind::Created);
+            assert_eq!(s, session);
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+    match nav_rx.recv_async().await.unwrap() {
+        NavCommand::Invalidate(node) => assert_eq!(reg.resolve(node), Some(root)),
+        other => panic!("unexpected nav command: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_poller_stops_when_nothing_watched() {
+    let provider = RemoteMock::default();
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, _evt_rx) = flume::unbounded();
+    let poller = Poller::new(cmd_rx, evt_tx, Arc::new(provider.clone()), NodeRegistry::new())
+        .with_config(fast_config());
+    tokio::spawn(poller.run());
+
+    let session = SessionId::new();
+    cmd_tx.send(PollCommand::Watch(PathBuf::from("/bucket"), session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(provider.calls() >= 2);
+
+    cmd_tx.send(PollCommand::Unwatch(PathBuf::from("/bucket"), session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let calls = provider.calls();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(provider.calls(), calls);
+}
+
+#[tokio::test]
+async fn test_poller_backs_off_for_idle_sessions() {
+    let provider = RemoteMock::default();
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, _evt_rx) = flume::unbounded();
+    let config = PollerConfig {
+        idle_after: Duration::ZERO,
+        ..fast_config()
+    };
+    let poller = Poller::new(cmd_rx, evt_tx, Arc::new(provider.clone()), NodeRegistry::new())
+        .with_config(config);
+    tokio::spawn(poller.run());
+
+    cmd_tx.send(PollCommand::Watch(PathBuf::from("/bucket"), SessionId::new())).unwrap();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+
+    // 30, 60, 120, 200, 200ms... is far fewer polls than a fixed 30ms rate
+    assert!(provider.calls() < 10, "polled {} times", provider.calls());
+}
+
+#[tokio::test]
+async fn test_poller_waits_for_a_scheduler_slot() {
+    let provider = RemoteMock::default();
+    let scheduler = IoScheduler::new(SchedulerConfig {
+        local_slots: 1,
+        reserved: 0,
+        ..Default::default()
+    });
+    let root = PathBuf::from("/bucket");
+    let busy = scheduler
+        .request(IoTicket::new(IoClass::Bulk).on(DeviceKey::of_path(&root)))
+        .granted()
+        .await;
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, _evt_rx) = flume::unbounded();
+    let poller = Poller::new(cmd_rx, evt_tx, Arc::new(provider.clone()), NodeRegistry::new())
+        .with_scheduler(scheduler.clone())
+        .with_config(fast_config());
+    tokio::spawn(poller.run());
+
+    cmd_tx.send(PollCommand::Watch(root, SessionId::new())).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(provider.calls(), 0);
+    assert_eq!(scheduler.queued(), 1);
+
+    drop(busy);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(provider.calls() >= 1);
+}
+
+#[tokio::test]
+async fn test_poller_hanging_directory_blocks_nothing_else() {
+    let provider = RemoteMock::default();
+    *provider.stuck.lock().unwrap() = Some(PathBuf::from("/slow"));
+    provider.set(vec![remote_file("a", 1, 1, None)]);
+    let reg = NodeRegistry::new();
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let poller = Poller::new(cmd_rx, evt_tx, Arc::new(provider.clone()), reg.clone())
+        .with_config(fast_config());
+    tokio::spawn(poller.run());
+
+    let session = SessionId::new();
+    cmd_tx.send(PollCommand::Watch(PathBuf::from("/slow"), session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    cmd_tx.send(PollCommand::Watch(PathBuf::from("/bucket"), session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    provider.set(vec![remote_file("a", 1, 1, None), remote_file("b", 1, 1, None)]);
+    match timeout(Duration::from_secs(1), evt_rx.recv_async()).await.unwrap().unwrap() {
+        Event::FsChanged { node, .. } => {
+            assert_eq!(reg.resolve(node), Some(PathBuf::from("/bucket/b")))
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+    // The hanging poll is not started again on top of itself
+    assert_eq!(*provider.stuck_calls.lock().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_poller_follows_navigator_activity() {
+    let provider = RemoteMock::default();
+    let registry = NodeRegistry::new();
+    let (poll_tx, poll_rx) = flume::unbounded();
+    let (nav_tx, nav_rx) = flume::unbounded();
+    let (evt_tx, _evt_rx) = flume::unbounded();
+    let (scan_tx, _scan_rx) = flume::unbounded();
+    let config = PollerConfig {
+        idle_after: Duration::from_millis(150),
+        max_interval: Duration::from_secs(5),
+        ..fast_config()
+    };
+    let poller = Poller::new(poll_rx, evt_tx.clone(), Arc::new(provider.clone()), registry.clone())
+        .with_config(config);
+    tokio::spawn(poller.run());
+    tokio::spawn(Navigator::new(nav_rx, evt_tx, scan_tx, registry).with_poller(poll_tx.clone()).run());
+
+    // Left alone, the session goes idle and polls back off to seconds
+    let session = SessionId::new();
+    poll_tx.send(PollCommand::Watch(PathBuf::from("/bucket"), session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(900)).await;
+    let idle = provider.calls();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(provider.calls() <= idle + 1, "polled {} times while idle", provider.calls() - idle);
+
+    // Navigating makes it active again
+    let active = provider.calls();
+    for _ in 0..6 {
+        nav_tx.send(NavCommand::Refresh(session)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(provider.calls() >= active + 4, "polled {} times while active", provider.calls() - active);
+
+    // Closing the session stops its polls
+    nav_tx.send(NavCommand::CloseSession(session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let closed = provider.calls();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(provider.calls(), closed);
+}
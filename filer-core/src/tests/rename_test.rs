@@ -0,0 +1,465 @@
+//! Tests for batch rename rules, plans and the Operations actor
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::actors::operations::OpCommand;
+use crate::api::events::Event;
+use crate::model::node::FileNode;
+use crate::services::journal::Journal;
+use crate::services::metadata::{
+    AudioMetadata, AudioTags, ExifData, ExtendedMetadata, ImageMetadata,
+};
+use crate::services::rename::{
+    self, CaseTransform, Counter, FindReplace, RenamePlan, RenameRule, RenameStatus, RenameSubject,
+    Renamer,
+};
+use crate::tests::common::{Harness, spawn_operations, tempdir};
+
+/// Subject for `path`, created with its own name as content
+fn subject(path: &Path) -> RenameSubject {
+    if !path.exists() {
+        fs::write(path, path.file_name().unwrap().as_encoded_bytes()).unwrap();
+    }
+    RenameSubject::new(FileNode::from_path(path.to_path_buf(), None).unwrap())
+}
+
+fn template(template: &str) -> RenameRule {
+    RenameRule {
+        template: Some(template.to_string()),
+        ..RenameRule::default()
+    }
+}
+
+fn find(find: &str, replace: &str, regex: bool) -> Option<FindReplace> {
+    Some(FindReplace {
+        find: find.to_string(),
+        replace: replace.to_string(),
+        regex,
+        ignore_case: false,
+    })
+}
+
+fn names(rule: &RenameRule, subjects: &[RenameSubject]) -> Vec<Result<String, String>> {
+    let renamer = Renamer::new(rule).unwrap();
+    subjects
+        .iter()
+        .enumerate()
+        .map(|(i, s)| renamer.name(s, i))
+        .collect()
+}
+
+fn ok(name: &str) -> Result<String, String> {
+    Ok(name.to_string())
+}
+
+// ===== Rule Tests =====
+
+#[test]
+fn test_rule_rejects_bad_templates_and_patterns() {
+    for (bad, position) in [
+        ("{name", 0),
+        ("ab}", 2),
+        ("a{bogus}", 1),
+        ("{n:x}", 0),
+        ("{size:3}", 0),
+        ("{mtime:%Q}", 0),
+    ] {
+        let err = Renamer::new(&template(bad)).unwrap_err();
+        assert_eq!(err.position, Some(position), "{bad}: {err}");
+    }
+    let rule = RenameRule {
+        find: find("(", "", true),
+        ..RenameRule::default()
+    };
+    assert_eq!(Renamer::new(&rule).unwrap_err().position, None);
+    assert!(Renamer::new(&template("{{literal}}")).is_ok());
+}
+
+#[test]
+fn test_rule_template_counter_and_case() {
+    let (_dir, root) = tempdir();
+    fs::create_dir(root.join("Trip.2023")).unwrap();
+    let subjects = [
+        subject(&root.join("IMG_01.JPG")),
+        subject(&root.join("notes")),
+        subject(&root.join("Trip.2023")),
+    ];
+    let rule = RenameRule {
+        template: Some("Holiday {n}{ext}".to_string()),
+        case: CaseTransform::Lower,
+        counter: Counter {
+            start: 8,
+            step: 2,
+            width: 3,
+        },
+        ..RenameRule::default()
+    };
+    // Directories have no extension
+    assert_eq!(
+        names(&rule, &subjects),
+        [ok("holiday 008.jpg"), ok("holiday 010"), ok("holiday 012")]
+    );
+
+    let rule = RenameRule {
+        case: CaseTransform::Title,
+        ..template("{{{name}}}_{n:2}{ext}")
+    };
+    assert_eq!(names(&rule, &subjects[..1]), [ok("{Img_01}_01.jpg")]);
+
+    let rule = RenameRule {
+        case: CaseTransform::Upper,
+        ..template("{parent}")
+    };
+    let parent = root.file_name().unwrap().to_string_lossy().to_uppercase();
+    assert_eq!(names(&rule, &subjects[..1]), [Ok(parent)]);
+}
+
+#[test]
+fn test_rule_find_replace() {
+    let (_dir, root) = tempdir();
+    let subjects = [
+        subject(&root.join("log-2024-01-31.txt")),
+        subject(&root.join("a.b.c")),
+    ];
+
+    let rule = RenameRule {
+        find: find(r"(\d{4})-(\d{2})-(\d{2})", "$3.$2.$1", true),
+        ..RenameRule::default()
+    };
+    assert_eq!(names(&rule, &subjects)[0], ok("log-31.01.2024.txt"));
+
+    // Literal search treats regex syntax and `$` as plain text
+    let rule = RenameRule {
+        find: find(".", "$1", false),
+        ..RenameRule::default()
+    };
+    assert_eq!(names(&rule, &subjects)[1], ok("a$1b$1c"));
+
+    // Find/replace runs first, so `{name}` sees its result
+    let rule = RenameRule {
+        find: Some(FindReplace {
+            find: "LOG".to_string(),
+            replace: "app".to_string(),
+            regex: false,
+            ignore_case: true,
+        }),
+        ..template("{name}-old{ext}")
+    };
+    assert_eq!(names(&rule, &subjects)[0], ok("app-2024-01-31-old.txt"));
+}
+
+#[test]
+fn test_rule_file_tokens() {
+    let (_dir, root) = tempdir();
+    let path = root.join("report.pdf");
+    fs::write(&path, vec![0u8; 1234]).unwrap();
+    let mid_2021 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_623_700_000);
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(mid_2021)
+        .unwrap();
+
+    assert_eq!(
+        names(&template("{mtime:%Y}_{size}{ext}"), &[subject(&path)]),
+        [ok("2021_1234.pdf")]
+    );
+}
+
+#[test]
+fn test_rule_metadata_tokens() {
+    let (_dir, root) = tempdir();
+    let photo =
+        subject(&root.join("DSC0001.jpg")).with_metadata(ExtendedMetadata::Image(ImageMetadata {
+            width: 10,
+            height: 10,
+            format: "jpeg".to_string(),
+            color_space: None,
+            bit_depth: None,
+            has_alpha: false,
+            exif: Some(ExifData {
+                camera_make: None,
+                camera_model: None,
+                date_taken: Some("2023:07:14 18:22:05".to_string()),
+                gps_latitude: None,
+                gps_longitude: None,
+                exposure_time: None,
+                f_number: None,
+                iso: None,
+                focal_length: None,
+                orientation: None,
+                raw: HashMap::new(),
+            }),
+        }));
+    let song =
+        subject(&root.join("track.mp3")).with_metadata(ExtendedMetadata::Audio(AudioMetadata {
+            duration_secs: 1.0,
+            sample_rate: None,
+            channels: None,
+            bit_rate: None,
+            format: "mp3".to_string(),
+            tags: AudioTags {
+                artist: Some("Nina Simone".to_string()),
+                track: Some(7),
+                ..AudioTags::default()
+            },
+        }));
+    let plain = subject(&root.join("plain.jpg"));
+
+    let rule = template("{taken:%Y%m%d_%H%M}{ext}");
+    assert!(Renamer::new(&rule).unwrap().needs_metadata());
+    assert!(
+        !Renamer::new(&template("{name}{ext}"))
+            .unwrap()
+            .needs_metadata()
+    );
+    assert_eq!(
+        names(&rule, &[photo, plain]),
+        [
+            ok("20230714_1822.jpg"),
+            Err("no EXIF date taken".to_string())
+        ]
+    );
+
+    let rule = template("{track} - {artist}{ext}");
+    assert_eq!(
+        names(&rule, std::slice::from_ref(&song)),
+        [ok("07 - Nina Simone.mp3")]
+    );
+    assert_eq!(
+        names(&template("{album}"), &[song]),
+        [Err("no album tag".to_string())]
+    );
+}
+
+// ===== Plan Tests =====
+
+fn plan(rule: &RenameRule, paths: &[PathBuf]) -> RenamePlan {
+    let subjects: Vec<_> = paths.iter().map(|p| subject(p)).collect();
+    RenamePlan::new(&Renamer::new(rule).unwrap(), &subjects)
+}
+
+fn statuses(plan: &RenamePlan) -> Vec<RenameStatus> {
+    plan.items.iter().map(|i| i.status.clone()).collect()
+}
+
+fn contents(root: &Path, names: &[&str]) -> Vec<String> {
+    names
+        .iter()
+        .map(|n| fs::read_to_string(root.join(n)).unwrap())
+        .collect()
+}
+
+/// `{n}` names counting from `start`
+fn numbered(start: u64) -> RenameRule {
+    RenameRule {
+        counter: Counter {
+            start,
+            ..Counter::default()
+        },
+        ..template("{n}")
+    }
+}
+
+#[test]
+fn test_plan_flags_collisions() {
+    let (_dir, root) = tempdir();
+    let paths = [
+        root.join("a.txt"),
+        root.join("b.txt"),
+        root.join("taken.txt"),
+    ];
+
+    let plan_same = plan(&template("same{ext}"), &paths[..2]);
+    assert_eq!(
+        statuses(&plan_same),
+        [RenameStatus::Duplicate, RenameStatus::Duplicate]
+    );
+    assert!(!plan_same.is_clean());
+    assert!(plan_same.apply().is_err());
+    assert!(paths[0].exists() && paths[1].exists());
+
+    let rule = RenameRule {
+        find: find("^a", "taken", true),
+        ..RenameRule::default()
+    };
+    let plan_taken = plan(&rule, &paths);
+    assert_eq!(
+        statuses(&plan_taken),
+        [
+            RenameStatus::Exists,
+            RenameStatus::Unchanged,
+            RenameStatus::Unchanged
+        ]
+    );
+
+    let rule = template("{name}/x");
+    assert!(
+        matches!(&statuses(&plan(&rule, &paths[..1]))[0], RenameStatus::Invalid(r) if r.contains("separator"))
+    );
+
+    let listed_twice = plan(&template("x{n}"), &[paths[0].clone(), paths[0].clone()]);
+    assert!(matches!(&statuses(&listed_twice)[1], RenameStatus::Invalid(r) if r == "listed twice"));
+}
+
+#[test]
+fn test_plan_blocked_names_cascade() {
+    let (_dir, root) = tempdir();
+    fs::write(root.join("4"), b"outside").unwrap();
+    // 1→2, 2→3, 3→4 with 4 outside the batch: 3 is blocked, so 2 cannot
+    // take its name and 1 cannot take 2's
+    let plan = plan(
+        &numbered(2),
+        &[root.join("1"), root.join("2"), root.join("3")],
+    );
+    assert_eq!(statuses(&plan), vec![RenameStatus::Exists; 3]);
+}
+
+#[test]
+fn test_plan_applies_chains_back_to_front() {
+    let (_dir, root) = tempdir();
+    let plan = plan(
+        &numbered(2),
+        &[root.join("1"), root.join("2"), root.join("3")],
+    );
+    assert!(plan.is_clean() && plan.cycles.is_empty());
+
+    plan.apply().unwrap();
+    assert_eq!(contents(&root, &["2", "3", "4"]), ["1", "2", "3"]);
+    assert!(!root.join("1").exists());
+}
+
+#[test]
+fn test_plan_swaps_cycles_through_temporary_names() {
+    let (_dir, root) = tempdir();
+    // Listed as 2, 3, 1 and renumbered: 2→1, 3→2, 1→3
+    let plan = plan(
+        &numbered(1),
+        &[root.join("2"), root.join("3"), root.join("1")],
+    );
+    assert!(plan.is_clean());
+    assert_eq!(plan.cycles.len(), 1);
+    assert_eq!(plan.cycles[0].len(), 3);
+
+    plan.apply().unwrap();
+    assert_eq!(contents(&root, &["1", "2", "3"]), ["2", "3", "1"]);
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 3);
+}
+
+#[test]
+fn test_rename_all_reverts_on_failure() {
+    let (_dir, root) = tempdir();
+    subject(&root.join("a"));
+    subject(&root.join("b"));
+    let pairs = [
+        (root.join("a"), root.join("b")),
+        (root.join("b"), root.join("a")),
+        (root.join("c-missing"), root.join("d")),
+    ];
+    assert!(rename::rename_all(&pairs).is_err());
+    assert_eq!(contents(&root, &["a", "b"]), ["a", "b"]);
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 2);
+}
+
+// ===== Operations Actor Tests =====
+
+impl Harness {
+    fn rename(&self, paths: &[PathBuf], rule: RenameRule, dry_run: bool) {
+        let nodes = paths
+            .iter()
+            .map(|p| self.registry.clone().register(p.clone()))
+            .collect();
+        self.commands
+            .send(OpCommand::BatchRename {
+                nodes,
+                rule,
+                dry_run,
+                session: self.session,
+            })
+            .unwrap();
+    }
+}
+
+fn succeeded(events: &[Event]) -> bool {
+    matches!(
+        events.last(),
+        Some(Event::OperationComplete { success: true, .. })
+    )
+}
+
+#[tokio::test]
+async fn test_operations_batch_rename_preview_apply_undo() {
+    let (_dir, root) = tempdir();
+    let paths: Vec<_> = ["2", "1", "x.log"].iter().map(|n| root.join(n)).collect();
+    for path in &paths {
+        subject(path);
+    }
+    let rule = RenameRule {
+        find: find(r"\.log$", "", true),
+        ..numbered(1)
+    };
+
+    assert_preview(&paths, &rule, &root).await;
+
+    let h = spawn_operations(|ops| ops.with_journal(Journal::in_memory()));
+    h.rename(&paths, rule, false);
+    let events = h.until_complete().await;
+    assert!(succeeded(&events));
+    assert!(events.iter().any(
+        |e| matches!(e, Event::HistoryChanged { undo: Some(u), .. } if u == "Rename 3 items")
+    ));
+    assert_eq!(contents(&root, &["1", "2", "3"]), ["2", "1", "x.log"]);
+
+    h.commands.send(OpCommand::Undo(h.session)).unwrap();
+    assert!(succeeded(&h.until_complete().await));
+    assert_eq!(contents(&root, &["2", "1", "x.log"]), ["2", "1", "x.log"]);
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 3);
+
+    h.commands.send(OpCommand::Redo(h.session)).unwrap();
+    assert!(succeeded(&h.until_complete().await));
+    assert_eq!(contents(&root, &["1", "2", "3"]), ["2", "1", "x.log"]);
+}
+
+/// A dry run reports the plan and leaves the files alone
+async fn assert_preview(paths: &[PathBuf], rule: &RenameRule, root: &Path) {
+    let h = spawn_operations(|ops| ops.with_journal(Journal::in_memory()));
+    h.rename(paths, rule.clone(), true);
+    let Event::RenamePreview { plan, .. } = h.next().await else {
+        panic!("expected a preview")
+    };
+    let targets: Vec<_> = plan.items.iter().map(|i| i.to.clone()).collect();
+    assert_eq!(targets, [root.join("1"), root.join("2"), root.join("3")]);
+    assert_eq!(plan.cycles.len(), 1);
+    assert!(root.join("x.log").exists() && !root.join("3").exists());
+}
+
+#[tokio::test]
+async fn test_operations_batch_rename_refuses_conflicts() {
+    let (_dir, root) = tempdir();
+    let paths = [root.join("a.txt"), root.join("b.txt")];
+    for path in &paths {
+        subject(path);
+    }
+    let h = spawn_operations(|ops| ops.with_journal(Journal::in_memory()));
+
+    h.rename(&paths, template("same{ext}"), false);
+    let events = h.until_complete().await;
+    assert!(!succeeded(&events));
+    assert!(events.iter().any(
+        |e| matches!(e, Event::Error { message, .. } if message.contains("2 of the new names"))
+    ));
+    assert!(paths.iter().all(|p| p.exists()));
+
+    h.rename(&paths, template("{bogus}"), false);
+    let events = h.until_complete().await;
+    assert!(!succeeded(&events));
+    assert!(
+        events.iter().any(
+            |e| matches!(e, Event::Error { message, .. } if message.contains("unknown token"))
+        )
+    );
+}
@@ -34,6 +34,7 @@ fn make_file(name: &str, path: &str, size: u64, hidden: bool) -> FileNode {
             hidden,
             readonly: false,
             permissions: None,
+            ..Default::default()
         },
     }
 }
@@ -53,6 +54,7 @@ fn _make_file_with_ext(name: &str, path: &str, ext: Option<&str>, size: u64) ->
             hidden: false,
             readonly: false,
             permissions: None,
+            ..Default::default()
         },
     }
 }
@@ -72,6 +74,7 @@ fn _make_dir(name: &str, full_path: &str, hidden: bool) -> FileNode {
             hidden,
             readonly: false,
             permissions: None,
+            ..Default::default()
         },
     }
 }
@@ -118,6 +121,7 @@ impl FsProvider for MockProvider {
             write: false,
             watch: false,
             search: false,
+            xattrs: false,
         }
     }
 
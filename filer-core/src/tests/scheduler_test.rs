@@ -0,0 +1,312 @@
+//! Tests for the global I/O scheduler
+
+use std::fs;
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::operations::{OpCommand, Operations};
+use crate::api::events::{Event, JobOutcome};
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::hash::HashAlgorithm;
+use crate::services::scheduler::{
+    DeviceKey, IoClass, IoPermit, IoScheduler, IoTicket, PendingIo, SchedulerConfig,
+};
+use crate::services::transfer::TransferOptions;
+use crate::tests::common::spawn_operations;
+
+const DISK: DeviceKey = DeviceKey::Local(1);
+
+fn scheduler(slots: usize, reserved: usize) -> IoScheduler {
+    IoScheduler::new(SchedulerConfig {
+        local_slots: slots,
+        remote_slots: slots,
+        reserved,
+        ..Default::default()
+    })
+}
+
+fn ticket(class: IoClass) -> IoTicket {
+    IoTicket::new(class).on(DISK)
+}
+
+/// Permit if the request has been granted already
+fn granted(pending: &PendingIo) -> Option<IoPermit> {
+    pending.wait_timeout(Duration::from_millis(20))
+}
+
+// ===== Queue Tests =====
+
+#[test]
+fn test_scheduler_limits_concurrency_per_device() {
+    let io = scheduler(2, 0);
+    let first = granted(&io.request(ticket(IoClass::Listing))).unwrap();
+    let _second = granted(&io.request(ticket(IoClass::Listing))).unwrap();
+    let third = io.request(ticket(IoClass::Listing));
+    assert!(granted(&third).is_none());
+    assert_eq!(io.running(&DISK), 2);
+    assert_eq!(io.queued(), 1);
+
+    drop(first);
+    let third = granted(&third).unwrap();
+    assert_eq!(third.ticket().class, IoClass::Listing);
+    assert_eq!(io.running(&DISK), 2);
+    assert_eq!(io.queued(), 0);
+}
+
+#[test]
+fn test_scheduler_grants_highest_class_first() {
+    let io = scheduler(1, 0);
+    let mut holder = granted(&io.request(ticket(IoClass::Bulk))).unwrap();
+    let pending: Vec<_> = [
+        IoClass::Bulk,
+        IoClass::Background,
+        IoClass::Preview,
+        IoClass::Listing,
+    ]
+    .into_iter()
+    .map(|class| io.request(ticket(class)))
+    .collect();
+
+    let mut order = Vec::new();
+    for _ in 0..pending.len() {
+        drop(holder);
+        holder = pending
+            .iter()
+            .find_map(granted)
+            .expect("a waiter is granted");
+        order.push(holder.ticket().class);
+    }
+    assert_eq!(
+        order,
+        [
+            IoClass::Listing,
+            IoClass::Preview,
+            IoClass::Background,
+            IoClass::Bulk
+        ]
+    );
+}
+
+#[test]
+fn test_scheduler_keeps_reserved_slots_for_interactive_work() {
+    let io = scheduler(2, 1);
+    let _copy = granted(&io.request(ticket(IoClass::Bulk))).unwrap();
+    let second_copy = io.request(ticket(IoClass::Bulk));
+    assert!(granted(&second_copy).is_none());
+    let indexing = io.request(ticket(IoClass::Background));
+    assert!(granted(&indexing).is_none());
+
+    // The reserved slot still serves a listing
+    assert!(granted(&io.request(ticket(IoClass::Listing))).is_some());
+}
+
+#[test]
+fn test_scheduler_reserve_never_blocks_single_slot_devices() {
+    let io = scheduler(1, 1);
+    assert!(granted(&io.request(ticket(IoClass::Bulk))).is_some());
+}
+
+#[test]
+fn test_scheduler_is_fair_between_sessions() {
+    let io = scheduler(2, 0);
+    let (busy, quiet) = (SessionId::new(), SessionId::new());
+    let first = granted(&io.request(ticket(IoClass::Bulk).for_session(busy))).unwrap();
+    let _second = granted(&io.request(ticket(IoClass::Bulk).for_session(busy))).unwrap();
+    let busy_third = io.request(ticket(IoClass::Bulk).for_session(busy));
+    let quiet_first = io.request(ticket(IoClass::Bulk).for_session(quiet));
+
+    // The older request loses to the session with nothing running
+    drop(first);
+    let permit = granted(&quiet_first).unwrap();
+    assert_eq!(permit.ticket().session, Some(quiet));
+    assert!(granted(&busy_third).is_none());
+}
+
+#[test]
+fn test_scheduler_devices_are_independent() {
+    let io = scheduler(1, 0);
+    let _disk = granted(&io.request(ticket(IoClass::Bulk))).unwrap();
+    let other = IoTicket::new(IoClass::Bulk).on(DeviceKey::Local(2));
+    assert!(granted(&io.request(other)).is_some());
+    let host = IoTicket::new(IoClass::Bulk).on(DeviceKey::remote("files.example.com"));
+    assert!(granted(&io.request(host)).is_some());
+}
+
+#[test]
+fn test_scheduler_ticket_needs_every_device() {
+    let io = scheduler(1, 0);
+    let target = DeviceKey::Local(2);
+    let busy = granted(&io.request(IoTicket::new(IoClass::Bulk).on(target.clone()))).unwrap();
+
+    let copy = io.request(ticket(IoClass::Bulk).on(target.clone()));
+    assert!(granted(&copy).is_none());
+    assert_eq!(io.running(&DISK), 0);
+
+    drop(busy);
+    let copy = granted(&copy).unwrap();
+    assert_eq!(copy.ticket().devices, [DISK, target.clone()]);
+    assert_eq!((io.running(&DISK), io.running(&target)), (1, 1));
+    drop(copy);
+    assert_eq!((io.running(&DISK), io.running(&target)), (0, 0));
+}
+
+#[test]
+fn test_scheduler_dropped_request_leaves_queue() {
+    let io = scheduler(1, 0);
+    let holder = granted(&io.request(ticket(IoClass::Bulk))).unwrap();
+    let abandoned = io.request(ticket(IoClass::Listing));
+    let waiting = io.request(ticket(IoClass::Bulk));
+    assert_eq!(io.queued(), 2);
+
+    drop(abandoned);
+    assert_eq!(io.queued(), 1);
+    drop(holder);
+    assert!(granted(&waiting).is_some());
+}
+
+#[test]
+fn test_scheduler_set_limit_grants_waiters() {
+    let io = scheduler(1, 0);
+    let _holder = granted(&io.request(ticket(IoClass::Listing))).unwrap();
+    let waiting = io.request(ticket(IoClass::Listing));
+    assert!(granted(&waiting).is_none());
+
+    io.set_limit(DISK, 2);
+    assert!(granted(&waiting).is_some());
+}
+
+#[tokio::test]
+async fn test_scheduler_run_holds_slot_while_working() {
+    let io = scheduler(1, 0);
+    let probe = io.clone();
+    let running = io
+        .run(ticket(IoClass::Background), move || probe.running(&DISK))
+        .await
+        .unwrap();
+    assert_eq!(running, 1);
+    assert_eq!(io.running(&DISK), 0);
+}
+
+// ===== DeviceKey Tests =====
+
+#[test]
+fn test_device_key_of_path_uses_existing_ancestor() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = DeviceKey::of_path(dir.path());
+    assert!(!key.is_remote());
+    assert_eq!(DeviceKey::of_path(&dir.path().join("not/yet/created")), key);
+}
+
+#[test]
+fn test_device_key_of_url() {
+    assert_eq!(
+        DeviceKey::of_url("https://user:pw@DAV.example.com:8443/files/a?x=1"),
+        DeviceKey::remote("dav.example.com:8443")
+    );
+    assert_eq!(
+        DeviceKey::of_url("ftp://host/pub"),
+        DeviceKey::remote("host")
+    );
+    assert_eq!(DeviceKey::remote("host").to_string(), "host:host");
+}
+
+// ===== Operations Integration Tests =====
+
+#[tokio::test]
+async fn test_operations_transfer_waits_for_slot_and_can_be_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("file.txt");
+    fs::write(&src, b"content").unwrap();
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    let disk = DeviceKey::of_path(dir.path());
+
+    let io = scheduler(1, 0);
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    let session = SessionId::new();
+    tokio::spawn(
+        Operations::new(cmd_rx, evt_tx, registry.clone())
+            .with_scheduler(io.clone())
+            .run(),
+    );
+    let next = || async {
+        loop {
+            let event = timeout(Duration::from_secs(5), evt_rx.recv_async())
+                .await
+                .expect("timed out waiting for event")
+                .unwrap();
+            if !matches!(event, Event::JobProgress { .. }) {
+                return event;
+            }
+        }
+    };
+    let copy = OpCommand::Copy {
+        sources: vec![registry.clone().register(src.clone())],
+        destination: registry.clone().register(dest.clone()),
+        options: TransferOptions::default(),
+        session,
+    };
+
+    // Queued behind other work on the same disk, then cancelled
+    let busy = granted(&io.request(IoTicket::new(IoClass::Bulk).on(disk.clone()))).unwrap();
+    cmd_tx.send(copy.clone()).unwrap();
+    let Event::JobStarted { job, .. } = next().await else {
+        panic!("expected JobStarted")
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(io.queued(), 1);
+    cmd_tx.send(OpCommand::Cancel(job, session)).unwrap();
+    match next().await {
+        Event::JobFinished { outcome, .. } => assert_eq!(outcome, JobOutcome::Cancelled),
+        other => panic!("unexpected event: {other:?}"),
+    }
+    assert!(matches!(
+        next().await,
+        Event::OperationComplete { success: false, .. }
+    ));
+    assert!(!dest.join("file.txt").exists());
+    assert_eq!(io.queued(), 0);
+
+    // Runs once the disk is free and gives its slot back
+    drop(busy);
+    cmd_tx.send(copy).unwrap();
+    assert!(matches!(next().await, Event::JobStarted { .. }));
+    match next().await {
+        Event::JobFinished { outcome, .. } => assert_eq!(outcome, JobOutcome::Completed),
+        other => panic!("unexpected event: {other:?}"),
+    }
+    assert_eq!(fs::read(dest.join("file.txt")).unwrap(), b"content");
+    assert_eq!(io.running(&disk), 0);
+}
+
+#[tokio::test]
+async fn test_operations_checksum_queues_as_background_work() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file.txt");
+    fs::write(&file, b"content").unwrap();
+    let disk = DeviceKey::of_path(dir.path());
+    let io = scheduler(1, 0);
+    let h = spawn_operations(|ops| ops.with_scheduler(io.clone()));
+
+    // A transfer queued first still has to let the checksum go ahead
+    let busy = granted(&io.request(IoTicket::new(IoClass::Bulk).on(disk.clone()))).unwrap();
+    let transfer = io.request(IoTicket::new(IoClass::Bulk).on(disk.clone()));
+    h.commands
+        .send(OpCommand::Checksum {
+            nodes: vec![h.registry.clone().register(file)],
+            algorithms: vec![HashAlgorithm::Sha256],
+            session: h.session,
+        })
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(io.queued(), 2);
+
+    drop(busy);
+    assert_eq!(h.finished().await.1, JobOutcome::Completed);
+    assert!(granted(&transfer).is_some());
+}
@@ -0,0 +1,429 @@
+//! Tests for the ordered selection model, selection edits in the
+//! Navigator and pruning as listings change
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::navigator::{NavCommand, Navigator, NavigatorState};
+use crate::actors::scanner::Scanner;
+use crate::api::events::Event;
+use crate::model::node::{FileNode, NodeId, NodeKind, NodeMeta};
+use crate::model::registry::NodeRegistry;
+use crate::model::selection::{Selection, SelectionEdit};
+use crate::model::session::{SessionId, ViewId};
+use crate::pipeline::FilterConfig;
+use crate::vfs::local::LocalFs;
+
+fn file(id: u64, name: &str, size: u64) -> FileNode {
+    FileNode {
+        id: NodeId(id),
+        name: name.to_string(),
+        path: PathBuf::from(format!("/test/{name}")),
+        kind: NodeKind::File { extension: None },
+        size,
+        modified: None,
+        created: None,
+        meta: NodeMeta::default(),
+    }
+}
+
+fn dir(id: u64, name: &str) -> FileNode {
+    FileNode {
+        kind: NodeKind::Directory {
+            children_count: None,
+        },
+        size: 4096,
+        ..file(id, name, 0)
+    }
+}
+
+/// a.txt b.rs c.txt d.rs e.md, in that display order
+fn listing() -> Vec<FileNode> {
+    vec![
+        file(1, "a.txt", 10),
+        file(2, "b.rs", 20),
+        file(3, "c.txt", 30),
+        file(4, "d.rs", 40),
+        file(5, "e.md", 50),
+    ]
+}
+
+fn ids(selection: &Selection) -> Vec<u64> {
+    selection.nodes().iter().map(|node| node.0).collect()
+}
+
+fn nodes(ids: &[u64]) -> Vec<NodeId> {
+    ids.iter().map(|id| NodeId(*id)).collect()
+}
+
+// ===== Selection Tests =====
+
+#[test]
+fn test_selection_keeps_the_order_nodes_were_selected_in() {
+    let listing = listing();
+    let mut selection = Selection::default();
+
+    assert!(
+        selection
+            .apply(SelectionEdit::Add(nodes(&[3, 1])), &listing)
+            .unwrap()
+    );
+    assert!(
+        selection
+            .apply(SelectionEdit::Add(nodes(&[5, 1])), &listing)
+            .unwrap()
+    );
+    assert_eq!(ids(&selection), vec![3, 1, 5]);
+    assert_eq!(selection.anchor(), Some(NodeId(1)));
+
+    assert!(
+        selection
+            .apply(SelectionEdit::Remove(nodes(&[1])), &listing)
+            .unwrap()
+    );
+    assert_eq!(ids(&selection), vec![3, 5]);
+    // Removing what is not selected changes nothing
+    assert!(
+        !selection
+            .apply(SelectionEdit::Remove(nodes(&[4])), &listing)
+            .unwrap()
+    );
+
+    selection
+        .apply(SelectionEdit::Replace(nodes(&[4, 2])), &listing)
+        .unwrap();
+    assert_eq!(ids(&selection), vec![4, 2]);
+    assert_eq!(selection.anchor(), Some(NodeId(2)));
+    assert!(
+        !selection
+            .apply(SelectionEdit::Replace(nodes(&[4, 2])), &listing)
+            .unwrap()
+    );
+
+    selection.apply(SelectionEdit::Clear, &listing).unwrap();
+    assert!(selection.is_empty());
+    assert_eq!(selection.anchor(), None);
+}
+
+#[test]
+fn test_toggle_flips_each_node() {
+    let listing = listing();
+    let mut selection: Selection = nodes(&[1, 2]).into_iter().collect();
+
+    selection
+        .apply(SelectionEdit::Toggle(nodes(&[2, 3])), &listing)
+        .unwrap();
+    assert_eq!(ids(&selection), vec![1, 3]);
+    assert_eq!(selection.anchor(), Some(NodeId(3)));
+    assert!(selection.contains(NodeId(3)) && !selection.contains(NodeId(2)));
+
+    // Toggling off keeps the anchor where it was clicked
+    selection
+        .apply(SelectionEdit::Toggle(nodes(&[1])), &listing)
+        .unwrap();
+    assert_eq!(ids(&selection), vec![3]);
+    assert_eq!(selection.anchor(), Some(NodeId(1)));
+}
+
+#[test]
+fn test_range_pivots_around_the_anchor() {
+    let listing = listing();
+    let mut selection = Selection::default();
+    selection
+        .apply(SelectionEdit::Replace(nodes(&[2])), &listing)
+        .unwrap();
+
+    // Shift-click below, then above: the range replaces the last one
+    selection
+        .apply(
+            SelectionEdit::Range {
+                to: NodeId(4),
+                extend: false,
+            },
+            &listing,
+        )
+        .unwrap();
+    assert_eq!(ids(&selection), vec![2, 3, 4]);
+    selection
+        .apply(
+            SelectionEdit::Range {
+                to: NodeId(1),
+                extend: false,
+            },
+            &listing,
+        )
+        .unwrap();
+    assert_eq!(ids(&selection), vec![1, 2]);
+    assert_eq!(selection.anchor(), Some(NodeId(2)));
+
+    // Ctrl+shift adds a range from a new anchor to what is selected
+    selection
+        .apply(SelectionEdit::Toggle(nodes(&[4])), &listing)
+        .unwrap();
+    selection
+        .apply(
+            SelectionEdit::Range {
+                to: NodeId(5),
+                extend: true,
+            },
+            &listing,
+        )
+        .unwrap();
+    assert_eq!(ids(&selection), vec![1, 2, 4, 5]);
+}
+
+#[test]
+fn test_range_follows_the_listing_order() {
+    // Sorted by size, descending
+    let mut listing = listing();
+    listing.reverse();
+    let mut selection = Selection::default();
+    selection
+        .apply(SelectionEdit::Replace(nodes(&[4])), &listing)
+        .unwrap();
+    selection
+        .apply(
+            SelectionEdit::Range {
+                to: NodeId(2),
+                extend: false,
+            },
+            &listing,
+        )
+        .unwrap();
+    assert_eq!(ids(&selection), vec![4, 3, 2]);
+}
+
+#[test]
+fn test_range_without_anchor_starts_at_the_target() {
+    let listing = listing();
+    let mut selection = Selection::default();
+    selection
+        .apply(
+            SelectionEdit::Range {
+                to: NodeId(3),
+                extend: false,
+            },
+            &listing,
+        )
+        .unwrap();
+    assert_eq!(ids(&selection), vec![3]);
+    assert_eq!(selection.anchor(), Some(NodeId(3)));
+
+    let error = selection.apply(
+        SelectionEdit::Range {
+            to: NodeId(99),
+            extend: false,
+        },
+        &listing,
+    );
+    assert!(error.is_err());
+    assert_eq!(ids(&selection), vec![3]);
+}
+
+#[test]
+fn test_select_all_and_invert() {
+    let listing = listing();
+    let mut selection: Selection = nodes(&[4, 2]).into_iter().collect();
+
+    selection.apply(SelectionEdit::Invert, &listing).unwrap();
+    assert_eq!(ids(&selection), vec![1, 3, 5]);
+
+    // What was selected stays first
+    selection.apply(SelectionEdit::All, &listing).unwrap();
+    assert_eq!(ids(&selection), vec![1, 3, 5, 2, 4]);
+    assert!(!selection.apply(SelectionEdit::All, &listing).unwrap());
+
+    selection.apply(SelectionEdit::Invert, &listing).unwrap();
+    assert!(selection.is_empty());
+}
+
+#[test]
+fn test_select_by_glob_and_filter() {
+    let mut listing = listing();
+    listing.push(dir(6, "src"));
+    let mut selection: Selection = nodes(&[5]).into_iter().collect();
+
+    selection
+        .apply(SelectionEdit::Glob("*.rs".into()), &listing)
+        .unwrap();
+    assert_eq!(ids(&selection), vec![5, 2, 4]);
+    assert!(
+        selection
+            .apply(SelectionEdit::Glob(String::new()), &listing)
+            .is_err()
+    );
+
+    let mut selection = Selection::default();
+    let filter = FilterConfig {
+        min_size: Some(25),
+        ..FilterConfig::only_extensions(vec!["txt".into(), "md".into()])
+    };
+    selection
+        .apply(SelectionEdit::Filter(filter), &listing)
+        .unwrap();
+    assert_eq!(ids(&selection), vec![3, 5]);
+
+    let filter = FilterConfig {
+        name_pattern: Some("?.*".into()),
+        exclude_patterns: vec!["a*".into()],
+        ..Default::default()
+    };
+    selection
+        .apply(SelectionEdit::Filter(filter), &listing)
+        .unwrap();
+    assert_eq!(ids(&selection), vec![3, 5, 2, 4]);
+}
+
+#[test]
+fn test_prune_and_size() {
+    let mut listing = listing();
+    listing.push(dir(6, "src"));
+    let mut selection = Selection::default();
+    selection
+        .apply(SelectionEdit::Replace(nodes(&[6, 2, 4])), &listing)
+        .unwrap();
+
+    // Directories count as empty
+    assert_eq!(selection.size(&listing), 60);
+
+    listing.retain(|entry| entry.id != NodeId(4));
+    assert!(selection.prune(&listing));
+    assert_eq!(ids(&selection), vec![6, 2]);
+    assert_eq!(selection.anchor(), None);
+    assert!(!selection.prune(&listing));
+    assert_eq!(selection.size(&listing), 20);
+}
+
+// ===== NavigatorState Tests =====
+
+#[test]
+fn test_state_only_takes_the_current_directory_listing() {
+    let registry = NodeRegistry::new();
+    let mut state = NavigatorState::new(registry);
+    state.navigate(NodeId(100));
+    state.selected = nodes(&[1, 7]).into_iter().collect();
+
+    // Stale listing of a directory left already
+    assert!(!state.set_listing(NodeId(200), listing().into()));
+    assert!(state.listing().is_empty());
+    assert_eq!(state.selected.len(), 2);
+
+    assert!(state.set_listing(NodeId(100), listing().into()));
+    assert_eq!(ids(&state.selected), vec![1]);
+    assert_eq!(state.selection_size(), 10);
+    assert!(state.select(SelectionEdit::All).unwrap());
+    assert_eq!(state.selection_size(), 150);
+
+    // A bigger file changes the size but not the selection
+    let mut grown = listing();
+    grown[0].size = 110;
+    assert!(state.set_listing(NodeId(100), grown.into()));
+    assert_eq!(state.selection_size(), 250);
+
+    // Elsewhere there is nothing to select yet
+    state.navigate(NodeId(200));
+    assert!(state.listing().is_empty());
+    assert_eq!(state.selection_size(), 0);
+}
+
+// ===== Actor Tests =====
+
+#[tokio::test]
+async fn test_selection_follows_the_listing() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::write(root.join("a.txt"), b"aaaa").unwrap();
+    fs::write(root.join("b.txt"), b"bb").unwrap();
+    fs::write(root.join("c.md"), b"c").unwrap();
+
+    let reg = NodeRegistry::new();
+    let (nav_tx, nav_rx) = flume::unbounded();
+    let (scan_tx, scan_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let scanner = Scanner::new(
+        scan_rx,
+        evt_tx.clone(),
+        Arc::new(LocalFs::new(reg.clone())),
+        reg.clone(),
+    )
+    .with_navigator(nav_tx.clone());
+    let navigator = Navigator::new(nav_rx, evt_tx, scan_tx, reg.clone());
+    tokio::spawn(scanner.run());
+    tokio::spawn(navigator.run());
+
+    let next_selection = || async {
+        loop {
+            let event = timeout(Duration::from_secs(2), evt_rx.recv_async())
+                .await
+                .unwrap()
+                .unwrap();
+            if let Event::SelectionChanged {
+                view,
+                selected,
+                count,
+                size,
+                ..
+            } = event
+            {
+                assert_eq!(view, ViewId::main());
+                assert_eq!(count, selected.len());
+                return (selected, size);
+            }
+        }
+    };
+
+    let session = SessionId(1);
+    nav_tx.send(NavCommand::NewSession(session)).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    nav_tx
+        .send(NavCommand::NavigateToPath {
+            session,
+            path: root.clone(),
+        })
+        .unwrap();
+    // Let the listing reach the navigator
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    nav_tx
+        .send(NavCommand::Select {
+            session,
+            edit: SelectionEdit::Glob("*.txt".into()),
+        })
+        .unwrap();
+    let (selected, size) = next_selection().await;
+    assert_eq!(selected.len(), 2);
+    assert_eq!(size, 6);
+
+    // A selected file goes away
+    fs::remove_file(root.join("a.txt")).unwrap();
+    nav_tx.send(NavCommand::Refresh(session)).unwrap();
+    let (selected, size) = next_selection().await;
+    assert_eq!(selected, vec![NodeId::from_path(&root.join("b.txt"))]);
+    assert_eq!(size, 2);
+
+    // Nothing to select outside the listing
+    nav_tx
+        .send(NavCommand::Select {
+            session,
+            edit: SelectionEdit::Range {
+                to: NodeId::from_path(&root.join("a.txt")),
+                extend: false,
+            },
+        })
+        .unwrap();
+    loop {
+        let event = timeout(Duration::from_secs(1), evt_rx.recv_async())
+            .await
+            .unwrap()
+            .unwrap();
+        if let Event::Error { message, .. } = event {
+            assert!(message.contains("not in the listing"));
+            break;
+        }
+    }
+}
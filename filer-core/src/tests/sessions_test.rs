@@ -0,0 +1,301 @@
+//! Tests for saving sessions, restoring `NavigatorState` from them and
+//! the Navigator commands that open, close and reopen sessions
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::actors::navigator::{NavCommand, NavigatorState};
+use crate::api::events::Event;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::{SessionId, ViewId};
+use crate::pipeline::PipelineConfig;
+use crate::services::sessions::{SavedSession, SavedView, SessionStore};
+use crate::tests::common::{mkdirs, spawn_navigator, tempdir};
+
+fn view(history: &[PathBuf], history_index: usize) -> SavedView {
+    SavedView {
+        history: history.to_vec(),
+        history_index,
+        ..Default::default()
+    }
+}
+
+fn saved(name: &str, history: &[PathBuf], history_index: usize) -> SavedSession {
+    SavedSession {
+        name: Some(name.to_string()),
+        views: vec![view(history, history_index)],
+        ..Default::default()
+    }
+}
+
+// ===== Store Tests =====
+
+#[test]
+fn test_store_saves_by_name_and_persists() {
+    let (_dir, root) = tempdir();
+    let file = root.join("state").join("sessions.json");
+    let store = SessionStore::open(file.clone()).unwrap();
+    let session = SavedSession {
+        views: vec![SavedView {
+            pipeline: PipelineConfig::with_default_sort(),
+            ..view(&[root.join("a"), root.join("b")], 1)
+        }],
+        ..saved("left", &[], 0)
+    };
+
+    store.save(session.clone()).unwrap();
+    assert!(
+        SessionStore::in_memory()
+            .save(SavedSession::default())
+            .is_err()
+    );
+
+    let reopened = SessionStore::open(file).unwrap();
+    let loaded = reopened.get("left").unwrap();
+    assert_eq!(loaded.views, session.views);
+    assert_eq!(
+        loaded.view(&ViewId::main()).unwrap().current(),
+        Some(&root.join("a"))
+    );
+    assert!(loaded.saved_at > 0);
+    assert_eq!(reopened.names(), vec!["left"]);
+    assert!(reopened.get("right").is_none());
+}
+
+#[test]
+fn test_store_keeps_the_last_closed_sessions() {
+    let store = SessionStore::in_memory().with_closed_limit(2);
+    for name in ["one", "two", "three"] {
+        store.save(saved(name, &[], 0)).unwrap();
+        store.close(store.get(name).unwrap()).unwrap();
+        // Closing keeps the save under its name
+        assert!(store.get(name).is_some());
+    }
+    let closed: Vec<_> = store
+        .closed()
+        .into_iter()
+        .map(|s| s.name.unwrap())
+        .collect();
+    assert_eq!(closed, vec!["three", "two"]);
+
+    let reopened = store.reopen().unwrap().unwrap();
+    assert_eq!(reopened.name.as_deref(), Some("three"));
+    assert!(store.get("three").is_some());
+    assert_eq!(
+        store.reopen().unwrap().unwrap().name.as_deref(),
+        Some("two")
+    );
+    assert!(store.reopen().unwrap().is_none());
+}
+
+// ===== NavigatorState Tests =====
+
+#[test]
+fn test_state_round_trips_through_saved_session() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b", "c"]);
+    let registry = NodeRegistry::new();
+    let mut state = NavigatorState::new(registry.clone());
+    for dir in &dirs {
+        state.navigate(registry.clone().register(dir.clone()));
+    }
+    state.back(1);
+    state
+        .selected
+        .insert(registry.clone().register(dirs[0].clone()));
+    state.pipeline_config = PipelineConfig::with_default_sort();
+
+    let saved = state.to_saved(&ViewId::main());
+    assert_eq!(saved.view, ViewId::main());
+    assert_eq!(saved.history, dirs);
+    assert_eq!(saved.history_index, 1);
+    assert_eq!(saved.current(), Some(&dirs[1]));
+    assert_eq!(saved.selected, vec![dirs[0].clone()]);
+
+    // A fresh registry, as after a restart
+    let registry = NodeRegistry::new();
+    let mut restored = NavigatorState::new(registry.clone());
+    restored.restore(&saved);
+    assert_eq!(restored.history.len(), 3);
+    assert_eq!(
+        registry.resolve(restored.current.unwrap()),
+        Some(dirs[1].clone())
+    );
+    assert!(restored.can_back() && restored.can_forward());
+    assert_eq!(
+        restored.pipeline_config,
+        PipelineConfig::with_default_sort()
+    );
+    assert_eq!(restored.selected.len(), 1);
+}
+
+#[test]
+fn test_restore_drops_vanished_paths() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b", "c", "d"]);
+    let registry = NodeRegistry::new();
+
+    // a b [c] d, with c gone: back to b, d still ahead
+    fs::remove_dir(&dirs[2]).unwrap();
+    let mut state = NavigatorState::new(registry.clone());
+    state.restore(&SavedView {
+        selected: vec![dirs[2].clone(), dirs[3].clone()],
+        ..view(&dirs, 1)
+    });
+    let history: Vec<_> = state
+        .history
+        .iter()
+        .map(|n| registry.resolve(*n).unwrap())
+        .collect();
+    assert_eq!(
+        history,
+        vec![dirs[0].clone(), dirs[1].clone(), dirs[3].clone()]
+    );
+    assert_eq!(
+        registry.resolve(state.current.unwrap()),
+        Some(dirs[1].clone())
+    );
+    assert_eq!(state.history_index, 1);
+    assert_eq!(state.selected.len(), 1);
+
+    // a b a, with b gone: a single a
+    let mut state = NavigatorState::new(registry.clone());
+    let history = [dirs[0].clone(), dirs[2].clone(), dirs[0].clone()];
+    state.restore(&view(&history, 0));
+    assert_eq!(state.history.len(), 1);
+    assert_eq!(
+        registry.resolve(state.current.unwrap()),
+        Some(dirs[0].clone())
+    );
+
+    // Nothing older left: the oldest remaining entry
+    let mut state = NavigatorState::new(registry.clone());
+    state.restore(&view(&[dirs[2].clone(), dirs[3].clone()], 1));
+    assert_eq!(
+        registry.resolve(state.current.unwrap()),
+        Some(dirs[3].clone())
+    );
+    assert_eq!(state.history_index, 0);
+
+    // Nothing left at all
+    let mut state = NavigatorState::new(registry.clone());
+    state.restore(&view(&[dirs[2].clone()], 0));
+    assert!(state.current.is_none());
+    assert!(!state.can_back());
+}
+
+#[test]
+fn test_restore_respects_history_limit() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b", "c", "d"]);
+    let registry = NodeRegistry::new();
+    let mut state = NavigatorState::with_history_limit(2, registry.clone());
+    state.restore(&view(&dirs, 0));
+    assert_eq!(state.history.len(), 2);
+    assert_eq!(
+        registry.resolve(state.current.unwrap()),
+        Some(dirs[3].clone())
+    );
+    assert!(state.can_back());
+}
+
+// ===== Navigator Actor Tests =====
+
+#[tokio::test]
+async fn test_open_session_restores_and_rescans() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let store = SessionStore::in_memory();
+    store.save(saved("window-1", &dirs, 0)).unwrap();
+    let h = spawn_navigator(store.clone(), false);
+    let session = SessionId(1);
+
+    h.send(NavCommand::OpenSession {
+        session,
+        name: "window-1".into(),
+    })
+    .await;
+    assert_eq!(h.next_scan().await, dirs[1]);
+    match h.next_event().await {
+        Event::CurrentNavigateState {
+            session: s,
+            view,
+            state,
+        } => {
+            assert_eq!(s, session);
+            assert_eq!(view, ViewId::main());
+            assert!(state.can_back);
+            assert_eq!(
+                h.registry.resolve(state.current.unwrap()),
+                Some(dirs[1].clone())
+            );
+        }
+        other => panic!("expected the restored state, got {other:?}"),
+    }
+
+    // Changes are saved as they happen
+    h.send(NavCommand::Back(session)).await;
+    let saved = store.get("window-1").unwrap();
+    assert_eq!(saved.views[0].current(), Some(&dirs[0]));
+
+    // Unknown names start empty
+    h.send(NavCommand::OpenSession {
+        session: SessionId(2),
+        name: "window-2".into(),
+    })
+    .await;
+    let state = loop {
+        if let Event::CurrentNavigateState { session, state, .. } = h.next_event().await
+            && session == SessionId(2)
+        {
+            break state;
+        }
+    };
+    assert!(state.current.is_none());
+}
+
+#[tokio::test]
+async fn test_close_and_reopen_session() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a"]);
+    let store = SessionStore::in_memory();
+    let h = spawn_navigator(store.clone(), false);
+    let session = SessionId(1);
+
+    h.send(NavCommand::OpenSession {
+        session,
+        name: "tab".into(),
+    })
+    .await;
+    h.send(NavCommand::NavigateToPath {
+        session,
+        path: dirs[0].clone(),
+    })
+    .await;
+    h.send(NavCommand::CloseSession(session)).await;
+    // Closing keeps the named save as well as the closed copy
+    let saved = store.get("tab").unwrap();
+    assert_eq!(saved.views[0].current(), Some(&dirs[0]));
+    assert_eq!(store.closed().len(), 1);
+    let destroyed = loop {
+        if let Event::SessionDestroyed(s) = h.next_event().await {
+            break s;
+        }
+    };
+    assert_eq!(destroyed, session);
+    h.scans.drain();
+
+    let reopened = SessionId(7);
+    h.send(NavCommand::ReopenClosed(reopened)).await;
+    assert_eq!(h.next_scan().await, dirs[0]);
+    assert!(store.get("tab").is_some());
+    assert!(store.closed().is_empty());
+
+    h.send(NavCommand::ReopenClosed(SessionId(8))).await;
+    let error = loop {
+        if let Event::Error { session, .. } = h.next_event().await {
+            break session;
+        }
+    };
+    assert_eq!(error, SessionId(8));
+}
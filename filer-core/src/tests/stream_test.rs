@@ -0,0 +1,616 @@
+//! Tests for provider-to-provider streaming copies
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use md5::{Digest, Md5};
+
+use crate::actors::operations::OpCommand;
+use crate::api::events::{Event, JobOutcome};
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeId, NodeKind, NodeMeta};
+use crate::model::registry::NodeRegistry;
+use crate::services::transfer::{
+    ConflictAnswer, ConflictPolicy, ConflictResolution, JobControl, ProgressTracker, StreamCopy,
+    TransferObserver, TransferOptions, Verification,
+};
+use crate::tests::common::{Harness, local, spawn_operations, tree};
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::{Capabilities, FsProvider, Precondition, WriteStream};
+
+#[derive(Default)]
+struct Recorder {
+    last_bytes: u64,
+    files_done: u64,
+    failed: Vec<(PathBuf, String)>,
+}
+
+impl TransferObserver for Recorder {
+    fn progress(&mut self, tracker: &ProgressTracker, _current: &Path) {
+        assert!(
+            tracker.bytes_done >= self.last_bytes,
+            "progress went backwards"
+        );
+        self.last_bytes = tracker.bytes_done;
+        self.files_done = tracker.files_done;
+    }
+
+    fn failed(&mut self, path: &Path, error: &CoreError) {
+        self.failed.push((path.to_path_buf(), error.to_string()));
+    }
+}
+
+/// Flat in-memory store that reports an MD5 ETag, like S3
+///
+/// Directories exist while something is stored below them.
+#[derive(Default)]
+struct MemFs {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+    /// Flip the first byte of every upload
+    corrupt: bool,
+}
+
+impl MemFs {
+    fn with_files(files: &[(&str, &[u8])]) -> Self {
+        let files = files
+            .iter()
+            .map(|(path, data)| (PathBuf::from(path), data.to_vec()))
+            .collect();
+        Self {
+            files: Mutex::new(files),
+            corrupt: false,
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let files = self.files.lock().unwrap();
+        files.keys().any(|key| key != path && key.starts_with(path))
+    }
+
+    fn get(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(Path::new(path)).cloned()
+    }
+}
+
+struct MemUpload<'a> {
+    fs: &'a MemFs,
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+#[async_trait]
+impl WriteStream for MemUpload<'_> {
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), CoreError> {
+        self.data.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    async fn finish(mut self: Box<Self>) -> Result<(), CoreError> {
+        if self.fs.corrupt
+            && let Some(byte) = self.data.first_mut()
+        {
+            *byte ^= 0xff;
+        }
+        let data = std::mem::take(&mut self.data);
+        self.fs
+            .files
+            .lock()
+            .unwrap()
+            .insert(self.path.clone(), data);
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) {}
+}
+
+#[async_trait]
+impl FsProvider for MemFs {
+    fn scheme(&self) -> &'static str {
+        "mem"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            write: true,
+            watch: false,
+            search: false,
+            xattrs: false,
+        }
+    }
+
+    async fn list(&self, _path: &Path) -> Result<Vec<FileNode>, CoreError> {
+        Ok(Vec::new())
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, CoreError> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| CoreError::NotFound(path.to_path_buf()))
+    }
+
+    async fn read_range(&self, path: &Path, start: u64, len: u64) -> Result<Vec<u8>, CoreError> {
+        let data = self.read(path).await?;
+        let start = (start as usize).min(data.len());
+        let end = start.saturating_add(len as usize).min(data.len());
+        Ok(data[start..end].to_vec())
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, CoreError> {
+        Ok(self.files.lock().unwrap().contains_key(path) || self.is_dir(path))
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
+        if self.is_dir(path) {
+            return Ok(FileNode {
+                id: NodeId(0),
+                name: path.file_name().unwrap().to_string_lossy().into_owned(),
+                path: path.to_path_buf(),
+                kind: NodeKind::Directory {
+                    children_count: None,
+                },
+                size: 0,
+                modified: None,
+                created: None,
+                meta: NodeMeta::default(),
+            });
+        }
+        let data = self.read(path).await?;
+        let etag: String = Md5::digest(&data)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Ok(FileNode {
+            id: NodeId(0),
+            name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            path: path.to_path_buf(),
+            kind: NodeKind::File { extension: None },
+            size: data.len() as u64,
+            modified: None,
+            created: None,
+            meta: NodeMeta {
+                etag: Some(format!("\"{etag}\"")),
+                ..Default::default()
+            },
+        })
+    }
+
+    async fn create(
+        &self,
+        path: &Path,
+        _precondition: &Precondition,
+    ) -> Result<Box<dyn WriteStream + '_>, CoreError> {
+        Ok(Box::new(MemUpload {
+            fs: self,
+            path: path.to_path_buf(),
+            data: Vec::new(),
+        }))
+    }
+
+    async fn create_dir(&self, _path: &Path) -> Result<(), CoreError> {
+        Ok(())
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), CoreError> {
+        self.files.lock().unwrap().retain(|key, _| !key.starts_with(path));
+        Ok(())
+    }
+}
+
+/// Provider that only implements the required read methods
+struct ReadOnly(LocalFs);
+
+#[async_trait]
+impl FsProvider for ReadOnly {
+    fn scheme(&self) -> &'static str {
+        "ro"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            write: false,
+            ..self.0.capabilities()
+        }
+    }
+
+    async fn list(&self, path: &Path) -> Result<Vec<FileNode>, CoreError> {
+        self.0.list(path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, CoreError> {
+        self.0.read(path).await
+    }
+
+    async fn read_range(&self, path: &Path, start: u64, len: u64) -> Result<Vec<u8>, CoreError> {
+        self.0.read_range(path, start, len).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, CoreError> {
+        self.0.exists(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
+        self.0.metadata(path).await
+    }
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    Md5::digest(data).into()
+}
+
+#[tokio::test]
+async fn test_stream_copy_tree_between_providers() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    // Providers have no notion of symlinks
+    fs::remove_file(src.join("link")).unwrap();
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+
+    let mut recorder = Recorder::default();
+    let report = StreamCopy::new(local(), vec![src.clone()], local(), dest.clone())
+        .with_chunk_size(4096)
+        .run(&JobControl::new(), &mut recorder)
+        .await
+        .unwrap();
+
+    assert_eq!(report.errors, 0, "{:?}", recorder.failed);
+    assert_eq!(report.files.len(), 3);
+    assert_eq!((recorder.last_bytes, recorder.files_done), (10_005, 3));
+    assert_eq!(fs::read(dest.join("src/a.txt")).unwrap(), b"alpha");
+    assert_eq!(
+        fs::read(dest.join("src/sub/b.bin")).unwrap(),
+        vec![7u8; 10_000]
+    );
+    assert_eq!(fs::read(dest.join("src/sub/deeper/c")).unwrap(), b"");
+
+    let big = report
+        .files
+        .iter()
+        .find(|f| f.target.ends_with("b.bin"))
+        .unwrap();
+    assert_eq!(big.bytes, 10_000);
+    assert_eq!(big.md5, Some(md5(&[7u8; 10_000])));
+    assert!(
+        report
+            .files
+            .iter()
+            .all(|f| f.verification == Verification::Reread)
+    );
+
+    // Nothing left behind from the uploads
+    assert!(!dest.join("src/.a.txt.filer-part").exists());
+    assert_eq!(fs::read_dir(dest.join("src")).unwrap().count(), 2);
+}
+
+#[tokio::test]
+async fn test_stream_copy_same_provider_copies_server_side() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    let provider = local();
+
+    let mut recorder = Recorder::default();
+    let report = StreamCopy::new(
+        provider.clone(),
+        vec![src.join("sub")],
+        provider,
+        dest.clone(),
+    )
+    .run(&JobControl::new(), &mut recorder)
+    .await
+    .unwrap();
+
+    assert_eq!(report.errors, 0);
+    assert!(
+        report
+            .files
+            .iter()
+            .all(|f| f.verification == Verification::ServerSide && f.md5.is_none())
+    );
+    assert_eq!(fs::read(dest.join("sub/b.bin")).unwrap(), vec![7u8; 10_000]);
+    assert_eq!(recorder.last_bytes, 10_000);
+}
+
+#[tokio::test]
+async fn test_stream_copy_verifies_etag() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let mem = Arc::new(MemFs::default());
+
+    let report = StreamCopy::new(
+        local(),
+        vec![src.join("a.txt")],
+        mem.clone(),
+        PathBuf::from("/bucket"),
+    )
+    .run(&JobControl::new(), &mut Recorder::default())
+    .await
+    .unwrap();
+
+    assert_eq!(report.files[0].verification, Verification::Etag);
+    assert_eq!(report.files[0].md5, Some(md5(b"alpha")));
+    assert_eq!(
+        mem.read(Path::new("/bucket/a.txt")).await.unwrap(),
+        b"alpha"
+    );
+}
+
+#[tokio::test]
+async fn test_stream_copy_reports_checksum_mismatch() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let mem = Arc::new(MemFs {
+        corrupt: true,
+        ..Default::default()
+    });
+
+    let mut recorder = Recorder::default();
+    let report = StreamCopy::new(
+        local(),
+        vec![src.join("a.txt")],
+        mem,
+        PathBuf::from("/bucket"),
+    )
+    .run(&JobControl::new(), &mut recorder)
+    .await
+    .unwrap();
+
+    assert_eq!(report.errors, 1);
+    assert!(report.files.is_empty());
+    assert!(
+        recorder.failed[0].1.contains("checksum mismatch"),
+        "{:?}",
+        recorder.failed
+    );
+}
+
+#[tokio::test]
+async fn test_stream_copy_existing_target_needs_overwrite() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    fs::write(dest.join("a.txt"), b"old").unwrap();
+    let copy = || StreamCopy::new(local(), vec![src.join("a.txt")], local(), dest.clone());
+
+    let mut recorder = Recorder::default();
+    let report = copy().run(&JobControl::new(), &mut recorder).await.unwrap();
+    assert_eq!(report.errors, 1);
+    assert!(recorder.failed[0].1.contains("already exists"));
+    assert_eq!(fs::read(dest.join("a.txt")).unwrap(), b"old");
+
+    let report = copy()
+        .with_overwrite(true)
+        .run(&JobControl::new(), &mut Recorder::default())
+        .await
+        .unwrap();
+    assert_eq!(report.errors, 0);
+    assert_eq!(fs::read(dest.join("a.txt")).unwrap(), b"alpha");
+}
+
+#[tokio::test]
+async fn test_stream_copy_into_read_only_provider_fails_items() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    let read_only = Arc::new(ReadOnly(LocalFs::new(NodeRegistry::new())));
+
+    let mut recorder = Recorder::default();
+    let report = StreamCopy::new(local(), vec![src.join("a.txt")], read_only, dest.clone())
+        .run(&JobControl::new(), &mut recorder)
+        .await
+        .unwrap();
+
+    assert_eq!(report.errors, 1);
+    assert!(recorder.failed[0].1.contains("Permission Denied"));
+    assert!(!dest.join("a.txt").exists());
+}
+
+#[tokio::test]
+async fn test_stream_copy_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let dest = dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    let control = JobControl::new();
+    control.cancel();
+
+    let result = StreamCopy::new(local(), vec![src], local(), dest.clone())
+        .run(&control, &mut Recorder::default())
+        .await;
+    assert!(matches!(result, Err(CoreError::Cancelled)));
+    assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+}
+
+// ===== Operations Actor Tests =====
+
+#[tokio::test]
+async fn test_operations_move_to_mounted_provider_streams() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let mem = Arc::new(MemFs::default());
+    let h = spawn_operations(|ops| ops.with_mount(PathBuf::from("/mem"), mem.clone()));
+    let file = src.join("sub/b.bin");
+
+    h.commands
+        .send(OpCommand::Move {
+            sources: vec![h.registry.clone().register(file.clone())],
+            destination: h.registry.clone().register(PathBuf::from("/mem/bucket")),
+            options: TransferOptions::default(),
+            session: h.session,
+        })
+        .unwrap();
+    match h.finished().await {
+        (
+            _,
+            JobOutcome::Completed,
+            Event::OperationComplete {
+                success: true,
+                affected,
+                ..
+            },
+        ) => {
+            assert_eq!(
+                h.registry.resolve(affected[0]),
+                Some(PathBuf::from("/mem/bucket/b.bin"))
+            );
+        }
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+    assert_eq!(
+        mem.read(Path::new("/mem/bucket/b.bin")).await.unwrap(),
+        vec![7u8; 10_000]
+    );
+    assert!(!file.exists());
+}
+
+fn copy_to_mem(h: &Harness, sources: &[PathBuf], conflict: ConflictPolicy) -> OpCommand {
+    OpCommand::Copy {
+        sources: sources
+            .iter()
+            .map(|path| h.registry.clone().register(path.clone()))
+            .collect(),
+        destination: h.registry.clone().register(PathBuf::from("/mem/bucket")),
+        options: TransferOptions {
+            conflict,
+            ..Default::default()
+        },
+        session: h.session,
+    }
+}
+
+/// Paths of the targets an operation reports
+async fn affected(h: &Harness) -> Vec<PathBuf> {
+    match h.finished().await {
+        (_, JobOutcome::Completed, Event::OperationComplete { affected, .. }) => affected
+            .iter()
+            .map(|id| h.registry.resolve(*id).unwrap())
+            .collect(),
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_operations_stream_conflict_skip() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let mem = Arc::new(MemFs::with_files(&[("/mem/bucket/a.txt", b"old")]));
+    let h = spawn_operations(|ops| ops.with_mount(PathBuf::from("/mem"), mem.clone()));
+
+    let policy = ConflictPolicy::always(ConflictResolution::Skip);
+    h.commands
+        .send(copy_to_mem(&h, &[src.join("a.txt")], policy))
+        .unwrap();
+    assert!(affected(&h).await.is_empty());
+    assert_eq!(mem.get("/mem/bucket/a.txt").unwrap(), b"old");
+}
+
+#[tokio::test]
+async fn test_operations_stream_conflict_overwrite() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let mem = Arc::new(MemFs::with_files(&[("/mem/bucket/a.txt", b"old")]));
+    let h = spawn_operations(|ops| ops.with_mount(PathBuf::from("/mem"), mem.clone()));
+
+    let policy = ConflictPolicy::always(ConflictResolution::Overwrite);
+    h.commands
+        .send(copy_to_mem(&h, &[src.join("a.txt")], policy))
+        .unwrap();
+    assert_eq!(
+        affected(&h).await,
+        vec![PathBuf::from("/mem/bucket/a.txt")]
+    );
+    assert_eq!(mem.get("/mem/bucket/a.txt").unwrap(), b"alpha");
+}
+
+#[tokio::test]
+async fn test_operations_stream_conflict_keep_both() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let mem = Arc::new(MemFs::with_files(&[
+        ("/mem/bucket/a.txt", b"old"),
+        ("/mem/bucket/a (2).txt", b"older"),
+    ]));
+    let h = spawn_operations(|ops| ops.with_mount(PathBuf::from("/mem"), mem.clone()));
+
+    let policy = ConflictPolicy::always(ConflictResolution::KeepBoth);
+    h.commands
+        .send(copy_to_mem(&h, &[src.join("a.txt")], policy))
+        .unwrap();
+    assert_eq!(
+        affected(&h).await,
+        vec![PathBuf::from("/mem/bucket/a (3).txt")]
+    );
+    assert_eq!(mem.get("/mem/bucket/a.txt").unwrap(), b"old");
+    assert_eq!(mem.get("/mem/bucket/a (3).txt").unwrap(), b"alpha");
+}
+
+#[tokio::test]
+async fn test_operations_stream_conflict_merge() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let mem = Arc::new(MemFs::with_files(&[("/mem/bucket/sub/b.bin", b"old")]));
+    let h = spawn_operations(|ops| ops.with_mount(PathBuf::from("/mem"), mem.clone()));
+
+    let policy = ConflictPolicy {
+        files: Some(ConflictResolution::Skip),
+        directories: Some(ConflictResolution::Merge),
+    };
+    h.commands
+        .send(copy_to_mem(&h, &[src.join("sub")], policy))
+        .unwrap();
+    assert_eq!(affected(&h).await, vec![PathBuf::from("/mem/bucket/sub")]);
+    // The existing file was left alone, the rest copied in next to it
+    assert_eq!(mem.get("/mem/bucket/sub/b.bin").unwrap(), b"old");
+    assert_eq!(mem.get("/mem/bucket/sub/deeper/c").unwrap(), b"");
+}
+
+#[tokio::test]
+async fn test_operations_stream_conflict_asks() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = tree(dir.path());
+    let mem = Arc::new(MemFs::with_files(&[("/mem/bucket/a.txt", b"old")]));
+    let h = spawn_operations(|ops| ops.with_mount(PathBuf::from("/mem"), mem.clone()));
+    let file = src.join("a.txt");
+
+    h.commands
+        .send(OpCommand::Move {
+            sources: vec![h.registry.clone().register(file.clone())],
+            destination: h.registry.clone().register(PathBuf::from("/mem/bucket")),
+            options: TransferOptions::default(),
+            session: h.session,
+        })
+        .unwrap();
+    let job = loop {
+        if let Event::OperationConflict { job, target, .. } = h.next().await {
+            assert_eq!(
+                h.registry.resolve(target.node),
+                Some(PathBuf::from("/mem/bucket/a.txt"))
+            );
+            break job;
+        }
+    };
+    h.commands
+        .send(OpCommand::Resolve {
+            job,
+            answer: ConflictAnswer {
+                resolution: ConflictResolution::Skip,
+                apply_to_all: false,
+            },
+            session: h.session,
+        })
+        .unwrap();
+    assert!(affected(&h).await.is_empty());
+    // A skipped source is not moved, so it stays where it was
+    assert!(file.exists());
+    assert_eq!(mem.get("/mem/bucket/a.txt").unwrap(), b"old");
+}
@@ -0,0 +1,409 @@
+//! Tests for the actor wiring
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use flume::Receiver;
+use tokio::time::timeout;
+
+use crate::actors::navigator::NavCommand;
+use crate::actors::operations::OpCommand;
+use crate::actors::poller::{PollCommand, PollerConfig};
+use crate::actors::scanner::ScanCommand;
+use crate::actors::syncer::{SyncerCommand, SyncerConfig};
+use crate::actors::system::{Actors, System};
+use crate::actors::trasher::TrashCommand;
+#[cfg(target_os = "linux")]
+use crate::actors::watcher::WatchCommand;
+use crate::api::events::{Event, OperationKind};
+use crate::errors::CoreError;
+use crate::model::node::FileNode;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::{SessionId, ViewId};
+use crate::pipeline::PipelineConfig;
+use crate::services::bisync::SyncPair;
+use crate::services::journal::Journal;
+use crate::services::metadata::{
+    AudioMetadata, AudioTags, ExtendedMetadata, MetadataExtractor, MetadataRegistry,
+};
+use crate::services::mime::MimeCategory;
+use crate::services::rename::RenameRule;
+use crate::services::scheduler::{DeviceKey, IoClass, IoScheduler, IoTicket, SchedulerConfig};
+use crate::services::transfer::TransferOptions;
+use crate::services::trash::TrashBin;
+use crate::tests::common::tempdir;
+
+/// Skip events until `pick` accepts one
+async fn wait<T>(events: &Receiver<Event>, mut pick: impl FnMut(Event) -> Option<T>) -> T {
+    loop {
+        let event = timeout(Duration::from_secs(5), events.recv_async())
+            .await
+            .expect("timed out waiting for event")
+            .unwrap();
+        if let Some(found) = pick(event) {
+            return found;
+        }
+    }
+}
+
+fn scan(actors: &Actors, registry: &NodeRegistry, root: &Path, session: SessionId) {
+    actors
+        .scanner
+        .send(ScanCommand::ScanNode {
+            node: registry.clone().register(root.to_path_buf()),
+            session,
+            view: ViewId::main(),
+            pipeline: PipelineConfig::default(),
+        })
+        .unwrap()
+}
+
+fn loaded(event: Event) -> Option<Vec<FileNode>> {
+    match event {
+        Event::DirectoryLoaded { entries, .. } => Some(entries),
+        _ => None,
+    }
+}
+
+fn completed(operation: OperationKind) -> impl FnMut(Event) -> Option<bool> {
+    move |event| match event {
+        Event::OperationComplete {
+            operation: done,
+            success,
+            ..
+        } if std::mem::discriminant(&done) == std::mem::discriminant(&operation) => Some(success),
+        _ => None,
+    }
+}
+
+/// Open `session` under `name` and wait until its history is known
+async fn open_session(actors: &Actors, events: &Receiver<Event>, session: SessionId, name: &str) {
+    actors
+        .navigator
+        .send(NavCommand::OpenSession {
+            session,
+            name: name.to_string(),
+        })
+        .unwrap();
+    wait(events, |event| {
+        matches!(event, Event::HistoryChanged { session: s, .. } if s == session).then_some(())
+    })
+    .await;
+}
+
+/// Trash `path` through the trasher
+async fn trash(
+    actors: &Actors,
+    events: &Receiver<Event>,
+    registry: &NodeRegistry,
+    path: &Path,
+    session: SessionId,
+) {
+    let node = registry.clone().register(path.to_path_buf());
+    let trasher = actors.trasher.as_ref().unwrap();
+    trasher
+        .send(TrashCommand::Trash {
+            nodes: vec![node],
+            session,
+        })
+        .unwrap();
+    assert!(wait(events, completed(OperationKind::Delete)).await);
+}
+
+#[tokio::test]
+async fn test_system_trash_can_be_undone() {
+    let (_dir, root) = tempdir();
+    let file = root.join("a.txt");
+    fs::write(&file, b"a").unwrap();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    let actors = System::new(evt_tx, registry.clone())
+        .with_journal(Journal::in_memory())
+        .with_trash(TrashBin::with_home(root.join("Trash")))
+        .spawn();
+    let session = SessionId::new();
+
+    trash(&actors, &evt_rx, &registry, &file, session).await;
+    assert!(!file.exists());
+    actors.operations.send(OpCommand::Undo(session)).unwrap();
+    assert!(wait(&evt_rx, completed(OperationKind::Undo)).await);
+    assert_eq!(fs::read(&file).unwrap(), b"a");
+}
+
+#[tokio::test]
+async fn test_system_named_session_undoes_after_restart() {
+    let (_dir, root) = tempdir();
+    let file = root.join("a.txt");
+    fs::write(&file, b"a").unwrap();
+    let journal = root.join("state/journal.json");
+    let spawn = |events, registry| {
+        System::new(events, registry)
+            .with_journal(Journal::open(journal.clone()).unwrap())
+            .with_trash(TrashBin::with_home(root.join("Trash")))
+            .spawn()
+    };
+    {
+        let (evt_tx, evt_rx) = flume::unbounded();
+        let registry = NodeRegistry::new();
+        let actors = spawn(evt_tx, registry.clone());
+        let session = SessionId::new();
+        open_session(&actors, &evt_rx, session, "work").await;
+        trash(&actors, &evt_rx, &registry, &file, session).await;
+    }
+    assert!(!file.exists());
+
+    // The next run's session has another id but the same name
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let actors = spawn(evt_tx, NodeRegistry::new());
+    let stranger = SessionId::new();
+    actors.operations.send(OpCommand::Undo(stranger)).unwrap();
+    assert!(!wait(&evt_rx, completed(OperationKind::Undo)).await);
+    let session = SessionId::new();
+    open_session(&actors, &evt_rx, session, "work").await;
+    actors.operations.send(OpCommand::Undo(session)).unwrap();
+    assert!(wait(&evt_rx, completed(OperationKind::Undo)).await);
+    assert_eq!(fs::read(&file).unwrap(), b"a");
+}
+
+/// Wait until `count` requests wait for a slot
+async fn until_queued(scheduler: &IoScheduler, count: usize) {
+    let queued = async {
+        while scheduler.queued() < count {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), queued)
+        .await
+        .expect("requests never queued");
+}
+
+#[tokio::test]
+async fn test_system_listings_go_ahead_of_transfers() {
+    let (_dir, root) = tempdir();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+    fs::create_dir(root.join("dest")).unwrap();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    let scheduler = IoScheduler::new(SchedulerConfig {
+        local_slots: 1,
+        reserved: 0,
+        ..Default::default()
+    });
+    let actors = System::new(evt_tx, registry.clone())
+        .with_scheduler(scheduler)
+        .spawn();
+    let session = SessionId::new();
+    let busy = actors
+        .scheduler
+        .request(IoTicket::new(IoClass::Bulk).on(DeviceKey::of_path(&root)))
+        .granted()
+        .await;
+
+    // The copy queues first, the listing of its destination after it
+    let dest = root.join("dest");
+    actors
+        .operations
+        .send(OpCommand::Copy {
+            sources: vec![registry.clone().register(root.join("a.txt"))],
+            destination: registry.clone().register(dest.clone()),
+            options: TransferOptions::default(),
+            session,
+        })
+        .unwrap();
+    until_queued(&actors.scheduler, 1).await;
+    scan(&actors, &registry, &dest, session);
+    until_queued(&actors.scheduler, 2).await;
+
+    // Listed before the copy could write into it
+    drop(busy);
+    let (mut listing, mut copied) = (None, None);
+    while listing.is_none() || copied.is_none() {
+        match wait(&evt_rx, |event| match event {
+            Event::DirectoryLoaded { .. } | Event::OperationComplete { .. } => Some(event),
+            _ => None,
+        })
+        .await
+        {
+            Event::DirectoryLoaded { entries, .. } => listing = Some(entries),
+            Event::OperationComplete { success, .. } => copied = Some(success),
+            _ => unreachable!(),
+        }
+    }
+    assert_eq!(listing.unwrap().len(), 0);
+    assert_eq!(copied, Some(true));
+    assert!(dest.join("a.txt").exists());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_system_watched_pair_syncs_on_change() {
+    let (_dir, root) = tempdir();
+    let (left, right) = (root.join("left"), root.join("right"));
+    fs::create_dir(&left).unwrap();
+    fs::create_dir(&right).unwrap();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    // Longer than the watcher's debounce, so the run's own changes fall in it
+    let settle = Duration::from_millis(300);
+    let actors = System::new(evt_tx, NodeRegistry::new())
+        .with_syncer_config(SyncerConfig { settle })
+        .with_sync_states(root.join("state"))
+        .spawn();
+    let pair = SyncPair::new("docs".to_string(), left.clone(), right.clone()).with_watch(true);
+    actors
+        .syncer
+        .send(SyncerCommand::Add(Box::new(pair)))
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    fs::write(left.join("a.txt"), b"a").unwrap();
+    let synced = |event| match event {
+        Event::BisyncFinished { pair, .. } => Some(pair),
+        _ => None,
+    };
+    assert_eq!(wait(&evt_rx, synced).await, "docs");
+    assert_eq!(fs::read(right.join("a.txt")).unwrap(), b"a");
+
+    // Writing a.txt on the right is the run's own change, not a new one
+    let again = timeout(settle * 3, async {
+        loop {
+            if let Ok(Event::BisyncFinished { .. }) = evt_rx.recv_async().await {
+                return;
+            }
+        }
+    });
+    assert!(again.await.is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_system_watcher_invalidates_cached_listing() {
+    let (_dir, root) = tempdir();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    let actors = System::new(evt_tx, registry.clone()).spawn();
+    let session = SessionId::new();
+
+    actors
+        .watcher
+        .send(WatchCommand::Watch(root.clone(), session))
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    scan(&actors, &registry, &root, session);
+    assert_eq!(wait(&evt_rx, loaded).await[0].size, 1);
+
+    // Rewriting a file leaves the directory's stamp alone; only the
+    // watcher's invalidation keeps the cache from serving the old size
+    fs::write(root.join("a.txt"), b"aaa").unwrap();
+    wait(&evt_rx, |event| {
+        matches!(event, Event::FsChanged { .. }).then_some(())
+    })
+    .await;
+    scan(&actors, &registry, &root, session);
+    assert_eq!(wait(&evt_rx, loaded).await[0].size, 3);
+}
+
+#[tokio::test]
+async fn test_system_poller_invalidates_cached_listing() {
+    let (_dir, root) = tempdir();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    let config = PollerConfig {
+        interval: Duration::from_millis(50),
+        jitter: 0.0,
+        ..Default::default()
+    };
+    let actors = System::new(evt_tx, registry.clone())
+        .with_poller_config(config)
+        .spawn();
+    let session = SessionId::new();
+
+    actors
+        .poller
+        .send(PollCommand::Watch(root.clone(), session))
+        .unwrap();
+    scan(&actors, &registry, &root, session);
+    assert_eq!(wait(&evt_rx, loaded).await[0].size, 1);
+    // Let the baseline poll run before the change
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    fs::write(root.join("a.txt"), b"aaa").unwrap();
+    wait(&evt_rx, |event| {
+        matches!(event, Event::FsChanged { .. }).then_some(())
+    })
+    .await;
+    scan(&actors, &registry, &root, session);
+    assert_eq!(wait(&evt_rx, loaded).await[0].size, 3);
+}
+
+/// Audio tags named after the file: "07 Artist.mp3"
+struct NameTags;
+
+#[async_trait]
+impl MetadataExtractor for NameTags {
+    fn supported_categories(&self) -> &[MimeCategory] {
+        &[MimeCategory::Audio]
+    }
+
+    async fn extract(&self, path: &Path) -> Result<ExtendedMetadata, CoreError> {
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        let (track, artist) = stem.split_once(' ').unwrap();
+        Ok(ExtendedMetadata::Audio(AudioMetadata {
+            duration_secs: 1.0,
+            sample_rate: None,
+            channels: None,
+            bit_rate: None,
+            format: "mp3".to_string(),
+            tags: AudioTags {
+                artist: Some(artist.to_string()),
+                track: track.parse().ok(),
+                ..AudioTags::default()
+            },
+        }))
+    }
+
+    fn name(&self) -> &'static str {
+        "name-tags"
+    }
+}
+
+#[tokio::test]
+async fn test_system_batch_rename_reads_audio_tags() {
+    let (_dir, root) = tempdir();
+    fs::write(root.join("7 Nina.mp3"), b"a").unwrap();
+    fs::write(root.join("12 Etta.mp3"), b"b").unwrap();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let registry = NodeRegistry::new();
+    let mut metadata = MetadataRegistry::new();
+    metadata.register(Box::new(NameTags));
+    let actors = System::new(evt_tx, registry.clone())
+        .with_metadata(Arc::new(metadata))
+        .spawn();
+
+    actors
+        .operations
+        .send(OpCommand::BatchRename {
+            nodes: ["7 Nina.mp3", "12 Etta.mp3"]
+                .map(|name| registry.clone().register(root.join(name)))
+                .to_vec(),
+            rule: RenameRule {
+                template: Some("{track} - {artist}{ext}".to_string()),
+                ..Default::default()
+            },
+            dry_run: false,
+            session: SessionId::new(),
+        })
+        .unwrap();
+
+    assert!(wait(&evt_rx, completed(OperationKind::Rename)).await);
+    let mut names: Vec<_> = fs::read_dir(&root)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["07 - Nina.mp3", "12 - Etta.mp3"]);
+}
@@ -0,0 +1,384 @@
+//! Tests for tags, labels and notes, their pipeline stages, `TagsFs` and
+//! the Operations actor keeping them with relocated files
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::actors::operations::OpCommand;
+use crate::api::events::OperationKind;
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeId, NodeKind, NodeMeta};
+use crate::model::registry::NodeRegistry;
+use crate::pipeline::{
+    FilterConfig, GroupBy, Listing, ListingDelta, Pipeline, PipelineConfig, PipelineData,
+};
+use crate::services::journal::Journal;
+use crate::services::tags::{ColorLabel, FileTags, TagEdit, TagStore};
+use crate::services::transfer::TransferOptions;
+use crate::tests::common::{spawn_operations, tempdir};
+use crate::utils::xattr;
+use crate::vfs::provider::FsProvider;
+use crate::vfs::tags::TagsFs;
+
+fn touch(path: &Path) -> PathBuf {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(path, b"data").unwrap();
+    path.to_path_buf()
+}
+
+fn tags(names: &[&str]) -> FileTags {
+    FileTags {
+        tags: names.iter().map(|t| t.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn raw_tags(path: &Path) -> Option<String> {
+    xattr::get(path, "user.xdg.tags")
+        .unwrap()
+        .map(|value| String::from_utf8(value).unwrap())
+}
+
+// ===== Store Tests =====
+
+#[test]
+fn test_tags_stored_in_xattrs_and_indexed() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("bill.pdf"));
+    let store = TagStore::in_memory();
+
+    let result = store
+        .edit(
+            &file,
+            &TagEdit::Set(vec!["review".into(), " invoice ".into(), "review".into()]),
+        )
+        .unwrap();
+    assert_eq!(result.tags, vec!["invoice", "review"]);
+    assert_eq!(raw_tags(&file).as_deref(), Some("invoice,review"));
+
+    store
+        .edit(&file, &TagEdit::Label(Some(ColorLabel::Red)))
+        .unwrap();
+    store
+        .edit(&file, &TagEdit::Note(Some("pay by Friday".into())))
+        .unwrap();
+    assert_eq!(
+        xattr::get(&file, "user.xdg.comment").unwrap(),
+        Some(b"pay by Friday".to_vec())
+    );
+    let found = store.get(&file);
+    assert_eq!(found.label, Some(ColorLabel::Red));
+    assert_eq!(found.note.as_deref(), Some("pay by Friday"));
+
+    assert_eq!(store.tagged("invoice"), vec![file.clone()]);
+    assert_eq!(
+        store.tags(),
+        vec![("invoice".to_string(), 1), ("review".to_string(), 1)]
+    );
+}
+
+#[test]
+fn test_tags_fall_back_to_database_and_persist() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("notes.txt"));
+    let db = root.join("state/tags.json");
+
+    let store = TagStore::open(db.clone()).unwrap().with_xattrs(false);
+    store.set(&file, tags(&["draft"])).unwrap();
+    assert_eq!(raw_tags(&file), None);
+    assert!(db.exists());
+
+    let reopened = TagStore::open(db).unwrap().with_xattrs(false);
+    assert_eq!(reopened.get(&file).tags, vec!["draft"]);
+    assert_eq!(reopened.tagged("draft"), vec![file]);
+}
+
+#[test]
+fn test_tags_from_other_tools_are_read_and_learned() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("photo.jpg"));
+    xattr::set(&file, "user.xdg.tags", b"holiday, family,,holiday").unwrap();
+    let store = TagStore::in_memory();
+    assert!(store.tagged("holiday").is_empty());
+
+    let mut nodes = vec![FileNode::from_path(file.clone(), None).unwrap()];
+    store.annotate(&mut nodes);
+    assert_eq!(nodes[0].meta.tags, vec!["family", "holiday"]);
+    assert_eq!(store.tagged("holiday"), vec![file]);
+}
+
+#[test]
+fn test_tag_edits_validate_and_clear() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("a.txt"));
+    let store = TagStore::in_memory();
+
+    for bad in ["", "  ", "a,b", "a/b"] {
+        assert!(matches!(
+            store.edit(&file, &TagEdit::Add(bad.into())),
+            Err(CoreError::InvalidInput)
+        ));
+    }
+    assert!(matches!(
+        store.edit(&root.join("missing"), &TagEdit::Add("x".into())),
+        Err(CoreError::NotFound(_))
+    ));
+
+    store.edit(&file, &TagEdit::Add("x".into())).unwrap();
+    store
+        .edit(&file, &TagEdit::Note(Some("hi".into())))
+        .unwrap();
+    store.edit(&file, &TagEdit::Remove("x".into())).unwrap();
+    assert_eq!(raw_tags(&file), None);
+    store
+        .edit(&file, &TagEdit::Note(Some(String::new())))
+        .unwrap();
+
+    assert!(store.get(&file).is_empty());
+    assert_eq!(xattr::get(&file, "user.xdg.comment").unwrap(), None);
+    assert!(store.tags().is_empty());
+}
+
+#[test]
+fn test_moved_follows_directories_and_rewrites_lost_xattrs() {
+    let (_dir, root) = tempdir();
+    let inner = touch(&root.join("project/docs/spec.md"));
+    let store = TagStore::in_memory();
+    store.set(&inner, tags(&["spec"])).unwrap();
+
+    // A rename keeps the attributes; only the index changes
+    fs::rename(root.join("project"), root.join("renamed")).unwrap();
+    store
+        .moved(&root.join("project"), &root.join("renamed"))
+        .unwrap();
+    let renamed = root.join("renamed/docs/spec.md");
+    assert_eq!(store.tagged("spec"), vec![renamed.clone()]);
+
+    // A copy-and-delete move loses them; they are written again
+    let target = root.join("spec.md");
+    fs::copy(&renamed, &target).unwrap();
+    fs::remove_file(&renamed).unwrap();
+    assert_eq!(raw_tags(&target), None);
+    store.moved(&renamed, &target).unwrap();
+    assert_eq!(raw_tags(&target).as_deref(), Some("spec"));
+    assert_eq!(store.tagged("spec"), vec![target]);
+}
+
+#[test]
+fn test_moved_updates_database_records() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("a.txt"));
+    let store = TagStore::in_memory().with_xattrs(false);
+    store.set(&file, tags(&["x"])).unwrap();
+
+    fs::rename(&file, root.join("b.txt")).unwrap();
+    store.moved(&file, &root.join("b.txt")).unwrap();
+    assert!(store.get(&file).is_empty());
+    assert_eq!(store.get(&root.join("b.txt")).tags, vec!["x"]);
+}
+
+// ===== Pipeline Tests =====
+
+fn node(name: &str, tags: &[&str]) -> FileNode {
+    FileNode {
+        id: NodeId::from_path(&PathBuf::from(name)),
+        name: name.to_string(),
+        path: PathBuf::from(format!("/test/{name}")),
+        kind: NodeKind::File { extension: None },
+        size: 0,
+        modified: None,
+        created: None,
+        meta: NodeMeta {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        },
+    }
+}
+
+#[test]
+fn test_filter_by_tag() {
+    let config = PipelineConfig::new().filter(FilterConfig {
+        show_hidden: true,
+        tags: vec!["a".into(), "c".into()],
+        ..Default::default()
+    });
+    let nodes = vec![
+        node("one", &["a"]),
+        node("two", &["b"]),
+        node("three", &["b", "c"]),
+        node("four", &[]),
+    ];
+    let names: Vec<String> = Pipeline::from_config(&config)
+        .execute_flat(nodes)
+        .into_iter()
+        .map(|n| n.name)
+        .collect();
+    assert_eq!(names, vec!["one", "three"]);
+}
+
+#[test]
+fn test_group_by_tag_puts_nodes_in_every_group() {
+    let config = PipelineConfig::new().group_by(GroupBy::Tag);
+    let nodes = vec![
+        node("one", &["a", "b"]),
+        node("two", &["b"]),
+        node("three", &[]),
+    ];
+    let PipelineData::Grouped(grouped) = Pipeline::from_config(&config).execute(nodes) else {
+        panic!("expected groups");
+    };
+    let groups: Vec<(String, Vec<String>)> = grouped
+        .groups
+        .into_iter()
+        .map(|g| (g.label, g.nodes.into_iter().map(|n| n.name).collect()))
+        .collect();
+    assert_eq!(
+        groups,
+        vec![
+            ("Untagged".to_string(), vec!["three".to_string()]),
+            ("a".to_string(), vec!["one".to_string()]),
+            ("b".to_string(), vec!["one".to_string(), "two".to_string()]),
+        ]
+    );
+}
+
+#[test]
+fn test_delta_reports_tag_changes() {
+    let old = Listing::from_data(PipelineData::Flat(vec![node("one", &[])]));
+    let new = Listing::from_data(PipelineData::Flat(vec![node("one", &["a"])]));
+    let delta = ListingDelta::between(&old, &new);
+    assert_eq!(delta.modified.len(), 1);
+}
+
+// ===== TagsFs Tests =====
+
+#[tokio::test]
+async fn test_tags_fs_lists_tags_and_tagged_files() {
+    let (_dir, root) = tempdir();
+    let a = touch(&root.join("a.txt"));
+    let b = touch(&root.join("sub/b.txt"));
+    let untagged = touch(&root.join("c.txt"));
+    let store = TagStore::in_memory();
+    store.set(&a, tags(&["work"])).unwrap();
+    store.set(&b, tags(&["home", "work"])).unwrap();
+    store.set(&root.join("sub"), tags(&["home"])).unwrap();
+    let fs = TagsFs::new(store, NodeRegistry::new());
+
+    let root_list: Vec<(String, Option<u32>)> = fs
+        .list(Path::new("/"))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|n| match n.kind {
+            NodeKind::Directory { children_count } => (n.name, children_count),
+            _ => panic!("tags are directories"),
+        })
+        .collect();
+    assert_eq!(
+        root_list,
+        vec![("home".to_string(), Some(2)), ("work".to_string(), Some(2))]
+    );
+
+    let work = fs.list(Path::new("/work")).await.unwrap();
+    let paths: Vec<PathBuf> = work.iter().map(|n| n.path.clone()).collect();
+    assert_eq!(paths, vec![a.clone(), b.clone()]);
+    assert_eq!(work[1].meta.tags, vec!["home", "work"]);
+
+    // Tagged items and what tagged directories hold are reachable
+    assert_eq!(fs.read(&a).await.unwrap(), b"data");
+    assert_eq!(fs.list(&root.join("sub")).await.unwrap().len(), 1);
+    assert!(matches!(
+        fs.read(&untagged).await,
+        Err(CoreError::NotFound(_))
+    ));
+    assert!(!fs.exists(Path::new("/nothing")).await.unwrap());
+    assert!(fs.exists(Path::new("/home")).await.unwrap());
+}
+
+// ===== Operations Actor Tests =====
+
+#[tokio::test]
+async fn test_operations_edit_tags() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("a.txt"));
+    let store = TagStore::in_memory();
+    let h = spawn_operations(|ops| {
+        ops.with_journal(Journal::in_memory())
+            .with_tags(store.clone())
+    });
+    let node = h.registry.clone().register(file.clone());
+
+    let done = h
+        .complete(OpCommand::EditTags {
+            nodes: vec![node],
+            edit: TagEdit::Add("urgent".into()),
+            session: h.session,
+        })
+        .await;
+    assert!(matches!(done, (true, OperationKind::EditTags)));
+    assert_eq!(store.get(&file).tags, vec!["urgent"]);
+
+    let done = h
+        .complete(OpCommand::EditTags {
+            nodes: vec![node],
+            edit: TagEdit::Add("a,b".into()),
+            session: h.session,
+        })
+        .await;
+    assert!(!done.0);
+
+    let without = spawn_operations(|ops| ops.with_journal(Journal::in_memory()));
+    let node = without.registry.clone().register(file);
+    let done = without
+        .complete(OpCommand::EditTags {
+            nodes: vec![node],
+            edit: TagEdit::Add("x".into()),
+            session: without.session,
+        })
+        .await;
+    assert!(!done.0);
+}
+
+#[tokio::test]
+async fn test_tags_follow_rename_undo_and_move() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("a.txt"));
+    let dest = root.join("dest");
+    fs::create_dir(&dest).unwrap();
+    let store = TagStore::in_memory().with_xattrs(false);
+    store.set(&file, tags(&["keep"])).unwrap();
+    let h = spawn_operations(|ops| {
+        ops.with_journal(Journal::in_memory())
+            .with_tags(store.clone())
+    });
+
+    let node = h.registry.clone().register(file.clone());
+    let done = h
+        .complete(OpCommand::Rename {
+            node,
+            new_name: "b.txt".into(),
+            session: h.session,
+        })
+        .await;
+    assert!(done.0);
+    assert_eq!(store.tagged("keep"), vec![root.join("b.txt")]);
+
+    assert!(h.complete(OpCommand::Undo(h.session)).await.0);
+    assert_eq!(store.tagged("keep"), vec![file.clone()]);
+    assert!(h.complete(OpCommand::Redo(h.session)).await.0);
+    assert_eq!(store.tagged("keep"), vec![root.join("b.txt")]);
+
+    let node = h.registry.clone().register(root.join("b.txt"));
+    let done = h
+        .complete(OpCommand::Move {
+            sources: vec![node],
+            destination: h.registry.clone().register(dest.clone()),
+            options: TransferOptions::default(),
+            session: h.session,
+        })
+        .await;
+    assert!(matches!(done, (true, OperationKind::Move)));
+    assert_eq!(store.tagged("keep"), vec![dest.join("b.txt")]);
+}
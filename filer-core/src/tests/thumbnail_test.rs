@@ -0,0 +1,290 @@
+//! Tests for the freedesktop thumbnail cache and the preview cache tiers
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::services::preview::{ImageFormat, PreviewCache, PreviewData, ThumbnailSize, ThumbnailStore};
+use crate::services::scheduler::{DeviceKey, IoClass, IoScheduler, IoTicket, SchedulerConfig};
+
+/// Build a PNG container with the given size and tEXt chunks
+///
+/// The pixel data is not valid; the store never decodes it.
+fn make_png(width: u32, height: u32, text: &[(&str, &str)]) -> Vec<u8> {
+    fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(kind);
+        crc.update(data);
+        out.extend_from_slice(&crc.finalize().to_be_bytes());
+    }
+    let mut out = vec![137, 80, 78, 71, 13, 10, 26, 10];
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &ihdr);
+    for (key, value) in text {
+        chunk(&mut out, b"tEXt", format!("{key}\0{value}").as_bytes());
+    }
+    chunk(&mut out, b"IDAT", &[0; 4]);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+struct Fixture {
+    _dir: tempfile::TempDir,
+    store: ThumbnailStore,
+    file: PathBuf,
+}
+
+fn fixture() -> Fixture {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let file = root.join("photo.png");
+    fs::write(&file, b"original image").unwrap();
+    Fixture {
+        store: ThumbnailStore::with_root(root.join("thumbnails")),
+        file,
+        _dir: dir,
+    }
+}
+
+fn touch(path: &Path, secs_later: u64) {
+    let f = fs::File::options().write(true).open(path).unwrap();
+    let mtime = fs::metadata(path).unwrap().modified().unwrap();
+    f.set_modified(mtime + Duration::from_secs(secs_later)).unwrap();
+}
+
+fn mtime_secs(path: &Path) -> String {
+    fs::metadata(path)
+        .unwrap()
+        .modified()
+        .unwrap()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .to_string()
+}
+
+// ===== ThumbnailStore Tests =====
+
+#[test]
+fn test_thumbnail_uri_and_name_follow_spec() {
+    let store = ThumbnailStore::with_root(PathBuf::from("/cache/thumbnails"));
+    // Example from the thumbnail spec
+    let path = Path::new("/home/jens/photos/me.png");
+    assert_eq!(ThumbnailStore::uri_for(path), "file:///home/jens/photos/me.png");
+    assert_eq!(
+        store.thumbnail_path(path, ThumbnailSize::Normal),
+        PathBuf::from("/cache/thumbnails/normal/c6ee772d9e49320e97ec29a7eb5b1697.png")
+    );
+    assert!(store.thumbnail_path(path, ThumbnailSize::XLarge).starts_with("/cache/thumbnails/x-large"));
+
+    // Escaping matches g_filename_to_uri
+    assert_eq!(
+        ThumbnailStore::uri_for(Path::new("/tmp/a b(1)#é.png")),
+        "file:///tmp/a%20b(1)%23%C3%A9.png"
+    );
+}
+
+#[test]
+fn test_thumbnail_size_buckets() {
+    assert_eq!(ThumbnailSize::for_edge(100), Some(ThumbnailSize::Normal));
+    assert_eq!(ThumbnailSize::for_edge(256), Some(ThumbnailSize::Large));
+    assert_eq!(ThumbnailSize::for_edge(400), Some(ThumbnailSize::XLarge));
+    assert_eq!(ThumbnailSize::for_edge(2000), None);
+}
+
+#[test]
+fn test_thumbnail_store_and_lookup() {
+    let f = fixture();
+    let target = f.store.store(&f.file, ThumbnailSize::Large, &make_png(256, 200, &[])).unwrap();
+    assert_eq!(target, f.store.thumbnail_path(&f.file, ThumbnailSize::Large));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    let thumb = f.store.lookup(&f.file, ThumbnailSize::Normal).unwrap().unwrap();
+    assert_eq!(thumb.size, ThumbnailSize::Large);
+    assert_eq!((thumb.width, thumb.height), (256, 200));
+
+    // A smaller thumbnail never satisfies a bigger request
+    assert!(f.store.lookup(&f.file, ThumbnailSize::XLarge).unwrap().is_none());
+}
+
+#[test]
+fn test_thumbnail_reuses_foreign_thumbnails() {
+    let f = fixture();
+    let uri = ThumbnailStore::uri_for(&f.file);
+    let mtime = mtime_secs(&f.file);
+    let foreign = make_png(
+        128,
+        96,
+        &[("Thumb::URI", &uri), ("Thumb::MTime", &mtime), ("Software", "GNOME::ThumbnailFactory")],
+    );
+    let path = f.store.thumbnail_path(&f.file, ThumbnailSize::Normal);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, foreign).unwrap();
+
+    let thumb = f.store.lookup(&f.file, ThumbnailSize::Normal).unwrap().unwrap();
+    assert_eq!((thumb.width, thumb.height), (128, 96));
+}
+
+#[test]
+fn test_thumbnail_discards_stale_entries() {
+    let f = fixture();
+    f.store.store(&f.file, ThumbnailSize::Normal, &make_png(64, 64, &[])).unwrap();
+    touch(&f.file, 10);
+
+    assert!(f.store.lookup(&f.file, ThumbnailSize::Normal).unwrap().is_none());
+    assert!(!f.store.thumbnail_path(&f.file, ThumbnailSize::Normal).exists());
+}
+
+#[test]
+fn test_thumbnail_rejects_uri_mismatch() {
+    let f = fixture();
+    let mtime = mtime_secs(&f.file);
+    let wrong = make_png(64, 64, &[("Thumb::URI", "file:///elsewhere"), ("Thumb::MTime", &mtime)]);
+    let path = f.store.thumbnail_path(&f.file, ThumbnailSize::Normal);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, wrong).unwrap();
+
+    assert!(f.store.lookup(&f.file, ThumbnailSize::Normal).unwrap().is_none());
+}
+
+#[test]
+fn test_thumbnail_fail_cache() {
+    let f = fixture();
+    assert!(!f.store.has_failed(&f.file));
+
+    f.store.mark_failed(&f.file).unwrap();
+    assert!(f.store.fail_path(&f.file).starts_with(f.store.root().join("fail")));
+    assert!(f.store.has_failed(&f.file));
+
+    // A changed file gets another chance
+    touch(&f.file, 10);
+    assert!(!f.store.has_failed(&f.file));
+}
+
+#[test]
+fn test_thumbnail_remove() {
+    let f = fixture();
+    f.store.store(&f.file, ThumbnailSize::Normal, &make_png(64, 64, &[])).unwrap();
+    f.store.mark_failed(&f.file).unwrap();
+
+    f.store.remove(&f.file);
+    assert!(f.store.lookup(&f.file, ThumbnailSize::Normal).unwrap().is_none());
+    assert!(!f.store.has_failed(&f.file));
+}
+
+#[test]
+fn test_thumbnail_store_takes_a_preview_slot() {
+    let f = fixture();
+    let scheduler = IoScheduler::new(SchedulerConfig {
+        local_slots: 1,
+        reserved: 0,
+        ..Default::default()
+    });
+    let store = f.store.clone().with_scheduler(scheduler.clone());
+    let device = DeviceKey::of_path(&f.file);
+    let busy = scheduler.request(IoTicket::new(IoClass::Bulk).on(device.clone())).wait();
+    let background = scheduler.request(IoTicket::new(IoClass::Background).on(device));
+    let thumbnail = f.store.thumbnail_path(&f.file, ThumbnailSize::Normal);
+    let waiter = std::thread::spawn(move || {
+        let _permit = background.wait();
+        thumbnail.exists()
+    });
+
+    let file = f.file.clone();
+    let writer = std::thread::spawn(move || {
+        store.store(&file, ThumbnailSize::Normal, &make_png(64, 64, &[])).unwrap();
+    });
+    while scheduler.queued() < 2 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(!f.store.thumbnail_path(&f.file, ThumbnailSize::Normal).exists());
+
+    // The thumbnail goes ahead of background work queued before it
+    drop(busy);
+    writer.join().unwrap();
+    assert!(waiter.join().unwrap());
+}
+
+// ===== PreviewCache Tests =====
+
+fn image(png: Vec<u8>, edge: u32) -> PreviewData {
+    PreviewData::Image {
+        data: png,
+        format: ImageFormat::Png,
+        width: edge,
+        height: edge,
+        original_width: edge * 4,
+        original_height: edge * 4,
+    }
+}
+
+#[test]
+fn test_preview_cache_memory_tier() {
+    let mut cache = PreviewCache::new(1 << 20, Duration::from_secs(60));
+    let path = PathBuf::from("/virtual/file.txt");
+    cache.put(
+        path.clone(),
+        PreviewData::Text {
+            content: "hello".to_string(),
+            truncated: false,
+            total_lines: 1,
+        },
+    );
+    assert!(matches!(cache.get(&path), Some(PreviewData::Text { .. })));
+
+    cache.invalidate(&path);
+    assert!(cache.get(&path).is_none());
+}
+
+#[test]
+fn test_preview_cache_evicts_to_budget() {
+    let mut cache = PreviewCache::new(3000, Duration::from_secs(60));
+    for i in 0..10 {
+        cache.put(PathBuf::from(format!("/v/{i}")), image(vec![0; 1000], 10));
+    }
+    let kept = (0..10)
+        .filter(|i| cache.get(&PathBuf::from(format!("/v/{i}"))).is_some())
+        .count();
+    assert!((1..=2).contains(&kept), "kept {kept}");
+    assert!(cache.get(&PathBuf::from("/v/9")).is_some());
+}
+
+#[test]
+fn test_preview_cache_expires_entries() {
+    let mut cache = PreviewCache::new(1 << 20, Duration::ZERO);
+    let path = PathBuf::from("/v/x");
+    cache.put(path.clone(), image(vec![1, 2, 3], 10));
+    assert!(cache.get(&path).is_none());
+}
+
+#[test]
+fn test_preview_cache_disk_tier_survives_restart() {
+    let f = fixture();
+    let mut cache = PreviewCache::new(1 << 20, Duration::from_secs(60))
+        .with_thumbnails(f.store.clone(), ThumbnailSize::Normal);
+    cache.put(f.file.clone(), image(make_png(128, 128, &[]), 128));
+    assert!(f.store.thumbnail_path(&f.file, ThumbnailSize::Normal).exists());
+
+    // A fresh cache (next launch) finds it on disk
+    let mut cache = PreviewCache::new(1 << 20, Duration::from_secs(60))
+        .with_thumbnails(f.store.clone(), ThumbnailSize::Normal);
+    match cache.load(&f.file) {
+        Some(PreviewData::Image { width, format: ImageFormat::Png, .. }) => assert_eq!(width, 128),
+        other => panic!("unexpected preview: {other:?}"),
+    }
+    assert!(cache.get(&f.file).is_some());
+
+    cache.mark_failed(&f.file);
+    assert!(cache.has_failed(&f.file));
+}
@@ -0,0 +1,362 @@
+//! Tests for FreeDesktop trash support
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::trasher::{TrashCommand, Trasher};
+use crate::api::events::{Event, OperationKind};
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::trash::{DirectorySizes, RestoreConflict, TrashBin, TrashInfo};
+use crate::vfs::provider::FsProvider;
+use crate::vfs::trash::TrashFs;
+
+fn setup() -> (tempfile::TempDir, TrashBin, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = TrashBin::with_home(dir.path().join("Trash"));
+    let work = dir.path().join("work");
+    fs::create_dir(&work).unwrap();
+    (dir, bin, work.canonicalize().unwrap())
+}
+
+// ===== TrashInfo Tests =====
+
+#[test]
+fn test_trash_info_roundtrip() {
+    let date = NaiveDate::from_ymd_opt(2004, 8, 31)
+        .unwrap()
+        .and_hms_opt(22, 32, 8)
+        .unwrap();
+    let info = TrashInfo::new(PathBuf::from("/home/user/a file.txt"), date);
+    let text = info.to_string_relative(None);
+
+    assert!(text.starts_with("[Trash Info]\n"));
+    assert!(text.contains("Path=/home/user/a%20file.txt\n"));
+    assert!(text.contains("DeletionDate=2004-08-31T22:32:08\n"));
+    assert_eq!(TrashInfo::parse(&text, None).unwrap(), info);
+}
+
+#[test]
+fn test_trash_info_relative_to_topdir() {
+    let date = NaiveDate::from_ymd_opt(2020, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let info = TrashInfo::new(PathBuf::from("/mnt/usb/photos/cat.jpg"), date);
+    let text = info.to_string_relative(Some(Path::new("/mnt/usb")));
+
+    assert!(text.contains("Path=photos/cat.jpg\n"));
+    let parsed = TrashInfo::parse(&text, Some(Path::new("/mnt/usb"))).unwrap();
+    assert_eq!(parsed.original_path, PathBuf::from("/mnt/usb/photos/cat.jpg"));
+}
+
+#[test]
+fn test_trash_info_rejects_missing_header() {
+    assert!(TrashInfo::parse("Path=/a\nDeletionDate=2020-01-01T00:00:00\n", None).is_err());
+}
+
+// ===== TrashBin Tests =====
+
+#[test]
+fn test_trash_file_creates_info() {
+    let (_dir, bin, work) = setup();
+    let file = work.join("note.txt");
+    fs::write(&file, b"hello").unwrap();
+
+    let item = bin.trash(&file).unwrap();
+
+    assert!(!file.exists());
+    assert_eq!(item.name, "note.txt");
+    assert_eq!(item.size, 5);
+    assert_eq!(fs::read(item.files_path()).unwrap(), b"hello");
+    let info = fs::read_to_string(item.info_path()).unwrap();
+    assert!(info.contains(&format!("Path={}", file.display())));
+}
+
+#[test]
+fn test_trash_name_collision() {
+    let (_dir, bin, work) = setup();
+    let file = work.join("dup.txt");
+
+    fs::write(&file, b"1").unwrap();
+    let first = bin.trash(&file).unwrap();
+    fs::write(&file, b"2").unwrap();
+    let second = bin.trash(&file).unwrap();
+
+    assert_eq!(first.name, "dup.txt");
+    assert_eq!(second.name, "dup.2.txt");
+    assert_eq!(bin.list().len(), 2);
+
+    // A dangling symlink in files/ holds its name
+    std::os::unix::fs::symlink("missing", bin.home().join("files/dup.3.txt")).unwrap();
+    fs::write(&file, b"3").unwrap();
+    assert_eq!(bin.trash(&file).unwrap().name, "dup.4.txt");
+    assert!(fs::symlink_metadata(bin.home().join("files/dup.3.txt")).unwrap().is_symlink());
+}
+
+#[test]
+fn test_trash_directory_updates_directorysizes() {
+    let (_dir, bin, work) = setup();
+    let sub = work.join("folder");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("a"), vec![0u8; 10]).unwrap();
+    fs::write(sub.join("b"), vec![0u8; 20]).unwrap();
+
+    let item = bin.trash(&sub).unwrap();
+    assert!(item.is_dir);
+    assert_eq!(item.size, 30);
+    assert_eq!(DirectorySizes::load(bin.home()).get("folder"), Some(30));
+
+    bin.erase(&item).unwrap();
+    assert!(DirectorySizes::load(bin.home()).is_empty());
+}
+
+#[test]
+fn test_restore_to_original_location() {
+    let (_dir, bin, work) = setup();
+    let file = work.join("back.txt");
+    fs::write(&file, b"data").unwrap();
+    let item = bin.trash(&file).unwrap();
+
+    let restored = bin.restore(&item, RestoreConflict::Fail).unwrap();
+
+    assert_eq!(restored, file);
+    assert_eq!(fs::read(&file).unwrap(), b"data");
+    assert!(!item.info_path().exists());
+    assert!(bin.list().is_empty());
+}
+
+#[test]
+fn test_restore_conflict_policies() {
+    let (_dir, bin, work) = setup();
+    let file = work.join("c.txt");
+
+    fs::write(&file, b"old").unwrap();
+    let item = bin.trash(&file).unwrap();
+    fs::write(&file, b"new").unwrap();
+
+    assert!(bin.restore(&item, RestoreConflict::Fail).is_err());
+    assert_eq!(bin.list().len(), 1);
+
+    let restored = bin.restore(&item, RestoreConflict::KeepBoth).unwrap();
+    assert_eq!(restored, work.join("c (2).txt"));
+    assert_eq!(fs::read(&restored).unwrap(), b"old");
+    assert_eq!(fs::read(&file).unwrap(), b"new");
+
+    let item = bin.trash(&restored).unwrap();
+    let restored = bin.restore(&item, RestoreConflict::Overwrite).unwrap();
+    assert_eq!(restored, work.join("c (2).txt"));
+}
+
+#[test]
+fn test_restore_recreates_missing_parent() {
+    let (_dir, bin, work) = setup();
+    let sub = work.join("gone");
+    fs::create_dir(&sub).unwrap();
+    let file = sub.join("x.txt");
+    fs::write(&file, b"x").unwrap();
+
+    let item = bin.trash(&file).unwrap();
+    fs::remove_dir(&sub).unwrap();
+
+    assert_eq!(bin.restore(&item, RestoreConflict::Fail).unwrap(), file);
+}
+
+#[test]
+fn test_empty_and_purge_older_than() {
+    let (_dir, bin, work) = setup();
+    for name in ["old.txt", "new.txt"] {
+        let file = work.join(name);
+        fs::write(&file, name).unwrap();
+        bin.trash(&file).unwrap();
+    }
+
+    // Backdate one item
+    let old = bin.list().into_iter().find(|i| i.name == "old.txt").unwrap();
+    let info = TrashInfo::new(
+        old.info.original_path.clone(),
+        NaiveDate::from_ymd_opt(2000, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+    );
+    fs::write(old.info_path(), info.to_string_relative(None)).unwrap();
+
+    let purged = bin.purge_older_than(30);
+    assert!(purged.errors.is_empty());
+    assert_eq!(purged.erased.len(), 1);
+    assert_eq!(purged.erased[0].name, "old.txt");
+    assert!(!old.files_path().exists());
+
+    let emptied = bin.empty();
+    assert_eq!(emptied.erased.len(), 1);
+    assert!(bin.list().is_empty());
+}
+
+#[test]
+fn test_empty_reports_what_it_removed_despite_failures() {
+    let (_dir, bin, work) = setup();
+    let sub = work.join("folder");
+    fs::create_dir(&sub).unwrap();
+    bin.trash(&sub).unwrap();
+    let file = work.join("file.txt");
+    fs::write(&file, b"x").unwrap();
+    bin.trash(&file).unwrap();
+    // Saving directorysizes after erasing the folder fails
+    let blocker = bin.home().join(format!("directorysizes.{}", std::process::id()));
+    fs::create_dir_all(blocker.join("x")).unwrap();
+
+    let purge = bin.empty();
+    assert_eq!(purge.errors.len(), 1);
+    let mut names: Vec<_> = purge.erased.iter().map(|i| i.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, ["file.txt", "folder"]);
+    assert!(bin.list().is_empty());
+}
+
+#[test]
+fn test_trash_rejects_items_inside_trash() {
+    let (_dir, bin, work) = setup();
+    let file = work.join("f");
+    fs::write(&file, b"").unwrap();
+    let item = bin.trash(&file).unwrap();
+
+    assert!(bin.trash(&item.files_path()).is_err());
+}
+
+// ===== TrashFs Tests =====
+
+#[tokio::test]
+async fn test_trash_fs_lists_root_and_subdirs() {
+    let (_dir, bin, work) = setup();
+    let sub = work.join("docs");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("inner.txt"), b"inner").unwrap();
+    fs::write(work.join("top.txt"), b"top").unwrap();
+    bin.trash(&sub).unwrap();
+    bin.trash(&work.join("top.txt")).unwrap();
+
+    let fs = TrashFs::new(bin.clone(), NodeRegistry::new());
+    assert_eq!(fs.scheme(), "trash");
+
+    let root = fs.list(Path::new("/")).await.unwrap();
+    let mut names: Vec<_> = root.iter().map(|n| n.name.clone()).collect();
+    names.sort();
+    assert_eq!(names, vec!["docs", "top.txt"]);
+
+    let docs = root.iter().find(|n| n.name == "docs").unwrap();
+    let inner = fs.list(&docs.path).await.unwrap();
+    assert_eq!(inner.len(), 1);
+    assert_eq!(fs.read(&inner[0].path).await.unwrap(), b"inner");
+
+    assert!(fs.list(&work).await.is_err());
+    assert!(!fs.exists(&work.join("top.txt")).await.unwrap());
+}
+
+// ===== Trasher Actor Tests =====
+
+#[tokio::test]
+async fn test_trasher_trash_list_restore() {
+    let (_dir, bin, work) = setup();
+    let file = work.join("actor.txt");
+    fs::write(&file, b"a").unwrap();
+
+    let reg = NodeRegistry::new();
+    let id = reg.clone().register(file.clone());
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    tokio::spawn(Trasher::new(cmd_rx, evt_tx, bin, reg.clone()).run());
+    let session = SessionId::new();
+
+    cmd_tx.send(TrashCommand::Trash { nodes: vec![id], session }).unwrap();
+    let trashed = match timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap() {
+        Event::OperationComplete {
+            operation: OperationKind::Delete,
+            success: true,
+            affected,
+            ..
+        } => affected,
+        other => panic!("unexpected event: {other:?}"),
+    };
+    assert!(!file.exists());
+    assert_eq!(trashed.len(), 1);
+
+    cmd_tx.send(TrashCommand::List(session)).unwrap();
+    match timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap() {
+        Event::TrashListed { entries, .. } => {
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].original_path, file);
+            assert_eq!(entries[0].node, trashed[0]);
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+
+    cmd_tx
+        .send(TrashCommand::Restore {
+            items: trashed,
+            conflict: RestoreConflict::Fail,
+            session,
+        })
+        .unwrap();
+    match timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap() {
+        Event::OperationComplete {
+            operation: OperationKind::Restore,
+            success: true,
+            ..
+        } => {}
+        other => panic!("unexpected event: {other:?}"),
+    }
+    assert!(file.exists());
+}
+
+#[tokio::test]
+async fn test_trasher_runs_commands_in_order() {
+    let (_dir, bin, work) = setup();
+    let file = work.join("queued.txt");
+    fs::write(&file, b"q").unwrap();
+
+    let reg = NodeRegistry::new();
+    let id = reg.clone().register(file.clone());
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    tokio::spawn(Trasher::new(cmd_rx, evt_tx, bin.clone(), reg.clone()).run());
+    let session = SessionId::new();
+
+    // Sent without waiting: each must see what the previous one did
+    cmd_tx.send(TrashCommand::Trash { nodes: vec![id], session }).unwrap();
+    cmd_tx.send(TrashCommand::List(session)).unwrap();
+    cmd_tx
+        .send(TrashCommand::Empty {
+            older_than_days: None,
+            session,
+        })
+        .unwrap();
+
+    let next = async || timeout(Duration::from_secs(2), evt_rx.recv_async()).await.unwrap().unwrap();
+    assert!(matches!(
+        next().await,
+        Event::OperationComplete {
+            operation: OperationKind::Delete,
+            success: true,
+            ..
+        }
+    ));
+    let listed = match next().await {
+        Event::TrashListed { entries, .. } => entries,
+        other => panic!("unexpected event: {other:?}"),
+    };
+    assert_eq!(listed.len(), 1);
+    match next().await {
+        Event::OperationComplete {
+            operation: OperationKind::EmptyTrash,
+            success: true,
+            affected,
+            ..
+        } => assert_eq!(affected, [listed[0].node]),
+        other => panic!("unexpected event: {other:?}"),
+    }
+    assert!(bin.list().is_empty());
+    assert!(reg.resolve(listed[0].node).is_none());
+}
@@ -159,6 +159,67 @@ mod path_tests {
         let _res = PathBuf::from("/");
         assert!(matches!(normalize(test_path), Ok(_res)));
     }
+
+    #[test]
+    fn test_matches_glob() {
+        assert!(matches_glob("*.tmp", "cache.tmp"));
+        assert!(matches_glob("*.tmp", ".tmp"));
+        assert!(!matches_glob("*.tmp", "cache.tmp.bak"));
+        assert!(matches_glob("report-??.pdf", "report-07.pdf"));
+        assert!(!matches_glob("report-??.pdf", "report-7.pdf"));
+        assert!(matches_glob("*a*b*", "xaxxbx"));
+        assert!(!matches_glob("*a*b", "xaxxbx"));
+        assert!(matches_glob("node_modules", "node_modules"));
+        assert!(matches_glob("*", ""));
+        assert!(!matches_glob("?", ""));
+    }
+
+    #[test]
+    fn test_unique_name_skips_dangling_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo.txt");
+        assert_eq!(unique_name(&path), path);
+        std::fs::write(&path, b"x").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("gone"), dir.path().join("foo (2).txt")).unwrap();
+        assert_eq!(unique_name(&path), dir.path().join("foo (3).txt"));
+    }
+}
+
+mod rename_tests {
+    use crate::utils::rename::rename_noreplace;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_rename_noreplace_keeps_existing_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("from"), dir.path().join("to"));
+        std::fs::write(&from, b"new").unwrap();
+        std::fs::write(&to, b"old").unwrap();
+
+        let error = rename_noreplace(&from, &to).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&from).unwrap(), b"new");
+        assert_eq!(std::fs::read(&to).unwrap(), b"old");
+
+        std::fs::remove_file(&to).unwrap();
+        rename_noreplace(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(std::fs::read(&to).unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_rename_noreplace_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("from"), dir.path().join("to"));
+        std::fs::create_dir(&from).unwrap();
+        std::fs::create_dir(&to).unwrap();
+
+        // An empty directory would be replaced by a plain rename
+        assert!(rename_noreplace(&from, &to).is_err());
+        std::fs::remove_dir(&to).unwrap();
+        rename_noreplace(&from, &to).unwrap();
+        assert!(to.is_dir() && !from.exists());
+    }
 }
 
 mod size_tests {
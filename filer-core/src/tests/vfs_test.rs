@@ -4,7 +4,8 @@ use crate::errors::CoreError;
 use crate::model::node::FileNode;
 use crate::model::registry::NodeRegistry;
 use crate::vfs::local::LocalFs;
-use crate::vfs::provider::{Capabilities, FsProvider};
+use crate::vfs::provider::{Capabilities, FsProvider, Precondition};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -260,6 +261,178 @@ async fn test_local_fs_metadata_not_found() {
     }
 }
 
+// ===== LocalFs Safe-Save Tests =====
+
+fn part_files(dir: &Path) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".filer-part"))
+        .collect()
+}
+
+fn assert_precondition_failed(result: Result<(), CoreError>, expected: &str) {
+    match result {
+        Err(CoreError::PreconditionFailed { reason, .. }) => {
+            assert!(reason.contains(expected), "unexpected reason: {reason}")
+        }
+        other => panic!("expected PreconditionFailed, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_local_fs_write_creates_and_replaces() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("notes.txt");
+    let fs = LocalFs::new(NodeRegistry::new());
+
+    fs.write(&path, b"first", &Precondition::default()).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"first");
+    let inode = std::fs::metadata(&path).unwrap().ino();
+
+    fs.write(&path, b"second", &Precondition::default()).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"second");
+    // Replaced by rename, not rewritten in place
+    assert_ne!(std::fs::metadata(&path).unwrap().ino(), inode);
+    assert!(part_files(dir.path()).is_empty());
+}
+
+#[tokio::test]
+async fn test_local_fs_write_preserves_permissions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("secret.txt");
+    std::fs::write(&path, b"old").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+    let fs = LocalFs::new(NodeRegistry::new());
+
+    fs.write(&path, b"new", &Precondition::default()).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"new");
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+}
+
+#[tokio::test]
+async fn test_local_fs_write_through_symlink_keeps_link() {
+    let dir = tempfile::tempdir().unwrap();
+    let real = dir.path().join("real.txt");
+    let link = dir.path().join("link.txt");
+    std::fs::write(&real, b"old").unwrap();
+    std::os::unix::fs::symlink(&real, &link).unwrap();
+    let fs = LocalFs::new(NodeRegistry::new());
+
+    fs.write(&link, b"new", &Precondition::default()).await.unwrap();
+    assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    assert_eq!(std::fs::read(&real).unwrap(), b"new");
+}
+
+#[tokio::test]
+async fn test_local_fs_write_if_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("doc.txt");
+    std::fs::write(&path, b"v1").unwrap();
+    let fs = LocalFs::new(NodeRegistry::new());
+    let read = fs.metadata(&path).await.unwrap();
+
+    fs.write(&path, b"v2", &Precondition::unchanged(&read)).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"v2");
+
+    // Someone else saved since `read` was taken
+    std::fs::write(&path, b"theirs").unwrap();
+    let result = fs.write(&path, b"mine", &Precondition::unchanged(&read)).await;
+    assert_precondition_failed(result, "size");
+    assert_eq!(std::fs::read(&path).unwrap(), b"theirs");
+    assert!(part_files(dir.path()).is_empty());
+}
+
+#[tokio::test]
+async fn test_local_fs_write_precondition_checked_on_finish() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("doc.txt");
+    std::fs::write(&path, b"v1").unwrap();
+    let fs = LocalFs::new(NodeRegistry::new());
+    let read = fs.metadata(&path).await.unwrap();
+
+    let mut stream = fs.create(&path, &Precondition::unchanged(&read)).await.unwrap();
+    stream.write(b"mine").await.unwrap();
+    std::fs::write(&path, b"theirs!").unwrap();
+    assert_precondition_failed(stream.finish().await, "size");
+    assert_eq!(std::fs::read(&path).unwrap(), b"theirs!");
+    assert!(part_files(dir.path()).is_empty());
+}
+
+#[tokio::test]
+async fn test_local_fs_write_absent_and_missing_targets() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("doc.txt");
+    let fs = LocalFs::new(NodeRegistry::new());
+
+    fs.write(&path, b"created", &Precondition::absent()).await.unwrap();
+    let result = fs.write(&path, b"again", &Precondition::absent()).await;
+    assert_precondition_failed(result, "already exists");
+
+    let read = fs.metadata(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let result = fs.write(&path, b"back", &Precondition::unchanged(&read)).await;
+    assert_precondition_failed(result, "no longer exists");
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_local_fs_write_etag_precondition_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("doc.txt");
+    std::fs::write(&path, b"v1").unwrap();
+    let fs = LocalFs::new(NodeRegistry::new());
+    let precondition = Precondition {
+        etag: Some("\"abc\"".to_string()),
+        ..Default::default()
+    };
+
+    let result = fs.write(&path, b"v2", &precondition).await;
+    assert_precondition_failed(result, "no ETag");
+}
+
+#[tokio::test]
+async fn test_local_fs_write_abort_leaves_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("doc.txt");
+    std::fs::write(&path, b"v1").unwrap();
+    let fs = LocalFs::new(NodeRegistry::new());
+
+    let mut stream = fs.create(&path, &Precondition::default()).await.unwrap();
+    stream.write(b"half").await.unwrap();
+    assert_eq!(part_files(dir.path()).len(), 1);
+    stream.abort().await;
+
+    assert_eq!(std::fs::read(&path).unwrap(), b"v1");
+    assert!(part_files(dir.path()).is_empty());
+}
+
+#[tokio::test]
+async fn test_local_fs_copy_within_publishes_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let from = dir.path().join("from.sh");
+    let to = dir.path().join("to.sh");
+    std::fs::write(&from, b"echo").unwrap();
+    std::fs::set_permissions(&from, std::fs::Permissions::from_mode(0o750)).unwrap();
+    let fs = LocalFs::new(NodeRegistry::new());
+
+    assert!(fs.copy_within(&from, &to, &Precondition::absent()).await.unwrap());
+    assert_eq!(std::fs::read(&to).unwrap(), b"echo");
+    assert_eq!(std::fs::metadata(&to).unwrap().permissions().mode() & 0o777, 0o750);
+
+    std::fs::write(&from, b"echo again").unwrap();
+    let result = fs.copy_within(&from, &to, &Precondition::absent()).await.map(drop);
+    assert_precondition_failed(result, "already exists");
+    assert_eq!(std::fs::read(&to).unwrap(), b"echo");
+
+    let inode = std::fs::metadata(&to).unwrap().ino();
+    fs.copy_within(&from, &to, &Precondition::default()).await.unwrap();
+    assert_eq!(std::fs::read(&to).unwrap(), b"echo again");
+    // Replaced by rename, not rewritten in place
+    assert_ne!(std::fs::metadata(&to).unwrap().ino(), inode);
+    assert!(part_files(dir.path()).is_empty());
+}
+
 // ===== MockFs Implementation =====
 
 pub struct MockFs {
@@ -296,6 +469,7 @@ impl FsProvider for MockFs {
             write: true,
             watch: false,
             search: true,
+            xattrs: false,
         }
     }
 
@@ -438,3 +612,12 @@ async fn test_mock_fs_trait_usage() {
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), b"content");
 }
+
+#[tokio::test]
+async fn test_mock_fs_write_unsupported_by_default() {
+    let fs = MockFs::new();
+    let result = fs
+        .write(Path::new("/a.txt"), b"data", &Precondition::default())
+        .await;
+    assert!(matches!(result, Err(CoreError::PermissionDenied(_))));
+}
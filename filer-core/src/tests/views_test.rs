@@ -0,0 +1,636 @@
+//! Tests for several views (panes or tabs) in one session and the
+//! clipboard they share
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::navigator::{Clipboard, NavCommand, SessionViews};
+use crate::actors::operations::OpCommand;
+use crate::actors::scanner::{ScanCommand, Scanner};
+use crate::api::events::Event;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::{SessionId, ViewId};
+use crate::pipeline::PipelineConfig;
+use crate::services::sessions::SessionStore;
+use crate::services::transfer::{TransferMode, TransferOptions};
+use crate::tests::common::{NavHarness, mkdirs, spawn_navigator, tempdir};
+use crate::vfs::local::LocalFs;
+
+fn right() -> ViewId {
+    ViewId::new("right")
+}
+
+// ===== SessionViews Tests =====
+
+#[test]
+fn test_open_view_starts_where_the_focused_one_is() {
+    let registry = NodeRegistry::new();
+    let dir = registry.clone().register(PathBuf::from("/tmp"));
+    let mut views = SessionViews::new(registry);
+    let main = views.get_mut(&ViewId::main()).unwrap();
+    main.navigate(dir);
+    main.pipeline_config = PipelineConfig::with_default_sort();
+
+    let opened = views.open(right()).unwrap();
+    assert_eq!(opened.current, Some(dir));
+    assert_eq!(opened.pipeline_config, PipelineConfig::with_default_sort());
+    // Its history starts there
+    assert!(!opened.can_back());
+    assert_eq!(views.ids(), vec![ViewId::main(), right()]);
+    assert_eq!(views.focused(), &right());
+    assert!(views.open(right()).is_err());
+
+    // New views go right after the focused one
+    views.focus(&ViewId::main()).unwrap();
+    views.open(ViewId::new("tab")).unwrap();
+    assert_eq!(
+        views.ids(),
+        vec![ViewId::main(), ViewId::new("tab"), right()]
+    );
+}
+
+#[test]
+fn test_other_view_is_the_one_focused_before() {
+    let mut views = SessionViews::new(NodeRegistry::new());
+    assert_eq!(views.other(), None);
+
+    views.open(right()).unwrap();
+    assert_eq!(views.other(), Some(&ViewId::main()));
+    views.open(ViewId::new("third")).unwrap();
+    assert_eq!(views.other(), Some(&right()));
+    views.focus(&ViewId::main()).unwrap();
+    assert_eq!(views.other(), Some(&ViewId::new("third")));
+    assert!(views.focus(&ViewId::new("missing")).is_err());
+
+    // Closing the focused view hands focus to the other one
+    views.close(&ViewId::main()).unwrap();
+    assert_eq!(views.focused(), &ViewId::new("third"));
+    // With no view focused before, the next in tab order
+    assert_eq!(views.other(), Some(&right()));
+
+    views.close(&right()).unwrap();
+    assert_eq!(views.other(), None);
+    assert!(views.close(&ViewId::new("third")).is_err());
+    assert_eq!(views.ids(), vec![ViewId::new("third")]);
+}
+
+#[test]
+fn test_views_round_trip_through_saved_session() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let registry = NodeRegistry::new();
+    let mut views = SessionViews::new(registry.clone());
+    views.name = Some("window".into());
+    views
+        .get_mut(&ViewId::main())
+        .unwrap()
+        .navigate(registry.clone().register(dirs[0].clone()));
+    views.open(right()).unwrap();
+    views
+        .get_mut(&right())
+        .unwrap()
+        .navigate(registry.clone().register(dirs[1].clone()));
+
+    let saved = views.to_saved();
+    assert_eq!(saved.views.len(), 2);
+    assert_eq!(saved.focused, right());
+    assert_eq!(saved.other, Some(ViewId::main()));
+    assert_eq!(saved.view(&right()).unwrap().history, dirs);
+
+    let registry = NodeRegistry::new();
+    let mut restored = SessionViews::new(registry.clone());
+    restored.restore(&saved);
+    assert_eq!(restored.name.as_deref(), Some("window"));
+    assert_eq!(restored.ids(), vec![ViewId::main(), right()]);
+    assert_eq!(restored.focused(), &right());
+    assert_eq!(restored.other(), Some(&ViewId::main()));
+    let current = restored.get(&right()).unwrap().current.unwrap();
+    assert_eq!(registry.resolve(current), Some(dirs[1].clone()));
+}
+
+// ===== Navigator Actor Tests =====
+
+impl NavHarness {
+    async fn in_view(&self, view: ViewId, command: NavCommand) {
+        self.send(NavCommand::View {
+            view,
+            command: Box::new(command),
+        })
+        .await;
+    }
+
+    /// Open `session` with a main view showing `main` and a right view
+    /// showing `right`, focus left on the right view
+    async fn two_panes(&self, session: SessionId, main: &Path, right_dir: &Path) {
+        self.send(NavCommand::NewSession(session)).await;
+        self.send(NavCommand::NavigateToPath {
+            session,
+            path: main.to_path_buf(),
+        })
+        .await;
+        self.send(NavCommand::OpenView {
+            session,
+            view: right(),
+        })
+        .await;
+        self.in_view(
+            right(),
+            NavCommand::NavigateToPath {
+                session,
+                path: right_dir.to_path_buf(),
+            },
+        )
+        .await;
+        self.events.drain();
+        self.scans.drain();
+    }
+}
+
+#[tokio::test]
+async fn test_commands_go_to_the_named_view() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let h = spawn_navigator(SessionStore::in_memory(), false);
+    let session = SessionId(1);
+
+    h.send(NavCommand::NewSession(session)).await;
+    h.send(NavCommand::NavigateToPath {
+        session,
+        path: dirs[0].clone(),
+    })
+    .await;
+    h.send(NavCommand::OpenView {
+        session,
+        view: right(),
+    })
+    .await;
+    h.scans.drain();
+    h.events.drain();
+
+    h.in_view(
+        ViewId::main(),
+        NavCommand::NavigateToPath {
+            session,
+            path: dirs[1].clone(),
+        },
+    )
+    .await;
+    match h.scans.recv_async().await.unwrap() {
+        ScanCommand::ScanNode { node, view, .. } => {
+            assert_eq!(view, ViewId::main());
+            assert_eq!(h.registry.resolve(node), Some(dirs[1].clone()));
+        }
+        other => panic!("expected a scan, got {other:?}"),
+    }
+
+    // Without a view, the focused one
+    h.send(NavCommand::GetState(session)).await;
+    match h.next_event().await {
+        Event::CurrentNavigateState { view, state, .. } => {
+            assert_eq!(view, right());
+            assert_eq!(
+                h.registry.resolve(state.current.unwrap()),
+                Some(dirs[0].clone())
+            );
+        }
+        other => panic!("expected the right view's state, got {other:?}"),
+    }
+
+    h.in_view(ViewId::new("missing"), NavCommand::GetState(session))
+        .await;
+    assert!(h.next_error().await.contains("view:missing"));
+}
+
+#[tokio::test]
+async fn test_view_changes_are_reported() {
+    let h = spawn_navigator(SessionStore::in_memory(), false);
+    let session = SessionId(1);
+    h.send(NavCommand::NewSession(session)).await;
+
+    h.send(NavCommand::OpenView {
+        session,
+        view: right(),
+    })
+    .await;
+    let changed = loop {
+        if let Event::ViewsChanged {
+            views,
+            focused,
+            other,
+            ..
+        } = h.next_event().await
+        {
+            break (views, focused, other);
+        }
+    };
+    assert_eq!(
+        changed,
+        (vec![ViewId::main(), right()], right(), Some(ViewId::main()))
+    );
+
+    h.send(NavCommand::CloseView {
+        session,
+        view: ViewId::main(),
+    })
+    .await;
+    match h.next_event().await {
+        Event::ViewsChanged {
+            views,
+            focused,
+            other,
+            ..
+        } => {
+            assert_eq!(views, vec![right()]);
+            assert_eq!(focused, right());
+            assert_eq!(other, None);
+        }
+        other => panic!("expected the views, got {other:?}"),
+    }
+
+    h.send(NavCommand::CloseView {
+        session,
+        view: right(),
+    })
+    .await;
+    assert!(h.next_error().await.contains("last view"));
+}
+
+#[tokio::test]
+async fn test_named_sessions_save_every_view() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let store = SessionStore::in_memory();
+    let h = spawn_navigator(store.clone(), false);
+
+    h.send(NavCommand::OpenSession {
+        session: SessionId(1),
+        name: "window".into(),
+    })
+    .await;
+    h.send(NavCommand::NavigateToPath {
+        session: SessionId(1),
+        path: dirs[0].clone(),
+    })
+    .await;
+    h.send(NavCommand::OpenView {
+        session: SessionId(1),
+        view: right(),
+    })
+    .await;
+    h.send(NavCommand::NavigateToPath {
+        session: SessionId(1),
+        path: dirs[1].clone(),
+    })
+    .await;
+    let saved = store.get("window").unwrap();
+    assert_eq!(saved.focused, right());
+    assert_eq!(saved.view(&right()).unwrap().current(), Some(&dirs[1]));
+    assert_eq!(
+        saved.view(&ViewId::main()).unwrap().current(),
+        Some(&dirs[0])
+    );
+
+    // Both views come back and are scanned
+    h.send(NavCommand::CloseSession(SessionId(1))).await;
+    h.scans.drain();
+    h.events.drain();
+    h.send(NavCommand::ReopenClosed(SessionId(2))).await;
+    let mut scanned: Vec<_> = h
+        .scans
+        .drain()
+        .map(|scan| match scan {
+            ScanCommand::ScanNode { view, .. } => view,
+            other => panic!("expected a scan, got {other:?}"),
+        })
+        .collect();
+    scanned.sort();
+    assert_eq!(scanned, vec![ViewId::main(), right()]);
+}
+
+#[tokio::test]
+async fn test_cut_and_paste_moves_once() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let h = spawn_navigator(SessionStore::in_memory(), true);
+    let session = SessionId(1);
+    h.two_panes(session, &dirs[0], &dirs[1]).await;
+
+    let file = h.node(&dirs[0].join("file.txt"));
+    h.in_view(
+        ViewId::main(),
+        NavCommand::SetSelected {
+            session,
+            nodes: vec![file],
+        },
+    )
+    .await;
+    assert!(matches!(
+        h.next_event().await,
+        Event::SelectionChanged { .. }
+    ));
+    h.in_view(
+        ViewId::main(),
+        NavCommand::Clip {
+            session,
+            mode: TransferMode::Move,
+            nodes: vec![],
+        },
+    )
+    .await;
+    match h.next_event().await {
+        Event::ClipboardChanged { clipboard, .. } => assert_eq!(
+            clipboard,
+            Some(Clipboard {
+                mode: TransferMode::Move,
+                nodes: vec![file],
+            })
+        ),
+        other => panic!("expected the clipboard, got {other:?}"),
+    }
+
+    // Pasted into the focused view's directory
+    h.send(NavCommand::Paste {
+        session,
+        destination: None,
+        options: TransferOptions::default(),
+    })
+    .await;
+    match h.next_operation().await {
+        OpCommand::Move {
+            sources,
+            destination,
+            ..
+        } => {
+            assert_eq!(sources, vec![file]);
+            assert_eq!(h.registry.resolve(destination), Some(dirs[1].clone()));
+        }
+        other => panic!("expected a move, got {other:?}"),
+    }
+    match h.next_event().await {
+        Event::ClipboardChanged { clipboard, .. } => assert!(clipboard.is_none()),
+        other => panic!("expected the clipboard to empty, got {other:?}"),
+    }
+
+    h.send(NavCommand::Paste {
+        session,
+        destination: None,
+        options: TransferOptions::default(),
+    })
+    .await;
+    assert!(h.next_error().await.contains("clipboard is empty"));
+}
+
+#[tokio::test]
+async fn test_copied_items_paste_again_and_stay_per_session() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let h = spawn_navigator(SessionStore::in_memory(), true);
+    h.two_panes(SessionId(1), &dirs[0], &dirs[1]).await;
+    h.send(NavCommand::NewSession(SessionId(2))).await;
+
+    let file = h.node(&dirs[0].join("file.txt"));
+    h.send(NavCommand::Clip {
+        session: SessionId(1),
+        mode: TransferMode::Copy,
+        nodes: vec![file],
+    })
+    .await;
+    for _ in 0..2 {
+        h.send(NavCommand::Paste {
+            session: SessionId(1),
+            destination: Some(h.node(&dirs[0])),
+            options: TransferOptions::default(),
+        })
+        .await;
+        assert!(matches!(h.next_operation().await, OpCommand::Copy { .. }));
+    }
+
+    // Other clients have their own clipboard
+    h.events.drain();
+    h.send(NavCommand::Paste {
+        session: SessionId(2),
+        destination: Some(h.node(&dirs[0])),
+        options: TransferOptions::default(),
+    })
+    .await;
+    assert!(h.next_error().await.contains("clipboard is empty"));
+
+    h.send(NavCommand::ClearClipboard(SessionId(1))).await;
+    match h.next_event().await {
+        Event::ClipboardChanged { session, clipboard } => {
+            assert_eq!(session, SessionId(1));
+            assert!(clipboard.is_none());
+        }
+        other => panic!("expected the clipboard to empty, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_transfer_goes_to_the_other_view() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let h = spawn_navigator(SessionStore::in_memory(), true);
+    let session = SessionId(1);
+    h.two_panes(session, &dirs[0], &dirs[1]).await;
+
+    let files = [h.node(&dirs[1].join("y")), h.node(&dirs[1].join("x"))];
+    h.send(NavCommand::SetSelected {
+        session,
+        nodes: files.to_vec(),
+    })
+    .await;
+    h.send(NavCommand::Transfer {
+        session,
+        mode: TransferMode::Copy,
+        nodes: vec![],
+        destination: None,
+        options: TransferOptions::default(),
+    })
+    .await;
+    match h.next_operation().await {
+        OpCommand::Copy {
+            sources,
+            destination,
+            ..
+        } => {
+            assert_eq!(sources, files.to_vec());
+            assert_eq!(h.registry.resolve(destination), Some(dirs[0].clone()));
+        }
+        other => panic!("expected a copy, got {other:?}"),
+    }
+
+    // Nothing selected in the main view
+    h.in_view(
+        ViewId::main(),
+        NavCommand::Transfer {
+            session,
+            mode: TransferMode::Move,
+            nodes: vec![],
+            destination: None,
+            options: TransferOptions::default(),
+        },
+    )
+    .await;
+    assert!(h.next_error().await.contains("nothing is selected"));
+}
+
+#[tokio::test]
+async fn test_focused_view_takes_selection_and_clipboard_commands() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let h = spawn_navigator(SessionStore::in_memory(), true);
+    let session = SessionId(1);
+    h.two_panes(session, &dirs[0], &dirs[1]).await;
+
+    h.send(NavCommand::FocusView {
+        session,
+        view: ViewId::main(),
+    })
+    .await;
+    match h.next_event().await {
+        Event::ViewsChanged { focused, other, .. } => {
+            assert_eq!(focused, ViewId::main());
+            assert_eq!(other, Some(right()));
+        }
+        other => panic!("expected the views, got {other:?}"),
+    }
+
+    // Without a view, commands now act on the main view
+    let file = h.node(&dirs[0].join("file.txt"));
+    h.send(NavCommand::SetSelected {
+        session,
+        nodes: vec![file],
+    })
+    .await;
+    match h.next_event().await {
+        Event::SelectionChanged { view, selected, .. } => {
+            assert_eq!(view, ViewId::main());
+            assert_eq!(selected, vec![file]);
+        }
+        other => panic!("expected the selection, got {other:?}"),
+    }
+    h.send(NavCommand::Clip {
+        session,
+        mode: TransferMode::Copy,
+        nodes: vec![],
+    })
+    .await;
+    match h.next_event().await {
+        Event::ClipboardChanged { clipboard, .. } => {
+            assert_eq!(clipboard.unwrap().nodes, vec![file])
+        }
+        other => panic!("expected the clipboard, got {other:?}"),
+    }
+    h.send(NavCommand::Paste {
+        session,
+        destination: None,
+        options: TransferOptions::default(),
+    })
+    .await;
+    match h.next_operation().await {
+        OpCommand::Copy { destination, .. } => {
+            assert_eq!(h.registry.resolve(destination), Some(dirs[0].clone()))
+        }
+        other => panic!("expected a copy, got {other:?}"),
+    }
+
+    h.send(NavCommand::FocusView {
+        session,
+        view: ViewId::new("missing"),
+    })
+    .await;
+    assert!(h.next_error().await.contains("view:missing"));
+}
+
+#[tokio::test]
+async fn test_transfer_without_operations_is_refused() {
+    let (_dir, root) = tempdir();
+    let dirs = mkdirs(&root, &["a", "b"]);
+    let h = spawn_navigator(SessionStore::in_memory(), false);
+    let session = SessionId(1);
+    h.two_panes(session, &dirs[0], &dirs[1]).await;
+
+    h.send(NavCommand::Transfer {
+        session,
+        mode: TransferMode::Copy,
+        nodes: vec![h.node(&dirs[1].join("x"))],
+        destination: None,
+        options: TransferOptions::default(),
+    })
+    .await;
+    assert!(h.next_error().await.contains("not connected"));
+}
+
+// ===== Scanner Tests =====
+
+#[tokio::test]
+async fn test_scanner_keeps_a_listing_per_view() {
+    let (_dir, root) = tempdir();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+
+    let reg = NodeRegistry::new();
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (evt_tx, evt_rx) = flume::unbounded();
+    let scanner = Scanner::new(
+        cmd_rx,
+        evt_tx,
+        Arc::new(LocalFs::new(reg.clone())),
+        reg.clone(),
+    );
+    tokio::spawn(scanner.run());
+
+    let session = SessionId::new();
+    let node = reg.clone().register(root.clone());
+    for view in [ViewId::main(), right()] {
+        cmd_tx
+            .send(ScanCommand::ScanNode {
+                node,
+                session,
+                view: view.clone(),
+                pipeline: PipelineConfig::default(),
+            })
+            .unwrap();
+        match timeout(Duration::from_secs(2), evt_rx.recv_async())
+            .await
+            .unwrap()
+            .unwrap()
+        {
+            Event::DirectoryLoaded { view: loaded, .. } => assert_eq!(loaded, view),
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+
+    // Each view gets the change against what it has shown
+    fs::write(root.join("b.txt"), b"b").unwrap();
+    for view in [right(), ViewId::main()] {
+        cmd_tx
+            .send(ScanCommand::Update {
+                node,
+                session,
+                view: view.clone(),
+                pipeline: PipelineConfig::default(),
+            })
+            .unwrap();
+        match timeout(Duration::from_secs(2), evt_rx.recv_async())
+            .await
+            .unwrap()
+            .unwrap()
+        {
+            Event::DirectoryDelta {
+                added,
+                view: changed,
+                ..
+            } => {
+                assert_eq!(changed, view);
+                assert_eq!(added.len(), 1);
+                assert_eq!(added[0].node.name, "b.txt");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+}
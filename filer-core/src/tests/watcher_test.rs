@@ -0,0 +1,273 @@
+//! Tests for the Watcher actor and change debouncing
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use flume::Receiver;
+use tokio::time::timeout;
+
+use crate::actors::Actor;
+use crate::actors::navigator::NavCommand;
+use crate::actors::watcher::{WatchCommand, Watcher, WatcherConfig};
+use crate::api::events::Event;
+use crate::model::fs_change::FsChangeKind;
+use crate::model::registry::NodeRegistry;
+use crate::model::session::SessionId;
+use crate::services::watch::Debouncer;
+
+// ===== Debouncer Tests =====
+
+fn debouncer() -> Debouncer {
+    Debouncer::new(Duration::from_millis(100), Duration::from_secs(1))
+}
+
+#[test]
+fn test_debouncer_coalesces_modifications() {
+    let mut d = debouncer();
+    let start = Instant::now();
+    for i in 0..1000 {
+        d.push(PathBuf::from("/a"), FsChangeKind::Modified, start + Duration::from_micros(i));
+    }
+    assert_eq!(d.len(), 1);
+    assert!(d.drain_due(start + Duration::from_millis(50)).is_empty());
+
+    let out = d.drain_due(start + Duration::from_millis(200));
+    assert_eq!(out, vec![(PathBuf::from("/a"), FsChangeKind::Modified)]);
+    assert!(d.is_empty());
+}
+
+#[test]
+fn test_debouncer_max_delay_releases_busy_path() {
+    let mut d = debouncer();
+    let start = Instant::now();
+    let mut now = start;
+    while now < start + Duration::from_millis(1100) {
+        d.push(PathBuf::from("/busy"), FsChangeKind::Modified, now);
+        now += Duration::from_millis(10);
+    }
+    assert_eq!(d.drain_due(now).len(), 1);
+}
+
+#[test]
+fn test_debouncer_merge_rules() {
+    let mut d = debouncer();
+    let t = Instant::now();
+
+    d.push(PathBuf::from("/new"), FsChangeKind::Created, t);
+    d.push(PathBuf::from("/new"), FsChangeKind::Modified, t);
+
+    d.push(PathBuf::from("/tmp"), FsChangeKind::Created, t);
+    d.push(PathBuf::from("/tmp"), FsChangeKind::Deleted, t);
+
+    d.push(PathBuf::from("/replaced"), FsChangeKind::Deleted, t);
+    d.push(PathBuf::from("/replaced"), FsChangeKind::Created, t);
+
+    let mut out = d.flush();
+    out.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        out,
+        vec![
+            (PathBuf::from("/new"), FsChangeKind::Created),
+            (PathBuf::from("/replaced"), FsChangeKind::Modified),
+        ]
+    );
+}
+
+#[test]
+fn test_debouncer_rename_merges() {
+    let mut d = debouncer();
+    let t = Instant::now();
+
+    // Created then renamed -> created under the new name
+    d.push(PathBuf::from("/a.tmp"), FsChangeKind::Created, t);
+    d.push(PathBuf::from("/a"), FsChangeKind::Renamed { from: PathBuf::from("/a.tmp") }, t);
+    assert_eq!(d.flush(), vec![(PathBuf::from("/a"), FsChangeKind::Created)]);
+
+    // Renamed then deleted -> the old name is gone
+    d.push(PathBuf::from("/y"), FsChangeKind::Renamed { from: PathBuf::from("/x") }, t);
+    d.push(PathBuf::from("/y"), FsChangeKind::Deleted, t);
+    assert_eq!(d.flush(), vec![(PathBuf::from("/x"), FsChangeKind::Deleted)]);
+}
+
+#[test]
+fn test_debouncer_rescan_wins() {
+    let mut d = debouncer();
+    let t = Instant::now();
+    d.push(PathBuf::from("/r"), FsChangeKind::Rescan, t);
+    d.push(PathBuf::from("/r"), FsChangeKind::Deleted, t);
+    assert_eq!(d.flush(), vec![(PathBuf::from("/r"), FsChangeKind::Rescan)]);
+}
+
+// ===== Watcher Actor Tests =====
+
+struct Harness {
+    _dir: tempfile::TempDir,
+    root: PathBuf,
+    reg: NodeRegistry,
+    cmd: flume::Sender<WatchCommand>,
+    events: Receiver<Event>,
+    nav: Receiver<NavCommand>,
+}
+
+fn spawn_watcher() -> Harness {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let reg = NodeRegistry::new();
+    let (cmd, cmd_rx) = flume::unbounded();
+    let (evt_tx, events) = flume::unbounded();
+    let (nav_tx, nav) = flume::unbounded();
+    let config = WatcherConfig {
+        debounce: Duration::from_millis(50),
+        max_delay: Duration::from_millis(500),
+        move_timeout: Duration::from_millis(30),
+    };
+    let watcher = Watcher::new(cmd_rx, evt_tx, reg.clone())
+        .with_navigator(nav_tx)
+        .with_config(config);
+    tokio::spawn(watcher.run());
+    Harness {
+        _dir: dir,
+        root,
+        reg,
+        cmd,
+        events,
+        nav,
+    }
+}
+
+/// Collect FsChanged events until the stream is quiet
+async fn collect(h: &Harness) -> Vec<(PathBuf, FsChangeKind, SessionId)> {
+    let mut out = Vec::new();
+    while let Ok(Ok(ev)) = timeout(Duration::from_millis(400), h.events.recv_async()).await {
+        if let Event::FsChanged { node, kind, session } = ev {
+            out.push((h.reg.resolve(node).unwrap(), kind, session));
+        }
+    }
+    out
+}
+
+async fn watch(h: &Harness, path: &Path, session: SessionId) {
+    h.cmd.send(WatchCommand::Watch(path.to_path_buf(), session)).unwrap();
+    // Let the actor install the watches
+    tokio::time::sleep(Duration::from_millis(50)).await;
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_watcher_reports_create_and_delete() {
+    let h = spawn_watcher();
+    let session = SessionId::new();
+    watch(&h, &h.root, session).await;
+
+    let file = h.root.join("a.txt");
+    fs::write(&file, b"x").unwrap();
+    let changes = collect(&h).await;
+    assert_eq!(changes, vec![(file.clone(), FsChangeKind::Created, session)]);
+
+    fs::remove_file(&file).unwrap();
+    let changes = collect(&h).await;
+    assert_eq!(changes, vec![(file, FsChangeKind::Deleted, session)]);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_watcher_coalesces_write_burst() {
+    let h = spawn_watcher();
+    let file = h.root.join("build.log");
+    fs::write(&file, b"").unwrap();
+    let session = SessionId::new();
+    watch(&h, &h.root, session).await;
+
+    let mut f = fs::OpenOptions::new().append(true).open(&file).unwrap();
+    for _ in 0..1000 {
+        f.write_all(b"line\n").unwrap();
+    }
+    drop(f);
+
+    let changes = collect(&h).await;
+    assert_eq!(changes, vec![(file, FsChangeKind::Modified, session)]);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_watcher_pairs_renames() {
+    let h = spawn_watcher();
+    let from = h.root.join("old.txt");
+    let to = h.root.join("new.txt");
+    fs::write(&from, b"x").unwrap();
+    let session = SessionId::new();
+    watch(&h, &h.root, session).await;
+
+    fs::rename(&from, &to).unwrap();
+    let changes = collect(&h).await;
+    assert_eq!(changes, vec![(to, FsChangeKind::Renamed { from }, session)]);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_watcher_is_recursive() {
+    let h = spawn_watcher();
+    let existing = h.root.join("existing");
+    fs::create_dir(&existing).unwrap();
+    let session = SessionId::new();
+    watch(&h, &h.root, session).await;
+
+    let nested = existing.join("deep.txt");
+    fs::write(&nested, b"x").unwrap();
+    assert_eq!(collect(&h).await, vec![(nested, FsChangeKind::Created, session)]);
+
+    // Directories created after the watch are watched too
+    let fresh = h.root.join("fresh");
+    fs::create_dir(&fresh).unwrap();
+    assert_eq!(collect(&h).await, vec![(fresh.clone(), FsChangeKind::Created, session)]);
+    let inner = fresh.join("inner.txt");
+    fs::write(&inner, b"x").unwrap();
+    assert_eq!(collect(&h).await, vec![(inner, FsChangeKind::Created, session)]);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_watcher_refcounts_per_session() {
+    let h = spawn_watcher();
+    let s1 = SessionId::new();
+    let s2 = SessionId::new();
+    watch(&h, &h.root, s1).await;
+    watch(&h, &h.root, s2).await;
+
+    h.cmd.send(WatchCommand::Unwatch(h.root.clone(), s1)).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let file = h.root.join("shared.txt");
+    fs::write(&file, b"x").unwrap();
+    assert_eq!(collect(&h).await, vec![(file.clone(), FsChangeKind::Created, s2)]);
+
+    h.cmd.send(WatchCommand::UnwatchAll(s2)).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    fs::remove_file(&file).unwrap();
+    assert!(collect(&h).await.is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_watcher_invalidates_parent_directory() {
+    let h = spawn_watcher();
+    let session = SessionId::new();
+    watch(&h, &h.root, session).await;
+
+    fs::write(h.root.join("z"), b"x").unwrap();
+    collect(&h).await;
+
+    let parent = h.reg.get_id(&h.root).unwrap();
+    match h.nav.try_recv() {
+        Ok(NavCommand::Invalidate(node)) => assert_eq!(node, parent),
+        other => panic!("expected Invalidate, got {other:?}"),
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_watcher_reports_missing_path() {
+    let h = spawn_watcher();
+    let session = SessionId::new();
+    watch(&h, &h.root.join("does-not-exist"), session).await;
+
+    match timeout(Duration::from_secs(1), h.events.recv_async()).await {
+        Ok(Ok(Event::Error { session: s, .. })) => assert_eq!(s, session),
+        other => panic!("expected Error, got {other:?}"),
+    }
+}
@@ -0,0 +1,376 @@
+//! Tests for POSIX ACLs, extended attributes through `LocalFs` and the
+//! Operations actor commands that view and edit them
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::actors::operations::OpCommand;
+use crate::api::events::{Event, OperationKind};
+use crate::errors::CoreError;
+use crate::model::node::FileNode;
+use crate::model::registry::NodeRegistry;
+use crate::services::metadata::{
+    AttributeEdit, FileAttributes, XattrNamespace, is_visible, is_writable,
+};
+use crate::services::permissions::{Accounts, Acl, AclEntry, AclKind, AclTag};
+use crate::services::tags::TagStore;
+use crate::tests::common::{spawn_operations, tempdir};
+use crate::utils::xattr;
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::{FsProvider, Precondition};
+use crate::vfs::tags::TagsFs;
+
+fn touch(path: &Path) -> PathBuf {
+    fs::write(path, b"data").unwrap();
+    path.to_path_buf()
+}
+
+fn accounts() -> Accounts {
+    Accounts::parse(
+        "root:x:0:0::/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\n",
+        "root:x:0:\nstaff:x:50:\n",
+    )
+}
+
+fn entry(tag: AclTag, perms: u8) -> AclEntry {
+    AclEntry { tag, perms }
+}
+
+/// Whether the filesystem under `root` takes ACLs; some test machines
+/// mount without them
+fn acls_supported(root: &Path) -> bool {
+    let probe = touch(&root.join(".probe"));
+    let acl = Acl::new(vec![
+        entry(AclTag::UserObj, 6),
+        entry(AclTag::GroupObj, 4),
+        entry(AclTag::Other, 4),
+    ]);
+    let supported = xattr::set(&probe, AclKind::Access.xattr_name(), &acl.to_xattr()).is_ok();
+    fs::remove_file(probe).unwrap();
+    supported
+}
+
+// ===== ACL Tests =====
+
+#[test]
+fn test_acl_parse_and_format() {
+    let accounts = accounts();
+    let acl = Acl::parse(
+        "other::---\nuser::rw-\nuser:alice:r-x # reviewer\ngroup::r\ng:50:6,mask::7",
+        &accounts,
+    )
+    .unwrap();
+
+    assert_eq!(
+        acl.entries,
+        vec![
+            entry(AclTag::UserObj, 6),
+            entry(AclTag::User(1000), 5),
+            entry(AclTag::GroupObj, 4),
+            entry(AclTag::Group(50), 6),
+            entry(AclTag::Mask, 7),
+            entry(AclTag::Other, 0),
+        ]
+    );
+    assert_eq!(
+        acl.to_text(&accounts),
+        "user::rw-,user:alice:r-x,group::r--,group:staff:rw-,mask::rwx,other::---"
+    );
+    assert_eq!(acl.get(AclTag::User(1000)), Some(5));
+    assert!(!acl.is_minimal());
+    // Unknown IDs are shown as numbers
+    assert!(acl.to_string().contains("user:1000:r-x"));
+}
+
+#[test]
+fn test_acl_rejects_what_the_kernel_would() {
+    let accounts = accounts();
+    for text in [
+        "user::rw-,other::---",
+        "user::rw-,user::r--,group::r--,other::---",
+        "user::rw-,user:alice:r--,group::r--,other::---",
+        "user::rw-,user:alice:r--,user:1000:rw-,group::r--,mask::rw-,other::---",
+        "user::rwz,group::r--,other::---",
+        "user:nobody:r--",
+        "bogus",
+    ] {
+        assert!(
+            matches!(Acl::parse(text, &accounts), Err(CoreError::InvalidPath(_))),
+            "{text} was accepted"
+        );
+    }
+}
+
+#[test]
+fn test_acl_xattr_round_trip() {
+    let acl = Acl::new(vec![
+        entry(AclTag::Other, 4),
+        entry(AclTag::Mask, 7),
+        entry(AclTag::Group(50), 5),
+        entry(AclTag::GroupObj, 4),
+        entry(AclTag::UserObj, 7),
+    ]);
+    let value = acl.to_xattr();
+    // Version header, then 8 bytes per entry
+    assert_eq!(value.len(), 4 + 5 * 8);
+    assert_eq!(&value[..4], &2u32.to_le_bytes());
+    assert_eq!(Acl::from_xattr(&value).unwrap(), acl);
+
+    assert!(matches!(
+        Acl::from_xattr(&value[..7]),
+        Err(CoreError::InvalidData)
+    ));
+    let mut bad_version = value.clone();
+    bad_version[0] = 9;
+    assert!(matches!(
+        Acl::from_xattr(&bad_version),
+        Err(CoreError::InvalidData)
+    ));
+}
+
+#[test]
+fn test_attribute_names() {
+    assert_eq!(
+        XattrNamespace::of("user.xdg.tags"),
+        Some(XattrNamespace::User)
+    );
+    assert_eq!(
+        XattrNamespace::of("security.selinux"),
+        Some(XattrNamespace::Security)
+    );
+    assert_eq!(XattrNamespace::of("user."), None);
+    assert_eq!(XattrNamespace::of("plain"), None);
+
+    assert!(is_visible("security.selinux"));
+    assert!(is_visible("system.posix_acl_default"));
+    assert!(!is_visible("trusted.overlay.opaque"));
+    assert!(!is_visible("system.nfs4_acl"));
+
+    assert!(is_writable("user.comment"));
+    assert!(is_writable("system.posix_acl_access"));
+    assert!(!is_writable("security.selinux"));
+}
+
+// ===== LocalFs Tests =====
+
+#[tokio::test]
+async fn test_local_fs_lists_reads_writes_and_removes() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("a.txt"));
+    let fs = LocalFs::new(NodeRegistry::new());
+    assert!(fs.capabilities().xattrs);
+
+    fs.set_xattr(&file, "user.origin", b"https://example.com")
+        .await
+        .unwrap();
+    fs.set_xattr(&file, "user.checked", b"yes").await.unwrap();
+    let mut names = fs.list_xattrs(&file).await.unwrap();
+    names.sort();
+    assert_eq!(names, vec!["user.checked", "user.origin"]);
+    assert_eq!(
+        fs.get_xattr(&file, "user.origin").await.unwrap(),
+        Some(b"https://example.com".to_vec())
+    );
+    assert_eq!(fs.get_xattr(&file, "user.missing").await.unwrap(), None);
+
+    fs.remove_xattr(&file, "user.checked").await.unwrap();
+    // Removing twice is fine
+    fs.remove_xattr(&file, "user.checked").await.unwrap();
+    assert_eq!(fs.list_xattrs(&file).await.unwrap(), vec!["user.origin"]);
+
+    assert!(matches!(
+        fs.set_xattr(&file, "security.selinux", b"x").await,
+        Err(CoreError::Refused(_))
+    ));
+    assert!(matches!(
+        fs.remove_xattr(&file, "trusted.x").await,
+        Err(CoreError::Refused(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_file_attributes_load_and_edit_acls() {
+    let (_dir, root) = tempdir();
+    if !acls_supported(&root) {
+        return;
+    }
+    let file = touch(&root.join("shared.txt"));
+    let fs = LocalFs::new(NodeRegistry::new());
+
+    AttributeEdit::Set {
+        name: "user.note".into(),
+        value: b"draft\0".to_vec(),
+    }
+    .apply(&fs, &file)
+    .await
+    .unwrap();
+    let acl = Acl::parse(
+        "user::rw-,user:1000:r--,group::r--,mask::r--,other::---",
+        &Accounts::default(),
+    )
+    .unwrap();
+    AttributeEdit::SetAcl {
+        kind: AclKind::Access,
+        acl: acl.clone(),
+    }
+    .apply(&fs, &file)
+    .await
+    .unwrap();
+
+    let attributes = FileAttributes::load(&fs, &file).await.unwrap();
+    assert_eq!(attributes.access_acl, Some(acl));
+    assert_eq!(attributes.default_acl, None);
+    let note = attributes.get("user.note").unwrap();
+    assert_eq!(note.text(), Some("draft"));
+    assert!(note.is_writable());
+    // The ACL is not listed again as a raw attribute
+    assert!(attributes.get(AclKind::Access.xattr_name()).is_none());
+
+    // Listings flag the ACL like `ls -l`
+    assert!(FileNode::from_path(file.clone(), None).unwrap().has_acl());
+    AttributeEdit::RemoveAcl(AclKind::Access)
+        .apply(&fs, &file)
+        .await
+        .unwrap();
+    assert!(!FileNode::from_path(file.clone(), None).unwrap().has_acl());
+
+    // Default ACLs only exist on directories
+    let minimal = Acl::parse("u::rwx,g::r-x,o::r-x", &Accounts::default()).unwrap();
+    let edit = AttributeEdit::SetAcl {
+        kind: AclKind::Default,
+        acl: minimal.clone(),
+    };
+    assert!(matches!(
+        edit.apply(&fs, &file).await,
+        Err(CoreError::Refused(_))
+    ));
+    edit.apply(&fs, &root).await.unwrap();
+    let attributes = FileAttributes::load(&fs, &root).await.unwrap();
+    assert_eq!(attributes.default_acl, Some(minimal));
+}
+
+#[test]
+fn test_attribute_edits_validate() {
+    let raw = AttributeEdit::Set {
+        name: "system.posix_acl_access".into(),
+        value: vec![2, 0, 0, 0],
+    };
+    assert!(matches!(raw.validate(), Err(CoreError::Refused(_))));
+    assert!(matches!(
+        AttributeEdit::Remove("security.selinux".into()).validate(),
+        Err(CoreError::Refused(_))
+    ));
+    let incomplete = AttributeEdit::SetAcl {
+        kind: AclKind::Access,
+        acl: Acl::new(vec![entry(AclTag::UserObj, 7)]),
+    };
+    assert!(incomplete.validate().is_err());
+    assert!(AttributeEdit::Remove("user.x".into()).validate().is_ok());
+}
+
+#[tokio::test]
+async fn test_safe_save_keeps_attributes() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("doc.txt"));
+    let fs = LocalFs::new(NodeRegistry::new());
+    fs.set_xattr(&file, "user.xdg.tags", b"work").await.unwrap();
+
+    fs.write(&file, b"new content", &Precondition::default())
+        .await
+        .unwrap();
+    assert_eq!(fs::read(&file).unwrap(), b"new content");
+    assert_eq!(
+        fs.get_xattr(&file, "user.xdg.tags").await.unwrap(),
+        Some(b"work".to_vec())
+    );
+}
+
+#[tokio::test]
+async fn test_providers_without_xattrs_say_so() {
+    let fs = TagsFs::new(TagStore::in_memory(), NodeRegistry::new());
+    assert!(!fs.capabilities().xattrs);
+    assert!(fs.list_xattrs(Path::new("/")).await.is_err());
+    assert!(matches!(
+        AttributeEdit::Remove("user.x".into())
+            .apply(&fs, Path::new("/"))
+            .await,
+        Err(CoreError::Io { .. })
+    ));
+}
+
+// ===== Operations Actor Tests =====
+
+#[tokio::test]
+async fn test_operations_view_and_edit_attributes() {
+    let (_dir, root) = tempdir();
+    let a = touch(&root.join("a.txt"));
+    let b = touch(&root.join("b.txt"));
+    let h = spawn_operations(|ops| ops);
+    let nodes = vec![
+        h.registry.clone().register(a.clone()),
+        h.registry.clone().register(b.clone()),
+    ];
+
+    let done = h
+        .complete(OpCommand::EditAttributes {
+            nodes: nodes.clone(),
+            edit: AttributeEdit::Set {
+                name: "user.project".into(),
+                value: b"apollo".to_vec(),
+            },
+            session: h.session,
+        })
+        .await;
+    assert!(matches!(done, (true, OperationKind::EditAttributes)));
+    assert_eq!(
+        xattr::get(&b, "user.project").unwrap(),
+        Some(b"apollo".to_vec())
+    );
+
+    h.commands
+        .send(OpCommand::LoadAttributes {
+            node: nodes[0],
+            session: h.session,
+        })
+        .unwrap();
+    let attributes = loop {
+        if let Event::AttributesLoaded {
+            node, attributes, ..
+        } = h.next().await
+        {
+            assert_eq!(node, nodes[0]);
+            break attributes;
+        }
+    };
+    assert_eq!(
+        attributes.get("user.project").unwrap().text(),
+        Some("apollo")
+    );
+
+    let done = h
+        .complete(OpCommand::EditAttributes {
+            nodes,
+            edit: AttributeEdit::Remove("security.selinux".into()),
+            session: h.session,
+        })
+        .await;
+    assert!(!done.0);
+}
+
+#[tokio::test]
+async fn test_operations_refuse_providers_without_xattrs() {
+    let (_dir, root) = tempdir();
+    let file = touch(&root.join("a.txt"));
+    let tags = TagsFs::new(TagStore::in_memory(), NodeRegistry::new());
+    let h = spawn_operations(|ops| ops.with_provider(std::sync::Arc::new(tags)));
+    let node = h.registry.clone().register(file);
+
+    let done = h
+        .complete(OpCommand::EditAttributes {
+            nodes: vec![node],
+            edit: AttributeEdit::Remove("user.x".into()),
+            session: h.session,
+        })
+        .await;
+    assert!(matches!(done, (false, OperationKind::EditAttributes)));
+}
@@ -1,7 +1,9 @@
 pub(crate) mod path;
+pub(crate) mod rename;
 pub(crate) mod size;
 pub(crate) mod time;
 pub(crate) mod grouped_node;
+pub(crate) mod xattr;
 
 pub use path::*;
 pub use size::*;
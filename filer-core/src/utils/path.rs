@@ -37,3 +37,67 @@ pub fn normalize(path: &Path) -> Result<PathBuf, Error> {
 pub fn parent_name(path: &Path) -> Option<&str> {
     path.parent().map(|s| s.to_str().unwrap())
 }
+
+/// Build a non-existing sibling path by appending " (N)" to the stem
+/// (e.g., "report.pdf" -> "report (2).pdf")
+///
+/// Symlinks are not followed: a dangling link still takes up its name.
+pub fn unique_name(path: &Path) -> PathBuf {
+    if !taken(path) {
+        return path.to_path_buf();
+    }
+    let mut n = 2;
+    loop {
+        let candidate = numbered_name(path, n);
+        if !taken(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// `path` with " (N)" appended to the stem, e.g. "report (2).pdf"
+pub fn numbered_name(path: &Path, n: usize) -> PathBuf {
+    let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let stem = get_stem(path).unwrap_or_default();
+    let name = match get_extension(path) {
+        Some(ext) => format!("{stem} ({n}).{ext}"),
+        None => format!("{stem} ({n})"),
+    };
+    parent.join(name)
+}
+
+fn taken(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok()
+}
+
+/// Match a file name against a glob pattern: `*` is any run of
+/// characters, `?` any one character
+pub fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, tried)) => {
+                    p = after;
+                    n = tried + 1;
+                    star = Some((after, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,151 @@
+//! Extended attributes of local files
+//!
+//! Thin wrappers over the Linux `*xattr` calls. They follow symlinks like
+//! the rest of `LocalFs`. Elsewhere every call fails with
+//! `ErrorKind::Unsupported`.
+
+use std::io;
+use std::path::Path;
+
+/// Value of `name` on `path`, `None` if it is not set
+#[cfg(target_os = "linux")]
+pub(crate) fn get(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let (path, name) = (c_path(path)?, c_name(name)?);
+    loop {
+        // SAFETY: both strings are NUL-terminated; a null buffer asks for the size
+        let size = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return absent_is_none(io::Error::last_os_error());
+        }
+        let mut value = vec![0u8; size as usize];
+        // SAFETY: `value` has room for `size` bytes
+        let read = unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            )
+        };
+        if read >= 0 {
+            value.truncate(read as usize);
+            return Ok(Some(value));
+        }
+        let error = io::Error::last_os_error();
+        // The value grew between the two calls
+        if error.raw_os_error() != Some(libc::ERANGE) {
+            return absent_is_none(error);
+        }
+    }
+}
+
+/// Names of the attributes set on `path` that this process may see
+#[cfg(target_os = "linux")]
+pub(crate) fn list(path: &Path) -> io::Result<Vec<String>> {
+    let path = c_path(path)?;
+    loop {
+        // SAFETY: the path is NUL-terminated; a null buffer asks for the size
+        let size = unsafe { libc::listxattr(path.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut names = vec![0u8; size as usize];
+        // SAFETY: `names` has room for `size` bytes
+        let read =
+            unsafe { libc::listxattr(path.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+        if read >= 0 {
+            names.truncate(read as usize);
+            return Ok(names
+                .split(|&b| b == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .collect());
+        }
+        let error = io::Error::last_os_error();
+        // The list grew between the two calls
+        if error.raw_os_error() != Some(libc::ERANGE) {
+            return Err(error);
+        }
+    }
+}
+
+/// Set `name` on `path` to `value`
+#[cfg(target_os = "linux")]
+pub(crate) fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let (path, name) = (c_path(path)?, c_name(name)?);
+    // SAFETY: both strings are NUL-terminated and `value` is valid for its length
+    let result = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Remove `name` from `path`; fine if it is not set
+#[cfg(target_os = "linux")]
+pub(crate) fn remove(path: &Path, name: &str) -> io::Result<()> {
+    let (path, name) = (c_path(path)?, c_name(name)?);
+    // SAFETY: both strings are NUL-terminated
+    if unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) } < 0 {
+        return absent_is_none(io::Error::last_os_error()).map(|_| ());
+    }
+    Ok(())
+}
+
+/// The filesystem (or this platform) has no extended attributes
+pub(crate) fn is_unsupported(error: &io::Error) -> bool {
+    #[cfg(target_os = "linux")]
+    if error.raw_os_error() == Some(libc::EOPNOTSUPP) {
+        return true;
+    }
+    error.kind() == io::ErrorKind::Unsupported
+}
+
+#[cfg(target_os = "linux")]
+fn absent_is_none(error: io::Error) -> io::Result<Option<Vec<u8>>> {
+    if error.raw_os_error() == Some(libc::ENODATA) {
+        Ok(None)
+    } else {
+        Err(error)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn c_path(path: &Path) -> io::Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+#[cfg(target_os = "linux")]
+fn c_name(name: &str) -> io::Result<std::ffi::CString> {
+    std::ffi::CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn get(_path: &Path, _name: &str) -> io::Result<Option<Vec<u8>>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn list(_path: &Path) -> io::Result<Vec<String>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn set(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn remove(_path: &Path, _name: &str) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
@@ -28,6 +28,7 @@ impl FsProvider for ArchiveFs {
             write: false,
             watch: false,
             search: false,
+            xattrs: false,
         }
     }
 
@@ -3,8 +3,9 @@ use async_trait::async_trait;
 
 use crate::errors::CoreError;
 use crate::model::node::FileNode;
-use crate::vfs::provider::{Capabilities, FsProvider};
-use crate::vfs::remote::{RemoteConfig, RemoteProvider};
+use crate::services::scheduler::DeviceKey;
+use crate::vfs::provider::{Capabilities, FsProvider, Precondition, WriteStream};
+use crate::vfs::remote::{BufferedUpload, PutFile, RemoteConfig, RemoteProvider};
 
 /// FTP/SFTP configuration
 #[derive(Debug, Clone)]
@@ -84,6 +85,13 @@ impl FtpFs {
     }
 }
 
+#[async_trait]
+impl PutFile for FtpFs {
+    async fn put_file(&self, path: &Path, data: &[u8]) -> Result<(), CoreError> {
+        self.upload(&path.to_string_lossy(), data).await
+    }
+}
+
 #[async_trait]
 impl FsProvider for FtpFs {
     fn scheme(&self) -> &'static str {
@@ -94,12 +102,17 @@ impl FsProvider for FtpFs {
         }
     }
 
+    fn io_device(&self, _path: &Path) -> DeviceKey {
+        DeviceKey::remote(format!("{}:{}", self.config.host, self.config.port))
+    }
+
     fn capabilities(&self) -> Capabilities {
         Capabilities {
             read: true,
             write: true,
             watch: false,
             search: false,
+            xattrs: false,
         }
     }
 
@@ -122,6 +135,23 @@ impl FsProvider for FtpFs {
     async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
         todo!()
     }
+
+    async fn create(
+        &self,
+        path: &Path,
+        precondition: &Precondition,
+    ) -> Result<Box<dyn WriteStream + '_>, CoreError> {
+        Ok(Box::new(BufferedUpload::new(self, path, precondition).await?))
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<(), CoreError> {
+        for dir in path.ancestors().collect::<Vec<_>>().into_iter().rev().skip(1) {
+            if !self.exists(dir).await? {
+                self.mkdir(&dir.to_string_lossy()).await?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
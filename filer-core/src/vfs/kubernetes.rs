@@ -100,6 +100,7 @@ impl FsProvider for K8sFs {
             write: true,
             watch: true,
             search: false,
+            xattrs: false,
         }
     }
 
@@ -1,12 +1,21 @@
 use async_trait::async_trait;
-use std::path::Path;
-use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use std::ffi::OsString;
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 use crate::errors::CoreError;
 use crate::model::node::FileNode;
 use crate::model::registry::NodeRegistry;
-use crate::vfs::provider::{Capabilities, FsProvider};
+use crate::services::metadata::{is_visible, is_writable};
+use crate::utils::rename::rename_noreplace;
+use crate::utils::xattr;
+use crate::vfs::provider::{Capabilities, FsProvider, Precondition, WriteStream};
 
 /// Local filesystem provider
 pub struct LocalFs {
@@ -19,6 +28,164 @@ impl LocalFs {
     }
 }
 
+/// Safe-save: written to `.name.<unique>.filer-part` in the target's
+/// directory, fsynced, then renamed over the target so a crash leaves
+/// either the old or the new content. The precondition is checked again
+/// right before the rename, and the target keeps its permissions.
+struct LocalUpload {
+    file: File,
+    part: PathBuf,
+    target: PathBuf,
+    precondition: Precondition,
+}
+
+#[async_trait]
+impl WriteStream for LocalUpload {
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), CoreError> {
+        self.file
+            .write_all(chunk)
+            .await
+            .map_err(|e| CoreError::from_io_error(e, self.part.clone()))
+    }
+
+    async fn finish(self: Box<Self>) -> Result<(), CoreError> {
+        let Self {
+            file,
+            part,
+            target,
+            precondition,
+        } = *self;
+        if let Err(e) = file.sync_all().await {
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(CoreError::from_io_error(e, part));
+        }
+        drop(file);
+        tokio::task::spawn_blocking(move || publish(&part, &target, &precondition))
+            .await
+            .map_err(|e| CoreError::Io {
+                path: PathBuf::new(),
+                message: e.to_string(),
+            })?
+    }
+
+    async fn abort(self: Box<Self>) {
+        drop(self.file);
+        let _ = tokio::fs::remove_file(&self.part).await;
+    }
+}
+
+/// Saves go through symlinks so the link itself survives
+fn save_target(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `(node, metadata)` of `path`, or `None` if it does not exist
+fn current(path: &Path) -> Result<Option<(FileNode, Metadata)>, CoreError> {
+    match std::fs::metadata(path) {
+        Ok(meta) => Ok(Some((
+            FileNode::from_metadata(meta.clone(), path.to_path_buf(), None)?,
+            meta,
+        ))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(CoreError::from_io_error(e, path.to_path_buf())),
+    }
+}
+
+/// Fresh part file next to `target`, unique within this process
+fn part_path(target: &Path) -> Result<PathBuf, CoreError> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let name = target
+        .file_name()
+        .ok_or_else(|| CoreError::InvalidPath(target.display().to_string()))?;
+    let mut part = OsString::from(".");
+    part.push(name);
+    part.push(format!(
+        ".{}-{}.filer-part",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(target.with_file_name(part))
+}
+
+/// Carry the attributes that can be written (tags, ACLs) over to the
+/// replacement; best effort, the content matters more
+fn keep_xattrs(from: &Path, to: &Path) {
+    let Ok(names) = xattr::list(from) else {
+        return;
+    };
+    for name in names.iter().filter(|name| is_writable(name)) {
+        if let Ok(Some(value)) = xattr::get(from, name) {
+            let _ = xattr::set(to, name, &value);
+        }
+    }
+}
+
+/// Refuse names outside `user.*` and the ACLs
+fn check_writable(name: &str) -> Result<(), CoreError> {
+    if is_writable(name) {
+        Ok(())
+    } else {
+        Err(CoreError::Refused(format!("{name} is read-only")))
+    }
+}
+
+/// Copy `from` into the new file `part` with its permissions, fsynced
+fn copy_to_part(from: &Path, part: &Path) -> Result<(), CoreError> {
+    let io = |e| CoreError::from_io_error(e, part.to_path_buf());
+    let mut source = std::fs::File::open(from).map_err(|e| CoreError::from_io_error(e, from.to_path_buf()))?;
+    let mode = source.metadata().map_err(io)?.permissions().mode();
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(part)
+        .map_err(io)?;
+    // `io::copy` between two files uses `copy_file_range`
+    let result = std::io::copy(&mut source, &mut file).and_then(|_| file.sync_all());
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(part);
+        return Err(io(e));
+    }
+    Ok(())
+}
+
+/// Rename `part` over `target` if the precondition still holds
+///
+/// Targets that must be absent are never replaced, even when one appears
+/// after the check.
+fn publish(part: &Path, target: &Path, precondition: &Precondition) -> Result<(), CoreError> {
+    let result = current(target).and_then(|existing| {
+        precondition.check(target, existing.as_ref().map(|(node, _)| node))?;
+        if let Some((_, meta)) = &existing {
+            std::fs::set_permissions(part, meta.permissions())
+                .map_err(|e| CoreError::from_io_error(e, part.to_path_buf()))?;
+            keep_xattrs(target, part);
+        }
+        let renamed = match precondition.absent {
+            true => rename_noreplace(part, target),
+            false => std::fs::rename(part, target),
+        };
+        renamed.map_err(|e| match e.kind() {
+            ErrorKind::AlreadyExists => CoreError::PreconditionFailed {
+                path: target.to_path_buf(),
+                reason: "it already exists".to_string(),
+            },
+            _ => CoreError::from_io_error(e, target.to_path_buf()),
+        })
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(part);
+        return result;
+    }
+    // Make the rename itself durable
+    if let Some(dir) = target.parent()
+        && let Ok(dir) = std::fs::File::open(dir)
+    {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl FsProvider for LocalFs {
     fn scheme(&self) -> &'static str {
@@ -31,6 +198,7 @@ impl FsProvider for LocalFs {
             write: true,
             watch: true,
             search: false,
+            xattrs: true,
         }
     }
 
@@ -95,4 +263,93 @@ impl FsProvider for LocalFs {
     async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
         FileNode::from_path(path.to_path_buf(), Some(self.reg.clone()))
     }
+
+    async fn create(
+        &self,
+        path: &Path,
+        precondition: &Precondition,
+    ) -> Result<Box<dyn WriteStream + '_>, CoreError> {
+        let target = save_target(path);
+        let existing = current(&target)?;
+        precondition.check(&target, existing.as_ref().map(|(node, _)| node))?;
+        // Never readable by more people than the file it replaces
+        let mode = existing.map_or(0o666, |(_, meta)| meta.permissions().mode());
+        let part = part_path(&target)?;
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(&part)
+            .await
+            .map_err(|e| CoreError::from_io_error(e, part.clone()))?;
+        Ok(Box::new(LocalUpload {
+            file,
+            part,
+            target,
+            precondition: precondition.clone(),
+        }))
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<(), CoreError> {
+        tokio::fs::create_dir_all(path)
+            .await
+            .map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+    }
+
+    /// `copy_file_range` (a reflink or an in-kernel copy) into a part file
+    /// that is published like an upload
+    async fn copy_within(&self, from: &Path, to: &Path, precondition: &Precondition) -> Result<bool, CoreError> {
+        let (from, target, precondition) = (from.to_path_buf(), save_target(to), precondition.clone());
+        tokio::task::spawn_blocking(move || {
+            precondition.check(&target, current(&target)?.as_ref().map(|(node, _)| node))?;
+            let part = part_path(&target)?;
+            copy_to_part(&from, &part)?;
+            publish(&part, &target, &precondition)
+        })
+        .await
+        .map_err(|e| CoreError::Io {
+            path: PathBuf::new(),
+            message: e.to_string(),
+        })??;
+        Ok(true)
+    }
+
+    async fn set_modified(&self, path: &Path, modified: SystemTime) -> Result<bool, CoreError> {
+        let io = |e| CoreError::from_io_error(e, path.to_path_buf());
+        std::fs::File::open(path).and_then(|file| file.set_modified(modified)).map_err(io)?;
+        Ok(true)
+    }
+
+    /// Symlinks are removed themselves, never what they point to
+    async fn remove(&self, path: &Path) -> Result<(), CoreError> {
+        let io = |e| CoreError::from_io_error(e, path.to_path_buf());
+        let meta = tokio::fs::symlink_metadata(path).await.map_err(io)?;
+        if meta.is_dir() {
+            tokio::fs::remove_dir_all(path).await.map_err(io)
+        } else {
+            tokio::fs::remove_file(path).await.map_err(io)
+        }
+    }
+
+    /// `user.*`, `security.*` and the ACLs; others are left out
+    async fn list_xattrs(&self, path: &Path) -> Result<Vec<String>, CoreError> {
+        let mut names = xattr::list(path).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))?;
+        names.retain(|name| is_visible(name));
+        Ok(names)
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, CoreError> {
+        xattr::get(path, name).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+    }
+
+    /// Only `user.*` and the ACLs can be written
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), CoreError> {
+        check_writable(name)?;
+        xattr::set(path, name, value).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+    }
+
+    async fn remove_xattr(&self, path: &Path, name: &str) -> Result<(), CoreError> {
+        check_writable(name)?;
+        xattr::remove(path, name).map_err(|e| CoreError::from_io_error(e, path.to_path_buf()))
+    }
 }
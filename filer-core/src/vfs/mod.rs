@@ -1,6 +1,9 @@
 pub mod archive;
 pub mod local;
 pub mod provider;
+pub mod snapshot;
+pub mod tags;
+pub mod trash;
 
 #[cfg(any(feature = "s3", feature = "webdav", feature = "ftp", feature = "sftp", feature = "kubernetes"))]
 pub mod remote;
@@ -1,8 +1,11 @@
 use std::path::Path;
+use std::time::SystemTime;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::CoreError;
 use crate::model::node::FileNode;
+use crate::services::scheduler::DeviceKey;
 
 /// Capabilities of a filesystem provider
 #[derive(Debug, Clone, Copy)]
@@ -11,6 +14,112 @@ pub struct Capabilities {
     pub write: bool,
     pub watch: bool,
     pub search: bool,
+    /// Extended attributes (and the POSIX ACLs kept in them) can be read
+    /// and written through the `*_xattr` methods
+    pub xattrs: bool,
+}
+
+/// What the target of a write must still look like
+///
+/// Taken from the `FileNode` a client read before editing; a write whose
+/// target changed since then fails with `CoreError::PreconditionFailed`
+/// instead of overwriting someone else's change. The default has no
+/// conditions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Precondition {
+    pub modified: Option<SystemTime>,
+    pub size: Option<u64>,
+    pub etag: Option<String>,
+    /// The target must not exist yet
+    pub absent: bool,
+}
+
+impl Precondition {
+    /// Only create, never replace
+    pub fn absent() -> Self {
+        Self {
+            absent: true,
+            ..Default::default()
+        }
+    }
+
+    /// The target must still be the version described by `node`
+    pub fn unchanged(node: &FileNode) -> Self {
+        Self {
+            modified: node.modified,
+            size: Some(node.size),
+            etag: node.meta.etag.clone(),
+            absent: false,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check against the current state of `path` (`None` if missing)
+    pub fn check(&self, path: &Path, current: Option<&FileNode>) -> Result<(), CoreError> {
+        let failed = |reason: String| {
+            Err(CoreError::PreconditionFailed {
+                path: path.to_path_buf(),
+                reason,
+            })
+        };
+        let Some(node) = current else {
+            if self.modified.is_some() || self.size.is_some() || self.etag.is_some() {
+                return failed("it no longer exists".to_string());
+            }
+            return Ok(());
+        };
+        if self.absent {
+            return failed("it already exists".to_string());
+        }
+        if let Some(size) = self.size
+            && node.size != size
+        {
+            return failed(format!("size is {} instead of {size}", node.size));
+        }
+        if self.modified.is_some() && node.modified != self.modified {
+            return failed("it was modified".to_string());
+        }
+        if let Some(etag) = &self.etag
+            && node.meta.etag.as_ref() != Some(etag)
+        {
+            return failed(match &node.meta.etag {
+                Some(current) => format!("ETag is {current} instead of {etag}"),
+                None => "it has no ETag".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check against what `provider` reports for `path` now
+    pub async fn verify(&self, provider: &dyn FsProvider, path: &Path) -> Result<(), CoreError> {
+        if self.is_none() {
+            return Ok(());
+        }
+        let current = match provider.metadata(path).await {
+            Ok(node) => Some(node),
+            Err(CoreError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        self.check(path, current.as_ref())
+    }
+}
+
+/// A file being written through `FsProvider::create`
+///
+/// Nothing appears at the target path before `finish`.
+#[async_trait]
+pub trait WriteStream: Send {
+    /// Append the next chunk
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), CoreError>;
+
+    /// Publish the file at its target path, replacing what was there
+    async fn finish(self: Box<Self>) -> Result<(), CoreError>;
+
+    /// Throw away what was written
+    async fn abort(self: Box<Self>);
 }
 
 /// Trait for filesystem backends
@@ -22,6 +131,11 @@ pub trait FsProvider: Send + Sync {
     /// Provider capabilities
     fn capabilities(&self) -> Capabilities;
     
+    /// Device or host that I/O on `path` goes to, for the I/O scheduler
+    fn io_device(&self, path: &Path) -> DeviceKey {
+        DeviceKey::of_path(path)
+    }
+    
     /// List contents of a directory
     async fn list(&self, path: &Path) -> Result<Vec<FileNode>, CoreError>;
     
@@ -36,4 +150,78 @@ pub trait FsProvider: Send + Sync {
     
     /// Get metadata for a path
     async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError>;
+    
+    /// Start writing the file at `path` if `precondition` holds
+    ///
+    /// The precondition is checked again when the stream finishes.
+    async fn create(
+        &self,
+        path: &Path,
+        _precondition: &Precondition,
+    ) -> Result<Box<dyn WriteStream + '_>, CoreError> {
+        Err(CoreError::PermissionDenied(path.to_path_buf()))
+    }
+    
+    /// Replace the file at `path` with `data` if `precondition` holds
+    async fn write(&self, path: &Path, data: &[u8], precondition: &Precondition) -> Result<(), CoreError> {
+        let mut stream = self.create(path, precondition).await?;
+        if let Err(e) = stream.write(data).await {
+            stream.abort().await;
+            return Err(e);
+        }
+        stream.finish().await
+    }
+    
+    /// Create a directory (and missing parents); fine if it exists
+    async fn create_dir(&self, path: &Path) -> Result<(), CoreError> {
+        Err(CoreError::PermissionDenied(path.to_path_buf()))
+    }
+    
+    /// Copy a file without passing its content through this process
+    /// (server-side copy, reflink); `false` if the provider cannot
+    ///
+    /// `to` must meet `precondition`, as with `create`.
+    async fn copy_within(&self, _from: &Path, _to: &Path, _precondition: &Precondition) -> Result<bool, CoreError> {
+        Ok(false)
+    }
+
+    /// Set the modification time of `path`; `false` if the provider cannot
+    async fn set_modified(&self, _path: &Path, _modified: SystemTime) -> Result<bool, CoreError> {
+        Ok(false)
+    }
+
+    /// Delete a file, or a directory with everything in it
+    async fn remove(&self, path: &Path) -> Result<(), CoreError> {
+        Err(CoreError::Io {
+            path: path.to_path_buf(),
+            message: format!("{} cannot delete", self.scheme()),
+        })
+    }
+
+    /// Names of the extended attributes of `path`
+    async fn list_xattrs(&self, path: &Path) -> Result<Vec<String>, CoreError> {
+        Err(no_xattrs(self.scheme(), path))
+    }
+
+    /// Value of the extended attribute `name`, `None` if it is not set
+    async fn get_xattr(&self, path: &Path, _name: &str) -> Result<Option<Vec<u8>>, CoreError> {
+        Err(no_xattrs(self.scheme(), path))
+    }
+
+    /// Set the extended attribute `name`
+    async fn set_xattr(&self, path: &Path, _name: &str, _value: &[u8]) -> Result<(), CoreError> {
+        Err(no_xattrs(self.scheme(), path))
+    }
+
+    /// Remove the extended attribute `name`; fine if it is not set
+    async fn remove_xattr(&self, path: &Path, _name: &str) -> Result<(), CoreError> {
+        Err(no_xattrs(self.scheme(), path))
+    }
+}
+
+fn no_xattrs(scheme: &str, path: &Path) -> CoreError {
+    CoreError::Io {
+        path: path.to_path_buf(),
+        message: format!("{scheme} has no extended attributes"),
+    }
 }
\ No newline at end of file
@@ -1,9 +1,9 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use async_trait::async_trait;
 
 use crate::errors::CoreError;
 use crate::model::node::FileNode;
-use crate::vfs::provider::{Capabilities, FsProvider};
+use crate::vfs::provider::{Capabilities, FsProvider, Precondition, WriteStream};
 
 /// Remote filesystem connection config
 #[derive(Debug, Clone)]
@@ -47,3 +47,47 @@ pub trait RemoteProvider: FsProvider {
         Ok(())
     }
 }
+
+/// Clients that can only upload a whole file in one request
+#[async_trait]
+pub(crate) trait PutFile: FsProvider {
+    async fn put_file(&self, path: &Path, data: &[u8]) -> Result<(), CoreError>;
+}
+
+/// `WriteStream` for `PutFile` clients: collected in memory, sent on finish
+///
+/// The precondition is checked right before sending, which leaves a short
+/// window for a concurrent change.
+pub(crate) struct BufferedUpload<'a, P: PutFile> {
+    client: &'a P,
+    path: PathBuf,
+    precondition: Precondition,
+    data: Vec<u8>,
+}
+
+impl<'a, P: PutFile> BufferedUpload<'a, P> {
+    pub(crate) async fn new(client: &'a P, path: &Path, precondition: &Precondition) -> Result<Self, CoreError> {
+        precondition.verify(client, path).await?;
+        Ok(Self {
+            client,
+            path: path.to_path_buf(),
+            precondition: precondition.clone(),
+            data: Vec::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl<P: PutFile> WriteStream for BufferedUpload<'_, P> {
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), CoreError> {
+        self.data.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    async fn finish(self: Box<Self>) -> Result<(), CoreError> {
+        self.precondition.verify(self.client, &self.path).await?;
+        self.client.put_file(&self.path, &self.data).await
+    }
+
+    async fn abort(self: Box<Self>) {}
+}
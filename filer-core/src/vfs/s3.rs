@@ -3,8 +3,9 @@ use async_trait::async_trait;
 
 use crate::errors::CoreError;
 use crate::model::node::FileNode;
-use crate::vfs::provider::{Capabilities, FsProvider};
-use crate::vfs::remote::{RemoteConfig, RemoteProvider};
+use crate::services::scheduler::DeviceKey;
+use crate::vfs::provider::{Capabilities, FsProvider, Precondition, WriteStream};
+use crate::vfs::remote::{BufferedUpload, PutFile, RemoteConfig, RemoteProvider};
 
 /// S3 configuration
 #[derive(Debug, Clone)]
@@ -63,6 +64,23 @@ impl S3Fs {
     async fn delete_object(&self, key: &str) -> Result<(), CoreError> {
         todo!()
     }
+
+    /// Server-side CopyObject within the bucket
+    async fn copy_object(&self, from: &str, to: &str) -> Result<(), CoreError> {
+        todo!()
+    }
+
+    /// Object key of a path
+    fn key(path: &Path) -> String {
+        path.to_string_lossy().trim_start_matches('/').to_string()
+    }
+}
+
+#[async_trait]
+impl PutFile for S3Fs {
+    async fn put_file(&self, path: &Path, data: &[u8]) -> Result<(), CoreError> {
+        self.put_object(&Self::key(path), data).await
+    }
 }
 
 #[async_trait]
@@ -71,12 +89,20 @@ impl FsProvider for S3Fs {
         "s3"
     }
 
+    fn io_device(&self, _path: &Path) -> DeviceKey {
+        match &self.config.endpoint {
+            Some(endpoint) => DeviceKey::of_url(endpoint),
+            None => DeviceKey::remote(format!("s3.{}.amazonaws.com", self.config.region)),
+        }
+    }
+
     fn capabilities(&self) -> Capabilities {
         Capabilities {
             read: true,
             write: true,
             watch: false,
             search: false,
+            xattrs: false,
         }
     }
 
@@ -99,6 +125,34 @@ impl FsProvider for S3Fs {
     async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
         todo!()
     }
+
+    async fn create(
+        &self,
+        path: &Path,
+        precondition: &Precondition,
+    ) -> Result<Box<dyn WriteStream + '_>, CoreError> {
+        Ok(Box::new(BufferedUpload::new(self, path, precondition).await?))
+    }
+
+    /// Prefixes exist as soon as an object is stored under them
+    async fn create_dir(&self, _path: &Path) -> Result<(), CoreError> {
+        Ok(())
+    }
+
+    async fn copy_within(&self, from: &Path, to: &Path, precondition: &Precondition) -> Result<bool, CoreError> {
+        precondition.verify(self, to).await?;
+        self.copy_object(&Self::key(from), &Self::key(to)).await?;
+        Ok(true)
+    }
+
+    /// Every object under the prefix for directories
+    async fn remove(&self, path: &Path) -> Result<(), CoreError> {
+        let key = Self::key(path);
+        for object in self.list_objects(&format!("{key}/")).await? {
+            self.delete_object(&Self::key(&object.path)).await?;
+        }
+        self.delete_object(&key).await
+    }
 }
 
 #[async_trait]
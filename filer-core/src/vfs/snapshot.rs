@@ -0,0 +1,193 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeId, NodeKind, NodeMeta};
+use crate::services::backup::{Repository, Snapshot, SnapshotEntry};
+use crate::vfs::provider::{Capabilities, FsProvider};
+
+/// Read-only view of a backup repository's snapshots
+///
+/// `/` lists one directory per snapshot, oldest first and named by its
+/// ID; below it are the backed-up sources as they were. Snapshots are
+/// loaded on first use and kept, since they never change.
+pub struct SnapshotFs {
+    repository: Repository,
+    loaded: Mutex<HashMap<String, Arc<Snapshot>>>,
+}
+
+impl SnapshotFs {
+    pub fn new(repository: Repository) -> Self {
+        Self {
+            repository,
+            loaded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn snapshot(&self, id: &str) -> Result<Arc<Snapshot>, CoreError> {
+        if let Some(snapshot) = self.loaded.lock().unwrap().get(id) {
+            return Ok(snapshot.clone());
+        }
+        let snapshot = Arc::new(self.repository.snapshot(id).await?);
+        self.loaded
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), snapshot.clone());
+        Ok(snapshot)
+    }
+
+    /// The snapshot ID and the path inside it, or `None` for the root
+    fn split(path: &Path) -> Result<Option<(String, PathBuf)>, CoreError> {
+        let mut parts = path
+            .components()
+            .filter(|c| !matches!(c, Component::RootDir));
+        let Some(first) = parts.next() else {
+            return Ok(None);
+        };
+        let rest: Vec<Component> = parts.collect();
+        match first {
+            // No `..` climbing out of a snapshot
+            Component::Normal(id) if rest.iter().all(|c| matches!(c, Component::Normal(_))) => Ok(
+                Some((id.to_string_lossy().into_owned(), rest.iter().collect())),
+            ),
+            _ => Err(CoreError::NotFound(path.to_path_buf())),
+        }
+    }
+
+    /// The file entry at `path`
+    async fn file(&self, path: &Path) -> Result<(Arc<Snapshot>, usize), CoreError> {
+        let Some((id, inner)) = Self::split(path)? else {
+            return Err(CoreError::InvalidPath(path.display().to_string()));
+        };
+        let snapshot = self.snapshot(&id).await?;
+        let index = snapshot
+            .entries
+            .iter()
+            .position(|entry| entry.path == inner)
+            .ok_or_else(|| CoreError::NotFound(path.to_path_buf()))?;
+        if snapshot.entries[index].is_dir {
+            return Err(CoreError::InvalidPath(path.display().to_string()));
+        }
+        Ok((snapshot, index))
+    }
+}
+
+/// Node for a snapshot directory or entry at `path`
+fn node(path: PathBuf, entry: Option<&SnapshotEntry>, time: Option<SystemTime>) -> FileNode {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let kind = match entry {
+        Some(entry) if !entry.is_dir => NodeKind::File {
+            extension: path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_string()),
+        },
+        _ => NodeKind::Directory {
+            children_count: None,
+        },
+    };
+    FileNode {
+        id: NodeId::from_path(&path),
+        kind,
+        size: entry.map_or(0, |entry| entry.size),
+        modified: entry.map_or(time, |entry| entry.modified),
+        created: None,
+        meta: NodeMeta {
+            hidden: name.starts_with('.'),
+            readonly: true,
+            permissions: entry.and_then(|entry| entry.permissions),
+            etag: None,
+            ..Default::default()
+        },
+        name,
+        path,
+    }
+}
+
+#[async_trait]
+impl FsProvider for SnapshotFs {
+    fn scheme(&self) -> &'static str {
+        "snapshot"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            write: false,
+            watch: false,
+            search: false,
+            xattrs: false,
+        }
+    }
+
+    async fn list(&self, path: &Path) -> Result<Vec<FileNode>, CoreError> {
+        let Some((id, inner)) = Self::split(path)? else {
+            let mut snapshots = Vec::new();
+            for id in self.repository.snapshot_ids().await? {
+                snapshots.push(self.snapshot(&id).await?);
+            }
+            snapshots.sort_by_key(|snapshot| snapshot.time);
+            return Ok(snapshots
+                .iter()
+                .map(|snapshot| node(Path::new("/").join(&snapshot.id), None, Some(snapshot.time)))
+                .collect());
+        };
+        let snapshot = self.snapshot(&id).await?;
+        if !inner.as_os_str().is_empty() {
+            match snapshot.entry(&inner) {
+                Some(entry) if entry.is_dir => {}
+                Some(_) => return Err(CoreError::InvalidPath(path.display().to_string())),
+                None => return Err(CoreError::NotFound(path.to_path_buf())),
+            }
+        }
+        let base = Path::new("/").join(&id);
+        Ok(snapshot
+            .entries
+            .iter()
+            .filter(|entry| entry.path.parent() == Some(inner.as_path()))
+            .map(|entry| node(base.join(&entry.path), Some(entry), None))
+            .collect())
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, CoreError> {
+        let (snapshot, index) = self.file(path).await?;
+        let entry = &snapshot.entries[index];
+        self.repository.read_range(entry, 0, entry.size).await
+    }
+
+    async fn read_range(&self, path: &Path, start: u64, len: u64) -> Result<Vec<u8>, CoreError> {
+        let (snapshot, index) = self.file(path).await?;
+        self.repository
+            .read_range(&snapshot.entries[index], start, len)
+            .await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, CoreError> {
+        match self.metadata(path).await {
+            Ok(_) => Ok(true),
+            Err(CoreError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
+        let Some((id, inner)) = Self::split(path)? else {
+            return Ok(node(PathBuf::from("/"), None, None));
+        };
+        let snapshot = self.snapshot(&id).await?;
+        let base = Path::new("/").join(&id);
+        if inner.as_os_str().is_empty() {
+            return Ok(node(base, None, Some(snapshot.time)));
+        }
+        let entry = snapshot
+            .entry(&inner)
+            .ok_or_else(|| CoreError::NotFound(path.to_path_buf()))?;
+        Ok(node(base.join(&entry.path), Some(entry), None))
+    }
+}
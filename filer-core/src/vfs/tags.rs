@@ -0,0 +1,155 @@
+use async_trait::async_trait;
+use std::path::{Component, Path, PathBuf};
+
+use crate::errors::CoreError;
+use crate::model::node::{FileNode, NodeId, NodeKind};
+use crate::model::registry::NodeRegistry;
+use crate::services::tags::TagStore;
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::{Capabilities, FsProvider};
+
+/// Virtual `tags://` location
+///
+/// `tags:///` (path `/`) has one directory per tag; `/<tag>` lists every
+/// existing file carrying it, under its real path. Deeper paths are the
+/// real locations of tagged items and of what tagged directories hold.
+pub struct TagsFs {
+    tags: TagStore,
+    local: LocalFs,
+    reg: NodeRegistry,
+}
+
+impl TagsFs {
+    pub fn new(tags: TagStore, register: NodeRegistry) -> Self {
+        Self {
+            tags,
+            local: LocalFs::new(register.clone()),
+            reg: register,
+        }
+    }
+
+    fn is_root(path: &Path) -> bool {
+        path.as_os_str().is_empty() || path == Path::new("/")
+    }
+
+    /// The tag a one-component path names
+    fn tag_of(path: &Path) -> Option<String> {
+        let mut parts = path
+            .components()
+            .filter(|c| !matches!(c, Component::RootDir));
+        match (parts.next(), parts.next()) {
+            (Some(Component::Normal(tag)), None) => Some(tag.to_string_lossy().into_owned()),
+            _ => None,
+        }
+    }
+
+    /// Only tagged items and what is inside them are reachable
+    fn check(&self, path: &Path) -> Result<PathBuf, CoreError> {
+        let inside = path
+            .components()
+            .all(|c| matches!(c, Component::RootDir | Component::Normal(_)));
+        if inside && self.tags.covers(path) {
+            Ok(path.to_path_buf())
+        } else {
+            Err(CoreError::NotFound(path.to_path_buf()))
+        }
+    }
+
+    fn annotated(&self, mut nodes: Vec<FileNode>) -> Vec<FileNode> {
+        self.tags.annotate(&mut nodes);
+        nodes
+    }
+}
+
+/// Virtual directory node holding `count` items
+fn dir_node(path: PathBuf, count: usize) -> FileNode {
+    FileNode {
+        id: NodeId::from_path(&path),
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path,
+        kind: NodeKind::Directory {
+            children_count: Some(count as u32),
+        },
+        size: 0,
+        modified: None,
+        created: None,
+        meta: Default::default(),
+    }
+}
+
+#[async_trait]
+impl FsProvider for TagsFs {
+    fn scheme(&self) -> &'static str {
+        "tags"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            write: false,
+            watch: false,
+            search: false,
+            xattrs: false,
+        }
+    }
+
+    async fn list(&self, path: &Path) -> Result<Vec<FileNode>, CoreError> {
+        if Self::is_root(path) {
+            return Ok(self
+                .tags
+                .tags()
+                .iter()
+                .map(|(tag, count)| dir_node(Path::new("/").join(tag), *count))
+                .collect());
+        }
+        if let Some(tag) = Self::tag_of(path) {
+            let nodes = self
+                .tags
+                .tagged(&tag)
+                .into_iter()
+                .filter_map(|path| FileNode::from_path(path, Some(self.reg.clone())).ok())
+                .collect();
+            return Ok(self.annotated(nodes));
+        }
+        let path = self.check(path)?;
+        Ok(self.annotated(self.local.list(&path).await?))
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, CoreError> {
+        let path = self.check(path)?;
+        self.local.read(&path).await
+    }
+
+    async fn read_range(&self, path: &Path, start: u64, len: u64) -> Result<Vec<u8>, CoreError> {
+        let path = self.check(path)?;
+        self.local.read_range(&path, start, len).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, CoreError> {
+        match self.metadata(path).await {
+            Ok(_) => Ok(true),
+            Err(CoreError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
+        if Self::is_root(path) {
+            return Ok(dir_node(PathBuf::from("/"), self.tags.tags().len()));
+        }
+        if let Some(tag) = Self::tag_of(path) {
+            return match self.tags.tags().into_iter().find(|(name, _)| *name == tag) {
+                Some((tag, count)) => Ok(dir_node(Path::new("/").join(tag), count)),
+                None => Err(CoreError::NotFound(path.to_path_buf())),
+            };
+        }
+        let path = self.check(path)?;
+        let mut node = [self.local.metadata(&path).await?];
+        self.tags.annotate(&mut node);
+        let [node] = node;
+        Ok(node)
+    }
+}
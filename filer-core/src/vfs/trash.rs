@@ -0,0 +1,102 @@
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+
+use crate::errors::CoreError;
+use crate::model::node::FileNode;
+use crate::model::registry::NodeRegistry;
+use crate::services::trash::TrashBin;
+use crate::vfs::local::LocalFs;
+use crate::vfs::provider::{Capabilities, FsProvider};
+
+/// Virtual `trash://` location
+///
+/// `trash:///` (path `/`) lists the top-level items of every trash
+/// directory; deeper paths are the real locations inside `files/`.
+pub struct TrashFs {
+    bin: TrashBin,
+    local: LocalFs,
+    reg: NodeRegistry,
+}
+
+impl TrashFs {
+    pub fn new(bin: TrashBin, register: NodeRegistry) -> Self {
+        Self {
+            bin,
+            local: LocalFs::new(register.clone()),
+            reg: register,
+        }
+    }
+
+    fn is_root(path: &Path) -> bool {
+        path.as_os_str().is_empty() || path == Path::new("/")
+    }
+
+    /// Only paths inside a trash `files/` directory are reachable
+    fn check(&self, path: &Path) -> Result<PathBuf, CoreError> {
+        let inside = self
+            .bin
+            .list()
+            .iter()
+            .any(|item| path.starts_with(item.files_path()));
+        if inside {
+            Ok(path.to_path_buf())
+        } else {
+            Err(CoreError::NotFound(path.to_path_buf()))
+        }
+    }
+}
+
+#[async_trait]
+impl FsProvider for TrashFs {
+    fn scheme(&self) -> &'static str {
+        "trash"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            write: false,
+            watch: false,
+            search: false,
+            xattrs: false,
+        }
+    }
+
+    async fn list(&self, path: &Path) -> Result<Vec<FileNode>, CoreError> {
+        if Self::is_root(path) {
+            return Ok(self
+                .bin
+                .list()
+                .into_iter()
+                .filter_map(|item| FileNode::from_path(item.files_path(), Some(self.reg.clone())).ok())
+                .collect());
+        }
+        let path = self.check(path)?;
+        self.local.list(&path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, CoreError> {
+        let path = self.check(path)?;
+        self.local.read(&path).await
+    }
+
+    async fn read_range(&self, path: &Path, start: u64, len: u64) -> Result<Vec<u8>, CoreError> {
+        let path = self.check(path)?;
+        self.local.read_range(&path, start, len).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, CoreError> {
+        if Self::is_root(path) {
+            return Ok(true);
+        }
+        match self.check(path) {
+            Ok(path) => self.local.exists(&path).await,
+            Err(_) => Ok(false),
+        }
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
+        let path = self.check(path)?;
+        self.local.metadata(&path).await
+    }
+}
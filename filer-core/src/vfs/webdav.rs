@@ -3,8 +3,9 @@ use async_trait::async_trait;
 
 use crate::errors::CoreError;
 use crate::model::node::FileNode;
-use crate::vfs::provider::{Capabilities, FsProvider};
-use crate::vfs::remote::{RemoteConfig, RemoteProvider};
+use crate::services::scheduler::DeviceKey;
+use crate::vfs::provider::{Capabilities, FsProvider, Precondition, WriteStream};
+use crate::vfs::remote::{BufferedUpload, PutFile, RemoteConfig, RemoteProvider};
 
 /// WebDAV configuration
 #[derive(Debug, Clone)]
@@ -76,18 +77,30 @@ impl WebDavFs {
     }
 }
 
+#[async_trait]
+impl PutFile for WebDavFs {
+    async fn put_file(&self, path: &Path, data: &[u8]) -> Result<(), CoreError> {
+        self.put(&path.to_string_lossy(), data).await
+    }
+}
+
 #[async_trait]
 impl FsProvider for WebDavFs {
     fn scheme(&self) -> &'static str {
         "webdav"
     }
 
+    fn io_device(&self, _path: &Path) -> DeviceKey {
+        DeviceKey::of_url(&self.config.url)
+    }
+
     fn capabilities(&self) -> Capabilities {
         Capabilities {
             read: true,
             write: true,
             watch: false,
             search: false,
+            xattrs: false,
         }
     }
 
@@ -110,6 +123,34 @@ impl FsProvider for WebDavFs {
     async fn metadata(&self, path: &Path) -> Result<FileNode, CoreError> {
         todo!()
     }
+
+    async fn create(
+        &self,
+        path: &Path,
+        precondition: &Precondition,
+    ) -> Result<Box<dyn WriteStream + '_>, CoreError> {
+        Ok(Box::new(BufferedUpload::new(self, path, precondition).await?))
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<(), CoreError> {
+        for dir in path.ancestors().collect::<Vec<_>>().into_iter().rev().skip(1) {
+            if !self.exists(dir).await? {
+                self.mkcol(&dir.to_string_lossy()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn copy_within(&self, from: &Path, to: &Path, precondition: &Precondition) -> Result<bool, CoreError> {
+        precondition.verify(self, to).await?;
+        self.copy(&from.to_string_lossy(), &to.to_string_lossy()).await?;
+        Ok(true)
+    }
+
+    /// DELETE on a collection removes its members too
+    async fn remove(&self, path: &Path) -> Result<(), CoreError> {
+        self.delete(&path.to_string_lossy()).await
+    }
 }
 
 #[async_trait]